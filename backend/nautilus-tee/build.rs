@@ -0,0 +1,72 @@
+//! Embeds build metadata for GET /version. Everything here is fixed by the
+//! inputs (commit, SOURCE_DATE_EPOCH, features) so a rebuild of the same
+//! revision still reproduces PCR0.
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+fn main() {
+    // Docker builds have no .git; the pipeline passes the commit in
+    let commit = env::var("GIT_COMMIT")
+        .ok()
+        .filter(|c| !c.is_empty())
+        .or_else(|| {
+            Command::new("git")
+                .args(["rev-parse", "HEAD"])
+                .output()
+                .ok()
+                .filter(|o| o.status.success())
+                .and_then(|o| String::from_utf8(o.stdout).ok())
+                .map(|c| c.trim().to_string())
+        })
+        .unwrap_or_else(|| "unknown".to_string());
+    let dirty = Command::new("git")
+        .args(["status", "--porcelain", "--untracked-files=no"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .is_some_and(|o| !o.stdout.is_empty());
+
+    // Wall-clock time would change the image on every build
+    let timestamp = env::var("SOURCE_DATE_EPOCH").unwrap_or_else(|_| "0".to_string());
+
+    let mut features: Vec<String> = env::vars()
+        .filter_map(|(k, _)| {
+            k.strip_prefix("CARGO_FEATURE_")
+                .map(|f| f.to_lowercase().replace('_', "-"))
+        })
+        .collect();
+    features.sort();
+
+    let rustc = Command::new(env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string()))
+        .arg("--version")
+        .output()
+        .ok()
+        .and_then(|o| String::from_utf8(o.stdout).ok())
+        .map(|v| v.trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+
+    println!("cargo:rustc-env=LUMINA_GIT_COMMIT={}", commit);
+    println!("cargo:rustc-env=LUMINA_GIT_DIRTY={}", dirty);
+    println!("cargo:rustc-env=LUMINA_BUILD_TIMESTAMP={}", timestamp);
+    println!("cargo:rustc-env=LUMINA_FEATURES={}", features.join(","));
+    println!("cargo:rustc-env=LUMINA_RUSTC={}", rustc);
+    println!("cargo:rerun-if-env-changed=GIT_COMMIT");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    println!("cargo:rerun-if-changed=../../.git/HEAD");
+    // HEAD names the branch; commits move the branch's ref, loose or packed
+    let branch = std::fs::read_to_string("../../.git/HEAD")
+        .ok()
+        .and_then(|head| head.strip_prefix("ref: ").map(|r| r.trim().to_string()));
+    for path in branch
+        .map(|r| format!("../../.git/{}", r))
+        .into_iter()
+        .chain(["../../.git/packed-refs".to_string()])
+    {
+        // A path that doesn't exist would rerun the script on every build
+        if Path::new(&path).exists() {
+            println!("cargo:rerun-if-changed={}", path);
+        }
+    }
+}
@@ -0,0 +1,214 @@
+/**
+ * Global Allocator
+ * Allocator choice and allocation statistics for sizing enclave memory
+ *
+ * MSM and witness generation allocate and free large, short-lived buffers,
+ * which fragments the system allocator's heap until resident memory sits
+ * well above what is live. Builds can swap in jemalloc (`jemalloc`
+ * feature) or mimalloc (`mimalloc` feature) instead.
+ *
+ * Whichever allocator is in use is wrapped in a counter of allocations,
+ * live and peak bytes, and allocation sizes, reported to admins through
+ * POST /admin/memory/allocations. With jemalloc the report adds its own
+ * view of allocated, active and resident memory, whose gap is the
+ * fragmentation the choice is meant to reduce.
+ */
+
+use serde::Serialize;
+use std::alloc::{GlobalAlloc, Layout};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+#[cfg(all(feature = "jemalloc", feature = "mimalloc"))]
+compile_error!("The jemalloc and mimalloc features are mutually exclusive");
+
+#[cfg(feature = "jemalloc")]
+type Inner = tikv_jemallocator::Jemalloc;
+#[cfg(feature = "jemalloc")]
+const INNER: Inner = tikv_jemallocator::Jemalloc;
+#[cfg(feature = "jemalloc")]
+const NAME: &str = "jemalloc";
+
+#[cfg(all(feature = "mimalloc", not(feature = "jemalloc")))]
+type Inner = mimalloc::MiMalloc;
+#[cfg(all(feature = "mimalloc", not(feature = "jemalloc")))]
+const INNER: Inner = mimalloc::MiMalloc;
+#[cfg(all(feature = "mimalloc", not(feature = "jemalloc")))]
+const NAME: &str = "mimalloc";
+
+#[cfg(not(any(feature = "jemalloc", feature = "mimalloc")))]
+type Inner = std::alloc::System;
+#[cfg(not(any(feature = "jemalloc", feature = "mimalloc")))]
+const INNER: Inner = std::alloc::System;
+#[cfg(not(any(feature = "jemalloc", feature = "mimalloc")))]
+const NAME: &str = "system";
+
+/// Upper bounds of the allocation size classes; larger ones land in a
+/// final class
+const SIZE_CLASSES: [usize; 5] = [1 << 10, 64 << 10, 1 << 20, 16 << 20, 256 << 20];
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting::new();
+
+struct Counting {
+    allocations: AtomicU64,
+    deallocations: AtomicU64,
+    allocated_bytes: AtomicU64,
+    live_bytes: AtomicUsize,
+    peak_live_bytes: AtomicUsize,
+    by_size: [AtomicU64; SIZE_CLASSES.len() + 1],
+}
+
+#[derive(Serialize)]
+pub struct SizeClass {
+    /// None for the class above the largest bound
+    pub up_to_bytes: Option<usize>,
+    pub allocations: u64,
+}
+
+#[derive(Serialize)]
+pub struct AllocationStats {
+    pub allocator: &'static str,
+    pub allocations: u64,
+    pub deallocations: u64,
+    /// Every byte ever allocated, freed or not
+    pub allocated_bytes: u64,
+    pub live_bytes: usize,
+    pub peak_live_bytes: usize,
+    pub size_classes: Vec<SizeClass>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jemalloc: Option<JemallocStats>,
+}
+
+/// jemalloc's own accounting, in bytes
+#[derive(Serialize)]
+#[cfg_attr(not(feature = "jemalloc"), allow(dead_code))]
+pub struct JemallocStats {
+    /// Held by the application
+    pub allocated: usize,
+    /// In pages backing allocations; the excess over `allocated` is
+    /// fragmentation within those pages
+    pub active: usize,
+    /// Physically resident in the allocator's mappings
+    pub resident: usize,
+    pub mapped: usize,
+    /// Unmapped but kept for reuse
+    pub retained: usize,
+}
+
+impl Counting {
+    const fn new() -> Self {
+        Self {
+            allocations: AtomicU64::new(0),
+            deallocations: AtomicU64::new(0),
+            allocated_bytes: AtomicU64::new(0),
+            live_bytes: AtomicUsize::new(0),
+            peak_live_bytes: AtomicUsize::new(0),
+            by_size: [const { AtomicU64::new(0) }; SIZE_CLASSES.len() + 1],
+        }
+    }
+
+    fn allocated(&self, size: usize) {
+        self.allocations.fetch_add(1, Ordering::Relaxed);
+        self.allocated_bytes
+            .fetch_add(size as u64, Ordering::Relaxed);
+        self.by_size[size_class(size)].fetch_add(1, Ordering::Relaxed);
+        let live = self.live_bytes.fetch_add(size, Ordering::Relaxed) + size;
+        self.peak_live_bytes.fetch_max(live, Ordering::Relaxed);
+    }
+
+    fn freed(&self, size: usize) {
+        self.deallocations.fetch_add(1, Ordering::Relaxed);
+        self.live_bytes.fetch_sub(size, Ordering::Relaxed);
+    }
+}
+
+// Counting only; every call is forwarded unchanged to the inner allocator
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = INNER.alloc(layout);
+        if !ptr.is_null() {
+            self.allocated(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = INNER.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            self.allocated(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        INNER.dealloc(ptr, layout);
+        self.freed(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let moved = INNER.realloc(ptr, layout, new_size);
+        if !moved.is_null() {
+            self.freed(layout.size());
+            self.allocated(new_size);
+        }
+        moved
+    }
+}
+
+fn size_class(size: usize) -> usize {
+    SIZE_CLASSES
+        .iter()
+        .position(|&bound| size <= bound)
+        .unwrap_or(SIZE_CLASSES.len())
+}
+
+pub fn stats() -> AllocationStats {
+    let counts = &ALLOCATOR;
+    AllocationStats {
+        allocator: NAME,
+        allocations: counts.allocations.load(Ordering::Relaxed),
+        deallocations: counts.deallocations.load(Ordering::Relaxed),
+        allocated_bytes: counts.allocated_bytes.load(Ordering::Relaxed),
+        live_bytes: counts.live_bytes.load(Ordering::Relaxed),
+        peak_live_bytes: counts.peak_live_bytes.load(Ordering::Relaxed),
+        size_classes: counts
+            .by_size
+            .iter()
+            .enumerate()
+            .map(|(i, count)| SizeClass {
+                up_to_bytes: SIZE_CLASSES.get(i).copied(),
+                allocations: count.load(Ordering::Relaxed),
+            })
+            .collect(),
+        jemalloc: jemalloc_stats(),
+    }
+}
+
+/// Start peak tracking over from the current live bytes, to measure one
+/// workload
+pub fn reset_peak() {
+    ALLOCATOR.peak_live_bytes.store(
+        ALLOCATOR.live_bytes.load(Ordering::Relaxed),
+        Ordering::Relaxed,
+    );
+}
+
+#[cfg(feature = "jemalloc")]
+fn jemalloc_stats() -> Option<JemallocStats> {
+    use tikv_jemalloc_ctl::{epoch, stats};
+
+    // jemalloc caches its statistics until the epoch advances
+    epoch::advance().ok()?;
+    Some(JemallocStats {
+        allocated: stats::allocated::read().ok()?,
+        active: stats::active::read().ok()?,
+        resident: stats::resident::read().ok()?,
+        mapped: stats::mapped::read().ok()?,
+        retained: stats::retained::read().ok()?,
+    })
+}
+
+#[cfg(not(feature = "jemalloc"))]
+fn jemalloc_stats() -> Option<JemallocStats> {
+    None
+}
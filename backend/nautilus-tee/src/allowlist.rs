@@ -0,0 +1,251 @@
+/**
+ * Measurement Allowlist
+ * Which enclave builds this one accepts as peers
+ *
+ * Attested channels and peer routes only accept an enclave whose PCRs are
+ * on the allowlist. The list is a JSON document naming the PCR set of each
+ * released version, signed in the release pipeline with the key in
+ * TEE_ALLOWLIST_KEY (hex Ed25519, signature over the exact document bytes):
+ *
+ *   { "sequence": 7, "issued_at": 1760000000, "upgrade_window_secs": 604800,
+ *     "versions": [{ "version": "1.4.0", "pcr0": "..", "pcr1": "..", "pcr2": ".." }] }
+ *
+ * Admins install a list at runtime through POST /admin/measurements; its
+ * sequence must exceed the installed one, so an older list can't be
+ * replayed over a newer one. A version a new list drops isn't cut off at
+ * once: it stays accepted for the new list's upgrade window so the fleet
+ * can roll over, then expires. Until a list is installed, the PCR0 values
+ * in TEE_PEER_MEASUREMENTS are accepted, or only this same image.
+ */
+
+use ed25519_dalek::{Signature, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::info;
+
+use crate::attestation::VerifiedAttestation;
+use crate::store::Store;
+
+const NAMESPACE: &str = "measurement_allowlist";
+const CURRENT: &str = "current";
+const MAX_VERSIONS: usize = 64;
+/// Longest a dropped version may linger after an upgrade
+const MAX_UPGRADE_WINDOW_SECS: u64 = 90 * 24 * 60 * 60;
+
+/// The PCR set of one released version; PCR1 and PCR2 are only compared
+/// when the list gives them
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AllowedVersion {
+    pub version: String,
+    pub pcr0: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pcr1: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pcr2: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AllowlistDocument {
+    pub sequence: u64,
+    pub issued_at: u64,
+    /// How long versions this list drops stay accepted
+    pub upgrade_window_secs: u64,
+    pub versions: Vec<AllowedVersion>,
+}
+
+/// A version a newer list dropped, accepted until `expires_at`
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RetiringVersion {
+    #[serde(flatten)]
+    pub version: AllowedVersion,
+    pub expires_at: u64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct InstalledAllowlist {
+    pub document: AllowlistDocument,
+    pub document_sha256: String, // Hex, over the signed bytes
+    pub installed_by: String,
+    pub installed_at: u64,
+    #[serde(default)]
+    pub retiring: Vec<RetiringVersion>,
+}
+
+pub struct MeasurementAllowlist {
+    store: Arc<Store>,
+    /// Release key lists must be signed with; None refuses every install
+    signer: Option<VerifyingKey>,
+    /// PCR0 values accepted until a signed list is installed
+    fallback: Vec<String>,
+    own_measurement: String,
+    install: Mutex<()>,
+}
+
+impl AllowedVersion {
+    fn matches(&self, attestation: &VerifiedAttestation) -> bool {
+        self.pcr0.eq_ignore_ascii_case(&attestation.pcr0)
+            && self
+                .pcr1
+                .as_ref()
+                .map_or(true, |p| p.eq_ignore_ascii_case(&attestation.pcr1))
+            && self
+                .pcr2
+                .as_ref()
+                .map_or(true, |p| p.eq_ignore_ascii_case(&attestation.pcr2))
+    }
+}
+
+impl MeasurementAllowlist {
+    pub fn new(
+        store: Arc<Store>,
+        signer: Option<&str>,
+        fallback: Vec<String>,
+        own_measurement: String,
+    ) -> Result<Self, String> {
+        let signer = signer
+            .map(|key| {
+                let key: [u8; 32] = hex::decode(key)
+                    .ok()
+                    .and_then(|k| k.try_into().ok())
+                    .ok_or("TEE_ALLOWLIST_KEY must be a hex Ed25519 public key")?;
+                VerifyingKey::from_bytes(&key).map_err(|_| "Invalid allowlist signing key")
+            })
+            .transpose()?;
+        Ok(Self {
+            store,
+            signer,
+            fallback,
+            own_measurement,
+            install: Mutex::new(()),
+        })
+    }
+
+    /// Whether the attested enclave runs an allowed build
+    pub fn allows(&self, attestation: &VerifiedAttestation) -> Result<bool, String> {
+        let Some(installed) = self.current()? else {
+            if self.fallback.is_empty() {
+                return Ok(attestation.pcr0 == self.own_measurement);
+            }
+            return Ok(self.fallback.iter().any(|m| m == &attestation.pcr0));
+        };
+        let now = now();
+        Ok(installed
+            .document
+            .versions
+            .iter()
+            .any(|v| v.matches(attestation))
+            || installed
+                .retiring
+                .iter()
+                .any(|r| r.expires_at > now && r.version.matches(attestation)))
+    }
+
+    pub fn current(&self) -> Result<Option<InstalledAllowlist>, String> {
+        self.store.get(NAMESPACE, CURRENT)
+    }
+
+    /// Install a signed list in place of the current one. Versions it
+    /// drops start their upgrade window now; expired ones are forgotten.
+    pub fn install(
+        &self,
+        document: &[u8],
+        signature: &str,
+        installed_by: &str,
+    ) -> Result<InstalledAllowlist, String> {
+        let signer = self
+            .signer
+            .as_ref()
+            .ok_or("No allowlist signing key is configured")?;
+        let signature: [u8; 64] = hex::decode(signature.trim())
+            .ok()
+            .and_then(|s| s.try_into().ok())
+            .ok_or("Allowlist signature must be 64 hex bytes")?;
+        signer
+            .verify_strict(document, &Signature::from_bytes(&signature))
+            .map_err(|_| "Allowlist signature does not verify".to_string())?;
+        let parsed: AllowlistDocument = serde_json::from_slice(document)
+            .map_err(|e| format!("Invalid allowlist document: {}", e))?;
+        validate(&parsed)?;
+
+        let _guard = self.install.lock().unwrap();
+        let now = now();
+        let mut retiring = Vec::new();
+        if let Some(previous) = self.current()? {
+            if parsed.sequence <= previous.document.sequence {
+                return Err(format!(
+                    "Allowlist sequence {} does not supersede installed sequence {}",
+                    parsed.sequence, previous.document.sequence
+                ));
+            }
+            let expires_at = now + parsed.upgrade_window_secs;
+            retiring = previous
+                .retiring
+                .into_iter()
+                .filter(|r| r.expires_at > now)
+                .chain(previous.document.versions.into_iter().map(|version| {
+                    RetiringVersion {
+                        version,
+                        expires_at,
+                    }
+                }))
+                .filter(|r| !parsed.versions.contains(&r.version))
+                .collect();
+        }
+
+        let installed = InstalledAllowlist {
+            document_sha256: hex::encode(Sha256::digest(document)),
+            document: parsed,
+            installed_by: installed_by.to_string(),
+            installed_at: now,
+            retiring,
+        };
+        self.store.put(NAMESPACE, CURRENT, &installed)?;
+        info!(
+            "Measurement allowlist {} installed by {}: {} versions, {} retiring",
+            installed.document.sequence,
+            installed_by,
+            installed.document.versions.len(),
+            installed.retiring.len()
+        );
+        Ok(installed)
+    }
+}
+
+fn validate(document: &AllowlistDocument) -> Result<(), String> {
+    if document.versions.is_empty() || document.versions.len() > MAX_VERSIONS {
+        return Err(format!(
+            "An allowlist names between 1 and {} versions",
+            MAX_VERSIONS
+        ));
+    }
+    if document.upgrade_window_secs > MAX_UPGRADE_WINDOW_SECS {
+        return Err(format!(
+            "Upgrade window may be at most {} days",
+            MAX_UPGRADE_WINDOW_SECS / (24 * 60 * 60)
+        ));
+    }
+    for version in &document.versions {
+        let pcrs = [Some(&version.pcr0), version.pcr1.as_ref(), version.pcr2.as_ref()];
+        if version.version.is_empty()
+            || pcrs
+                .into_iter()
+                .flatten()
+                .any(|pcr| pcr.is_empty() || hex::decode(pcr).is_err())
+        {
+            return Err(format!(
+                "Version {:?} needs a name and hex PCR values",
+                version.version
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
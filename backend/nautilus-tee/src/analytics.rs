@@ -0,0 +1,230 @@
+/**
+ * Analytics
+ * Aggregate usage statistics that never carry per-user data out of the
+ * enclave
+ *
+ * The enclave keeps only counts: proofs generated per claim type since
+ * start, and, read from the check-in schedules when a report is made,
+ * vaults per check-in interval bucket. Nothing names a vault or an
+ * address.
+ *
+ * Reports are differentially private. Each count gets Laplace noise of
+ * scale 1/epsilon per metric (a vault sits in one check-in bucket; a
+ * proof adds one to one claim type), is rounded, and buckets whose noisy
+ * count falls under `min_count` are suppressed rather than reported.
+ * Every report spends a fresh budget, so admins should pull them rarely.
+ *
+ * Proofs are counted by following the event bus live, so a proof
+ * generated while the follower lags behind goes uncounted; the counts are
+ * noisy anyway.
+ */
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::bus::{BusEvent, EventBus};
+use crate::check_in::CheckInService;
+use crate::randomness::RandomnessSource;
+
+/// Upper bounds, in days, of the check-in interval buckets
+const CHECK_IN_BUCKETS: &[(u32, &str)] = &[
+    (1, "daily"),
+    (7, "weekly"),
+    (31, "monthly"),
+    (92, "quarterly"),
+    (u32::MAX, "longer"),
+];
+
+#[derive(Clone, Copy, Debug)]
+pub struct PrivacyParams {
+    /// Privacy budget per metric per report
+    pub epsilon: f64,
+    /// Noisy counts below this are suppressed
+    pub min_count: u64,
+}
+
+#[derive(Clone, Serialize)]
+pub struct AnalyticsReport {
+    pub generated_at: u64,
+    /// Start of the window the proof counts cover
+    pub since: u64,
+    pub epsilon: f64,
+    pub min_count: u64,
+    pub metrics: Vec<Metric>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct Metric {
+    pub name: &'static str,
+    /// Noisy counts per bucket, suppressed buckets left out
+    pub buckets: BTreeMap<String, u64>,
+    /// Buckets left out for falling under min_count
+    pub suppressed: usize,
+}
+
+pub struct AnalyticsService {
+    check_ins: Arc<CheckInService>,
+    randomness: Arc<RandomnessSource>,
+    params: PrivacyParams,
+    claims: Mutex<BTreeMap<String, u64>>,
+    since: u64,
+}
+
+impl AnalyticsService {
+    pub fn new(
+        check_ins: Arc<CheckInService>,
+        randomness: Arc<RandomnessSource>,
+        params: PrivacyParams,
+    ) -> Self {
+        Self {
+            check_ins,
+            randomness,
+            params,
+            claims: Mutex::new(BTreeMap::new()),
+            since: now(),
+        }
+    }
+
+    /// Count a generated proof
+    pub fn record_claim(&self, claim_type: &str) {
+        *self
+            .claims
+            .lock()
+            .unwrap()
+            .entry(claim_type.to_string())
+            .or_default() += 1;
+    }
+
+    /// Count proofs as the bus publishes them
+    pub fn follow(self: Arc<Self>, bus: &EventBus) {
+        let mut live = bus.subscribe();
+        tokio::spawn(async move {
+            loop {
+                match live.recv().await {
+                    Ok(envelope) => {
+                        if let BusEvent::ProofGenerated { claim_type } = envelope.event {
+                            self.record_claim(&claim_type);
+                        }
+                    }
+                    Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => return,
+                }
+            }
+        });
+    }
+
+    pub fn report(&self) -> Result<AnalyticsReport, String> {
+        let mut check_ins: BTreeMap<String, u64> = BTreeMap::new();
+        for schedule in self.check_ins.schedules()? {
+            *check_ins
+                .entry(check_in_bucket(schedule.interval_days).to_string())
+                .or_default() += 1;
+        }
+        let claims = self.claims.lock().unwrap().clone();
+
+        Ok(AnalyticsReport {
+            generated_at: now(),
+            since: self.since,
+            epsilon: self.params.epsilon,
+            min_count: self.params.min_count,
+            metrics: vec![
+                self.release("check_in_interval", check_ins, &bucket_names()),
+                self.release("claim_types", claims, &[]),
+            ],
+        })
+    }
+
+    /// Noise and threshold every bucket, `expected` ones included when
+    /// empty, so a bucket's absence says nothing on its own
+    fn release(
+        &self,
+        name: &'static str,
+        mut counts: BTreeMap<String, u64>,
+        expected: &[&str],
+    ) -> Metric {
+        for bucket in expected {
+            counts.entry(bucket.to_string()).or_default();
+        }
+        let mut buckets = BTreeMap::new();
+        let mut suppressed = 0;
+        for (bucket, count) in counts {
+            let noisy = (count as f64 + self.laplace(1.0 / self.params.epsilon))
+                .round()
+                .max(0.0) as u64;
+            if noisy < self.params.min_count {
+                suppressed += 1;
+            } else {
+                buckets.insert(bucket, noisy);
+            }
+        }
+        Metric {
+            name,
+            buckets,
+            suppressed,
+        }
+    }
+
+    /// A draw from Laplace(0, scale), by inverting its CDF
+    fn laplace(&self, scale: f64) -> f64 {
+        let mut bytes = [0u8; 8];
+        self.randomness.fill(&mut bytes);
+        // Uniform in (-0.5, 0.5), from 53 random bits
+        let u = ((u64::from_le_bytes(bytes) >> 11) as f64 + 0.5) / (1u64 << 53) as f64 - 0.5;
+        -scale * u.signum() * (1.0 - 2.0 * u.abs()).ln()
+    }
+}
+
+fn check_in_bucket(interval_days: u32) -> &'static str {
+    CHECK_IN_BUCKETS
+        .iter()
+        .find(|(max, _)| interval_days <= *max)
+        .map(|(_, name)| *name)
+        .unwrap_or("longer")
+}
+
+fn bucket_names() -> Vec<&'static str> {
+    CHECK_IN_BUCKETS.iter().map(|(_, name)| *name).collect()
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::Store;
+
+    #[test]
+    fn small_buckets_are_suppressed() {
+        let dir = std::env::temp_dir().join(format!("lumina-analytics-{}", std::process::id()));
+        let store = Arc::new(Store::open(dir).unwrap());
+        let bus = Arc::new(EventBus::new(store.clone()).unwrap());
+        let analytics = AnalyticsService::new(
+            Arc::new(CheckInService::new(store, bus)),
+            Arc::new(RandomnessSource::new()),
+            PrivacyParams {
+                // Noise well under one count
+                epsilon: 1e6,
+                min_count: 10,
+            },
+        );
+        for _ in 0..12 {
+            analytics.record_claim("age_range");
+        }
+        analytics.record_claim("solvency");
+
+        let report = analytics.report().unwrap();
+        let claims = &report.metrics[1];
+        assert_eq!(claims.buckets.get("age_range"), Some(&12));
+        assert_eq!(claims.suppressed, 1);
+        assert!(report.metrics[0].buckets.is_empty());
+        assert_eq!(report.metrics[0].suppressed, CHECK_IN_BUCKETS.len());
+    }
+}
@@ -0,0 +1,525 @@
+/**
+ * Guardian Approvals
+ * BLS12-381 (min-pk) guardian signatures aggregated into one approval
+ *
+ * Guardians register a BLS public key with a proof of possession, which
+ * rules out rogue-key attacks on same-message aggregation. Approvals for
+ * an operation are signed with Sui's basic-scheme DST, so the aggregate
+ * signature verifies on-chain with `bls12381_min_pk_verify` against the
+ * aggregate public key of the signers.
+ */
+
+use blst::min_pk::{AggregatePublicKey, AggregateSignature, PublicKey, Signature};
+use blst::BLST_ERROR;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::auth::normalize_address;
+use crate::store::Store;
+
+const GUARDIANS_NAMESPACE: &str = "approval_guardians";
+const APPROVALS_NAMESPACE: &str = "approvals";
+
+/// Signature DST used by Sui's bls12381_min_pk_verify
+const SIGNATURE_DST: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_NUL_";
+/// Separate DST for proofs of possession over the public key bytes
+const POP_DST: &[u8] = b"BLS_POP_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+
+#[derive(Clone, Deserialize)]
+pub struct BlsGuardianRegistration {
+    pub address: String,
+    pub public_key: String,          // Hex 48-byte compressed G1 point
+    pub proof_of_possession: String, // Hex signature over the public key bytes
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BlsGuardian {
+    pub address: String,
+    pub public_key: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ApprovalGuardianSet {
+    pub vault_id: String,
+    pub owner: String,
+    pub threshold: u8,
+    pub guardians: Vec<BlsGuardian>,
+}
+
+#[derive(Deserialize)]
+pub struct GuardianSignature {
+    pub guardian_address: String,
+    pub signature: String, // Hex 96-byte compressed G2 point
+}
+
+/// Either individual signatures (aggregated here) or a pre-aggregated one
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum ApprovalSignatures {
+    Individual {
+        signatures: Vec<GuardianSignature>,
+    },
+    Aggregated {
+        aggregate_signature: String,
+        signers: Vec<String>,
+    },
+}
+
+/// Compact approval: one signature, one key, and a bitmap of signers
+/// indexed by registration order
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ApprovalRecord {
+    pub vault_id: String,
+    pub operation: String,
+    pub expires_at: u64,
+    pub message: String,        // Hex message every guardian signed
+    pub signers_bitmap: String, // Hex, bit i = guardian i signed
+    pub signer_count: usize,
+    pub aggregate_signature: String,  // Hex 96 bytes
+    pub aggregate_public_key: String, // Hex 48 bytes
+    pub verified_at: u64,
+}
+
+impl ApprovalRecord {
+    /// Digest bound into attestations for this approval
+    pub fn digest(&self) -> Vec<u8> {
+        let mut hasher = Sha256::new();
+        hasher.update(self.message.as_bytes());
+        hasher.update(self.signers_bitmap.as_bytes());
+        hasher.update(self.aggregate_signature.as_bytes());
+        hasher.finalize().to_vec()
+    }
+}
+
+pub struct ApprovalService {
+    store: Arc<Store>,
+}
+
+impl ApprovalService {
+    pub fn new(store: Arc<Store>) -> Self {
+        Self { store }
+    }
+
+    /// Register the vault's approval guardians; `owners` are the owners of
+    /// the registered vault, empty if it isn't registered
+    pub fn register_guardians(
+        &self,
+        vault_id: &str,
+        owner: &str,
+        owners: &[String],
+        threshold: u8,
+        registrations: &[BlsGuardianRegistration],
+    ) -> Result<ApprovalGuardianSet, String> {
+        if !owners.iter().any(|o| o == owner) {
+            return Err(
+                "Only an owner of the registered vault may register its guardians".to_string(),
+            );
+        }
+        if threshold == 0 || threshold as usize > registrations.len() {
+            return Err(format!(
+                "Threshold must be between 1 and {}",
+                registrations.len()
+            ));
+        }
+        let mut guardians = Vec::with_capacity(registrations.len());
+        for registration in registrations {
+            let address = normalize_address(&registration.address);
+            if guardians.iter().any(|g: &BlsGuardian| g.address == address) {
+                return Err(format!("Duplicate guardian {}", address));
+            }
+
+            let public_key = decode_public_key(&registration.public_key)?;
+            let proof = decode_signature(&registration.proof_of_possession)?;
+            check(
+                proof.verify(
+                    true,
+                    &public_key.to_bytes(),
+                    POP_DST,
+                    &[],
+                    &public_key,
+                    true,
+                ),
+                &format!("Invalid proof of possession for {}", address),
+            )?;
+
+            guardians.push(BlsGuardian {
+                address,
+                public_key: hex::encode(public_key.to_bytes()),
+            });
+        }
+
+        let set = ApprovalGuardianSet {
+            vault_id: vault_id.to_string(),
+            owner: owner.to_string(),
+            threshold,
+            guardians,
+        };
+        self.store.put(GUARDIANS_NAMESPACE, vault_id, &set)?;
+        Ok(set)
+    }
+
+    /// Verify a threshold of guardian signatures over the approval message
+    /// and persist the compact aggregate
+    pub fn approve(
+        &self,
+        vault_id: &str,
+        operation: &str,
+        expires_at: u64,
+        signatures: ApprovalSignatures,
+    ) -> Result<ApprovalRecord, String> {
+        let set = self
+            .guardian_set(vault_id)?
+            .ok_or_else(|| format!("No approval guardians for vault {}", vault_id))?;
+        let now = now();
+        if expires_at <= now {
+            return Err("Approval has already expired".to_string());
+        }
+
+        let message = approval_message(vault_id, operation, expires_at);
+
+        let (aggregate, signer_indices) = match signatures {
+            ApprovalSignatures::Individual { signatures } => {
+                let mut indices = Vec::with_capacity(signatures.len());
+                let mut sigs = Vec::with_capacity(signatures.len());
+                for sig in &signatures {
+                    let index = guardian_index(&set, &sig.guardian_address)?;
+                    let signature = decode_signature(&sig.signature)?;
+                    let public_key = decode_public_key(&set.guardians[index].public_key)?;
+                    check(
+                        signature.verify(true, &message, SIGNATURE_DST, &[], &public_key, false),
+                        &format!("Invalid approval signature from {}", sig.guardian_address),
+                    )?;
+                    indices.push(index);
+                    sigs.push(signature);
+                }
+                let refs: Vec<&Signature> = sigs.iter().collect();
+                let aggregate = AggregateSignature::aggregate(&refs, false)
+                    .map_err(|e| format!("Failed to aggregate signatures: {:?}", e))?
+                    .to_signature();
+                (aggregate, indices)
+            }
+            ApprovalSignatures::Aggregated {
+                aggregate_signature,
+                signers,
+            } => {
+                let indices = signers
+                    .iter()
+                    .map(|address| guardian_index(&set, address))
+                    .collect::<Result<Vec<_>, _>>()?;
+                (decode_signature(&aggregate_signature)?, indices)
+            }
+        };
+
+        let mut bitmap = vec![0u8; set.guardians.len().div_ceil(8)];
+        for &index in &signer_indices {
+            if bitmap[index / 8] & (1 << (index % 8)) != 0 {
+                return Err("Duplicate signer in approval".to_string());
+            }
+            bitmap[index / 8] |= 1 << (index % 8);
+        }
+        if signer_indices.len() < set.threshold as usize {
+            return Err(format!(
+                "{} of {} required guardian approvals",
+                signer_indices.len(),
+                set.threshold
+            ));
+        }
+
+        let public_keys = signer_indices
+            .iter()
+            .map(|&i| decode_public_key(&set.guardians[i].public_key))
+            .collect::<Result<Vec<_>, _>>()?;
+        let key_refs: Vec<&PublicKey> = public_keys.iter().collect();
+
+        // Registration checked proofs of possession, so keys need no revalidation
+        check(
+            aggregate.fast_aggregate_verify(true, &message, SIGNATURE_DST, &key_refs),
+            "Aggregate approval signature is invalid",
+        )?;
+        let aggregate_public_key = AggregatePublicKey::aggregate(&key_refs, false)
+            .map_err(|e| format!("Failed to aggregate public keys: {:?}", e))?
+            .to_public_key();
+
+        let record = ApprovalRecord {
+            vault_id: vault_id.to_string(),
+            operation: operation.to_string(),
+            expires_at,
+            message: hex::encode(&message),
+            signers_bitmap: hex::encode(&bitmap),
+            signer_count: signer_indices.len(),
+            aggregate_signature: hex::encode(aggregate.to_bytes()),
+            aggregate_public_key: hex::encode(aggregate_public_key.to_bytes()),
+            verified_at: now,
+        };
+        self.store.put(
+            APPROVALS_NAMESPACE,
+            &approval_key(vault_id, operation),
+            &record,
+        )?;
+
+        Ok(record)
+    }
+
+    /// Latest verified, unexpired approval for an operation
+    pub fn current_approval(
+        &self,
+        vault_id: &str,
+        operation: &str,
+    ) -> Result<Option<ApprovalRecord>, String> {
+        let record: Option<ApprovalRecord> = self
+            .store
+            .get(APPROVALS_NAMESPACE, &approval_key(vault_id, operation))?;
+        Ok(record.filter(|r| r.expires_at > now()))
+    }
+
+    pub fn guardian_set(&self, vault_id: &str) -> Result<Option<ApprovalGuardianSet>, String> {
+        self.store.get(GUARDIANS_NAMESPACE, vault_id)
+    }
+}
+
+/// sha256("lumina-approval-v1" || vault_id || 0 || operation || 0 || expires_at)
+pub fn approval_message(vault_id: &str, operation: &str, expires_at: u64) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(b"lumina-approval-v1");
+    hasher.update(vault_id.as_bytes());
+    hasher.update([0]);
+    hasher.update(operation.as_bytes());
+    hasher.update([0]);
+    hasher.update(expires_at.to_be_bytes());
+    hasher.finalize().to_vec()
+}
+
+fn approval_key(vault_id: &str, operation: &str) -> String {
+    format!("{}:{}", vault_id, operation)
+}
+
+fn guardian_index(set: &ApprovalGuardianSet, address: &str) -> Result<usize, String> {
+    let address = normalize_address(address);
+    set.guardians
+        .iter()
+        .position(|g| g.address == address)
+        .ok_or_else(|| format!("{} is not an approval guardian", address))
+}
+
+fn decode_public_key(encoded: &str) -> Result<PublicKey, String> {
+    let bytes = hex::decode(encoded.trim_start_matches("0x"))
+        .map_err(|e| format!("Invalid BLS public key hex: {}", e))?;
+    PublicKey::key_validate(&bytes).map_err(|e| format!("Invalid BLS public key: {:?}", e))
+}
+
+fn decode_signature(encoded: &str) -> Result<Signature, String> {
+    let bytes = hex::decode(encoded.trim_start_matches("0x"))
+        .map_err(|e| format!("Invalid BLS signature hex: {}", e))?;
+    Signature::from_bytes(&bytes).map_err(|e| format!("Invalid BLS signature: {:?}", e))
+}
+
+fn check(result: BLST_ERROR, message: &str) -> Result<(), String> {
+    match result {
+        BLST_ERROR::BLST_SUCCESS => Ok(()),
+        _ => Err(message.to_string()),
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blst::min_pk::SecretKey;
+
+    fn service(name: &str) -> ApprovalService {
+        let dir = std::env::temp_dir().join(format!("lumina-approval-tests-{}-{}", name, now()));
+        ApprovalService::new(Arc::new(Store::open(&dir).unwrap()))
+    }
+
+    /// A guardian key registered with its proof of possession
+    fn guardian(seed: u8) -> (SecretKey, BlsGuardianRegistration) {
+        let secret = SecretKey::key_gen(&[seed; 32], &[]).unwrap();
+        let public_key = secret.sk_to_pk().to_bytes();
+        let proof = secret.sign(&public_key, POP_DST, &[]);
+        let registration = BlsGuardianRegistration {
+            address: format!("0x{}", seed),
+            public_key: hex::encode(public_key),
+            proof_of_possession: hex::encode(proof.to_bytes()),
+        };
+        (secret, registration)
+    }
+
+    fn owners() -> Vec<String> {
+        vec!["0xowner".to_string()]
+    }
+
+    /// Three guardians, any two of whom approve
+    fn registered(approvals: &ApprovalService) -> Vec<SecretKey> {
+        let (secrets, registrations): (Vec<_>, Vec<_>) = (1..=3).map(guardian).unzip();
+        approvals
+            .register_guardians("vault-1", "0xowner", &owners(), 2, &registrations)
+            .unwrap();
+        secrets
+    }
+
+    fn sign(secret: &SecretKey, expires_at: u64) -> Signature {
+        let message = approval_message("vault-1", "release", expires_at);
+        secret.sign(&message, SIGNATURE_DST, &[])
+    }
+
+    #[test]
+    fn threshold_approvals_aggregate_into_one_verifiable_signature() {
+        let approvals = service("aggregate");
+        let secrets = registered(&approvals);
+        let expires_at = now() + 3600;
+
+        let signatures = [0, 2]
+            .iter()
+            .map(|&i| GuardianSignature {
+                guardian_address: format!("0x{}", i + 1),
+                signature: hex::encode(sign(&secrets[i], expires_at).to_bytes()),
+            })
+            .collect();
+        let record = approvals
+            .approve(
+                "vault-1",
+                "release",
+                expires_at,
+                ApprovalSignatures::Individual { signatures },
+            )
+            .unwrap();
+        assert_eq!(record.signer_count, 2);
+        assert_eq!(record.signers_bitmap, "05");
+
+        // What bls12381_min_pk_verify checks on-chain
+        let signature = decode_signature(&record.aggregate_signature).unwrap();
+        let public_key = decode_public_key(&record.aggregate_public_key).unwrap();
+        let message = hex::decode(&record.message).unwrap();
+        check(
+            signature.verify(true, &message, SIGNATURE_DST, &[], &public_key, false),
+            "aggregate does not verify",
+        )
+        .unwrap();
+
+        // A client may aggregate the same signatures itself
+        let aggregated = AggregateSignature::aggregate(
+            &[
+                &sign(&secrets[0], expires_at),
+                &sign(&secrets[1], expires_at),
+            ],
+            false,
+        )
+        .unwrap()
+        .to_signature();
+        let record = approvals
+            .approve(
+                "vault-1",
+                "release",
+                expires_at,
+                ApprovalSignatures::Aggregated {
+                    aggregate_signature: hex::encode(aggregated.to_bytes()),
+                    signers: vec!["0x1".to_string(), "0x2".to_string()],
+                },
+            )
+            .unwrap();
+        assert_eq!(record.signers_bitmap, "03");
+        assert!(approvals
+            .current_approval("vault-1", "release")
+            .unwrap()
+            .is_some());
+    }
+
+    #[test]
+    fn approvals_missing_a_signer_are_rejected() {
+        let approvals = service("missing");
+        let secrets = registered(&approvals);
+        let expires_at = now() + 3600;
+
+        let signatures = vec![GuardianSignature {
+            guardian_address: "0x1".to_string(),
+            signature: hex::encode(sign(&secrets[0], expires_at).to_bytes()),
+        }];
+        let error = approvals
+            .approve(
+                "vault-1",
+                "release",
+                expires_at,
+                ApprovalSignatures::Individual { signatures },
+            )
+            .err()
+            .unwrap();
+        assert!(error.contains("1 of 2"));
+
+        // Claiming a signer whose signature isn't in the aggregate
+        let error = approvals
+            .approve(
+                "vault-1",
+                "release",
+                expires_at,
+                ApprovalSignatures::Aggregated {
+                    aggregate_signature: hex::encode(sign(&secrets[0], expires_at).to_bytes()),
+                    signers: vec!["0x1".to_string(), "0x2".to_string()],
+                },
+            )
+            .err()
+            .unwrap();
+        assert!(error.contains("invalid"));
+        assert!(approvals
+            .current_approval("vault-1", "release")
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn registrations_need_a_proof_of_possession() {
+        let approvals = service("pop");
+        let (_, honest) = guardian(1);
+
+        // A proof made with another key
+        let (_, mut borrowed) = guardian(2);
+        borrowed.proof_of_possession = guardian(3).1.proof_of_possession;
+        let error = approvals
+            .register_guardians(
+                "vault-1",
+                "0xowner",
+                &owners(),
+                1,
+                &[honest.clone(), borrowed],
+            )
+            .err()
+            .unwrap();
+        assert!(error.contains("proof of possession"));
+
+        // A signature over the key under the approval DST is no proof
+        let (secret, mut wrong_dst) = guardian(4);
+        let public_key = secret.sk_to_pk().to_bytes();
+        wrong_dst.proof_of_possession =
+            hex::encode(secret.sign(&public_key, SIGNATURE_DST, &[]).to_bytes());
+        assert!(approvals
+            .register_guardians("vault-1", "0xowner", &owners(), 1, &[honest, wrong_dst])
+            .is_err());
+        assert!(approvals.guardian_set("vault-1").unwrap().is_none());
+    }
+
+    #[test]
+    fn only_an_owner_of_the_registered_vault_registers_guardians() {
+        let approvals = service("owner");
+        let (_, registration) = guardian(1);
+
+        for (caller, vault_owners) in [("0xattacker", owners()), ("0xowner", Vec::new())] {
+            let error = approvals
+                .register_guardians("vault-1", caller, &vault_owners, 1, &[registration.clone()])
+                .err()
+                .unwrap();
+            assert!(error.contains("Only an owner"));
+        }
+        assert!(approvals.guardian_set("vault-1").unwrap().is_none());
+
+        approvals
+            .register_guardians("vault-1", "0xowner", &owners(), 1, &[registration])
+            .unwrap();
+    }
+}
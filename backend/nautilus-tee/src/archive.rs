@@ -0,0 +1,635 @@
+/**
+ * Archive
+ * Old audit segments, settled jobs and store snapshots, encrypted and
+ * shipped to S3 through the parent
+ *
+ * Enclave storage is finite. A sweep moves out audit entries older than
+ * TEE_ARCHIVE_AUDIT_AFTER_SECS (all but each vault's latest, which the
+ * next entry chains onto), and notification jobs and chain intents
+ * settled for TEE_ARCHIVE_JOBS_AFTER_SECS, in objects of up to
+ * MAX_RECORDS records of one vault. A second task snapshots the whole
+ * store every TEE_ARCHIVE_SNAPSHOT_SECS. Records are removed locally only
+ * once the agent has stored their object, in the batch that records its
+ * manifest.
+ *
+ * An object is the records' JSON, zstd-compressed and sealed with
+ * ChaCha20-Poly1305 under the vault's archival key, unwrapped under the
+ * key release policy (see keys::release; snapshots use the key for the
+ * empty vault id), with the archive id as
+ * associated data: nonce || ciphertext. The parent's agent keeps it in S3
+ * under "<kind>/<archive id>" and learns only its size and age. It is
+ * reached with JSON lines over VSOCK (TCP in development), under the
+ * archive retry policy (see retry):
+ *
+ *   {"op":"put","key":...,"body":<base64>}  ->  {"stored":true}
+ *   {"op":"get","key":...}                  ->  {"body":<base64>}
+ *
+ * or {"error":...} when either fails.
+ *
+ * Each object's manifest stays in enclave storage: what it holds, the
+ * SHA-256 of the object and of the records, and for an audit segment the
+ * sequence range and the last hash, which the vault's next local entry
+ * chains onto. Retrieval fetches the object back and checks it against
+ * its manifest before decrypting, so the parent or the bucket can
+ * withhold archived data but not alter or swap it.
+ *
+ * Deleting a vault erases its manifests; its objects stay in the bucket
+ * until its lifecycle rules expire them, sealed under the retired vault's
+ * key. Snapshots cover every vault, deleted ones included until they
+ * expire.
+ */
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tracing::{info, warn};
+
+use crate::audit::{AuditEntry, AuditTrail};
+use crate::config::{ArchiveConfig, ParentRelay};
+use crate::crypto;
+use crate::keys::derive::{Archival, KeyHierarchy, KeyPurpose};
+use crate::keys::release::{KeyRelease, ReleaseGrant};
+use crate::notifications::NotificationService;
+use crate::outbox::ChainOutbox;
+use crate::retry::{self, Dependency, Failure, FailureKind};
+use crate::store::{Batch, Store};
+use crate::tasks::{Schedule, TaskScheduler};
+
+pub const MANIFESTS_NAMESPACE: &str = "archive_manifests";
+const MAX_RECORDS: usize = 1000;
+const COMPRESSION_LEVEL: i32 = 9;
+/// Snapshots can run to many megabytes each way
+const RELAY_TIMEOUT: Duration = Duration::from_secs(120);
+/// Snapshots belong to no one vault
+const ENCLAVE_SCOPE: &str = "";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveKind {
+    AuditSegment,
+    NotificationJobs,
+    ChainIntents,
+    Snapshot,
+}
+
+impl ArchiveKind {
+    /// Where the agent files objects of this kind
+    fn prefix(self) -> &'static str {
+        match self {
+            ArchiveKind::AuditSegment => "audit",
+            ArchiveKind::NotificationJobs => "notifications",
+            ArchiveKind::ChainIntents => "intents",
+            ArchiveKind::Snapshot => "snapshots",
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AuditRange {
+    pub first_seq: u64,
+    pub last_seq: u64,
+    /// Hex hash of the last entry; the next entry's prev_hash
+    pub last_hash: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    pub archive_id: String,
+    pub kind: ArchiveKind,
+    /// None for snapshots
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vault_id: Option<String>,
+    pub object_key: String,
+    /// Entries or jobs held, or namespaces for a snapshot
+    pub records: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audit_range: Option<AuditRange>,
+    /// Id of the archival key the object is sealed under
+    pub key_id: String,
+    /// Hex SHA-256 of the object as stored
+    pub object_sha256: String,
+    /// Hex SHA-256 of the records' JSON
+    pub content_sha256: String,
+    pub object_bytes: usize,
+    pub archived_at: u64,
+}
+
+/// An archived object fetched back and checked against its manifest
+#[derive(Serialize)]
+pub struct Retrieved {
+    pub manifest: ArchiveManifest,
+    pub records: Value,
+}
+
+/// An object ready to ship, with the manifest to record once it is stored
+struct Sealed {
+    manifest: ArchiveManifest,
+    object: Vec<u8>,
+}
+
+#[derive(Deserialize)]
+struct AgentResponse {
+    #[serde(default)]
+    body: Option<String>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+pub struct ArchiveService {
+    store: Arc<Store>,
+    keys: Arc<KeyHierarchy>,
+    release: Arc<KeyRelease>,
+    audit: Arc<AuditTrail>,
+    notifications: Arc<NotificationService>,
+    outbox: Arc<ChainOutbox>,
+    config: Option<ArchiveConfig>,
+}
+
+impl ArchiveService {
+    pub fn new(
+        store: Arc<Store>,
+        keys: Arc<KeyHierarchy>,
+        release: Arc<KeyRelease>,
+        audit: Arc<AuditTrail>,
+        notifications: Arc<NotificationService>,
+        outbox: Arc<ChainOutbox>,
+        config: Option<ArchiveConfig>,
+    ) -> Self {
+        if config.is_none() {
+            warn!("No archive relay; audit entries and settled jobs stay in enclave storage");
+        }
+        Self {
+            store,
+            keys,
+            release,
+            audit,
+            notifications,
+            outbox,
+            config,
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.is_some()
+    }
+
+    /// Sweep and snapshot on the task scheduler
+    pub fn schedule(self: Arc<Self>, tasks: &TaskScheduler) -> Result<(), String> {
+        let Some(config) = self.config.clone() else {
+            return Ok(());
+        };
+        let sweeper = self.clone();
+        tasks.register(
+            "archive.sweep",
+            &Schedule::every(config.sweep),
+            config.sweep / 10,
+            move || {
+                let service = sweeper.clone();
+                async move {
+                    let shipped = service.sweep().await?;
+                    if shipped > 0 {
+                        info!("Archive sweep shipped {} objects", shipped);
+                    }
+                    Ok(())
+                }
+            },
+        )?;
+        tasks.register(
+            "archive.snapshot",
+            &Schedule::every(config.snapshot_every),
+            config.snapshot_every / 10,
+            move || {
+                let service = self.clone();
+                async move { service.snapshot().await.map(|_| ()) }
+            },
+        )
+    }
+
+    /// Manifests, newest first, optionally of one kind or vault
+    pub fn manifests(
+        &self,
+        kind: Option<ArchiveKind>,
+        vault_id: Option<&str>,
+    ) -> Result<Vec<ArchiveManifest>, String> {
+        let mut manifests: Vec<ArchiveManifest> = self
+            .store
+            .list::<ArchiveManifest>(MANIFESTS_NAMESPACE)?
+            .into_iter()
+            .map(|(_, manifest)| manifest)
+            .filter(|manifest| kind.is_none_or(|k| manifest.kind == k))
+            .filter(|manifest| vault_id.is_none_or(|v| manifest.vault_id.as_deref() == Some(v)))
+            .collect();
+        manifests.sort_by_key(|manifest| std::cmp::Reverse(manifest.archived_at));
+        Ok(manifests)
+    }
+
+    /// Ship the whole store as it stands
+    pub async fn snapshot(&self) -> Result<ArchiveManifest, String> {
+        let config = self.config()?;
+        let namespaces = self.store.snapshot()?;
+        let sealed = seal(
+            &self.keys,
+            &self.release.grant(ENCLAVE_SCOPE, "archive.snapshot")?,
+            ArchiveKind::Snapshot,
+            None,
+            &namespaces,
+            namespaces.len(),
+            None,
+        )?;
+        self.ship(config, &sealed).await?;
+        let manifest = sealed.manifest;
+        self.store
+            .put(MANIFESTS_NAMESPACE, &manifest.archive_id, &manifest)?;
+        info!(
+            "Snapshot {} of {} namespaces archived ({} bytes)",
+            manifest.archive_id, manifest.records, manifest.object_bytes
+        );
+        Ok(manifest)
+    }
+
+    /// Fetch an archived object back; None for an unknown archive id
+    pub async fn retrieve(&self, archive_id: &str) -> Result<Option<Retrieved>, String> {
+        let config = self.config()?;
+        let Some(manifest) = self
+            .store
+            .get::<ArchiveManifest>(MANIFESTS_NAMESPACE, archive_id)?
+        else {
+            return Ok(None);
+        };
+        let response = call(
+            &config.relay,
+            &json!({ "op": "get", "key": manifest.object_key }),
+        )
+        .await?;
+        let object = STANDARD
+            .decode(response.body.ok_or("Archive agent returned no object")?)
+            .map_err(|e| format!("Archive agent returned a malformed object: {}", e))?;
+        let grant = self.release.grant(
+            manifest.vault_id.as_deref().unwrap_or(ENCLAVE_SCOPE),
+            "archive.retrieve",
+        )?;
+        let records = open(&self.keys, &grant, &manifest, &object)?;
+        Ok(Some(Retrieved { manifest, records }))
+    }
+
+    async fn sweep(&self) -> Result<usize, String> {
+        let config = self.config()?;
+        let now = now();
+        let mut shipped = 0;
+
+        let audit_before = now.saturating_sub(config.audit_after.as_secs());
+        for segment in self.audit.archivable(audit_before, MAX_RECORDS)? {
+            let (first, last) = (&segment[0], &segment[segment.len() - 1]);
+            let range = AuditRange {
+                first_seq: first.seq,
+                last_seq: last.seq,
+                last_hash: last.hash.clone(),
+            };
+            let sealed = seal(
+                &self.keys,
+                &self.release.grant(&first.vault_id, "archive.seal")?,
+                ArchiveKind::AuditSegment,
+                Some(&first.vault_id),
+                &segment,
+                segment.len(),
+                Some(range),
+            )?;
+            self.ship(config, &sealed).await?;
+            self.audit
+                .remove_archived(manifest_batch(&sealed.manifest)?, &segment)?;
+            shipped += 1;
+        }
+
+        let jobs_before = now.saturating_sub(config.jobs_after.as_secs());
+        for (vault_id, jobs) in by_vault(self.notifications.settled(jobs_before)?, |record| {
+            &record.job.vault_id
+        }) {
+            for chunk in jobs.chunks(MAX_RECORDS) {
+                let sealed = seal(
+                    &self.keys,
+                    &self.release.grant(&vault_id, "archive.seal")?,
+                    ArchiveKind::NotificationJobs,
+                    Some(&vault_id),
+                    chunk,
+                    chunk.len(),
+                    None,
+                )?;
+                self.ship(config, &sealed).await?;
+                self.notifications
+                    .remove_archived(manifest_batch(&sealed.manifest)?, chunk)?;
+                shipped += 1;
+            }
+        }
+        for (vault_id, intents) in by_vault(self.outbox.settled(jobs_before)?, |record| {
+            &record.intent.vault_id
+        }) {
+            for chunk in intents.chunks(MAX_RECORDS) {
+                let sealed = seal(
+                    &self.keys,
+                    &self.release.grant(&vault_id, "archive.seal")?,
+                    ArchiveKind::ChainIntents,
+                    Some(&vault_id),
+                    chunk,
+                    chunk.len(),
+                    None,
+                )?;
+                self.ship(config, &sealed).await?;
+                self.outbox
+                    .remove_archived(manifest_batch(&sealed.manifest)?, chunk)?;
+                shipped += 1;
+            }
+        }
+        Ok(shipped)
+    }
+
+    async fn ship(&self, config: &ArchiveConfig, sealed: &Sealed) -> Result<(), String> {
+        call(
+            &config.relay,
+            &json!({
+                "op": "put",
+                "key": sealed.manifest.object_key,
+                "body": STANDARD.encode(&sealed.object),
+            }),
+        )
+        .await
+        .map(|_| ())
+    }
+
+    fn config(&self) -> Result<&ArchiveConfig, String> {
+        self.config
+            .as_ref()
+            .ok_or_else(|| "Archival is not configured".to_string())
+    }
+}
+
+/// Compress, encrypt and describe `records`
+fn seal<T: Serialize + ?Sized>(
+    keys: &KeyHierarchy,
+    grant: &ReleaseGrant,
+    kind: ArchiveKind,
+    vault_id: Option<&str>,
+    records: &T,
+    count: usize,
+    audit_range: Option<AuditRange>,
+) -> Result<Sealed, String> {
+    let content = serde_json::to_vec(records)
+        .map_err(|e| format!("Failed to serialize archived records: {}", e))?;
+    let compressed = zstd::encode_all(content.as_slice(), COMPRESSION_LEVEL)
+        .map_err(|e| format!("Failed to compress archived records: {}", e))?;
+    let mut id = [0u8; 16];
+    OsRng.fill_bytes(&mut id);
+    let archive_id = hex::encode(id);
+
+    let key = keys.unwrap::<Archival>(grant, vault_id.unwrap_or(ENCLAVE_SCOPE));
+    let (nonce, ciphertext) =
+        crypto::aead_encrypt(key.as_bytes(), &compressed, archive_id.as_bytes())?;
+    let object = [nonce.as_slice(), &ciphertext].concat();
+    Ok(Sealed {
+        manifest: ArchiveManifest {
+            object_key: format!("{}/{}", kind.prefix(), archive_id),
+            archive_id,
+            kind,
+            vault_id: vault_id.map(str::to_string),
+            records: count,
+            audit_range,
+            key_id: key.key_id(),
+            object_sha256: hex::encode(Sha256::digest(&object)),
+            content_sha256: hex::encode(Sha256::digest(&content)),
+            object_bytes: object.len(),
+            archived_at: now(),
+        },
+        object,
+    })
+}
+
+/// Check an object against its manifest, then decrypt it
+fn open(
+    keys: &KeyHierarchy,
+    grant: &ReleaseGrant,
+    manifest: &ArchiveManifest,
+    object: &[u8],
+) -> Result<Value, String> {
+    if hex::encode(Sha256::digest(object)) != manifest.object_sha256 {
+        return Err(format!(
+            "Archive {} doesn't match its manifest",
+            manifest.archive_id
+        ));
+    }
+    let key = keys.unwrap::<Archival>(grant, manifest.vault_id.as_deref().unwrap_or(ENCLAVE_SCOPE));
+    if key.key_id() != manifest.key_id {
+        return Err(format!(
+            "Archive {} is sealed under {} key {}, not the current one",
+            manifest.archive_id,
+            Archival::LABEL,
+            manifest.key_id
+        ));
+    }
+    if object.len() < 12 {
+        return Err(format!("Archive {} is truncated", manifest.archive_id));
+    }
+    let (nonce, ciphertext) = object.split_at(12);
+    let compressed = crypto::aead_decrypt(
+        key.as_bytes(),
+        nonce,
+        ciphertext,
+        manifest.archive_id.as_bytes(),
+    )?;
+    let content = zstd::decode_all(compressed.as_slice())
+        .map_err(|e| format!("Corrupt archive {}: {}", manifest.archive_id, e))?;
+    if hex::encode(Sha256::digest(&content)) != manifest.content_sha256 {
+        return Err(format!(
+            "Archive {} doesn't match its manifest",
+            manifest.archive_id
+        ));
+    }
+    serde_json::from_slice(&content)
+        .map_err(|e| format!("Corrupt archive {}: {}", manifest.archive_id, e))
+}
+
+/// The batch that records a stored object's manifest
+fn manifest_batch(manifest: &ArchiveManifest) -> Result<Batch, String> {
+    let mut batch = Batch::new();
+    batch.put(MANIFESTS_NAMESPACE, &manifest.archive_id, manifest)?;
+    Ok(batch)
+}
+
+fn by_vault<T>(records: Vec<T>, vault_id: impl Fn(&T) -> &String) -> BTreeMap<String, Vec<T>> {
+    let mut vaults: BTreeMap<String, Vec<T>> = BTreeMap::new();
+    for record in records {
+        vaults
+            .entry(vault_id(&record).clone())
+            .or_default()
+            .push(record);
+    }
+    vaults
+}
+
+/// One request to the agent, retried under the archive's retry policy
+async fn call(relay: &ParentRelay, request: &Value) -> Result<AgentResponse, String> {
+    let mut line = serde_json::to_vec(request)
+        .map_err(|e| format!("Failed to encode archive request: {}", e))?;
+    line.push(b'\n');
+
+    let line = &line;
+    retry::run(Dependency::Archive, move || attempt(relay, line)).await
+}
+
+async fn attempt(relay: &ParentRelay, line: &[u8]) -> Result<AgentResponse, Failure> {
+    let response = tokio::time::timeout(RELAY_TIMEOUT, async {
+        match relay {
+            ParentRelay::Tcp(addr) => {
+                let stream = tokio::net::TcpStream::connect(addr).await.map_err(|e| {
+                    Failure::new(
+                        FailureKind::Unreachable,
+                        format!("Failed to reach archive agent at {}: {}", addr, e),
+                    )
+                })?;
+                exchange(stream, line).await
+            }
+            #[cfg(feature = "vsock")]
+            ParentRelay::Vsock { port } => {
+                let stream = tokio_vsock::VsockStream::connect(tokio_vsock::VsockAddr::new(
+                    crate::bootstrap::PARENT_CID,
+                    *port,
+                ))
+                .await
+                .map_err(|e| {
+                    Failure::new(
+                        FailureKind::Unreachable,
+                        format!(
+                            "Failed to reach archive agent on vsock port {}: {}",
+                            port, e
+                        ),
+                    )
+                })?;
+                exchange(stream, line).await
+            }
+            #[cfg(not(feature = "vsock"))]
+            ParentRelay::Vsock { port } => Err(Failure::new(
+                FailureKind::Unreachable,
+                format!(
+                    "VSOCK archive agent (port {}) requires building with the `vsock` feature",
+                    port
+                ),
+            )),
+        }
+    })
+    .await
+    .map_err(|_| Failure::new(FailureKind::Timeout, "Archive agent timed out"))??;
+
+    let response: AgentResponse = serde_json::from_str(&response).map_err(|e| {
+        Failure::new(
+            FailureKind::Malformed,
+            format!("Malformed archive agent response: {}", e),
+        )
+    })?;
+    if let Some(error) = response.error {
+        return Err(Failure::new(
+            FailureKind::Remote,
+            format!("Archive agent failed: {}", error),
+        ));
+    }
+    Ok(response)
+}
+
+async fn exchange<S>(stream: S, line: &[u8]) -> Result<String, Failure>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let failed = |what: &str, e: std::io::Error| {
+        Failure::new(FailureKind::Transport, format!("Failed to {}: {}", what, e))
+    };
+    let mut stream = BufReader::new(stream);
+    stream
+        .get_mut()
+        .write_all(line)
+        .await
+        .map_err(|e| failed("send archive request", e))?;
+    let mut response = String::new();
+    let read = stream
+        .read_line(&mut response)
+        .await
+        .map_err(|e| failed("read archive response", e))?;
+    if read == 0 {
+        return Err(Failure::new(
+            FailureKind::Transport,
+            "Archive agent closed the connection",
+        ));
+    }
+    Ok(response)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::release::KeyReleasePolicy;
+
+    #[test]
+    fn objects_open_only_against_their_manifest() {
+        let keys = Arc::new(KeyHierarchy::ephemeral());
+        let dir = std::env::temp_dir().join(format!("lumina-archive-{}", std::process::id()));
+        let store = Arc::new(Store::open(dir).unwrap());
+        let audit = Arc::new(AuditTrail::new(store, keys.clone()));
+        let policy = KeyReleasePolicy {
+            allow_debug: true,
+            pcr8_signers: Vec::new(),
+        };
+        let grant = KeyRelease::new(policy, None, audit)
+            .grant("vault-1", "test")
+            .unwrap();
+        let records = vec![json!({ "seq": 0 }), json!({ "seq": 1 })];
+        let sealed = seal(
+            &keys,
+            &grant,
+            ArchiveKind::AuditSegment,
+            Some("vault-1"),
+            &records,
+            records.len(),
+            None,
+        )
+        .unwrap();
+        assert!(sealed
+            .manifest
+            .object_key
+            .ends_with(&sealed.manifest.archive_id));
+        assert_eq!(
+            open(&keys, &grant, &sealed.manifest, &sealed.object).unwrap(),
+            json!(records)
+        );
+
+        let mut altered = sealed.object.clone();
+        *altered.last_mut().unwrap() ^= 1;
+        assert!(open(&keys, &grant, &sealed.manifest, &altered).is_err());
+
+        // Another object can't stand in for this one, even under the same key
+        let other = seal(
+            &keys,
+            &grant,
+            ArchiveKind::AuditSegment,
+            Some("vault-1"),
+            &records,
+            records.len(),
+            None,
+        )
+        .unwrap();
+        assert!(open(&keys, &grant, &sealed.manifest, &other.object).is_err());
+        let mut forged = sealed.manifest.clone();
+        forged.object_sha256 = other.manifest.object_sha256.clone();
+        assert!(open(&keys, &grant, &forged, &other.object).is_err());
+    }
+}
@@ -1,16 +1,67 @@
 /**
  * Attestation Service
  * Generates AWS Nitro Enclave attestation documents
+ *
+ * Every document carries the time it was issued and an expires_at after
+ * which verifiers reject it. Validity depends on the operation: decisions
+ * that unlock a vault go stale in minutes, template backups are imported
+ * days later. Verifiers also apply their own limit to the document's age,
+ * so a peer configured with a longer validity can't extend it here.
+ *
+ * Inside an enclave the NSM signs the document's SHA-256 as user data
+ * into a COSE_Sign1 document (see nitro), carried as the attestation's
+ * signature. Verifiers check that up to the pinned Nitro root and take
+ * the PCRs it signs, not the ones enclave_info claims. Outside an enclave
+ * the signature is a bare hash anyone could forge; `verify` accepts those
+ * only when placeholder signatures are explicitly trusted (development).
  */
 
+use aws_nitro_enclaves_nsm_api::api::{Request, Response};
+use aws_nitro_enclaves_nsm_api::driver::{nsm_exit, nsm_init, nsm_process_request};
 use serde::{Deserialize, Serialize};
-use std::time::{SystemTime, UNIX_EPOCH};
-use sha2::{Sha256, Digest};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+use crate::nitro;
+
+/// Validity per operation unless configured otherwise
+const DEFAULT_MAX_AGES: &[(&str, u64)] = &[
+    ("biometric_verification", 5 * 60),
+    ("liveness_check", 5 * 60),
+    ("vault_release", 5 * 60),
+    ("threshold_release", 5 * 60),
+    ("item_release", 5 * 60),
+    ("channel_handshake", 60),
+    ("peer_request", 5 * 60),
+    ("template_backup", 30 * 24 * 60 * 60),
+];
+/// How far ahead of our clock a peer's issue time may be
+const MAX_FUTURE_SKEW_SECS: u64 = 60;
+
+#[derive(Debug, Error)]
+pub enum AttestationError {
+    #[error("Failed to build attestation: {0}")]
+    Internal(String),
+    #[error("Malformed attestation: {0}")]
+    Malformed(String),
+    #[error("Attestation signature is invalid")]
+    BadSignature,
+    /// Not signed by the NSM under the pinned root, or not over this document
+    #[error("Attestation is not trusted: {0}")]
+    Untrusted(String),
+    #[error("Attestation digest does not match its measurements")]
+    DigestMismatch,
+    /// Expired, past this enclave's age limit, or issued in the future
+    #[error("{0}")]
+    Stale(String),
+}
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Attestation {
-    pub document: String, // Base64-encoded attestation document
-    pub signature: String, // AWS-signed signature
+    pub document: String,  // Base64-encoded attestation document
+    pub signature: String, // Base64 NSM COSE_Sign1 over the document's hash
     pub enclave_info: EnclaveInfo,
 }
 
@@ -19,6 +70,9 @@ pub struct EnclaveInfo {
     pub image_id: String,
     pub measurements: Measurements,
     pub timestamp: u64,
+    /// Unix seconds after which verifiers reject the document
+    #[serde(default)]
+    pub expires_at: u64,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -26,10 +80,67 @@ pub struct Measurements {
     pub pcr0: String,
     pub pcr1: String,
     pub pcr2: String,
+    /// Hash of the certificate that signed the image; unsigned images have none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pcr8: Option<String>,
+}
+
+impl Measurements {
+    /// Debug-mode enclaves report all-zero PCRs, and their memory can be
+    /// inspected from the parent
+    pub fn is_debug(&self) -> bool {
+        [&self.pcr0, &self.pcr1, &self.pcr2]
+            .iter()
+            .all(|pcr| pcr.chars().all(|c| c == '0'))
+    }
+}
+
+/// How long attestations stay valid, per operation
+#[derive(Clone, Debug)]
+pub struct Freshness {
+    default: Duration,
+    per_operation: HashMap<String, Duration>,
 }
 
 pub struct AttestationService {
     image_id: String,
+    /// SHA-256 of the face model, bound into every document when loaded
+    face_model: Option<String>,
+    freshness: Freshness,
+    /// Accept documents signed with the placeholder hash; development only
+    placeholder_signatures: bool,
+    /// SHA-256 of the DER root certificate NSM documents must chain up to
+    nitro_root: [u8; 32],
+    /// PCRs as the NSM reported them at startup; None outside an enclave
+    nsm_measurements: Option<Measurements>,
+}
+
+impl Freshness {
+    /// `overrides` replace the built-in limit of the operations they name
+    pub fn new(default: Duration, overrides: &[(String, Duration)]) -> Self {
+        let mut per_operation: HashMap<String, Duration> = DEFAULT_MAX_AGES
+            .iter()
+            .map(|(operation, secs)| (operation.to_string(), Duration::from_secs(*secs)))
+            .collect();
+        per_operation.extend(overrides.iter().cloned());
+        Self {
+            default,
+            per_operation,
+        }
+    }
+
+    pub fn max_age(&self, operation: &str) -> Duration {
+        self.per_operation
+            .get(operation)
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+impl Default for Freshness {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(60 * 60), &[])
+    }
 }
 
 impl AttestationService {
@@ -39,12 +150,193 @@ impl AttestationService {
         let image_id = std::env::var("ENCLAVE_IMAGE_ID")
             .unwrap_or_else(|_| "nautilus-tee-image-v1".to_string());
 
-        Self { image_id }
+        Self {
+            image_id,
+            face_model: None,
+            freshness: Freshness::default(),
+            placeholder_signatures: false,
+            nitro_root: hex::decode(nitro::AWS_NITRO_ROOT_SHA256)
+                .unwrap()
+                .try_into()
+                .unwrap(),
+            nsm_measurements: read_nsm_measurements().ok(),
+        }
+    }
+
+    pub fn with_face_model(mut self, hash: &str) -> Self {
+        self.face_model = Some(hash.to_string());
+        self
+    }
+
+    pub fn with_freshness(mut self, freshness: Freshness) -> Self {
+        self.freshness = freshness;
+        self
     }
 
-    pub async fn generate(&self, vault_id: &str, operation: &str) -> Result<Attestation, String> {
+    /// Trust the placeholder signature when verifying; never in production
+    pub fn with_placeholder_signatures(mut self, trusted: bool) -> Self {
+        self.placeholder_signatures = trusted;
+        self
+    }
+
+    /// Pin another root than the commercial partition's, e.g. GovCloud's
+    pub fn with_nitro_root(mut self, sha256: [u8; 32]) -> Self {
+        self.nitro_root = sha256;
+        self
+    }
+
+    pub async fn generate(
+        &self,
+        vault_id: &str,
+        operation: &str,
+    ) -> Result<Attestation, AttestationError> {
+        self.build(vault_id, operation, Bindings::default())
+    }
+
+    /// Attestation committing to operation-specific data (e.g. the digest of
+    /// an aggregated guardian approval), carried as the document's user_data
+    pub async fn generate_with_user_data(
+        &self,
+        vault_id: &str,
+        operation: &str,
+        user_data: &[u8],
+    ) -> Result<Attestation, AttestationError> {
+        let bindings = Bindings {
+            user_data: Some(hex::encode(user_data)),
+            ..Bindings::default()
+        };
+        self.build(vault_id, operation, bindings)
+    }
+
+    /// Attestation over one request/response exchange: the canonical
+    /// hashes of both (see exchange), plus optional operation data
+    pub async fn generate_for_exchange(
+        &self,
+        vault_id: &str,
+        operation: &str,
+        request_sha256: &[u8],
+        response_sha256: &[u8],
+        user_data: Option<&[u8]>,
+    ) -> Result<Attestation, AttestationError> {
+        let bindings = Bindings {
+            user_data: user_data.map(hex::encode),
+            request_sha256: Some(hex::encode(request_sha256)),
+            response_sha256: Some(hex::encode(response_sha256)),
+            ..Bindings::default()
+        };
+        self.build(vault_id, operation, bindings)
+    }
+
+    /// Attestation binding an enclave-held public key, so a relying party
+    /// (e.g. KMS) only releases data encrypted to a key inside this enclave.
+    /// The NSM document in the signature carries the key as its public_key.
+    pub async fn generate_for_key(
+        &self,
+        operation: &str,
+        public_key: &[u8],
+    ) -> Result<Attestation, AttestationError> {
+        let bindings = Bindings {
+            public_key: Some(public_key.to_vec()),
+            ..Bindings::default()
+        };
+        self.build("", operation, bindings)
+    }
+
+    /// Check an attestation produced by another enclave: its NSM signature
+    /// up to the Nitro root, that the document digest commits to the signed
+    /// PCR0 and bindings, and that it hasn't expired or outlived this
+    /// enclave's limit
+    pub fn verify(
+        &self,
+        attestation: &Attestation,
+    ) -> Result<VerifiedAttestation, AttestationError> {
+        use base64::engine::general_purpose::STANDARD;
+        use base64::Engine;
+
+        let document_bytes = STANDARD
+            .decode(&attestation.document)
+            .map_err(|e| AttestationError::Malformed(format!("document encoding: {}", e)))?;
+        let signature = STANDARD
+            .decode(&attestation.signature)
+            .map_err(|e| AttestationError::Malformed(format!("signature encoding: {}", e)))?;
+
+        let [pcr0, pcr1, pcr2] =
+            if self.placeholder_signatures && signature == placeholder_signature(&document_bytes) {
+                // Development: nothing signs the PCRs, so take the claimed ones
+                let measurements = &attestation.enclave_info.measurements;
+                [&measurements.pcr0, &measurements.pcr1, &measurements.pcr2].map(String::clone)
+            } else {
+                let signed = nitro::verify(&signature, &self.nitro_root)
+                    .map_err(AttestationError::Untrusted)?;
+                if signed.user_data.as_deref().map(Vec::as_slice)
+                    != Some(Sha256::digest(&document_bytes).as_slice())
+                {
+                    return Err(AttestationError::BadSignature);
+                }
+                let pcr = |index: usize| {
+                    signed.pcrs.get(&index).map(hex::encode).ok_or_else(|| {
+                        AttestationError::Malformed(format!("NSM document has no PCR{}", index))
+                    })
+                };
+                [pcr(0)?, pcr(1)?, pcr(2)?]
+            };
+
+        let document: AttestationDocument = serde_json::from_slice(&document_bytes)
+            .map_err(|e| AttestationError::Malformed(format!("document: {}", e)))?;
+        self.check_freshness(&document)?;
+        let mut hasher = Sha256::new();
+        hasher.update(format!("{}{}{}", document.vault_id, document.operation, pcr0).as_bytes());
+        if let Some(public_key) = &document.public_key {
+            hasher.update(public_key.as_bytes());
+        }
+        if let Some(user_data) = &document.user_data {
+            hasher.update(user_data.as_bytes());
+        }
+        let exchange = [&document.request_sha256, &document.response_sha256];
+        for hash in exchange.into_iter().flatten() {
+            hasher.update(hash.as_bytes());
+        }
+        if let Some(face_model) = &document.face_model {
+            hasher.update(face_model.as_bytes());
+        }
+        if let Some(expires_at) = document.expires_at {
+            hasher.update(expires_at.to_be_bytes());
+        }
+        if document.digest != format!("sha256:{}", hex::encode(hasher.finalize())) {
+            return Err(AttestationError::DigestMismatch);
+        }
+
+        Ok(VerifiedAttestation {
+            pcr0,
+            pcr1,
+            pcr2,
+            operation: document.operation,
+            user_data: document.user_data,
+            request_sha256: document.request_sha256,
+            response_sha256: document.response_sha256,
+        })
+    }
+
+    fn build(
+        &self,
+        vault_id: &str,
+        operation: &str,
+        bindings: Bindings,
+    ) -> Result<Attestation, AttestationError> {
+        use base64::engine::general_purpose::STANDARD;
+        use base64::Engine;
+
         // Get PCR measurements from NSM
         let measurements = self.get_pcr_measurements()?;
+        let public_key = bindings
+            .public_key
+            .as_deref()
+            .map(|key| STANDARD.encode(key));
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let expires_at = timestamp + self.freshness.max_age(operation).as_secs();
 
         // Create attestation document
         let document = AttestationDocument {
@@ -52,27 +344,48 @@ impl AttestationService {
             digest: {
                 let mut hasher = Sha256::new();
                 hasher.update(format!("{}{}{}", vault_id, operation, measurements.pcr0).as_bytes());
+                if let Some(public_key) = &public_key {
+                    hasher.update(public_key.as_bytes());
+                }
+                if let Some(user_data) = &bindings.user_data {
+                    hasher.update(user_data.as_bytes());
+                }
+                let exchange = [&bindings.request_sha256, &bindings.response_sha256];
+                for hash in exchange.into_iter().flatten() {
+                    hasher.update(hash.as_bytes());
+                }
+                if let Some(face_model) = &self.face_model {
+                    hasher.update(face_model.as_bytes());
+                }
+                hasher.update(expires_at.to_be_bytes());
                 format!("sha256:{}", hex::encode(hasher.finalize()))
             },
-            timestamp: SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs(),
+            timestamp,
+            expires_at: Some(expires_at),
             operation: operation.to_string(),
             vault_id: vault_id.to_string(),
+            public_key,
+            user_data: bindings.user_data,
+            request_sha256: bindings.request_sha256,
+            response_sha256: bindings.response_sha256,
+            face_model: self.face_model.clone(),
         };
 
         // Serialize document
         let document_bytes = serde_json::to_vec(&document)
-            .map_err(|e| format!("Failed to serialize document: {}", e))?;
+            .map_err(|e| AttestationError::Internal(format!("serializing document: {}", e)))?;
 
-        // Sign with NSM (Nitro Security Module)
-        // In real deployment, this uses the enclave's private key
-        let signature = self.sign_document(&document_bytes)?;
+        // The NSM signs the document's hash and any bound key; there is no
+        // NSM outside an enclave
+        let signature = match &self.nsm_measurements {
+            Some(_) => nitro::request_document(
+                Some(&Sha256::digest(&document_bytes)),
+                bindings.public_key.as_deref(),
+            )
+            .map_err(AttestationError::Internal)?,
+            None => placeholder_signature(&document_bytes),
+        };
 
-        use base64::engine::general_purpose::STANDARD;
-        use base64::Engine;
-        
         Ok(Attestation {
             document: STANDARD.encode(&document_bytes),
             signature: STANDARD.encode(&signature),
@@ -80,13 +393,45 @@ impl AttestationService {
                 image_id: self.image_id.clone(),
                 measurements,
                 timestamp: document.timestamp,
+                expires_at,
             },
         })
     }
 
-    fn get_pcr_measurements(&self) -> Result<Measurements, String> {
-        // In real deployment, read PCRs from NSM
-        // For now, return placeholder values
+    fn check_freshness(&self, document: &AttestationDocument) -> Result<(), AttestationError> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        if document.timestamp > now + MAX_FUTURE_SKEW_SECS {
+            return Err(AttestationError::Stale(
+                "Attestation is issued in the future".to_string(),
+            ));
+        }
+        if document
+            .expires_at
+            .is_some_and(|expires_at| expires_at <= now)
+        {
+            return Err(AttestationError::Stale(
+                "Attestation has expired".to_string(),
+            ));
+        }
+        let max_age = self.freshness.max_age(&document.operation).as_secs();
+        if now.saturating_sub(document.timestamp) > max_age {
+            return Err(AttestationError::Stale(format!(
+                "Attestation for {} is older than {}s",
+                document.operation, max_age
+            )));
+        }
+        Ok(())
+    }
+
+    /// Measurements this enclave reports: the NSM's, or placeholders
+    /// derived from the image id outside an enclave
+    pub fn get_pcr_measurements(&self) -> Result<Measurements, AttestationError> {
+        if let Some(measurements) = &self.nsm_measurements {
+            return Ok(measurements.clone());
+        }
         // PCR0 = Image ID hash
         // PCR1 = Image version hash
         // PCR2 = User data hash
@@ -106,23 +451,46 @@ impl AttestationService {
                 hasher.update(b"nautilus-tee");
                 hex::encode(hasher.finalize())
             },
+            pcr8: None,
         })
     }
 
-    fn sign_document(&self, document: &[u8]) -> Result<Vec<u8>, String> {
-        // In real deployment, use NSM to sign with enclave's private key
-        // For now, use a placeholder signature
-        // This would be replaced with actual NSM API calls:
-        // let nsm_fd = nsm_init();
-        // let response = nsm_attestation(nsm_fd, document);
-        // nsm_exit(nsm_fd);
-        
-        // Placeholder: hash-based signature (not secure, for testing only)
-            let mut hasher = Sha256::new();
-            hasher.update(document);
-            let signature = hasher.finalize();
-        Ok(signature.to_vec())
+    /// Measurements read from the NSM, the only ones a key release
+    /// decision may trust
+    pub fn nsm_measurements(&self) -> Option<Measurements> {
+        self.nsm_measurements.clone()
+    }
+}
+
+/// Outside an enclave: a hash of the document, which proves nothing
+fn placeholder_signature(document: &[u8]) -> Vec<u8> {
+    Sha256::digest(document).to_vec()
+}
+
+/// PCRs 0-2 and 8 via DescribePCR; PCR8 is all zeros for unsigned images
+fn read_nsm_measurements() -> Result<Measurements, AttestationError> {
+    let fd = nsm_init();
+    if fd < 0 {
+        return Err(AttestationError::Internal("NSM is unavailable".to_string()));
     }
+    let describe = |index: u16| match nsm_process_request(fd, Request::DescribePCR { index }) {
+        Response::DescribePCR { data, .. } => Ok(hex::encode(data)),
+        other => Err(AttestationError::Internal(format!(
+            "DescribePCR {} failed: {:?}",
+            index, other
+        ))),
+    };
+    let measurements = (|| -> Result<Measurements, AttestationError> {
+        let pcr8 = describe(8)?;
+        Ok(Measurements {
+            pcr0: describe(0)?,
+            pcr1: describe(1)?,
+            pcr2: describe(2)?,
+            pcr8: Some(pcr8).filter(|pcr| !pcr.chars().all(|c| c == '0')),
+        })
+    })();
+    nsm_exit(fd);
+    measurements
 }
 
 #[derive(Serialize, Deserialize)]
@@ -130,7 +498,123 @@ struct AttestationDocument {
     module_id: String,
     digest: String,
     timestamp: u64,
+    /// Absent from documents issued before expiry was introduced
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires_at: Option<u64>,
     operation: String,
     vault_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    public_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user_data: Option<String>,
+    /// Hex canonical hashes of the exchange the document vouches for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    request_sha256: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    response_sha256: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    face_model: Option<String>,
+}
+
+/// Claims from an attestation whose signature and digest checked out
+pub struct VerifiedAttestation {
+    pub pcr0: String,
+    pub pcr1: String,
+    pub pcr2: String,
+    pub operation: String,
+    pub user_data: Option<String>,
+    pub request_sha256: Option<String>,
+    pub response_sha256: Option<String>,
 }
 
+/// Optional values a document commits to beyond operation and vault
+#[derive(Default)]
+struct Bindings {
+    public_key: Option<Vec<u8>>, // Raw; base64 in the document
+    user_data: Option<String>,
+    request_sha256: Option<String>,
+    response_sha256: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nitro::testing::TestPki;
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+
+    /// The measurements this (non-enclave) service reports, as the NSM signs them
+    fn signed_pcrs(service: &AttestationService) -> Vec<(usize, Vec<u8>)> {
+        let measurements = service.get_pcr_measurements().unwrap();
+        [measurements.pcr0, measurements.pcr1, measurements.pcr2]
+            .iter()
+            .enumerate()
+            .map(|(index, pcr)| (index, hex::decode(pcr).unwrap()))
+            .collect()
+    }
+
+    fn document_sha256(attestation: &Attestation) -> Vec<u8> {
+        Sha256::digest(STANDARD.decode(&attestation.document).unwrap()).to_vec()
+    }
+
+    #[tokio::test]
+    async fn nsm_signed_attestations_verify_up_to_the_pinned_root() {
+        let service = AttestationService::new();
+        let mut attestation = service
+            .generate_with_user_data("vault-1", "peer_request", b"request")
+            .await
+            .unwrap();
+        let pki = TestPki::generate();
+        let nsm_document = pki.sign(&signed_pcrs(&service), &document_sha256(&attestation));
+        attestation.signature = STANDARD.encode(nsm_document);
+        // Only what the NSM signed counts, whatever enclave_info claims
+        attestation.enclave_info.measurements.pcr1 = "00".repeat(32);
+
+        let verifier = AttestationService::new().with_nitro_root(pki.root_sha256());
+        let verified = verifier.verify(&attestation).unwrap();
+        assert_eq!(verified.pcr1, service.get_pcr_measurements().unwrap().pcr1);
+        assert_eq!(verified.user_data, Some(hex::encode(b"request")));
+
+        // The same document under the AWS root this service pins by default
+        assert!(matches!(
+            AttestationService::new().verify(&attestation),
+            Err(AttestationError::Untrusted(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn nsm_documents_must_cover_this_document_and_its_pcr0() {
+        let service = AttestationService::new();
+        let mut attestation = service.generate("vault-1", "peer_request").await.unwrap();
+        let pki = TestPki::generate();
+        let verifier = AttestationService::new().with_nitro_root(pki.root_sha256());
+
+        let other_document = pki.sign(&signed_pcrs(&service), &Sha256::digest(b"other"));
+        attestation.signature = STANDARD.encode(other_document);
+        assert!(matches!(
+            verifier.verify(&attestation),
+            Err(AttestationError::BadSignature)
+        ));
+
+        let mut pcrs = signed_pcrs(&service);
+        pcrs[0].1 = vec![7; 48];
+        attestation.signature = STANDARD.encode(pki.sign(&pcrs, &document_sha256(&attestation)));
+        assert!(matches!(
+            verifier.verify(&attestation),
+            Err(AttestationError::DigestMismatch)
+        ));
+    }
+
+    #[tokio::test]
+    async fn placeholder_signatures_verify_only_in_development() {
+        let service = AttestationService::new();
+        let attestation = service.generate("vault-1", "peer_request").await.unwrap();
+
+        assert!(matches!(
+            service.verify(&attestation),
+            Err(AttestationError::Untrusted(_))
+        ));
+        let development = AttestationService::new().with_placeholder_signatures(true);
+        assert!(development.verify(&attestation).is_ok());
+    }
+}
@@ -0,0 +1,221 @@
+/**
+ * Audit Trail
+ * Per-vault, hash-chained record of governance actions
+ *
+ * Each entry commits to the one before it and is signed with the vault's
+ * audit key (see keys::derive), whose public half is published at
+ * /vault/:vault_id/keys. Dropping, reordering or editing an entry breaks
+ * the chain, and only the enclave can sign a replacement:
+ *
+ *   hash      = sha256(JSON of the entry without hash and signature)
+ *   signature = ed25519(audit key, hash)
+ *
+ * The first entry's prev_hash is 64 zeros.
+ *
+ * Old entries move out to the archive (see archive), all but each vault's
+ * latest, which the next entry chains onto; `entries` returns the ones
+ * still held, and the first of them chains onto the archived segments.
+ */
+
+use ed25519_dalek::Signer;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::keys::derive::{AuditSigning, KeyHierarchy};
+use crate::pagination::Paged;
+use crate::store::{Batch, Store};
+
+const NAMESPACE: &str = "audit_trail";
+const GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+#[derive(Serialize)]
+struct UnsignedEntry<'a> {
+    vault_id: &'a str,
+    seq: u64,
+    action: &'a str,
+    actor: &'a str,
+    detail: &'a Value,
+    at: u64,
+    prev_hash: &'a str,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub vault_id: String,
+    pub seq: u64,
+    /// Dotted name, e.g. "legal_hold.placed"
+    pub action: String,
+    pub actor: String,
+    pub detail: Value,
+    pub at: u64,
+    pub prev_hash: String,
+    pub hash: String,      // Hex
+    pub signature: String, // Hex Ed25519 over the hash bytes
+}
+
+/// Commits to the head of every vault's chain at once
+#[derive(Clone, Serialize)]
+pub struct AuditRoot {
+    pub vaults: usize,
+    pub entries: u64,
+    /// Hex sha256 over "<vault_id> <seq> <hash>\n" per vault head, sorted
+    /// by vault id; GENESIS when there are no entries
+    pub root: String,
+}
+
+pub struct AuditTrail {
+    store: Arc<Store>,
+    keys: Arc<KeyHierarchy>,
+    // Held across appends so each entry chains onto the latest
+    append: Mutex<()>,
+}
+
+impl Paged for AuditEntry {
+    fn timestamp(&self) -> u64 {
+        self.at
+    }
+
+    /// Zero-padded so ties order by sequence
+    fn id(&self) -> String {
+        format!("{:020}", self.seq)
+    }
+}
+
+impl AuditTrail {
+    pub fn new(store: Arc<Store>, keys: Arc<KeyHierarchy>) -> Self {
+        Self {
+            store,
+            keys,
+            append: Mutex::new(()),
+        }
+    }
+
+    pub fn record(
+        &self,
+        vault_id: &str,
+        action: &str,
+        actor: &str,
+        detail: Value,
+    ) -> Result<AuditEntry, String> {
+        let _guard = self.append.lock().unwrap();
+        let head = self.entries(vault_id)?.pop();
+        let seq = head.as_ref().map_or(0, |h| h.seq + 1);
+        let prev_hash = head.map_or_else(|| GENESIS.to_string(), |h| h.hash);
+        let at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        let unsigned = serde_json::to_vec(&UnsignedEntry {
+            vault_id,
+            seq,
+            action,
+            actor,
+            detail: &detail,
+            at,
+            prev_hash: &prev_hash,
+        })
+        .map_err(|e| format!("Failed to serialize audit entry: {}", e))?;
+        let hash = Sha256::digest(unsigned);
+        let signature = self
+            .keys
+            .derive::<AuditSigning>(vault_id)
+            .signing_key()
+            .sign(&hash);
+
+        let entry = AuditEntry {
+            vault_id: vault_id.to_string(),
+            seq,
+            action: action.to_string(),
+            actor: actor.to_string(),
+            detail,
+            at,
+            prev_hash,
+            hash: hex::encode(hash),
+            signature: hex::encode(signature.to_bytes()),
+        };
+        self.store
+            .put(NAMESPACE, &entry_key(vault_id, seq), &entry)?;
+        Ok(entry)
+    }
+
+    /// Root over every vault's latest entry, so an auditor holding the
+    /// vaults' exports can check none was cut short or rewritten
+    pub fn root(&self) -> Result<AuditRoot, String> {
+        let mut heads: BTreeMap<String, AuditEntry> = BTreeMap::new();
+        for (_, entry) in self.store.list::<AuditEntry>(NAMESPACE)? {
+            if heads
+                .get(&entry.vault_id)
+                .map_or(true, |head| head.seq < entry.seq)
+            {
+                heads.insert(entry.vault_id.clone(), entry);
+            }
+        }
+        if heads.is_empty() {
+            return Ok(AuditRoot {
+                vaults: 0,
+                entries: 0,
+                root: GENESIS.to_string(),
+            });
+        }
+
+        let mut hasher = Sha256::new();
+        for head in heads.values() {
+            hasher.update(format!("{} {} {}\n", head.vault_id, head.seq, head.hash));
+        }
+        Ok(AuditRoot {
+            vaults: heads.len(),
+            entries: heads.values().map(|head| head.seq + 1).sum(),
+            root: hex::encode(hasher.finalize()),
+        })
+    }
+
+    /// The vault's entries, oldest first
+    pub fn entries(&self, vault_id: &str) -> Result<Vec<AuditEntry>, String> {
+        Ok(self
+            .store
+            .list::<AuditEntry>(NAMESPACE)?
+            .into_iter()
+            .map(|(_, entry)| entry)
+            .filter(|entry| entry.vault_id == vault_id)
+            .collect())
+    }
+
+    /// Runs of up to `max` entries recorded before `before`, oldest first
+    /// per vault, leaving each vault's latest entry out
+    pub fn archivable(&self, before: u64, max: usize) -> Result<Vec<Vec<AuditEntry>>, String> {
+        let mut vaults: BTreeMap<String, Vec<AuditEntry>> = BTreeMap::new();
+        for (_, entry) in self.store.list::<AuditEntry>(NAMESPACE)? {
+            vaults
+                .entry(entry.vault_id.clone())
+                .or_default()
+                .push(entry);
+        }
+        let mut segments = Vec::new();
+        for mut entries in vaults.into_values() {
+            entries.pop();
+            let old = entries.iter().take_while(|entry| entry.at < before).count();
+            entries.truncate(old);
+            segments.extend(entries.chunks(max).map(<[AuditEntry]>::to_vec));
+        }
+        Ok(segments)
+    }
+
+    /// Commit `batch` with an archived segment's entries removed
+    pub fn remove_archived(&self, mut batch: Batch, segment: &[AuditEntry]) -> Result<(), String> {
+        let _guard = self.append.lock().unwrap();
+        for entry in segment {
+            batch.remove(NAMESPACE, &entry_key(&entry.vault_id, entry.seq));
+        }
+        self.store.commit(batch)
+    }
+}
+
+/// Zero-padded so a vault's entries list in sequence order
+fn entry_key(vault_id: &str, seq: u64) -> String {
+    format!("{}:{:020}", vault_id, seq)
+}
@@ -0,0 +1,448 @@
+/**
+ * Request Authentication
+ * Verifies that signed requests come from the owner of `user_address`
+ *
+ * Clients sign a canonical form of the request with their Sui Ed25519 key:
+ *
+ *   METHOD \n PATH \n hex(sha256(body)) \n TIMESTAMP \n NONCE
+ *
+ * and send the signature, public key, timestamp and nonce as headers. The
+ * public key must hash to the `user_address` in the request body, and the
+ * timestamp/nonce pair is checked by the replay guard.
+ *
+ * A request may also carry a capability token (see capability); its
+ * holder then acts as the owner who issued it, on the routes it grants.
+ */
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{MatchedPath, Request, State},
+    http::{HeaderMap, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use blake2::{digest::consts::U32, Blake2b, Digest};
+use ed25519_dalek::{Signature, VerifyingKey};
+use serde::Deserialize;
+use sha2::Sha256;
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use crate::capability::{CapabilityService, CAPABILITY_HEADER};
+use crate::error::AppError;
+use crate::replay::ReplayGuard;
+
+pub const SIGNATURE_HEADER: &str = "x-lumina-signature";
+pub const PUBLIC_KEY_HEADER: &str = "x-lumina-public-key";
+pub const TIMESTAMP_HEADER: &str = "x-lumina-timestamp";
+pub const NONCE_HEADER: &str = "x-lumina-nonce";
+
+/// Default bound on bodies buffered for signature verification
+pub const MAX_SIGNED_BODY_BYTES: usize = 1024 * 1024;
+
+/// Sui signature scheme flag for Ed25519 keys
+const ED25519_FLAG: u8 = 0x00;
+
+/// Address of the verified signer, available to handlers via `Extension`
+#[derive(Clone, Debug)]
+pub struct VerifiedSigner {
+    pub address: String,
+    /// Capability holder who signed on behalf of `address`
+    pub delegate: Option<String>,
+    /// Hex SHA-256 of the body the signature covers
+    pub request_sha256: String,
+}
+
+#[derive(Clone)]
+pub struct AuthState {
+    pub replay: Arc<ReplayGuard>,
+    pub capabilities: Arc<CapabilityService>,
+    /// Upper bound on bodies buffered for signature verification; the
+    /// routes themselves may accept less. MAX_SIGNED_BODY_BYTES except on
+    /// routes carrying vault blobs
+    pub max_body_bytes: usize,
+}
+
+/// The only field the signature check reads; the rest of a (possibly
+/// large) body is skipped without being copied
+#[derive(Deserialize)]
+struct Addressed<'a> {
+    #[serde(borrow)]
+    user_address: Cow<'a, str>,
+}
+
+pub async fn require_signature(
+    State(auth): State<AuthState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let (parts, body) = request.into_parts();
+
+    // Malformed and stale requests are turned away before their body is
+    // buffered
+    let headers = SignatureHeaders::from_headers(&parts.headers)?;
+    auth.replay.check_timestamp(headers.timestamp)?;
+
+    let body = to_bytes(body, auth.max_body_bytes)
+        .await
+        .map_err(|_| AppError::bad_request("BODY_TOO_LARGE", "Request body too large to verify"))?;
+
+    let user_address = serde_json::from_slice::<Addressed>(&body)
+        .map(|a| a.user_address)
+        .map_err(|_| {
+            AppError::bad_request("MISSING_USER_ADDRESS", "Signed requests must include user_address")
+        })?;
+
+    let signer_address = sui_address(&headers.public_key);
+    if normalize_address(&user_address) != signer_address {
+        return Err(AppError::unauthorized(
+            "SIGNER_MISMATCH",
+            "Public key does not belong to user_address",
+        ));
+    }
+
+    let path = parts
+        .uri
+        .path_and_query()
+        .map(|p| p.as_str())
+        .unwrap_or_else(|| parts.uri.path());
+    let message = canonical_request(
+        parts.method.as_str(),
+        path,
+        &body,
+        headers.timestamp,
+        &headers.nonce,
+    );
+
+    headers
+        .public_key
+        .verify_strict(message.as_bytes(), &headers.signature)
+        .map_err(|_| AppError::unauthorized("SIGNATURE_INVALID", "Request signature is invalid"))?;
+
+    // Only remember nonces from valid signatures, so forged requests can't
+    // burn a legitimate client's nonces
+    auth.replay
+        .record(&signer_address, &headers.nonce, headers.timestamp)?;
+
+    let request_sha256 = hex::encode(Sha256::digest(&body));
+    let signer = match parts.headers.get(CAPABILITY_HEADER) {
+        None => VerifiedSigner {
+            address: signer_address,
+            delegate: None,
+            request_sha256,
+        },
+        Some(token) => {
+            let token = token.to_str().map_err(|_| {
+                AppError::bad_request("MALFORMED_CAPABILITY", "Malformed capability header")
+            })?;
+            let route = parts
+                .extensions
+                .get::<MatchedPath>()
+                .map(MatchedPath::as_str);
+            let claims = auth
+                .capabilities
+                .authorize(token, &signer_address, route, parts.uri.path())
+                .map_err(|e| AppError::new(StatusCode::FORBIDDEN, "CAPABILITY_DENIED", e))?;
+            VerifiedSigner {
+                address: claims.issuer,
+                delegate: Some(signer_address),
+                request_sha256,
+            }
+        }
+    };
+
+    let mut request = Request::from_parts(parts, Body::from(body));
+    request.extensions_mut().insert(signer);
+
+    Ok(next.run(request).await)
+}
+
+pub fn canonical_request(
+    method: &str,
+    path: &str,
+    body: &[u8],
+    timestamp: u64,
+    nonce: &str,
+) -> String {
+    let body_hash = hex::encode(Sha256::digest(body));
+    format!(
+        "{}\n{}\n{}\n{}\n{}",
+        method.to_uppercase(),
+        path,
+        body_hash,
+        timestamp,
+        nonce
+    )
+}
+
+/// Sui address: blake2b-256(flag || public key)
+pub fn sui_address(public_key: &VerifyingKey) -> String {
+    let mut hasher = Blake2b::<U32>::new();
+    hasher.update([ED25519_FLAG]);
+    hasher.update(public_key.as_bytes());
+    format!("0x{}", hex::encode(hasher.finalize()))
+}
+
+pub fn normalize_address(address: &str) -> String {
+    let hex = address.trim_start_matches("0x").to_lowercase();
+    format!("0x{:0>64}", hex)
+}
+
+struct SignatureHeaders {
+    signature: Signature,
+    public_key: VerifyingKey,
+    timestamp: u64,
+    nonce: String,
+}
+
+impl SignatureHeaders {
+    fn from_headers(headers: &HeaderMap) -> Result<Self, AppError> {
+        let signature = decode_header::<64>(headers, SIGNATURE_HEADER)?;
+        let public_key = decode_header::<32>(headers, PUBLIC_KEY_HEADER)?;
+        let timestamp = header_str(headers, TIMESTAMP_HEADER)?
+            .parse()
+            .map_err(|_| AppError::bad_request("INVALID_TIMESTAMP", "Timestamp must be unix seconds"))?;
+        let nonce = header_str(headers, NONCE_HEADER)?.to_string();
+
+        Ok(Self {
+            signature: Signature::from_bytes(&signature),
+            public_key: VerifyingKey::from_bytes(&public_key)
+                .map_err(|_| AppError::bad_request("INVALID_PUBLIC_KEY", "Malformed Ed25519 public key"))?,
+            timestamp,
+            nonce,
+        })
+    }
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> Result<&'a str, AppError> {
+    headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .ok_or_else(|| AppError::unauthorized("MISSING_SIGNATURE", format!("Missing {} header", name)))
+}
+
+fn decode_header<const N: usize>(headers: &HeaderMap, name: &str) -> Result<[u8; N], AppError> {
+    STANDARD
+        .decode(header_str(headers, name)?)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| AppError::bad_request("MALFORMED_SIGNATURE", format!("Malformed {} header", name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{routing::post, Extension, Router};
+    use ed25519_dalek::{Signer, SigningKey};
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use tower::Service;
+
+    use crate::keys::identity::EnclaveIdentity;
+    use crate::store::Store;
+
+    const AUDIT_PATH: &str = "/vault/vault-1/audit/export";
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    fn signing_key() -> SigningKey {
+        SigningKey::from_bytes(&rand::random())
+    }
+
+    /// A router answering with whoever the middleware verified
+    fn app(name: &str) -> (Router, Arc<CapabilityService>) {
+        let dir = std::env::temp_dir().join(format!("lumina-auth-tests-{}-{}", name, now()));
+        let store = Arc::new(Store::open(&dir).unwrap());
+        let identity = Arc::new(EnclaveIdentity::ephemeral(&"00".repeat(32), "test"));
+        let capabilities = Arc::new(CapabilityService::new(identity, store.clone()));
+        let auth = AuthState {
+            replay: Arc::new(ReplayGuard::new(store, 300).unwrap()),
+            capabilities: capabilities.clone(),
+            max_body_bytes: MAX_SIGNED_BODY_BYTES,
+        };
+        let app = Router::new()
+            .route("/echo", post(verified))
+            .route("/vault/:vault_id/audit/export", post(verified))
+            .route_layer(axum::middleware::from_fn_with_state(
+                auth,
+                require_signature,
+            ));
+        (app, capabilities)
+    }
+
+    async fn verified(Extension(signer): Extension<VerifiedSigner>) -> String {
+        match signer.delegate {
+            Some(delegate) => format!("{} via {}", signer.address, delegate),
+            None => signer.address,
+        }
+    }
+
+    fn body(key: &SigningKey) -> String {
+        serde_json::json!({ "user_address": sui_address(&key.verifying_key()) }).to_string()
+    }
+
+    /// Headers signing exactly this method, path, body, timestamp and nonce
+    fn signature_headers(
+        key: &SigningKey,
+        method: &str,
+        path: &str,
+        body: &str,
+        timestamp: u64,
+        nonce: &str,
+    ) -> Vec<(&'static str, String)> {
+        let message = canonical_request(method, path, body.as_bytes(), timestamp, nonce);
+        vec![
+            (
+                SIGNATURE_HEADER,
+                STANDARD.encode(key.sign(message.as_bytes()).to_bytes()),
+            ),
+            (
+                PUBLIC_KEY_HEADER,
+                STANDARD.encode(key.verifying_key().as_bytes()),
+            ),
+            (TIMESTAMP_HEADER, timestamp.to_string()),
+            (NONCE_HEADER, nonce.to_string()),
+        ]
+    }
+
+    fn request(
+        method: &str,
+        path: &str,
+        body: String,
+        headers: Vec<(&'static str, String)>,
+    ) -> Request {
+        let mut request = axum::http::Request::builder().method(method).uri(path);
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        request.body(Body::from(body)).unwrap()
+    }
+
+    fn signed(key: &SigningKey, path: &str, nonce: &str) -> Request {
+        let body = body(key);
+        let headers = signature_headers(key, "POST", path, &body, now(), nonce);
+        request("POST", path, body, headers)
+    }
+
+    /// Status and, for errors, the code
+    async fn send(app: &Router, request: Request) -> (StatusCode, String) {
+        let response = app.clone().call(request).await.unwrap();
+        let status = response.status();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let text = String::from_utf8(body.to_vec()).unwrap();
+        if status.is_success() {
+            return (status, text);
+        }
+        let error: serde_json::Value = serde_json::from_str(&text).unwrap();
+        (status, error["code"].as_str().unwrap().to_string())
+    }
+
+    #[tokio::test]
+    async fn valid_signatures_verify_once() {
+        let (app, _) = app("valid");
+        let key = signing_key();
+
+        let (status, signer) = send(&app, signed(&key, "/echo", "n1")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(signer, sui_address(&key.verifying_key()));
+
+        let (status, code) = send(&app, signed(&key, "/echo", "n1")).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(code, "REQUEST_REPLAYED");
+    }
+
+    #[tokio::test]
+    async fn signatures_bind_the_address_method_path_and_body() {
+        let (app, _) = app("tampered");
+        let key = signing_key();
+        let other = signing_key();
+        let ts = now();
+
+        // Someone else's address under this key
+        let headers = signature_headers(&key, "POST", "/echo", &body(&other), ts, "n1");
+        let (status, code) = send(&app, request("POST", "/echo", body(&other), headers)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(code, "SIGNER_MISMATCH");
+
+        let tampered = body(&key).replace('}', r#","extra":true}"#);
+        let headers = signature_headers(&key, "POST", "/echo", &body(&key), ts, "n2");
+        let (_, code) = send(&app, request("POST", "/echo", tampered, headers)).await;
+        assert_eq!(code, "SIGNATURE_INVALID");
+
+        let headers = signature_headers(&key, "POST", AUDIT_PATH, &body(&key), ts, "n3");
+        let (_, code) = send(&app, request("POST", "/echo", body(&key), headers)).await;
+        assert_eq!(code, "SIGNATURE_INVALID");
+
+        let headers = signature_headers(&key, "PUT", "/echo", &body(&key), ts, "n4");
+        let (_, code) = send(&app, request("POST", "/echo", body(&key), headers)).await;
+        assert_eq!(code, "SIGNATURE_INVALID");
+
+        // None of the forgeries used up the nonce
+        let (status, _) = send(&app, signed(&key, "/echo", "n2")).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn expired_and_unsigned_requests_are_refused() {
+        let (app, _) = app("expired");
+        let key = signing_key();
+
+        let stale = now() - 3600;
+        let headers = signature_headers(&key, "POST", "/echo", &body(&key), stale, "n1");
+        let (status, code) = send(&app, request("POST", "/echo", body(&key), headers)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(code, "REQUEST_EXPIRED");
+
+        let (status, code) = send(&app, request("POST", "/echo", body(&key), Vec::new())).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(code, "MISSING_SIGNATURE");
+    }
+
+    #[tokio::test]
+    async fn capability_holders_act_as_the_issuer_on_granted_routes() {
+        let (app, capabilities) = app("capability");
+        let owner = sui_address(&signing_key().verifying_key());
+        let holder = signing_key();
+        let holder_address = sui_address(&holder.verifying_key());
+        let (token, _) = capabilities
+            .mint(
+                "vault-1",
+                &owner,
+                &holder_address,
+                &["audit".to_string()],
+                60,
+            )
+            .unwrap();
+
+        let mut granted = signed(&holder, AUDIT_PATH, "n1");
+        granted
+            .headers_mut()
+            .insert(CAPABILITY_HEADER, token.parse().unwrap());
+        let (status, signer) = send(&app, granted).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(signer, format!("{} via {}", owner, holder_address));
+
+        let mut ungranted = signed(&holder, "/echo", "n2");
+        ungranted
+            .headers_mut()
+            .insert(CAPABILITY_HEADER, token.parse().unwrap());
+        let (status, code) = send(&app, ungranted).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(code, "CAPABILITY_DENIED");
+
+        // Presented by anyone but its holder, the token grants nothing
+        let stranger = signing_key();
+        let mut stolen = signed(&stranger, AUDIT_PATH, "n3");
+        stolen
+            .headers_mut()
+            .insert(CAPABILITY_HEADER, token.parse().unwrap());
+        let (_, code) = send(&app, stolen).await;
+        assert_eq!(code, "CAPABILITY_DENIED");
+    }
+}
@@ -4,6 +4,28 @@
  */
 
 use serde::Serialize;
+use thiserror::Error;
+
+use crate::ct;
+use crate::embedding::{self, Embedding, EmbeddingModel};
+use crate::face::FaceModel;
+
+/// Minimum match confidence for a successful verification
+pub const MATCH_THRESHOLD: f64 = 0.7;
+/// Minimum cosine similarity for a client-side embedding to match
+pub const EMBEDDING_MATCH_THRESHOLD: f64 = 0.8;
+
+#[derive(Debug, Error)]
+pub enum BiometricError {
+    #[error("Empty biometric data")]
+    EmptySample,
+    /// The model couldn't make an embedding of the sample
+    #[error("Unreadable biometric sample: {0}")]
+    Unreadable(String),
+    /// Stored features that don't decode as an embedding
+    #[error("Corrupt biometric template: {0}")]
+    CorruptTemplate(String),
+}
 
 #[derive(Serialize)]
 pub struct BiometricResult {
@@ -11,18 +33,45 @@ pub struct BiometricResult {
     pub confidence: f64,
 }
 
-pub struct BiometricService;
+pub struct BiometricService {
+    /// In-enclave face embedding model, when one is configured
+    face: Option<FaceModel>,
+}
 
 impl BiometricService {
-    pub fn new() -> Self {
-        Self
+    pub fn new(face: Option<FaceModel>) -> Self {
+        Self { face }
+    }
+
+    /// Embedding of a raw sample, for methods with an in-enclave model;
+    /// `None` means the method still uses the placeholder matcher
+    pub fn embed(
+        &self,
+        biometric_data: &[u8],
+        method: &str,
+    ) -> Result<Option<Embedding>, BiometricError> {
+        match (&self.face, method) {
+            (Some(face), "face") => face
+                .embed(biometric_data)
+                .map(Some)
+                .map_err(BiometricError::Unreadable),
+            _ => Ok(None),
+        }
+    }
+
+    /// Model that templates extracted for `method` are recorded against
+    pub fn feature_model(&self, method: &str) -> Option<EmbeddingModel> {
+        match (&self.face, method) {
+            (Some(face), "face") => Some(face.model().clone()),
+            _ => None,
+        }
     }
 
     pub async fn verify(
         &self,
         biometric_data: &[u8],
         method: &str,
-    ) -> Result<BiometricResult, String> {
+    ) -> Result<BiometricResult, BiometricError> {
         // In real implementation, this would:
         // 1. Decrypt biometric data (if encrypted)
         // 2. Extract features (fingerprint minutiae, face landmarks, voice patterns)
@@ -36,7 +85,7 @@ impl BiometricService {
         // - Voice: voiceprint analysis
         
         if biometric_data.is_empty() {
-            return Err("Empty biometric data".to_string());
+            return Err(BiometricError::EmptySample);
         }
 
         // Placeholder: Basic validation
@@ -47,7 +96,8 @@ impl BiometricService {
         // - Calculate confidence score
         
         let confidence = self.calculate_confidence(biometric_data, method);
-        let verified = confidence >= 0.7; // Threshold for verification
+        // Constant-time, so response timing doesn't leak which side of the threshold we landed
+        let verified = ct::score_at_least(confidence, MATCH_THRESHOLD);
 
         Ok(BiometricResult {
             verified,
@@ -55,6 +105,59 @@ impl BiometricService {
         })
     }
 
+    /// Privacy mode: match a client-side embedding against an enrolled one.
+    /// Both are normalized vectors from compatible models. `ceiling` caps
+    /// the score of stale templates.
+    pub fn match_embedding(
+        &self,
+        enrolled: &[f32],
+        probe: &[f32],
+        ceiling: Option<f64>,
+    ) -> BiometricResult {
+        let confidence = embedding::similarity(enrolled, probe).min(ceiling.unwrap_or(1.0));
+        BiometricResult {
+            verified: ct::score_at_least(confidence, EMBEDDING_MATCH_THRESHOLD),
+            confidence,
+        }
+    }
+
+    /// Match score of `probe` against `reference` features, as used by
+    /// verification; calibration scores labeled pairs through this
+    pub fn compare(
+        &self,
+        reference: &[u8],
+        probe: &[u8],
+        method: &str,
+    ) -> Result<f64, BiometricError> {
+        if reference.is_empty() || probe.is_empty() {
+            return Err(BiometricError::EmptySample);
+        }
+        if let Some(probe) = self.embed(probe, method)? {
+            let reference =
+                embedding::from_f32le(reference).map_err(BiometricError::CorruptTemplate)?;
+            return Ok(embedding::similarity(&reference, &probe.vector));
+        }
+        // Placeholder: verification doesn't consult the template yet either
+        Ok(self.calculate_confidence(probe, method))
+    }
+
+    /// Features stored as the enrolled template
+    pub fn extract_features(
+        &self,
+        biometric_data: &[u8],
+        method: &str,
+    ) -> Result<Vec<u8>, BiometricError> {
+        if biometric_data.is_empty() {
+            return Err(BiometricError::EmptySample);
+        }
+        if let Some(embedding) = self.embed(biometric_data, method)? {
+            return Ok(embedding::to_f32le(&embedding.vector));
+        }
+        // Placeholder: real implementation extracts minutiae/landmarks/voiceprint
+        // for `method`; until then the sample itself stands in for the template
+        Ok(biometric_data.to_vec())
+    }
+
     fn calculate_confidence(&self, data: &[u8], method: &str) -> f64 {
         // Placeholder confidence calculation
         // Real implementation would use actual biometric matching algorithms
@@ -0,0 +1,234 @@
+/**
+ * Blob Streaming
+ * Chunk-wise hashing and decryption of vault blobs
+ *
+ * Vault blobs run to hundreds of megabytes, so integrity checks and
+ * witness preparation work over fixed-size chunks instead of a whole
+ * decoded or decrypted copy: base64 is decoded a chunk at a time into a
+ * stack buffer, and digests are fed incrementally.
+ *
+ * Chunked ciphertexts follow the STREAM construction over
+ * ChaCha20-Poly1305, so each chunk is authenticated on its own and
+ * chunks can't be reordered, dropped or the stream cut short:
+ *
+ *   blob  = nonce_prefix (7 bytes) || chunk_0 || chunk_1 || ...
+ *   chunk = seal(key, nonce_prefix || counter (u32 BE) || last (0|1), aad, plaintext)
+ *
+ * Every plaintext chunk but the last is CHUNK_BYTES long.
+ */
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Nonce};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+/// Plaintext bytes per chunk of a chunked ciphertext
+pub const CHUNK_BYTES: usize = 64 * 1024;
+const TAG_BYTES: usize = 16;
+const NONCE_PREFIX_BYTES: usize = 7;
+/// Base64 characters decoded per step; whole quads, so padding can only
+/// end the last step
+const ENCODED_CHUNK: usize = 16 * 1024;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    #[default]
+    Sha256,
+    Blake3,
+}
+
+/// An incremental digest in either algorithm; both are 32 bytes
+pub enum StreamingHasher {
+    Sha256(Sha256),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl StreamingHasher {
+    pub fn new(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Sha256 => Self::Sha256(Sha256::new()),
+            HashAlgorithm::Blake3 => Self::Blake3(Box::new(blake3::Hasher::new())),
+        }
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        match self {
+            Self::Sha256(hasher) => hasher.update(bytes),
+            Self::Blake3(hasher) => {
+                hasher.update(bytes);
+            }
+        }
+    }
+
+    pub fn finalize(self) -> [u8; 32] {
+        match self {
+            Self::Sha256(hasher) => hasher.finalize().into(),
+            Self::Blake3(hasher) => hasher.finalize().into(),
+        }
+    }
+}
+
+/// Decode base64 a chunk at a time, handing each decoded chunk to `sink`
+pub fn decode_chunks(
+    encoded: &str,
+    mut sink: impl FnMut(&[u8]) -> Result<(), String>,
+) -> Result<(), String> {
+    let encoded = encoded.as_bytes();
+    let mut decoded = [0u8; ENCODED_CHUNK / 4 * 3];
+    for (i, chunk) in encoded.chunks(ENCODED_CHUNK).enumerate() {
+        let last = (i + 1) * ENCODED_CHUNK >= encoded.len();
+        if !last && chunk.last() == Some(&b'=') {
+            return Err("Invalid base64: padding before the end".to_string());
+        }
+        let len = STANDARD
+            .decode_slice(chunk, &mut decoded)
+            .map_err(|e| format!("Invalid base64: {}", e))?;
+        sink(&decoded[..len])?;
+    }
+    Ok(())
+}
+
+/// Digest of a base64 blob's decoded bytes, without decoding it whole
+pub fn hash_base64(encoded: &str, algorithm: HashAlgorithm) -> Result<[u8; 32], String> {
+    let mut hasher = StreamingHasher::new(algorithm);
+    decode_chunks(encoded, |chunk| {
+        hasher.update(chunk);
+        Ok(())
+    })?;
+    Ok(hasher.finalize())
+}
+
+/// Digest of a decoded blob, fed a chunk at a time
+pub fn hash_chunks(blob: &[u8], algorithm: HashAlgorithm) -> [u8; 32] {
+    let mut hasher = StreamingHasher::new(algorithm);
+    for chunk in blob.chunks(CHUNK_BYTES) {
+        hasher.update(chunk);
+    }
+    hasher.finalize()
+}
+
+/// Encrypt into the chunked format
+pub fn encrypt_chunked(key: &[u8; 32], plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>, String> {
+    let cipher = ChaCha20Poly1305::new(key.into());
+    let mut prefix = [0u8; NONCE_PREFIX_BYTES];
+    OsRng.fill_bytes(&mut prefix);
+
+    let chunks = plaintext.len().div_ceil(CHUNK_BYTES).max(1);
+    let mut blob = Vec::with_capacity(NONCE_PREFIX_BYTES + plaintext.len() + chunks * TAG_BYTES);
+    blob.extend_from_slice(&prefix);
+    for index in 0..chunks {
+        let start = index * CHUNK_BYTES;
+        let end = (start + CHUNK_BYTES).min(plaintext.len());
+        let nonce = chunk_nonce(&prefix, index, index + 1 == chunks)?;
+        let sealed = cipher
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &plaintext[start..end],
+                    aad,
+                },
+            )
+            .map_err(|_| "Encryption failed".to_string())?;
+        blob.extend_from_slice(&sealed);
+    }
+    Ok(blob)
+}
+
+/// Decrypt a chunked ciphertext, handing each authenticated plaintext
+/// chunk to `sink`; only one chunk is decrypted in memory at a time.
+/// A failure after some chunks were handed over means the blob as a whole
+/// is invalid, so callers must discard whatever they derived from them.
+pub fn decrypt_chunked(
+    key: &[u8; 32],
+    blob: &[u8],
+    aad: &[u8],
+    mut sink: impl FnMut(&[u8]) -> Result<(), String>,
+) -> Result<(), String> {
+    if blob.len() < NONCE_PREFIX_BYTES + TAG_BYTES {
+        return Err("Chunked ciphertext is truncated".to_string());
+    }
+    let cipher = ChaCha20Poly1305::new(key.into());
+    let (prefix, body) = blob.split_at(NONCE_PREFIX_BYTES);
+    let chunks: Vec<&[u8]> = body.chunks(CHUNK_BYTES + TAG_BYTES).collect();
+    for (index, chunk) in chunks.iter().enumerate() {
+        let nonce = chunk_nonce(prefix, index, index + 1 == chunks.len())?;
+        let plaintext = Zeroizing::new(
+            cipher
+                .decrypt(Nonce::from_slice(&nonce), Payload { msg: chunk, aad })
+                .map_err(|_| format!("Chunk {} failed authentication", index))?,
+        );
+        sink(&plaintext)?;
+    }
+    Ok(())
+}
+
+fn chunk_nonce(prefix: &[u8], index: usize, last: bool) -> Result<[u8; 12], String> {
+    let counter = u32::try_from(index).map_err(|_| "Blob has too many chunks".to_string())?;
+    let mut nonce = [0u8; 12];
+    nonce[..NONCE_PREFIX_BYTES].copy_from_slice(prefix);
+    nonce[NONCE_PREFIX_BYTES..11].copy_from_slice(&counter.to_be_bytes());
+    nonce[11] = last as u8;
+    Ok(nonce)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 7 % 253) as u8).collect()
+    }
+
+    #[test]
+    fn streamed_digests_match_one_shot() {
+        for len in [0, 1, 12_288, 12_289, 200_000] {
+            let data = sample(len);
+            let encoded = STANDARD.encode(&data);
+            let sha256: [u8; 32] = Sha256::digest(&data).into();
+            let blake3: [u8; 32] = blake3::hash(&data).into();
+            assert_eq!(
+                hash_base64(&encoded, HashAlgorithm::Sha256).unwrap(),
+                sha256
+            );
+            assert_eq!(
+                hash_base64(&encoded, HashAlgorithm::Blake3).unwrap(),
+                blake3
+            );
+            assert_eq!(hash_chunks(&data, HashAlgorithm::Sha256), sha256);
+            assert_eq!(hash_chunks(&data, HashAlgorithm::Blake3), blake3);
+        }
+    }
+
+    #[test]
+    fn chunked_ciphertext_round_trips() {
+        let key = [9u8; 32];
+        for len in [0, 1, CHUNK_BYTES, CHUNK_BYTES + 1, 3 * CHUNK_BYTES - 5] {
+            let data = sample(len);
+            let blob = encrypt_chunked(&key, &data, b"vault").unwrap();
+            let mut opened = Vec::new();
+            decrypt_chunked(&key, &blob, b"vault", |chunk| {
+                opened.extend_from_slice(chunk);
+                Ok(())
+            })
+            .unwrap();
+            assert_eq!(opened, data);
+        }
+    }
+
+    #[test]
+    fn chunked_ciphertext_rejects_truncation() {
+        let key = [9u8; 32];
+        let blob = encrypt_chunked(&key, &sample(2 * CHUNK_BYTES + 10), b"").unwrap();
+        // Dropping the final chunk leaves a stream whose last chunk was
+        // sealed as non-final
+        let cut = &blob[..NONCE_PREFIX_BYTES + 2 * (CHUNK_BYTES + TAG_BYTES)];
+        assert!(decrypt_chunked(&key, cut, b"", |_| Ok(())).is_err());
+        assert!(decrypt_chunked(&key, &blob, b"other", |_| Ok(())).is_err());
+    }
+}
@@ -0,0 +1,180 @@
+/**
+ * Secrets Bootstrap
+ * Receives the KMS-wrapped secrets bundle from the parent instance at startup
+ *
+ * 1. The enclave generates an ephemeral RSA-2048 key and an attestation
+ *    binding its public key (DER SubjectPublicKeyInfo), whose NSM document
+ *    carries that key as its public_key.
+ * 2. The parent calls KMS Decrypt on the bundle's encrypted data key with
+ *    Recipient = { attestation_document, RSAES_OAEP_SHA_256 }. KMS checks
+ *    the NSM document against the key policy and, instead of the plaintext,
+ *    returns CiphertextForRecipient: the data key in a CMS envelope only
+ *    the attested key opens (see cms).
+ * 3. The parent returns that envelope and the encrypted bundle, and the
+ *    enclave opens both. Plaintext secrets never exist outside it.
+ *
+ * Messages are single JSON lines over VSOCK (TCP in development). The
+ * exchange is retried under the KMS retry policy (see retry), since the
+ * parent's relay may still be starting when the enclave boots.
+ */
+
+use rsa::pkcs8::EncodePublicKey;
+use rsa::RsaPrivateKey;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tracing::info;
+
+use crate::attestation::{Attestation, AttestationService};
+use crate::config::{BootstrapSource, SecretsConfig};
+use crate::retry::{self, Dependency, Failure, FailureKind};
+use crate::{cms, crypto};
+
+const BOOTSTRAP_TIMEOUT: Duration = Duration::from_secs(30);
+/// KMS only wraps to RSA keys of 2048 bits or more
+const RECIPIENT_KEY_BITS: usize = 2048;
+const BUNDLE_AAD: &[u8] = b"lumina-bootstrap-bundle";
+
+/// Parent instance CID as seen from inside a Nitro Enclave
+#[cfg(feature = "vsock")]
+pub const PARENT_CID: u32 = 3;
+
+#[derive(Serialize)]
+struct BootstrapRequest {
+    attestation: Attestation,
+    /// Base64 NSM document for KMS's Recipient.AttestationDocument
+    attestation_document: String,
+}
+
+#[derive(Deserialize)]
+struct BootstrapResponse {
+    /// Base64 CiphertextForRecipient holding the bundle's data key
+    ciphertext_for_recipient: String,
+    nonce: String,      // Base64 bundle nonce
+    ciphertext: String, // Base64 bundle, encrypted under the data key
+}
+
+pub async fn fetch_secrets(
+    source: &BootstrapSource,
+    attestation: &AttestationService,
+) -> Result<SecretsConfig, String> {
+    let secret = RsaPrivateKey::new(&mut rand::rngs::OsRng, RECIPIENT_KEY_BITS)
+        .map_err(|e| format!("Failed to generate the recipient key: {}", e))?;
+    let public_key = secret
+        .to_public_key()
+        .to_public_key_der()
+        .map_err(|e| format!("Failed to encode the recipient key: {}", e))?;
+    let attestation = attestation
+        .generate_for_key("secrets_bootstrap", public_key.as_bytes())
+        .await
+        .map_err(|e| e.to_string())?;
+    let request = BootstrapRequest {
+        attestation_document: attestation.signature.clone(),
+        attestation,
+    };
+
+    let request = &request;
+    let response = retry::run(Dependency::Kms, move || async move {
+        tokio::time::timeout(BOOTSTRAP_TIMEOUT, request_bundle(source, request))
+            .await
+            .map_err(|_| {
+                Failure::new(FailureKind::Timeout, "Timed out waiting for secrets bundle")
+            })?
+    })
+    .await?;
+
+    let data_key = cms::open_recipient_ciphertext(
+        &secret,
+        &crypto::decode(&response.ciphertext_for_recipient)?,
+    )?;
+    let data_key: &[u8; 32] = data_key
+        .as_slice()
+        .try_into()
+        .map_err(|_| "Bundle data key must be 32 bytes".to_string())?;
+    let bundle = crypto::aead_decrypt(
+        data_key,
+        &crypto::decode(&response.nonce)?,
+        &crypto::decode(&response.ciphertext)?,
+        BUNDLE_AAD,
+    )?;
+
+    let secrets: SecretsConfig =
+        serde_json::from_slice(&bundle).map_err(|e| format!("Malformed secrets bundle: {}", e))?;
+    info!("Bootstrap delivered secrets: {:?}", secrets.loaded());
+    Ok(secrets)
+}
+
+async fn request_bundle(
+    source: &BootstrapSource,
+    request: &BootstrapRequest,
+) -> Result<BootstrapResponse, Failure> {
+    let unreachable = |message: String| Failure::new(FailureKind::Unreachable, message);
+    match source {
+        BootstrapSource::Tcp(addr) => {
+            let stream = tokio::net::TcpStream::connect(addr).await.map_err(|e| {
+                unreachable(format!(
+                    "Failed to reach bootstrap relay at {}: {}",
+                    addr, e
+                ))
+            })?;
+            exchange(stream, request).await
+        }
+        #[cfg(feature = "vsock")]
+        BootstrapSource::Vsock { port } => {
+            let stream =
+                tokio_vsock::VsockStream::connect(tokio_vsock::VsockAddr::new(PARENT_CID, *port))
+                    .await
+                    .map_err(|e| {
+                        unreachable(format!(
+                            "Failed to reach bootstrap relay on vsock port {}: {}",
+                            port, e
+                        ))
+                    })?;
+            exchange(stream, request).await
+        }
+        #[cfg(not(feature = "vsock"))]
+        BootstrapSource::Vsock { port } => Err(unreachable(format!(
+            "VSOCK bootstrap (port {}) requires building with the `vsock` feature",
+            port
+        ))),
+    }
+}
+
+async fn exchange<S>(stream: S, request: &BootstrapRequest) -> Result<BootstrapResponse, Failure>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let transport = |message: String| Failure::new(FailureKind::Transport, message);
+    let mut stream = BufReader::new(stream);
+    let mut line = serde_json::to_vec(request).map_err(|e| {
+        Failure::new(
+            FailureKind::Malformed,
+            format!("Failed to encode request: {}", e),
+        )
+    })?;
+    line.push(b'\n');
+
+    stream
+        .get_mut()
+        .write_all(&line)
+        .await
+        .map_err(|e| transport(format!("Failed to send bootstrap request: {}", e)))?;
+
+    let mut response = String::new();
+    let read = stream
+        .read_line(&mut response)
+        .await
+        .map_err(|e| transport(format!("Failed to read bootstrap response: {}", e)))?;
+    if read == 0 {
+        return Err(transport(
+            "Bootstrap relay closed the connection".to_string(),
+        ));
+    }
+
+    serde_json::from_str(&response).map_err(|e| {
+        Failure::new(
+            FailureKind::Malformed,
+            format!("Malformed bootstrap response: {}", e),
+        )
+    })
+}
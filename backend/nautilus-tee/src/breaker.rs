@@ -0,0 +1,263 @@
+/**
+ * Circuit Breakers
+ * Failing fast on a dependency that keeps failing
+ *
+ * Each dependency (see retry) has a breaker. It trips open after
+ * `threshold` consecutive attempts fail to get an answer (unreachable,
+ * timed out, cut off); an answer, even an error, counts as the dependency
+ * being up. While open, calls fail at once instead of waiting out their
+ * timeouts, and callers fall back on what they have cached, flagging the
+ * result as degraded. After `cooldown` one call is let through as a probe:
+ * success closes the breaker, failure opens it for another cooldown.
+ *
+ * Breakers are overridden with TEE_CIRCUIT_BREAKERS entries of the form
+ * `dependency=threshold/cooldown_secs`, e.g. "chain=3/60". Their state is
+ * public at GET /health/detail.
+ */
+
+use serde::Serialize;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+use crate::retry::{Dependency, Failure, FailureKind};
+
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct BreakerConfig {
+    pub threshold: u32,
+    pub cooldown_secs: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    Closed,
+    Open,
+    /// Cooled down; the next call is a probe
+    HalfOpen,
+}
+
+#[derive(Serialize)]
+pub struct BreakerStatus {
+    pub dependency: Dependency,
+    pub state: BreakerState,
+    pub config: BreakerConfig,
+    pub consecutive_failures: u32,
+    /// Times the breaker has opened since startup
+    pub trips: u64,
+    /// Calls failed fast while open
+    pub rejected: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub opened_at: Option<u64>,
+}
+
+struct Breaker {
+    consecutive_failures: u32,
+    opened: Option<(Instant, u64)>,
+    probe_started: Option<Instant>,
+    trips: u64,
+    rejected: u64,
+}
+
+impl Breaker {
+    const fn new() -> Self {
+        Self {
+            consecutive_failures: 0,
+            opened: None,
+            probe_started: None,
+            trips: 0,
+            rejected: 0,
+        }
+    }
+
+    fn state(&self, cooldown: Duration) -> BreakerState {
+        match self.opened {
+            None => BreakerState::Closed,
+            Some((at, _)) if at.elapsed() < cooldown => BreakerState::Open,
+            Some(_) => BreakerState::HalfOpen,
+        }
+    }
+}
+
+static CONFIGS: OnceLock<Vec<BreakerConfig>> = OnceLock::new();
+static BREAKERS: [Mutex<Breaker>; Dependency::ALL.len()] = [
+    Mutex::new(Breaker::new()),
+    Mutex::new(Breaker::new()),
+    Mutex::new(Breaker::new()),
+    Mutex::new(Breaker::new()),
+];
+
+fn default_config(dependency: Dependency) -> BreakerConfig {
+    match dependency {
+        Dependency::Chain => BreakerConfig {
+            threshold: 5,
+            cooldown_secs: 30,
+        },
+        // Only called at startup, where there is nothing to fall back on
+        Dependency::Kms => BreakerConfig {
+            threshold: 10,
+            cooldown_secs: 10,
+        },
+        Dependency::Notifications => BreakerConfig {
+            threshold: 5,
+            cooldown_secs: 60,
+        },
+        Dependency::Archive => BreakerConfig {
+            threshold: 5,
+            cooldown_secs: 300,
+        },
+    }
+}
+
+/// Install breaker overrides; call once, before any call is made
+pub fn configure(overrides: &[String]) -> Result<(), String> {
+    let mut configs: Vec<BreakerConfig> =
+        Dependency::ALL.iter().map(|&d| default_config(d)).collect();
+    for entry in overrides {
+        let invalid = || format!("Invalid TEE_CIRCUIT_BREAKERS entry: {}", entry);
+        let (name, spec) = entry.split_once('=').ok_or_else(invalid)?;
+        let dependency = Dependency::from_name(name.trim())
+            .ok_or_else(|| format!("Unknown circuit breaker dependency: {}", name.trim()))?;
+        let (threshold, cooldown) = spec.split_once('/').ok_or_else(invalid)?;
+        let config = BreakerConfig {
+            threshold: threshold.trim().parse().map_err(|_| invalid())?,
+            cooldown_secs: cooldown.trim().parse().map_err(|_| invalid())?,
+        };
+        if config.threshold == 0 {
+            return Err(invalid());
+        }
+        configs[dependency.index()] = config;
+    }
+    CONFIGS
+        .set(configs)
+        .map_err(|_| "Circuit breakers are already configured".to_string())
+}
+
+fn config(dependency: Dependency) -> BreakerConfig {
+    match CONFIGS.get() {
+        Some(configs) => configs[dependency.index()],
+        None => default_config(dependency),
+    }
+}
+
+/// Whether an attempt may go ahead; Err while the breaker is open, or
+/// half open with a probe already out
+pub fn admit(dependency: Dependency) -> Result<(), Failure> {
+    let cooldown = Duration::from_secs(config(dependency).cooldown_secs);
+    let mut breaker = BREAKERS[dependency.index()].lock().unwrap();
+    let probing = breaker
+        .probe_started
+        .is_some_and(|started| started.elapsed() < cooldown);
+    match breaker.state(cooldown) {
+        BreakerState::Closed => Ok(()),
+        BreakerState::HalfOpen if !probing => {
+            breaker.probe_started = Some(Instant::now());
+            Ok(())
+        }
+        BreakerState::Open | BreakerState::HalfOpen => {
+            breaker.rejected += 1;
+            Err(Failure::new(
+                FailureKind::CircuitOpen,
+                format!("{} circuit breaker is open", dependency.name()),
+            ))
+        }
+    }
+}
+
+/// Record an attempt's outcome; None for success
+pub fn record(dependency: Dependency, failure: Option<FailureKind>) {
+    let config = config(dependency);
+    let mut breaker = BREAKERS[dependency.index()].lock().unwrap();
+    let down = failure.is_some_and(|kind| {
+        matches!(
+            kind,
+            FailureKind::Unreachable | FailureKind::Timeout | FailureKind::Transport
+        )
+    });
+    if !down {
+        if breaker.opened.take().is_some() {
+            info!("{} circuit breaker closed", dependency.name());
+        }
+        breaker.consecutive_failures = 0;
+        breaker.probe_started = None;
+        return;
+    }
+
+    breaker.consecutive_failures += 1;
+    let probe_failed = breaker.probe_started.take().is_some();
+    if probe_failed
+        || (breaker.opened.is_none() && breaker.consecutive_failures >= config.threshold)
+    {
+        breaker.opened = Some((Instant::now(), now()));
+        breaker.trips += 1;
+        warn!(
+            "{} circuit breaker open for {}s after {} consecutive failures",
+            dependency.name(),
+            config.cooldown_secs,
+            breaker.consecutive_failures
+        );
+    }
+}
+
+pub fn state(dependency: Dependency) -> BreakerState {
+    let cooldown = Duration::from_secs(config(dependency).cooldown_secs);
+    BREAKERS[dependency.index()].lock().unwrap().state(cooldown)
+}
+
+pub fn statuses() -> Vec<BreakerStatus> {
+    Dependency::ALL
+        .iter()
+        .map(|&dependency| {
+            let config = config(dependency);
+            let breaker = BREAKERS[dependency.index()].lock().unwrap();
+            BreakerStatus {
+                dependency,
+                state: breaker.state(Duration::from_secs(config.cooldown_secs)),
+                config,
+                consecutive_failures: breaker.consecutive_failures,
+                trips: breaker.trips,
+                rejected: breaker.rejected,
+                opened_at: breaker.opened.map(|(_, at)| at),
+            }
+        })
+        .collect()
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trips_after_threshold_and_probes_after_cooldown() {
+        // Notifications aren't called from other tests
+        let dependency = Dependency::Notifications;
+        let threshold = default_config(dependency).threshold;
+        for _ in 0..threshold - 1 {
+            assert!(admit(dependency).is_ok());
+            record(dependency, Some(FailureKind::Unreachable));
+        }
+        // An answer, even an error, means the dependency is up
+        record(dependency, Some(FailureKind::Remote));
+        for _ in 0..threshold {
+            record(dependency, Some(FailureKind::Timeout));
+        }
+        assert_eq!(state(dependency), BreakerState::Open);
+        assert!(admit(dependency).is_err());
+
+        // Past the cooldown, one probe goes through and its success closes
+        BREAKERS[dependency.index()].lock().unwrap().opened =
+            Some((Instant::now() - Duration::from_secs(61), now()));
+        assert!(admit(dependency).is_ok());
+        assert!(admit(dependency).is_err());
+        record(dependency, None);
+        assert_eq!(state(dependency), BreakerState::Closed);
+        assert!(admit(dependency).is_ok());
+    }
+}
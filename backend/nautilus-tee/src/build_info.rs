@@ -0,0 +1,35 @@
+/**
+ * Build Info
+ * What this binary was built from, embedded by build.rs
+ *
+ * Verifiers map an attested PCR0 back to a source revision by rebuilding
+ * it; these values say which revision, toolchain and features to use.
+ */
+
+use serde::Serialize;
+
+#[derive(Clone, Serialize)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    pub git_commit: &'static str,
+    /// Built with uncommitted changes; such a build can't be reproduced
+    pub git_dirty: bool,
+    /// SOURCE_DATE_EPOCH at build time, in Unix seconds
+    pub build_timestamp: u64,
+    pub rustc: &'static str,
+    pub features: Vec<&'static str>,
+}
+
+pub fn build_info() -> BuildInfo {
+    BuildInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        git_commit: env!("LUMINA_GIT_COMMIT"),
+        git_dirty: env!("LUMINA_GIT_DIRTY") == "true",
+        build_timestamp: env!("LUMINA_BUILD_TIMESTAMP").parse().unwrap_or(0),
+        rustc: env!("LUMINA_RUSTC"),
+        features: env!("LUMINA_FEATURES")
+            .split(',')
+            .filter(|f| !f.is_empty())
+            .collect(),
+    }
+}
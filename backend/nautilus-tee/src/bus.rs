@@ -0,0 +1,392 @@
+/**
+ * Event Bus
+ * Typed events published once and consumed by each subsystem on its own
+ *
+ * Producers (check-ins, the reminder, escalation and template sweeps,
+ * proof generation) publish a BusEvent without knowing who listens. Each
+ * event is appended to a journal in the store, then broadcast.
+ *
+ * Subsystems that must not miss an event consume durably: each keeps a
+ * cursor in the store, catches up from the journal at startup and
+ * whenever the broadcast wakes it, and advances its cursor only once it
+ * has handled an event. They see every event at least once, in order; a
+ * consumer that fails is retried from its cursor after RETRY_AFTER. Live
+ * followers (server-sent events, in-memory counters) subscribe to the
+ * broadcast instead and may miss events they fall behind on.
+ *
+ * The journal is split into segments of SEGMENT_EVENTS events, each its
+ * own store namespace, so publishing rewrites only the newest segment.
+ * Starting a segment drops those beyond the last MAX_SEGMENTS; a consumer
+ * further behind than that skips ahead and logs how many it missed.
+ */
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::warn;
+
+use crate::check_in::CheckIn;
+use crate::escalation::StageAction;
+use crate::store::{Batch, Store};
+
+/// Segment namespaces are this followed by the zero-padded segment number
+const SEGMENT_PREFIX: &str = "bus_journal.";
+/// The single namespace the journal was kept in before it was segmented
+const LEGACY_JOURNAL_NAMESPACE: &str = "bus_journal";
+const CURSORS_NAMESPACE: &str = "bus_cursors";
+const SEGMENT_EVENTS: u64 = 500;
+const MAX_SEGMENTS: u64 = 20;
+const CATCH_UP_BATCH: usize = 200;
+/// Followers that fall this far behind miss events rather than stall
+/// publishers
+const LIVE_CAPACITY: usize = 256;
+const RETRY_AFTER: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum BusEvent {
+    #[serde(rename = "check_in.reminder")]
+    CheckInReminder {
+        vault_id: String,
+        owner: String,
+        days_before: u32,
+        expires_at: u64,
+    },
+    #[serde(rename = "check_in.recorded")]
+    CheckedIn(CheckIn),
+    #[serde(rename = "escalation.stage")]
+    EscalationStage {
+        vault_id: String,
+        stage: usize,
+        action: StageAction,
+        entered_at: u64,
+        /// The missed check-in deadline that started the ladder
+        missed_deadline: u64,
+    },
+    #[serde(rename = "template.expiring")]
+    TemplateExpiring(TemplateNotice),
+    #[serde(rename = "template.expired")]
+    TemplateExpired(TemplateNotice),
+    #[serde(rename = "proof.generated")]
+    ProofGenerated { claim_type: String },
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TemplateNotice {
+    pub vault_id: String,
+    pub owner: String,
+    pub method: String,
+    pub template_id: String,
+    pub label: Option<String>,
+    pub expires_at: u64,
+    pub stale_confidence_cap: Option<f64>,
+}
+
+impl BusEvent {
+    /// Matches the serialized `kind`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            BusEvent::CheckInReminder { .. } => "check_in.reminder",
+            BusEvent::CheckedIn(_) => "check_in.recorded",
+            BusEvent::EscalationStage { .. } => "escalation.stage",
+            BusEvent::TemplateExpiring(_) => "template.expiring",
+            BusEvent::TemplateExpired(_) => "template.expired",
+            BusEvent::ProofGenerated { .. } => "proof.generated",
+        }
+    }
+
+    /// None for events about no one vault
+    pub fn vault_id(&self) -> Option<&str> {
+        match self {
+            BusEvent::CheckInReminder { vault_id, .. }
+            | BusEvent::EscalationStage { vault_id, .. } => Some(vault_id),
+            BusEvent::CheckedIn(check_in) => Some(&check_in.vault_id),
+            BusEvent::TemplateExpiring(notice) | BusEvent::TemplateExpired(notice) => {
+                Some(&notice.vault_id)
+            }
+            BusEvent::ProofGenerated { .. } => None,
+        }
+    }
+
+    /// The event's fields besides its kind and vault
+    pub fn data(&self) -> Value {
+        let mut data = serde_json::to_value(self).unwrap_or_default();
+        if let Some(fields) = data.as_object_mut() {
+            fields.remove("kind");
+            fields.remove("vault_id");
+        }
+        data
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Envelope {
+    /// Increasing; consumer cursors refer to it
+    pub seq: u64,
+    pub published_at: u64,
+    pub event: BusEvent,
+}
+
+pub struct EventBus {
+    store: Arc<Store>,
+    live: broadcast::Sender<Envelope>,
+    next_seq: Mutex<u64>,
+}
+
+impl EventBus {
+    pub fn new(store: Arc<Store>) -> Result<Self, String> {
+        migrate_legacy_journal(&store)?;
+        let last = match store.namespaces(SEGMENT_PREFIX)?.last() {
+            Some(segment) => store
+                .list::<Envelope>(segment)?
+                .last()
+                .map(|(_, envelope)| envelope.seq)
+                .unwrap_or(0),
+            None => 0,
+        };
+        Ok(Self {
+            store,
+            live: broadcast::channel(LIVE_CAPACITY).0,
+            next_seq: Mutex::new(last + 1),
+        })
+    }
+
+    /// Journal the event, then broadcast it
+    pub fn publish(&self, event: BusEvent) -> Result<Envelope, String> {
+        // Held across the write so the journal is in seq order; the write
+        // is of one segment, so it stays short
+        let mut next_seq = self.next_seq.lock().unwrap();
+        let envelope = Envelope {
+            seq: *next_seq,
+            published_at: now(),
+            event,
+        };
+        let value = serde_json::to_value(&envelope)
+            .map_err(|e| format!("Failed to serialize {}: {}", envelope.event.kind(), e))?;
+        let segment = envelope.seq / SEGMENT_EVENTS;
+        self.store.update(&segment_namespace(segment), |ns| {
+            ns.insert(journal_key(envelope.seq), value);
+        })?;
+        *next_seq += 1;
+        if envelope.seq % SEGMENT_EVENTS == 0 {
+            self.prune(segment)?;
+        }
+        let _ = self.live.send(envelope.clone());
+        Ok(envelope)
+    }
+
+    /// Events as they are published, for followers that can miss some
+    pub fn subscribe(&self) -> broadcast::Receiver<Envelope> {
+        self.live.subscribe()
+    }
+
+    /// Hand every event to `handle`, at least once and in order, under a
+    /// cursor kept as `name`. Spawns the consumer; call from the runtime.
+    pub fn consume<F>(self: &Arc<Self>, name: &'static str, handle: F)
+    where
+        F: Fn(&BusEvent) -> Result<(), String> + Send + 'static,
+    {
+        let bus = self.clone();
+        // Subscribed before catching up, so nothing falls in between
+        let mut live = bus.subscribe();
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = bus.catch_up(name, &handle) {
+                    warn!("Bus consumer {} failed: {}", name, e);
+                    tokio::time::sleep(RETRY_AFTER).await;
+                    continue;
+                }
+                // What woke us is read back from the journal
+                match live.recv().await {
+                    Ok(_) | Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => return,
+                }
+            }
+        });
+    }
+
+    fn catch_up<F>(&self, name: &str, handle: &F) -> Result<(), String>
+    where
+        F: Fn(&BusEvent) -> Result<(), String>,
+    {
+        let mut cursor: u64 = self.store.get(CURSORS_NAMESPACE, name)?.unwrap_or(0);
+        loop {
+            let page = self.after(cursor, CATCH_UP_BATCH)?;
+            let Some(first) = page.first() else {
+                return Ok(());
+            };
+            if first.seq > cursor + 1 {
+                warn!(
+                    "Bus consumer {} missed {} events that left the journal",
+                    name,
+                    first.seq - cursor - 1
+                );
+            }
+            for envelope in page {
+                handle(&envelope.event)?;
+                cursor = envelope.seq;
+                self.store.put(CURSORS_NAMESPACE, name, &cursor)?;
+            }
+        }
+    }
+
+    /// Up to `limit` journaled events after `seq`, oldest first
+    fn after(&self, seq: u64, limit: usize) -> Result<Vec<Envelope>, String> {
+        let first = segment_namespace((seq + 1) / SEGMENT_EVENTS);
+        let mut page = Vec::new();
+        for segment in self.store.namespaces(SEGMENT_PREFIX)? {
+            if segment < first {
+                continue;
+            }
+            let wanted = limit - page.len();
+            page.extend(
+                self.store
+                    .list::<Envelope>(&segment)?
+                    .into_iter()
+                    .map(|(_, envelope)| envelope)
+                    .filter(|envelope| envelope.seq > seq)
+                    .take(wanted),
+            );
+            if page.len() == limit {
+                break;
+            }
+        }
+        Ok(page)
+    }
+
+    /// Drop the segments that fall out of the journal once `newest` starts
+    fn prune(&self, newest: u64) -> Result<(), String> {
+        let Some(oldest) = newest.checked_sub(MAX_SEGMENTS - 1) else {
+            return Ok(());
+        };
+        let oldest = segment_namespace(oldest);
+        for segment in self.store.namespaces(SEGMENT_PREFIX)? {
+            if segment < oldest {
+                self.store.drop_namespace(&segment)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Move a journal kept in one namespace into segments, once
+fn migrate_legacy_journal(store: &Store) -> Result<(), String> {
+    let legacy = store.list::<Value>(LEGACY_JOURNAL_NAMESPACE)?;
+    if legacy.is_empty() {
+        return Ok(());
+    }
+    let mut batch = Batch::new();
+    for (key, value) in &legacy {
+        let seq: u64 = key
+            .parse()
+            .map_err(|_| format!("Corrupt {}/{}", LEGACY_JOURNAL_NAMESPACE, key))?;
+        batch.put(&segment_namespace(seq / SEGMENT_EVENTS), key, value)?;
+        batch.remove(LEGACY_JOURNAL_NAMESPACE, key);
+    }
+    store.commit(batch)?;
+    store.drop_namespace(LEGACY_JOURNAL_NAMESPACE)
+}
+
+/// Zero-padded so the store's key order is seq order
+fn journal_key(seq: u64) -> String {
+    format!("{:020}", seq)
+}
+
+/// Zero-padded so namespace order is segment order
+fn segment_namespace(segment: u64) -> String {
+    format!("{}{:016}", SEGMENT_PREFIX, segment)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bus(name: &str) -> (Arc<Store>, EventBus) {
+        let dir = std::env::temp_dir().join(format!("lumina-bus-tests-{}-{}", name, now()));
+        let store = Arc::new(Store::open(&dir).unwrap());
+        (store.clone(), EventBus::new(store).unwrap())
+    }
+
+    fn event(n: u64) -> BusEvent {
+        BusEvent::ProofGenerated {
+            claim_type: n.to_string(),
+        }
+    }
+
+    fn number(event: &BusEvent) -> u64 {
+        match event {
+            BusEvent::ProofGenerated { claim_type } => claim_type.parse().unwrap(),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn consumers_resume_from_their_cursor_and_see_every_event() {
+        let (store, bus) = bus("resume");
+        // Enough to span segments
+        let count = SEGMENT_EVENTS + 100;
+        for n in 1..=count {
+            bus.publish(event(n)).unwrap();
+        }
+
+        // A handler that fails leaves the cursor on the last event it handled
+        let seen = Mutex::new(Vec::new());
+        let failing = |event: &BusEvent| {
+            seen.lock().unwrap().push(number(event));
+            if number(event) == 300 {
+                return Err("unavailable".to_string());
+            }
+            Ok(())
+        };
+        assert!(bus.catch_up("test", &failing).is_err());
+        let cursor: Option<u64> = store.get(CURSORS_NAMESPACE, "test").unwrap();
+        assert_eq!(cursor, Some(299));
+
+        // The retry hands the failed event over again and skips nothing
+        let handle = |event: &BusEvent| {
+            seen.lock().unwrap().push(number(event));
+            Ok(())
+        };
+        bus.catch_up("test", &handle).unwrap();
+        let expected: Vec<u64> = (1..=300).chain(300..=count).collect();
+        assert_eq!(*seen.lock().unwrap(), expected);
+
+        // After a restart the sequence continues and only new events arrive
+        let bus = EventBus::new(store).unwrap();
+        assert_eq!(bus.publish(event(count + 1)).unwrap().seq, count + 1);
+        seen.lock().unwrap().clear();
+        bus.catch_up("test", &handle).unwrap();
+        assert_eq!(*seen.lock().unwrap(), vec![count + 1]);
+    }
+
+    #[test]
+    fn consumers_behind_the_journal_skip_ahead() {
+        let (store, bus) = bus("prune");
+        for n in 1..=SEGMENT_EVENTS + 10 {
+            bus.publish(event(n)).unwrap();
+        }
+        assert_eq!(store.namespaces(SEGMENT_PREFIX).unwrap().len(), 2);
+
+        // Once segment MAX_SEGMENTS starts, segment 0 is out of the journal
+        bus.prune(MAX_SEGMENTS).unwrap();
+        assert_eq!(store.namespaces(SEGMENT_PREFIX).unwrap().len(), 1);
+
+        let seen = Mutex::new(Vec::new());
+        bus.catch_up("late", &|event: &BusEvent| {
+            seen.lock().unwrap().push(number(event));
+            Ok(())
+        })
+        .unwrap();
+        let expected: Vec<u64> = (SEGMENT_EVENTS..=SEGMENT_EVENTS + 10).collect();
+        assert_eq!(*seen.lock().unwrap(), expected);
+    }
+}
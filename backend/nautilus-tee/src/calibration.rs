@@ -0,0 +1,183 @@
+/**
+ * Biometric Calibration
+ * FAR/FRR curves from labeled comparisons, and the thresholds they suggest
+ *
+ * Admins submit sets of reference/probe pairs labeled genuine or impostor
+ * for one method and matcher. Every pair is scored by the same matcher
+ * verification uses, and the scores are swept across thresholds:
+ *
+ *   FAR(t) = impostor pairs scoring >= t / impostor pairs
+ *   FRR(t) = genuine pairs scoring < t  / genuine pairs
+ *
+ * The recommended threshold is the lowest one whose FAR is within the
+ * target. Ingesting samples is development-only, since labeled sets are
+ * real biometrics; the stored reports are readable in any environment.
+ */
+
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::store::Store;
+
+const NAMESPACE: &str = "calibration_reports";
+/// Sweep resolution: thresholds 0.00, 0.01, ..., 1.00
+const CURVE_STEPS: u32 = 100;
+const MIN_PAIRS_PER_LABEL: usize = 10;
+pub const DEFAULT_TARGET_FAR: f64 = 0.01;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Matcher {
+    /// Raw samples, features extracted in the enclave
+    Sample,
+    /// Client-side embeddings
+    Embedding,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CurvePoint {
+    pub threshold: f64,
+    pub far: f64,
+    pub frr: f64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CalibrationReport {
+    pub method: String,
+    pub matcher: Matcher,
+    pub genuine_pairs: usize,
+    pub impostor_pairs: usize,
+    pub target_far: f64,
+    /// Lowest threshold with FAR <= target_far
+    pub recommended_threshold: f64,
+    pub far_at_recommended: f64,
+    pub frr_at_recommended: f64,
+    /// Threshold currently compiled in, for comparison
+    pub current_threshold: f64,
+    pub equal_error_rate: f64,
+    pub curve: Vec<CurvePoint>,
+    pub calibrated_by: String,
+    pub calibrated_at: u64,
+}
+
+pub struct CalibrationService {
+    store: Arc<Store>,
+    /// Whether labeled samples may be ingested (development only)
+    ingest_enabled: bool,
+}
+
+impl CalibrationService {
+    pub fn new(store: Arc<Store>, ingest_enabled: bool) -> Self {
+        Self {
+            store,
+            ingest_enabled,
+        }
+    }
+
+    pub fn ingest_enabled(&self) -> bool {
+        self.ingest_enabled
+    }
+
+    /// Build and store the report for `scores`, as (score, genuine) pairs
+    pub fn calibrate(
+        &self,
+        method: &str,
+        matcher: Matcher,
+        scores: &[(f64, bool)],
+        target_far: f64,
+        current_threshold: f64,
+        admin: &str,
+    ) -> Result<CalibrationReport, String> {
+        if !self.ingest_enabled {
+            return Err("Calibration ingest is only available in development".to_string());
+        }
+        if !(0.0..1.0).contains(&target_far) {
+            return Err("target_far must be in [0, 1)".to_string());
+        }
+
+        let genuine: Vec<f64> = scores.iter().filter(|s| s.1).map(|s| s.0).collect();
+        let impostor: Vec<f64> = scores.iter().filter(|s| !s.1).map(|s| s.0).collect();
+        if genuine.len() < MIN_PAIRS_PER_LABEL || impostor.len() < MIN_PAIRS_PER_LABEL {
+            return Err(format!(
+                "Need at least {} genuine and {} impostor pairs, got {} and {}",
+                MIN_PAIRS_PER_LABEL,
+                MIN_PAIRS_PER_LABEL,
+                genuine.len(),
+                impostor.len()
+            ));
+        }
+
+        let curve: Vec<CurvePoint> = (0..=CURVE_STEPS)
+            .map(|step| {
+                let threshold = f64::from(step) / f64::from(CURVE_STEPS);
+                CurvePoint {
+                    threshold,
+                    far: rate(&impostor, |s| s >= threshold),
+                    frr: rate(&genuine, |s| s < threshold),
+                }
+            })
+            .collect();
+
+        // FAR only falls as the threshold rises, so the first point within
+        // target is the one that rejects the fewest genuine users
+        let recommended = curve
+            .iter()
+            .find(|p| p.far <= target_far)
+            .unwrap_or(&curve[curve.len() - 1]);
+        let equal_error_rate = curve
+            .iter()
+            .min_by(|a, b| (a.far - a.frr).abs().total_cmp(&(b.far - b.frr).abs()))
+            .map(|p| (p.far + p.frr) / 2.0)
+            .unwrap_or(1.0);
+
+        let report = CalibrationReport {
+            method: method.to_string(),
+            matcher,
+            genuine_pairs: genuine.len(),
+            impostor_pairs: impostor.len(),
+            target_far,
+            recommended_threshold: recommended.threshold,
+            far_at_recommended: recommended.far,
+            frr_at_recommended: recommended.frr,
+            current_threshold,
+            equal_error_rate,
+            curve,
+            calibrated_by: admin.to_string(),
+            calibrated_at: now(),
+        };
+        self.store
+            .put(NAMESPACE, &report_key(method, matcher), &report)?;
+        Ok(report)
+    }
+
+    /// Latest report per method and matcher, optionally for one method
+    pub fn reports(&self, method: Option<&str>) -> Result<Vec<CalibrationReport>, String> {
+        Ok(self
+            .store
+            .list::<CalibrationReport>(NAMESPACE)?
+            .into_iter()
+            .map(|(_, report)| report)
+            .filter(|report| method.is_none_or(|m| m == report.method))
+            .collect())
+    }
+}
+
+fn rate(scores: &[f64], counts: impl Fn(f64) -> bool) -> f64 {
+    scores.iter().filter(|s| counts(**s)).count() as f64 / scores.len() as f64
+}
+
+fn report_key(method: &str, matcher: Matcher) -> String {
+    let matcher = match matcher {
+        Matcher::Sample => "sample",
+        Matcher::Embedding => "embedding",
+    };
+    format!("{}:{}", method, matcher)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
@@ -0,0 +1,360 @@
+/**
+ * Capability Tokens
+ * Time-limited delegation of vault operations to another address
+ *
+ * A vault owner can let someone else, e.g. a lawyer, run named operations
+ * on one vault until an expiry. The enclave mints the token and signs it
+ * with its identity key:
+ *
+ *   token = base64url(claims JSON) "." base64url(ed25519(DOMAIN || claims))
+ *
+ * The holder still signs requests with their own key and sends the token
+ * in the x-lumina-capability header; the auth middleware then lets them
+ * act as the owner, but only on the granted vault and operations. Every
+ * minted token is recorded, and revoking one marks its record, so a
+ * revoked token fails even before it expires. Tokens also stop verifying
+ * when the enclave identity changes.
+ */
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use ed25519_dalek::Signature;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::auth::normalize_address;
+use crate::keys::identity::EnclaveIdentity;
+use crate::store::Store;
+
+pub const CAPABILITY_HEADER: &str = "x-lumina-capability";
+const DOMAIN: &[u8] = b"lumina-capability-v1";
+const NAMESPACE: &str = "capabilities";
+pub const MAX_TTL_SECS: u64 = 30 * 24 * 60 * 60;
+
+/// Delegable operations and the signed route each one covers
+pub const OPERATIONS: &[(&str, &str)] = &[
+    ("audit", "/vault/:vault_id/audit/export"),
+    ("manifest", "/vault/:vault_id/manifest"),
+    ("search", "/vault/:vault_id/search"),
+];
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CapabilityClaims {
+    pub token_id: String,
+    pub vault_id: String,
+    pub issuer: String,
+    pub holder: String,
+    pub operations: Vec<String>,
+    pub issued_at: u64,
+    pub expires_at: u64,
+}
+
+/// Persisted per token; `revoked_at` makes up the revocation list
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CapabilityRecord {
+    pub claims: CapabilityClaims,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revoked_at: Option<u64>,
+}
+
+pub struct CapabilityService {
+    identity: Arc<EnclaveIdentity>,
+    store: Arc<Store>,
+}
+
+impl CapabilityService {
+    pub fn new(identity: Arc<EnclaveIdentity>, store: Arc<Store>) -> Self {
+        Self { identity, store }
+    }
+
+    /// Mint a token; the caller must already be allowed to grant on the vault
+    pub fn mint(
+        &self,
+        vault_id: &str,
+        issuer: &str,
+        holder: &str,
+        operations: &[String],
+        ttl_secs: u64,
+    ) -> Result<(String, CapabilityClaims), String> {
+        if ttl_secs == 0 || ttl_secs > MAX_TTL_SECS {
+            return Err(format!(
+                "Capabilities last between 1 and {} seconds",
+                MAX_TTL_SECS
+            ));
+        }
+        let operations = check_operations(operations)?;
+        let holder = normalize_address(holder);
+        if holder == issuer {
+            return Err("Capabilities are for other addresses".to_string());
+        }
+
+        let mut id = [0u8; 16];
+        OsRng.fill_bytes(&mut id);
+        let issued_at = now();
+        let claims = CapabilityClaims {
+            token_id: hex::encode(id),
+            vault_id: vault_id.to_string(),
+            issuer: issuer.to_string(),
+            holder,
+            operations,
+            issued_at,
+            expires_at: issued_at + ttl_secs,
+        };
+
+        let payload = serde_json::to_vec(&claims).map_err(|e| e.to_string())?;
+        let signature = self.identity.sign(&signed_message(&payload));
+        let token = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(&payload),
+            URL_SAFE_NO_PAD.encode(signature.to_bytes())
+        );
+        self.store.put(
+            NAMESPACE,
+            &claims.token_id,
+            &CapabilityRecord {
+                claims: claims.clone(),
+                revoked_at: None,
+            },
+        )?;
+        Ok((token, claims))
+    }
+
+    /// Only the issuer may revoke; revoking twice is harmless
+    pub fn revoke(
+        &self,
+        vault_id: &str,
+        token_id: &str,
+        caller: &str,
+    ) -> Result<CapabilityRecord, String> {
+        let mut record: CapabilityRecord = self
+            .store
+            .get(NAMESPACE, token_id)?
+            .filter(|r: &CapabilityRecord| r.claims.vault_id == vault_id)
+            .ok_or("Unknown capability")?;
+        if record.claims.issuer != caller {
+            return Err("Only the issuer may revoke a capability".to_string());
+        }
+        if record.revoked_at.is_none() {
+            record.revoked_at = Some(now());
+            self.store.put(NAMESPACE, token_id, &record)?;
+        }
+        Ok(record)
+    }
+
+    /// Erase every token minted for the vault; returns their ids
+    pub fn erase_vault(&self, vault_id: &str) -> Result<Vec<String>, String> {
+        let ids: Vec<String> = self
+            .store
+            .list::<CapabilityRecord>(NAMESPACE)?
+            .into_iter()
+            .filter(|(_, record)| record.claims.vault_id == vault_id)
+            .map(|(id, _)| id)
+            .collect();
+        if !ids.is_empty() {
+            self.store.erase(NAMESPACE, &ids)?;
+        }
+        Ok(ids)
+    }
+
+    /// Claims of a valid, unexpired, unrevoked token presented by `holder`
+    /// for the signed route `route` (its template) at `path`
+    pub fn authorize(
+        &self,
+        token: &str,
+        holder: &str,
+        route: Option<&str>,
+        path: &str,
+    ) -> Result<CapabilityClaims, String> {
+        let claims = self.verify(token)?;
+        if claims.holder != holder {
+            return Err("Capability was issued to a different address".to_string());
+        }
+
+        let route = route.ok_or("Route can't be reached with a capability")?;
+        let (operation, _) = OPERATIONS
+            .iter()
+            .find(|(_, template)| *template == route)
+            .ok_or("Route can't be reached with a capability")?;
+        if !claims.operations.iter().any(|op| op == operation) {
+            return Err(format!("Capability does not grant {}", operation));
+        }
+        if path_vault_id(route, path) != Some(claims.vault_id.as_str()) {
+            return Err("Capability is for a different vault".to_string());
+        }
+        Ok(claims)
+    }
+
+    fn verify(&self, token: &str) -> Result<CapabilityClaims, String> {
+        let malformed = || "Malformed capability token".to_string();
+        let (payload, signature) = token.split_once('.').ok_or_else(malformed)?;
+        let payload = URL_SAFE_NO_PAD.decode(payload).map_err(|_| malformed())?;
+        let signature: [u8; 64] = URL_SAFE_NO_PAD
+            .decode(signature)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(malformed)?;
+
+        self.identity
+            .public_key()
+            .verify_strict(
+                &signed_message(&payload),
+                &Signature::from_bytes(&signature),
+            )
+            .map_err(|_| "Capability was not issued by this enclave".to_string())?;
+        let claims: CapabilityClaims = serde_json::from_slice(&payload).map_err(|_| malformed())?;
+
+        if claims.expires_at <= now() {
+            return Err("Capability has expired".to_string());
+        }
+        let record: Option<CapabilityRecord> = self.store.get(NAMESPACE, &claims.token_id)?;
+        match record {
+            Some(record) if record.revoked_at.is_none() => Ok(claims),
+            Some(_) => Err("Capability has been revoked".to_string()),
+            None => Err("Unknown capability".to_string()),
+        }
+    }
+}
+
+/// The operations, sorted and deduplicated, if they can all be delegated
+pub fn check_operations(operations: &[String]) -> Result<Vec<String>, String> {
+    if operations.is_empty() {
+        return Err("A capability needs at least one operation".to_string());
+    }
+    if let Some(unknown) = operations
+        .iter()
+        .find(|op| !OPERATIONS.iter().any(|(name, _)| name == op))
+    {
+        return Err(format!("Operation {} can't be delegated", unknown));
+    }
+    let mut operations = operations.to_vec();
+    operations.sort();
+    operations.dedup();
+    Ok(operations)
+}
+
+fn signed_message(payload: &[u8]) -> Vec<u8> {
+    [DOMAIN, payload].concat()
+}
+
+/// The path segment standing where the template has `:vault_id`
+fn path_vault_id<'a>(template: &str, path: &'a str) -> Option<&'a str> {
+    let position = template.split('/').position(|s| s == ":vault_id")?;
+    path.split('/').nth(position)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AUDIT_ROUTE: &str = "/vault/:vault_id/audit/export";
+    const AUDIT_PATH: &str = "/vault/vault-1/audit/export";
+
+    fn service(name: &str) -> CapabilityService {
+        let dir = std::env::temp_dir().join(format!("lumina-capability-tests-{}-{}", name, now()));
+        CapabilityService::new(
+            Arc::new(EnclaveIdentity::ephemeral(&"00".repeat(32), "test")),
+            Arc::new(Store::open(dir).unwrap()),
+        )
+    }
+
+    fn mint(capabilities: &CapabilityService, holder: &str) -> (String, CapabilityClaims) {
+        capabilities
+            .mint("vault-1", "0xowner", holder, &["audit".to_string()], 60)
+            .unwrap()
+    }
+
+    /// A token over `claims` signed by `identity`, as mint would produce
+    fn token_for(identity: &EnclaveIdentity, claims: &CapabilityClaims) -> String {
+        let payload = serde_json::to_vec(claims).unwrap();
+        let signature = identity.sign(&signed_message(&payload));
+        format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(&payload),
+            URL_SAFE_NO_PAD.encode(signature.to_bytes())
+        )
+    }
+
+    #[test]
+    fn tokens_grant_their_holder_only_the_vault_and_operations_minted() {
+        let capabilities = service("grants");
+        let holder = normalize_address("0xb0b");
+        let (token, claims) = mint(&capabilities, &holder);
+
+        let granted = capabilities
+            .authorize(&token, &holder, Some(AUDIT_ROUTE), AUDIT_PATH)
+            .unwrap();
+        assert_eq!(granted.issuer, "0xowner");
+
+        let refused = |holder: &str, route, path| {
+            capabilities
+                .authorize(&token, holder, Some(route), path)
+                .err()
+                .unwrap()
+        };
+        let eve = normalize_address("0xeve");
+        assert!(refused(&eve, AUDIT_ROUTE, AUDIT_PATH).contains("different address"));
+        let other_vault = "/vault/vault-2/audit/export";
+        assert!(refused(&holder, AUDIT_ROUTE, other_vault).contains("different vault"));
+        let search = "/vault/:vault_id/search";
+        assert!(refused(&holder, search, "/vault/vault-1/search").contains("does not grant"));
+
+        assert!(capabilities
+            .revoke("vault-1", &claims.token_id, &holder)
+            .is_err());
+        capabilities
+            .revoke("vault-1", &claims.token_id, "0xowner")
+            .unwrap();
+        assert!(refused(&holder, AUDIT_ROUTE, AUDIT_PATH).contains("revoked"));
+
+        let forever = capabilities.mint("vault-1", "0xowner", &holder, &["audit".to_string()], 0);
+        assert!(forever.is_err());
+    }
+
+    #[test]
+    fn forged_and_expired_tokens_are_refused() {
+        let capabilities = service("forged");
+        let holder = normalize_address("0xb0b");
+        let (token, claims) = mint(&capabilities, &holder);
+        let refused = |token: &str, path| {
+            capabilities
+                .authorize(token, &holder, Some(AUDIT_ROUTE), path)
+                .err()
+                .unwrap()
+        };
+
+        // Widening the claims breaks the enclave's signature over them
+        let mut widened = claims.clone();
+        widened.vault_id = "vault-2".to_string();
+        let (_, signature) = token.split_once('.').unwrap();
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&widened).unwrap());
+        let forged = format!("{}.{}", payload, signature);
+        let other_vault = "/vault/vault-2/audit/export";
+        assert!(refused(&forged, other_vault).contains("not issued by this enclave"));
+
+        let elsewhere = EnclaveIdentity::ephemeral(&"00".repeat(32), "test");
+        let foreign = token_for(&elsewhere, &claims);
+        assert!(refused(&foreign, AUDIT_PATH).contains("not issued by this enclave"));
+
+        let mut expired = claims.clone();
+        expired.expires_at = now() - 1;
+        let expired = token_for(&capabilities.identity, &expired);
+        assert!(refused(&expired, AUDIT_PATH).contains("expired"));
+
+        let mut unminted = claims;
+        unminted.token_id = "00".repeat(16);
+        let unminted = token_for(&capabilities.identity, &unminted);
+        assert!(refused(&unminted, AUDIT_PATH).contains("Unknown capability"));
+
+        assert!(refused("not-a-token", AUDIT_PATH).contains("Malformed"));
+    }
+}
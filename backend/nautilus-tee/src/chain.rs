@@ -0,0 +1,222 @@
+/**
+ * Chain Relay
+ * JSON-RPC to a Sui node through a relay the parent runs
+ *
+ * The enclave has no network of its own. Each call is one JSON-RPC
+ * request line out to the relay and one response line back, over VSOCK
+ * (TCP in development). The relay and node can withhold or invent
+ * answers, so callers treat what comes back as a claim about the chain,
+ * never as authorization. Calls are retried under the chain's retry
+ * policy (see retry).
+ */
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+
+use crate::config::ParentRelay;
+use crate::retry::{self, Dependency, Failure, FailureKind};
+
+const RELAY_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Deserialize)]
+struct RpcResponse<T> {
+    result: Option<T>,
+    error: Option<Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventPage {
+    pub data: Vec<ChainEvent>,
+    pub next_cursor: Option<Value>,
+    pub has_next_page: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainEvent {
+    pub id: EventId,
+    pub sender: String,
+    pub parsed_json: Value,
+    pub timestamp_ms: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventId {
+    pub tx_digest: String,
+    pub event_seq: String,
+}
+
+/// A page of Move events of one type, oldest first, after `cursor`
+pub async fn query_events(
+    relay: &ParentRelay,
+    event_type: &str,
+    cursor: Option<&Value>,
+    limit: usize,
+) -> Result<EventPage, String> {
+    call(
+        relay,
+        "suix_queryEvents",
+        json!([{ "MoveEventType": event_type }, cursor, limit, false]),
+    )
+    .await
+}
+
+#[derive(Deserialize)]
+struct TransactionPage {
+    data: Vec<TransactionSummary>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TransactionSummary {
+    timestamp_ms: Option<String>,
+}
+
+/// When `address` last sent a transaction, in unix seconds; None if never
+pub async fn last_transaction_at(
+    relay: &ParentRelay,
+    address: &str,
+) -> Result<Option<u64>, String> {
+    let page: TransactionPage = call(
+        relay,
+        "suix_queryTransactionBlocks",
+        json!([{ "filter": { "FromAddress": address } }, null, 1, true]),
+    )
+    .await?;
+    Ok(page
+        .data
+        .first()
+        .and_then(|tx| tx.timestamp_ms.as_deref())
+        .and_then(|ms| ms.parse::<u64>().ok())
+        .map(|ms| ms / 1000))
+}
+
+/// One JSON-RPC call, retried under the chain's retry policy
+pub async fn call<T: DeserializeOwned>(
+    relay: &ParentRelay,
+    method: &str,
+    params: Value,
+) -> Result<T, String> {
+    let mut line = serde_json::to_vec(&json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": method,
+        "params": params,
+    }))
+    .map_err(|e| format!("Failed to encode {} request: {}", method, e))?;
+    line.push(b'\n');
+
+    let line = &line;
+    retry::run(Dependency::Chain, move || attempt(relay, method, line)).await
+}
+
+async fn attempt<T: DeserializeOwned>(
+    relay: &ParentRelay,
+    method: &str,
+    line: &[u8],
+) -> Result<T, Failure> {
+    let response = tokio::time::timeout(RELAY_TIMEOUT, async {
+        match relay {
+            ParentRelay::Tcp(addr) => {
+                let stream = tokio::net::TcpStream::connect(addr).await.map_err(|e| {
+                    Failure::new(
+                        FailureKind::Unreachable,
+                        format!("Failed to reach chain relay at {}: {}", addr, e),
+                    )
+                })?;
+                exchange(stream, line).await
+            }
+            #[cfg(feature = "vsock")]
+            ParentRelay::Vsock { port } => {
+                let stream = tokio_vsock::VsockStream::connect(tokio_vsock::VsockAddr::new(
+                    crate::bootstrap::PARENT_CID,
+                    *port,
+                ))
+                .await
+                .map_err(|e| {
+                    Failure::new(
+                        FailureKind::Unreachable,
+                        format!("Failed to reach chain relay on vsock port {}: {}", port, e),
+                    )
+                })?;
+                exchange(stream, line).await
+            }
+            #[cfg(not(feature = "vsock"))]
+            ParentRelay::Vsock { port } => Err(Failure::new(
+                FailureKind::Unreachable,
+                format!(
+                    "VSOCK chain relay (port {}) requires building with the `vsock` feature",
+                    port
+                ),
+            )),
+        }
+    })
+    .await
+    .map_err(|_| Failure::new(FailureKind::Timeout, "Chain relay timed out"))??;
+
+    let response: RpcResponse<T> = serde_json::from_str(&response).map_err(|e| {
+        Failure::new(
+            FailureKind::Malformed,
+            format!("Malformed {} response: {}", method, e),
+        )
+    })?;
+    if let Some(error) = response.error {
+        return Err(Failure::new(
+            FailureKind::Remote,
+            format!("{} failed: {}", method, error),
+        ));
+    }
+    response.result.ok_or_else(|| {
+        Failure::new(
+            FailureKind::Malformed,
+            format!("{} returned no result", method),
+        )
+    })
+}
+
+/// A string field of an event, or a `vector<u8>` one holding UTF-8
+pub fn field_string(data: &Value, field: &str) -> Option<String> {
+    match data.get(field)? {
+        Value::String(s) => Some(s.clone()),
+        Value::Array(items) => {
+            let bytes = items
+                .iter()
+                .map(|b| b.as_u64().and_then(|b| u8::try_from(b).ok()))
+                .collect::<Option<Vec<u8>>>()?;
+            String::from_utf8(bytes).ok()
+        }
+        _ => None,
+    }
+}
+
+async fn exchange<S>(stream: S, line: &[u8]) -> Result<String, Failure>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let failed = |what: &str, e: std::io::Error| {
+        Failure::new(FailureKind::Transport, format!("Failed to {}: {}", what, e))
+    };
+    let mut stream = BufReader::new(stream);
+    stream
+        .get_mut()
+        .write_all(line)
+        .await
+        .map_err(|e| failed("send chain request", e))?;
+    let mut response = String::new();
+    let read = stream
+        .read_line(&mut response)
+        .await
+        .map_err(|e| failed("read chain response", e))?;
+    if read == 0 {
+        return Err(Failure::new(
+            FailureKind::Transport,
+            "Chain relay closed the connection",
+        ));
+    }
+    Ok(response)
+}
@@ -0,0 +1,431 @@
+/**
+ * Enclave Channels
+ * Mutually attested, encrypted streams between enclaves
+ *
+ * Replication, sharding and migration between enclaves share this
+ * transport. Over any byte stream (VSOCK through the parent, or TCP), the
+ * two ends exchange one hello each:
+ *
+ *   initiator  fresh X25519 key, identity key and purpose, attested with
+ *              user_data = SHA-256(DOMAIN || "initiator" || purpose ||
+ *              ephemeral || identity) and signed by the identity key
+ *   responder  the same, but its transcript starts with the hash of the
+ *              initiator's hello instead of the purpose
+ *
+ * Each end checks the other's attestation comes from a build on the
+ * measurement allowlist (see allowlist) and commits to exactly the keys in its hello. Both then derive directional
+ * ChaCha20-Poly1305 keys with HKDF(DH(ephemerals), salt = hash of both
+ * hellos). Frames are length-prefixed; encrypted frames use a counter
+ * nonce per direction, so dropped, reordered or replayed frames fail to
+ * open and end the channel. The ephemeral secrets are dropped once the
+ * keys are derived.
+ *
+ * Over HTTP, a peer opens a channel by upgrading POST /peer/channel to
+ * UPGRADE_PROTOCOL (see routes::peer); the purpose in its hello picks the
+ * protocol run over it.
+ */
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Nonce};
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use hkdf::Hkdf;
+use rand::rngs::OsRng;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use x25519_dalek::{EphemeralSecret, PublicKey};
+use zeroize::Zeroizing;
+
+use crate::allowlist::MeasurementAllowlist;
+use crate::attestation::AttestationService;
+use crate::keys::identity::EnclaveIdentity;
+
+pub const OPERATION: &str = "channel_handshake";
+/// Upgrade token for channels opened over HTTP/1.1
+pub const UPGRADE_PROTOCOL: &str = "lumina-channel/1";
+
+const DOMAIN: &[u8] = b"lumina-channel-v1";
+const INFO_INITIATOR: &[u8] = b"lumina-channel-v1 initiator->responder";
+const INFO_RESPONDER: &[u8] = b"lumina-channel-v1 responder->initiator";
+const VERSION: u32 = 1;
+const MAX_FRAME_BYTES: usize = 16 * 1024 * 1024;
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Role {
+    Initiator,
+    Responder,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Hello {
+    version: u32,
+    role: Role,
+    purpose: String,
+    ephemeral_key: String, // Base64 X25519
+    identity_key: String,  // Base64 Ed25519
+    signature: String,     // Base64 Ed25519 over the transcript hash
+    attestation: crate::attestation::Attestation,
+}
+
+/// The enclave at the other end, as its attestation vouched for it
+#[derive(Clone, Debug)]
+pub struct Peer {
+    pub measurement: String, // PCR0
+    pub identity: String,    // Identity key id
+    pub purpose: String,
+}
+
+pub struct ChannelService {
+    attestation: Arc<AttestationService>,
+    identity: Arc<EnclaveIdentity>,
+    /// Builds of enclaves this one will open channels with
+    allowlist: Arc<MeasurementAllowlist>,
+}
+
+pub struct SecureChannel<S> {
+    stream: S,
+    peer: Peer,
+    send_key: Zeroizing<[u8; 32]>,
+    recv_key: Zeroizing<[u8; 32]>,
+    sent: u64,
+    received: u64,
+}
+
+impl ChannelService {
+    pub fn new(
+        attestation: Arc<AttestationService>,
+        identity: Arc<EnclaveIdentity>,
+        allowlist: Arc<MeasurementAllowlist>,
+    ) -> Self {
+        Self {
+            attestation,
+            identity,
+            allowlist,
+        }
+    }
+
+    /// Open a channel for `purpose` over `stream` to a listening enclave
+    pub async fn connect<S>(
+        &self,
+        mut stream: S,
+        purpose: &str,
+    ) -> Result<SecureChannel<S>, String>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let secret = EphemeralSecret::random_from_rng(OsRng);
+        let public = PublicKey::from(&secret);
+        let identity_key = self.identity.public_key();
+        let transcript = transcript(
+            Role::Initiator,
+            purpose.as_bytes(),
+            public.as_bytes(),
+            identity_key.as_bytes(),
+        );
+        let hello = self
+            .hello(Role::Initiator, purpose, &public, &transcript)
+            .await?;
+        let sent = serde_json::to_vec(&hello).map_err(|e| e.to_string())?;
+        write_frame(&mut stream, &sent).await?;
+
+        let received = read_frame(&mut stream).await?;
+        let reply: Hello = serde_json::from_slice(&received)
+            .map_err(|e| format!("Malformed channel hello: {}", e))?;
+        if reply.role != Role::Responder || reply.purpose != purpose {
+            return Err("Peer answered with an unexpected hello".to_string());
+        }
+        let (peer, peer_ephemeral) = self.check_hello(&reply, &Sha256::digest(&sent))?;
+
+        let (initiator_key, responder_key) =
+            derive_keys(secret, &peer_ephemeral, &sent, &received)?;
+        Ok(SecureChannel {
+            stream,
+            peer,
+            send_key: initiator_key,
+            recv_key: responder_key,
+            sent: 0,
+            received: 0,
+        })
+    }
+
+    /// Answer a channel opened by another enclave over `stream`
+    pub async fn accept<S>(&self, mut stream: S) -> Result<SecureChannel<S>, String>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let received = read_frame(&mut stream).await?;
+        let hello: Hello = serde_json::from_slice(&received)
+            .map_err(|e| format!("Malformed channel hello: {}", e))?;
+        if hello.role != Role::Initiator {
+            return Err("Expected an initiator hello".to_string());
+        }
+        let (peer, peer_ephemeral) = self.check_hello(&hello, hello.purpose.as_bytes())?;
+
+        let secret = EphemeralSecret::random_from_rng(OsRng);
+        let public = PublicKey::from(&secret);
+        let transcript = transcript(
+            Role::Responder,
+            &Sha256::digest(&received),
+            public.as_bytes(),
+            self.identity.public_key().as_bytes(),
+        );
+        let reply = self
+            .hello(Role::Responder, &hello.purpose, &public, &transcript)
+            .await?;
+        let sent = serde_json::to_vec(&reply).map_err(|e| e.to_string())?;
+        write_frame(&mut stream, &sent).await?;
+
+        let (initiator_key, responder_key) =
+            derive_keys(secret, &peer_ephemeral, &received, &sent)?;
+        Ok(SecureChannel {
+            stream,
+            peer,
+            send_key: responder_key,
+            recv_key: initiator_key,
+            sent: 0,
+            received: 0,
+        })
+    }
+
+    async fn hello(
+        &self,
+        role: Role,
+        purpose: &str,
+        ephemeral: &PublicKey,
+        transcript: &[u8],
+    ) -> Result<Hello, String> {
+        let attestation = self
+            .attestation
+            .generate_with_user_data("", OPERATION, transcript)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(Hello {
+            version: VERSION,
+            role,
+            purpose: purpose.to_string(),
+            ephemeral_key: STANDARD.encode(ephemeral.as_bytes()),
+            identity_key: STANDARD.encode(self.identity.public_key().as_bytes()),
+            signature: STANDARD.encode(self.identity.sign(transcript).to_bytes()),
+            attestation,
+        })
+    }
+
+    /// Check a peer's hello against its attestation; `context` is what its
+    /// transcript starts with after the role
+    fn check_hello(&self, hello: &Hello, context: &[u8]) -> Result<(Peer, PublicKey), String> {
+        if hello.version != VERSION {
+            return Err(format!("Unsupported channel version {}", hello.version));
+        }
+        let ephemeral = crate::crypto::decode_public_key(&hello.ephemeral_key)?;
+        let identity_bytes: [u8; 32] = crate::crypto::decode(&hello.identity_key)?
+            .try_into()
+            .map_err(|_| "Identity key must be 32 bytes".to_string())?;
+        let identity = VerifyingKey::from_bytes(&identity_bytes)
+            .map_err(|_| "Invalid identity key".to_string())?;
+        let transcript = transcript(hello.role, context, ephemeral.as_bytes(), &identity_bytes);
+
+        let attested = self
+            .attestation
+            .verify(&hello.attestation)
+            .map_err(|e| e.to_string())?;
+        if attested.operation != OPERATION
+            || attested.user_data.as_deref() != Some(hex::encode(&transcript).as_str())
+        {
+            return Err("Peer attestation does not cover its hello".to_string());
+        }
+        if !self.allowlist.allows(&attested)? {
+            return Err(format!("Peer measurement {} is not allowed", attested.pcr0));
+        }
+
+        let signature: [u8; 64] = crate::crypto::decode(&hello.signature)?
+            .try_into()
+            .map_err(|_| "Signature must be 64 bytes".to_string())?;
+        identity
+            .verify(&transcript, &Signature::from_bytes(&signature))
+            .map_err(|_| "Peer identity signature is invalid".to_string())?;
+
+        Ok((
+            Peer {
+                measurement: attested.pcr0,
+                identity: hex::encode(&Sha256::digest(identity_bytes)[..16]),
+                purpose: hello.purpose.clone(),
+            },
+            ephemeral,
+        ))
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> SecureChannel<S> {
+    pub fn peer(&self) -> &Peer {
+        &self.peer
+    }
+
+    pub async fn send(&mut self, message: &[u8]) -> Result<(), String> {
+        let key: &[u8; 32] = &self.send_key;
+        let ciphertext = ChaCha20Poly1305::new(key.into())
+            .encrypt(&nonce(self.sent), message)
+            .map_err(|_| "Channel encryption failed".to_string())?;
+        self.sent += 1;
+        write_frame(&mut self.stream, &ciphertext).await
+    }
+
+    pub async fn recv(&mut self) -> Result<Vec<u8>, String> {
+        let ciphertext = read_frame(&mut self.stream).await?;
+        let key: &[u8; 32] = &self.recv_key;
+        let message = ChaCha20Poly1305::new(key.into())
+            .decrypt(&nonce(self.received), ciphertext.as_slice())
+            .map_err(|_| "Channel frame failed to authenticate".to_string())?;
+        self.received += 1;
+        Ok(message)
+    }
+
+    pub async fn send_json<T: Serialize>(&mut self, message: &T) -> Result<(), String> {
+        let bytes = serde_json::to_vec(message)
+            .map_err(|e| format!("Failed to serialize channel message: {}", e))?;
+        self.send(&bytes).await
+    }
+
+    pub async fn recv_json<T: DeserializeOwned>(&mut self) -> Result<T, String> {
+        let bytes = self.recv().await?;
+        serde_json::from_slice(&bytes).map_err(|e| format!("Malformed channel message: {}", e))
+    }
+}
+
+fn transcript(role: Role, context: &[u8], ephemeral: &[u8], identity: &[u8]) -> Vec<u8> {
+    let role: &[u8] = match role {
+        Role::Initiator => b"initiator",
+        Role::Responder => b"responder",
+    };
+    let mut hasher = Sha256::new();
+    hasher.update(DOMAIN);
+    for field in [role, context] {
+        hasher.update((field.len() as u64).to_be_bytes());
+        hasher.update(field);
+    }
+    hasher.update(ephemeral);
+    hasher.update(identity);
+    hasher.finalize().to_vec()
+}
+
+/// (initiator->responder, responder->initiator) keys
+fn derive_keys(
+    secret: EphemeralSecret,
+    peer: &PublicKey,
+    initiator_hello: &[u8],
+    responder_hello: &[u8],
+) -> Result<(Zeroizing<[u8; 32]>, Zeroizing<[u8; 32]>), String> {
+    let shared = secret.diffie_hellman(peer);
+    if !shared.was_contributory() {
+        return Err("Peer ephemeral key is a low-order point".to_string());
+    }
+    let mut salt = Sha256::new();
+    salt.update(initiator_hello);
+    salt.update(responder_hello);
+    let hkdf = Hkdf::<Sha256>::new(Some(&salt.finalize()), shared.as_bytes());
+
+    let mut initiator = Zeroizing::new([0u8; 32]);
+    let mut responder = Zeroizing::new([0u8; 32]);
+    hkdf.expand(INFO_INITIATOR, initiator.as_mut())
+        .and_then(|_| hkdf.expand(INFO_RESPONDER, responder.as_mut()))
+        .map_err(|_| "Channel key derivation failed".to_string())?;
+    Ok((initiator, responder))
+}
+
+fn nonce(counter: u64) -> Nonce {
+    let mut nonce = [0u8; 12];
+    nonce[4..].copy_from_slice(&counter.to_be_bytes());
+    *Nonce::from_slice(&nonce)
+}
+
+async fn write_frame<S: AsyncWrite + Unpin>(stream: &mut S, frame: &[u8]) -> Result<(), String> {
+    if frame.len() > MAX_FRAME_BYTES {
+        return Err("Channel frame is too large".to_string());
+    }
+    let failed = |e: std::io::Error| format!("Channel write failed: {}", e);
+    stream
+        .write_all(&(frame.len() as u32).to_be_bytes())
+        .await
+        .map_err(failed)?;
+    stream.write_all(frame).await.map_err(failed)?;
+    stream.flush().await.map_err(failed)
+}
+
+async fn read_frame<S: AsyncRead + Unpin>(stream: &mut S) -> Result<Vec<u8>, String> {
+    let failed = |e: std::io::Error| format!("Channel read failed: {}", e);
+    let mut len = [0u8; 4];
+    stream.read_exact(&mut len).await.map_err(failed)?;
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_FRAME_BYTES {
+        return Err("Channel frame is too large".to_string());
+    }
+    let mut frame = vec![0u8; len];
+    stream.read_exact(&mut frame).await.map_err(failed)?;
+    Ok(frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::Store;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn service(allowed: Vec<String>) -> ChannelService {
+        service_trusting(allowed, true)
+    }
+
+    fn service_trusting(allowed: Vec<String>, placeholders: bool) -> ChannelService {
+        let attestation =
+            Arc::new(AttestationService::new().with_placeholder_signatures(placeholders));
+        let measurement = attestation.get_pcr_measurements().unwrap().pcr0;
+        let identity = Arc::new(EnclaveIdentity::ephemeral(&measurement, "test"));
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("lumina-channel-tests-{}", nanos));
+        let store = Arc::new(Store::open(dir).unwrap());
+        let allowlist = MeasurementAllowlist::new(store, None, allowed, measurement).unwrap();
+        ChannelService::new(attestation, identity, Arc::new(allowlist))
+    }
+
+    #[tokio::test]
+    async fn messages_round_trip_between_attested_ends() {
+        let (a, b) = tokio::io::duplex(64 * 1024);
+        let (initiator, responder) = (service(Vec::new()), service(Vec::new()));
+
+        let (connected, accepted) =
+            tokio::join!(initiator.connect(a, "replication"), responder.accept(b));
+        let (mut connected, mut accepted) = (connected.unwrap(), accepted.unwrap());
+        assert_eq!(accepted.peer().purpose, "replication");
+
+        connected.send(b"ping").await.unwrap();
+        assert_eq!(accepted.recv().await.unwrap(), b"ping");
+        accepted.send_json(&vec![1, 2, 3]).await.unwrap();
+        assert_eq!(connected.recv_json::<Vec<u32>>().await.unwrap(), vec![1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn unlisted_measurements_are_refused() {
+        let (a, b) = tokio::io::duplex(64 * 1024);
+        let initiator = service(Vec::new());
+        let responder = service(vec!["00".repeat(32)]);
+
+        let (_, accepted) = tokio::join!(initiator.connect(a, "replication"), responder.accept(b));
+        assert!(accepted.unwrap_err().contains("not allowed"));
+    }
+
+    #[tokio::test]
+    async fn placeholder_hellos_are_refused_outside_development() {
+        let (a, b) = tokio::io::duplex(64 * 1024);
+        let initiator = service(Vec::new());
+        let responder = service_trusting(Vec::new(), false);
+
+        let (_, accepted) = tokio::join!(initiator.connect(a, "replication"), responder.accept(b));
+        assert!(accepted.unwrap_err().contains("not trusted"));
+    }
+}
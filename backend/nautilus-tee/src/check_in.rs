@@ -0,0 +1,349 @@
+/**
+ * Check-In Reminders
+ * Per-vault proof-of-life deadlines and the reminders leading up to them
+ *
+ * An owner sets how often they must check in and when to be reminded,
+ * e.g. every 90 days with reminders 7, 3 and 1 days before the deadline.
+ * The reminder sweep sends each reminder once per cycle, as a bus event
+ * (which reaches webhooks and anyone following the vault's check-ins) and
+ * a notification to the vault's contacts. A sweep that finds several
+ * reminders due at once (after downtime) sends only the most urgent.
+ *
+ * Checking in starts a new cycle. Reminders of the old cycle that hadn't
+ * fired yet are snoozed: they fire against the new deadline instead. A
+ * heartbeat on chain counts as a check-in at the time it was emitted, unless
+ * the owner has checked in since. Each check-in is published on the bus,
+ * which records it in the vault's audit trail.
+ */
+
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::info;
+
+use crate::auth::normalize_address;
+use crate::bus::{BusEvent, EventBus};
+use crate::chain::EventId;
+use crate::notifications::{Notification, NotificationService};
+use crate::store::Store;
+use crate::tasks::{Schedule, TaskScheduler};
+
+const NAMESPACE: &str = "check_in_schedules";
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+const MAX_INTERVAL_DAYS: u32 = 3650;
+const MAX_REMINDERS: usize = 10;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CheckInSchedule {
+    pub vault_id: String,
+    pub owner: String,
+    pub interval_days: u32,
+    /// Days before the deadline, most distant first
+    pub reminder_days: Vec<u32>,
+    pub last_check_in: u64,
+    /// Reminders already sent this cycle
+    #[serde(default)]
+    pub sent: Vec<u32>,
+    pub updated_at: u64,
+}
+
+impl CheckInSchedule {
+    pub fn expires_at(&self) -> u64 {
+        self.last_check_in + u64::from(self.interval_days) * SECS_PER_DAY
+    }
+
+    /// Reminders due by `now` that haven't been sent this cycle
+    fn due(&self, now: u64) -> Vec<u32> {
+        let expires_at = self.expires_at();
+        self.reminder_days
+            .iter()
+            .copied()
+            .filter(|day| !self.sent.contains(day))
+            .filter(|day| now + u64::from(*day) * SECS_PER_DAY >= expires_at)
+            .collect()
+    }
+}
+
+/// How a check-in reached the enclave
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckInSource {
+    /// A signed request to the enclave
+    Direct,
+    /// A heartbeat event on chain
+    Chain,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CheckIn {
+    pub vault_id: String,
+    pub checked_in_at: u64,
+    pub expires_at: u64,
+    /// Reminders of the interrupted cycle that now wait for the new deadline
+    pub snoozed: Vec<u32>,
+    pub source: CheckInSource,
+    /// The signer, or the owner for a heartbeat
+    pub checked_in_by: String,
+    /// The heartbeat event, for a check-in on chain
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub heartbeat: Option<EventId>,
+}
+
+/// What followers of a vault's check-ins see
+#[derive(Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CheckInEvent {
+    Reminder {
+        vault_id: String,
+        days_before: u32,
+        expires_at: u64,
+    },
+    CheckedIn(CheckIn),
+}
+
+impl CheckInEvent {
+    /// Matches the serialized `kind`
+    pub fn name(&self) -> &'static str {
+        match self {
+            CheckInEvent::Reminder { .. } => "reminder",
+            CheckInEvent::CheckedIn(_) => "checked_in",
+        }
+    }
+
+    pub fn vault_id(&self) -> &str {
+        match self {
+            CheckInEvent::Reminder { vault_id, .. } => vault_id,
+            CheckInEvent::CheckedIn(check_in) => &check_in.vault_id,
+        }
+    }
+
+    /// The follower's view of a bus event, if it is about check-ins
+    pub fn from_bus(event: &BusEvent) -> Option<Self> {
+        match event {
+            BusEvent::CheckInReminder {
+                vault_id,
+                days_before,
+                expires_at,
+                ..
+            } => Some(CheckInEvent::Reminder {
+                vault_id: vault_id.clone(),
+                days_before: *days_before,
+                expires_at: *expires_at,
+            }),
+            BusEvent::CheckedIn(check_in) => Some(CheckInEvent::CheckedIn(check_in.clone())),
+            _ => None,
+        }
+    }
+}
+
+pub struct CheckInService {
+    store: Arc<Store>,
+    bus: Arc<EventBus>,
+    /// Held across read-modify-write so check-ins and the sweep don't
+    /// overwrite each other
+    writes: Mutex<()>,
+}
+
+impl CheckInService {
+    pub fn new(store: Arc<Store>, bus: Arc<EventBus>) -> Self {
+        Self {
+            store,
+            bus,
+            writes: Mutex::new(()),
+        }
+    }
+
+    /// Set the vault's schedule, starting a cycle now. The caller has been
+    /// authorized.
+    pub fn set_schedule(
+        &self,
+        vault_id: &str,
+        owner: &str,
+        interval_days: u32,
+        mut reminder_days: Vec<u32>,
+    ) -> Result<CheckInSchedule, String> {
+        if interval_days == 0 || interval_days > MAX_INTERVAL_DAYS {
+            return Err(format!(
+                "Check-in interval must be 1 to {} days",
+                MAX_INTERVAL_DAYS
+            ));
+        }
+        reminder_days.sort_by(|a, b| b.cmp(a));
+        reminder_days.dedup();
+        if reminder_days.len() > MAX_REMINDERS {
+            return Err(format!("At most {} reminders are allowed", MAX_REMINDERS));
+        }
+        if let Some(day) = reminder_days
+            .iter()
+            .find(|d| **d == 0 || **d >= interval_days)
+        {
+            return Err(format!(
+                "Reminder {} days before the deadline doesn't fit a {}-day interval",
+                day, interval_days
+            ));
+        }
+
+        let _writes = self.writes.lock().unwrap();
+        let now = now();
+        let schedule = CheckInSchedule {
+            vault_id: vault_id.to_string(),
+            owner: normalize_address(owner),
+            interval_days,
+            reminder_days,
+            last_check_in: now,
+            sent: Vec::new(),
+            updated_at: now,
+        };
+        self.store.put(NAMESPACE, vault_id, &schedule)?;
+        Ok(schedule)
+    }
+
+    pub fn schedule(&self, vault_id: &str) -> Result<Option<CheckInSchedule>, String> {
+        self.store.get(NAMESPACE, vault_id)
+    }
+
+    /// Every vault's schedule
+    pub fn schedules(&self) -> Result<Vec<CheckInSchedule>, String> {
+        Ok(self
+            .store
+            .list::<CheckInSchedule>(NAMESPACE)?
+            .into_iter()
+            .map(|(_, schedule)| schedule)
+            .collect())
+    }
+
+    /// Start a new cycle. The caller has been authorized.
+    pub fn check_in(&self, vault_id: &str, by: &str) -> Result<CheckIn, String> {
+        let _writes = self.writes.lock().unwrap();
+        let schedule = self
+            .schedule(vault_id)?
+            .ok_or("Vault has no check-in schedule")?;
+        let by = by.to_string();
+        self.start_cycle(schedule, now(), CheckInSource::Direct, by, None)
+    }
+
+    /// Start a new cycle from a heartbeat seen on chain at `at`. None when
+    /// a later check-in, direct or on chain, already covers it. The caller
+    /// has checked the heartbeat came from the schedule's owner.
+    pub fn record_heartbeat(
+        &self,
+        vault_id: &str,
+        at: u64,
+        heartbeat: EventId,
+    ) -> Result<Option<CheckIn>, String> {
+        let _writes = self.writes.lock().unwrap();
+        let schedule = self
+            .schedule(vault_id)?
+            .ok_or("Vault has no check-in schedule")?;
+        if at <= schedule.last_check_in {
+            return Ok(None);
+        }
+        let by = schedule.owner.clone();
+        self.start_cycle(schedule, at, CheckInSource::Chain, by, Some(heartbeat))
+            .map(Some)
+    }
+
+    fn start_cycle(
+        &self,
+        mut schedule: CheckInSchedule,
+        at: u64,
+        source: CheckInSource,
+        checked_in_by: String,
+        heartbeat: Option<EventId>,
+    ) -> Result<CheckIn, String> {
+        // Only a cycle that has started reminding is interrupted
+        let snoozed = if schedule.sent.is_empty() {
+            Vec::new()
+        } else {
+            schedule
+                .reminder_days
+                .iter()
+                .copied()
+                .filter(|day| !schedule.sent.contains(day))
+                .collect()
+        };
+        schedule.last_check_in = at;
+        schedule.sent.clear();
+        self.store.put(NAMESPACE, &schedule.vault_id, &schedule)?;
+
+        let check_in = CheckIn {
+            vault_id: schedule.vault_id.clone(),
+            checked_in_at: at,
+            expires_at: schedule.expires_at(),
+            snoozed,
+            source,
+            checked_in_by,
+            heartbeat,
+        };
+        self.bus.publish(BusEvent::CheckedIn(check_in.clone()))?;
+        Ok(check_in)
+    }
+
+    /// Check deadlines on the task scheduler and send due reminders
+    pub fn schedule_reminder_sweep(
+        self: Arc<Self>,
+        tasks: &TaskScheduler,
+        notifications: Arc<NotificationService>,
+        every: Duration,
+    ) -> Result<(), String> {
+        tasks.register(
+            "check_in.reminders",
+            &Schedule::every(every),
+            every / 10,
+            move || {
+                let (service, notifications) = (self.clone(), notifications.clone());
+                async move {
+                    let sent = service.sweep_reminders(&notifications)?;
+                    if sent > 0 {
+                        info!("Check-in sweep sent {} reminders", sent);
+                    }
+                    Ok(())
+                }
+            },
+        )
+    }
+
+    fn sweep_reminders(&self, notifications: &NotificationService) -> Result<usize, String> {
+        let _writes = self.writes.lock().unwrap();
+        let now = now();
+        let mut sent = 0;
+        for (_, mut schedule) in self.store.list::<CheckInSchedule>(NAMESPACE)? {
+            let due = schedule.due(now);
+            let Some(days_before) = due.iter().copied().min() else {
+                continue;
+            };
+            let expires_at = schedule.expires_at();
+
+            self.bus.publish(BusEvent::CheckInReminder {
+                vault_id: schedule.vault_id.clone(),
+                owner: schedule.owner.clone(),
+                days_before,
+                expires_at,
+            })?;
+            let days_left = expires_at.saturating_sub(now).div_ceil(SECS_PER_DAY);
+            notifications.notify(
+                &schedule.vault_id,
+                &Notification {
+                    kind: "check_in.reminder".to_string(),
+                    title: "Check-in due".to_string(),
+                    body: format!(
+                        "Check in to vault {} within {} days to confirm you still hold it.",
+                        schedule.vault_id, days_left
+                    ),
+                },
+            )?;
+
+            schedule.sent.extend(due);
+            self.store.put(NAMESPACE, &schedule.vault_id, &schedule)?;
+            sent += 1;
+        }
+        Ok(sent)
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
@@ -0,0 +1,439 @@
+/**
+ * Circuit Versions
+ * Which circuit version proves each claim type, and the verification key
+ * that checks its proofs
+ *
+ * A proof only verifies against the key of the circuit version that
+ * produced it, so versions stay registered after they are superseded.
+ * Requests may pin a version, every proof and its attestation name the
+ * version used, and the compatibility listing maps versions to
+ * verification keys so archived proofs remain checkable.
+ *
+ *   current:    proves new claims by default
+ *   deprecated: still proves claims that pin it
+ *   retired:    proves nothing; its key still verifies old proofs
+ *
+ * Operators can change a version's support without a rebuild, e.g. retire
+ * one found unsound: TEE_CIRCUIT_SUPPORT="solvency@1=retired,...".
+ *
+ * Proving keys are pinned by a manifest signed in the release pipeline:
+ * at load each version's .zkey and verification key must hash to the
+ * manifest's digests, and optionally each key must be the output of its
+ * ceremony's final contribution. A version whose artifacts don't check out
+ * isn't served, and outside development neither is one no manifest pins.
+ * The zkey digest goes into every proof response and attestation. A pinned
+ * verification key is also loaded, to check proofs handed to the enclave.
+ *
+ * Proving keys run to hundreds of megabytes, so an image may ship any
+ * artifact zstd-compressed as <name>.zst in place of <name>. It is
+ * decompressed as a stream while it is hashed, and manifest digests are
+ * always of the uncompressed bytes, so compressing artifacts doesn't
+ * change what is pinned. A zkey is only held whole when its ceremony is
+ * verified.
+ *
+ * The first version of a circuit keeps the unversioned artifact names
+ * (solvency.wasm, solvency_verification_key.json); later versions add a
+ * _vN suffix.
+ */
+
+use ark_bn254::Bn254;
+use ark_groth16::PreparedVerifyingKey;
+use ed25519_dalek::{Signature, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::config::{CircuitArtifactsConfig, Environment};
+use crate::groth16;
+use crate::zkey;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Support {
+    Current,
+    Deprecated,
+    Retired,
+}
+
+#[derive(Clone, Serialize)]
+pub struct CircuitVersion {
+    pub claim_type: &'static str,
+    pub version: u32,
+    /// Stem of the .wasm and .zkey files; None while the claim type has no
+    /// circuit and its proofs are placeholders
+    pub artifact: Option<&'static str>,
+    pub verification_key: Option<&'static str>,
+    /// Number of public signals, outputs first
+    pub public_signals: usize,
+    pub support: Support,
+    /// Hex SHA-256 digests the signed manifest pinned; None when unpinned
+    pub zkey_sha256: Option<String>,
+    pub verification_key_sha256: Option<String>,
+    /// Why the version isn't served, e.g. its zkey failed verification
+    pub unavailable: Option<String>,
+    /// Loaded with the pinned verification key
+    #[serde(skip)]
+    pub verifying_key: Option<Arc<PreparedVerifyingKey<Bn254>>>,
+    /// The pinned verification key as shipped, for export
+    #[serde(skip)]
+    pub verification_key_json: Option<Arc<Value>>,
+}
+
+#[derive(Deserialize)]
+struct Manifest {
+    circuits: Vec<ManifestEntry>,
+}
+
+#[derive(Deserialize)]
+struct ManifestEntry {
+    claim_type: String,
+    version: u32,
+    /// File name in the artifact directory
+    zkey: String,
+    zkey_sha256: String,
+    verification_key_sha256: String,
+    /// Checked when ceremony verification is on
+    #[serde(default)]
+    contributions: Option<u32>,
+    /// Hex transcript hash of the final contribution
+    #[serde(default)]
+    final_transcript: Option<String>,
+}
+
+const REGISTRY: &[CircuitVersion] = &[
+    CircuitVersion {
+        claim_type: "keyword",
+        version: 1,
+        artifact: None,
+        verification_key: None,
+        public_signals: 1,
+        support: Support::Current,
+        zkey_sha256: None,
+        verification_key_sha256: None,
+        unavailable: None,
+        verifying_key: None,
+        verification_key_json: None,
+    },
+    CircuitVersion {
+        claim_type: "timestamp",
+        version: 1,
+        artifact: None,
+        verification_key: None,
+        public_signals: 2,
+        support: Support::Current,
+        zkey_sha256: None,
+        verification_key_sha256: None,
+        unavailable: None,
+        verifying_key: None,
+        verification_key_json: None,
+    },
+    CircuitVersion {
+        claim_type: "file_hash",
+        version: 1,
+        artifact: None,
+        verification_key: None,
+        public_signals: 2,
+        support: Support::Current,
+        zkey_sha256: None,
+        verification_key_sha256: None,
+        unavailable: None,
+        verifying_key: None,
+        verification_key_json: None,
+    },
+    CircuitVersion {
+        claim_type: "identity_attribute",
+        version: 1,
+        artifact: Some("identity_attribute"),
+        verification_key: Some("identity_attribute_verification_key.json"),
+        public_signals: 24,
+        support: Support::Current,
+        zkey_sha256: None,
+        verification_key_sha256: None,
+        unavailable: None,
+        verifying_key: None,
+        verification_key_json: None,
+    },
+    CircuitVersion {
+        claim_type: "solvency",
+        version: 1,
+        artifact: Some("solvency"),
+        verification_key: Some("solvency_verification_key.json"),
+        public_signals: 3,
+        support: Support::Current,
+        zkey_sha256: None,
+        verification_key_sha256: None,
+        unavailable: None,
+        verifying_key: None,
+        verification_key_json: None,
+    },
+    CircuitVersion {
+        claim_type: "dkim_email",
+        version: 1,
+        artifact: Some("dkim_email"),
+        verification_key: Some("dkim_email_verification_key.json"),
+        public_signals: 23,
+        support: Support::Current,
+        zkey_sha256: None,
+        verification_key_sha256: None,
+        unavailable: None,
+        verifying_key: None,
+        verification_key_json: None,
+    },
+    CircuitVersion {
+        claim_type: "predicate",
+        version: 1,
+        artifact: Some("predicate"),
+        verification_key: Some("predicate_verification_key.json"),
+        public_signals: 18,
+        support: Support::Current,
+        zkey_sha256: None,
+        verification_key_sha256: None,
+        unavailable: None,
+        verifying_key: None,
+        verification_key_json: None,
+    },
+];
+
+pub struct CircuitRegistry {
+    versions: Vec<CircuitVersion>,
+}
+
+impl CircuitRegistry {
+    /// The built-in versions, with `claim_type@version=support` overrides,
+    /// their artifacts checked against the signed manifest
+    pub fn new(
+        overrides: &[String],
+        artifacts: Option<&CircuitArtifactsConfig>,
+        environment: Environment,
+    ) -> Result<Self, String> {
+        let mut versions = REGISTRY.to_vec();
+        for entry in overrides {
+            let invalid = || format!("Invalid circuit support {:?}", entry);
+            let (circuit, support) = entry.split_once('=').ok_or_else(invalid)?;
+            let (claim_type, version) = circuit.split_once('@').ok_or_else(invalid)?;
+            let version: u32 = version.parse().map_err(|_| invalid())?;
+            let support = match support {
+                "current" => Support::Current,
+                "deprecated" => Support::Deprecated,
+                "retired" => Support::Retired,
+                _ => return Err(invalid()),
+            };
+            versions
+                .iter_mut()
+                .find(|c| c.claim_type == claim_type && c.version == version)
+                .ok_or_else(|| format!("No circuit {}@{}", claim_type, version))?
+                .support = support;
+        }
+
+        let manifest = artifacts.map(load_manifest).transpose()?;
+        for circuit in versions.iter_mut().filter(|c| c.artifact.is_some()) {
+            let pinned = match (&manifest, artifacts) {
+                (Some(manifest), Some(config)) => circuit.pin(manifest, config),
+                _ if environment == Environment::Development => Ok(()),
+                _ => Err("No signed artifact manifest pins its zkey".to_string()),
+            };
+            match pinned {
+                Ok(()) => {}
+                Err(reason) => {
+                    warn!(
+                        "Not serving {} circuit version {}: {}",
+                        circuit.claim_type, circuit.version, reason
+                    );
+                    circuit.unavailable = Some(reason);
+                }
+            }
+        }
+        Ok(Self { versions })
+    }
+
+    /// Every registered version, by claim type then version
+    pub fn all(&self) -> &[CircuitVersion] {
+        &self.versions
+    }
+
+    /// A registered version, whatever its support
+    pub fn get(&self, claim_type: &str, version: u32) -> Option<&CircuitVersion> {
+        self.versions
+            .iter()
+            .find(|c| c.claim_type == claim_type && c.version == version)
+    }
+
+    /// The version to prove a claim with: the pinned one, or the current one
+    pub fn resolve(
+        &self,
+        claim_type: &str,
+        version: Option<u32>,
+    ) -> Result<&CircuitVersion, String> {
+        let mut versions = self
+            .versions
+            .iter()
+            .filter(|c| c.claim_type == claim_type)
+            .peekable();
+        if versions.peek().is_none() {
+            return Err(format!("Unsupported claim type: {}", claim_type));
+        }
+
+        let circuit = match version {
+            Some(version) => versions
+                .find(|c| c.version == version)
+                .ok_or_else(|| format!("{} has no circuit version {}", claim_type, version))?,
+            None => versions
+                .find(|c| c.support == Support::Current)
+                .ok_or_else(|| format!("{} has no current circuit version", claim_type))?,
+        };
+        if let Some(reason) = &circuit.unavailable {
+            return Err(format!(
+                "{} circuit version {} is unavailable: {}",
+                claim_type, circuit.version, reason
+            ));
+        }
+        if circuit.support == Support::Retired {
+            return Err(format!(
+                "{} circuit version {} is retired; it only verifies existing proofs",
+                claim_type, circuit.version
+            ));
+        }
+        Ok(circuit)
+    }
+}
+
+impl CircuitVersion {
+    /// Check the version's artifacts against its manifest entry
+    fn pin(&mut self, manifest: &Manifest, config: &CircuitArtifactsConfig) -> Result<(), String> {
+        let entry = manifest
+            .circuits
+            .iter()
+            .find(|e| e.claim_type == self.claim_type && e.version == self.version)
+            .ok_or_else(|| "Not in the artifact manifest".to_string())?;
+
+        let (zkey_sha256, zkey) =
+            digest_artifact(&config.dir, &entry.zkey, config.verify_ceremony)?;
+        if !zkey_sha256.eq_ignore_ascii_case(&entry.zkey_sha256) {
+            return Err(format!(
+                "zkey hash {} does not match pinned {}",
+                zkey_sha256, entry.zkey_sha256
+            ));
+        }
+        let verification_key = self
+            .verification_key
+            .ok_or_else(|| "No verification key".to_string())?;
+        let (verification_key_sha256, verification_key) =
+            digest_artifact(&config.dir, verification_key, true)?;
+        if !verification_key_sha256.eq_ignore_ascii_case(&entry.verification_key_sha256) {
+            return Err(format!(
+                "Verification key hash {} does not match pinned {}",
+                verification_key_sha256, entry.verification_key_sha256
+            ));
+        }
+        let verification_key = verification_key.unwrap_or_default();
+        let verifying_key = groth16::verifying_key(&verification_key)?;
+        let verification_key_json = serde_json::from_slice(&verification_key)
+            .map_err(|e| format!("Invalid verification key: {}", e))?;
+
+        if config.verify_ceremony {
+            let contribution = zkey::final_contribution(&zkey.unwrap_or_default())?;
+            if entry.contributions.is_some_and(|n| n != contribution.count) {
+                return Err(format!(
+                    "zkey has {} contributions; the manifest lists {}",
+                    contribution.count,
+                    entry.contributions.unwrap_or_default()
+                ));
+            }
+            if entry
+                .final_transcript
+                .as_ref()
+                .is_some_and(|t| !t.eq_ignore_ascii_case(&contribution.transcript))
+            {
+                return Err("Final contribution transcript does not match the manifest".to_string());
+            }
+            info!(
+                "{} circuit version {}: {} contributions, the last by {}",
+                self.claim_type,
+                self.version,
+                contribution.count,
+                contribution
+                    .name
+                    .as_deref()
+                    .unwrap_or("an unnamed contributor")
+            );
+        }
+
+        self.zkey_sha256 = Some(zkey_sha256);
+        self.verification_key_sha256 = Some(verification_key_sha256);
+        self.verifying_key = Some(Arc::new(verifying_key));
+        self.verification_key_json = Some(Arc::new(verification_key_json));
+        Ok(())
+    }
+}
+
+/// Hex SHA-256 of an artifact's uncompressed bytes, read from `name` or
+/// else decompressed from `name.zst`; the bytes are returned when `keep`
+fn digest_artifact(
+    dir: &Path,
+    name: &str,
+    keep: bool,
+) -> Result<(String, Option<Vec<u8>>), String> {
+    let plain = dir.join(name);
+    let compressed = dir.join(format!("{}.zst", name));
+    let failed =
+        |path: &Path, e: std::io::Error| format!("Failed to read {}: {}", path.display(), e);
+    let (path, mut reader): (_, Box<dyn Read>) = match File::open(&plain) {
+        Ok(file) => (plain, Box::new(BufReader::new(file))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let file = File::open(&compressed).map_err(|e| failed(&compressed, e))?;
+            let decoder =
+                zstd::stream::read::Decoder::new(file).map_err(|e| failed(&compressed, e))?;
+            (compressed, Box::new(decoder))
+        }
+        Err(e) => return Err(failed(&plain, e)),
+    };
+
+    let mut hasher = Sha256::new();
+    let mut kept = keep.then(Vec::new);
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = reader.read(&mut buffer).map_err(|e| failed(&path, e))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        if let Some(kept) = kept.as_mut() {
+            kept.extend_from_slice(&buffer[..read]);
+        }
+    }
+    Ok((hex::encode(hasher.finalize()), kept))
+}
+
+/// The manifest, once its detached signature (`<manifest>.sig`, hex)
+/// verifies under the configured key
+fn load_manifest(config: &CircuitArtifactsConfig) -> Result<Manifest, String> {
+    let bytes = std::fs::read(&config.manifest).map_err(|e| {
+        format!(
+            "Failed to read circuit manifest {}: {}",
+            config.manifest.display(),
+            e
+        )
+    })?;
+    let signature_path = format!("{}.sig", config.manifest.display());
+    let signature = std::fs::read_to_string(&signature_path)
+        .map_err(|e| format!("Failed to read {}: {}", signature_path, e))?;
+
+    let key: [u8; 32] = hex::decode(&config.manifest_key)
+        .ok()
+        .and_then(|k| k.try_into().ok())
+        .ok_or_else(|| "TEE_CIRCUIT_MANIFEST_KEY must be a hex Ed25519 public key".to_string())?;
+    let key = VerifyingKey::from_bytes(&key).map_err(|_| "Invalid circuit manifest key")?;
+    let signature: [u8; 64] = hex::decode(signature.trim())
+        .ok()
+        .and_then(|s| s.try_into().ok())
+        .ok_or_else(|| "Circuit manifest signature must be 64 hex bytes".to_string())?;
+    key.verify_strict(&bytes, &Signature::from_bytes(&signature))
+        .map_err(|_| "Circuit manifest signature does not verify".to_string())?;
+
+    serde_json::from_slice(&bytes).map_err(|e| format!("Invalid circuit manifest: {}", e))
+}
@@ -0,0 +1,295 @@
+/**
+ * Claim Definitions
+ * Declarative predicates over structured vault data, compiled into inputs
+ * for the generic predicate circuit
+ *
+ * Integrators describe a claim as data instead of writing a circuit: the
+ * fields it reads from the vault's JSON, and what each must satisfy.
+ *
+ *   {
+ *     "name": "adult_nz_resident",
+ *     "conditions": [
+ *       { "field": "age", "op": "gte", "value": 18 },
+ *       { "field": "address.country", "op": "eq", "value": "NZ" },
+ *       { "field": "passport.number", "op": "commit", "salt": "<hex>" }
+ *     ]
+ *   }
+ *
+ * Fields are dotted paths through objects and array indexes. eq and neq
+ * take a number, string or boolean of the field's own type; lt, lte, gt
+ * and gte take integers. commit tests nothing: it publishes
+ * sha256(salt || element) of the field, so the holder can later open it
+ * to whoever they give the salt (at least 16 bytes).
+ *
+ * Every definition compiles onto the one circuit, MAX_CONDITIONS slots of
+ * (op, public constant, private value), unused slots zero. Values become
+ * field elements: integers shifted by 2^63 so signed order survives,
+ * strings as their truncated SHA-256, booleans as 0 or 1. The definition's
+ * own digest is public, so a verifier knows which claim was proven.
+ */
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::exchange::canonical_sha256;
+use crate::field;
+
+/// Condition slots in the predicate circuit
+pub const MAX_CONDITIONS: usize = 8;
+const MAX_NAME_LEN: usize = 64;
+const MIN_SALT_BYTES: usize = 16;
+/// Puts i64 into 0..2^64 in order, which the circuit compares in 64 bits
+const NUMBER_OFFSET: i128 = 1 << 63;
+
+#[derive(Serialize, Deserialize)]
+pub struct ClaimDefinition {
+    pub name: String,
+    pub conditions: Vec<Condition>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Condition {
+    pub field: String,
+    #[serde(flatten)]
+    pub test: Test,
+}
+
+/// Op codes are the circuit's: 0 marks an unused slot
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Test {
+    Eq { value: Value },
+    Neq { value: Value },
+    Lt { value: i64 },
+    Lte { value: i64 },
+    Gt { value: i64 },
+    Gte { value: i64 },
+    Commit { salt: String }, // Hex
+}
+
+/// Witness for the predicate circuit
+pub struct PredicateWitness {
+    /// Circuit input signals, by name
+    pub inputs: Value,
+    /// Public signals in the order the verifier sees them: the result,
+    /// then the public inputs in declaration order
+    pub public_signals: Vec<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Scalar {
+    Number(i64),
+    Text([u8; 32]),
+    Bool(bool),
+}
+
+/// One compiled slot
+struct Slot {
+    op: u8,
+    constant: String,
+    value: String,
+    holds: bool,
+}
+
+impl ClaimDefinition {
+    pub fn witness(&self, data: &[u8]) -> Result<PredicateWitness, String> {
+        if self.name.is_empty() || self.name.len() > MAX_NAME_LEN {
+            return Err(format!("name must be 1 to {} characters", MAX_NAME_LEN));
+        }
+        if self.conditions.is_empty() || self.conditions.len() > MAX_CONDITIONS {
+            return Err(format!("A claim has 1 to {} conditions", MAX_CONDITIONS));
+        }
+        let data: Value =
+            serde_json::from_slice(data).map_err(|e| format!("Vault data must be JSON: {}", e))?;
+
+        let slots = self
+            .conditions
+            .iter()
+            .map(|condition| {
+                condition
+                    .compile(&data)
+                    .map_err(|e| format!("{}: {}", condition.field, e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let holds = slots.iter().all(|slot| slot.holds);
+        let digest = field::truncated(&canonical_sha256(self)?);
+
+        let column = |pick: fn(&Slot) -> String| -> Vec<String> {
+            let mut column: Vec<String> = slots.iter().map(pick).collect();
+            column.resize(MAX_CONDITIONS, "0".to_string());
+            column
+        };
+        let ops = column(|slot| slot.op.to_string());
+        let constants = column(|slot| slot.constant.clone());
+        let values = column(|slot| slot.value.clone());
+
+        let mut public_signals = vec![u32::from(holds).to_string()];
+        public_signals.extend(ops.iter().cloned());
+        public_signals.extend(constants.iter().cloned());
+        public_signals.push(digest.clone());
+        Ok(PredicateWitness {
+            inputs: json!({
+                "ops": ops,
+                "constants": constants,
+                "definition_digest": digest,
+                "values": values,
+            }),
+            public_signals,
+        })
+    }
+}
+
+impl Condition {
+    fn compile(&self, data: &Value) -> Result<Slot, String> {
+        let found = self
+            .field
+            .split('.')
+            .try_fold(data, |value, segment| match value {
+                Value::Object(map) => map.get(segment),
+                Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+                _ => None,
+            })
+            .ok_or_else(|| "Not in the vault data".to_string())?;
+        let actual = Scalar::of(found)?;
+
+        let (op, constant, holds) = match &self.test {
+            Test::Eq { value } | Test::Neq { value } => {
+                let expected = Scalar::of(value)?;
+                if std::mem::discriminant(&expected) != std::mem::discriminant(&actual) {
+                    return Err("Compared with a value of another type".to_string());
+                }
+                let equal = actual == expected;
+                match self.test {
+                    Test::Eq { .. } => (1, expected.element(), equal),
+                    _ => (2, expected.element(), !equal),
+                }
+            }
+            Test::Lt { value } | Test::Lte { value } | Test::Gt { value } | Test::Gte { value } => {
+                let Scalar::Number(number) = actual else {
+                    return Err("Only integers can be ordered".to_string());
+                };
+                let (op, holds) = match self.test {
+                    Test::Lt { .. } => (3, number < *value),
+                    Test::Lte { .. } => (4, number <= *value),
+                    Test::Gt { .. } => (5, number > *value),
+                    _ => (6, number >= *value),
+                };
+                (op, Scalar::Number(*value).element(), holds)
+            }
+            Test::Commit { salt } => {
+                let salt = hex::decode(salt.trim_start_matches("0x"))
+                    .ok()
+                    .filter(|s| s.len() >= MIN_SALT_BYTES)
+                    .ok_or_else(|| format!("salt must be at least {} hex bytes", MIN_SALT_BYTES))?;
+                let mut commitment = Sha256::new();
+                commitment.update(&salt);
+                commitment.update(actual.element().as_bytes());
+                (7, field::truncated(&commitment.finalize()), true)
+            }
+        };
+        Ok(Slot {
+            op,
+            constant,
+            value: actual.element(),
+            holds,
+        })
+    }
+}
+
+impl Scalar {
+    fn of(value: &Value) -> Result<Self, String> {
+        match value {
+            Value::Number(n) => n
+                .as_i64()
+                .map(Self::Number)
+                .ok_or_else(|| "Numbers must be 64-bit integers".to_string()),
+            Value::String(s) => Ok(Self::Text(Sha256::digest(s.as_bytes()).into())),
+            Value::Bool(b) => Ok(Self::Bool(*b)),
+            _ => Err("Only numbers, strings and booleans can be tested".to_string()),
+        }
+    }
+
+    /// The field element the circuit sees
+    fn element(self) -> String {
+        match self {
+            Self::Number(n) => (i128::from(n) + NUMBER_OFFSET).to_string(),
+            Self::Text(digest) => field::truncated(&digest),
+            Self::Bool(b) => u8::from(b).to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data() -> Vec<u8> {
+        serde_json::to_vec(&json!({
+            "age": 34,
+            "address": { "country": "NZ" },
+            "accounts": [{ "balance": -120 }],
+            "verified": true,
+        }))
+        .unwrap()
+    }
+
+    fn definition(conditions: Value) -> ClaimDefinition {
+        serde_json::from_value(json!({ "name": "test", "conditions": conditions })).unwrap()
+    }
+
+    #[test]
+    fn conditions_compile_to_slots() {
+        let witness = definition(json!([
+            { "field": "age", "op": "gte", "value": 18 },
+            { "field": "address.country", "op": "eq", "value": "NZ" },
+            { "field": "accounts.0.balance", "op": "lt", "value": 0 },
+            { "field": "verified", "op": "neq", "value": false },
+        ]))
+        .witness(&data())
+        .unwrap();
+        assert_eq!(witness.public_signals.len(), 2 + 2 * MAX_CONDITIONS);
+        assert_eq!(witness.public_signals[0], "1");
+        assert_eq!(&witness.public_signals[1..6], ["6", "1", "3", "2", "0"]);
+        assert_eq!(
+            witness.public_signals[1 + MAX_CONDITIONS],
+            (i128::from(18) + NUMBER_OFFSET).to_string()
+        );
+    }
+
+    #[test]
+    fn failing_conditions_prove_false() {
+        let witness = definition(json!([
+            { "field": "age", "op": "gte", "value": 18 },
+            { "field": "address.country", "op": "eq", "value": "AU" },
+        ]))
+        .witness(&data())
+        .unwrap();
+        assert_eq!(witness.public_signals[0], "0");
+    }
+
+    #[test]
+    fn commitments_depend_on_the_salt() {
+        let commit = |salt: &str| {
+            definition(json!([{ "field": "age", "op": "commit", "salt": salt }]))
+                .witness(&data())
+                .unwrap()
+                .public_signals[1 + MAX_CONDITIONS]
+                .clone()
+        };
+        assert_ne!(commit(&"11".repeat(16)), commit(&"22".repeat(16)));
+    }
+
+    #[test]
+    fn rejects_mistyped_and_missing_fields() {
+        for conditions in [
+            json!([{ "field": "address.country", "op": "gt", "value": 1 }]),
+            json!([{ "field": "age", "op": "eq", "value": "34" }]),
+            json!([{ "field": "address.city", "op": "eq", "value": "x" }]),
+            json!([{ "field": "age", "op": "commit", "salt": "00" }]),
+            json!([]),
+        ] {
+            assert!(definition(conditions).witness(&data()).is_err());
+        }
+    }
+}
@@ -0,0 +1,63 @@
+/**
+ * Hardened Clock
+ * Wall time the host can't quietly step once the enclave is running
+ *
+ * An enclave's wall clock is set by its host, which can move it at will.
+ * This clock reads it once at startup and advances from there by the
+ * monotonic clock, so a later step shows up as the two disagreeing. Past
+ * MAX_SKEW the clock refuses to answer rather than vouch for either
+ * reading; a restart re-anchors it. It also never goes backwards.
+ */
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+/// Drift tolerated between the wall and monotonic clocks, e.g. from NTP slew
+pub const MAX_SKEW: Duration = Duration::from_secs(5);
+
+pub struct HardenedClock {
+    anchor_wall: Duration,
+    anchor: Instant,
+    /// Latest reading handed out
+    last: Mutex<Duration>,
+}
+
+impl HardenedClock {
+    pub fn new() -> Self {
+        let anchor_wall = wall();
+        Self {
+            anchor_wall,
+            anchor: Instant::now(),
+            last: Mutex::new(anchor_wall),
+        }
+    }
+
+    /// Time since the epoch, or an error if the wall clock has been stepped
+    pub fn now(&self) -> Result<Duration, String> {
+        let monotonic = self.anchor_wall + self.anchor.elapsed();
+        let wall = wall();
+        let skew = if wall > monotonic {
+            wall - monotonic
+        } else {
+            monotonic - wall
+        };
+        if skew > MAX_SKEW {
+            warn!(
+                "Wall clock is {:?} off the monotonic clock; refusing to read it",
+                skew
+            );
+            return Err("Enclave clock is unreliable".to_string());
+        }
+
+        let mut last = self.last.lock().unwrap();
+        *last = (*last).max(monotonic);
+        Ok(*last)
+    }
+}
+
+fn wall() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+}
@@ -0,0 +1,312 @@
+/**
+ * CMS Recipient Ciphertexts
+ * Opens what KMS returns when asked to decrypt for an attested enclave
+ *
+ * KMS Decrypt with a Recipient (an attestation binding an RSA public key)
+ * doesn't return the plaintext. It returns CiphertextForRecipient, a CMS
+ * EnvelopedData (RFC 5652) with one KeyTransRecipientInfo: a fresh
+ * content key wrapped with RSAES-OAEP-SHA-256 to the attested key, and
+ * the plaintext under AES-256-CBC with that key. KMS streams it as BER,
+ * with indefinite lengths and the encrypted content in chunks, so the few
+ * fields needed are walked by hand rather than with a DER parser.
+ *
+ * The RSA key is generated per boot and decrypts exactly one ciphertext,
+ * so there is no decryption oracle to time.
+ */
+
+use aes::cipher::block_padding::Pkcs7;
+use aes::cipher::{BlockDecryptMut, KeyIvInit};
+use rsa::{Oaep, RsaPrivateKey};
+use sha2::Sha256;
+use zeroize::Zeroizing;
+
+/// id-envelopedData, id-RSAES-OAEP and id-aes256-CBC, DER-encoded
+const OID_ENVELOPED_DATA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x03];
+const OID_RSAES_OAEP: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x07];
+const OID_AES256_CBC: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x01, 0x2a];
+
+const SEQUENCE: u8 = 0x30;
+const SET: u8 = 0x31;
+const OCTET_STRING: u8 = 0x04;
+const OID: u8 = 0x06;
+const CONSTRUCTED: u8 = 0x20;
+
+/// One BER element; `body` excludes the end-of-contents octets of an
+/// indefinite-length element
+struct Element<'a> {
+    tag: u8,
+    body: &'a [u8],
+}
+
+/// The plaintext of a CiphertextForRecipient addressed to `key`
+pub fn open_recipient_ciphertext(
+    key: &RsaPrivateKey,
+    ciphertext: &[u8],
+) -> Result<Zeroizing<Vec<u8>>, String> {
+    let malformed = |what: &str| format!("Malformed KMS ciphertext: {}", what);
+
+    let content_info = children(expect(single(ciphertext)?, SEQUENCE)?.body)?;
+    let [content_type, content, ..] = content_info.as_slice() else {
+        return Err(malformed("ContentInfo is incomplete"));
+    };
+    if expect_ref(content_type, OID)?.body != OID_ENVELOPED_DATA {
+        return Err(malformed("not EnvelopedData"));
+    }
+    let enveloped = children(content.body)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| malformed("EnvelopedData is missing"))?;
+    let fields = children(expect(enveloped, SEQUENCE)?.body)?;
+    let recipients = fields
+        .iter()
+        .position(|field| field.tag == SET)
+        .ok_or_else(|| malformed("no recipientInfos"))?;
+    let encrypted_content_info = fields
+        .get(recipients + 1)
+        .ok_or_else(|| malformed("no encryptedContentInfo"))?;
+
+    // KeyTransRecipientInfo: version, rid, keyEncryptionAlgorithm, encryptedKey
+    let recipient = children(fields[recipients].body)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| malformed("recipientInfos is empty"))?;
+    let recipient = children(expect(recipient, SEQUENCE)?.body)?;
+    let [_, _, algorithm, encrypted_key, ..] = recipient.as_slice() else {
+        return Err(malformed("recipient is not a KeyTransRecipientInfo"));
+    };
+    if algorithm_oid(algorithm)? != OID_RSAES_OAEP {
+        return Err(malformed("content key is not wrapped with RSAES-OAEP"));
+    }
+    let content_key = Zeroizing::new(
+        key.decrypt(
+            Oaep::new::<Sha256>(),
+            &octets(expect_ref(encrypted_key, OCTET_STRING)?)?,
+        )
+        .map_err(|_| "Failed to unwrap the KMS content key".to_string())?,
+    );
+
+    // EncryptedContentInfo: contentType, contentEncryptionAlgorithm, [0] encryptedContent
+    let info = children(expect_ref(encrypted_content_info, SEQUENCE)?.body)?;
+    let [_, algorithm, encrypted, ..] = info.as_slice() else {
+        return Err(malformed("no encryptedContent"));
+    };
+    let parameters = children(expect_ref(algorithm, SEQUENCE)?.body)?;
+    let [oid, iv, ..] = parameters.as_slice() else {
+        return Err(malformed("content algorithm has no IV"));
+    };
+    if expect_ref(oid, OID)?.body != OID_AES256_CBC {
+        return Err(malformed("content is not AES-256-CBC"));
+    }
+    let iv = octets(expect_ref(iv, OCTET_STRING)?)?;
+    if encrypted.tag & !CONSTRUCTED != 0x80 {
+        return Err(malformed("no encryptedContent"));
+    }
+    let mut content = Zeroizing::new(octets(encrypted)?);
+    let plaintext_len = cbc::Decryptor::<aes::Aes256>::new_from_slices(&content_key, &iv)
+        .map_err(|_| malformed("bad content key or IV length"))?
+        .decrypt_padded_mut::<Pkcs7>(&mut content)
+        .map_err(|_| "Failed to decrypt the KMS ciphertext".to_string())?
+        .len();
+    content.truncate(plaintext_len);
+    Ok(content)
+}
+
+fn algorithm_oid<'a>(algorithm: &Element<'a>) -> Result<&'a [u8], String> {
+    let fields = children(expect_ref(algorithm, SEQUENCE)?.body)?;
+    let oid = fields
+        .into_iter()
+        .next()
+        .ok_or("Malformed KMS ciphertext: empty AlgorithmIdentifier")?;
+    Ok(expect(oid, OID)?.body)
+}
+
+fn expect(element: Element<'_>, tag: u8) -> Result<Element<'_>, String> {
+    expect_ref(&element, tag)?;
+    Ok(element)
+}
+
+fn expect_ref<'e, 'a>(element: &'e Element<'a>, tag: u8) -> Result<&'e Element<'a>, String> {
+    // An octet string may arrive constructed, in chunks
+    if element.tag != tag && !(tag == OCTET_STRING && element.tag == tag | CONSTRUCTED) {
+        return Err(format!(
+            "Malformed KMS ciphertext: expected tag {:#04x}, found {:#04x}",
+            tag, element.tag
+        ));
+    }
+    Ok(element)
+}
+
+/// An octet string's contents, joining the chunks of a constructed one
+fn octets(element: &Element<'_>) -> Result<Vec<u8>, String> {
+    if element.tag & CONSTRUCTED == 0 {
+        return Ok(element.body.to_vec());
+    }
+    let mut joined = Vec::new();
+    for chunk in children(element.body)? {
+        joined.extend(octets(&chunk)?);
+    }
+    Ok(joined)
+}
+
+fn single(input: &[u8]) -> Result<Element<'_>, String> {
+    let (element, rest) = read(input)?;
+    if !rest.iter().all(|&b| b == 0) {
+        return Err("Malformed KMS ciphertext: trailing data".to_string());
+    }
+    Ok(element)
+}
+
+fn children(mut body: &[u8]) -> Result<Vec<Element<'_>>, String> {
+    let mut elements = Vec::new();
+    while !body.is_empty() {
+        let (element, rest) = read(body)?;
+        elements.push(element);
+        body = rest;
+    }
+    Ok(elements)
+}
+
+fn read(input: &[u8]) -> Result<(Element<'_>, &[u8]), String> {
+    let truncated = || "Malformed KMS ciphertext: truncated".to_string();
+    let (&tag, rest) = input.split_first().ok_or_else(truncated)?;
+    if tag & 0x1f == 0x1f {
+        return Err("Malformed KMS ciphertext: unsupported tag".to_string());
+    }
+    let (&first, mut rest) = rest.split_first().ok_or_else(truncated)?;
+    if first == 0x80 {
+        if tag & CONSTRUCTED == 0 {
+            return Err("Malformed KMS ciphertext: indefinite primitive".to_string());
+        }
+        // Indefinite length: children run up to the end-of-contents octets
+        let start = rest;
+        loop {
+            if rest.starts_with(&[0, 0]) {
+                let body = &start[..start.len() - rest.len()];
+                return Ok((Element { tag, body }, &rest[2..]));
+            }
+            rest = read(rest)?.1;
+        }
+    }
+    let len = if first & 0x80 == 0 {
+        first as usize
+    } else {
+        let count = (first & 0x7f) as usize;
+        if count > 4 || rest.len() < count {
+            return Err(truncated());
+        }
+        let (bytes, remaining) = rest.split_at(count);
+        rest = remaining;
+        bytes.iter().fold(0usize, |len, &b| (len << 8) | b as usize)
+    };
+    if rest.len() < len {
+        return Err(truncated());
+    }
+    let (body, rest) = rest.split_at(len);
+    Ok((Element { tag, body }, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aes::cipher::BlockEncryptMut;
+    use rand::rngs::OsRng;
+    use rand::RngCore;
+    use rsa::RsaPublicKey;
+
+    fn der(tag: u8, body: &[u8]) -> Vec<u8> {
+        let len = body.len();
+        let mut out = vec![tag];
+        if len < 0x80 {
+            out.push(len as u8);
+        } else {
+            let bytes = (len as u32).to_be_bytes();
+            let skip = bytes.iter().take_while(|&&b| b == 0).count();
+            out.push(0x80 | (4 - skip) as u8);
+            out.extend(&bytes[skip..]);
+        }
+        out.extend(body);
+        out
+    }
+
+    fn ber(tag: u8, parts: &[Vec<u8>]) -> Vec<u8> {
+        let mut out = vec![tag, 0x80];
+        for part in parts {
+            out.extend(part);
+        }
+        out.extend([0, 0]);
+        out
+    }
+
+    /// EnvelopedData the way KMS streams it: indefinite lengths and the
+    /// encrypted content in chunks
+    fn envelope(recipient: &RsaPublicKey, plaintext: &[u8]) -> Vec<u8> {
+        let mut content_key = [0u8; 32];
+        let mut iv = [0u8; 16];
+        OsRng.fill_bytes(&mut content_key);
+        OsRng.fill_bytes(&mut iv);
+        let mut buffer = vec![0u8; plaintext.len() + 16];
+        buffer[..plaintext.len()].copy_from_slice(plaintext);
+        let encrypted = cbc::Encryptor::<aes::Aes256>::new(&content_key.into(), &iv.into())
+            .encrypt_padded_mut::<Pkcs7>(&mut buffer, plaintext.len())
+            .unwrap()
+            .to_vec();
+        let wrapped = recipient
+            .encrypt(&mut OsRng, Oaep::new::<Sha256>(), &content_key)
+            .unwrap();
+
+        let recipient_info = der(
+            SEQUENCE,
+            &[
+                der(0x02, &[2]),
+                der(0x80, b"subject key id"),
+                der(
+                    SEQUENCE,
+                    &[der(OID, OID_RSAES_OAEP), der(SEQUENCE, &[])].concat(),
+                ),
+                der(OCTET_STRING, &wrapped),
+            ]
+            .concat(),
+        );
+        let chunks: Vec<Vec<u8>> = encrypted
+            .chunks(7)
+            .map(|chunk| der(OCTET_STRING, chunk))
+            .collect();
+        let encrypted_content_info = ber(
+            SEQUENCE,
+            &[
+                der(OID, &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x01]),
+                der(
+                    SEQUENCE,
+                    &[der(OID, OID_AES256_CBC), der(OCTET_STRING, &iv)].concat(),
+                ),
+                ber(0xa0, &chunks),
+            ],
+        );
+        let enveloped = ber(
+            SEQUENCE,
+            &[
+                der(0x02, &[2]),
+                ber(SET, &[recipient_info]),
+                encrypted_content_info,
+            ],
+        );
+        ber(
+            SEQUENCE,
+            &[der(OID, OID_ENVELOPED_DATA), ber(0xa0, &[enveloped])],
+        )
+    }
+
+    #[test]
+    fn kms_recipient_ciphertexts_open_with_the_attested_key() {
+        let key = RsaPrivateKey::new(&mut OsRng, 2048).unwrap();
+        let data_key = [9u8; 32];
+        let ciphertext = envelope(&key.to_public_key(), &data_key);
+
+        let opened = open_recipient_ciphertext(&key, &ciphertext).unwrap();
+        assert_eq!(opened.as_slice(), &data_key);
+
+        let other = RsaPrivateKey::new(&mut OsRng, 2048).unwrap();
+        assert!(open_recipient_ciphertext(&other, &ciphertext).is_err());
+        assert!(open_recipient_ciphertext(&key, &ciphertext[..ciphertext.len() / 2]).is_err());
+    }
+}
@@ -0,0 +1,294 @@
+/**
+ * Co-owned Vaults
+ * Several owners, with m of them needed to change what protects the vault
+ *
+ * A vault's owner can add co-owners and a threshold. Co-owners count as
+ * owners everywhere an owner is checked; on top of that, the actions in
+ * QUORUM_ACTIONS (policies, beneficiaries, triggers, the owner set itself)
+ * only apply once `threshold` distinct owners have agreed to the exact
+ * change.
+ *
+ * Agreement is per request body. One owner drafts the signed request;
+ * the others each approve its body's SHA-256 with a signed request of
+ * their own; the drafter then submits it and counts as one approval.
+ * Approvals expire rather than being spent, so a submission that fails
+ * validation can be retried, and re-submitting the same body sets the
+ * same state. Only owners in the current set count.
+ */
+
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::auth::normalize_address;
+use crate::store::Store;
+
+const SETS_NAMESPACE: &str = "vault_co_owners";
+const APPROVALS_NAMESPACE: &str = "owner_approvals";
+pub const MAX_OWNERS: usize = 10;
+pub const APPROVAL_TTL_SECS: u64 = 24 * 60 * 60;
+
+/// Actions a co-owned vault only takes with its owner quorum
+pub const QUORUM_ACTIONS: &[&str] = &[
+    "approvals.guardians",
+    "beneficiaries.set",
+    "check_in.schedule",
+    "co_owners.set",
+    "duress.configure",
+    "escalation.set",
+    "items.policies",
+    "redaction.set",
+    "vault.register",
+    "vault_delete",
+];
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CoOwnerSet {
+    pub vault_id: String,
+    /// Normalized, sorted
+    pub owners: Vec<String>,
+    pub threshold: usize,
+    pub updated_by: String,
+    pub updated_at: u64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct OwnerApproval {
+    pub vault_id: String,
+    pub action: String,
+    pub request_sha256: String, // Hex
+    pub owner: String,
+    pub expires_at: u64,
+}
+
+pub struct CoOwnerService {
+    store: Arc<Store>,
+}
+
+impl CoOwnerService {
+    pub fn new(store: Arc<Store>) -> Self {
+        Self { store }
+    }
+
+    /// Replace the vault's owner set; the caller must be an owner and, on
+    /// a co-owned vault, have the quorum
+    pub fn set_owners(
+        &self,
+        vault_id: &str,
+        caller: &str,
+        owners: &[String],
+        threshold: usize,
+    ) -> Result<CoOwnerSet, String> {
+        let mut owners: Vec<String> = owners.iter().map(|o| normalize_address(o)).collect();
+        owners.sort();
+        owners.dedup();
+        if owners.len() < 2 || owners.len() > MAX_OWNERS {
+            return Err(format!(
+                "A co-owned vault has 2 to {} distinct owners",
+                MAX_OWNERS
+            ));
+        }
+        if !owners.iter().any(|o| o == caller) {
+            return Err("The owner making the change must stay an owner".to_string());
+        }
+        if threshold == 0 || threshold > owners.len() {
+            return Err(format!("Threshold must be 1 to {}", owners.len()));
+        }
+
+        let set = CoOwnerSet {
+            vault_id: vault_id.to_string(),
+            owners,
+            threshold,
+            updated_by: caller.to_string(),
+            updated_at: now(),
+        };
+        self.store.put(SETS_NAMESPACE, vault_id, &set)?;
+        Ok(set)
+    }
+
+    pub fn co_owners(&self, vault_id: &str) -> Result<Option<CoOwnerSet>, String> {
+        self.store.get(SETS_NAMESPACE, vault_id)
+    }
+
+    /// Record `owner`'s agreement to submitting `action` with the body
+    /// hashing to `request_sha256`
+    pub fn approve(
+        &self,
+        vault_id: &str,
+        owner: &str,
+        action: &str,
+        request_sha256: &str,
+    ) -> Result<OwnerApproval, String> {
+        if !QUORUM_ACTIONS.contains(&action) {
+            return Err(format!("{} doesn't need owner approval", action));
+        }
+        let request_sha256 = request_sha256.trim_start_matches("0x").to_lowercase();
+        if hex::decode(&request_sha256).map_or(true, |d| d.len() != 32) {
+            return Err("request_sha256 must be 32 hex-encoded bytes".to_string());
+        }
+
+        let approval = OwnerApproval {
+            vault_id: vault_id.to_string(),
+            action: action.to_string(),
+            request_sha256,
+            owner: owner.to_string(),
+            expires_at: now() + APPROVAL_TTL_SECS,
+        };
+        self.store.put(
+            APPROVALS_NAMESPACE,
+            &format!(
+                "{}:{}:{}:{}",
+                vault_id, approval.action, approval.request_sha256, owner
+            ),
+            &approval,
+        )?;
+        Ok(approval)
+    }
+
+    /// Owners in `owners` with a current approval of the exact request
+    pub fn approvers(
+        &self,
+        vault_id: &str,
+        action: &str,
+        request_sha256: &str,
+        owners: &[String],
+    ) -> Result<Vec<String>, String> {
+        let now = now();
+        let mut approvers: Vec<String> = self
+            .store
+            .list::<OwnerApproval>(APPROVALS_NAMESPACE)?
+            .into_iter()
+            .map(|(_, approval)| approval)
+            .filter(|a| {
+                a.vault_id == vault_id
+                    && a.action == action
+                    && a.request_sha256 == request_sha256
+                    && a.expires_at > now
+                    && owners.contains(&a.owner)
+            })
+            .map(|a| a.owner)
+            .collect();
+        approvers.sort();
+        approvers.dedup();
+        Ok(approvers)
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service(name: &str) -> CoOwnerService {
+        let dir = std::env::temp_dir().join(format!("lumina-co-owner-tests-{}-{}", name, now()));
+        CoOwnerService::new(Arc::new(Store::open(dir).unwrap()))
+    }
+
+    fn owners(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| normalize_address(n)).collect()
+    }
+
+    #[test]
+    fn approvals_count_once_per_current_owner_for_the_exact_request() {
+        let co_owners = service("quorum");
+        let [alice, bob, carol] = owners(&["0xa", "0xb", "0xc"]).try_into().unwrap();
+        let set = co_owners
+            .set_owners(
+                "vault-1",
+                &alice,
+                &[alice.clone(), bob.clone(), carol.clone()],
+                2,
+            )
+            .unwrap();
+        assert_eq!(set.owners, [alice.clone(), bob.clone(), carol.clone()]);
+
+        let body = "ab".repeat(32);
+        co_owners
+            .approve(
+                "vault-1",
+                &bob,
+                "beneficiaries.set",
+                &format!("0x{}", body.to_uppercase()),
+            )
+            .unwrap();
+        co_owners
+            .approve("vault-1", &bob, "beneficiaries.set", &body)
+            .unwrap();
+        co_owners
+            .approve("vault-1", &carol, "beneficiaries.set", &body)
+            .unwrap();
+        let approvers = co_owners
+            .approvers("vault-1", "beneficiaries.set", &body, &set.owners)
+            .unwrap();
+        assert_eq!(approvers, [bob.clone(), carol.clone()]);
+
+        // Approving one change approves nothing else
+        let other = "cd".repeat(32);
+        let count = |vault_id, action, request_sha256: &str, owners: &[String]| {
+            co_owners
+                .approvers(vault_id, action, request_sha256, owners)
+                .unwrap()
+                .len()
+        };
+        assert_eq!(
+            count("vault-1", "beneficiaries.set", &other, &set.owners),
+            0
+        );
+        assert_eq!(count("vault-1", "escalation.set", &body, &set.owners), 0);
+        assert_eq!(count("vault-2", "beneficiaries.set", &body, &set.owners), 0);
+
+        // Owners removed from the set no longer count
+        let set = co_owners
+            .set_owners("vault-1", &alice, &[alice.clone(), bob.clone()], 2)
+            .unwrap();
+        assert_eq!(count("vault-1", "beneficiaries.set", &body, &set.owners), 1);
+    }
+
+    #[test]
+    fn malformed_sets_and_approvals_are_refused() {
+        let co_owners = service("refused");
+        let [alice, bob] = owners(&["0xa", "0xb"]).try_into().unwrap();
+
+        let set = |caller: &str, owners: &[String], threshold| {
+            co_owners.set_owners("vault-1", caller, owners, threshold)
+        };
+        assert!(set(&alice, &[alice.clone(), alice.clone()], 1).is_err());
+        assert!(set(&alice, &owners(&["0xb", "0xc"]), 1).is_err());
+        assert!(set(&alice, &[alice.clone(), bob.clone()], 0).is_err());
+        assert!(set(&alice, &[alice.clone(), bob.clone()], 3).is_err());
+        let crowd: Vec<String> = (1..=MAX_OWNERS + 1)
+            .map(|n| normalize_address(&n.to_string()))
+            .collect();
+        assert!(set(&crowd[0], &crowd, 2).is_err());
+
+        let body = "ab".repeat(32);
+        let approve = |action, request_sha256: &str| {
+            co_owners.approve("vault-1", &bob, action, request_sha256)
+        };
+        assert!(approve("items.read", &body)
+            .err()
+            .unwrap()
+            .contains("doesn't need"));
+        assert!(approve("beneficiaries.set", "abcd").is_err());
+        assert!(approve("beneficiaries.set", &"zz".repeat(32)).is_err());
+
+        // Approvals lapse rather than waiting for a submission forever
+        let mut approval = approve("beneficiaries.set", &body).unwrap();
+        approval.expires_at = now() - 1;
+        let key = format!("vault-1:beneficiaries.set:{}:{}", body, bob);
+        co_owners
+            .store
+            .put(APPROVALS_NAMESPACE, &key, &approval)
+            .unwrap();
+        let approvers = co_owners
+            .approvers("vault-1", "beneficiaries.set", &body, &[alice, bob])
+            .unwrap();
+        assert!(approvers.is_empty());
+    }
+}
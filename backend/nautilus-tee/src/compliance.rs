@@ -0,0 +1,197 @@
+/**
+ * Compliance Evidence
+ * One signed document describing the controls this enclave runs with
+ *
+ * For auditors: the image's measurements, the history of its long-lived
+ * keys, a fingerprint of its configuration, a root over every vault's
+ * audit chain, the circuits it proves with and the policy settings it
+ * enforces. The enclave identity key signs
+ *
+ *   DOMAIN || sha256(JSON of the report)
+ *
+ * and the route also binds that digest into an attestation, so the
+ * report can be checked offline against the identity key or against the
+ * measurements themselves.
+ */
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::attestation::Measurements;
+use crate::audit::{AuditRoot, AuditTrail};
+use crate::build_info::{self, BuildInfo};
+use crate::circuits::CircuitVersion;
+use crate::config::Config;
+use crate::keys::identity::EnclaveIdentity;
+use crate::keys::rotation::{RotationEvent, RotationLog};
+
+pub const OPERATION: &str = "compliance_report";
+const DOMAIN: &[u8] = b"lumina-compliance-v1";
+
+/// Settings the enclave enforces, as configured at startup
+#[derive(Clone, Serialize)]
+pub struct PolicySettings {
+    pub environment: String,
+    pub admins: Vec<String>,
+    pub signing_purposes: Vec<String>,
+    pub signature_window_secs: u64,
+    pub attestation_ttl_secs: u64,
+    /// Per-operation overrides
+    pub attestation_max_age_secs: BTreeMap<String, u64>,
+    pub key_release_allow_debug: bool,
+    pub key_release_pcr8_signers: Vec<String>,
+    pub migration_measurements: Vec<String>,
+    pub peer_measurements: Vec<String>,
+    pub template_import_measurements: Vec<String>,
+    /// Recovery keys needed out of those registered, and admin sign-offs;
+    /// None when escrow is off
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub escrow: Option<EscrowSettings>,
+    pub analytics_epsilon: f64,
+    pub analytics_min_count: u64,
+    pub erasure_grace_secs: u64,
+    pub rule_packs: Vec<String>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct EscrowSettings {
+    pub threshold: u8,
+    pub recipients: usize,
+    pub admin_quorum: usize,
+}
+
+#[derive(Clone, Serialize)]
+pub struct KeyHistory {
+    pub kind: String,
+    pub scope: String,
+    pub events: Vec<RotationEvent>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct ComplianceReport {
+    pub generated_at: u64,
+    pub build: BuildInfo,
+    pub identity_key_id: String,
+    pub measurements: Measurements,
+    pub key_rotations: Vec<KeyHistory>,
+    pub config_sha256: String,
+    pub audit: AuditRoot,
+    pub circuits: Vec<CircuitVersion>,
+    pub policy: PolicySettings,
+}
+
+/// A report with the identity key's signature over its digest
+#[derive(Clone, Serialize)]
+pub struct SignedReport {
+    pub report: ComplianceReport,
+    pub report_sha256: String, // Hex
+    pub signature: String,     // Hex Ed25519 over DOMAIN || digest
+}
+
+pub struct ComplianceService {
+    identity: Arc<EnclaveIdentity>,
+    rotations: Arc<RotationLog>,
+    audit: Arc<AuditTrail>,
+    /// Rotation log scopes of the long-lived keys
+    key_scopes: Vec<(String, String)>,
+    config_sha256: String,
+    policy: PolicySettings,
+}
+
+impl ComplianceService {
+    pub fn new(
+        config: &Config,
+        identity: Arc<EnclaveIdentity>,
+        rotations: Arc<RotationLog>,
+        audit: Arc<AuditTrail>,
+    ) -> Self {
+        Self {
+            identity,
+            rotations,
+            audit,
+            key_scopes: vec![
+                ("identity".to_string(), config.role.clone()),
+                ("root".to_string(), "enclave".to_string()),
+            ],
+            config_sha256: config.fingerprint(),
+            policy: PolicySettings::from_config(config),
+        }
+    }
+
+    pub fn report(
+        &self,
+        measurements: Measurements,
+        circuits: &[CircuitVersion],
+    ) -> Result<SignedReport, String> {
+        let key_rotations = self
+            .key_scopes
+            .iter()
+            .map(|(kind, scope)| {
+                Ok(KeyHistory {
+                    kind: kind.clone(),
+                    scope: scope.clone(),
+                    events: self.rotations.history(kind, scope)?,
+                })
+            })
+            .collect::<Result<_, String>>()?;
+        let report = ComplianceReport {
+            generated_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            build: build_info::build_info(),
+            identity_key_id: self.identity.key_id(),
+            measurements,
+            key_rotations,
+            config_sha256: self.config_sha256.clone(),
+            audit: self.audit.root()?,
+            circuits: circuits.to_vec(),
+            policy: self.policy.clone(),
+        };
+
+        let digest = Sha256::digest(
+            serde_json::to_vec(&report)
+                .map_err(|e| format!("Failed to serialize compliance report: {}", e))?,
+        );
+        let signature = self.identity.sign(&[DOMAIN, &digest[..]].concat());
+        Ok(SignedReport {
+            report,
+            report_sha256: hex::encode(digest),
+            signature: hex::encode(signature.to_bytes()),
+        })
+    }
+}
+
+impl PolicySettings {
+    fn from_config(config: &Config) -> Self {
+        Self {
+            environment: format!("{:?}", config.environment).to_lowercase(),
+            admins: config.admins.clone(),
+            signing_purposes: config.signing_purposes.clone(),
+            signature_window_secs: config.signature_window_secs,
+            attestation_ttl_secs: config.attestation_ttl.as_secs(),
+            attestation_max_age_secs: config
+                .attestation_max_age
+                .iter()
+                .map(|(operation, age)| (operation.clone(), age.as_secs()))
+                .collect(),
+            key_release_allow_debug: config.key_release_allow_debug,
+            key_release_pcr8_signers: config.key_release_pcr8_signers.clone(),
+            migration_measurements: config.migration_measurements.clone(),
+            peer_measurements: config.peer_measurements.clone(),
+            template_import_measurements: config.template_import_measurements.clone(),
+            escrow: config.escrow.as_ref().map(|escrow| EscrowSettings {
+                threshold: escrow.threshold,
+                recipients: escrow.recipients.len(),
+                admin_quorum: escrow.admin_quorum,
+            }),
+            analytics_epsilon: config.analytics_epsilon,
+            analytics_min_count: config.analytics_min_count,
+            erasure_grace_secs: config.erasure_grace.as_secs(),
+            rule_packs: config.rule_packs.clone(),
+        }
+    }
+}
@@ -0,0 +1,796 @@
+/**
+ * Server Configuration
+ * Loaded once at startup from the process environment
+ */
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::auth::normalize_address;
+
+/// How the server is reached: plain TCP (dev, parent-terminated deployments)
+/// or VSOCK when running inside a Nitro Enclave.
+#[derive(Clone, Debug)]
+pub enum Transport {
+    Tcp(SocketAddr),
+    Vsock { port: u32 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Environment {
+    Development,
+    Staging,
+    Production,
+}
+
+#[derive(Clone, Debug)]
+pub struct TlsConfig {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+    /// CA bundle used to verify client certificates (mutual TLS)
+    pub client_ca_path: Option<PathBuf>,
+    /// Reject clients that don't present a certificate when a CA is configured
+    pub client_auth_required: bool,
+    /// Offer h2 in ALPN
+    pub http2: bool,
+    /// How often certificate files are checked for rotation
+    pub reload_interval: Duration,
+}
+
+/// Connection handling shared by the TCP and VSOCK listeners
+#[derive(Clone, Debug)]
+pub struct HttpConfig {
+    /// Serve HTTP/2 alongside HTTP/1.1: negotiated by ALPN over TLS, by
+    /// prior knowledge otherwise
+    pub http2: bool,
+    /// Keep HTTP/1.1 connections open between requests
+    pub keep_alive: bool,
+    /// HTTP/2 ping interval on idle connections; `None` never pings
+    pub h2_keep_alive_interval: Option<Duration>,
+    /// How long a ping may go unanswered before the connection is closed
+    pub h2_keep_alive_timeout: Duration,
+    pub h2_max_concurrent_streams: u32,
+    /// Largest request header section, in either protocol
+    pub max_header_bytes: usize,
+    /// How long a client may take to send its request headers
+    pub header_read_timeout: Duration,
+}
+
+#[derive(Clone, Debug)]
+pub struct CorsConfig {
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Vec<String>,
+    pub max_age_secs: u64,
+}
+
+/// Where the parent instance serves the encrypted secrets bundle at startup
+#[derive(Clone, Debug)]
+pub enum BootstrapSource {
+    Vsock { port: u32 },
+    Tcp(String),
+}
+
+/// A line-oriented relay the parent instance runs for the enclave, e.g.
+/// handing notification jobs to providers
+#[derive(Clone, Debug)]
+pub enum ParentRelay {
+    Vsock { port: u32 },
+    Tcp(String),
+}
+
+/// Credentials delivered by the bootstrap protocol; never read from plain
+/// environment variables outside development
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct SecretsConfig {
+    pub chain_rpc_key: Option<String>,
+    pub webhook_signing_secret: Option<String>,
+    pub sponsor_credentials: Option<String>,
+    /// Base64 32-byte root of the per-vault key hierarchy
+    pub root_key: Option<String>,
+    /// Base64 seed the enclave identity key is derived from (>= 32 bytes)
+    pub identity_seed: Option<String>,
+    /// Base64 X25519 secret that template backups are wrapped to
+    pub template_backup_key: Option<String>,
+}
+
+/// Break-glass export of enclave secrets to offline recovery keys
+#[derive(Clone, Debug)]
+pub struct EscrowConfig {
+    /// Base64 X25519 recovery public keys, fixed at deploy time
+    pub recipients: Vec<String>,
+    /// Recovery keys needed to reassemble an export (m of n)
+    pub threshold: u8,
+    /// Distinct admins who must sign off before an export runs
+    pub admin_quorum: usize,
+}
+
+/// In-enclave face embedding model (requires the `onnx` feature)
+#[derive(Clone, Debug)]
+pub struct FaceModelConfig {
+    pub path: PathBuf,
+    /// Hex SHA-256 the model file must match; required outside development
+    pub sha256: Option<String>,
+    pub name: String,
+    pub version: String,
+}
+
+/// On-chain heartbeat events, read through a parent relay to a Sui node
+#[derive(Clone, Debug)]
+pub struct HeartbeatConfig {
+    pub relay: ParentRelay,
+    /// Fully qualified Move event type, e.g. `0x..::heartbeat::CheckedIn`
+    pub event_type: String,
+    /// Event field holding the vault id or a bound object id
+    pub vault_field: String,
+    pub poll: Duration,
+}
+
+/// Submitter and confirmation events for on-chain intents, through a
+/// parent relay to a Sui node
+#[derive(Clone, Debug)]
+pub struct OutboxConfig {
+    pub relay: ParentRelay,
+    /// Fully qualified Move event the contract emits per recorded intent,
+    /// with an `intent_id` field
+    pub confirmed_event: String,
+    /// How often due intents are submitted
+    pub dispatch: Duration,
+    /// How often confirmation events are read
+    pub reconcile: Duration,
+}
+
+/// Encrypted archival to S3 through an agent on the parent
+#[derive(Clone, Debug)]
+pub struct ArchiveConfig {
+    pub relay: ParentRelay,
+    /// How often audit segments and settled jobs are archived
+    pub sweep: Duration,
+    /// Age past which audit entries (all but each vault's latest) move out
+    pub audit_after: Duration,
+    /// Time since settling past which notification jobs and intents move
+    /// out; under their retention, or they are pruned first
+    pub jobs_after: Duration,
+    /// How often the whole store is snapshotted
+    pub snapshot_every: Duration,
+}
+
+/// Proving keys and the signed manifest pinning their digests
+#[derive(Clone, Debug)]
+pub struct CircuitArtifactsConfig {
+    pub dir: PathBuf,
+    pub manifest: PathBuf,
+    /// Hex Ed25519 key; `<manifest>.sig` holds its hex signature
+    pub manifest_key: String,
+    /// Also check each key is the output of its ceremony's final contribution
+    pub verify_ceremony: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub environment: Environment,
+    /// Deployment role; part of the enclave identity derivation
+    pub role: String,
+    pub transport: Transport,
+    /// TLS for the TCP listener, required outside development; VSOCK traffic
+    /// is already confined to the host
+    pub tls: Option<TlsConfig>,
+    pub http: HttpConfig,
+    /// Directory for persisted enclave state
+    pub data_dir: PathBuf,
+    /// Store namespaces persisted zstd-compressed
+    pub store_compressed: Vec<String>,
+    /// Maximum clock skew accepted on signed request timestamps
+    pub signature_window_secs: u64,
+    /// Browser origin policy; `None` disables CORS (always the case over VSOCK)
+    pub cors: Option<CorsConfig>,
+    pub bootstrap: Option<BootstrapSource>,
+    pub secrets: SecretsConfig,
+    /// Sui addresses allowed to authorize administrative operations
+    pub admins: Vec<String>,
+    /// `None` when no recovery keys are registered
+    pub escrow: Option<EscrowConfig>,
+    /// Purposes the general-purpose signing endpoint will sign for
+    pub signing_purposes: Vec<String>,
+    /// Lifetime of an encrypted session's keys
+    pub session_ttl: Duration,
+    /// PCR0 values whose template backups may be imported; empty means
+    /// only backups from this same image
+    pub template_import_measurements: Vec<String>,
+    /// PCR0 values of enclaves vaults may migrate to or from; empty means
+    /// only this same image
+    pub migration_measurements: Vec<String>,
+    /// PCR0 values of enclaves that may open attested channels with this
+    /// one or call its peer routes until a signed measurement allowlist is
+    /// installed; empty means only this same image
+    pub peer_measurements: Vec<String>,
+    /// Hex Ed25519 key measurement allowlists must be signed with
+    pub allowlist_key: Option<String>,
+    /// Hex Ed25519 key Cedar policy bundles must be signed with
+    pub policy_bundle_key: Option<String>,
+    /// Validity of attestations for operations without their own limit
+    pub attestation_ttl: Duration,
+    /// Per-operation attestation validity, e.g. "vault_release=300"
+    pub attestation_max_age: Vec<(String, Duration)>,
+    /// Hex SHA-256 of the Nitro root certificate peer attestations must
+    /// chain to; `None` pins the AWS commercial root
+    pub nitro_root_sha256: Option<String>,
+    /// Whether a debug-mode enclave may unwrap vault keys
+    pub key_release_allow_debug: bool,
+    /// PCR8 values of trusted image signers; empty accepts any signer
+    pub key_release_pcr8_signers: Vec<String>,
+    /// How often template ages are checked against refresh policies
+    pub template_refresh_sweep: Duration,
+    /// `None` queues notifications without delivering them
+    pub notification_relay: Option<ParentRelay>,
+    /// Sui JSON-RPC for reads such as owners' chain activity; `None` leaves
+    /// liveness on its placeholder chain signal
+    pub chain_relay: Option<ParentRelay>,
+    /// How often pending notifications are handed to the relay
+    pub notification_dispatch: Duration,
+    /// How often check-in deadlines are checked for due reminders
+    pub check_in_sweep: Duration,
+    /// How often the task scheduler looks for due tasks
+    pub task_tick: Duration,
+    /// Per-task schedule overrides, e.g. "check_in.reminders=0 9,17 * * *";
+    /// separated by semicolons, since cron lists use commas
+    pub task_schedules: Vec<String>,
+    /// Per-task jitter overrides, e.g. "heartbeat.poll=5"
+    pub task_jitter: Vec<String>,
+    /// `None` leaves check-ins to direct requests
+    pub heartbeat: Option<HeartbeatConfig>,
+    /// `None` queues on-chain intents without submitting them
+    pub outbox: Option<OutboxConfig>,
+    /// `None` keeps everything in enclave storage
+    pub archive: Option<ArchiveConfig>,
+    /// `None` leaves face matching on the placeholder matcher
+    pub face_model: Option<FaceModelConfig>,
+    /// `claim_type@version=support` overrides of circuit versions
+    pub circuit_support: Vec<String>,
+    /// `None` leaves zkeys unpinned, which only development serves
+    pub circuit_artifacts: Option<CircuitArtifactsConfig>,
+    /// `auto`, `serial` or `parallel`
+    pub msm_backend: String,
+    /// Proofs generated at once
+    pub proof_workers: usize,
+    /// Proofs running or queued before even unlock-critical ones are refused
+    pub proof_queue_capacity: usize,
+    /// Queue depth past which exploratory proofs are refused
+    pub proof_queue_watermark: usize,
+    /// Largest body accepted on routes that carry vault blobs
+    pub max_payload_bytes: usize,
+    /// `route=deprecated_at/sunset_at` entries, in unix seconds
+    pub deprecated_routes: Vec<String>,
+    /// `jurisdiction=contest_days[/claim_type@version+...]` rule packs
+    pub rule_packs: Vec<String>,
+    /// `dependency=attempts/base_ms/max_ms[/jitter_pct]` retry overrides
+    pub retry_policies: Vec<String>,
+    /// `dependency=threshold/cooldown_secs` circuit breaker overrides
+    pub circuit_breakers: Vec<String>,
+    /// Dead letters past which the queue alerts
+    pub dead_letter_alert: usize,
+    /// Memory all in-flight requests and caches may hold together
+    pub memory_budget_bytes: usize,
+    /// Memory one request may hold
+    pub request_memory_bytes: usize,
+    /// How long a request waits for room in the memory budget
+    pub memory_wait: Duration,
+    /// Witness buffer reserved per proof
+    pub proof_witness_bytes: usize,
+    /// Privacy budget per metric of each analytics report
+    pub analytics_epsilon: f64,
+    /// Analytics buckets with fewer (noisy) members are suppressed
+    pub analytics_min_count: u64,
+    /// How long a requested erasure waits, for guardians to hear of it and
+    /// the owner to change their mind
+    pub erasure_grace: Duration,
+}
+
+impl Config {
+    pub fn from_env() -> Result<Self, String> {
+        let environment = match env_or("TEE_ENV", "production").as_str() {
+            "development" | "dev" => Environment::Development,
+            "staging" => Environment::Staging,
+            "production" | "prod" => Environment::Production,
+            other => return Err(format!("Unsupported TEE_ENV: {}", other)),
+        };
+
+        let transport = match env_or("TEE_TRANSPORT", "tcp").as_str() {
+            "tcp" => {
+                let addr = env_or("TEE_LISTEN_ADDR", "0.0.0.0:8080");
+                Transport::Tcp(
+                    addr.parse()
+                        .map_err(|e| format!("Invalid TEE_LISTEN_ADDR {}: {}", addr, e))?,
+                )
+            }
+            "vsock" => Transport::Vsock {
+                port: parse_env("TEE_VSOCK_PORT", 5005)?,
+            },
+            other => return Err(format!("Unsupported TEE_TRANSPORT: {}", other)),
+        };
+
+        let http = HttpConfig::from_env()?;
+        let tls = match (std::env::var("TEE_TLS_CERT"), std::env::var("TEE_TLS_KEY")) {
+            (Ok(cert), Ok(key)) => Some(TlsConfig {
+                cert_path: cert.into(),
+                key_path: key.into(),
+                client_ca_path: std::env::var("TEE_TLS_CLIENT_CA").ok().map(PathBuf::from),
+                client_auth_required: parse_env("TEE_TLS_CLIENT_AUTH_REQUIRED", true)?,
+                http2: http.http2,
+                reload_interval: Duration::from_secs(parse_env("TEE_TLS_RELOAD_SECS", 60)?),
+            }),
+            (Err(_), Err(_)) => None,
+            _ => return Err("TEE_TLS_CERT and TEE_TLS_KEY must be set together".to_string()),
+        };
+
+        if tls.is_some() && matches!(transport, Transport::Vsock { .. }) {
+            return Err("TLS is only supported on the TCP transport".to_string());
+        }
+        if tls.is_none()
+            && matches!(transport, Transport::Tcp(_))
+            && environment != Environment::Development
+        {
+            return Err(
+                "TEE_TLS_CERT and TEE_TLS_KEY are required on TCP outside development".to_string(),
+            );
+        }
+
+        let cors = match transport {
+            Transport::Vsock { .. } => None,
+            Transport::Tcp(_) => CorsConfig::from_env(environment)?,
+        };
+
+        let bootstrap = match env_or("TEE_BOOTSTRAP", "off").as_str() {
+            "off" => None,
+            "vsock" => Some(BootstrapSource::Vsock {
+                port: parse_env("TEE_BOOTSTRAP_PORT", 7000)?,
+            }),
+            "tcp" => Some(BootstrapSource::Tcp(env_or(
+                "TEE_BOOTSTRAP_ADDR",
+                "127.0.0.1:7000",
+            ))),
+            other => return Err(format!("Unsupported TEE_BOOTSTRAP: {}", other)),
+        };
+
+        let notification_relay = match env_or("TEE_NOTIFY_RELAY", "off").as_str() {
+            "off" => None,
+            "vsock" => Some(ParentRelay::Vsock {
+                port: parse_env("TEE_NOTIFY_RELAY_PORT", 7001)?,
+            }),
+            "tcp" => Some(ParentRelay::Tcp(env_or(
+                "TEE_NOTIFY_RELAY_ADDR",
+                "127.0.0.1:7001",
+            ))),
+            other => return Err(format!("Unsupported TEE_NOTIFY_RELAY: {}", other)),
+        };
+
+        let chain_relay = match env_or("TEE_CHAIN_RELAY", "off").as_str() {
+            "off" => None,
+            "vsock" => Some(ParentRelay::Vsock {
+                port: parse_env("TEE_CHAIN_RELAY_PORT", 7004)?,
+            }),
+            "tcp" => Some(ParentRelay::Tcp(env_or(
+                "TEE_CHAIN_RELAY_ADDR",
+                "127.0.0.1:7004",
+            ))),
+            other => return Err(format!("Unsupported TEE_CHAIN_RELAY: {}", other)),
+        };
+
+        let secrets = match environment {
+            Environment::Development => SecretsConfig {
+                chain_rpc_key: std::env::var("TEE_CHAIN_RPC_KEY").ok(),
+                webhook_signing_secret: std::env::var("TEE_WEBHOOK_SIGNING_SECRET").ok(),
+                sponsor_credentials: std::env::var("TEE_SPONSOR_CREDENTIALS").ok(),
+                root_key: std::env::var("TEE_ROOT_KEY").ok(),
+                identity_seed: std::env::var("TEE_IDENTITY_SEED").ok(),
+                template_backup_key: std::env::var("TEE_TEMPLATE_BACKUP_KEY").ok(),
+            },
+            Environment::Staging | Environment::Production => SecretsConfig::default(),
+        };
+
+        let admins: Vec<String> = env_list("TEE_ADMIN_ADDRESSES", "")
+            .iter()
+            .map(|a| normalize_address(a))
+            .collect();
+        let escrow = EscrowConfig::from_env(admins.len())?;
+        let face_model = FaceModelConfig::from_env(environment)?;
+        let circuit_artifacts = CircuitArtifactsConfig::from_env()?;
+        let heartbeat = HeartbeatConfig::from_env()?;
+        let outbox = OutboxConfig::from_env()?;
+        let archive = ArchiveConfig::from_env()?;
+        let proof_workers = parse_env(
+            "TEE_PROOF_WORKERS",
+            std::thread::available_parallelism().map_or(1, |n| n.get()),
+        )?;
+        let proof_queue_capacity = parse_env("TEE_PROOF_QUEUE_CAPACITY", 32)?;
+        let proof_queue_watermark =
+            parse_env("TEE_PROOF_QUEUE_WATERMARK", proof_queue_capacity * 3 / 4)?;
+        if proof_workers == 0 || proof_queue_watermark > proof_queue_capacity {
+            return Err(
+                "TEE_PROOF_WORKERS must be positive and the watermark within capacity".to_string(),
+            );
+        }
+
+        // Three quarters of the enclave's memory, leaving the rest to the
+        // server itself
+        let memory_budget_bytes = parse_env(
+            "TEE_MEMORY_BUDGET_BYTES",
+            total_memory_bytes().map_or(2 * 1024 * 1024 * 1024, |total| total / 4 * 3),
+        )?;
+        let request_memory_bytes = parse_env("TEE_REQUEST_MEMORY_BYTES", memory_budget_bytes / 2)?;
+        if request_memory_bytes > memory_budget_bytes {
+            return Err("TEE_REQUEST_MEMORY_BYTES exceeds the memory budget".to_string());
+        }
+        let analytics_epsilon: f64 = parse_env("TEE_ANALYTICS_EPSILON", 1.0)?;
+        if !(analytics_epsilon > 0.0 && analytics_epsilon.is_finite()) {
+            return Err("TEE_ANALYTICS_EPSILON must be positive".to_string());
+        }
+
+        Ok(Self {
+            environment,
+            role: env_or("TEE_ROLE", "primary"),
+            transport,
+            tls,
+            http,
+            data_dir: env_or("TEE_DATA_DIR", "./data").into(),
+            store_compressed: env_list("TEE_STORE_COMPRESS", "audit_trail,escrow_exports"),
+            signature_window_secs: parse_env("TEE_SIGNATURE_WINDOW_SECS", 300)?,
+            cors,
+            bootstrap,
+            secrets,
+            admins,
+            escrow,
+            signing_purposes: env_list("TEE_SIGNING_PURPOSES", ""),
+            session_ttl: Duration::from_secs(parse_env("TEE_SESSION_TTL_SECS", 900)?),
+            template_import_measurements: env_list("TEE_TEMPLATE_IMPORT_MEASUREMENTS", ""),
+            migration_measurements: env_list("TEE_MIGRATION_MEASUREMENTS", ""),
+            peer_measurements: env_list("TEE_PEER_MEASUREMENTS", ""),
+            allowlist_key: std::env::var("TEE_ALLOWLIST_KEY").ok(),
+            policy_bundle_key: std::env::var("TEE_POLICY_BUNDLE_KEY").ok(),
+            attestation_ttl: Duration::from_secs(parse_env("TEE_ATTESTATION_TTL_SECS", 3600)?),
+            attestation_max_age: parse_max_ages(&env_list("TEE_ATTESTATION_MAX_AGE", ""))?,
+            nitro_root_sha256: std::env::var("TEE_NITRO_ROOT_SHA256").ok(),
+            key_release_allow_debug: parse_env(
+                "TEE_KEY_RELEASE_ALLOW_DEBUG",
+                environment == Environment::Development,
+            )?,
+            key_release_pcr8_signers: env_list("TEE_KEY_RELEASE_PCR8_SIGNERS", ""),
+            template_refresh_sweep: Duration::from_secs(parse_env(
+                "TEE_TEMPLATE_REFRESH_SWEEP_SECS",
+                3600,
+            )?),
+            notification_relay,
+            chain_relay,
+            notification_dispatch: Duration::from_secs(parse_env("TEE_NOTIFY_DISPATCH_SECS", 30)?),
+            check_in_sweep: Duration::from_secs(parse_env("TEE_CHECK_IN_SWEEP_SECS", 300)?),
+            task_tick: Duration::from_secs(parse_env("TEE_TASK_TICK_SECS", 5)?),
+            task_schedules: env_or("TEE_TASK_SCHEDULES", "")
+                .split(';')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+                .collect(),
+            task_jitter: env_list("TEE_TASK_JITTER", ""),
+            heartbeat,
+            outbox,
+            archive,
+            face_model,
+            circuit_support: env_list("TEE_CIRCUIT_SUPPORT", ""),
+            circuit_artifacts,
+            msm_backend: env_or("TEE_MSM_BACKEND", "auto"),
+            proof_workers,
+            proof_queue_capacity,
+            proof_queue_watermark,
+            max_payload_bytes: parse_env("TEE_MAX_PAYLOAD_BYTES", 512 * 1024 * 1024)?,
+            deprecated_routes: env_list("TEE_DEPRECATED_ROUTES", ""),
+            rule_packs: env_list("TEE_RULE_PACKS", ""),
+            retry_policies: env_list("TEE_RETRY_POLICIES", ""),
+            circuit_breakers: env_list("TEE_CIRCUIT_BREAKERS", ""),
+            dead_letter_alert: parse_env("TEE_DEAD_LETTER_ALERT", 25)?,
+            memory_budget_bytes,
+            request_memory_bytes,
+            memory_wait: Duration::from_millis(parse_env("TEE_MEMORY_WAIT_MS", 5000)?),
+            proof_witness_bytes: parse_env("TEE_PROOF_WITNESS_BYTES", 64 * 1024 * 1024)?,
+            analytics_epsilon,
+            analytics_min_count: parse_env("TEE_ANALYTICS_MIN_COUNT", 10)?,
+            erasure_grace: Duration::from_secs(parse_env("TEE_ERASURE_GRACE_SECS", 7 * 86400)?),
+        })
+    }
+
+    /// Hex SHA-256 of the configuration's Debug form, which names the
+    /// secrets loaded but never their values. Stable within one build.
+    pub fn fingerprint(&self) -> String {
+        hex::encode(Sha256::digest(format!("{:?}", self)))
+    }
+}
+
+impl SecretsConfig {
+    /// Names of the secrets that are present, for startup logging
+    pub fn loaded(&self) -> Vec<&'static str> {
+        [
+            ("chain_rpc_key", self.chain_rpc_key.is_some()),
+            (
+                "webhook_signing_secret",
+                self.webhook_signing_secret.is_some(),
+            ),
+            ("sponsor_credentials", self.sponsor_credentials.is_some()),
+            ("root_key", self.root_key.is_some()),
+            ("identity_seed", self.identity_seed.is_some()),
+            ("template_backup_key", self.template_backup_key.is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(name, present)| present.then_some(name))
+        .collect()
+    }
+}
+
+impl std::fmt::Debug for SecretsConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SecretsConfig")
+            .field("loaded", &self.loaded())
+            .finish_non_exhaustive()
+    }
+}
+
+impl CorsConfig {
+    /// Origins must be listed explicitly outside development; with none
+    /// configured, CORS stays off and browsers are limited to same-origin.
+    fn from_env(environment: Environment) -> Result<Option<Self>, String> {
+        let default_origins = match environment {
+            Environment::Development => "http://localhost:5173,http://localhost:3001",
+            Environment::Staging | Environment::Production => "",
+        };
+        let allowed_origins = env_list("TEE_CORS_ORIGINS", default_origins);
+        if allowed_origins.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            allowed_origins,
+            allowed_methods: env_list("TEE_CORS_METHODS", "GET,POST"),
+            allowed_headers: env_list(
+                "TEE_CORS_HEADERS",
+                "content-type,x-lumina-signature,x-lumina-public-key,x-lumina-timestamp,x-lumina-nonce,x-lumina-session,x-lumina-capability,x-lumina-schema",
+            ),
+            max_age_secs: parse_env("TEE_CORS_MAX_AGE_SECS", 600)?,
+        }))
+    }
+}
+
+impl EscrowConfig {
+    fn from_env(admin_count: usize) -> Result<Option<Self>, String> {
+        let recipients = env_list("TEE_ESCROW_RECIPIENTS", "");
+        if recipients.is_empty() {
+            return Ok(None);
+        }
+
+        let threshold: u8 = parse_env("TEE_ESCROW_THRESHOLD", 2)?;
+        if threshold < 2 || threshold as usize > recipients.len() || recipients.len() > 255 {
+            return Err(format!(
+                "TEE_ESCROW_THRESHOLD must be between 2 and {}",
+                recipients.len()
+            ));
+        }
+
+        // A single admin must never be able to exfiltrate key material
+        let admin_quorum: usize = parse_env("TEE_ESCROW_ADMIN_QUORUM", 2)?;
+        if admin_quorum < 2 || admin_quorum > admin_count {
+            return Err(format!(
+                "TEE_ESCROW_ADMIN_QUORUM must be between 2 and the number of TEE_ADMIN_ADDRESSES ({})",
+                admin_count
+            ));
+        }
+
+        Ok(Some(Self {
+            recipients,
+            threshold,
+            admin_quorum,
+        }))
+    }
+}
+
+impl FaceModelConfig {
+    fn from_env(environment: Environment) -> Result<Option<Self>, String> {
+        let path = match std::env::var("TEE_FACE_MODEL_PATH") {
+            Ok(path) => PathBuf::from(path),
+            Err(_) => return Ok(None),
+        };
+
+        let sha256 = std::env::var("TEE_FACE_MODEL_SHA256").ok();
+        if sha256.is_none() && environment != Environment::Development {
+            return Err(
+                "TEE_FACE_MODEL_SHA256 must pin the face model outside development".to_string(),
+            );
+        }
+
+        Ok(Some(Self {
+            path,
+            sha256,
+            name: env_or("TEE_FACE_MODEL_NAME", "arcface"),
+            version: env_or("TEE_FACE_MODEL_VERSION", "1.0"),
+        }))
+    }
+}
+
+impl HttpConfig {
+    fn from_env() -> Result<Self, String> {
+        let h2_keep_alive_secs: u64 = parse_env("TEE_HTTP2_KEEP_ALIVE_SECS", 20)?;
+        let max_header_bytes = parse_env("TEE_HTTP_MAX_HEADER_BYTES", 16 * 1024)?;
+        if max_header_bytes < 1024 {
+            return Err("TEE_HTTP_MAX_HEADER_BYTES must be at least 1024".to_string());
+        }
+        Ok(Self {
+            http2: parse_env("TEE_HTTP2", true)?,
+            keep_alive: parse_env("TEE_HTTP_KEEP_ALIVE", true)?,
+            h2_keep_alive_interval: (h2_keep_alive_secs > 0)
+                .then(|| Duration::from_secs(h2_keep_alive_secs)),
+            h2_keep_alive_timeout: Duration::from_secs(parse_env(
+                "TEE_HTTP2_KEEP_ALIVE_TIMEOUT_SECS",
+                20,
+            )?),
+            h2_max_concurrent_streams: parse_env("TEE_HTTP2_MAX_STREAMS", 256)?,
+            max_header_bytes,
+            header_read_timeout: Duration::from_secs(parse_env(
+                "TEE_HTTP_HEADER_TIMEOUT_SECS",
+                30,
+            )?),
+        })
+    }
+}
+
+impl HeartbeatConfig {
+    fn from_env() -> Result<Option<Self>, String> {
+        let event_type = match std::env::var("TEE_HEARTBEAT_EVENT") {
+            Ok(event_type) => event_type,
+            Err(_) => return Ok(None),
+        };
+        if event_type.split("::").count() != 3 {
+            return Err(format!(
+                "TEE_HEARTBEAT_EVENT must be <package>::<module>::<event>, got {}",
+                event_type
+            ));
+        }
+
+        let relay = match env_or("TEE_HEARTBEAT_RELAY", "vsock").as_str() {
+            "vsock" => ParentRelay::Vsock {
+                port: parse_env("TEE_HEARTBEAT_RELAY_PORT", 7002)?,
+            },
+            "tcp" => ParentRelay::Tcp(env_or("TEE_HEARTBEAT_RELAY_ADDR", "127.0.0.1:7002")),
+            other => return Err(format!("Unsupported TEE_HEARTBEAT_RELAY: {}", other)),
+        };
+
+        Ok(Some(Self {
+            relay,
+            event_type,
+            vault_field: env_or("TEE_HEARTBEAT_VAULT_FIELD", "vault_id"),
+            poll: Duration::from_secs(parse_env("TEE_HEARTBEAT_POLL_SECS", 15)?),
+        }))
+    }
+}
+
+impl OutboxConfig {
+    fn from_env() -> Result<Option<Self>, String> {
+        let confirmed_event = match std::env::var("TEE_OUTBOX_CONFIRMED_EVENT") {
+            Ok(event_type) => event_type,
+            Err(_) => return Ok(None),
+        };
+        if confirmed_event.split("::").count() != 3 {
+            return Err(format!(
+                "TEE_OUTBOX_CONFIRMED_EVENT must be <package>::<module>::<event>, got {}",
+                confirmed_event
+            ));
+        }
+
+        let relay = match env_or("TEE_OUTBOX_RELAY", "vsock").as_str() {
+            "vsock" => ParentRelay::Vsock {
+                port: parse_env("TEE_OUTBOX_RELAY_PORT", 7003)?,
+            },
+            "tcp" => ParentRelay::Tcp(env_or("TEE_OUTBOX_RELAY_ADDR", "127.0.0.1:7003")),
+            other => return Err(format!("Unsupported TEE_OUTBOX_RELAY: {}", other)),
+        };
+
+        Ok(Some(Self {
+            relay,
+            confirmed_event,
+            dispatch: Duration::from_secs(parse_env("TEE_OUTBOX_DISPATCH_SECS", 30)?),
+            reconcile: Duration::from_secs(parse_env("TEE_OUTBOX_RECONCILE_SECS", 60)?),
+        }))
+    }
+}
+
+impl ArchiveConfig {
+    fn from_env() -> Result<Option<Self>, String> {
+        let relay = match env_or("TEE_ARCHIVE_RELAY", "off").as_str() {
+            "off" => return Ok(None),
+            "vsock" => ParentRelay::Vsock {
+                port: parse_env("TEE_ARCHIVE_RELAY_PORT", 7005)?,
+            },
+            "tcp" => ParentRelay::Tcp(env_or("TEE_ARCHIVE_RELAY_ADDR", "127.0.0.1:7005")),
+            other => return Err(format!("Unsupported TEE_ARCHIVE_RELAY: {}", other)),
+        };
+
+        Ok(Some(Self {
+            relay,
+            sweep: Duration::from_secs(parse_env("TEE_ARCHIVE_SWEEP_SECS", 3600)?),
+            audit_after: Duration::from_secs(parse_env(
+                "TEE_ARCHIVE_AUDIT_AFTER_SECS",
+                90 * 86400,
+            )?),
+            jobs_after: Duration::from_secs(parse_env("TEE_ARCHIVE_JOBS_AFTER_SECS", 86400)?),
+            snapshot_every: Duration::from_secs(parse_env("TEE_ARCHIVE_SNAPSHOT_SECS", 86400)?),
+        }))
+    }
+}
+
+impl CircuitArtifactsConfig {
+    fn from_env() -> Result<Option<Self>, String> {
+        let dir = match std::env::var("TEE_CIRCUIT_DIR") {
+            Ok(dir) => PathBuf::from(dir),
+            Err(_) => return Ok(None),
+        };
+
+        let manifest_key = std::env::var("TEE_CIRCUIT_MANIFEST_KEY")
+            .map_err(|_| "TEE_CIRCUIT_MANIFEST_KEY is required with TEE_CIRCUIT_DIR".to_string())?;
+
+        Ok(Some(Self {
+            manifest: std::env::var("TEE_CIRCUIT_MANIFEST")
+                .map(PathBuf::from)
+                .unwrap_or_else(|_| dir.join("manifest.json")),
+            dir,
+            manifest_key,
+            verify_ceremony: parse_env("TEE_CIRCUIT_VERIFY_CEREMONY", false)?,
+        }))
+    }
+}
+
+/// `operation=secs` pairs
+fn parse_max_ages(entries: &[String]) -> Result<Vec<(String, Duration)>, String> {
+    entries
+        .iter()
+        .map(|entry| {
+            let (operation, secs) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid TEE_ATTESTATION_MAX_AGE entry: {}", entry))?;
+            let secs: u64 = secs
+                .trim()
+                .parse()
+                .map_err(|_| format!("Invalid TEE_ATTESTATION_MAX_AGE entry: {}", entry))?;
+            Ok((operation.trim().to_string(), Duration::from_secs(secs)))
+        })
+        .collect()
+}
+
+/// MemTotal from /proc/meminfo
+fn total_memory_bytes() -> Option<usize> {
+    std::fs::read_to_string("/proc/meminfo")
+        .ok()?
+        .lines()
+        .find_map(|line| line.strip_prefix("MemTotal:"))
+        .and_then(|kb| kb.trim_end_matches("kB").trim().parse::<usize>().ok())
+        .map(|kb| kb * 1024)
+}
+
+fn env_or(name: &str, default: &str) -> String {
+    std::env::var(name).unwrap_or_else(|_| default.to_string())
+}
+
+fn env_list(name: &str, default: &str) -> Vec<String> {
+    env_or(name, default)
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn parse_env<T: std::str::FromStr>(name: &str, default: T) -> Result<T, String> {
+    match std::env::var(name) {
+        Ok(value) => value
+            .parse()
+            .map_err(|_| format!("Invalid value for {}: {}", name, value)),
+        Err(_) => Ok(default),
+    }
+}
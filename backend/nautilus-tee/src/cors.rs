@@ -0,0 +1,47 @@
+/**
+ * CORS Policy
+ * Builds the browser origin policy from configuration
+ */
+
+use axum::http::{header, HeaderName, HeaderValue, Method};
+use std::time::Duration;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+use crate::config::CorsConfig;
+use crate::schema::SCHEMA_HEADER;
+
+pub fn cors_layer(config: &CorsConfig) -> Result<CorsLayer, String> {
+    let origins = config
+        .allowed_origins
+        .iter()
+        .map(|o| HeaderValue::from_str(o).map_err(|_| format!("Invalid CORS origin: {}", o)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let methods = config
+        .allowed_methods
+        .iter()
+        .map(|m| {
+            Method::from_bytes(m.to_uppercase().as_bytes())
+                .map_err(|_| format!("Invalid CORS method: {}", m))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let headers = config
+        .allowed_headers
+        .iter()
+        .map(|h| HeaderName::from_bytes(h.as_bytes()).map_err(|_| format!("Invalid CORS header: {}", h)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(CorsLayer::new()
+        .allow_origin(AllowOrigin::list(origins))
+        .allow_methods(methods)
+        .allow_headers(headers)
+        // Schema negotiation and deprecation notices, readable by SDKs in browsers
+        .expose_headers([
+            HeaderName::from_static(SCHEMA_HEADER),
+            HeaderName::from_static("deprecation"),
+            HeaderName::from_static("sunset"),
+            header::LINK,
+        ])
+        .max_age(Duration::from_secs(config.max_age_secs)))
+}
@@ -0,0 +1,215 @@
+/**
+ * Identity Credentials
+ * Issuer-signed identity attributes, canonicalized for the
+ * identity_attribute circuit
+ *
+ * A credential carries the subject's birth date and nationality, signed by
+ * the issuer with EdDSA-Poseidon over Baby Jubjub (the scheme circomlib
+ * verifies). The signature is checked inside the circuit rather than here,
+ * so a forged or altered credential fails witness generation. The enclave
+ * only canonicalizes the credential into circuit inputs and evaluates the
+ * predicate, so the public result always matches what the circuit proves.
+ */
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::field;
+
+/// Slots for allowed nationalities in the circuit; unused ones are 0
+pub const MAX_NATIONALITIES: usize = 16;
+
+#[derive(Deserialize)]
+pub struct IdentityCredential {
+    pub issuer: CredentialIssuer,
+    pub subject: String, // Sui address the credential was issued to
+    pub attributes: IdentityAttributes,
+    pub expires: String, // YYYY-MM-DD
+    pub signature: CredentialSignature,
+}
+
+#[derive(Deserialize)]
+pub struct CredentialIssuer {
+    pub id: String,
+    pub public_key: [String; 2], // Baby Jubjub point (Ax, Ay), decimal
+}
+
+#[derive(Deserialize)]
+pub struct IdentityAttributes {
+    pub birth_date: String,  // YYYY-MM-DD
+    pub nationality: String, // ISO 3166-1 alpha-2
+}
+
+#[derive(Deserialize)]
+pub struct CredentialSignature {
+    pub r8: [String; 2], // Decimal
+    pub s: String,       // Decimal
+}
+
+/// What the claim proves; at least one predicate must be set
+#[derive(Deserialize)]
+pub struct AttributePredicate {
+    pub min_age: Option<u32>,
+    pub nationality_in: Option<Vec<String>>,
+}
+
+/// Witness for the identity_attribute circuit
+pub struct IdentityWitness {
+    /// Circuit input signals, by name
+    pub inputs: Value,
+    /// Public signals in the order the verifier sees them: the result,
+    /// then the public inputs in declaration order
+    pub public_signals: Vec<String>,
+}
+
+impl IdentityCredential {
+    pub fn parse(bytes: &[u8]) -> Result<Self, String> {
+        serde_json::from_slice(bytes).map_err(|e| format!("Invalid identity credential: {}", e))
+    }
+
+    /// Inputs proving `predicate` as of `today` (YYYYMMDD)
+    pub fn witness(
+        &self,
+        predicate: &AttributePredicate,
+        today: u32,
+    ) -> Result<IdentityWitness, String> {
+        if predicate.min_age.is_none() && predicate.nationality_in.is_none() {
+            return Err("Set min_age, nationality_in or both".to_string());
+        }
+
+        let birth_date = parse_date(&self.attributes.birth_date)?;
+        let expires = parse_date(&self.expires)?;
+        if expires < today {
+            return Err("Identity credential has expired".to_string());
+        }
+        let nationality = country_code(&self.attributes.nationality)?;
+
+        let min_age = predicate.min_age.unwrap_or(0);
+        if min_age > 150 {
+            return Err("min_age must be at most 150".to_string());
+        }
+        let allowed = match &predicate.nationality_in {
+            Some(codes) if codes.is_empty() || codes.len() > MAX_NATIONALITIES => {
+                return Err(format!(
+                    "nationality_in takes 1 to {} codes",
+                    MAX_NATIONALITIES
+                ))
+            }
+            Some(codes) => codes
+                .iter()
+                .map(|c| country_code(c))
+                .collect::<Result<Vec<_>, _>>()?,
+            None => Vec::new(),
+        };
+        let mut allowed_slots = allowed.clone();
+        allowed_slots.resize(MAX_NATIONALITIES, 0);
+
+        let issuer_ax = field::element(&self.issuer.public_key[0])?;
+        let issuer_ay = field::element(&self.issuer.public_key[1])?;
+        let subject = address_field(&self.subject)?;
+        let check_age = u32::from(predicate.min_age.is_some());
+        let check_nationality = u32::from(predicate.nationality_in.is_some());
+
+        let age_ok = check_age == 0 || birth_date + min_age * 10_000 <= today;
+        let nationality_ok = check_nationality == 0 || allowed.contains(&nationality);
+        let satisfied = age_ok && nationality_ok;
+        let allowed_signals: Vec<String> = allowed_slots.iter().map(u32::to_string).collect();
+        let mut public_signals = vec![
+            u32::from(satisfied).to_string(),
+            issuer_ax.clone(),
+            issuer_ay.clone(),
+            subject.clone(),
+            today.to_string(),
+            check_age.to_string(),
+            min_age.to_string(),
+            check_nationality.to_string(),
+        ];
+        public_signals.extend(allowed_signals.iter().cloned());
+
+        let inputs = json!({
+            "issuer_ax": issuer_ax,
+            "issuer_ay": issuer_ay,
+            "subject": subject,
+            "today": today.to_string(),
+            "check_age": check_age.to_string(),
+            "min_age": min_age.to_string(),
+            "check_nationality": check_nationality.to_string(),
+            "allowed_nationalities": allowed_signals,
+            "birth_date": birth_date.to_string(),
+            "nationality": nationality.to_string(),
+            "expires": expires.to_string(),
+            "sig_r8x": field::element(&self.signature.r8[0])?,
+            "sig_r8y": field::element(&self.signature.r8[1])?,
+            "sig_s": field::element(&self.signature.s)?,
+        });
+
+        Ok(IdentityWitness {
+            inputs,
+            public_signals,
+        })
+    }
+}
+
+/// Today's UTC date as YYYYMMDD
+pub fn today() -> u32 {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+        / 86_400;
+    civil_date(days as i64)
+}
+
+/// Days since 1970-01-01 to YYYYMMDD (Howard Hinnant's civil_from_days)
+pub fn civil_date(days: i64) -> u32 {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year * 10_000 + month * 100 + day) as u32
+}
+
+/// YYYY-MM-DD to YYYYMMDD
+fn parse_date(date: &str) -> Result<u32, String> {
+    let invalid = || format!("Invalid date {:?}; expected YYYY-MM-DD", date);
+    let parts: Vec<&str> = date.split('-').collect();
+    let [year, month, day] = parts.as_slice() else {
+        return Err(invalid());
+    };
+    if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+        return Err(invalid());
+    }
+    let year: u32 = year.parse().map_err(|_| invalid())?;
+    let month: u32 = month.parse().map_err(|_| invalid())?;
+    let day: u32 = day.parse().map_err(|_| invalid())?;
+    if year < 1800 || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return Err(invalid());
+    }
+    Ok(year * 10_000 + month * 100 + day)
+}
+
+/// ISO 3166-1 alpha-2 code packed as (first << 8) | second
+fn country_code(code: &str) -> Result<u32, String> {
+    match code.as_bytes() {
+        [a, b] if a.is_ascii_alphabetic() && b.is_ascii_alphabetic() => {
+            Ok(u32::from(a.to_ascii_uppercase()) << 8 | u32::from(b.to_ascii_uppercase()))
+        }
+        _ => Err(format!("Invalid country code {:?}", code)),
+    }
+}
+
+/// A Sui address as a field element
+fn address_field(address: &str) -> Result<String, String> {
+    let bytes = address
+        .strip_prefix("0x")
+        .and_then(|hex_part| hex::decode(hex_part).ok())
+        .filter(|bytes| bytes.len() == 32)
+        .ok_or_else(|| format!("Invalid subject address {:?}", address))?;
+    Ok(field::truncated(&bytes))
+}
@@ -0,0 +1,373 @@
+/**
+ * Crypto Primitives
+ * Authenticated encryption and public-key envelopes used inside the enclave
+ *
+ * Envelopes are ECIES-style: an ephemeral X25519 key agrees a secret with
+ * the recipient, HKDF-SHA256 derives a ChaCha20-Poly1305 key from it, and
+ * the ephemeral public key travels alongside the ciphertext.
+ *
+ * Hybrid envelopes additionally encapsulate to the recipient's ML-KEM-768
+ * key and feed both shared secrets into HKDF, so the payload stays secret
+ * unless both X25519 and ML-KEM are broken.
+ */
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Nonce};
+use hkdf::Hkdf;
+use ml_kem::kem::{Encapsulate, EncapsulationKey};
+use ml_kem::{EncodedSizeUser, MlKem768Params};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use x25519_dalek::{PublicKey, StaticSecret};
+use zeroize::Zeroizing;
+
+const ENVELOPE_INFO: &[u8] = b"lumina-envelope-v1";
+const HYBRID_ENVELOPE_INFO: &[u8] = b"lumina-envelope-x25519-mlkem768-v1";
+
+pub type KemPublicKey = EncapsulationKey<MlKem768Params>;
+
+/// Key agreement negotiated by the client
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KeyExchange {
+    #[default]
+    #[serde(rename = "x25519")]
+    X25519,
+    #[serde(rename = "x25519_mlkem768")]
+    HybridMlKem768,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Envelope {
+    pub ephemeral_public_key: String, // Base64 X25519 public key
+    pub nonce: String,                // Base64 12-byte nonce
+    pub ciphertext: String,           // Base64 ciphertext + tag
+    /// Base64 ML-KEM-768 ciphertext, present on hybrid envelopes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kem_ciphertext: Option<String>,
+}
+
+/// Encrypt with a 256-bit key; returns (nonce, ciphertext)
+pub fn aead_encrypt(
+    key: &[u8; 32],
+    plaintext: &[u8],
+    aad: &[u8],
+) -> Result<([u8; 12], Vec<u8>), String> {
+    let mut nonce = [0u8; 12];
+    OsRng.fill_bytes(&mut nonce);
+
+    let ciphertext = ChaCha20Poly1305::new(key.into())
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: plaintext,
+                aad,
+            },
+        )
+        .map_err(|_| "Encryption failed".to_string())?;
+
+    Ok((nonce, ciphertext))
+}
+
+pub fn aead_decrypt(
+    key: &[u8; 32],
+    nonce: &[u8],
+    ciphertext: &[u8],
+    aad: &[u8],
+) -> Result<Vec<u8>, String> {
+    if nonce.len() != 12 {
+        return Err("Invalid nonce length".to_string());
+    }
+
+    ChaCha20Poly1305::new(key.into())
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad,
+            },
+        )
+        .map_err(|_| "Decryption failed: ciphertext or key is invalid".to_string())
+}
+
+/// Encrypt to a recipient's X25519 public key
+pub fn seal(recipient: &PublicKey, plaintext: &[u8], aad: &[u8]) -> Result<Envelope, String> {
+    let ephemeral = StaticSecret::random_from_rng(OsRng);
+    let ephemeral_public = PublicKey::from(&ephemeral);
+    let key = envelope_key(
+        &ephemeral.diffie_hellman(recipient).to_bytes(),
+        &ephemeral_public,
+        recipient,
+    )?;
+
+    let (nonce, ciphertext) = aead_encrypt(&key, plaintext, aad)?;
+
+    Ok(Envelope {
+        ephemeral_public_key: STANDARD.encode(ephemeral_public.as_bytes()),
+        nonce: STANDARD.encode(nonce),
+        ciphertext: STANDARD.encode(ciphertext),
+        kem_ciphertext: None,
+    })
+}
+
+/// Encrypt to a recipient's X25519 and ML-KEM-768 public keys
+pub fn seal_hybrid(
+    recipient: &PublicKey,
+    kem_key: &KemPublicKey,
+    plaintext: &[u8],
+    aad: &[u8],
+) -> Result<Envelope, String> {
+    let ephemeral = StaticSecret::random_from_rng(OsRng);
+    let ephemeral_public = PublicKey::from(&ephemeral);
+    let (kem_ciphertext, kem_shared) = kem_encapsulate(kem_key)?;
+    let key = hybrid_envelope_key(
+        &ephemeral.diffie_hellman(recipient).to_bytes(),
+        &kem_shared,
+        &ephemeral_public,
+        recipient,
+        &kem_ciphertext,
+    )?;
+
+    let (nonce, ciphertext) = aead_encrypt(&key, plaintext, aad)?;
+
+    Ok(Envelope {
+        ephemeral_public_key: STANDARD.encode(ephemeral_public.as_bytes()),
+        nonce: STANDARD.encode(nonce),
+        ciphertext: STANDARD.encode(ciphertext),
+        kem_ciphertext: Some(STANDARD.encode(kem_ciphertext)),
+    })
+}
+
+/// Seal under the negotiated key exchange
+pub fn seal_with(
+    mode: KeyExchange,
+    recipient: &PublicKey,
+    kem_key: Option<&KemPublicKey>,
+    plaintext: &[u8],
+    aad: &[u8],
+) -> Result<Envelope, String> {
+    match mode {
+        KeyExchange::X25519 => seal(recipient, plaintext, aad),
+        KeyExchange::HybridMlKem768 => seal_hybrid(
+            recipient,
+            kem_key.ok_or("Hybrid key exchange requires an ML-KEM public key")?,
+            plaintext,
+            aad,
+        ),
+    }
+}
+
+/// Encapsulate to an ML-KEM-768 key; returns (ciphertext, shared secret)
+pub fn kem_encapsulate(key: &KemPublicKey) -> Result<(Vec<u8>, Zeroizing<[u8; 32]>), String> {
+    let (ciphertext, shared) = key
+        .encapsulate(&mut OsRng)
+        .map_err(|_| "ML-KEM encapsulation failed".to_string())?;
+
+    let mut secret = Zeroizing::new([0u8; 32]);
+    secret.copy_from_slice(&shared);
+    Ok((ciphertext.to_vec(), secret))
+}
+
+/// Decrypt an X25519 envelope addressed to `secret`
+pub fn open(secret: &StaticSecret, envelope: &Envelope, aad: &[u8]) -> Result<Vec<u8>, String> {
+    if envelope.kem_ciphertext.is_some() {
+        return Err("Hybrid envelopes cannot be opened with an X25519 key alone".to_string());
+    }
+    let ephemeral_public = decode_public_key(&envelope.ephemeral_public_key)?;
+    let recipient = PublicKey::from(secret);
+    let key = envelope_key(
+        &secret.diffie_hellman(&ephemeral_public).to_bytes(),
+        &ephemeral_public,
+        &recipient,
+    )?;
+
+    aead_decrypt(
+        &key,
+        &decode(&envelope.nonce)?,
+        &decode(&envelope.ciphertext)?,
+        aad,
+    )
+}
+
+pub fn decode_public_key(encoded: &str) -> Result<PublicKey, String> {
+    let bytes: [u8; 32] = decode(encoded)?
+        .try_into()
+        .map_err(|_| "X25519 public key must be 32 bytes".to_string())?;
+    Ok(PublicKey::from(bytes))
+}
+
+pub fn decode_kem_public_key(encoded: &str) -> Result<KemPublicKey, String> {
+    let bytes = decode(encoded)?;
+    let encoded = bytes
+        .as_slice()
+        .try_into()
+        .map_err(|_| "ML-KEM-768 public key must be 1184 bytes".to_string())?;
+    Ok(KemPublicKey::from_bytes(encoded))
+}
+
+pub fn decode(encoded: &str) -> Result<Vec<u8>, String> {
+    STANDARD
+        .decode(encoded)
+        .map_err(|e| format!("Invalid base64: {}", e))
+}
+
+/// Decode a large base64 payload into the buffer that held it, a chunk at
+/// a time, so the blob is never in memory twice. Decoded bytes trail the
+/// read position, so each chunk is copied out before it is overwritten.
+pub fn decode_in_place(encoded: String) -> Result<Vec<u8>, String> {
+    const CHUNK: usize = 16 * 1024; // Whole quads, so padding ends a chunk only at the end
+    let mut buffer = encoded.into_bytes();
+    let mut chunk = [0u8; CHUNK];
+    let (mut read, mut written) = (0, 0);
+    while read < buffer.len() {
+        let end = (read + CHUNK).min(buffer.len());
+        let len = end - read;
+        chunk[..len].copy_from_slice(&buffer[read..end]);
+        if end < buffer.len() && chunk[len - 1] == b'=' {
+            return Err("Invalid base64: padding before the end".to_string());
+        }
+        written += STANDARD
+            .decode_slice(&chunk[..len], &mut buffer[written..])
+            .map_err(|e| format!("Invalid base64: {}", e))?;
+        read = end;
+    }
+    buffer.truncate(written);
+    Ok(buffer)
+}
+
+// Binding both public keys into the KDF ties the key to this exchange
+fn envelope_key(
+    shared: &[u8; 32],
+    ephemeral: &PublicKey,
+    recipient: &PublicKey,
+) -> Result<[u8; 32], String> {
+    let mut salt = [0u8; 64];
+    salt[..32].copy_from_slice(ephemeral.as_bytes());
+    salt[32..].copy_from_slice(recipient.as_bytes());
+
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(&salt), shared)
+        .expand(ENVELOPE_INFO, &mut key)
+        .map_err(|_| "Key derivation failed".to_string())?;
+    Ok(key)
+}
+
+// The KEM ciphertext goes into the info so the key commits to it as well
+fn hybrid_envelope_key(
+    shared: &[u8; 32],
+    kem_shared: &[u8; 32],
+    ephemeral: &PublicKey,
+    recipient: &PublicKey,
+    kem_ciphertext: &[u8],
+) -> Result<Zeroizing<[u8; 32]>, String> {
+    let ikm = Zeroizing::new([shared.as_slice(), kem_shared].concat());
+
+    let mut salt = [0u8; 64];
+    salt[..32].copy_from_slice(ephemeral.as_bytes());
+    salt[32..].copy_from_slice(recipient.as_bytes());
+
+    let mut key = Zeroizing::new([0u8; 32]);
+    Hkdf::<Sha256>::new(Some(&salt), &ikm)
+        .expand_multi_info(&[HYBRID_ENVELOPE_INFO, kem_ciphertext], key.as_mut())
+        .map_err(|_| "Key derivation failed".to_string())?;
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ml_kem::kem::{Decapsulate, DecapsulationKey};
+    use ml_kem::{Ciphertext, KemCore, MlKem768};
+
+    /// Decapsulate an ML-KEM-768 ciphertext addressed to `key`
+    fn kem_decapsulate(
+        key: &DecapsulationKey<MlKem768Params>,
+        ciphertext: &[u8],
+    ) -> Result<Zeroizing<[u8; 32]>, String> {
+        let ciphertext: &Ciphertext<MlKem768> = ciphertext
+            .try_into()
+            .map_err(|_| "ML-KEM-768 ciphertext must be 1088 bytes".to_string())?;
+        let shared = key
+            .decapsulate(ciphertext)
+            .map_err(|_| "ML-KEM decapsulation failed".to_string())?;
+
+        let mut secret = Zeroizing::new([0u8; 32]);
+        secret.copy_from_slice(&shared);
+        Ok(secret)
+    }
+
+    /// The recipient's side of seal_hybrid; the enclave only seals them
+    fn open_hybrid(
+        secret: &StaticSecret,
+        kem_key: &DecapsulationKey<MlKem768Params>,
+        envelope: &Envelope,
+        aad: &[u8],
+    ) -> Result<Vec<u8>, String> {
+        let kem_ciphertext = decode(
+            envelope
+                .kem_ciphertext
+                .as_deref()
+                .ok_or("Envelope is not hybrid")?,
+        )?;
+        let ephemeral_public = decode_public_key(&envelope.ephemeral_public_key)?;
+        let kem_shared = kem_decapsulate(kem_key, &kem_ciphertext)?;
+        let key = hybrid_envelope_key(
+            &secret.diffie_hellman(&ephemeral_public).to_bytes(),
+            &kem_shared,
+            &ephemeral_public,
+            &PublicKey::from(secret),
+            &kem_ciphertext,
+        )?;
+
+        aead_decrypt(
+            &key,
+            &decode(&envelope.nonce)?,
+            &decode(&envelope.ciphertext)?,
+            aad,
+        )
+    }
+
+    #[test]
+    fn in_place_decoding_matches_across_chunks() {
+        for len in [0, 1, 2, 3, 12_287, 12_288, 12_289, 100_000] {
+            let data: Vec<u8> = (0..len).map(|i| (i * 31 % 251) as u8).collect();
+            assert_eq!(decode_in_place(STANDARD.encode(&data)).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn in_place_decoding_rejects_early_padding() {
+        let mut encoded = "A".repeat(16 * 1024 - 2);
+        encoded.push_str("==");
+        encoded.push_str("QUFB");
+        assert!(decode_in_place(encoded).is_err());
+        assert!(decode_in_place("QUF=QUFB".to_string()).is_err());
+    }
+
+    #[test]
+    fn hybrid_envelopes_open_with_both_keys() {
+        let secret = StaticSecret::random_from_rng(OsRng);
+        let recipient = PublicKey::from(&secret);
+        let (kem_secret, kem_public) = MlKem768::generate(&mut OsRng);
+        let envelope = seal_hybrid(&recipient, &kem_public, b"content key", b"aad").unwrap();
+
+        let opened = open_hybrid(&secret, &kem_secret, &envelope, b"aad").unwrap();
+        assert_eq!(opened, b"content key");
+        assert!(open_hybrid(&secret, &kem_secret, &envelope, b"other aad").is_err());
+        assert!(open(&secret, &envelope, b"aad").is_err());
+
+        let (other_kem_secret, _) = MlKem768::generate(&mut OsRng);
+        assert!(open_hybrid(&secret, &other_kem_secret, &envelope, b"aad").is_err());
+        let other_secret = StaticSecret::random_from_rng(OsRng);
+        assert!(open_hybrid(&other_secret, &kem_secret, &envelope, b"aad").is_err());
+
+        // Swapping in another encapsulation changes the key
+        let other = seal_hybrid(&recipient, &kem_public, b"content key", b"aad").unwrap();
+        let mut spliced = envelope.clone();
+        spliced.kem_ciphertext = other.kem_ciphertext;
+        assert!(open_hybrid(&secret, &kem_secret, &spliced, b"aad").is_err());
+    }
+}
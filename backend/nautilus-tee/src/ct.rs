@@ -0,0 +1,116 @@
+/**
+ * Constant-Time Comparisons
+ * Decision helpers whose running time doesn't depend on secret inputs
+ *
+ * A hostile parent instance can time every response, so comparisons of
+ * secret-derived values (hashes, commitments, match scores) must not
+ * short-circuit on the first differing byte or branch on the score.
+ * Only lengths are treated as public.
+ */
+
+use subtle::{ConstantTimeEq, ConstantTimeGreater};
+
+/// Scores are compared as fixed-point integers with this many steps per 1.0
+const SCORE_SCALE: f64 = 1_000_000.0;
+
+pub fn bytes_eq(a: &[u8], b: &[u8]) -> bool {
+    a.ct_eq(b).into()
+}
+
+/// Equality of encoded digests (hex commitments and the like)
+pub fn str_eq(a: &str, b: &str) -> bool {
+    bytes_eq(a.as_bytes(), b.as_bytes())
+}
+
+/// `score >= threshold` for scores in [0, 1]
+pub fn score_at_least(score: f64, threshold: f64) -> bool {
+    !bool::from(fixed_point(threshold).ct_gt(&fixed_point(score)))
+}
+
+fn fixed_point(score: f64) -> u64 {
+    // Saturating cast: NaN maps to 0, out-of-range values clamp
+    (score.clamp(0.0, 1.0) * SCORE_SCALE) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::hint::black_box;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn bytes_eq_matches_ordinary_equality() {
+        assert!(bytes_eq(b"commitment", b"commitment"));
+        assert!(!bytes_eq(b"commitment", b"commitmenT"));
+        assert!(!bytes_eq(b"commitment", b"commit"));
+        assert!(str_eq("", ""));
+    }
+
+    #[test]
+    fn score_threshold_is_inclusive() {
+        assert!(score_at_least(0.7, 0.7));
+        assert!(score_at_least(0.85, 0.7));
+        assert!(!score_at_least(0.699999, 0.7));
+        assert!(!score_at_least(f64::NAN, 0.7));
+        assert!(score_at_least(2.0, 1.0));
+    }
+
+    /// Minimum over several rounds, which filters scheduler noise
+    fn time(f: impl Fn()) -> Duration {
+        (0..15)
+            .map(|_| {
+                let start = Instant::now();
+                for _ in 0..2_000 {
+                    f();
+                }
+                start.elapsed()
+            })
+            .min()
+            .unwrap()
+    }
+
+    fn assert_similar(a: Duration, b: Duration) {
+        let ratio = a.as_secs_f64() / b.as_secs_f64();
+        assert!(
+            (0.5..2.0).contains(&ratio),
+            "timing differs by {:.2}x ({:?} vs {:?})",
+            ratio,
+            a,
+            b
+        );
+    }
+
+    /// Timing-sensitive; on an idle machine:
+    /// cargo test --release ct::tests::bytes_eq_timing_does_not_depend_on_mismatch_position -- --ignored
+    #[test]
+    #[ignore]
+    fn bytes_eq_timing_does_not_depend_on_mismatch_position() {
+        let secret = vec![0xa5u8; 4096];
+        let mut early = secret.clone();
+        early[0] ^= 1;
+        let mut late = secret.clone();
+        late[4095] ^= 1;
+
+        let early_time = time(|| {
+            black_box(bytes_eq(black_box(&secret), black_box(&early)));
+        });
+        let late_time = time(|| {
+            black_box(bytes_eq(black_box(&secret), black_box(&late)));
+        });
+        assert_similar(early_time, late_time);
+    }
+
+    /// Timing-sensitive; on an idle machine:
+    /// cargo test --release ct::tests::score_timing_does_not_depend_on_outcome -- --ignored
+    #[test]
+    #[ignore]
+    fn score_timing_does_not_depend_on_outcome() {
+        let accept = time(|| {
+            black_box(score_at_least(black_box(0.95), black_box(0.7)));
+        });
+        let reject = time(|| {
+            black_box(score_at_least(black_box(0.05), black_box(0.7)));
+        });
+        assert_similar(accept, reject);
+    }
+}
@@ -0,0 +1,193 @@
+/**
+ * Data-Subject Export
+ * Everything the enclave holds about a vault owner, in one archive
+ *
+ * An export gathers four sections: the vault's stored records, the
+ * owner's biometric templates (re-wrapped to the owner's key), the
+ * vault's audit chain and its liveness history (check-in schedule,
+ * heartbeat devices and bound heartbeat objects). The archive's JSON is
+ * sealed to an X25519 key the owner supplies, so only they can open it.
+ *
+ * The manifest travels in the clear and says what was included: a count
+ * and SHA-256 per section, and the digest of the archive before sealing.
+ * The route binds the manifest's digest into an attestation. Records
+ * kept from the owner on purpose (duress settings, which must stay hidden
+ * from whoever is coercing them, and key shares held for guardians) are
+ * named in the manifest as withheld, never exported.
+ */
+
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use x25519_dalek::PublicKey;
+
+use crate::audit::{AuditEntry, AuditTrail};
+use crate::crypto::{self, Envelope};
+use crate::store::Store;
+use crate::templates::{TemplateBackupEntry, TemplateService};
+use crate::vault_state::{self, VaultRecord, VAULT_NAMESPACES};
+
+pub const OPERATION: &str = "data_export";
+/// The outbox isn't a vault namespace but does hold events about the vault
+const EVENTS_NAMESPACE: &str = "events";
+const LIVENESS_NAMESPACES: &[&str] = &[
+    "check_in_schedules",
+    "heartbeat_devices",
+    "heartbeat_objects",
+];
+/// Never exported, see above
+const WITHHELD_NAMESPACES: &[&str] = &[
+    "duress_locks",
+    "duress_settings",
+    "guardian_shares",
+    "threshold_keys",
+];
+
+#[derive(Clone, Serialize)]
+pub struct ExportSection {
+    pub name: &'static str,
+    pub count: usize,
+    pub sha256: String, // Hex, over the section's JSON
+}
+
+#[derive(Clone, Serialize)]
+pub struct ExportManifest {
+    pub export_id: String,
+    pub vault_id: String,
+    pub subject: String,
+    pub recipient_public_key: String, // Base64 X25519 key the archive is sealed to
+    pub sections: Vec<ExportSection>,
+    pub withheld: Vec<&'static str>,
+    pub archive_sha256: String, // Hex, over the archive's JSON before sealing
+    pub exported_at: u64,
+}
+
+impl ExportManifest {
+    /// Digest bound into the export attestation
+    pub fn digest(&self) -> Result<Vec<u8>, String> {
+        let bytes = serde_json::to_vec(self)
+            .map_err(|e| format!("Failed to serialize export manifest: {}", e))?;
+        Ok(Sha256::digest(bytes).to_vec())
+    }
+}
+
+/// What the sealed archive holds
+#[derive(Serialize)]
+struct Archive {
+    export_id: String,
+    vault_id: String,
+    subject: String,
+    metadata: Vec<VaultRecord>,
+    templates: Vec<TemplateBackupEntry>,
+    audit: Vec<AuditEntry>,
+    liveness: Vec<VaultRecord>,
+}
+
+pub struct DataExport {
+    pub manifest: ExportManifest,
+    /// The archive, sealed to the recipient key with the export id as AAD
+    pub archive: Envelope,
+}
+
+pub struct DataExportService {
+    store: Arc<Store>,
+    templates: Arc<TemplateService>,
+    audit: Arc<AuditTrail>,
+}
+
+impl DataExportService {
+    pub fn new(store: Arc<Store>, templates: Arc<TemplateService>, audit: Arc<AuditTrail>) -> Self {
+        Self {
+            store,
+            templates,
+            audit,
+        }
+    }
+
+    /// Gather and seal everything held about `subject` in the vault; the
+    /// caller must already have checked they own it
+    pub fn export(
+        &self,
+        vault_id: &str,
+        subject: &str,
+        recipient_public_key: &str,
+    ) -> Result<DataExport, String> {
+        let recipient = crypto::decode_public_key(recipient_public_key)?;
+        let export_id = new_export_id();
+
+        let metadata_namespaces: Vec<&str> = VAULT_NAMESPACES
+            .iter()
+            .copied()
+            .filter(|ns| {
+                *ns != "audit_trail"
+                    && !LIVENESS_NAMESPACES.contains(ns)
+                    && !WITHHELD_NAMESPACES.contains(ns)
+            })
+            .chain([EVENTS_NAMESPACE])
+            .collect();
+        let archive = Archive {
+            export_id: export_id.clone(),
+            vault_id: vault_id.to_string(),
+            subject: subject.to_string(),
+            metadata: vault_state::records(&self.store, vault_id, &metadata_namespaces)?,
+            templates: self
+                .templates
+                .export_for_owner(vault_id, subject, &recipient)?,
+            audit: self.audit.entries(vault_id)?,
+            liveness: vault_state::records(&self.store, vault_id, LIVENESS_NAMESPACES)?,
+        };
+        let sections = vec![
+            section("metadata", &archive.metadata)?,
+            section("templates", &archive.templates)?,
+            section("audit", &archive.audit)?,
+            section("liveness", &archive.liveness)?,
+        ];
+
+        let plaintext = serde_json::to_vec(&archive)
+            .map_err(|e| format!("Failed to serialize export archive: {}", e))?;
+        let sealed = seal(&recipient, &plaintext, &export_id)?;
+        Ok(DataExport {
+            manifest: ExportManifest {
+                export_id,
+                vault_id: vault_id.to_string(),
+                subject: subject.to_string(),
+                recipient_public_key: recipient_public_key.to_string(),
+                sections,
+                withheld: WITHHELD_NAMESPACES.to_vec(),
+                archive_sha256: hex::encode(Sha256::digest(&plaintext)),
+                exported_at: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs(),
+            },
+            archive: sealed,
+        })
+    }
+}
+
+fn section<T: Serialize>(name: &'static str, items: &[T]) -> Result<ExportSection, String> {
+    let bytes = serde_json::to_vec(items)
+        .map_err(|e| format!("Failed to serialize export section {}: {}", name, e))?;
+    Ok(ExportSection {
+        name,
+        count: items.len(),
+        sha256: hex::encode(Sha256::digest(bytes)),
+    })
+}
+
+fn seal(recipient: &PublicKey, plaintext: &[u8], export_id: &str) -> Result<Envelope, String> {
+    crypto::seal(
+        recipient,
+        plaintext,
+        format!("lumina-data-export:{}", export_id).as_bytes(),
+    )
+}
+
+fn new_export_id() -> String {
+    let mut id = [0u8; 16];
+    OsRng.fill_bytes(&mut id);
+    hex::encode(id)
+}
@@ -0,0 +1,278 @@
+/**
+ * Dead Letters
+ * Deliveries and triggers that ran out of retries, kept for admins
+ *
+ * A notification job the relay gave up on, an on-chain intent that
+ * exhausted its attempts, or a webhook delivery the host's relay reports
+ * as exhausted lands here with a snapshot of what failed, instead of
+ * only in a log line. Notification jobs and intents are dead-lettered in
+ * the same store batch that marks them failed.
+ *
+ * Admins list and inspect letters, and retry or discard each one: a retry
+ * queues the snapshot again with fresh attempts (a webhook retry emits a
+ * `webhook.redelivery` event naming the one endpoint to redeliver to),
+ * and either way the letter is removed. A letter's id is derived from its
+ * source and reference, so failing again after a retry doesn't duplicate
+ * it.
+ *
+ * Past TEE_DEAD_LETTER_ALERT letters the queue is alerting: a watch task
+ * logs each further growth and GET /health/detail reports it degraded.
+ */
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+use crate::store::{Batch, Store};
+use crate::tasks::{Schedule, TaskScheduler};
+
+pub const NAMESPACE: &str = "dead_letters";
+const WATCH_EVERY: Duration = Duration::from_secs(5 * 60);
+/// Emitted to retry a webhook letter: `{event, endpoint}`, for the relay
+/// to deliver `event` to that one endpoint
+pub const REDELIVERY_EVENT: &str = "webhook.redelivery";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeadLetterSource {
+    /// An outbox intent; the reference is its intent id
+    ChainIntent,
+    /// A notification job; the reference is its job id
+    Notification,
+    /// A webhook delivery; the reference is "<event id>@<endpoint>"
+    Webhook,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DeadLetter {
+    pub letter_id: String,
+    pub source: DeadLetterSource,
+    pub reference: String,
+    pub vault_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub attempts: u32,
+    /// What failed, as it stood: the job or intent record, or the event
+    pub payload: Value,
+    pub dead_at: u64,
+}
+
+#[derive(Serialize)]
+pub struct DeadLetterHealth {
+    pub pending: usize,
+    pub alert_threshold: usize,
+    pub alerting: bool,
+}
+
+pub struct DeadLetterQueue {
+    store: Arc<Store>,
+    alert_threshold: usize,
+    /// Letters at the last alert, so each growth is reported once
+    alerted_at: Mutex<usize>,
+}
+
+impl DeadLetterQueue {
+    pub fn new(store: Arc<Store>, alert_threshold: usize) -> Self {
+        Self {
+            store,
+            alert_threshold,
+            alerted_at: Mutex::new(0),
+        }
+    }
+
+    /// Add a letter to `batch`, replacing any earlier one for the same
+    /// reference; returns its id
+    #[allow(clippy::too_many_arguments)]
+    pub fn stage<T: Serialize>(
+        &self,
+        batch: &mut Batch,
+        source: DeadLetterSource,
+        reference: &str,
+        vault_id: &str,
+        error: Option<String>,
+        attempts: u32,
+        payload: &T,
+    ) -> Result<String, String> {
+        let letter = DeadLetter {
+            letter_id: letter_id(source, reference),
+            source,
+            reference: reference.to_string(),
+            vault_id: vault_id.to_string(),
+            error,
+            attempts,
+            payload: serde_json::to_value(payload)
+                .map_err(|e| format!("Failed to serialize dead letter: {}", e))?,
+            dead_at: now(),
+        };
+        batch.put(NAMESPACE, &letter.letter_id, &letter)?;
+        Ok(letter.letter_id)
+    }
+
+    /// Remove the reference's letter in `batch`, e.g. once it succeeded
+    /// after all
+    pub fn stage_removal(&self, batch: &mut Batch, source: DeadLetterSource, reference: &str) {
+        batch.remove(NAMESPACE, &letter_id(source, reference));
+    }
+
+    /// Record a letter on its own, outside any batch
+    pub fn add<T: Serialize>(
+        &self,
+        source: DeadLetterSource,
+        reference: &str,
+        vault_id: &str,
+        error: Option<String>,
+        attempts: u32,
+        payload: &T,
+    ) -> Result<DeadLetter, String> {
+        let mut batch = Batch::new();
+        let letter_id = self.stage(
+            &mut batch, source, reference, vault_id, error, attempts, payload,
+        )?;
+        self.store.commit(batch)?;
+        self.get(&letter_id)?
+            .ok_or_else(|| "Dead letter was not recorded".to_string())
+    }
+
+    /// Letters, newest first, optionally from one source
+    pub fn list(&self, source: Option<DeadLetterSource>) -> Result<Vec<DeadLetter>, String> {
+        let mut letters: Vec<DeadLetter> = self
+            .store
+            .list::<DeadLetter>(NAMESPACE)?
+            .into_iter()
+            .map(|(_, letter)| letter)
+            .filter(|letter| source.is_none_or(|s| letter.source == s))
+            .collect();
+        letters.sort_by_key(|letter| std::cmp::Reverse(letter.dead_at));
+        Ok(letters)
+    }
+
+    pub fn get(&self, letter_id: &str) -> Result<Option<DeadLetter>, String> {
+        self.store.get(NAMESPACE, letter_id)
+    }
+
+    pub fn remove(&self, letter_id: &str) -> Result<(), String> {
+        self.store.update(NAMESPACE, |ns| {
+            ns.remove(letter_id);
+        })
+    }
+
+    pub fn health(&self) -> Result<DeadLetterHealth, String> {
+        let pending = self.store.list::<Value>(NAMESPACE)?.len();
+        Ok(DeadLetterHealth {
+            pending,
+            alert_threshold: self.alert_threshold,
+            alerting: pending >= self.alert_threshold,
+        })
+    }
+
+    /// Watch the queue's size on the task scheduler
+    pub fn schedule_watch(self: Arc<Self>, tasks: &TaskScheduler) -> Result<(), String> {
+        let schedule = Schedule::every(WATCH_EVERY);
+        tasks.register(
+            "dead_letters.watch",
+            &schedule,
+            WATCH_EVERY / 10,
+            move || {
+                let queue = self.clone();
+                async move { queue.watch() }
+            },
+        )
+    }
+
+    fn watch(&self) -> Result<(), String> {
+        let health = self.health()?;
+        let mut alerted_at = self.alerted_at.lock().unwrap();
+        if !health.alerting {
+            *alerted_at = 0;
+        } else if health.pending > *alerted_at {
+            warn!(
+                "Dead-letter queue holds {} letters (alert threshold {})",
+                health.pending, health.alert_threshold
+            );
+            *alerted_at = health.pending;
+        }
+        Ok(())
+    }
+}
+
+fn letter_id(source: DeadLetterSource, reference: &str) -> String {
+    let source = serde_json::to_string(&source).unwrap_or_default();
+    let digest = Sha256::new()
+        .chain_update(source.as_bytes())
+        .chain_update([0])
+        .chain_update(reference.as_bytes())
+        .finalize();
+    hex::encode(&digest[..16])
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn letters_replace_by_reference_and_alert_past_threshold() {
+        let dir = std::env::temp_dir().join(format!("lumina-dead-letter-tests-{}", now()));
+        let queue = DeadLetterQueue::new(Arc::new(Store::open(&dir).unwrap()), 2);
+
+        let first = queue
+            .add(
+                DeadLetterSource::Notification,
+                "job-1",
+                "vault-1",
+                None,
+                6,
+                &json!({}),
+            )
+            .unwrap();
+        let again = queue
+            .add(
+                DeadLetterSource::Notification,
+                "job-1",
+                "vault-1",
+                Some("bounced".to_string()),
+                6,
+                &json!({}),
+            )
+            .unwrap();
+        assert_eq!(first.letter_id, again.letter_id);
+        assert!(!queue.health().unwrap().alerting);
+
+        // The same reference from another source is another letter
+        queue
+            .add(
+                DeadLetterSource::ChainIntent,
+                "job-1",
+                "vault-1",
+                None,
+                8,
+                &json!({}),
+            )
+            .unwrap();
+        let health = queue.health().unwrap();
+        assert_eq!(health.pending, 2);
+        assert!(health.alerting);
+        assert_eq!(
+            queue.list(Some(DeadLetterSource::Notification)).unwrap()[0]
+                .error
+                .as_deref(),
+            Some("bounced")
+        );
+
+        let mut batch = Batch::new();
+        queue.stage_removal(&mut batch, DeadLetterSource::ChainIntent, "job-1");
+        queue.store.commit(batch).unwrap();
+        queue.remove(&first.letter_id).unwrap();
+        assert!(queue.list(None).unwrap().is_empty());
+    }
+}
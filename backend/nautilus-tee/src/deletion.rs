@@ -0,0 +1,356 @@
+/**
+ * Vault Deletion
+ * Destroying everything the enclave holds for a vault, with evidence
+ *
+ * Deletion drops the vault's in-memory secrets (resubmitted shares,
+ * reassembled content keys, decryption sessions and their plaintext,
+ * voice challenges), then erases its stored records, templates, tokens,
+ * events, notification jobs and archive manifests (see Store::erase;
+ * archived objects are left to expire from the bucket, see archive). The
+ * deletion record lists every artifact destroyed and is attested for
+ * compliance evidence.
+ *
+ * The vault's derived keys (see keys::derive) are never stored, so there
+ * is nothing to erase; the record names them by key id, and the vault id
+ * stays retired so nothing is written under them again.
+ */
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::archive;
+use crate::capability::CapabilityService;
+use crate::dead_letters;
+use crate::keys::derive::{
+    Archival, AuditSigning, KeyHierarchy, KeyPurpose, Storage, TemplateEncryption,
+};
+use crate::notifications;
+use crate::secrets::SecretsService;
+use crate::share_grants::ShareGrantService;
+use crate::store::Store;
+use crate::templates::TemplateService;
+use crate::threshold::ThresholdService;
+use crate::vault_index;
+use crate::vault_state::{self, VAULT_NAMESPACES};
+use crate::voice::VoiceGuard;
+use crate::webhooks;
+
+pub const OPERATION: &str = "vault_deletion";
+const NAMESPACE: &str = "vault_deletions";
+/// Event feed entries name their vault but don't move with it
+const EVENTS_NAMESPACE: &str = "events";
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArtifactLocation {
+    Memory,
+    Store,
+    DerivedKey,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DestroyedArtifact {
+    pub location: ArtifactLocation,
+    /// Namespace, in-memory cache or key purpose
+    pub name: String,
+    pub count: usize,
+    /// Store keys, session ids or key ids, where they exist
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ids: Vec<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct VaultDeletion {
+    pub vault_id: String,
+    pub deleted_by: String,
+    pub artifacts: Vec<DestroyedArtifact>,
+    pub deleted_at: u64,
+}
+
+impl VaultDeletion {
+    /// Digest bound into the deletion attestation
+    pub fn digest(&self) -> Result<Vec<u8>, String> {
+        let bytes = serde_json::to_vec(self)
+            .map_err(|e| format!("Failed to serialize vault deletion: {}", e))?;
+        Ok(Sha256::digest(bytes).to_vec())
+    }
+}
+
+pub struct DeletionService {
+    store: Arc<Store>,
+    keys: Arc<KeyHierarchy>,
+    secrets: Arc<SecretsService>,
+    threshold: Arc<ThresholdService>,
+    voice: Arc<VoiceGuard>,
+    templates: Arc<TemplateService>,
+    capabilities: Arc<CapabilityService>,
+    share_grants: Arc<ShareGrantService>,
+    // One deletion at a time, so a vault is never deleted twice
+    lock: Mutex<()>,
+}
+
+impl DeletionService {
+    pub fn new(
+        store: Arc<Store>,
+        keys: Arc<KeyHierarchy>,
+        secrets: Arc<SecretsService>,
+        threshold: Arc<ThresholdService>,
+        voice: Arc<VoiceGuard>,
+        templates: Arc<TemplateService>,
+        capabilities: Arc<CapabilityService>,
+        share_grants: Arc<ShareGrantService>,
+    ) -> Self {
+        Self {
+            store,
+            keys,
+            secrets,
+            threshold,
+            voice,
+            templates,
+            capabilities,
+            share_grants,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Destroy the vault; the caller has been authorized
+    pub fn delete(&self, vault_id: &str, caller: &str) -> Result<VaultDeletion, String> {
+        let _guard = self.lock.lock().unwrap();
+        if self.deletion(vault_id)?.is_some() {
+            return Err("Vault has already been deleted".to_string());
+        }
+
+        let (shares, content_key) = self.secrets.forget(vault_id);
+        let sessions = self.threshold.forget(vault_id);
+        let mut artifacts: Vec<DestroyedArtifact> = [
+            memory("submitted_shares", shares, Vec::new()),
+            memory(
+                "reconstructed_content_key",
+                content_key as usize,
+                Vec::new(),
+            ),
+            memory("decryption_sessions", sessions.len(), sessions),
+            memory("voice_challenges", self.voice.forget(vault_id), Vec::new()),
+        ]
+        .into_iter()
+        .filter(|artifact| artifact.count > 0)
+        .collect();
+
+        let namespaces: Vec<&str> = VAULT_NAMESPACES
+            .iter()
+            .copied()
+            // Notification jobs, webhooks, dead letters, index entries and
+            // archive manifests don't move with the vault either
+            .chain([
+                EVENTS_NAMESPACE,
+                notifications::JOBS_NAMESPACE,
+                webhooks::ENDPOINTS_NAMESPACE,
+                webhooks::DELIVERIES_NAMESPACE,
+                dead_letters::NAMESPACE,
+                vault_index::NAMESPACE,
+                archive::MANIFESTS_NAMESPACE,
+            ])
+            .collect();
+        for erased in vault_state::erase(&self.store, vault_id, &namespaces)? {
+            artifacts.push(stored(erased.namespace, erased.keys));
+        }
+        let templates = self.templates.remove_vault(vault_id)?;
+        if !templates.is_empty() {
+            artifacts.push(stored("biometric_templates".to_string(), templates));
+        }
+        let tokens = self.capabilities.erase_vault(vault_id)?;
+        if !tokens.is_empty() {
+            artifacts.push(stored("capabilities".to_string(), tokens));
+        }
+        let grants = self.share_grants.erase_vault(vault_id)?;
+        if !grants.is_empty() {
+            artifacts.push(stored("share_grants".to_string(), grants));
+        }
+
+        artifacts.push(self.derived::<TemplateEncryption>(vault_id));
+        artifacts.push(self.derived::<AuditSigning>(vault_id));
+        artifacts.push(self.derived::<Storage>(vault_id));
+        artifacts.push(self.derived::<Archival>(vault_id));
+
+        let deletion = VaultDeletion {
+            vault_id: vault_id.to_string(),
+            deleted_by: caller.to_string(),
+            artifacts,
+            deleted_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+        };
+        self.store.put(NAMESPACE, vault_id, &deletion)?;
+        Ok(deletion)
+    }
+
+    pub fn deletion(&self, vault_id: &str) -> Result<Option<VaultDeletion>, String> {
+        self.store.get(NAMESPACE, vault_id)
+    }
+
+    fn derived<P: KeyPurpose>(&self, vault_id: &str) -> DestroyedArtifact {
+        DestroyedArtifact {
+            location: ArtifactLocation::DerivedKey,
+            name: P::LABEL.to_string(),
+            count: 1,
+            ids: vec![self.keys.key_id::<P>(vault_id)],
+        }
+    }
+}
+
+fn memory(name: &str, count: usize, ids: Vec<String>) -> DestroyedArtifact {
+    DestroyedArtifact {
+        location: ArtifactLocation::Memory,
+        name: name.to_string(),
+        count,
+        ids,
+    }
+}
+
+fn stored(namespace: String, keys: Vec<String>) -> DestroyedArtifact {
+    DestroyedArtifact {
+        location: ArtifactLocation::Store,
+        name: namespace,
+        count: keys.len(),
+        ids: keys,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::AuditTrail;
+    use crate::integrity::IntegrityService;
+    use crate::keys::identity::EnclaveIdentity;
+    use crate::keys::release::{KeyRelease, KeyReleasePolicy};
+    use crate::randomness::RandomnessSource;
+    use crate::share_grants::GrantTerms;
+    use serde_json::{json, Value};
+
+    fn service(name: &str) -> DeletionService {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("lumina-deletion-tests-{}-{}", name, nanos));
+        let store = Arc::new(Store::open(dir).unwrap());
+        let keys = Arc::new(KeyHierarchy::ephemeral());
+        let identity = Arc::new(EnclaveIdentity::ephemeral(&"00".repeat(32), "test"));
+        let audit = Arc::new(AuditTrail::new(store.clone(), keys.clone()));
+        let policy = KeyReleasePolicy {
+            allow_debug: true,
+            pcr8_signers: Vec::new(),
+        };
+        let release = Arc::new(KeyRelease::new(policy, None, audit));
+        let capabilities = Arc::new(CapabilityService::new(identity.clone(), store.clone()));
+        DeletionService::new(
+            store.clone(),
+            keys.clone(),
+            Arc::new(SecretsService::new(store.clone())),
+            Arc::new(ThresholdService::new(
+                store.clone(),
+                Arc::new(IntegrityService::new(store.clone())),
+            )),
+            Arc::new(VoiceGuard::new(
+                store.clone(),
+                Arc::new(RandomnessSource::new()),
+            )),
+            Arc::new(TemplateService::new(
+                store.clone(),
+                keys,
+                release,
+                None,
+                Vec::new(),
+            )),
+            capabilities.clone(),
+            Arc::new(ShareGrantService::new(identity, store, capabilities)),
+        )
+    }
+
+    /// Records, a capability, a share grant and a voice challenge for the vault
+    fn populate(deletions: &DeletionService, vault_id: &str) -> (String, String) {
+        let store = &deletions.store;
+        store
+            .put("escalation_ladders", vault_id, &json!({ "stages": [] }))
+            .unwrap();
+        let event = format!("{}-event", vault_id);
+        store
+            .put(EVENTS_NAMESPACE, &event, &json!({ "vault_id": vault_id }))
+            .unwrap();
+        let (_, token) = deletions
+            .capabilities
+            .mint(vault_id, "0xowner", "0xb0b", &["audit".to_string()], 60)
+            .unwrap();
+        let terms = GrantTerms {
+            operations: vec!["audit".to_string()],
+            link_ttl_secs: 60,
+            capability_ttl_secs: 60,
+            password: None,
+            proof: None,
+        };
+        let (_, grant) = deletions
+            .share_grants
+            .create(vault_id, "0xowner", terms)
+            .unwrap();
+        deletions.voice.issue(vault_id);
+        (token.token_id, grant.grant_id)
+    }
+
+    fn artifact<'a>(deletion: &'a VaultDeletion, name: &str) -> &'a DestroyedArtifact {
+        deletion
+            .artifacts
+            .iter()
+            .find(|a| a.name == name)
+            .unwrap_or_else(|| panic!("{} wasn't destroyed", name))
+    }
+
+    #[test]
+    fn deletion_erases_the_vault_and_records_what_was_destroyed() {
+        let deletions = service("erase");
+        let (token_id, grant_id) = populate(&deletions, "vault-1");
+        // A vault whose id extends this one's is left alone
+        populate(&deletions, "vault-10");
+
+        let deletion = deletions.delete("vault-1", "0xowner").unwrap();
+        assert_eq!(artifact(&deletion, "escalation_ladders").ids, ["vault-1"]);
+        assert_eq!(artifact(&deletion, EVENTS_NAMESPACE).ids, ["vault-1-event"]);
+        assert_eq!(artifact(&deletion, "capabilities").ids, [token_id]);
+        assert_eq!(artifact(&deletion, "share_grants").ids, [grant_id]);
+        assert_eq!(artifact(&deletion, "voice_challenges").count, 1);
+        let storage = artifact(&deletion, Storage::LABEL);
+        assert_eq!(storage.ids, [deletions.keys.key_id::<Storage>("vault-1")]);
+
+        let store = &deletions.store;
+        let ladder = |vault_id| store.get::<Value>("escalation_ladders", vault_id).unwrap();
+        assert!(ladder("vault-1").is_none());
+        assert!(ladder("vault-10").is_some());
+        assert_eq!(
+            deletions
+                .capabilities
+                .erase_vault("vault-10")
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(deletions.voice.forget("vault-10"), 1);
+
+        let recorded = deletions.deletion("vault-1").unwrap().unwrap();
+        assert_eq!(recorded.digest().unwrap(), deletion.digest().unwrap());
+    }
+
+    #[test]
+    fn vaults_are_deleted_only_once() {
+        let deletions = service("once");
+        populate(&deletions, "vault-1");
+        let first = deletions.delete("vault-1", "0xowner").unwrap();
+
+        let again = deletions.delete("vault-1", "0xowner").err().unwrap();
+        assert!(again.contains("already been deleted"));
+        let recorded = deletions.deletion("vault-1").unwrap().unwrap();
+        assert_eq!(recorded.digest().unwrap(), first.digest().unwrap());
+        assert!(deletions.deletion("vault-2").unwrap().is_none());
+    }
+}
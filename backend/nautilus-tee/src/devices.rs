@@ -0,0 +1,344 @@
+/**
+ * Heartbeat Devices
+ * Per-device signing keys whose heartbeats count as liveness signals
+ *
+ * An owner enrolls each trusted device (a phone, a laptop) under the vault
+ * with the device's own Ed25519 key. Enrolling or rotating a key needs a
+ * proof of possession, the device's signature over
+ *
+ *   "lumina-device-enroll-v1:" || "<vault_id>:<public key, base64>"
+ *
+ * A heartbeat is the device's signature over
+ *
+ *   "lumina-device-heartbeat-v1:" || "<vault_id>:<device_id>:<timestamp>"
+ *
+ * with a timestamp near the enclave's clock and later than the device's
+ * previous heartbeat, so a captured heartbeat can't be replayed. Devices
+ * are revoked one at a time; a revoked device's key never counts again.
+ * The owner can require heartbeats from at least K distinct devices within
+ * a window before liveness scores the vault's owner as alive.
+ */
+
+use ed25519_dalek::{Signature, VerifyingKey};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::auth::normalize_address;
+use crate::crypto;
+use crate::store::Store;
+
+const NAMESPACE: &str = "heartbeat_devices";
+const ENROLL_DOMAIN: &[u8] = b"lumina-device-enroll-v1:";
+const HEARTBEAT_DOMAIN: &[u8] = b"lumina-device-heartbeat-v1:";
+const MAX_DEVICES: usize = 16;
+const MAX_NAME_CHARS: usize = 64;
+const MAX_SKEW_SECS: u64 = 300;
+const MAX_WINDOW_HOURS: u32 = 24 * 90;
+const SECS_PER_HOUR: u64 = 60 * 60;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Device {
+    pub device_id: String,
+    pub name: String,
+    /// Base64 Ed25519 public key
+    pub public_key: String,
+    pub enrolled_at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotated_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revoked_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_heartbeat: Option<u64>,
+}
+
+/// Heartbeats from `min_devices` distinct devices within the window
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DeviceRequirement {
+    pub min_devices: u32,
+    pub window_hours: u32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DeviceSet {
+    pub vault_id: String,
+    pub owner: String,
+    pub devices: Vec<Device>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requirement: Option<DeviceRequirement>,
+    pub updated_at: u64,
+}
+
+/// What a vault's devices contribute to its liveness score
+#[derive(Clone, Serialize)]
+pub struct DeviceSignals {
+    /// Unrevoked devices heard from within the window
+    pub active: usize,
+    /// Zero when the owner set no requirement
+    pub required: u32,
+    pub window_hours: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_heartbeat: Option<u64>,
+}
+
+pub struct DeviceService {
+    store: Arc<Store>,
+    /// Held across read-modify-write so enrollments and heartbeats don't
+    /// overwrite each other
+    writes: Mutex<()>,
+}
+
+impl DeviceService {
+    pub fn new(store: Arc<Store>) -> Self {
+        Self {
+            store,
+            writes: Mutex::new(()),
+        }
+    }
+
+    pub fn devices(&self, vault_id: &str) -> Result<Option<DeviceSet>, String> {
+        self.store.get(NAMESPACE, vault_id)
+    }
+
+    /// Enroll a device key. The caller has been authorized.
+    pub fn enroll(
+        &self,
+        vault_id: &str,
+        owner: &str,
+        name: &str,
+        public_key: &str,
+        proof: &str,
+    ) -> Result<Device, String> {
+        let name = name.trim();
+        if name.is_empty() || name.chars().count() > MAX_NAME_CHARS {
+            return Err(format!(
+                "Device name must be 1 to {} characters",
+                MAX_NAME_CHARS
+            ));
+        }
+        verify_possession(vault_id, public_key, proof)?;
+
+        let _writes = self.writes.lock().unwrap();
+        let now = now();
+        let mut set = self.devices(vault_id)?.unwrap_or_else(|| DeviceSet {
+            vault_id: vault_id.to_string(),
+            owner: String::new(),
+            devices: Vec::new(),
+            requirement: None,
+            updated_at: now,
+        });
+        if set
+            .devices
+            .iter()
+            .filter(|d| d.revoked_at.is_none())
+            .count()
+            >= MAX_DEVICES
+        {
+            return Err(format!("At most {} devices can be enrolled", MAX_DEVICES));
+        }
+        require_unused(&set, public_key)?;
+
+        let mut id = [0u8; 16];
+        OsRng.fill_bytes(&mut id);
+        let device = Device {
+            device_id: hex::encode(id),
+            name: name.to_string(),
+            public_key: public_key.to_string(),
+            enrolled_at: now,
+            rotated_at: None,
+            revoked_at: None,
+            last_heartbeat: None,
+        };
+        set.owner = normalize_address(owner);
+        set.devices.push(device.clone());
+        set.updated_at = now;
+        self.store.put(NAMESPACE, vault_id, &set)?;
+        Ok(device)
+    }
+
+    /// Replace a device's key, keeping its identity. The caller has been
+    /// authorized.
+    pub fn rotate(
+        &self,
+        vault_id: &str,
+        device_id: &str,
+        public_key: &str,
+        proof: &str,
+    ) -> Result<Device, String> {
+        verify_possession(vault_id, public_key, proof)?;
+
+        let _writes = self.writes.lock().unwrap();
+        let mut set = self.devices(vault_id)?.ok_or("Vault has no devices")?;
+        require_unused(&set, public_key)?;
+        let now = now();
+        let device = active_device(&mut set, device_id)?;
+        device.public_key = public_key.to_string();
+        device.rotated_at = Some(now);
+        let device = device.clone();
+        set.updated_at = now;
+        self.store.put(NAMESPACE, vault_id, &set)?;
+        Ok(device)
+    }
+
+    /// The caller has been authorized
+    pub fn revoke(&self, vault_id: &str, device_id: &str) -> Result<Device, String> {
+        let _writes = self.writes.lock().unwrap();
+        let mut set = self.devices(vault_id)?.ok_or("Vault has no devices")?;
+        let now = now();
+        let device = active_device(&mut set, device_id)?;
+        device.revoked_at = Some(now);
+        let device = device.clone();
+        set.updated_at = now;
+        self.store.put(NAMESPACE, vault_id, &set)?;
+        Ok(device)
+    }
+
+    /// `min_devices` of zero drops the requirement. The caller has been
+    /// authorized.
+    pub fn set_requirement(
+        &self,
+        vault_id: &str,
+        owner: &str,
+        min_devices: u32,
+        window_hours: u32,
+    ) -> Result<DeviceSet, String> {
+        let _writes = self.writes.lock().unwrap();
+        let mut set = self.devices(vault_id)?.ok_or("Vault has no devices")?;
+        let requirement = if min_devices == 0 {
+            None
+        } else {
+            let enrolled = set
+                .devices
+                .iter()
+                .filter(|d| d.revoked_at.is_none())
+                .count();
+            if min_devices as usize > enrolled {
+                return Err(format!(
+                    "Can't require {} devices with {} enrolled",
+                    min_devices, enrolled
+                ));
+            }
+            if window_hours == 0 || window_hours > MAX_WINDOW_HOURS {
+                return Err(format!(
+                    "Device window must be 1 to {} hours",
+                    MAX_WINDOW_HOURS
+                ));
+            }
+            Some(DeviceRequirement {
+                min_devices,
+                window_hours,
+            })
+        };
+
+        set.owner = normalize_address(owner);
+        set.requirement = requirement;
+        set.updated_at = now();
+        self.store.put(NAMESPACE, vault_id, &set)?;
+        Ok(set)
+    }
+
+    /// Record a heartbeat signed by the device's key
+    pub fn heartbeat(
+        &self,
+        vault_id: &str,
+        device_id: &str,
+        timestamp: u64,
+        signature: &str,
+    ) -> Result<Device, String> {
+        let now = now();
+        if timestamp.abs_diff(now) > MAX_SKEW_SECS {
+            return Err("Heartbeat timestamp is outside the allowed window".to_string());
+        }
+
+        let _writes = self.writes.lock().unwrap();
+        let mut set = self.devices(vault_id)?.ok_or("Vault has no devices")?;
+        let device = active_device(&mut set, device_id)?;
+        let message = format!("{}:{}:{}", vault_id, device_id, timestamp);
+        verify(
+            &device.public_key,
+            &[HEARTBEAT_DOMAIN, message.as_bytes()].concat(),
+            signature,
+        )?;
+        if device.last_heartbeat.is_some_and(|last| timestamp <= last) {
+            return Err("Heartbeat is not newer than the device's last one".to_string());
+        }
+        device.last_heartbeat = Some(timestamp);
+        let device = device.clone();
+        self.store.put(NAMESPACE, vault_id, &set)?;
+        Ok(device)
+    }
+
+    /// None when the vault has no devices
+    pub fn signals(&self, vault_id: &str, now: u64) -> Result<Option<DeviceSignals>, String> {
+        let Some(set) = self.devices(vault_id)? else {
+            return Ok(None);
+        };
+        let (required, window_hours) = set
+            .requirement
+            .as_ref()
+            .map_or((0, 0), |r| (r.min_devices, r.window_hours));
+        let since = now.saturating_sub(u64::from(window_hours) * SECS_PER_HOUR);
+        let heard = set
+            .devices
+            .iter()
+            .filter(|d| d.revoked_at.is_none())
+            .filter_map(|d| d.last_heartbeat);
+        Ok(Some(DeviceSignals {
+            active: heard.clone().filter(|at| *at >= since).count(),
+            required,
+            window_hours,
+            last_heartbeat: heard.max(),
+        }))
+    }
+}
+
+fn active_device<'a>(set: &'a mut DeviceSet, device_id: &str) -> Result<&'a mut Device, String> {
+    let device = set
+        .devices
+        .iter_mut()
+        .find(|d| d.device_id == device_id)
+        .ok_or("No such device")?;
+    if device.revoked_at.is_some() {
+        return Err("Device has been revoked".to_string());
+    }
+    Ok(device)
+}
+
+/// A key counts for one device once, revoked devices included
+fn require_unused(set: &DeviceSet, public_key: &str) -> Result<(), String> {
+    if set.devices.iter().any(|d| d.public_key == public_key) {
+        return Err("Key is already enrolled for this vault".to_string());
+    }
+    Ok(())
+}
+
+fn verify_possession(vault_id: &str, public_key: &str, proof: &str) -> Result<(), String> {
+    let message = format!("{}:{}", vault_id, public_key);
+    verify(
+        public_key,
+        &[ENROLL_DOMAIN, message.as_bytes()].concat(),
+        proof,
+    )
+    .map_err(|e| format!("Proof of possession failed: {}", e))
+}
+
+fn verify(public_key: &str, message: &[u8], signature: &str) -> Result<(), String> {
+    let key: [u8; 32] = crypto::decode(public_key)?
+        .try_into()
+        .map_err(|_| "Ed25519 public key must be 32 bytes".to_string())?;
+    let key = VerifyingKey::from_bytes(&key).map_err(|_| "Invalid Ed25519 public key")?;
+    let signature: [u8; 64] = crypto::decode(signature)?
+        .try_into()
+        .map_err(|_| "Ed25519 signature must be 64 bytes".to_string())?;
+    key.verify_strict(message, &Signature::from_bytes(&signature))
+        .map_err(|_| "Signature does not verify".to_string())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
@@ -0,0 +1,585 @@
+/**
+ * DKIM Email
+ * Received emails with a DKIM signature, canonicalized for the dkim_email
+ * circuit
+ *
+ * The enclave parses the .eml, rebuilds the signed header string and the
+ * canonical body the way the signer did (RFC 6376), and checks the body
+ * hash and RSA-SHA256 signature before producing a witness, so an email
+ * that wouldn't verify fails here rather than in the prover.
+ *
+ * The enclave can't resolve DNS, so the claim names the domain's DKIM key
+ * (the p= value of its selector record). The key is a public signal and
+ * verifiers check it against DNS themselves.
+ *
+ * Limits of the circuit:
+ *   - rsa-sha256 with 2048-bit keys only
+ *   - relaxed header canonicalization (the circuit matches the lowercased
+ *     "dkim-signature:" name); the body may be simple or relaxed
+ *   - signed headers up to 1024 bytes and, from the 64-byte block holding
+ *     the start of the pattern, at most 2048 bytes of body
+ *   - no l= tag, and the pattern is matched in the raw (for example
+ *     quoted-printable) body, as signed
+ */
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ring::signature::{RsaPublicKeyComponents, RSA_PKCS1_2048_8192_SHA256};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::digest::generic_array::GenericArray;
+use sha2::{Digest, Sha256};
+
+use crate::field;
+
+pub const MAX_HEADER_BYTES: usize = 1024;
+pub const MAX_BODY_BYTES: usize = 2048;
+pub const MAX_DOMAIN_BYTES: usize = 64;
+pub const MAX_PATTERN_BYTES: usize = 64;
+const LIMB_BITS: usize = 121;
+const LIMBS: usize = 17;
+const RSA_BYTES: usize = 256;
+const SHA256_IV: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+#[derive(Deserialize)]
+pub struct DkimEmailClaim {
+    /// Signing domain, the DKIM-Signature d= tag
+    pub domain: String,
+    /// The p= value of the domain's DKIM record (base64 DER, either
+    /// SubjectPublicKeyInfo or a bare RSAPublicKey)
+    pub public_key: String,
+    /// Bytes the signed body must contain
+    pub pattern: String,
+}
+
+/// Witness for the dkim_email circuit
+pub struct EmailWitness {
+    /// Circuit input signals, by name
+    pub inputs: Value,
+    /// Public signals in the order the verifier sees them: the public
+    /// inputs in declaration order (the circuit has no outputs)
+    pub public_signals: Vec<String>,
+}
+
+/// A header field as it appears in the message, folding included
+struct HeaderField<'a> {
+    name: &'a str,
+    raw: &'a str,
+}
+
+struct Signature<'a> {
+    field: &'a HeaderField<'a>,
+    signed_headers: Vec<String>,
+    relaxed_body: bool,
+    body_hash: Vec<u8>,
+    signature: Vec<u8>,
+}
+
+impl DkimEmailClaim {
+    pub fn witness(&self, eml: &[u8]) -> Result<EmailWitness, String> {
+        let domain = self.domain.trim().to_ascii_lowercase();
+        if domain.is_empty() || domain.len() > MAX_DOMAIN_BYTES {
+            return Err(format!("domain must be 1 to {} bytes", MAX_DOMAIN_BYTES));
+        }
+        let pattern = self.pattern.as_bytes();
+        if pattern.is_empty() || pattern.len() > MAX_PATTERN_BYTES || pattern.contains(&0) {
+            return Err(format!(
+                "pattern must be 1 to {} non-NUL bytes",
+                MAX_PATTERN_BYTES
+            ));
+        }
+        let (modulus, exponent) = rsa_public_key(&self.public_key)?;
+
+        let eml = crlf(eml);
+        let split = find(&eml, b"\r\n\r\n").ok_or_else(|| "Email has no body".to_string())?;
+        let header_block = std::str::from_utf8(&eml[..split + 2])
+            .map_err(|_| "Email headers must be UTF-8".to_string())?;
+        let body = &eml[split + 4..];
+        let fields = header_fields(header_block);
+
+        let dkim = fields
+            .iter()
+            .find(|f| {
+                f.name.eq_ignore_ascii_case("DKIM-Signature")
+                    && tag(f, "d").is_some_and(|d| d.eq_ignore_ascii_case(&domain))
+            })
+            .ok_or_else(|| format!("Email has no DKIM signature from {}", domain))
+            .and_then(parse_signature)?;
+
+        // Body hash
+        let body = canonical_body(body, dkim.relaxed_body);
+        if Sha256::digest(&body).as_slice() != dkim.body_hash.as_slice() {
+            return Err("Body hash doesn't match the DKIM signature".to_string());
+        }
+
+        // Signed header string: the listed headers, bottom-up for repeated
+        // names, then the DKIM-Signature header without its b= value
+        let mut header = String::new();
+        let mut used: Vec<usize> = Vec::new();
+        for name in &dkim.signed_headers {
+            let next = fields
+                .iter()
+                .enumerate()
+                .rev()
+                .find(|(i, f)| f.name.eq_ignore_ascii_case(name) && !used.contains(i));
+            if let Some((i, field)) = next {
+                used.push(i);
+                header.push_str(&relaxed_header(field.raw));
+            }
+        }
+        let dkim_header_index = header.len();
+        let unsigned = relaxed_header(&without_signature(dkim.field.raw));
+        header.push_str(unsigned.trim_end_matches("\r\n"));
+
+        RsaPublicKeyComponents {
+            n: &modulus,
+            e: &exponent,
+        }
+        .verify(
+            &RSA_PKCS1_2048_8192_SHA256,
+            header.as_bytes(),
+            &dkim.signature,
+        )
+        .map_err(|_| "DKIM signature doesn't verify with the given key".to_string())?;
+
+        // Positions the circuit checks
+        let dkim_header = &header[dkim_header_index..];
+        let domain_index = dkim_header_index
+            + tag_offset(dkim_header, "d")
+                .filter(|&at| {
+                    dkim_header[..at].ends_with("d=")
+                        && dkim_header[at..].starts_with(&domain)
+                        && matches!(
+                            dkim_header.as_bytes().get(at + domain.len()),
+                            Some(b';' | b' ' | b'\t')
+                        )
+                })
+                .ok_or_else(|| {
+                    "The d= tag must give the domain in lowercase, followed by ';'".to_string()
+                })?;
+        let body_hash_index = dkim_header_index
+            + tag_offset(dkim_header, "bh").ok_or_else(|| "Missing bh= tag".to_string())?;
+
+        let padded_header = sha_padded(header.as_bytes(), MAX_HEADER_BYTES)
+            .ok_or_else(|| format!("Signed headers exceed {} bytes", MAX_HEADER_BYTES))?;
+
+        // Hash the body up to the block holding the pattern in the enclave
+        let at =
+            find(&body, pattern).ok_or_else(|| "Pattern isn't in the signed body".to_string())?;
+        let cut = at / 64 * 64;
+        let mut padded_body = sha_padded(&body, usize::MAX).unwrap();
+        let mut state = SHA256_IV;
+        let blocks: Vec<_> = padded_body[..cut]
+            .chunks(64)
+            .map(|block| *GenericArray::from_slice(block))
+            .collect();
+        sha2::compress256(&mut state, &blocks);
+        padded_body.drain(..cut);
+        let body_length = padded_body.len();
+        if body_length > MAX_BODY_BYTES {
+            return Err(format!(
+                "Body after the pattern exceeds {} bytes",
+                MAX_BODY_BYTES
+            ));
+        }
+        padded_body.resize(MAX_BODY_BYTES, 0);
+        let precomputed_sha: Vec<String> = state
+            .iter()
+            .flat_map(|word| word.to_be_bytes())
+            .map(|b| b.to_string())
+            .collect();
+
+        let pubkey = limbs(&modulus);
+        let domain_signal = field::packed(domain.as_bytes(), MAX_DOMAIN_BYTES);
+        let pattern_signal = field::packed(pattern, MAX_PATTERN_BYTES);
+        let mut public_signals = pubkey.clone();
+        public_signals.extend(domain_signal.iter().cloned());
+        public_signals.extend(pattern_signal.iter().cloned());
+
+        let padded = |bytes: &[u8], max: usize| -> Vec<String> {
+            let mut out: Vec<String> = bytes.iter().map(u8::to_string).collect();
+            out.resize(max, "0".to_string());
+            out
+        };
+        let inputs = json!({
+            "pubkey": pubkey,
+            "domain": domain_signal,
+            "pattern": pattern_signal,
+            "email_header": padded(&padded_header, MAX_HEADER_BYTES),
+            "email_header_length": padded_header.len().to_string(),
+            "signature": limbs(&dkim.signature),
+            "email_body": padded(&padded_body, MAX_BODY_BYTES),
+            "email_body_length": body_length.to_string(),
+            "body_hash_index": body_hash_index.to_string(),
+            "precomputed_sha": precomputed_sha,
+            "dkim_header_index": dkim_header_index.to_string(),
+            "domain_index": domain_index.to_string(),
+            "domain_bytes": padded(domain.as_bytes(), MAX_DOMAIN_BYTES),
+            "domain_length": domain.len().to_string(),
+            "pattern_index": (at - cut).to_string(),
+            "pattern_bytes": padded(pattern, MAX_PATTERN_BYTES),
+            "pattern_length": pattern.len().to_string(),
+        });
+
+        Ok(EmailWitness {
+            inputs,
+            public_signals,
+        })
+    }
+}
+
+fn parse_signature<'a>(field: &'a HeaderField<'a>) -> Result<Signature<'a>, String> {
+    let required =
+        |name: &str| tag(field, name).ok_or_else(|| format!("DKIM signature has no {}= tag", name));
+    if required("v")? != "1" {
+        return Err("Unsupported DKIM version".to_string());
+    }
+    if !required("a")?.eq_ignore_ascii_case("rsa-sha256") {
+        return Err("Only rsa-sha256 DKIM signatures can be proven".to_string());
+    }
+    if tag(field, "l").is_some() {
+        return Err("DKIM signatures with an l= body length can't be proven".to_string());
+    }
+    let canonicalization = tag(field, "c").unwrap_or_else(|| "simple/simple".to_string());
+    let (header_c, body_c) = canonicalization
+        .split_once('/')
+        .unwrap_or((&canonicalization, "simple"));
+    if header_c != "relaxed" {
+        return Err("Only relaxed header canonicalization can be proven".to_string());
+    }
+    let decode = |name: &str| {
+        STANDARD
+            .decode(required(name)?)
+            .map_err(|_| format!("Invalid {}= tag", name))
+    };
+
+    Ok(Signature {
+        field,
+        signed_headers: required("h")?
+            .split(':')
+            .map(|h| h.trim().to_string())
+            .collect(),
+        relaxed_body: body_c == "relaxed",
+        body_hash: decode("bh")?,
+        signature: decode("b")?,
+    })
+}
+
+/// A tag value of a DKIM-Signature header, whitespace removed
+fn tag(field: &HeaderField, name: &str) -> Option<String> {
+    let value = field.raw.split_once(':')?.1;
+    value.split(';').find_map(|spec| {
+        let (tag, value) = spec.split_once('=')?;
+        (tag.trim() == name).then(|| value.split_whitespace().collect())
+    })
+}
+
+/// Offset of a tag's value in a canonicalized DKIM-Signature header
+fn tag_offset(header: &str, name: &str) -> Option<usize> {
+    let value_start = header.find(':')? + 1;
+    let mut offset = value_start;
+    for spec in header[value_start..].split(';') {
+        if let Some((tag, _)) = spec.split_once('=') {
+            if tag.trim() == name {
+                return Some(offset + tag.len() + 1);
+            }
+        }
+        offset += spec.len() + 1;
+    }
+    None
+}
+
+/// The header with the b= tag's value emptied, as it was signed
+fn without_signature(raw: &str) -> String {
+    let Some(colon) = raw.find(':') else {
+        return raw.to_string();
+    };
+    let mut offset = colon + 1;
+    for spec in raw[colon + 1..].split(';') {
+        if let Some((tag, _)) = spec.split_once('=') {
+            if tag.trim() == "b" {
+                let value_start = offset + tag.len() + 1;
+                return format!("{}{}", &raw[..value_start], &raw[offset + spec.len()..]);
+            }
+        }
+        offset += spec.len() + 1;
+    }
+    raw.to_string()
+}
+
+fn header_fields(block: &str) -> Vec<HeaderField<'_>> {
+    let mut fields = Vec::new();
+    let mut start = 0;
+    for (end, _) in block.match_indices("\r\n") {
+        let next = block.as_bytes().get(end + 2);
+        if matches!(next, Some(b' ' | b'\t')) {
+            continue;
+        }
+        let raw = &block[start..end];
+        if let Some((name, _)) = raw.split_once(':') {
+            fields.push(HeaderField {
+                name: name.trim(),
+                raw,
+            });
+        }
+        start = end + 2;
+    }
+    fields
+}
+
+/// Relaxed header canonicalization (RFC 6376 3.4.2)
+fn relaxed_header(raw: &str) -> String {
+    let (name, value) = raw.split_once(':').unwrap_or((raw, ""));
+    let value = value.replace("\r\n", "");
+    let value: Vec<&str> = value.split([' ', '\t']).filter(|w| !w.is_empty()).collect();
+    format!(
+        "{}:{}\r\n",
+        name.trim().to_ascii_lowercase(),
+        value.join(" ")
+    )
+}
+
+/// Simple or relaxed body canonicalization (RFC 6376 3.4.3, 3.4.4)
+fn canonical_body(body: &[u8], relaxed: bool) -> Vec<u8> {
+    let mut lines: Vec<&[u8]> = body.split(|&b| b == b'\n').collect();
+    if body.ends_with(b"\n") {
+        lines.pop();
+    }
+    let mut out = Vec::with_capacity(body.len() + 2);
+    for line in lines {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if relaxed {
+            let mut in_space = false;
+            for &b in line {
+                if b == b' ' || b == b'\t' {
+                    in_space = true;
+                } else {
+                    if in_space {
+                        out.push(b' ');
+                    }
+                    in_space = false;
+                    out.push(b);
+                }
+            }
+        } else {
+            out.extend_from_slice(line);
+        }
+        out.extend_from_slice(b"\r\n");
+    }
+    while out.ends_with(b"\r\n\r\n") {
+        out.truncate(out.len() - 2);
+    }
+    if out == b"\r\n" && relaxed {
+        out.clear();
+    }
+    if out.is_empty() && !relaxed {
+        out.extend_from_slice(b"\r\n");
+    }
+    out
+}
+
+/// Bare LF line endings, as saved by many clients, back to CRLF
+fn crlf(eml: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(eml.len());
+    for (i, &b) in eml.iter().enumerate() {
+        if b == b'\n' && (i == 0 || eml[i - 1] != b'\r') {
+            out.push(b'\r');
+        }
+        out.push(b);
+    }
+    out
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// SHA-256 padding; None past `max` bytes
+fn sha_padded(bytes: &[u8], max: usize) -> Option<Vec<u8>> {
+    let mut padded = bytes.to_vec();
+    padded.push(0x80);
+    while padded.len() % 64 != 56 {
+        padded.push(0);
+    }
+    padded.extend_from_slice(&(bytes.len() as u64 * 8).to_be_bytes());
+    (padded.len() <= max).then_some(padded)
+}
+
+/// Big-endian integer to the circuit's 121-bit limbs, least significant
+/// first
+fn limbs(bytes: &[u8]) -> Vec<String> {
+    let bit = |i: usize| {
+        let byte = i / 8;
+        byte < bytes.len() && bytes[bytes.len() - 1 - byte] >> (i % 8) & 1 == 1
+    };
+    (0..LIMBS)
+        .map(|limb| {
+            (0..LIMB_BITS)
+                .filter(|&b| bit(limb * LIMB_BITS + b))
+                .fold(0u128, |acc, b| acc | 1 << b)
+                .to_string()
+        })
+        .collect()
+}
+
+/// RSA modulus and exponent from a DKIM p= value
+fn rsa_public_key(p: &str) -> Result<(Vec<u8>, Vec<u8>), String> {
+    let invalid = || "Invalid DKIM public key".to_string();
+    let der = STANDARD
+        .decode(p.split_whitespace().collect::<String>())
+        .map_err(|_| invalid())?;
+    let (tag, outer, _) = der_element(&der).ok_or_else(invalid)?;
+    if tag != 0x30 {
+        return Err(invalid());
+    }
+    let (first_tag, _, rest) = der_element(outer).ok_or_else(invalid)?;
+    let rsa_key = match first_tag {
+        // SubjectPublicKeyInfo: algorithm, then the key in a BIT STRING
+        0x30 => {
+            let (bits_tag, bits, _) = der_element(rest).ok_or_else(invalid)?;
+            if bits_tag != 0x03 || bits.first() != Some(&0) {
+                return Err(invalid());
+            }
+            let (tag, key, _) = der_element(&bits[1..]).ok_or_else(invalid)?;
+            if tag != 0x30 {
+                return Err(invalid());
+            }
+            key
+        }
+        // RSAPublicKey
+        0x02 => outer,
+        _ => return Err(invalid()),
+    };
+    let (n_tag, n, rest) = der_element(rsa_key).ok_or_else(invalid)?;
+    let (e_tag, e, _) = der_element(rest).ok_or_else(invalid)?;
+    if n_tag != 0x02 || e_tag != 0x02 {
+        return Err(invalid());
+    }
+    let strip = |int: &[u8]| {
+        int.iter()
+            .skip_while(|&&b| b == 0)
+            .copied()
+            .collect::<Vec<u8>>()
+    };
+    let (n, e) = (strip(n), strip(e));
+    if n.len() != RSA_BYTES {
+        return Err("Only 2048-bit DKIM keys can be proven".to_string());
+    }
+    Ok((n, e))
+}
+
+/// One DER element: tag, contents and the bytes after it
+fn der_element(der: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = der.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+    let (length, rest) = if first < 0x80 {
+        (usize::from(first), rest)
+    } else {
+        let count = usize::from(first & 0x7f);
+        if count == 0 || count > 4 || rest.len() < count {
+            return None;
+        }
+        let length = rest[..count]
+            .iter()
+            .fold(0usize, |acc, &b| acc << 8 | usize::from(b));
+        (length, &rest[count..])
+    };
+    if rest.len() < length {
+        return None;
+    }
+    Some((tag, &rest[..length], &rest[length..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+    use rsa::pkcs8::EncodePublicKey;
+    use rsa::traits::PublicKeyParts;
+    use rsa::{Pkcs1v15Sign, RsaPrivateKey};
+
+    /// DigestInfo prefix of a PKCS #1 v1.5 SHA-256 signature
+    const SHA256_DIGEST_INFO: [u8; 19] = [
+        0x30, 0x31, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01,
+        0x05, 0x00, 0x04, 0x20,
+    ];
+
+    /// An email from example.com signed relaxed/relaxed over From and Subject
+    fn email(key: &RsaPrivateKey, subject: &str, body: &str) -> String {
+        let body_hash = STANDARD.encode(Sha256::digest(body.as_bytes()));
+        let dkim = format!(
+            "v=1; a=rsa-sha256; c=relaxed/relaxed; d=example.com; s=sel; h=from:subject; bh={}; b=",
+            body_hash
+        );
+        let signed = format!(
+            "from:alice@example.com\r\nsubject:{}\r\ndkim-signature:{}",
+            subject, dkim
+        );
+        let digest = [
+            SHA256_DIGEST_INFO.as_slice(),
+            &Sha256::digest(signed.as_bytes()),
+        ]
+        .concat();
+        let signature = key.sign(Pkcs1v15Sign::new_unprefixed(), &digest).unwrap();
+        format!(
+            "DKIM-Signature: {}{}\r\nFrom: alice@example.com\r\nSubject: {}\r\n\r\n{}",
+            dkim,
+            STANDARD.encode(signature),
+            subject,
+            body
+        )
+    }
+
+    fn claim(key: &RsaPrivateKey, domain: &str, pattern: &str) -> DkimEmailClaim {
+        let public_key = key.to_public_key().to_public_key_der().unwrap();
+        DkimEmailClaim {
+            domain: domain.to_string(),
+            public_key: STANDARD.encode(public_key.as_bytes()),
+            pattern: pattern.to_string(),
+        }
+    }
+
+    #[test]
+    fn signed_emails_yield_a_witness() {
+        let key = RsaPrivateKey::new(&mut OsRng, 2048).unwrap();
+        let eml = email(&key, "Your code", "Your code is 123456\r\n");
+
+        let witness = claim(&key, "example.com", "123456")
+            .witness(eml.as_bytes())
+            .unwrap();
+        assert_eq!(
+            witness.public_signals[..LIMBS],
+            limbs(&key.n().to_bytes_be())[..]
+        );
+        assert_eq!(witness.inputs["domain_length"], "11");
+
+        // Clients often save emails with bare LF line endings
+        let lf = eml.replace("\r\n", "\n");
+        assert!(claim(&key, "example.com", "123456")
+            .witness(lf.as_bytes())
+            .is_ok());
+    }
+
+    #[test]
+    fn emails_that_would_not_verify_are_refused() {
+        let key = RsaPrivateKey::new(&mut OsRng, 2048).unwrap();
+        let eml = email(&key, "Your code", "Your code is 123456\r\n");
+        let refused =
+            |claim: DkimEmailClaim, eml: &str| claim.witness(eml.as_bytes()).err().unwrap();
+
+        let body = eml.replace("123456", "654321");
+        let error = refused(claim(&key, "example.com", "654321"), &body);
+        assert!(error.contains("Body hash"));
+
+        let subject = eml.replace("Subject: Your code", "Subject: Your prize");
+        let error = refused(claim(&key, "example.com", "123456"), &subject);
+        assert!(error.contains("doesn't verify"));
+
+        let error = refused(claim(&key, "example.org", "123456"), &eml);
+        assert!(error.contains("no DKIM signature from example.org"));
+
+        let error = refused(claim(&key, "example.com", "999999"), &eml);
+        assert!(error.contains("Pattern isn't in the signed body"));
+    }
+}
@@ -0,0 +1,281 @@
+/**
+ * Duress Signals
+ * Checking in under coercion while quietly freezing the vault
+ *
+ * An owner can arrange three ways to signal duress: a duress code sent with
+ * a check-in, a heartbeat from a device enrolled as a duress device, or a
+ * biometric match against a template enrolled as a duress template (the
+ * "duress finger"). Each looks like an ordinary success to whoever watches
+ * the response. Behind it, the vault's release paths freeze and its
+ * guardians are notified; the owner's own contacts are not, since the
+ * coercer may be holding their phone.
+ *
+ * Which code, devices and templates signal duress lives here rather than
+ * on the device or template records, so nothing the owner can be made to
+ * list gives it away. For the same reason signals stay out of the audit
+ * trail and the event feed until the freeze is cleared, which takes an
+ * enclave admin or a guardian quorum approval.
+ */
+
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use subtle::ConstantTimeEq;
+use tracing::warn;
+
+use crate::auth::normalize_address;
+use crate::notifications::{Notification, NotificationService};
+use crate::store::Store;
+
+/// Guardians clear a freeze by approving this operation
+pub const CLEAR_OPERATION: &str = "duress_clear";
+
+const SETTINGS_NAMESPACE: &str = "duress_settings";
+const LOCKS_NAMESPACE: &str = "duress_locks";
+const CODE_DOMAIN: &[u8] = b"lumina-duress-code-v1:";
+const MIN_CODE_CHARS: usize = 4;
+const MAX_CODE_CHARS: usize = 64;
+const MAX_MARKED: usize = 16;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CodeHash {
+    pub salt: String,   // Hex
+    pub sha256: String, // Hex, over CODE_DOMAIN || salt || code
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DuressSettings {
+    pub vault_id: String,
+    pub owner: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<CodeHash>,
+    /// Heartbeat device ids
+    #[serde(default)]
+    pub devices: Vec<String>,
+    /// Biometric template ids
+    #[serde(default)]
+    pub templates: Vec<String>,
+    pub updated_at: u64,
+}
+
+/// What configuring duress returns; nothing that identifies the signals
+#[derive(Serialize)]
+pub struct DuressSummary {
+    pub vault_id: String,
+    pub code_set: bool,
+    pub devices: usize,
+    pub templates: usize,
+    pub updated_at: u64,
+}
+
+impl From<&DuressSettings> for DuressSummary {
+    fn from(settings: &DuressSettings) -> Self {
+        Self {
+            vault_id: settings.vault_id.clone(),
+            code_set: settings.code.is_some(),
+            devices: settings.devices.len(),
+            templates: settings.templates.len(),
+            updated_at: settings.updated_at,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuressSource {
+    CheckIn,
+    DeviceHeartbeat,
+    Biometric,
+}
+
+/// Something that succeeded and may have been a duress signal
+pub enum DuressTrigger<'a> {
+    Code(&'a str),
+    Device(&'a str),
+    Template(&'a str),
+}
+
+impl DuressTrigger<'_> {
+    fn source(&self) -> DuressSource {
+        match self {
+            DuressTrigger::Code(_) => DuressSource::CheckIn,
+            DuressTrigger::Device(_) => DuressSource::DeviceHeartbeat,
+            DuressTrigger::Template(_) => DuressSource::Biometric,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DuressLock {
+    pub vault_id: String,
+    pub triggered_at: u64,
+    pub source: DuressSource,
+    /// Duress signals seen while frozen, the first included
+    pub signals: u32,
+    pub last_signal_at: u64,
+}
+
+pub struct DuressService {
+    store: Arc<Store>,
+    notifications: Arc<NotificationService>,
+    /// Held across read-modify-write so concurrent signals count once each
+    writes: Mutex<()>,
+}
+
+impl DuressService {
+    pub fn new(store: Arc<Store>, notifications: Arc<NotificationService>) -> Self {
+        Self {
+            store,
+            notifications,
+            writes: Mutex::new(()),
+        }
+    }
+
+    /// Replace the vault's duress signals. The caller has been authorized
+    /// and has checked the devices and templates belong to the vault.
+    pub fn configure(
+        &self,
+        vault_id: &str,
+        owner: &str,
+        code: Option<&str>,
+        mut devices: Vec<String>,
+        mut templates: Vec<String>,
+    ) -> Result<DuressSettings, String> {
+        let code = match code {
+            Some(code) => {
+                let chars = code.chars().count();
+                if !(MIN_CODE_CHARS..=MAX_CODE_CHARS).contains(&chars) {
+                    return Err(format!(
+                        "Duress code must be {} to {} characters",
+                        MIN_CODE_CHARS, MAX_CODE_CHARS
+                    ));
+                }
+                let mut salt = [0u8; 16];
+                OsRng.fill_bytes(&mut salt);
+                Some(CodeHash {
+                    salt: hex::encode(salt),
+                    sha256: hex::encode(hash_code(&salt, code)),
+                })
+            }
+            None => None,
+        };
+        devices.sort();
+        devices.dedup();
+        templates.sort();
+        templates.dedup();
+        if devices.len() > MAX_MARKED || templates.len() > MAX_MARKED {
+            return Err(format!(
+                "At most {} duress devices and {} duress templates are allowed",
+                MAX_MARKED, MAX_MARKED
+            ));
+        }
+
+        let settings = DuressSettings {
+            vault_id: vault_id.to_string(),
+            owner: normalize_address(owner),
+            code,
+            devices,
+            templates,
+            updated_at: now(),
+        };
+        self.store.put(SETTINGS_NAMESPACE, vault_id, &settings)?;
+        Ok(settings)
+    }
+
+    pub fn settings(&self, vault_id: &str) -> Result<Option<DuressSettings>, String> {
+        self.store.get(SETTINGS_NAMESPACE, vault_id)
+    }
+
+    /// Freeze the vault if `trigger` is one of its duress signals. Returns
+    /// whether it was; callers respond exactly as they would otherwise.
+    pub fn check(&self, vault_id: &str, trigger: DuressTrigger) -> Result<bool, String> {
+        let Some(settings) = self.settings(vault_id)? else {
+            return Ok(false);
+        };
+        let duress = match &trigger {
+            DuressTrigger::Code(code) => settings.code.as_ref().is_some_and(|hash| {
+                let salt = hex::decode(&hash.salt).unwrap_or_default();
+                let expected = hex::decode(&hash.sha256).unwrap_or_default();
+                bool::from(hash_code(&salt, code).as_slice().ct_eq(&expected))
+            }),
+            DuressTrigger::Device(device_id) => settings.devices.iter().any(|d| d == device_id),
+            DuressTrigger::Template(template_id) => {
+                settings.templates.iter().any(|t| t == template_id)
+            }
+        };
+        if duress {
+            self.signal(vault_id, trigger.source())?;
+        }
+        Ok(duress)
+    }
+
+    fn signal(&self, vault_id: &str, source: DuressSource) -> Result<(), String> {
+        let _writes = self.writes.lock().unwrap();
+        let now = now();
+        if let Some(mut lock) = self.lock(vault_id)? {
+            lock.signals += 1;
+            lock.last_signal_at = now;
+            return self.store.put(LOCKS_NAMESPACE, vault_id, &lock);
+        }
+
+        let lock = DuressLock {
+            vault_id: vault_id.to_string(),
+            triggered_at: now,
+            source,
+            signals: 1,
+            last_signal_at: now,
+        };
+        self.store.put(LOCKS_NAMESPACE, vault_id, &lock)?;
+        warn!("Duress signalled for {}; releases frozen", vault_id);
+        self.notifications.notify_guardians(
+            vault_id,
+            &Notification {
+                kind: "duress.signalled".to_string(),
+                title: "Vault owner may be under duress".to_string(),
+                body: format!(
+                    "The owner of vault {} signalled duress. Releases from the vault are frozen \
+                     until an enclave admin or a guardian quorum clears it. Take care contacting \
+                     the owner; someone else may be watching.",
+                    vault_id
+                ),
+            },
+        )?;
+        Ok(())
+    }
+
+    pub fn lock(&self, vault_id: &str) -> Result<Option<DuressLock>, String> {
+        self.store.get(LOCKS_NAMESPACE, vault_id)
+    }
+
+    pub fn is_frozen(&self, vault_id: &str) -> Result<bool, String> {
+        Ok(self.lock(vault_id)?.is_some())
+    }
+
+    /// Lift the freeze. The caller has been authorized.
+    pub fn clear(&self, vault_id: &str) -> Result<DuressLock, String> {
+        let _writes = self.writes.lock().unwrap();
+        let lock = self.lock(vault_id)?.ok_or("Vault is not frozen")?;
+        self.store.update(LOCKS_NAMESPACE, |ns| {
+            ns.remove(vault_id);
+        })?;
+        Ok(lock)
+    }
+}
+
+fn hash_code(salt: &[u8], code: &str) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(CODE_DOMAIN);
+    hasher.update(salt);
+    hasher.update(code.as_bytes());
+    hasher.finalize().to_vec()
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
@@ -0,0 +1,151 @@
+/**
+ * Client-side Embeddings
+ * Feature vectors extracted on the client, for integrators that won't send
+ * raw biometrics to the enclave at all
+ *
+ * An embedding is declared with its encoding and the model that produced
+ * it. The enclave never extracts features in this mode; it only checks the
+ * vector is well formed, that its model is compatible with the one the
+ * template was enrolled with, and matches by cosine similarity.
+ *
+ * Models are compatible when they share a name, a dimension and a major
+ * version: minor versions are expected to keep the embedding space stable,
+ * a major bump is a retrained model whose vectors can't be compared.
+ */
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+const MIN_DIMENSION: usize = 64;
+const MAX_DIMENSION: usize = 4096;
+
+/// Wire encoding of the vector
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmbeddingFormat {
+    /// Little-endian IEEE 754 single precision, base64 encoded
+    F32le,
+}
+
+/// The model an embedding (and the template built from it) came from
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmbeddingModel {
+    pub name: String,
+    pub version: String, // MAJOR.MINOR[.PATCH]
+    pub dimension: usize,
+}
+
+#[derive(Deserialize)]
+pub struct EmbeddingSubmission {
+    pub format: EmbeddingFormat,
+    pub model: String,
+    pub model_version: String,
+    pub vector: String,
+}
+
+/// A validated, L2-normalized embedding
+pub struct Embedding {
+    pub model: EmbeddingModel,
+    pub vector: Vec<f32>,
+}
+
+impl EmbeddingSubmission {
+    pub fn decode(&self) -> Result<Embedding, String> {
+        if self.model.trim().is_empty() {
+            return Err("Embedding model name is required".to_string());
+        }
+        major_version(&self.model_version)?;
+
+        let bytes = STANDARD
+            .decode(&self.vector)
+            .map_err(|_| "Embedding vector must be base64".to_string())?;
+        let vector = match self.format {
+            EmbeddingFormat::F32le => from_f32le(&bytes)?,
+        };
+        if !(MIN_DIMENSION..=MAX_DIMENSION).contains(&vector.len()) {
+            return Err(format!(
+                "Embedding dimension {} outside {}..={}",
+                vector.len(),
+                MIN_DIMENSION,
+                MAX_DIMENSION
+            ));
+        }
+        if vector.iter().any(|v| !v.is_finite()) {
+            return Err("Embedding contains non-finite values".to_string());
+        }
+
+        let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if norm == 0.0 || !norm.is_finite() {
+            return Err("Embedding has zero norm".to_string());
+        }
+
+        Ok(Embedding {
+            model: EmbeddingModel {
+                name: self.model.clone(),
+                version: self.model_version.clone(),
+                dimension: vector.len(),
+            },
+            vector: vector.iter().map(|v| v / norm).collect(),
+        })
+    }
+}
+
+impl EmbeddingModel {
+    /// Whether a probe from `other` can be matched against a template from `self`
+    pub fn check_compatible(&self, other: &EmbeddingModel) -> Result<(), String> {
+        if self.name != other.name {
+            return Err(format!(
+                "Template was enrolled with model {}, not {}",
+                self.name, other.name
+            ));
+        }
+        if self.dimension != other.dimension {
+            return Err(format!(
+                "Template has dimension {}, embedding has {}",
+                self.dimension, other.dimension
+            ));
+        }
+        if major_version(&self.version)? != major_version(&other.version)? {
+            return Err(format!(
+                "Model version {} is not compatible with enrolled version {}",
+                other.version, self.version
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Cosine similarity of two normalized vectors, clamped to [0, 1]
+pub fn similarity(a: &[f32], b: &[f32]) -> f64 {
+    let dot: f64 = a
+        .iter()
+        .zip(b)
+        .map(|(x, y)| f64::from(*x) * f64::from(*y))
+        .sum();
+    dot.clamp(0.0, 1.0)
+}
+
+pub fn to_f32le(vector: &[f32]) -> Vec<u8> {
+    vector.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+pub fn from_f32le(bytes: &[u8]) -> Result<Vec<f32>, String> {
+    if !bytes.len().is_multiple_of(4) {
+        return Err("Embedding length is not a multiple of 4 bytes".to_string());
+    }
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+        .collect())
+}
+
+fn major_version(version: &str) -> Result<u32, String> {
+    let mut parts = version.split('.');
+    let major = parts.next().and_then(|p| p.parse().ok());
+    let rest_ok = parts.all(|p| p.parse::<u32>().is_ok());
+    match major {
+        Some(major) if rest_ok => Ok(major),
+        _ => Err(format!("Invalid model version: {}", version)),
+    }
+}
@@ -0,0 +1,364 @@
+/**
+ * Right to Erasure
+ * Deletion in steps, with a grace period and a record of what was kept
+ *
+ * An owner requests erasure; the vault's guardians (and the owner's own
+ * contacts) are told, and nothing happens until the grace period has
+ * passed, during which any owner can cancel. Once it has, an owner
+ * executes the erasure:
+ *
+ *   1. the vault's audit chain is pseudonymized and kept, since
+ *      governance records may have to outlive the vault
+ *   2. the vault is deleted as by DELETE /vault/:vault_id (see deletion):
+ *      templates, stores, in-memory secrets and derived keys
+ *   3. the erasure record lists the deletion's artifacts and every
+ *      record kept, with a digest of each; the route attests it
+ *
+ * Pseudonyms replace every address in the kept entries, actors and
+ * detail alike, with an HMAC under a key drawn for this erasure and then
+ * dropped. The same address keeps the same pseudonym within the vault's
+ * history, so the record still reads, but no one, the enclave included,
+ * can map it back. Entry hashes and signatures are dropped too, since
+ * they commit to the addresses.
+ */
+
+use rand::rngs::OsRng;
+use rand::RngCore;
+use ring::hmac;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::audit::{AuditEntry, AuditTrail};
+use crate::deletion::{DeletionService, VaultDeletion};
+use crate::notifications::{Notification, NotificationService};
+use crate::store::Store;
+
+pub const OPERATION: &str = "vault_erasure";
+const REQUESTS_NAMESPACE: &str = "erasure_requests";
+const RECORDS_NAMESPACE: &str = "vault_erasures";
+const RETAINED_NAMESPACE: &str = "retained_audit";
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErasureStatus {
+    Pending,
+    Cancelled,
+    Completed,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ErasureRequest {
+    pub vault_id: String,
+    pub requested_by: String,
+    pub requested_at: u64,
+    /// End of the grace period
+    pub execute_after: u64,
+    pub status: ErasureStatus,
+    /// Notification jobs queued for the vault's guardians
+    pub guardians_notified: usize,
+    pub updated_at: u64,
+}
+
+/// An audit entry as kept after erasure
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RetainedAuditEntry {
+    pub vault_id: String,
+    pub seq: u64,
+    pub action: String,
+    pub actor: String, // Pseudonym
+    pub detail: Value,
+    pub at: u64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RetainedRecord {
+    pub namespace: String,
+    pub count: usize,
+    pub sha256: String, // Hex, over the records' JSON
+    pub reason: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct VaultErasure {
+    pub vault_id: String,
+    pub requested_by: String,
+    pub requested_at: u64,
+    pub executed_by: String,
+    pub deletion: VaultDeletion,
+    pub retained: Vec<RetainedRecord>,
+    pub executed_at: u64,
+}
+
+impl VaultErasure {
+    /// Digest bound into the erasure attestation
+    pub fn digest(&self) -> Result<Vec<u8>, String> {
+        let bytes = serde_json::to_vec(self)
+            .map_err(|e| format!("Failed to serialize vault erasure: {}", e))?;
+        Ok(Sha256::digest(bytes).to_vec())
+    }
+}
+
+pub struct ErasureService {
+    store: Arc<Store>,
+    deletions: Arc<DeletionService>,
+    audit: Arc<AuditTrail>,
+    notifications: Arc<NotificationService>,
+    grace: Duration,
+    // Held across each step, so a request can't be cancelled mid-execution
+    lock: Mutex<()>,
+}
+
+impl ErasureService {
+    pub fn new(
+        store: Arc<Store>,
+        deletions: Arc<DeletionService>,
+        audit: Arc<AuditTrail>,
+        notifications: Arc<NotificationService>,
+        grace: Duration,
+    ) -> Self {
+        Self {
+            store,
+            deletions,
+            audit,
+            notifications,
+            grace,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Start the grace period; the caller has been authorized
+    pub fn request(&self, vault_id: &str, caller: &str) -> Result<ErasureRequest, String> {
+        let _guard = self.lock.lock().unwrap();
+        if self
+            .request_of(vault_id)?
+            .is_some_and(|r| r.status == ErasureStatus::Pending)
+        {
+            return Err("Erasure has already been requested".to_string());
+        }
+
+        let now = now();
+        let execute_after = now + self.grace.as_secs();
+        let notice = Notification {
+            kind: "erasure.requested".to_string(),
+            title: "Vault erasure requested".to_string(),
+            body: format!(
+                "The owner of vault {} asked for it to be erased. It can be erased from {} \
+                 (unix time) unless an owner cancels first.",
+                vault_id, execute_after
+            ),
+        };
+        let guardians_notified = self.notifications.notify_guardians(vault_id, &notice)?;
+        self.notifications.notify(vault_id, &notice)?;
+        self.audit.record(
+            vault_id,
+            "erasure.requested",
+            caller,
+            json!({ "execute_after": execute_after }),
+        )?;
+
+        let request = ErasureRequest {
+            vault_id: vault_id.to_string(),
+            requested_by: caller.to_string(),
+            requested_at: now,
+            execute_after,
+            status: ErasureStatus::Pending,
+            guardians_notified,
+            updated_at: now,
+        };
+        self.store.put(REQUESTS_NAMESPACE, vault_id, &request)?;
+        Ok(request)
+    }
+
+    pub fn cancel(&self, vault_id: &str, caller: &str) -> Result<ErasureRequest, String> {
+        let _guard = self.lock.lock().unwrap();
+        let mut request = self.pending(vault_id)?;
+        request.status = ErasureStatus::Cancelled;
+        request.updated_at = now();
+        self.notifications.notify_guardians(
+            vault_id,
+            &Notification {
+                kind: "erasure.cancelled".to_string(),
+                title: "Vault erasure cancelled".to_string(),
+                body: format!("Erasure of vault {} was cancelled.", vault_id),
+            },
+        )?;
+        self.audit
+            .record(vault_id, "erasure.cancelled", caller, json!({}))?;
+        self.store.put(REQUESTS_NAMESPACE, vault_id, &request)?;
+        Ok(request)
+    }
+
+    /// Pseudonymize and keep the audit chain, then delete the vault; the
+    /// caller has been authorized
+    pub fn execute(&self, vault_id: &str, caller: &str) -> Result<VaultErasure, String> {
+        let _guard = self.lock.lock().unwrap();
+        let mut request = self.pending(vault_id)?;
+        let now = now();
+        if now < request.execute_after {
+            return Err(format!("Grace period runs until {}", request.execute_after));
+        }
+
+        self.audit.record(
+            vault_id,
+            "erasure.executed",
+            caller,
+            json!({ "requested_at": request.requested_at }),
+        )?;
+        let pseudonyms = Pseudonyms::new();
+        let kept: Vec<RetainedAuditEntry> = self
+            .audit
+            .entries(vault_id)?
+            .into_iter()
+            .map(|entry| pseudonyms.entry(entry))
+            .collect();
+        let kept_json = serde_json::to_vec(&kept)
+            .map_err(|e| format!("Failed to serialize retained audit: {}", e))?;
+        let values = kept
+            .iter()
+            .map(|entry| {
+                serde_json::to_value(entry)
+                    .map(|value| (format!("{}:{:020}", entry.vault_id, entry.seq), value))
+                    .map_err(|e| format!("Failed to serialize retained audit: {}", e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        self.store
+            .update(RETAINED_NAMESPACE, |ns| ns.extend(values))?;
+
+        let deletion = self.deletions.delete(vault_id, caller)?;
+        let deletion_json = serde_json::to_vec(&deletion)
+            .map_err(|e| format!("Failed to serialize vault deletion: {}", e))?;
+        let erasure = VaultErasure {
+            vault_id: vault_id.to_string(),
+            requested_by: request.requested_by.clone(),
+            requested_at: request.requested_at,
+            executed_by: caller.to_string(),
+            retained: vec![
+                RetainedRecord {
+                    namespace: RETAINED_NAMESPACE.to_string(),
+                    count: kept.len(),
+                    sha256: hex::encode(Sha256::digest(kept_json)),
+                    reason: "Governance audit trail, pseudonymized".to_string(),
+                },
+                RetainedRecord {
+                    namespace: "vault_deletions".to_string(),
+                    count: 1,
+                    sha256: hex::encode(Sha256::digest(deletion_json)),
+                    reason: "Evidence of deletion; retires the vault id".to_string(),
+                },
+            ],
+            deletion,
+            executed_at: now,
+        };
+        self.store.put(RECORDS_NAMESPACE, vault_id, &erasure)?;
+        request.status = ErasureStatus::Completed;
+        request.updated_at = now;
+        self.store.put(REQUESTS_NAMESPACE, vault_id, &request)?;
+        Ok(erasure)
+    }
+
+    pub fn request_of(&self, vault_id: &str) -> Result<Option<ErasureRequest>, String> {
+        self.store.get(REQUESTS_NAMESPACE, vault_id)
+    }
+
+    pub fn erasure(&self, vault_id: &str) -> Result<Option<VaultErasure>, String> {
+        self.store.get(RECORDS_NAMESPACE, vault_id)
+    }
+
+    fn pending(&self, vault_id: &str) -> Result<ErasureRequest, String> {
+        self.request_of(vault_id)?
+            .filter(|r| r.status == ErasureStatus::Pending)
+            .ok_or_else(|| "No erasure is pending for this vault".to_string())
+    }
+}
+
+/// Consistent pseudonyms under a key that only lives as long as this
+struct Pseudonyms {
+    key: hmac::Key,
+}
+
+impl Pseudonyms {
+    fn new() -> Self {
+        let mut key = [0u8; 32];
+        OsRng.fill_bytes(&mut key);
+        Self {
+            key: hmac::Key::new(hmac::HMAC_SHA256, &key),
+        }
+    }
+
+    fn entry(&self, entry: AuditEntry) -> RetainedAuditEntry {
+        RetainedAuditEntry {
+            actor: self.address(&entry.actor),
+            detail: self.value(entry.detail),
+            vault_id: entry.vault_id,
+            seq: entry.seq,
+            action: entry.action,
+            at: entry.at,
+        }
+    }
+
+    fn value(&self, value: Value) -> Value {
+        match value {
+            Value::String(s) if is_address(&s) => Value::String(self.address(&s)),
+            Value::Array(items) => Value::Array(items.into_iter().map(|v| self.value(v)).collect()),
+            Value::Object(fields) => Value::Object(
+                fields
+                    .into_iter()
+                    .map(|(name, v)| (name, self.value(v)))
+                    .collect(),
+            ),
+            other => other,
+        }
+    }
+
+    fn address(&self, address: &str) -> String {
+        let tag = hmac::sign(&self.key, address.as_bytes());
+        format!("anon:{}", hex::encode(&tag.as_ref()[..16]))
+    }
+}
+
+/// Normalized Sui addresses (see auth::normalize_address)
+fn is_address(s: &str) -> bool {
+    s.len() == 66
+        && s.starts_with("0x")
+        && s[2..]
+            .bytes()
+            .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn addresses_get_stable_pseudonyms() {
+        let alice = format!("0x{}", "a".repeat(64));
+        let bob = format!("0x{}", "b".repeat(64));
+        let pseudonyms = Pseudonyms::new();
+        let detail = pseudonyms.value(json!({
+            "guardians": [alice, bob],
+            "owner": alice,
+            "threshold": 2,
+            "note": "0xabc",
+        }));
+
+        assert_eq!(detail["guardians"][0], detail["owner"]);
+        assert_ne!(detail["guardians"][0], detail["guardians"][1]);
+        assert!(detail["owner"].as_str().unwrap().starts_with("anon:"));
+        assert_eq!(detail["threshold"], 2);
+        assert_eq!(detail["note"], "0xabc");
+        assert_ne!(
+            Pseudonyms::new().address(&alice),
+            pseudonyms.address(&alice)
+        );
+    }
+}
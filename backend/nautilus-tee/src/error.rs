@@ -0,0 +1,156 @@
+/**
+ * API Errors
+ * Structured error responses with stable machine-readable codes
+ *
+ * Services report typed errors (see attestation, biometric, escrow,
+ * liveness, zk_proof) that map here onto a status and code. Every
+ * response says whether the same request may succeed if sent again later:
+ * by default only when the service was unavailable, overloaded or timed
+ * out.
+ */
+
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Json, Response},
+};
+use serde::Serialize;
+
+use crate::attestation::AttestationError;
+use crate::biometric::BiometricError;
+use crate::escrow::EscrowError;
+use crate::liveness::LivenessError;
+use crate::zk_proof::ZKProofError;
+
+#[derive(Clone, Debug)]
+pub struct AppError {
+    pub status: StatusCode,
+    pub code: &'static str,
+    pub message: String,
+    /// Structured context for the client, e.g. quality metrics
+    pub details: Option<serde_json::Value>,
+    pub retryable: bool,
+}
+
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    code: &'a str,
+    message: &'a str,
+    retryable: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<&'a serde_json::Value>,
+}
+
+impl AppError {
+    pub fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            code,
+            message: message.into(),
+            details: None,
+            retryable: matches!(
+                status,
+                StatusCode::TOO_MANY_REQUESTS
+                    | StatusCode::SERVICE_UNAVAILABLE
+                    | StatusCode::GATEWAY_TIMEOUT
+            ),
+        }
+    }
+
+    pub fn with_details(mut self, details: impl Serialize) -> Self {
+        self.details = serde_json::to_value(details).ok();
+        self
+    }
+
+    pub fn bad_request(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, code, message)
+    }
+
+    pub fn unauthorized(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(StatusCode::UNAUTHORIZED, code, message)
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, "INTERNAL_ERROR", message)
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let body = ErrorBody {
+            code: self.code,
+            message: &self.message,
+            retryable: self.retryable,
+            details: self.details.as_ref(),
+        };
+        (self.status, Json(body)).into_response()
+    }
+}
+
+impl From<AttestationError> for AppError {
+    fn from(e: AttestationError) -> Self {
+        let (status, code) = match e {
+            AttestationError::Internal(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, "ATTESTATION_FAILED")
+            }
+            AttestationError::Stale(_) => (StatusCode::BAD_REQUEST, "ATTESTATION_EXPIRED"),
+            AttestationError::Malformed(_)
+            | AttestationError::Untrusted(_)
+            | AttestationError::BadSignature
+            | AttestationError::DigestMismatch => (StatusCode::BAD_REQUEST, "ATTESTATION_INVALID"),
+        };
+        Self::new(status, code, e.to_string())
+    }
+}
+
+impl From<BiometricError> for AppError {
+    fn from(e: BiometricError) -> Self {
+        let (status, code) = match e {
+            BiometricError::EmptySample | BiometricError::Unreadable(_) => {
+                (StatusCode::BAD_REQUEST, "INVALID_BIOMETRIC_DATA")
+            }
+            BiometricError::CorruptTemplate(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, "TEMPLATE_CORRUPT")
+            }
+        };
+        Self::new(status, code, e.to_string())
+    }
+}
+
+impl From<EscrowError> for AppError {
+    fn from(e: EscrowError) -> Self {
+        let (status, code) = match e {
+            EscrowError::NotConfigured => (StatusCode::BAD_REQUEST, "ESCROW_NOT_CONFIGURED"),
+            EscrowError::NotAdmin => (StatusCode::FORBIDDEN, "NOT_ADMIN"),
+            EscrowError::UnknownExport(_) => (StatusCode::NOT_FOUND, "ESCROW_EXPORT_NOT_FOUND"),
+            EscrowError::Rejected(_) => (StatusCode::BAD_REQUEST, "ESCROW_EXPORT_FAILED"),
+            EscrowError::Internal(_) => (StatusCode::INTERNAL_SERVER_ERROR, "ESCROW_EXPORT_FAILED"),
+        };
+        Self::new(status, code, e.to_string())
+    }
+}
+
+impl From<LivenessError> for AppError {
+    fn from(e: LivenessError) -> Self {
+        // The check fails only when the enclave's own records can't be
+        // read, which passes
+        Self::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "LIVENESS_UNAVAILABLE",
+            e.to_string(),
+        )
+    }
+}
+
+impl From<ZKProofError> for AppError {
+    fn from(e: ZKProofError) -> Self {
+        let (status, code) = match e {
+            ZKProofError::InvalidPayload(_) => (StatusCode::BAD_REQUEST, "INVALID_ENCRYPTED_DATA"),
+            ZKProofError::InvalidClaim(_) => (StatusCode::UNPROCESSABLE_ENTITY, "INVALID_CLAIM"),
+            ZKProofError::NoProver { .. } => (StatusCode::BAD_REQUEST, "UNSUPPORTED_CIRCUIT"),
+            ZKProofError::Proving(_) | ZKProofError::SignalMismatch { .. } => {
+                (StatusCode::INTERNAL_SERVER_ERROR, "PROOF_GENERATION_FAILED")
+            }
+        };
+        Self::new(status, code, e.to_string())
+    }
+}
@@ -0,0 +1,459 @@
+/**
+ * Escalation Ladders
+ * What happens, stage by stage, once a vault owner misses a check-in
+ *
+ * A ladder is an ordered list of stages, e.g. notify the owner, notify the
+ * guardians, wait for guardian confirmation, unlock. Each stage lasts at
+ * least its duration and, if it names evidence, until that evidence is in;
+ * the policy engine decides when a stage is complete. The sweep starts a
+ * vault's ladder when its check-in deadline passes and moves it up one
+ * stage at a time, notifying whoever the new stage concerns. Reaching
+ * unlock also stages a "vault_unlocked" intent in the chain outbox, in the
+ * same batch as the ladder's progress.
+ *
+ * Progress belongs to one check-in cycle. Checking in starts a new cycle,
+ * so whatever stage the old one had reached, unlock included, no longer
+ * counts.
+ */
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::info;
+
+use crate::approvals::ApprovalService;
+use crate::auth::normalize_address;
+use crate::bus::{BusEvent, EventBus};
+use crate::check_in::CheckInService;
+use crate::notifications::{Notification, NotificationService};
+use crate::outbox::ChainOutbox;
+use crate::policy::PolicyEngine;
+use crate::store::{Batch, Store};
+use crate::tasks::{Schedule, TaskScheduler};
+
+/// Guardians confirm an escalation by approving this operation
+pub const CONFIRMATION_OPERATION: &str = "escalation_confirmation";
+/// Outbox intent recording an unlock on chain, once per missed deadline
+pub const UNLOCK_INTENT: &str = "vault_unlocked";
+
+const NAMESPACE: &str = "escalation_ladders";
+const SECS_PER_HOUR: u64 = 60 * 60;
+const MAX_STAGES: usize = 8;
+const MAX_STAGE_HOURS: u32 = 24 * 365;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StageAction {
+    NotifyOwner,
+    NotifyGuardians,
+    GuardianConfirmation,
+    Unlock,
+}
+
+/// What must be in before a stage is complete, beyond its duration
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Evidence {
+    #[default]
+    None,
+    /// A guardian quorum approval of `CONFIRMATION_OPERATION` made after
+    /// the stage began
+    GuardianApproval,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Stage {
+    pub action: StageAction,
+    pub duration_hours: u32,
+    #[serde(default)]
+    pub evidence: Evidence,
+}
+
+impl Stage {
+    pub fn duration_secs(&self) -> u64 {
+        u64::from(self.duration_hours) * SECS_PER_HOUR
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Progress {
+    /// The missed check-in deadline that started the ladder
+    pub cycle: u64,
+    pub stage: usize,
+    pub entered_at: u64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct EscalationLadder {
+    pub vault_id: String,
+    pub owner: String,
+    pub stages: Vec<Stage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub progress: Option<Progress>,
+    pub updated_at: u64,
+}
+
+/// Where a vault stands on its ladder right now
+#[derive(Clone, Serialize)]
+pub struct EscalationStatus {
+    pub stages: Vec<Stage>,
+    /// Index into `stages`; absent while the owner is checked in
+    pub stage: Option<usize>,
+    pub action: Option<StageAction>,
+    pub entered_at: Option<u64>,
+    /// Earliest time the current stage can complete
+    pub next_at: Option<u64>,
+    /// Evidence the current stage still waits for
+    pub awaiting: Option<Evidence>,
+    pub unlocked: bool,
+}
+
+pub struct EscalationService {
+    store: Arc<Store>,
+    check_ins: Arc<CheckInService>,
+    approvals: Arc<ApprovalService>,
+    outbox: Arc<ChainOutbox>,
+    /// Held across read-modify-write so ladder changes and the sweep don't
+    /// overwrite each other
+    writes: Mutex<()>,
+}
+
+impl EscalationService {
+    pub fn new(
+        store: Arc<Store>,
+        check_ins: Arc<CheckInService>,
+        approvals: Arc<ApprovalService>,
+        outbox: Arc<ChainOutbox>,
+    ) -> Self {
+        Self {
+            store,
+            check_ins,
+            approvals,
+            outbox,
+            writes: Mutex::new(()),
+        }
+    }
+
+    /// Replace the vault's ladder; any escalation in progress starts over
+    /// from the first stage. The caller has been authorized.
+    pub fn set_ladder(
+        &self,
+        vault_id: &str,
+        owner: &str,
+        stages: Vec<Stage>,
+    ) -> Result<EscalationLadder, String> {
+        validate(&stages)?;
+        if self.check_ins.schedule(vault_id)?.is_none() {
+            return Err(
+                "Vault has no check-in schedule; its deadline is what starts the ladder"
+                    .to_string(),
+            );
+        }
+
+        let _writes = self.writes.lock().unwrap();
+        let ladder = EscalationLadder {
+            vault_id: vault_id.to_string(),
+            owner: normalize_address(owner),
+            stages,
+            progress: None,
+            updated_at: now(),
+        };
+        self.store.put(NAMESPACE, vault_id, &ladder)?;
+        Ok(ladder)
+    }
+
+    pub fn ladder(&self, vault_id: &str) -> Result<Option<EscalationLadder>, String> {
+        self.store.get(NAMESPACE, vault_id)
+    }
+
+    /// None when the vault has no ladder
+    pub fn status(&self, vault_id: &str) -> Result<Option<EscalationStatus>, String> {
+        let Some(ladder) = self.ladder(vault_id)? else {
+            return Ok(None);
+        };
+        let progress = match self.check_ins.schedule(vault_id)? {
+            Some(schedule) => ladder.progress.filter(|p| p.cycle == schedule.expires_at()),
+            None => None,
+        };
+        let Some(progress) = progress else {
+            return Ok(Some(EscalationStatus {
+                stages: ladder.stages,
+                stage: None,
+                action: None,
+                entered_at: None,
+                next_at: None,
+                awaiting: None,
+                unlocked: false,
+            }));
+        };
+
+        let stage = &ladder.stages[progress.stage];
+        let unlocked = stage.action == StageAction::Unlock;
+        let awaiting = if unlocked || self.evidence_in(vault_id, stage, progress.entered_at)? {
+            None
+        } else {
+            Some(stage.evidence)
+        };
+        Ok(Some(EscalationStatus {
+            stage: Some(progress.stage),
+            action: Some(stage.action),
+            entered_at: Some(progress.entered_at),
+            next_at: (!unlocked).then(|| progress.entered_at + stage.duration_secs()),
+            awaiting,
+            unlocked,
+            stages: ladder.stages,
+        }))
+    }
+
+    /// Whether the vault's ladder, if it has one, has reached unlock
+    pub fn unlocked(&self, vault_id: &str) -> Result<Option<bool>, String> {
+        Ok(self.status(vault_id)?.map(|s| s.unlocked))
+    }
+
+    /// Advance ladders on the task scheduler
+    pub fn schedule_sweep(
+        self: Arc<Self>,
+        tasks: &TaskScheduler,
+        policy: Arc<PolicyEngine>,
+        bus: Arc<EventBus>,
+        notifications: Arc<NotificationService>,
+        every: Duration,
+    ) -> Result<(), String> {
+        tasks.register(
+            "escalation.sweep",
+            &Schedule::every(every),
+            every / 10,
+            move || {
+                let (service, policy, bus, notifications) = (
+                    self.clone(),
+                    policy.clone(),
+                    bus.clone(),
+                    notifications.clone(),
+                );
+                async move {
+                    let entered = service.sweep(&policy, &bus, &notifications)?;
+                    if entered > 0 {
+                        info!("Escalation sweep entered {} stages", entered);
+                    }
+                    Ok(())
+                }
+            },
+        )
+    }
+
+    fn sweep(
+        &self,
+        policy: &PolicyEngine,
+        bus: &EventBus,
+        notifications: &NotificationService,
+    ) -> Result<usize, String> {
+        let _writes = self.writes.lock().unwrap();
+        let now = now();
+        let mut entered = 0;
+        for (_, mut ladder) in self.store.list::<EscalationLadder>(NAMESPACE)? {
+            let Some(schedule) = self.check_ins.schedule(&ladder.vault_id)? else {
+                continue;
+            };
+            let deadline = schedule.expires_at();
+            if now < deadline {
+                // Checked in since the ladder last moved
+                if ladder.progress.take().is_some() {
+                    self.store.put(NAMESPACE, &ladder.vault_id, &ladder)?;
+                }
+                continue;
+            }
+
+            let mut progress = match ladder.progress.take() {
+                Some(p) if p.cycle == deadline => p,
+                _ => {
+                    let start = Progress {
+                        cycle: deadline,
+                        stage: 0,
+                        entered_at: deadline,
+                    };
+                    enter(&ladder, &start, bus, notifications)?;
+                    entered += 1;
+                    start
+                }
+            };
+            loop {
+                let stage = &ladder.stages[progress.stage];
+                if stage.action == StageAction::Unlock {
+                    break;
+                }
+                let evidence = self.evidence_in(&ladder.vault_id, stage, progress.entered_at)?;
+                let decision =
+                    policy.evaluate_escalation_stage(stage, progress.entered_at, now, evidence);
+                if !decision.allowed {
+                    break;
+                }
+                // A stage that only waited out its duration ends on time even
+                // if the sweep is late, so a long outage doesn't stretch the
+                // ladder; one that waited on evidence ends now
+                progress.entered_at = match stage.evidence {
+                    Evidence::None => progress.entered_at + stage.duration_secs(),
+                    Evidence::GuardianApproval => now,
+                };
+                progress.stage += 1;
+                enter(&ladder, &progress, bus, notifications)?;
+                entered += 1;
+            }
+            let mut batch = Batch::new();
+            if ladder.stages[progress.stage].action == StageAction::Unlock {
+                self.outbox.stage(
+                    &mut batch,
+                    UNLOCK_INTENT,
+                    &ladder.vault_id,
+                    &progress.cycle.to_string(),
+                    json!({
+                        "missed_deadline": progress.cycle,
+                        "unlocked_at": progress.entered_at,
+                    }),
+                )?;
+            }
+            ladder.progress = Some(progress);
+            batch.put(NAMESPACE, &ladder.vault_id, &ladder)?;
+            self.store.commit(batch)?;
+        }
+        Ok(entered)
+    }
+
+    fn evidence_in(&self, vault_id: &str, stage: &Stage, since: u64) -> Result<bool, String> {
+        Ok(match stage.evidence {
+            Evidence::None => true,
+            Evidence::GuardianApproval => self
+                .approvals
+                .current_approval(vault_id, CONFIRMATION_OPERATION)?
+                .is_some_and(|a| a.verified_at >= since),
+        })
+    }
+}
+
+fn validate(stages: &[Stage]) -> Result<(), String> {
+    if stages.is_empty() || stages.len() > MAX_STAGES {
+        return Err(format!("A ladder has 1 to {} stages", MAX_STAGES));
+    }
+    let last = stages.len() - 1;
+    for (i, stage) in stages.iter().enumerate() {
+        if stage.duration_hours > MAX_STAGE_HOURS {
+            return Err(format!(
+                "Stage {} lasts longer than {} hours",
+                i, MAX_STAGE_HOURS
+            ));
+        }
+        match stage.action {
+            StageAction::Unlock if i != last => {
+                return Err("Unlock can only be the last stage".to_string())
+            }
+            StageAction::Unlock
+                if stage.duration_hours != 0 || stage.evidence != Evidence::None =>
+            {
+                return Err("Unlock is final, so it takes no duration or evidence".to_string())
+            }
+            StageAction::GuardianConfirmation if stage.evidence != Evidence::GuardianApproval => {
+                return Err(format!(
+                    "Stage {} waits for guardians, so its evidence must be guardian_approval",
+                    i
+                ))
+            }
+            _ => {}
+        }
+    }
+    if stages[last].action != StageAction::Unlock {
+        return Err("A ladder must end with unlock".to_string());
+    }
+    Ok(())
+}
+
+/// Announce a stage to whoever it concerns
+fn enter(
+    ladder: &EscalationLadder,
+    progress: &Progress,
+    bus: &EventBus,
+    notifications: &NotificationService,
+) -> Result<(), String> {
+    let vault_id = &ladder.vault_id;
+    let stage = &ladder.stages[progress.stage];
+    bus.publish(BusEvent::EscalationStage {
+        vault_id: vault_id.clone(),
+        stage: progress.stage,
+        action: stage.action,
+        entered_at: progress.entered_at,
+        missed_deadline: progress.cycle,
+    })?;
+    info!(
+        "Escalation for {} entered stage {} ({:?})",
+        vault_id, progress.stage, stage.action
+    );
+
+    let notice = |kind: &str, title: &str, body: String| Notification {
+        kind: kind.to_string(),
+        title: title.to_string(),
+        body,
+    };
+    match stage.action {
+        StageAction::NotifyOwner => {
+            notifications.notify(
+                vault_id,
+                &notice(
+                    "escalation.owner",
+                    "Check-in missed",
+                    format!(
+                        "You missed the check-in deadline for vault {}. Check in now to stop \
+                         it escalating to your guardians.",
+                        vault_id
+                    ),
+                ),
+            )?;
+        }
+        StageAction::NotifyGuardians => {
+            notifications.notify_guardians(
+                vault_id,
+                &notice(
+                    "escalation.guardians",
+                    "Vault owner hasn't checked in",
+                    format!(
+                        "The owner of vault {} missed their check-in deadline and hasn't \
+                         responded since.",
+                        vault_id
+                    ),
+                ),
+            )?;
+        }
+        StageAction::GuardianConfirmation => {
+            notifications.notify_guardians(
+                vault_id,
+                &notice(
+                    "escalation.confirmation",
+                    "Guardian confirmation needed",
+                    format!(
+                        "Vault {} will only move towards unlock once its guardians approve \
+                         {}.",
+                        vault_id, CONFIRMATION_OPERATION
+                    ),
+                ),
+            )?;
+        }
+        StageAction::Unlock => {
+            let unlocked = notice(
+                "escalation.unlocked",
+                "Vault unlocked",
+                format!(
+                    "Vault {} has been unlocked for release to its beneficiaries.",
+                    vault_id
+                ),
+            );
+            notifications.notify(vault_id, &unlocked)?;
+            notifications.notify_guardians(vault_id, &unlocked)?;
+        }
+    }
+    Ok(())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
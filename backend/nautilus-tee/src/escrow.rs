@@ -0,0 +1,441 @@
+/**
+ * Key Escrow
+ * Break-glass export of enclave secrets for disaster recovery
+ *
+ * Recovery keys are fixed in configuration at deploy time. An export is
+ * opened by one admin and runs only once a quorum of distinct admins has
+ * signed off. The secrets are encrypted under a fresh escrow key, which
+ * is Shamir-split m-of-n and sealed to the recovery keys, so no single
+ * recovery key holder can open the export. The persisted record states
+ * exactly what was exported, to which keys and on whose authority.
+ */
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tracing::info;
+use zeroize::Zeroizing;
+
+use crate::config::{EscrowConfig, SecretsConfig};
+use crate::crypto::{self, Envelope};
+use crate::policy::PolicyEngine;
+use crate::secrets::split_secret;
+use crate::store::Store;
+
+const NAMESPACE: &str = "escrow_exports";
+/// Pending exports lapse if the quorum is not reached within a day
+const PENDING_TTL_SECS: u64 = 24 * 60 * 60;
+
+#[derive(Debug, Error)]
+pub enum EscrowError {
+    #[error("Key escrow is not configured")]
+    NotConfigured,
+    #[error("Caller is not an enclave admin")]
+    NotAdmin,
+    #[error("Unknown escrow export {0}")]
+    UnknownExport(String),
+    /// Missing reason, expired or already performed export, repeat approval
+    #[error("{0}")]
+    Rejected(&'static str),
+    /// The export couldn't be sealed or persisted
+    #[error("Escrow export failed: {0}")]
+    Internal(String),
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportStatus {
+    Pending,
+    Exported,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct EscrowRecipient {
+    pub index: u8,
+    pub public_key: String,  // Base64 X25519 recovery key
+    pub fingerprint: String, // Hex sha256 of the key bytes
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RecipientShare {
+    pub index: u8,
+    pub fingerprint: String,
+    pub envelope: Envelope,
+}
+
+/// Encrypted secrets plus the escrow key shares sealed to recovery keys
+#[derive(Clone, Serialize, Deserialize)]
+pub struct EscrowBundle {
+    pub nonce: String,      // Base64 12-byte nonce
+    pub ciphertext: String, // Base64 secrets payload + tag
+    pub shares: Vec<RecipientShare>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct EscrowExport {
+    pub export_id: String,
+    pub reason: String,
+    pub requested_by: String,
+    pub approvals: Vec<String>,
+    pub status: ExportStatus,
+    pub requested_at: u64,
+    pub expires_at: u64,
+    pub threshold: u8,
+    pub recipients: Vec<EscrowRecipient>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exported_at: Option<u64>,
+    /// Names of the secrets included in the export
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub items: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payload_commitment: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bundle: Option<EscrowBundle>,
+}
+
+impl EscrowExport {
+    /// Digest bound into the export attestation: what, to whom, approved by whom
+    pub fn digest(&self) -> Vec<u8> {
+        let mut hasher = Sha256::new();
+        hasher.update(b"lumina-escrow-export-v1");
+        hasher.update(self.export_id.as_bytes());
+        for item in self.items.iter().flatten() {
+            hasher.update(item.as_bytes());
+            hasher.update([0]);
+        }
+        hasher.update(self.payload_commitment.as_deref().unwrap_or("").as_bytes());
+        hasher.update([self.threshold]);
+        for recipient in &self.recipients {
+            hasher.update(recipient.fingerprint.as_bytes());
+        }
+        for admin in &self.approvals {
+            hasher.update(admin.as_bytes());
+        }
+        hasher.finalize().to_vec()
+    }
+}
+
+#[derive(Serialize)]
+struct EscrowPayload<'a> {
+    export_id: &'a str,
+    secrets: &'a SecretsConfig,
+}
+
+pub struct EscrowService {
+    store: Arc<Store>,
+    config: Option<EscrowConfig>,
+    admins: Vec<String>,
+    secrets: SecretsConfig,
+    // Serializes read-modify-write of pending exports
+    lock: Mutex<()>,
+}
+
+impl EscrowService {
+    pub fn new(
+        store: Arc<Store>,
+        config: Option<EscrowConfig>,
+        admins: Vec<String>,
+        secrets: SecretsConfig,
+    ) -> Self {
+        Self {
+            store,
+            config,
+            admins,
+            secrets,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Open an export; the requesting admin counts as the first approval
+    pub fn request(&self, admin: &str, reason: &str) -> Result<EscrowExport, EscrowError> {
+        let config = self.config()?;
+        self.require_admin(admin)?;
+        if reason.trim().is_empty() {
+            return Err(EscrowError::Rejected("An export reason is required"));
+        }
+
+        let mut id = [0u8; 16];
+        OsRng.fill_bytes(&mut id);
+        let now = now();
+
+        let export = EscrowExport {
+            export_id: hex::encode(id),
+            reason: reason.to_string(),
+            requested_by: admin.to_string(),
+            approvals: vec![admin.to_string()],
+            status: ExportStatus::Pending,
+            requested_at: now,
+            expires_at: now + PENDING_TTL_SECS,
+            threshold: config.threshold,
+            recipients: recipients(config).map_err(EscrowError::Internal)?,
+            exported_at: None,
+            items: None,
+            payload_commitment: None,
+            bundle: None,
+        };
+
+        let _guard = self.lock.lock().unwrap();
+        self.store
+            .put(NAMESPACE, &export.export_id, &export)
+            .map_err(EscrowError::Internal)?;
+        info!("Escrow export {} requested by {}", export.export_id, admin);
+        Ok(export)
+    }
+
+    /// Record an admin's approval and run the export once the policy allows it
+    pub fn approve(
+        &self,
+        export_id: &str,
+        admin: &str,
+        policy: &PolicyEngine,
+    ) -> Result<EscrowExport, EscrowError> {
+        let config = self.config()?;
+        self.require_admin(admin)?;
+
+        let _guard = self.lock.lock().unwrap();
+        let mut export: EscrowExport = self
+            .store
+            .get(NAMESPACE, export_id)
+            .map_err(EscrowError::Internal)?
+            .ok_or_else(|| EscrowError::UnknownExport(export_id.to_string()))?;
+
+        if export.status == ExportStatus::Exported {
+            return Err(EscrowError::Rejected("Export has already been performed"));
+        }
+        if export.expires_at <= now() {
+            return Err(EscrowError::Rejected("Export request has expired"));
+        }
+        if export.approvals.iter().any(|a| a == admin) {
+            return Err(EscrowError::Rejected(
+                "Admin has already approved this export",
+            ));
+        }
+        export.approvals.push(admin.to_string());
+
+        let decision = policy.evaluate_escrow_export(export.approvals.len(), config.admin_quorum);
+        info!("Escrow export {}: {}", export_id, decision.reason);
+        if decision.allowed {
+            self.perform(&mut export).map_err(EscrowError::Internal)?;
+        }
+
+        self.store
+            .put(NAMESPACE, export_id, &export)
+            .map_err(EscrowError::Internal)?;
+        Ok(export)
+    }
+
+    fn perform(&self, export: &mut EscrowExport) -> Result<(), String> {
+        let payload = Zeroizing::new(
+            serde_json::to_vec(&EscrowPayload {
+                export_id: &export.export_id,
+                secrets: &self.secrets,
+            })
+            .map_err(|e| format!("Failed to serialize escrow payload: {}", e))?,
+        );
+
+        let mut key = Zeroizing::new([0u8; 32]);
+        OsRng.fill_bytes(key.as_mut());
+        let (nonce, ciphertext) =
+            crypto::aead_encrypt(&key, &payload, payload_aad(&export.export_id).as_bytes())?;
+
+        let shares = split_secret(
+            key.as_ref(),
+            export.threshold,
+            export.recipients.len() as u8,
+        );
+        let mut sealed = Vec::with_capacity(shares.len());
+        for (recipient, share) in export.recipients.iter().zip(shares.iter()) {
+            let public_key = crypto::decode_public_key(&recipient.public_key)?;
+            sealed.push(RecipientShare {
+                index: recipient.index,
+                fingerprint: recipient.fingerprint.clone(),
+                envelope: crypto::seal(
+                    &public_key,
+                    share,
+                    share_aad(&export.export_id, recipient.index).as_bytes(),
+                )?,
+            });
+        }
+
+        let mut hasher = Sha256::new();
+        hasher.update(b"lumina-escrow-payload");
+        hasher.update(payload.as_slice());
+
+        export.status = ExportStatus::Exported;
+        export.exported_at = Some(now());
+        export.items = Some(
+            self.secrets
+                .loaded()
+                .into_iter()
+                .map(String::from)
+                .collect(),
+        );
+        export.payload_commitment = Some(hex::encode(hasher.finalize()));
+        export.bundle = Some(EscrowBundle {
+            nonce: STANDARD.encode(nonce),
+            ciphertext: STANDARD.encode(ciphertext),
+            shares: sealed,
+        });
+
+        info!(
+            "Escrow export {} sealed to {} recovery keys ({} required), approved by {:?}",
+            export.export_id,
+            export.recipients.len(),
+            export.threshold,
+            export.approvals
+        );
+        Ok(())
+    }
+
+    fn config(&self) -> Result<&EscrowConfig, EscrowError> {
+        self.config.as_ref().ok_or(EscrowError::NotConfigured)
+    }
+
+    fn require_admin(&self, address: &str) -> Result<(), EscrowError> {
+        if !self.admins.iter().any(|a| a == address) {
+            return Err(EscrowError::NotAdmin);
+        }
+        Ok(())
+    }
+}
+
+fn recipients(config: &EscrowConfig) -> Result<Vec<EscrowRecipient>, String> {
+    config
+        .recipients
+        .iter()
+        .enumerate()
+        .map(|(i, key)| {
+            let public_key = crypto::decode_public_key(key)?;
+            Ok(EscrowRecipient {
+                // Share x-coordinates start at 1
+                index: i as u8 + 1,
+                public_key: key.clone(),
+                fingerprint: hex::encode(Sha256::digest(public_key.as_bytes())),
+            })
+        })
+        .collect()
+}
+
+fn payload_aad(export_id: &str) -> String {
+    format!("lumina-escrow:{}", export_id)
+}
+
+fn share_aad(export_id: &str, index: u8) -> String {
+    format!("lumina-escrow-share:{}:{}", export_id, index)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use x25519_dalek::{PublicKey, StaticSecret};
+
+    const ADMINS: [&str; 3] = ["0xa", "0xb", "0xc"];
+
+    /// An escrow to three recovery keys, two of which reassemble an export
+    fn service(name: &str, quorum: usize) -> (EscrowService, Vec<StaticSecret>) {
+        let dir = std::env::temp_dir().join(format!("lumina-escrow-tests-{}-{}", name, now()));
+        let keys: Vec<StaticSecret> = (0..3)
+            .map(|_| StaticSecret::random_from_rng(OsRng))
+            .collect();
+        let recipients = keys
+            .iter()
+            .map(|key| STANDARD.encode(PublicKey::from(key).as_bytes()))
+            .collect();
+        let escrow = EscrowService::new(
+            Arc::new(Store::open(dir).unwrap()),
+            Some(EscrowConfig {
+                recipients,
+                threshold: 2,
+                admin_quorum: quorum,
+            }),
+            ADMINS.iter().map(|a| a.to_string()).collect(),
+            SecretsConfig {
+                root_key: Some(STANDARD.encode([7u8; 32])),
+                ..SecretsConfig::default()
+            },
+        );
+        (escrow, keys)
+    }
+
+    fn policy() -> PolicyEngine {
+        PolicyEngine::new(ADMINS.iter().map(|a| a.to_string()).collect(), Vec::new())
+    }
+
+    #[test]
+    fn exports_run_once_a_quorum_of_distinct_admins_approves() {
+        let (escrow, keys) = service("quorum", 3);
+        let export_id = escrow.request("0xa", "region lost").unwrap().export_id;
+
+        // The requester already counts; approving again adds nothing
+        assert!(matches!(
+            escrow.approve(&export_id, "0xa", &policy()),
+            Err(EscrowError::Rejected(_))
+        ));
+        let pending = escrow.approve(&export_id, "0xb", &policy()).unwrap();
+        assert!(pending.status == ExportStatus::Pending && pending.bundle.is_none());
+
+        let exported = escrow.approve(&export_id, "0xc", &policy()).unwrap();
+        assert!(exported.status == ExportStatus::Exported);
+        assert_eq!(exported.approvals, ADMINS);
+        assert_eq!(exported.items, Some(vec!["root_key".to_string()]));
+        let bundle = exported.bundle.unwrap();
+        assert_eq!(bundle.shares.len(), 3);
+        let aad = share_aad(&export_id, 1);
+        assert!(crypto::open(&keys[0], &bundle.shares[0].envelope, aad.as_bytes()).is_ok());
+        assert!(crypto::open(&keys[1], &bundle.shares[0].envelope, aad.as_bytes()).is_err());
+
+        assert!(matches!(
+            escrow.approve(&export_id, "0xb", &policy()),
+            Err(EscrowError::Rejected("Export has already been performed"))
+        ));
+    }
+
+    #[test]
+    fn only_admins_open_or_approve_configured_exports() {
+        let (escrow, _) = service("admins", 2);
+        assert!(matches!(
+            escrow.request("0xmallory", "region lost"),
+            Err(EscrowError::NotAdmin)
+        ));
+        assert!(matches!(
+            escrow.request("0xa", " "),
+            Err(EscrowError::Rejected(_))
+        ));
+
+        let export_id = escrow.request("0xa", "region lost").unwrap().export_id;
+        assert!(matches!(
+            escrow.approve(&export_id, "0xmallory", &policy()),
+            Err(EscrowError::NotAdmin)
+        ));
+        assert!(matches!(
+            escrow.approve("unknown", "0xb", &policy()),
+            Err(EscrowError::UnknownExport(_))
+        ));
+        let exported = escrow.approve(&export_id, "0xb", &policy()).unwrap();
+        assert!(exported.status == ExportStatus::Exported);
+
+        let unconfigured = EscrowService::new(
+            escrow.store.clone(),
+            None,
+            ADMINS.iter().map(|a| a.to_string()).collect(),
+            SecretsConfig::default(),
+        );
+        assert!(matches!(
+            unconfigured.request("0xa", "region lost"),
+            Err(EscrowError::NotConfigured)
+        ));
+    }
+}
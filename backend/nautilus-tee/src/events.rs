@@ -0,0 +1,159 @@
+/**
+ * Enclave Events
+ * Outbox of notable state changes for delivery to owner webhooks
+ *
+ * The enclave has no outbound network, so it doesn't call webhooks
+ * itself. Events are appended to a persistent outbox that the parent's
+ * backend drains by cursor and fans out to the webhooks owners have
+ * registered, or delivers to the endpoints owners registered with the
+ * enclave (see webhooks). Each event is signed with HMAC-SHA256 under the
+ * webhook signing secret from the secrets bundle, and with Ed25519 under
+ * the enclave identity, so receivers can check it came from the enclave
+ * and not from the relaying host: with the shared secret, or with only
+ * the attested public key. Deliveries the relay gives up on are reported
+ * back and dead-lettered.
+ *
+ * Subsystems don't emit here directly: they publish to the event bus,
+ * and the log's bus consumer appends each vault event in order.
+ */
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ring::hmac;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+use crate::keys::identity::EnclaveIdentity;
+use crate::store::Store;
+
+const NAMESPACE: &str = "events";
+const DOMAIN: &[u8] = b"lumina-webhook-v1:";
+/// Oldest events are dropped past this, whether or not they were drained
+const MAX_EVENTS: usize = 10_000;
+pub const MAX_BATCH: usize = 500;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Event {
+    /// Increasing; drain cursors refer to it
+    pub id: u64,
+    /// Dotted name, e.g. "template.expiring"
+    pub kind: String,
+    pub vault_id: String,
+    pub data: Value,
+    pub created_at: u64,
+}
+
+#[derive(Serialize)]
+pub struct SignedEvent {
+    #[serde(flatten)]
+    pub event: Event,
+    /// Hex HMAC-SHA256 over the event's JSON (fields in the order above);
+    /// absent when no webhook signing secret is configured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    /// The enclave identity that signed `identity_signature`
+    pub key_id: String,
+    /// Base64 Ed25519 over DOMAIN || the event's JSON
+    pub identity_signature: String,
+}
+
+pub struct EventLog {
+    store: Arc<Store>,
+    identity: Arc<EnclaveIdentity>,
+    key: Option<hmac::Key>,
+    next_id: Mutex<u64>,
+}
+
+impl EventLog {
+    pub fn new(
+        store: Arc<Store>,
+        identity: Arc<EnclaveIdentity>,
+        signing_secret: Option<&str>,
+    ) -> Result<Self, String> {
+        if signing_secret.is_none() {
+            warn!("No webhook signing secret; events will be delivered unsigned");
+        }
+        let last = store
+            .list::<Event>(NAMESPACE)?
+            .last()
+            .map(|(_, event)| event.id)
+            .unwrap_or(0);
+
+        Ok(Self {
+            store,
+            identity,
+            key: signing_secret.map(|s| hmac::Key::new(hmac::HMAC_SHA256, s.as_bytes())),
+            next_id: Mutex::new(last + 1),
+        })
+    }
+
+    pub fn emit(&self, kind: &str, vault_id: &str, data: Value) -> Result<Event, String> {
+        // Held across the write so ids reach the store in order
+        let mut next_id = self.next_id.lock().unwrap();
+        let event = Event {
+            id: *next_id,
+            kind: kind.to_string(),
+            vault_id: vault_id.to_string(),
+            data,
+            created_at: now(),
+        };
+        let value = serde_json::to_value(&event)
+            .map_err(|e| format!("Failed to serialize event: {}", e))?;
+        self.store.update(NAMESPACE, |ns| {
+            ns.insert(event_key(event.id), value);
+            while ns.len() > MAX_EVENTS {
+                ns.pop_first();
+            }
+        })?;
+        *next_id += 1;
+        Ok(event)
+    }
+
+    /// The event, unless it has been dropped
+    pub fn get(&self, id: u64) -> Result<Option<Event>, String> {
+        self.store.get(NAMESPACE, &event_key(id))
+    }
+
+    /// Up to `limit` events with ids after `cursor`, oldest first
+    pub fn after(&self, cursor: u64, limit: usize) -> Result<Vec<SignedEvent>, String> {
+        self.store
+            .list::<Event>(NAMESPACE)?
+            .into_iter()
+            .map(|(_, event)| event)
+            .filter(|event| event.id > cursor)
+            .take(limit)
+            .map(|event| self.sign(event))
+            .collect()
+    }
+
+    pub fn sign(&self, event: Event) -> Result<SignedEvent, String> {
+        let bytes =
+            serde_json::to_vec(&event).map_err(|e| format!("Failed to serialize event: {}", e))?;
+        let signature = self
+            .key
+            .as_ref()
+            .map(|key| hex::encode(hmac::sign(key, &bytes)));
+        let identity_signature = self.identity.sign(&[DOMAIN, &bytes].concat());
+        Ok(SignedEvent {
+            event,
+            signature,
+            key_id: self.identity.key_id(),
+            identity_signature: STANDARD.encode(identity_signature.to_bytes()),
+        })
+    }
+}
+
+/// Zero-padded so the store's key order is id order
+fn event_key(id: u64) -> String {
+    format!("{:020}", id)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
@@ -0,0 +1,84 @@
+/**
+ * Attested Exchanges
+ * Binding an attestation to the request and response it vouches for
+ *
+ * Attestations over a decision (biometric verification, liveness, proof
+ * generation) commit to SHA-256 of the canonical JSON of the request body
+ * and of the response payload, so a document can't be detached and shown
+ * next to a different result. Canonical JSON has object keys sorted and no
+ * insignificant whitespace; the response payload is the response body
+ * without its `attestation` field. A relying party recomputes both hashes
+ * from what was sent and received and compares them with the document's
+ * request_sha256 and response_sha256.
+ */
+
+use axum::{
+    async_trait,
+    extract::{FromRequest, Request},
+    http::StatusCode,
+    Json,
+};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::io::Write;
+
+use crate::error::AppError;
+
+/// SHA-256 of the canonical JSON encoding of `value`
+pub fn canonical_sha256<T: Serialize>(value: &T) -> Result<[u8; 32], String> {
+    // Value's maps are ordered by key, so re-encoding one sorts them
+    let value = serde_json::to_value(value)
+        .map_err(|e| format!("Failed to encode canonical JSON: {}", e))?;
+    value_sha256(&value)
+}
+
+/// SHA-256 of a Value's encoding, streamed into the hasher rather than
+/// buffered, since request bodies can carry large payloads
+fn value_sha256(value: &Value) -> Result<[u8; 32], String> {
+    let mut hasher = HashWriter(Sha256::new());
+    serde_json::to_writer(&mut hasher, value)
+        .map_err(|e| format!("Failed to encode canonical JSON: {}", e))?;
+    Ok(hasher.0.finalize().into())
+}
+
+struct HashWriter(Sha256);
+
+impl Write for HashWriter {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+        self.0.update(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A JSON body along with the canonical hash of it as the client sent it,
+/// before defaults were filled in
+pub struct HashedJson<T> {
+    pub value: T,
+    pub sha256: [u8; 32],
+}
+
+#[async_trait]
+impl<S, T> FromRequest<S> for HashedJson<T>
+where
+    S: Send + Sync,
+    T: DeserializeOwned,
+{
+    type Rejection = AppError;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(body) = Json::<Value>::from_request(request, state)
+            .await
+            .map_err(|e| AppError::new(e.status(), "INVALID_JSON", e.body_text()))?;
+        let sha256 = value_sha256(&body).map_err(AppError::internal)?;
+        let value = serde_json::from_value(body).map_err(|e| {
+            AppError::new(StatusCode::UNPROCESSABLE_ENTITY, "INVALID_REQUEST", e.to_string())
+        })?;
+        Ok(Self { value, sha256 })
+    }
+}
@@ -0,0 +1,189 @@
+/**
+ * Face Embedding Model
+ * ONNX face-recognition inference inside the enclave
+ *
+ * The model file ships in the enclave image (models/ in the build
+ * context), so it's covered by PCR0; its SHA-256 is also checked against
+ * the configured pin at startup and bound into every attestation, so a
+ * relying party can tell which model produced a match.
+ *
+ * Input is a cropped, aligned face: the image is resized to the model's
+ * 112x112 RGB input and normalized to [-1, 1], the usual convention for
+ * ArcFace-style models. Output embeddings are L2-normalized and compared
+ * by cosine similarity like client-side embeddings.
+ *
+ * Inference needs the `onnx` feature; without it a configured model is a
+ * startup error rather than a silent fallback to placeholder matching.
+ */
+
+use sha2::{Digest, Sha256};
+
+use crate::config::FaceModelConfig;
+use crate::embedding::{Embedding, EmbeddingModel};
+
+pub struct FaceModel {
+    /// Hex SHA-256 of the model file
+    hash: String,
+    model: EmbeddingModel,
+    #[cfg(feature = "onnx")]
+    runtime: runtime::FaceRuntime,
+}
+
+impl FaceModel {
+    pub fn load(config: &FaceModelConfig) -> Result<Self, String> {
+        let bytes = std::fs::read(&config.path)
+            .map_err(|e| format!("Failed to read face model {}: {}", config.path.display(), e))?;
+        let hash = hex::encode(Sha256::digest(&bytes));
+        if let Some(pinned) = &config.sha256 {
+            if !pinned.eq_ignore_ascii_case(&hash) {
+                return Err(format!(
+                    "Face model hash {} does not match pinned {}",
+                    hash, pinned
+                ));
+            }
+        }
+
+        Self::with_runtime(config, hash, &bytes)
+    }
+
+    #[cfg(feature = "onnx")]
+    fn with_runtime(config: &FaceModelConfig, hash: String, bytes: &[u8]) -> Result<Self, String> {
+        let runtime = runtime::FaceRuntime::new(bytes)?;
+        Ok(Self {
+            model: EmbeddingModel {
+                name: config.name.clone(),
+                version: config.version.clone(),
+                dimension: runtime.dimension(),
+            },
+            hash,
+            runtime,
+        })
+    }
+
+    #[cfg(not(feature = "onnx"))]
+    fn with_runtime(
+        config: &FaceModelConfig,
+        _hash: String,
+        _bytes: &[u8],
+    ) -> Result<Self, String> {
+        Err(format!(
+            "Face model {} {} is configured but this build lacks the onnx feature",
+            config.name, config.version
+        ))
+    }
+
+    #[cfg(feature = "onnx")]
+    fn infer(&self, image: &[u8]) -> Result<Vec<f32>, String> {
+        self.runtime.infer(image)
+    }
+
+    #[cfg(not(feature = "onnx"))]
+    fn infer(&self, _image: &[u8]) -> Result<Vec<f32>, String> {
+        Err("Face inference requires the onnx feature".to_string())
+    }
+
+    pub fn hash(&self) -> &str {
+        &self.hash
+    }
+
+    pub fn model(&self) -> &EmbeddingModel {
+        &self.model
+    }
+
+    /// Embedding of a face image (PNG or JPEG)
+    pub fn embed(&self, image: &[u8]) -> Result<Embedding, String> {
+        let raw = self.infer(image)?;
+        let norm = raw.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if norm == 0.0 || !norm.is_finite() {
+            return Err("Face model produced a degenerate embedding".to_string());
+        }
+        Ok(Embedding {
+            model: self.model.clone(),
+            vector: raw.iter().map(|v| v / norm).collect(),
+        })
+    }
+}
+
+#[cfg(feature = "onnx")]
+mod runtime {
+    use image::imageops::FilterType;
+    use ndarray::Array4;
+    use ort::session::{builder::GraphOptimizationLevel, Session};
+    use ort::value::Tensor;
+
+    const INPUT_SIDE: u32 = 112;
+
+    pub struct FaceRuntime {
+        session: Session,
+        input_name: String,
+        dimension: usize,
+    }
+
+    impl FaceRuntime {
+        pub fn new(model: &[u8]) -> Result<Self, String> {
+            let session = Session::builder()
+                .and_then(|b| b.with_optimization_level(GraphOptimizationLevel::Level3))
+                .and_then(|b| b.with_intra_threads(1))
+                .and_then(|b| b.commit_from_memory(model))
+                .map_err(|e| format!("Failed to load face model: {}", e))?;
+            let input_name = session
+                .inputs
+                .first()
+                .map(|input| input.name.clone())
+                .ok_or_else(|| "Face model has no inputs".to_string())?;
+
+            let mut runtime = Self {
+                session,
+                input_name,
+                dimension: 0,
+            };
+            // A warm-up pass on a blank face both validates the graph and
+            // tells us the embedding size, which may be dynamic in the model
+            runtime.dimension = runtime
+                .run(Array4::zeros((
+                    1,
+                    3,
+                    INPUT_SIDE as usize,
+                    INPUT_SIDE as usize,
+                )))?
+                .len();
+            Ok(runtime)
+        }
+
+        pub fn dimension(&self) -> usize {
+            self.dimension
+        }
+
+        pub fn infer(&self, image: &[u8]) -> Result<Vec<f32>, String> {
+            let rgb = image::load_from_memory(image)
+                .map_err(|e| format!("Failed to decode face image: {}", e))?
+                .resize_exact(INPUT_SIDE, INPUT_SIDE, FilterType::Triangle)
+                .to_rgb8();
+
+            let side = INPUT_SIDE as usize;
+            let mut input = Array4::<f32>::zeros((1, 3, side, side));
+            for (x, y, pixel) in rgb.enumerate_pixels() {
+                for channel in 0..3 {
+                    input[[0, channel, y as usize, x as usize]] =
+                        (f32::from(pixel[channel]) - 127.5) / 127.5;
+                }
+            }
+            self.run(input)
+        }
+
+        fn run(&self, input: Array4<f32>) -> Result<Vec<f32>, String> {
+            let tensor = Tensor::from_array(input)
+                .map_err(|e| format!("Failed to build face model input: {}", e))?;
+            let inputs = ort::inputs![self.input_name.as_str() => tensor]
+                .map_err(|e| format!("Failed to build face model input: {}", e))?;
+            let outputs = self
+                .session
+                .run(inputs)
+                .map_err(|e| format!("Face model inference failed: {}", e))?;
+            let embedding = outputs[0]
+                .try_extract_tensor::<f32>()
+                .map_err(|e| format!("Unexpected face model output: {}", e))?;
+            Ok(embedding.iter().copied().collect())
+        }
+    }
+}
@@ -0,0 +1,62 @@
+/**
+ * Circuit Field Encoding
+ * Decimal BN254 scalar field elements, as circom circuits take them
+ */
+
+/// BN254 scalar field modulus; circuit inputs must be below it
+const MODULUS: &str =
+    "21888242871839275222246405745257275088548364400416034343698204186575808495617";
+/// Bytes of a digest or address that always fit below the modulus
+const SAFE_BYTES: usize = 31;
+
+/// Check `value` is a canonical decimal below the modulus
+pub fn element(value: &str) -> Result<String, String> {
+    let canonical = !value.is_empty()
+        && value.bytes().all(|b| b.is_ascii_digit())
+        && (value == "0" || !value.starts_with('0'));
+    let in_field = value.len() < MODULUS.len() || (value.len() == MODULUS.len() && value < MODULUS);
+    if !canonical || !in_field {
+        return Err(format!("{:?} is not a field element", value));
+    }
+    Ok(value.to_string())
+}
+
+/// The first 31 bytes (248 bits) of a digest or address, big-endian; the
+/// full 256 bits don't fit the field
+pub fn truncated(bytes: &[u8]) -> String {
+    decimal(&bytes[..bytes.len().min(SAFE_BYTES)])
+}
+
+/// Bytes zero-padded to `max_bytes` and packed into 31-byte chunks, each
+/// little-endian
+pub fn packed(bytes: &[u8], max_bytes: usize) -> Vec<String> {
+    let mut padded = bytes.to_vec();
+    padded.resize(max_bytes.max(bytes.len()), 0);
+    padded
+        .chunks(SAFE_BYTES)
+        .map(|chunk| {
+            let big_endian: Vec<u8> = chunk.iter().rev().copied().collect();
+            decimal(&big_endian)
+        })
+        .collect()
+}
+
+/// Big-endian bytes to a decimal string
+fn decimal(bytes: &[u8]) -> String {
+    let mut number = bytes.to_vec();
+    let mut digits = Vec::new();
+    while number.iter().any(|&b| b != 0) {
+        let mut remainder = 0u32;
+        for byte in number.iter_mut() {
+            let value = remainder << 8 | u32::from(*byte);
+            *byte = (value / 10) as u8;
+            remainder = value % 10;
+        }
+        digits.push(b'0' + remainder as u8);
+    }
+    if digits.is_empty() {
+        return "0".to_string();
+    }
+    digits.reverse();
+    String::from_utf8(digits).unwrap()
+}
@@ -0,0 +1,363 @@
+/**
+ * Sample Formats
+ * Strict decoding of submitted biometric samples, per method
+ *
+ *   face:        PNG, JPEG
+ *   fingerprint: WSQ, raw 8-bit grayscale (binary PGM)
+ *   voice:       WAV (16-bit PCM), Ogg Opus
+ *
+ * Only container headers are trusted; declared dimensions and durations
+ * are capped before anything is decoded, so a small upload can't claim
+ * a huge decoded size.
+ */
+
+use serde::Serialize;
+
+/// Encoded upload limit for any sample
+pub const MAX_SAMPLE_BYTES: usize = 8 * 1024 * 1024;
+/// Decoded pixel limit for images (e.g. 4096 x 4096)
+const MAX_PIXELS: u64 = 16 * 1024 * 1024;
+const MAX_AUDIO_SECS: f64 = 60.0;
+/// Ogg Opus granule positions always count 48 kHz samples
+const OPUS_GRANULE_RATE: f64 = 48_000.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SampleFormat {
+    Png,
+    Jpeg,
+    Wsq,
+    Raw,
+    Wav,
+    Opus,
+}
+
+pub enum Sample {
+    Image {
+        format: SampleFormat,
+        width: u32,
+        height: u32,
+        /// Pixels, for uncompressed grayscale only
+        pixels: Option<Vec<u8>>,
+    },
+    Audio {
+        format: SampleFormat,
+        sample_rate: u32,
+        duration_secs: f64,
+        /// First-channel PCM, for WAV only
+        pcm: Option<Vec<i16>>,
+    },
+}
+
+impl Sample {
+    pub fn format(&self) -> SampleFormat {
+        match self {
+            Sample::Image { format, .. } | Sample::Audio { format, .. } => *format,
+        }
+    }
+}
+
+pub enum FormatError {
+    /// Recognized, but not accepted for this method
+    Unsupported(String),
+    Malformed(String),
+    TooLarge(String),
+}
+
+impl FormatError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            FormatError::Unsupported(_) => "UNSUPPORTED_SAMPLE_FORMAT",
+            FormatError::Malformed(_) => "INVALID_BIOMETRIC_DATA",
+            FormatError::TooLarge(_) => "SAMPLE_TOO_LARGE",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            FormatError::Unsupported(m) | FormatError::Malformed(m) | FormatError::TooLarge(m) => m,
+        }
+    }
+}
+
+pub fn accepted_formats(method: &str) -> Option<&'static [SampleFormat]> {
+    match method {
+        "face" => Some(&[SampleFormat::Png, SampleFormat::Jpeg]),
+        "fingerprint" => Some(&[SampleFormat::Wsq, SampleFormat::Raw]),
+        "voice" => Some(&[SampleFormat::Wav, SampleFormat::Opus]),
+        _ => None,
+    }
+}
+
+pub fn decode(data: &[u8], method: &str) -> Result<Sample, FormatError> {
+    let accepted = accepted_formats(method).ok_or_else(|| {
+        FormatError::Unsupported(format!("Unsupported biometric method: {}", method))
+    })?;
+    if data.len() > MAX_SAMPLE_BYTES {
+        return Err(FormatError::TooLarge(format!(
+            "Sample is {} bytes; the limit is {}",
+            data.len(),
+            MAX_SAMPLE_BYTES
+        )));
+    }
+
+    let format = detect(data)
+        .ok_or_else(|| FormatError::Malformed("Sample format is not recognized".to_string()))?;
+    if !accepted.contains(&format) {
+        return Err(FormatError::Unsupported(format!(
+            "{:?} samples are not accepted for {}",
+            format, method
+        )));
+    }
+
+    let malformed =
+        |what: &str| FormatError::Malformed(format!("Malformed {:?} sample: {}", format, what));
+    let sample = match format {
+        SampleFormat::Png => png(data).ok_or_else(|| malformed("bad IHDR"))?,
+        SampleFormat::Jpeg => jpeg(data).ok_or_else(|| malformed("no frame header"))?,
+        SampleFormat::Wsq => wsq(data).ok_or_else(|| malformed("no frame header"))?,
+        SampleFormat::Raw => pgm(data).ok_or_else(|| malformed("bad header or pixel count"))?,
+        SampleFormat::Wav => wav(data).ok_or_else(|| malformed("expected 16-bit PCM"))?,
+        SampleFormat::Opus => opus(data).ok_or_else(|| malformed("bad OpusHead or pages"))?,
+    };
+
+    match &sample {
+        Sample::Image { width, height, .. } => {
+            if *width as u64 * *height as u64 > MAX_PIXELS {
+                return Err(FormatError::TooLarge(format!(
+                    "{}x{} exceeds the {} pixel limit",
+                    width, height, MAX_PIXELS
+                )));
+            }
+        }
+        Sample::Audio { duration_secs, .. } => {
+            if *duration_secs > MAX_AUDIO_SECS {
+                return Err(FormatError::TooLarge(format!(
+                    "{:.1}s recording exceeds the {:.0}s limit",
+                    duration_secs, MAX_AUDIO_SECS
+                )));
+            }
+        }
+    }
+    Ok(sample)
+}
+
+fn detect(data: &[u8]) -> Option<SampleFormat> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some(SampleFormat::Png)
+    } else if data.starts_with(&[0xff, 0xd8]) {
+        Some(SampleFormat::Jpeg)
+    } else if data.starts_with(&[0xff, 0xa0]) {
+        Some(SampleFormat::Wsq)
+    } else if data.starts_with(b"P5") {
+        Some(SampleFormat::Raw)
+    } else if data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WAVE") {
+        Some(SampleFormat::Wav)
+    } else if data.starts_with(b"OggS") {
+        Some(SampleFormat::Opus)
+    } else {
+        None
+    }
+}
+
+fn image(format: SampleFormat, width: u32, height: u32) -> Option<Sample> {
+    (width > 0 && height > 0).then_some(Sample::Image {
+        format,
+        width,
+        height,
+        pixels: None,
+    })
+}
+
+fn png(data: &[u8]) -> Option<Sample> {
+    if data.get(12..16) != Some(b"IHDR") {
+        return None;
+    }
+    let width = u32::from_be_bytes(data.get(16..20)?.try_into().ok()?);
+    let height = u32::from_be_bytes(data.get(20..24)?.try_into().ok()?);
+    let bit_depth = *data.get(24)?;
+    let color_type = *data.get(25)?;
+    let valid = match color_type {
+        0 => [1, 2, 4, 8, 16].contains(&bit_depth),
+        3 => [1, 2, 4, 8].contains(&bit_depth),
+        2 | 4 | 6 => [8, 16].contains(&bit_depth),
+        _ => false,
+    };
+    if !valid || !data.ends_with(b"IEND\xaeB`\x82") {
+        return None;
+    }
+    image(SampleFormat::Png, width, height)
+}
+
+/// JPEG and WSQ share the marker layout; frames differ in marker and field order
+fn markers(
+    data: &[u8],
+    mut visit: impl FnMut(u8, &[u8]) -> Option<Option<Sample>>,
+) -> Option<Sample> {
+    let mut pos = 2;
+    while pos + 4 <= data.len() {
+        if data[pos] != 0xff {
+            return None;
+        }
+        let marker = data[pos + 1];
+        let length = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+        if length < 2 {
+            return None;
+        }
+        let segment = data.get(pos + 4..pos + 2 + length)?;
+        if let Some(result) = visit(marker, segment)? {
+            return Some(result);
+        }
+        pos += 2 + length;
+    }
+    None
+}
+
+fn jpeg(data: &[u8]) -> Option<Sample> {
+    markers(data, |marker, segment| {
+        // SOF0..SOF15, excluding DHT (C4), JPG (C8) and DAC (CC)
+        if (0xc0..=0xcf).contains(&marker) && ![0xc4, 0xc8, 0xcc].contains(&marker) {
+            let height = u16::from_be_bytes([*segment.get(1)?, *segment.get(2)?]);
+            let width = u16::from_be_bytes([*segment.get(3)?, *segment.get(4)?]);
+            return Some(image(SampleFormat::Jpeg, width as u32, height as u32));
+        }
+        Some(None)
+    })
+}
+
+fn wsq(data: &[u8]) -> Option<Sample> {
+    markers(data, |marker, segment| {
+        // SOF: black, white, height, width, ...
+        if marker == 0xa2 {
+            let height = u16::from_be_bytes([*segment.get(2)?, *segment.get(3)?]);
+            let width = u16::from_be_bytes([*segment.get(4)?, *segment.get(5)?]);
+            return Some(image(SampleFormat::Wsq, width as u32, height as u32));
+        }
+        Some(None)
+    })
+}
+
+/// Binary PGM: "P5" width height maxval, single whitespace, then 8-bit pixels
+fn pgm(data: &[u8]) -> Option<Sample> {
+    let mut fields = Vec::with_capacity(3);
+    let mut pos = 2;
+    while fields.len() < 3 {
+        while data.get(pos)?.is_ascii_whitespace() {
+            pos += 1;
+        }
+        if data[pos] == b'#' {
+            while *data.get(pos)? != b'\n' {
+                pos += 1;
+            }
+            continue;
+        }
+        let start = pos;
+        while data.get(pos)?.is_ascii_digit() {
+            pos += 1;
+        }
+        fields.push(
+            std::str::from_utf8(&data[start..pos])
+                .ok()?
+                .parse::<u32>()
+                .ok()?,
+        );
+    }
+    if !data.get(pos)?.is_ascii_whitespace() || fields[2] == 0 || fields[2] > 255 {
+        return None;
+    }
+
+    let (width, height) = (fields[0], fields[1]);
+    let pixels = &data[pos + 1..];
+    if width == 0 || height == 0 || pixels.len() as u64 != width as u64 * height as u64 {
+        return None;
+    }
+    Some(Sample::Image {
+        format: SampleFormat::Raw,
+        width,
+        height,
+        pixels: Some(pixels.to_vec()),
+    })
+}
+
+fn wav(data: &[u8]) -> Option<Sample> {
+    let mut pos = 12;
+    let mut format = None;
+    while pos + 8 <= data.len() {
+        let id = &data[pos..pos + 4];
+        let size = u32::from_le_bytes(data[pos + 4..pos + 8].try_into().ok()?) as usize;
+        let body = data.get(pos + 8..(pos + 8).checked_add(size)?)?;
+
+        match id {
+            b"fmt " if body.len() >= 16 => {
+                let audio_format = u16::from_le_bytes([body[0], body[1]]);
+                let channels = u16::from_le_bytes([body[2], body[3]]);
+                let sample_rate = u32::from_le_bytes(body[4..8].try_into().ok()?);
+                let bits = u16::from_le_bytes([body[14], body[15]]);
+                if audio_format != 1 || bits != 16 || channels == 0 || sample_rate == 0 {
+                    return None;
+                }
+                format = Some((sample_rate, channels as usize));
+            }
+            b"data" => {
+                let (sample_rate, channels) = format?;
+                let pcm: Vec<i16> = body
+                    .chunks_exact(2 * channels)
+                    .map(|frame| i16::from_le_bytes([frame[0], frame[1]]))
+                    .collect();
+                return Some(Sample::Audio {
+                    format: SampleFormat::Wav,
+                    sample_rate,
+                    duration_secs: pcm.len() as f64 / sample_rate as f64,
+                    pcm: Some(pcm),
+                });
+            }
+            _ => {}
+        }
+        // Chunks are word-aligned
+        pos += 8 + size + (size & 1);
+    }
+    None
+}
+
+/// Ogg pages: OpusHead in the first, duration from the last granule position
+fn opus(data: &[u8]) -> Option<Sample> {
+    let mut pos = 0;
+    let mut head = None;
+    let mut last_granule = 0u64;
+
+    while pos + 27 <= data.len() {
+        if &data[pos..pos + 4] != b"OggS" {
+            return None;
+        }
+        let granule = u64::from_le_bytes(data[pos + 6..pos + 14].try_into().ok()?);
+        let segments = data[pos + 26] as usize;
+        let table = data.get(pos + 27..pos + 27 + segments)?;
+        let body_start = pos + 27 + segments;
+        let body_len: usize = table.iter().map(|&s| s as usize).sum();
+        let body = data.get(body_start..body_start + body_len)?;
+
+        if head.is_none() {
+            if !body.starts_with(b"OpusHead") || body.len() < 19 || body[9] == 0 {
+                return None;
+            }
+            let pre_skip = u16::from_le_bytes([body[10], body[11]]) as u64;
+            let input_rate = u32::from_le_bytes(body[12..16].try_into().ok()?);
+            head = Some((pre_skip, input_rate));
+        } else if granule != u64::MAX {
+            last_granule = granule;
+        }
+        pos = body_start + body_len;
+    }
+
+    let (pre_skip, input_rate) = head?;
+    if pos != data.len() {
+        return None;
+    }
+    Some(Sample::Audio {
+        format: SampleFormat::Opus,
+        // Input rate 0 means unspecified; Opus always decodes at 48 kHz
+        sample_rate: if input_rate == 0 { 48_000 } else { input_rate },
+        duration_secs: last_granule.saturating_sub(pre_skip) as f64 / OPUS_GRANULE_RATE,
+        pcm: None,
+    })
+}
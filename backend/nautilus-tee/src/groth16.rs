@@ -0,0 +1,160 @@
+/**
+ * Groth16 Verification
+ * Checking snarkjs proofs against a circuit version's verification key
+ *
+ * Verification keys are the snarkjs verification_key.json shipped beside
+ * each circuit's artifacts and pinned by the signed manifest. They are
+ * prepared once at load, leaving one multi-pairing per proof.
+ *
+ * The same keys are exported for Sui's groth16 module, which takes
+ * arkworks' compressed encoding: the whole key for
+ * prepare_verifying_key, or the four prepared parts for pvk_from_bytes.
+ */
+
+use ark_bn254::{Bn254, Fr};
+use ark_groth16::{Groth16, PreparedVerifyingKey, Proof, VerifyingKey};
+use ark_serialize::CanonicalSerialize;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::proof_encoding;
+
+/// Parse and prepare a snarkjs verification key
+pub fn verifying_key(json: &[u8]) -> Result<PreparedVerifyingKey<Bn254>, String> {
+    let key: Value =
+        serde_json::from_slice(json).map_err(|e| format!("Invalid verification key: {}", e))?;
+    if key["protocol"] != "groth16" || key["curve"] != "bn128" {
+        return Err("Verification key is not for Groth16 over BN254".to_string());
+    }
+    let gamma_abc_g1 = key["IC"]
+        .as_array()
+        .ok_or_else(|| "IC: Expected a point array".to_string())?
+        .iter()
+        .map(|point| proof_encoding::g1(point).map_err(field("IC")))
+        .collect::<Result<Vec<_>, _>>()?;
+    if gamma_abc_g1.is_empty() || key["nPublic"].as_u64() != Some(gamma_abc_g1.len() as u64 - 1) {
+        return Err("IC doesn't have a point per public signal".to_string());
+    }
+
+    let key = VerifyingKey {
+        alpha_g1: proof_encoding::g1(&key["vk_alpha_1"]).map_err(field("vk_alpha_1"))?,
+        beta_g2: proof_encoding::g2(&key["vk_beta_2"]).map_err(field("vk_beta_2"))?,
+        gamma_g2: proof_encoding::g2(&key["vk_gamma_2"]).map_err(field("vk_gamma_2"))?,
+        delta_g2: proof_encoding::g2(&key["vk_delta_2"]).map_err(field("vk_delta_2"))?,
+        gamma_abc_g1,
+    };
+    Ok(ark_groth16::prepare_verifying_key(&key))
+}
+
+/// Whether the proof verifies for these public signals; Err when the proof
+/// or signals are malformed rather than wrong
+pub fn verify(
+    key: &PreparedVerifyingKey<Bn254>,
+    proof: &Value,
+    public_signals: &[String],
+) -> Result<bool, String> {
+    let (a, b, c) = proof_encoding::points(proof)?;
+    let inputs: Vec<Fr> = proof_encoding::scalars(public_signals)?;
+    if inputs.len() + 1 != key.vk.gamma_abc_g1.len() {
+        return Err(format!(
+            "{} public signals given; the key takes {}",
+            inputs.len(),
+            key.vk.gamma_abc_g1.len() - 1
+        ));
+    }
+    Groth16::<Bn254>::verify_proof(key, &Proof { a, b, c }, &inputs).map_err(|e| e.to_string())
+}
+
+/// A verification key as Sui's groth16 module reads it; all hex
+#[derive(Serialize)]
+pub struct SuiVerifyingKey {
+    /// For `groth16::prepare_verifying_key(&groth16::bn254(), ..)`
+    pub verifying_key: String,
+    /// For `groth16::pvk_from_bytes`, in argument order
+    pub vk_gamma_abc_g1: String,
+    pub alpha_g1_beta_g2: String,
+    pub gamma_g2_neg_pc: String,
+    pub delta_g2_neg_pc: String,
+}
+
+/// Encode a prepared key for Sui
+pub fn sui_verifying_key(key: &PreparedVerifyingKey<Bn254>) -> Result<SuiVerifyingKey, String> {
+    fn compressed(value: &impl CanonicalSerialize) -> Result<String, String> {
+        let mut bytes = Vec::new();
+        value
+            .serialize_compressed(&mut bytes)
+            .map_err(|e| format!("Failed to encode verification key: {}", e))?;
+        Ok(hex::encode(bytes))
+    }
+
+    let mut gamma_abc_g1 = String::new();
+    for point in &key.vk.gamma_abc_g1 {
+        gamma_abc_g1.push_str(&compressed(point)?);
+    }
+    Ok(SuiVerifyingKey {
+        verifying_key: compressed(&key.vk)?,
+        vk_gamma_abc_g1: gamma_abc_g1,
+        alpha_g1_beta_g2: compressed(&key.alpha_g1_beta_g2)?,
+        gamma_g2_neg_pc: compressed(&-key.vk.gamma_g2)?,
+        delta_g2_neg_pc: compressed(&-key.vk.delta_g2)?,
+    })
+}
+
+fn field(name: &'static str) -> impl Fn(String) -> String {
+    move |e| format!("{}: {}", name, e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bn254::{G1Affine, G2Affine};
+    use ark_ec::{AffineRepr, CurveGroup};
+    use serde_json::json;
+
+    /// A key with alpha, beta, gamma and delta at the generators, and a
+    /// proof that satisfies it for `signal`: e(A, B) = e(G1, G2)^(1 + ic0
+    /// + ic1 * signal + c)
+    fn fixture(signal: u64) -> (Vec<u8>, Value) {
+        let (ic0, ic1, c) = (Fr::from(3u64), Fr::from(5u64), Fr::from(7u64));
+        let g1 = |s: Fr| (G1Affine::generator() * s).into_affine();
+        let g2 = G2Affine::generator();
+        let point = |p: G1Affine| proof_encoding::snarkjs(p, g2, p)["pi_a"].clone();
+        let g2_json = proof_encoding::snarkjs(G1Affine::generator(), g2, G1Affine::generator())
+            ["pi_b"]
+            .clone();
+        let key = json!({
+            "protocol": "groth16",
+            "curve": "bn128",
+            "nPublic": 1,
+            "vk_alpha_1": point(G1Affine::generator()),
+            "vk_beta_2": g2_json,
+            "vk_gamma_2": g2_json,
+            "vk_delta_2": g2_json,
+            "IC": [point(g1(ic0)), point(g1(ic1))],
+        });
+        let a = Fr::from(1u64) + ic0 + ic1 * Fr::from(signal) + c;
+        let proof = proof_encoding::snarkjs(g1(a), g2, g1(c));
+        (serde_json::to_vec(&key).unwrap(), proof)
+    }
+
+    #[test]
+    fn verifies_only_the_proven_signals() {
+        let (key, proof) = fixture(11);
+        let key = verifying_key(&key).unwrap();
+        assert!(verify(&key, &proof, &["11".to_string()]).unwrap());
+        assert!(!verify(&key, &proof, &["12".to_string()]).unwrap());
+        assert!(verify(&key, &proof, &[]).is_err());
+    }
+
+    #[test]
+    fn sui_encoding_is_compressed_arkworks() {
+        let (key, _) = fixture(11);
+        let sui = sui_verifying_key(&verifying_key(&key).unwrap()).unwrap();
+        // alpha, beta, gamma, delta, then IC's length and two points
+        assert_eq!(sui.verifying_key.len(), 2 * (32 + 3 * 64 + 8 + 2 * 32));
+        assert_eq!(sui.vk_gamma_abc_g1.len(), 2 * 2 * 32);
+        assert_eq!(sui.alpha_g1_beta_g2.len(), 2 * 384);
+        assert_eq!(sui.gamma_g2_neg_pc.len(), 2 * 64);
+        assert_ne!(sui.gamma_g2_neg_pc, &sui.verifying_key[192..320]);
+    }
+}
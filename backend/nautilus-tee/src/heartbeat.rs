@@ -0,0 +1,179 @@
+/**
+ * On-chain Heartbeats
+ * Check-ins made by calling a Move function instead of the enclave
+ *
+ * A configured heartbeat contract emits an event per call. The poller asks
+ * a Sui node for new events of that type through the parent's relay (see
+ * chain), resuming from a cursor kept in the store. The event names a
+ * vault either directly or by an object the owner has bound to the vault.
+ * An event counts as a check-in only if its sender is the vault's check-in
+ * owner, and only if no later check-in, direct or on chain, already covers
+ * it.
+ *
+ * The relay and node can withhold heartbeats or invent them. A withheld
+ * one is made up by checking in directly; an invented one can only
+ * postpone escalation, never unlock anything.
+ */
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+use crate::auth::normalize_address;
+use crate::chain::{self, ChainEvent};
+use crate::check_in::CheckInService;
+use crate::config::HeartbeatConfig;
+use crate::store::Store;
+use crate::tasks::{Schedule, TaskScheduler};
+
+const BINDINGS_NAMESPACE: &str = "heartbeat_objects";
+const CURSORS_NAMESPACE: &str = "heartbeat_cursors";
+const PAGE_LIMIT: usize = 50;
+/// Pages read per poll, so a long backlog doesn't hold up the interval
+const MAX_PAGES: usize = 20;
+
+/// An on-chain object whose heartbeats count for a vault
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ObjectBinding {
+    pub object_id: String,
+    pub vault_id: String,
+    pub owner: String,
+    pub bound_at: u64,
+}
+
+pub struct HeartbeatService {
+    store: Arc<Store>,
+    check_ins: Arc<CheckInService>,
+}
+
+impl HeartbeatService {
+    pub fn new(store: Arc<Store>, check_ins: Arc<CheckInService>) -> Self {
+        Self { store, check_ins }
+    }
+
+    /// Count heartbeats naming `object_id` for the vault. The caller has
+    /// been authorized.
+    pub fn bind_object(
+        &self,
+        vault_id: &str,
+        owner: &str,
+        object_id: &str,
+    ) -> Result<ObjectBinding, String> {
+        let hex = object_id.trim_start_matches("0x");
+        if hex.is_empty() || hex.len() > 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("{} is not a Sui object id", object_id));
+        }
+        let object_id = normalize_address(object_id);
+        if let Some(existing) = self.binding(&object_id)? {
+            if existing.vault_id != vault_id {
+                return Err(format!("Object {} is bound to another vault", object_id));
+            }
+        }
+
+        let binding = ObjectBinding {
+            object_id,
+            vault_id: vault_id.to_string(),
+            owner: normalize_address(owner),
+            bound_at: now(),
+        };
+        self.store
+            .put(BINDINGS_NAMESPACE, &binding.object_id, &binding)?;
+        Ok(binding)
+    }
+
+    fn binding(&self, object_id: &str) -> Result<Option<ObjectBinding>, String> {
+        self.store.get(BINDINGS_NAMESPACE, object_id)
+    }
+
+    /// Poll the heartbeat contract's events on the task scheduler
+    pub fn schedule_poller(
+        self: Arc<Self>,
+        tasks: &TaskScheduler,
+        config: HeartbeatConfig,
+    ) -> Result<(), String> {
+        let (schedule, jitter) = (Schedule::every(config.poll), config.poll / 10);
+        tasks.register("heartbeat.poll", &schedule, jitter, move || {
+            let (service, config) = (self.clone(), config.clone());
+            async move {
+                let applied = service.poll(&config).await?;
+                if applied > 0 {
+                    info!("Heartbeat poll recorded {} check-ins", applied);
+                }
+                Ok(())
+            }
+        })
+    }
+
+    async fn poll(&self, config: &HeartbeatConfig) -> Result<usize, String> {
+        let mut cursor: Option<Value> = self.store.get(CURSORS_NAMESPACE, &config.event_type)?;
+        let mut applied = 0;
+        for _ in 0..MAX_PAGES {
+            let page = chain::query_events(
+                &config.relay,
+                &config.event_type,
+                cursor.as_ref(),
+                PAGE_LIMIT,
+            )
+            .await?;
+            for event in &page.data {
+                if self.apply(config, event)? {
+                    applied += 1;
+                }
+            }
+            if let Some(next) = page.next_cursor {
+                self.store
+                    .put(CURSORS_NAMESPACE, &config.event_type, &next)?;
+                cursor = Some(next);
+            }
+            if !page.has_next_page {
+                break;
+            }
+        }
+        Ok(applied)
+    }
+
+    /// Record the event as a check-in; false when it doesn't count
+    fn apply(&self, config: &HeartbeatConfig, event: &ChainEvent) -> Result<bool, String> {
+        let Some(reference) = chain::field_string(&event.parsed_json, &config.vault_field) else {
+            warn!(
+                "Heartbeat {}:{} has no {} field",
+                event.id.tx_digest, event.id.event_seq, config.vault_field
+            );
+            return Ok(false);
+        };
+        let vault_id = match self.binding(&normalize_address(&reference))? {
+            Some(binding) => binding.vault_id,
+            None => reference,
+        };
+        let Some(schedule) = self.check_ins.schedule(&vault_id)? else {
+            return Ok(false);
+        };
+        if normalize_address(&event.sender) != schedule.owner {
+            warn!(
+                "Heartbeat for {} from {}, who doesn't own its check-ins",
+                vault_id, event.sender
+            );
+            return Ok(false);
+        }
+
+        let now = now();
+        let at = event
+            .timestamp_ms
+            .as_deref()
+            .and_then(|ms| ms.parse::<u64>().ok())
+            .map_or(now, |ms| (ms / 1000).min(now));
+        let check_in = self
+            .check_ins
+            .record_heartbeat(&vault_id, at, event.id.clone())?;
+        Ok(check_in.is_some())
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
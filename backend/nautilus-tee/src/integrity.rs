@@ -0,0 +1,209 @@
+/**
+ * Vault Integrity
+ * Merkle commitments over a vault's encrypted blobs
+ *
+ * The owner commits a vault's blobs once: the enclave hashes each blob
+ * into a leaf, builds the Merkle root and keeps both in its registry,
+ * for the owner to also record on the vault's on-chain object. Anyone
+ * can later have the enclave recompute the root, over blobs they supply
+ * or over the registered leaves, and compare it with the registry and
+ * with the on-chain root.
+ *
+ * Blobs are hashed into leaves straight from their base64, a chunk at a
+ * time, so committing or checking a vault never holds a decoded blob.
+ *
+ * The enclave has no outbound network, so the on-chain root is relayed
+ * by the caller; the attested verdict names the root it was compared
+ * with, for verifiers to check against the chain themselves.
+ *
+ *   leaf = sha256(0x00 || blob)
+ *   node = sha256(0x01 || left || right), an unpaired node moves up as is
+ */
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::blob_stream;
+use crate::store::Store;
+
+const NAMESPACE: &str = "vault_commitments";
+pub const MAX_BLOBS: usize = 4096;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct VaultCommitment {
+    pub vault_id: String,
+    pub owner: String,
+    pub root: String,        // Hex
+    pub leaves: Vec<String>, // Hex, one per blob in order
+    pub committed_at: u64,
+}
+
+#[derive(Serialize)]
+pub struct RootCheck {
+    pub expected: String,
+    pub matched: bool,
+}
+
+#[derive(Serialize)]
+pub struct IntegrityVerdict {
+    pub vault_id: String,
+    /// Whether the root was recomputed from supplied blobs
+    pub supplied_blobs: bool,
+    pub blob_count: usize,
+    pub root: String,
+    pub registry: RootCheck,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_chain: Option<RootCheck>,
+    /// Positions whose blob differs from the registered leaf
+    pub mismatched: Vec<usize>,
+    pub intact: bool,
+}
+
+pub struct IntegrityService {
+    store: Arc<Store>,
+}
+
+impl IntegrityService {
+    pub fn new(store: Arc<Store>) -> Self {
+        Self { store }
+    }
+
+    /// Register the leaves of the vault's blobs; only the first committer
+    /// may recommit
+    pub fn commit(
+        &self,
+        vault_id: &str,
+        owner: &str,
+        leaves: &[[u8; 32]],
+    ) -> Result<VaultCommitment, String> {
+        if leaves.is_empty() || leaves.len() > MAX_BLOBS {
+            return Err(format!("Between 1 and {} blobs are required", MAX_BLOBS));
+        }
+        if let Some(existing) = self.commitment(vault_id)? {
+            if existing.owner != owner {
+                return Err("Vault is committed by a different owner".to_string());
+            }
+        }
+
+        let commitment = VaultCommitment {
+            vault_id: vault_id.to_string(),
+            owner: owner.to_string(),
+            root: hex::encode(merkle_root(leaves)),
+            leaves: leaves.iter().map(hex::encode).collect(),
+            committed_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+        };
+        self.store.put(NAMESPACE, vault_id, &commitment)?;
+        Ok(commitment)
+    }
+
+    pub fn commitment(&self, vault_id: &str) -> Result<Option<VaultCommitment>, String> {
+        self.store.get(NAMESPACE, vault_id)
+    }
+
+    /// Recompute the root over the leaves of supplied blobs, or the
+    /// registered leaves when none are supplied, and compare it with the
+    /// registry and `on_chain_root`
+    pub fn verify(
+        &self,
+        commitment: &VaultCommitment,
+        supplied: Option<&[[u8; 32]]>,
+        on_chain_root: Option<&str>,
+    ) -> Result<IntegrityVerdict, String> {
+        let leaves: Vec<[u8; 32]> = match supplied {
+            Some(leaves) => {
+                if leaves.is_empty() || leaves.len() > MAX_BLOBS {
+                    return Err(format!("Between 1 and {} blobs are required", MAX_BLOBS));
+                }
+                leaves.to_vec()
+            }
+            None => commitment
+                .leaves
+                .iter()
+                .map(|leaf| decode_hash(leaf))
+                .collect::<Result<_, _>>()?,
+        };
+        let root = hex::encode(merkle_root(&leaves));
+
+        let mismatched = leaves
+            .iter()
+            .enumerate()
+            .filter(|(i, leaf)| commitment.leaves.get(*i) != Some(&hex::encode(leaf)))
+            .map(|(i, _)| i)
+            .collect();
+        let registry = RootCheck {
+            matched: root == commitment.root,
+            expected: commitment.root.clone(),
+        };
+        let on_chain = on_chain_root
+            .map(|expected| {
+                let expected = hex::encode(decode_hash(expected)?);
+                Ok::<_, String>(RootCheck {
+                    matched: root == expected,
+                    expected,
+                })
+            })
+            .transpose()?;
+
+        Ok(IntegrityVerdict {
+            vault_id: commitment.vault_id.clone(),
+            supplied_blobs: supplied.is_some(),
+            blob_count: leaves.len(),
+            intact: registry.matched && on_chain.as_ref().is_none_or(|c| c.matched),
+            root,
+            registry,
+            on_chain,
+            mismatched,
+        })
+    }
+}
+
+/// The leaf of a base64 blob, decoded and hashed a chunk at a time
+pub fn leaf(encoded_blob: &str) -> Result<[u8; 32], String> {
+    let mut hasher = Sha256::new();
+    hasher.update([0x00]);
+    blob_stream::decode_chunks(encoded_blob, |chunk| {
+        hasher.update(chunk);
+        Ok(())
+    })?;
+    Ok(hasher.finalize().into())
+}
+
+/// The leaf of a blob already in memory
+pub fn blob_leaf(blob: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update([0x00]);
+    hasher.update(blob);
+    hasher.finalize().into()
+}
+
+fn merkle_root(leaves: &[[u8; 32]]) -> [u8; 32] {
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => {
+                    let mut hasher = Sha256::new();
+                    hasher.update([0x01]);
+                    hasher.update(left);
+                    hasher.update(right);
+                    hasher.finalize().into()
+                }
+                _ => pair[0],
+            })
+            .collect();
+    }
+    level.first().copied().unwrap_or_default()
+}
+
+fn decode_hash(encoded: &str) -> Result<[u8; 32], String> {
+    hex::decode(encoded.trim_start_matches("0x"))
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| "Roots and leaves must be 32-byte hex".to_string())
+}
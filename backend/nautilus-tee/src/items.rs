@@ -0,0 +1,286 @@
+/**
+ * Item Release Policies
+ * Releasing single files of a vault bundle to the people meant to get them
+ *
+ * A vault payload that is an archive (see manifest) can carry a policy
+ * per file, e.g. a letter that only one beneficiary may read, and only
+ * after a date or once guardians have approved. When a threshold
+ * decryption combines, the requester gets just the files their policies
+ * allow, each sealed to the key they supplied; the rest of the payload
+ * never leaves the enclave. Files without a policy are not released
+ * individually, and a vault with item policies can't be released whole.
+ */
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use x25519_dalek::PublicKey;
+
+use crate::approvals::ApprovalService;
+use crate::auth::normalize_address;
+use crate::crypto::{self, Envelope, KemPublicKey, KeyExchange};
+use crate::manifest;
+use crate::policy::PolicyEngine;
+use crate::redaction::{self, RedactionService, RedactionSummary};
+use crate::store::Store;
+
+const NAMESPACE: &str = "item_policies";
+const MAX_ITEMS: usize = 256;
+const MAX_RECIPIENTS: usize = 32;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ItemPolicy {
+    /// File name within the payload archive
+    pub item: String,
+    /// Addresses the item may be released to
+    pub recipients: Vec<String>,
+    /// Unix time before which the item stays sealed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub not_before: Option<u64>,
+    /// Approval operation that must be current (see approvals)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub approval: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ItemPolicySet {
+    pub vault_id: String,
+    pub owner: String,
+    pub items: Vec<ItemPolicy>,
+    pub updated_at: u64,
+}
+
+/// Key the released items are sealed to
+#[derive(Deserialize)]
+pub struct ItemRecipient {
+    pub public_key: String, // Base64 X25519
+    /// Base64 ML-KEM-768 key, required for hybrid key exchange
+    #[serde(default)]
+    pub kem_public_key: Option<String>,
+    #[serde(default)]
+    pub key_exchange: KeyExchange,
+}
+
+pub struct RecipientKey {
+    public_key: PublicKey,
+    kem_public_key: Option<KemPublicKey>,
+    key_exchange: KeyExchange,
+}
+
+impl ItemRecipient {
+    pub fn decode(&self) -> Result<RecipientKey, String> {
+        let kem_public_key = self
+            .kem_public_key
+            .as_deref()
+            .map(crypto::decode_kem_public_key)
+            .transpose()?;
+        if self.key_exchange == KeyExchange::HybridMlKem768 && kem_public_key.is_none() {
+            return Err("Hybrid key exchange requires an ML-KEM public key".to_string());
+        }
+        Ok(RecipientKey {
+            public_key: crypto::decode_public_key(&self.public_key)?,
+            kem_public_key,
+            key_exchange: self.key_exchange,
+        })
+    }
+}
+
+#[derive(Serialize)]
+pub struct ReleasedItem {
+    pub item: String,
+    pub size: u64,
+    pub sha256: String, // Hex, of the file before sealing
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redaction: Option<RedactionSummary>,
+    pub envelope: Envelope,
+}
+
+#[derive(Serialize)]
+pub struct WithheldItem {
+    pub item: String,
+    pub reason: String,
+}
+
+/// Only items addressed to the caller appear, released or withheld
+#[derive(Serialize)]
+pub struct ItemRelease {
+    pub released: Vec<ReleasedItem>,
+    pub withheld: Vec<WithheldItem>,
+}
+
+pub struct ItemPolicyService {
+    store: Arc<Store>,
+    redaction: Arc<RedactionService>,
+}
+
+impl ItemPolicyService {
+    pub fn new(store: Arc<Store>, redaction: Arc<RedactionService>) -> Self {
+        Self { store, redaction }
+    }
+
+    /// Replace the vault's item policies; an empty list clears them.
+    /// `key_owner` is the owner of the vault's threshold key.
+    pub fn set(
+        &self,
+        vault_id: &str,
+        caller: &str,
+        key_owner: &str,
+        items: Vec<ItemPolicy>,
+    ) -> Result<ItemPolicySet, String> {
+        if caller != key_owner {
+            return Err("Only the vault owner may set item policies".to_string());
+        }
+        if items.len() > MAX_ITEMS {
+            return Err(format!("At most {} item policies", MAX_ITEMS));
+        }
+
+        let mut names = HashSet::new();
+        let mut normalized = Vec::with_capacity(items.len());
+        for item in items {
+            if item.item.is_empty() || !names.insert(item.item.clone()) {
+                return Err(format!(
+                    "Item names must be unique and non-empty: {:?}",
+                    item.item
+                ));
+            }
+            if item.recipients.is_empty() || item.recipients.len() > MAX_RECIPIENTS {
+                return Err(format!(
+                    "Item {} needs 1 to {} recipients",
+                    item.item, MAX_RECIPIENTS
+                ));
+            }
+            if item.approval.as_deref().is_some_and(str::is_empty) {
+                return Err(format!("Item {} names an empty approval", item.item));
+            }
+            let mut recipients: Vec<String> = item
+                .recipients
+                .iter()
+                .map(|r| normalize_address(r))
+                .collect();
+            recipients.sort();
+            recipients.dedup();
+            normalized.push(ItemPolicy { recipients, ..item });
+        }
+
+        let set = ItemPolicySet {
+            vault_id: vault_id.to_string(),
+            owner: caller.to_string(),
+            items: normalized,
+            updated_at: now(),
+        };
+        self.store.put(NAMESPACE, vault_id, &set)?;
+        Ok(set)
+    }
+
+    /// The vault's policies, if any item is addressed to `caller`
+    pub fn addressed_to(
+        &self,
+        vault_id: &str,
+        caller: &str,
+    ) -> Result<Option<ItemPolicySet>, String> {
+        Ok(self.policies(vault_id)?.filter(|set| {
+            set.items
+                .iter()
+                .any(|item| item.recipients.iter().any(|r| r == caller))
+        }))
+    }
+
+    /// Whether the vault must be released item by item
+    pub fn itemized(&self, vault_id: &str) -> Result<bool, String> {
+        Ok(self.policies(vault_id)?.is_some())
+    }
+
+    /// Seal each item of `payload` addressed to `caller` whose policy
+    /// allows it; the payload itself is dropped by the caller
+    pub fn release(
+        &self,
+        set: &ItemPolicySet,
+        caller: &str,
+        payload: &[u8],
+        recipient: &RecipientKey,
+        policy: &PolicyEngine,
+        approvals: &ApprovalService,
+    ) -> Result<ItemRelease, String> {
+        let (_, files) = manifest::unpack(payload)?;
+        let redaction = self.redaction.policy(&set.vault_id)?;
+        let now = now();
+
+        let mut release = ItemRelease {
+            released: Vec::new(),
+            withheld: Vec::new(),
+        };
+        let addressed = set
+            .items
+            .iter()
+            .filter(|item| item.recipients.iter().any(|r| r == caller));
+        for item in addressed {
+            let approved = match &item.approval {
+                Some(operation) => approvals
+                    .current_approval(&set.vault_id, operation)?
+                    .is_some(),
+                None => false,
+            };
+            let decision = policy.evaluate_item_release(item, caller, now, approved);
+            if !decision.allowed {
+                release.withheld.push(WithheldItem {
+                    item: item.item.clone(),
+                    reason: decision.reason,
+                });
+                continue;
+            }
+
+            let Some(file) = files
+                .iter()
+                .find(|file| file.name.as_deref() == Some(item.item.as_str()))
+            else {
+                release.withheld.push(WithheldItem {
+                    item: item.item.clone(),
+                    reason: "Item is not in the vault payload".to_string(),
+                });
+                continue;
+            };
+            let (data, summary) = match &redaction {
+                Some(policy) => {
+                    let (redacted, summary) = redaction::redact(policy, &file.data)
+                        .map_err(|e| format!("{}: {}", item.item, e))?;
+                    (redacted, Some(summary))
+                }
+                None => (file.data.clone(), None),
+            };
+            release.released.push(ReleasedItem {
+                item: item.item.clone(),
+                size: data.len() as u64,
+                sha256: hex::encode(Sha256::digest(&data[..])),
+                redaction: summary,
+                envelope: crypto::seal_with(
+                    recipient.key_exchange,
+                    &recipient.public_key,
+                    recipient.kem_public_key.as_ref(),
+                    &data,
+                    item_aad(&set.vault_id, &item.item, caller).as_bytes(),
+                )?,
+            });
+        }
+        Ok(release)
+    }
+
+    pub fn policies(&self, vault_id: &str) -> Result<Option<ItemPolicySet>, String> {
+        Ok(self
+            .store
+            .get::<ItemPolicySet>(NAMESPACE, vault_id)?
+            .filter(|set| !set.items.is_empty()))
+    }
+}
+
+fn item_aad(vault_id: &str, item: &str, recipient: &str) -> String {
+    format!("lumina-vault-item:{}:{}:{}", vault_id, item, recipient)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
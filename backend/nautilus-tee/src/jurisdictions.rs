@@ -0,0 +1,320 @@
+/**
+ * Jurisdiction Rule Packs
+ * Inheritance rules a vault is registered under, enforced at unlock
+ *
+ * Waiting periods and required documents differ by jurisdiction, so rule
+ * packs are configured per deployment (TEE_RULE_PACKS), one per entry:
+ *
+ *   <jurisdiction>=<contest_days>[/<claim_type>@<version>+...]
+ *
+ * e.g. `de=30/dkim_email@1` for a 30-day contest window and a death
+ * certificate proven as a DKIM-signed email from the registry. A vault
+ * picks a pack when it is registered from a template (see
+ * vault_templates), and its registration keeps a copy, so a later change
+ * to the configuration never loosens a vault already registered.
+ *
+ * Once the vault's escalation ladder reaches unlock, its pack holds
+ * release back until
+ *   - the contest window has passed since unlock,
+ *   - every required claim has been proven since the missed check-in
+ *     that started the ladder, by a proof that verifies against the
+ *     circuit's pinned key, and
+ *   - no contest is open. Owners, guardians and beneficiaries may lodge
+ *     one; only an enclave admin resolves it, e.g. once a court has ruled.
+ */
+
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::store::Store;
+use crate::validation::ClaimType;
+
+const EVIDENCE_NAMESPACE: &str = "jurisdiction_evidence";
+const CONTESTS_NAMESPACE: &str = "jurisdiction_contests";
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+const MAX_CONTEST_DAYS: u32 = 3 * 365;
+const MAX_REASON_LEN: usize = 1024;
+
+/// A claim that must be proven before release
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EvidenceRule {
+    pub claim_type: ClaimType,
+    pub version: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RulePack {
+    pub jurisdiction: String,
+    pub contest_days: u32,
+    pub evidence: Vec<EvidenceRule>,
+}
+
+impl RulePack {
+    pub fn contest_secs(&self) -> u64 {
+        u64::from(self.contest_days) * SECS_PER_DAY
+    }
+}
+
+/// The latest verified proof per vault and rule
+#[derive(Clone, Serialize, Deserialize)]
+pub struct EvidenceRecord {
+    pub vault_id: String,
+    #[serde(flatten)]
+    pub rule: EvidenceRule,
+    pub public_signals: Vec<String>,
+    pub submitted_by: String,
+    pub verified_at: u64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Contest {
+    pub contest_id: String,
+    pub vault_id: String,
+    pub lodged_by: String,
+    pub reason: String,
+    pub lodged_at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolved_by: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolved_at: Option<u64>,
+}
+
+/// Where a vault stands against its pack
+#[derive(Clone, Serialize)]
+pub struct JurisdictionStatus {
+    pub pack: RulePack,
+    /// When the escalation ladder reached unlock, if it has
+    pub unlocked_at: Option<u64>,
+    pub window_ends_at: Option<u64>,
+    pub missing_evidence: Vec<EvidenceRule>,
+    pub open_contests: usize,
+}
+
+pub struct JurisdictionService {
+    store: Arc<Store>,
+    packs: Vec<RulePack>,
+}
+
+impl JurisdictionService {
+    pub fn new(store: Arc<Store>, entries: &[String]) -> Result<Self, String> {
+        let mut packs: Vec<RulePack> = Vec::new();
+        for entry in entries {
+            let pack = parse_pack(entry)?;
+            if packs.iter().any(|p| p.jurisdiction == pack.jurisdiction) {
+                return Err(format!(
+                    "Rule pack {} is configured twice",
+                    pack.jurisdiction
+                ));
+            }
+            packs.push(pack);
+        }
+        Ok(Self { store, packs })
+    }
+
+    pub fn packs(&self) -> &[RulePack] {
+        &self.packs
+    }
+
+    pub fn pack(&self, jurisdiction: &str) -> Option<&RulePack> {
+        self.packs
+            .iter()
+            .find(|p| p.jurisdiction.eq_ignore_ascii_case(jurisdiction))
+    }
+
+    /// Keep a proof the caller has verified, replacing any earlier one for
+    /// the same rule
+    pub fn record_evidence(
+        &self,
+        vault_id: &str,
+        rule: EvidenceRule,
+        public_signals: Vec<String>,
+        submitted_by: &str,
+    ) -> Result<EvidenceRecord, String> {
+        let record = EvidenceRecord {
+            vault_id: vault_id.to_string(),
+            rule,
+            public_signals,
+            submitted_by: submitted_by.to_string(),
+            verified_at: now(),
+        };
+        self.store
+            .put(EVIDENCE_NAMESPACE, &evidence_key(vault_id, &rule), &record)?;
+        Ok(record)
+    }
+
+    pub fn lodge_contest(
+        &self,
+        vault_id: &str,
+        lodged_by: &str,
+        reason: &str,
+    ) -> Result<Contest, String> {
+        let reason = reason.trim();
+        if reason.is_empty() || reason.chars().count() > MAX_REASON_LEN {
+            return Err(format!(
+                "A contest gives a reason of 1 to {} characters",
+                MAX_REASON_LEN
+            ));
+        }
+        let mut id = [0u8; 8];
+        OsRng.fill_bytes(&mut id);
+        let contest = Contest {
+            contest_id: hex::encode(id),
+            vault_id: vault_id.to_string(),
+            lodged_by: lodged_by.to_string(),
+            reason: reason.to_string(),
+            lodged_at: now(),
+            resolved_by: None,
+            resolved_at: None,
+        };
+        self.store.put(
+            CONTESTS_NAMESPACE,
+            &contest_key(vault_id, &contest.contest_id),
+            &contest,
+        )?;
+        Ok(contest)
+    }
+
+    pub fn resolve_contest(
+        &self,
+        vault_id: &str,
+        contest_id: &str,
+        resolved_by: &str,
+    ) -> Result<Contest, String> {
+        let key = contest_key(vault_id, contest_id);
+        let mut contest = self
+            .store
+            .get::<Contest>(CONTESTS_NAMESPACE, &key)?
+            .ok_or_else(|| "Contest not found".to_string())?;
+        if contest.resolved_at.is_some() {
+            return Err("Contest is already resolved".to_string());
+        }
+        contest.resolved_by = Some(resolved_by.to_string());
+        contest.resolved_at = Some(now());
+        self.store.put(CONTESTS_NAMESPACE, &key, &contest)?;
+        Ok(contest)
+    }
+
+    /// `unlocked_at` is when the ladder reached unlock; `since` is the
+    /// missed check-in that started it, before which evidence doesn't count
+    pub fn status(
+        &self,
+        vault_id: &str,
+        pack: RulePack,
+        unlocked_at: Option<u64>,
+        since: Option<u64>,
+    ) -> Result<JurisdictionStatus, String> {
+        let mut missing_evidence = Vec::new();
+        for rule in &pack.evidence {
+            let current = self
+                .store
+                .get::<EvidenceRecord>(EVIDENCE_NAMESPACE, &evidence_key(vault_id, rule))?
+                .is_some_and(|r| since.is_some_and(|since| r.verified_at >= since));
+            if !current {
+                missing_evidence.push(*rule);
+            }
+        }
+        let open_contests = self
+            .store
+            .list::<Contest>(CONTESTS_NAMESPACE)?
+            .into_iter()
+            .filter(|(_, c)| c.vault_id == vault_id && c.resolved_at.is_none())
+            .count();
+
+        Ok(JurisdictionStatus {
+            window_ends_at: unlocked_at.map(|at| at + pack.contest_secs()),
+            unlocked_at,
+            missing_evidence,
+            open_contests,
+            pack,
+        })
+    }
+}
+
+fn parse_pack(entry: &str) -> Result<RulePack, String> {
+    let invalid = || format!("Invalid rule pack entry: {}", entry);
+    let (jurisdiction, rules) = entry.split_once('=').ok_or_else(invalid)?;
+    let (days, claims) = match rules.split_once('/') {
+        Some((days, claims)) => (days, Some(claims)),
+        None => (rules, None),
+    };
+    let jurisdiction = jurisdiction.trim().to_lowercase();
+    if jurisdiction.is_empty()
+        || !jurisdiction
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+    {
+        return Err(invalid());
+    }
+    let contest_days: u32 = days.trim().parse().map_err(|_| invalid())?;
+    if contest_days > MAX_CONTEST_DAYS {
+        return Err(format!(
+            "Rule pack {} has a contest window over {} days",
+            jurisdiction, MAX_CONTEST_DAYS
+        ));
+    }
+
+    let mut evidence = Vec::new();
+    for claim in claims.into_iter().flat_map(|c| c.split('+')) {
+        let (claim_type, version) = claim.trim().split_once('@').ok_or_else(invalid)?;
+        let rule = EvidenceRule {
+            claim_type: serde_json::from_value(Value::String(claim_type.to_string()))
+                .map_err(|_| format!("Rule pack {} names unknown claim {}", jurisdiction, claim))?,
+            version: version.parse().map_err(|_| invalid())?,
+        };
+        if !evidence.contains(&rule) {
+            evidence.push(rule);
+        }
+    }
+    Ok(RulePack {
+        jurisdiction,
+        contest_days,
+        evidence,
+    })
+}
+
+fn evidence_key(vault_id: &str, rule: &EvidenceRule) -> String {
+    format!("{}:{}@{}", vault_id, rule.claim_type, rule.version)
+}
+
+fn contest_key(vault_id: &str, contest_id: &str) -> String {
+    format!("{}:{}", vault_id, contest_id)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_rule_pack_entries() {
+        let pack = parse_pack("DE=30/dkim_email@1+identity_attribute@1").unwrap();
+        assert_eq!(pack.jurisdiction, "de");
+        assert_eq!(pack.contest_secs(), 30 * SECS_PER_DAY);
+        assert_eq!(
+            pack.evidence,
+            vec![
+                EvidenceRule {
+                    claim_type: ClaimType::DkimEmail,
+                    version: 1,
+                },
+                EvidenceRule {
+                    claim_type: ClaimType::IdentityAttribute,
+                    version: 1,
+                },
+            ]
+        );
+        assert!(parse_pack("fr=14").unwrap().evidence.is_empty());
+        assert!(parse_pack("fr=14/death@1").is_err());
+        assert!(parse_pack("fr").is_err());
+    }
+}
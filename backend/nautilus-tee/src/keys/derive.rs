@@ -0,0 +1,230 @@
+/**
+ * Key Hierarchy
+ * Per-vault keys derived from one enclave root via HKDF-SHA256
+ *
+ * Every key is HKDF(root, info = "lumina/v1/<purpose>" || len(vault) ||
+ * vault). Purposes are types, so a key derived for one purpose cannot be
+ * handed to code expecting another, and the length prefix keeps vault
+ * ids from colliding across purposes. Rotating the root rotates every
+ * derived key; the root key id identifies the generation in use.
+ *
+ * Keys that decrypt vault data are taken with `unwrap`, which needs a
+ * grant from the key release policy (see keys::release). Only `Ungated`
+ * purposes, which never decrypt anything, can be derived without one;
+ * any other key can still be identified by its key id.
+ */
+
+use ed25519_dalek::{SigningKey, VerifyingKey};
+use hkdf::Hkdf;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::marker::PhantomData;
+use zeroize::Zeroizing;
+
+use crate::keys::release::ReleaseGrant;
+
+const SALT: &[u8] = b"lumina-key-hierarchy-v1";
+
+/// What a derived key may be used for
+pub trait KeyPurpose {
+    const LABEL: &'static str;
+}
+
+/// Purposes whose keys never decrypt vault data, derived without a grant
+pub trait Ungated: KeyPurpose {}
+
+/// Encrypting enrolled biometric templates at rest
+pub enum TemplateEncryption {}
+/// Signing a vault's audit trail entries
+pub enum AuditSigning {}
+/// Encrypting vault records in enclave storage
+pub enum Storage {}
+/// Search tokens and metadata in an owner's vault index; derived per owner
+/// address rather than per vault
+pub enum SearchIndex {}
+/// Encrypting records archived outside the enclave
+pub enum Archival {}
+
+impl KeyPurpose for TemplateEncryption {
+    const LABEL: &'static str = "template-encryption";
+}
+
+impl KeyPurpose for AuditSigning {
+    const LABEL: &'static str = "audit-signing";
+}
+
+// Refused key releases are recorded in the audit trail, so signing it
+// can't depend on a grant
+impl Ungated for AuditSigning {}
+
+impl KeyPurpose for Storage {
+    const LABEL: &'static str = "storage";
+}
+
+impl KeyPurpose for SearchIndex {
+    const LABEL: &'static str = "search-index";
+}
+
+impl KeyPurpose for Archival {
+    const LABEL: &'static str = "archival";
+}
+
+/// 256-bit key bound to one purpose and one vault
+pub struct VaultKey<P: KeyPurpose> {
+    bytes: Zeroizing<[u8; 32]>,
+    purpose: PhantomData<P>,
+}
+
+impl<P: KeyPurpose> VaultKey<P> {
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.bytes
+    }
+
+    /// Public identifier for the key, safe to log or publish
+    pub fn key_id(&self) -> String {
+        key_id(self.bytes.as_ref())
+    }
+}
+
+impl VaultKey<AuditSigning> {
+    pub fn signing_key(&self) -> SigningKey {
+        SigningKey::from_bytes(self.as_bytes())
+    }
+
+    pub fn verifying_key(&self) -> VerifyingKey {
+        self.signing_key().verifying_key()
+    }
+}
+
+pub struct KeyHierarchy {
+    root: Zeroizing<[u8; 32]>,
+}
+
+impl KeyHierarchy {
+    pub fn new(root: Zeroizing<[u8; 32]>) -> Self {
+        Self { root }
+    }
+
+    pub fn from_base64(encoded: &str) -> Result<Self, String> {
+        let bytes = Zeroizing::new(crate::crypto::decode(encoded)?);
+        let root: [u8; 32] = bytes
+            .as_slice()
+            .try_into()
+            .map_err(|_| "Root key must be 32 bytes".to_string())?;
+        Ok(Self::new(Zeroizing::new(root)))
+    }
+
+    /// Fresh root for enclaves started without one; keys do not survive restarts
+    pub fn ephemeral() -> Self {
+        let mut root = Zeroizing::new([0u8; 32]);
+        OsRng.fill_bytes(root.as_mut());
+        Self::new(root)
+    }
+
+    pub fn derive<P: Ungated>(&self, vault_id: &str) -> VaultKey<P> {
+        self.expand(vault_id)
+    }
+
+    /// A key for decrypting vault data, once the release policy allowed it
+    pub fn unwrap<P: KeyPurpose>(&self, _grant: &ReleaseGrant, vault_id: &str) -> VaultKey<P> {
+        self.expand(vault_id)
+    }
+
+    /// Public identifier of any vault key, without releasing the key
+    pub fn key_id<P: KeyPurpose>(&self, vault_id: &str) -> String {
+        self.expand::<P>(vault_id).key_id()
+    }
+
+    fn expand<P: KeyPurpose>(&self, vault_id: &str) -> VaultKey<P> {
+        let mut bytes = Zeroizing::new([0u8; 32]);
+        Hkdf::<Sha256>::new(Some(SALT), self.root.as_ref())
+            .expand_multi_info(
+                &[
+                    b"lumina/v1/",
+                    P::LABEL.as_bytes(),
+                    &(vault_id.len() as u64).to_be_bytes(),
+                    vault_id.as_bytes(),
+                ],
+                bytes.as_mut(),
+            )
+            .expect("32 bytes is a valid HKDF-SHA256 output length");
+
+        VaultKey {
+            bytes,
+            purpose: PhantomData,
+        }
+    }
+
+    /// Identifies the root generation without revealing it
+    pub fn root_key_id(&self) -> String {
+        key_id(self.root.as_ref())
+    }
+}
+
+fn key_id(key: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(b"lumina-key-id");
+    hasher.update(key);
+    hex::encode(&hasher.finalize()[..16])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hierarchy(seed: u8) -> KeyHierarchy {
+        KeyHierarchy::new(Zeroizing::new([seed; 32]))
+    }
+
+    #[test]
+    fn derivation_is_deterministic() {
+        let a = hierarchy(7).expand::<Storage>("vault-1");
+        let b = hierarchy(7).expand::<Storage>("vault-1");
+        assert_eq!(a.as_bytes(), b.as_bytes());
+    }
+
+    #[test]
+    fn purposes_yield_distinct_keys() {
+        let keys = hierarchy(7);
+        let template = keys.expand::<TemplateEncryption>("vault-1");
+        let audit = keys.expand::<AuditSigning>("vault-1");
+        let storage = keys.expand::<Storage>("vault-1");
+
+        assert_ne!(template.as_bytes(), audit.as_bytes());
+        assert_ne!(template.as_bytes(), storage.as_bytes());
+        assert_ne!(audit.as_bytes(), storage.as_bytes());
+    }
+
+    #[test]
+    fn vaults_yield_distinct_keys() {
+        let keys = hierarchy(7);
+        assert_ne!(
+            keys.expand::<Storage>("vault-1").as_bytes(),
+            keys.expand::<Storage>("vault-2").as_bytes()
+        );
+    }
+
+    #[test]
+    fn vault_ids_cannot_alias_another_purpose() {
+        // Without length-prefixing, "storage" + "x" and "storagex" + "" could collide
+        let keys = hierarchy(7);
+        assert_ne!(
+            keys.expand::<Storage>("x").as_bytes(),
+            keys.expand::<Storage>("").as_bytes()
+        );
+        assert_ne!(
+            keys.expand::<AuditSigning>("").as_bytes(),
+            keys.expand::<Storage>("audit-signing").as_bytes()
+        );
+    }
+
+    #[test]
+    fn roots_yield_distinct_keys() {
+        assert_ne!(
+            hierarchy(1).expand::<Storage>("vault-1").as_bytes(),
+            hierarchy(2).expand::<Storage>("vault-1").as_bytes()
+        );
+        assert_ne!(hierarchy(1).root_key_id(), hierarchy(2).root_key_id());
+    }
+}
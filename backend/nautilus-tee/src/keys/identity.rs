@@ -0,0 +1,84 @@
+/**
+ * Enclave Identity
+ * Ed25519 identity key recovered deterministically across restarts
+ *
+ * The identity is HKDF(seed, info = "lumina-identity-v1" || pcr0 || role).
+ * The seed is delivered in the KMS-wrapped secrets bundle, which KMS only
+ * releases to an enclave whose attestation matches the key policy, so the
+ * same measured image in the same role always recovers the same key and
+ * verifiers can pin it. A new image measurement or role yields a new
+ * identity, which is recorded through the rotation log.
+ */
+
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use hkdf::Hkdf;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+const INFO: &[u8] = b"lumina-identity-v1";
+const MIN_SEED_LEN: usize = 32;
+
+pub struct EnclaveIdentity {
+    signing_key: SigningKey,
+    role: String,
+}
+
+impl EnclaveIdentity {
+    pub fn derive(seed: &[u8], measurement: &str, role: &str) -> Result<Self, String> {
+        if seed.len() < MIN_SEED_LEN {
+            return Err(format!(
+                "Identity seed must be at least {} bytes",
+                MIN_SEED_LEN
+            ));
+        }
+
+        let mut secret = Zeroizing::new([0u8; 32]);
+        Hkdf::<Sha256>::new(None, seed)
+            .expand_multi_info(
+                &[
+                    INFO,
+                    &(measurement.len() as u64).to_be_bytes(),
+                    measurement.as_bytes(),
+                    role.as_bytes(),
+                ],
+                secret.as_mut(),
+            )
+            .map_err(|_| "Identity derivation failed".to_string())?;
+
+        Ok(Self {
+            signing_key: SigningKey::from_bytes(&secret),
+            role: role.to_string(),
+        })
+    }
+
+    pub fn from_base64_seed(encoded: &str, measurement: &str, role: &str) -> Result<Self, String> {
+        let seed = Zeroizing::new(crate::crypto::decode(encoded)?);
+        Self::derive(&seed, measurement, role)
+    }
+
+    /// Random identity for enclaves started without a sealed seed
+    pub fn ephemeral(measurement: &str, role: &str) -> Self {
+        let mut seed = Zeroizing::new([0u8; MIN_SEED_LEN]);
+        OsRng.fill_bytes(seed.as_mut());
+        Self::derive(seed.as_ref(), measurement, role).expect("Random seed has the minimum length")
+    }
+
+    pub fn public_key(&self) -> VerifyingKey {
+        self.signing_key.verifying_key()
+    }
+
+    /// Hex sha256 prefix of the public key
+    pub fn key_id(&self) -> String {
+        hex::encode(&Sha256::digest(self.public_key().as_bytes())[..16])
+    }
+
+    pub fn role(&self) -> &str {
+        &self.role
+    }
+
+    pub fn sign(&self, message: &[u8]) -> Signature {
+        self.signing_key.sign(message)
+    }
+}
@@ -0,0 +1,9 @@
+/**
+ * Enclave Key Management
+ * Everything keyed off the enclave root secret
+ */
+
+pub mod derive;
+pub mod identity;
+pub mod release;
+pub mod rotation;
@@ -0,0 +1,116 @@
+/**
+ * Key Release Policy
+ * Vault keys are only unwrapped by an enclave in a trusted state
+ *
+ * Before the key hierarchy hands out a key that decrypts vault data, the
+ * enclave's own measurements, as read from the NSM, are checked against
+ * the configured policy:
+ *
+ * - debug mode: a debug enclave reports all-zero PCRs and its memory is
+ *   readable from the parent, so it is refused unless explicitly allowed
+ *   (development only by default)
+ * - image signer: with signers configured, PCR8 must be one of them, so
+ *   an image built and signed outside the release pipeline can't unwrap
+ *   keys even if it somehow obtained the root
+ * - unreadable PCRs: outside an enclave nothing vouches for the
+ *   measurements, so keys are only released where debug enclaves are
+ *   allowed and no image signer is required
+ *
+ * Passing the check yields a `ReleaseGrant`, which `KeyHierarchy::unwrap`
+ * requires. A refusal is recorded in the vault's audit trail. Audit
+ * signing keys aren't gated, so refusals can always be recorded.
+ */
+
+use serde_json::json;
+use std::sync::Arc;
+use tracing::warn;
+
+use crate::attestation::Measurements;
+use crate::audit::AuditTrail;
+
+#[derive(Clone, Debug)]
+pub struct KeyReleasePolicy {
+    pub allow_debug: bool,
+    /// Hex PCR8 values of trusted image signers; empty accepts any image
+    pub pcr8_signers: Vec<String>,
+}
+
+/// The release policy allowed unwrapping keys for this call
+pub struct ReleaseGrant {
+    _private: (),
+}
+
+pub struct KeyRelease {
+    policy: KeyReleasePolicy,
+    /// PCRs 0-8 are fixed at boot, so they're read once; None when the
+    /// NSM couldn't be read
+    measurements: Option<Measurements>,
+    audit: Arc<AuditTrail>,
+}
+
+impl KeyReleasePolicy {
+    pub fn evaluate(&self, measurements: &Measurements) -> Result<(), String> {
+        if measurements.is_debug() && !self.allow_debug {
+            return Err("Enclave is running in debug mode".to_string());
+        }
+        if !self.pcr8_signers.is_empty() {
+            let signer = measurements
+                .pcr8
+                .as_deref()
+                .ok_or("Enclave image is unsigned")?;
+            if !self.pcr8_signers.iter().any(|s| s.eq_ignore_ascii_case(signer)) {
+                return Err(format!("Enclave image signer {} is not trusted", signer));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl KeyRelease {
+    pub fn new(
+        policy: KeyReleasePolicy,
+        measurements: Option<Measurements>,
+        audit: Arc<AuditTrail>,
+    ) -> Self {
+        let release = Self {
+            policy,
+            measurements,
+            audit,
+        };
+        if let Err(reason) = release.check() {
+            warn!("Vault keys will not be released: {}", reason);
+        }
+        release
+    }
+
+    fn check(&self) -> Result<(), String> {
+        match &self.measurements {
+            Some(measurements) => self.policy.evaluate(measurements),
+            None if self.policy.allow_debug && self.policy.pcr8_signers.is_empty() => Ok(()),
+            None => Err("Enclave PCRs can't be read from the NSM".to_string()),
+        }
+    }
+
+    /// Check the policy before unwrapping a key of `vault_id` for `operation`
+    pub fn grant(&self, vault_id: &str, operation: &str) -> Result<ReleaseGrant, String> {
+        let Err(reason) = self.check() else {
+            return Ok(ReleaseGrant { _private: () });
+        };
+        warn!(
+            "Refused to unwrap {} keys for {}: {}",
+            vault_id, operation, reason
+        );
+        self.audit.record(
+            vault_id,
+            "key_release.denied",
+            "enclave",
+            json!({
+                "operation": operation,
+                "reason": reason,
+                "pcr0": self.measurements.as_ref().map(|m| &m.pcr0),
+                "pcr8": self.measurements.as_ref().and_then(|m| m.pcr8.as_ref()),
+            }),
+        )?;
+        Err(format!("Key release denied: {}", reason))
+    }
+}
@@ -0,0 +1,85 @@
+/**
+ * Key Rotation Log
+ * Persistent record of every change to a long-lived enclave key
+ *
+ * At startup each long-lived key reports its current key id. A key id
+ * that differs from the last one recorded for the same scope becomes a
+ * rotation event, so verifiers that pin a key can see when and why it
+ * changed rather than silently losing trust.
+ */
+
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+use crate::store::Store;
+
+const NAMESPACE: &str = "key_rotations";
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RotationEvent {
+    pub key_id: String,
+    /// `None` for the first key recorded in a scope
+    pub previous_key_id: Option<String>,
+    pub reason: String,
+    pub rotated_at: u64,
+}
+
+pub struct RotationLog {
+    store: Arc<Store>,
+}
+
+impl RotationLog {
+    pub fn new(store: Arc<Store>) -> Self {
+        Self { store }
+    }
+
+    /// Record `key_id` as current for `kind`/`scope`; returns the new event
+    /// when it differs from the last recorded key
+    pub fn observe(
+        &self,
+        kind: &str,
+        scope: &str,
+        key_id: &str,
+        reason: &str,
+    ) -> Result<Option<RotationEvent>, String> {
+        let mut history = self.history(kind, scope)?;
+        let previous = history.last().map(|e| e.key_id.clone());
+        if previous.as_deref() == Some(key_id) {
+            return Ok(None);
+        }
+
+        if let Some(previous) = &previous {
+            warn!(
+                "{} key for {} changed: {} -> {} ({})",
+                kind, scope, previous, key_id, reason
+            );
+        }
+
+        let event = RotationEvent {
+            key_id: key_id.to_string(),
+            previous_key_id: previous,
+            reason: reason.to_string(),
+            rotated_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+        };
+        history.push(event.clone());
+        self.store.put(NAMESPACE, &log_key(kind, scope), &history)?;
+        Ok(Some(event))
+    }
+
+    /// Oldest first
+    pub fn history(&self, kind: &str, scope: &str) -> Result<Vec<RotationEvent>, String> {
+        Ok(self
+            .store
+            .get(NAMESPACE, &log_key(kind, scope))?
+            .unwrap_or_default())
+    }
+}
+
+fn log_key(kind: &str, scope: &str) -> String {
+    format!("{}:{}", kind, scope)
+}
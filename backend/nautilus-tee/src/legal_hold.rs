@@ -0,0 +1,92 @@
+/**
+ * Legal Hold
+ * Freezing a vault while its estate is in dispute
+ *
+ * A vault under legal hold can still be read, searched and audited, but
+ * nothing that changes who gets what, or when, goes through: key and
+ * policy changes, beneficiary edits, template changes and release
+ * triggers are refused until the hold is lifted. Placing or lifting a
+ * hold takes an enclave admin or a current guardian quorum approval for
+ * the "legal_hold" or "legal_hold_lift" operation (see approvals).
+ */
+
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::store::Store;
+
+const NAMESPACE: &str = "legal_holds";
+pub const PLACE_OPERATION: &str = "legal_hold";
+pub const LIFT_OPERATION: &str = "legal_hold_lift";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HoldAuthority {
+    Admin,
+    GuardianQuorum,
+}
+
+/// Latest change to a vault's hold
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LegalHold {
+    pub vault_id: String,
+    pub active: bool,
+    pub reason: String,
+    pub authority: HoldAuthority,
+    pub changed_by: String,
+    pub changed_at: u64,
+}
+
+pub struct LegalHoldService {
+    store: Arc<Store>,
+}
+
+impl LegalHoldService {
+    pub fn new(store: Arc<Store>) -> Self {
+        Self { store }
+    }
+
+    pub fn hold(&self, vault_id: &str) -> Result<Option<LegalHold>, String> {
+        self.store.get(NAMESPACE, vault_id)
+    }
+
+    pub fn is_held(&self, vault_id: &str) -> Result<bool, String> {
+        Ok(self.hold(vault_id)?.is_some_and(|hold| hold.active))
+    }
+
+    /// Place (`active`) or lift the hold; the caller has been authorized
+    pub fn set(
+        &self,
+        vault_id: &str,
+        active: bool,
+        reason: &str,
+        authority: HoldAuthority,
+        caller: &str,
+    ) -> Result<LegalHold, String> {
+        if reason.trim().is_empty() {
+            return Err("A legal hold change needs a reason".to_string());
+        }
+        if self.is_held(vault_id)? == active {
+            return Err(if active {
+                "Vault is already under legal hold".to_string()
+            } else {
+                "Vault is not under legal hold".to_string()
+            });
+        }
+
+        let hold = LegalHold {
+            vault_id: vault_id.to_string(),
+            active,
+            reason: reason.to_string(),
+            authority,
+            changed_by: caller.to_string(),
+            changed_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+        };
+        self.store.put(NAMESPACE, vault_id, &hold)?;
+        Ok(hold)
+    }
+}
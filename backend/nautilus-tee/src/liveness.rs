@@ -1,33 +1,82 @@
 /**
  * Liveness Service
  * Monitors proof-of-life without exposing user data
+ *
+ * Chain activity is read through the chain relay when one is configured.
+ * When it can't be read (the relay is down or its circuit breaker open),
+ * the last activity seen for the address stands in, however old, and the
+ * result lists "chain" under `degraded`.
  */
 
 use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tracing::warn;
+
+use crate::auth::normalize_address;
+use crate::chain;
+use crate::check_in::CheckInService;
+use crate::config::ParentRelay;
+use crate::devices::{DeviceService, DeviceSignals};
+
+/// How long an address's chain activity is reused before it's looked up again
+const CHAIN_CACHE_TTL: u64 = 5 * 60;
+/// How long it's kept to fall back on while the chain can't be read
+const CHAIN_CACHE_RETENTION: u64 = 7 * 24 * 60 * 60;
+
+#[derive(Debug, Error)]
+pub enum LivenessError {
+    /// Check-ins or device heartbeats couldn't be read
+    #[error("Liveness records unavailable: {0}")]
+    Records(String),
+}
 
 #[derive(Serialize)]
 pub struct LivenessResult {
     pub alive: bool,
     pub last_seen: String,
     pub confidence: f64,
+    /// Present when the vault has enrolled heartbeat devices
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub devices: Option<DeviceSignals>,
+    /// Signals served from cache because they couldn't be read, e.g. "chain"
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub degraded: Vec<&'static str>,
 }
 
 pub struct LivenessService {
-    // In real implementation, this would store liveness data in secure enclave memory
-    // For now, we'll use a simple in-memory store
+    // Check-ins are the proof of life the enclave persists itself
+    check_ins: Arc<CheckInService>,
+    // Signed heartbeats from the owner's enrolled devices
+    devices: Arc<DeviceService>,
+    // Last chain activity per address as (fetched_at, last_seen), so a batch
+    // or a busy owner doesn't go back to the chain on every check
+    chain_cache: Mutex<HashMap<String, (u64, u64)>>,
+    // None leaves chain activity on the placeholder
+    chain: Option<ParentRelay>,
 }
 
 impl LivenessService {
-    pub fn new() -> Self {
-        Self
+    pub fn new(
+        check_ins: Arc<CheckInService>,
+        devices: Arc<DeviceService>,
+        chain: Option<ParentRelay>,
+    ) -> Self {
+        Self {
+            check_ins,
+            devices,
+            chain_cache: Mutex::new(HashMap::new()),
+            chain,
+        }
     }
 
     pub async fn check(
         &self,
         vault_id: &str,
         user_address: &str,
-    ) -> Result<LivenessResult, String> {
+    ) -> Result<LivenessResult, LivenessError> {
         // In real implementation, this would:
         // 1. Ping user's device/account (privacy-preserving)
         // 2. Check for recent activity signatures
@@ -46,11 +95,28 @@ impl LivenessService {
             .unwrap()
             .as_secs();
 
-        // In real implementation, last_seen would come from:
-        // - Recent blockchain transactions
-        // - Encrypted heartbeat signals
-        // - Privacy-preserving activity checks
-        let last_seen = now - 3600; // 1 hour ago (placeholder)
+        // A check-in recorded by this enclave counts as much as chain activity
+        let checked_in = self
+            .check_ins
+            .schedule(vault_id)
+            .map_err(LivenessError::Records)?
+            .map(|s| s.last_check_in)
+            .unwrap_or(0);
+        let devices = self
+            .devices
+            .signals(vault_id, now)
+            .map_err(LivenessError::Records)?;
+        let heard = devices
+            .as_ref()
+            .and_then(|d| d.last_heartbeat)
+            .unwrap_or(0);
+        let (on_chain, chain_fresh) = self.chain_activity(user_address, now).await;
+        let last_seen = on_chain.max(checked_in).max(heard);
+        let degraded = if chain_fresh {
+            Vec::new()
+        } else {
+            vec!["chain"]
+        };
 
         let confidence = if last_seen > now - 86400 {
             // Seen within 24 hours
@@ -64,10 +130,46 @@ impl LivenessService {
         };
 
         Ok(LivenessResult {
-            alive: confidence > 0.5,
+            // Too few distinct devices vouching outweighs any other signal
+            alive: confidence > 0.5
+                && devices
+                    .as_ref()
+                    .is_none_or(|d| d.active >= d.required as usize),
             last_seen: last_seen.to_string(),
             confidence,
+            devices,
+            degraded,
         })
     }
-}
 
+    /// Last chain activity, and false when it's a cached value standing in
+    /// for one that couldn't be read
+    async fn chain_activity(&self, user_address: &str, now: u64) -> (u64, bool) {
+        let address = normalize_address(user_address);
+        let cached = self.chain_cache.lock().unwrap().get(&address).copied();
+        if let Some((fetched_at, last_seen)) = cached {
+            if now < fetched_at + CHAIN_CACHE_TTL {
+                return (last_seen, true);
+            }
+        }
+
+        let last_seen = match &self.chain {
+            Some(relay) => match chain::last_transaction_at(relay, &address).await {
+                Ok(last_seen) => last_seen.unwrap_or(0),
+                Err(e) => {
+                    warn!("Chain activity for {} unavailable: {}", address, e);
+                    return (cached.map_or(0, |(_, last_seen)| last_seen), false);
+                }
+            },
+            // In real implementation, last_seen would also come from:
+            // - Encrypted heartbeat signals
+            // - Privacy-preserving activity checks
+            None => now - 3600, // 1 hour ago (placeholder)
+        };
+
+        let mut cache = self.chain_cache.lock().unwrap();
+        cache.retain(|_, (fetched_at, _)| now < *fetched_at + CHAIN_CACHE_RETENTION);
+        cache.insert(address, (now, last_seen));
+        (last_seen, true)
+    }
+}
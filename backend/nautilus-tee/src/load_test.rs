@@ -0,0 +1,107 @@
+/**
+ * Load Testing
+ * Latency and memory figures for sizing enclave CPU and memory
+ *
+ * Only built with the `load-test` feature. POST /debug/load-test runs
+ * synthetic proof or biometric workloads through the same code paths as
+ * real requests, at a chosen concurrency and payload size, and reports
+ * throughput, latency percentiles and the process's peak resident memory.
+ * Peak memory is read from /proc, after resetting it where the kernel
+ * allows, so it reflects the run rather than everything since boot.
+ */
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+pub const MAX_ITERATIONS: usize = 10_000;
+pub const MAX_CONCURRENCY: usize = 256;
+pub const MAX_PAYLOAD_BYTES: usize = 64 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Workload {
+    /// The /zk/generate pipeline, attestation included
+    Proof,
+    /// Feature extraction and matching, as biometric verification runs them
+    Biometric,
+}
+
+#[derive(Serialize)]
+pub struct LatencySummary {
+    pub min_ms: f64,
+    pub mean_ms: f64,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+#[derive(Serialize)]
+pub struct MemoryReport {
+    /// Resident set size before the run
+    pub rss_before_kb: Option<u64>,
+    /// Peak resident set size during the run, or since start if the peak
+    /// couldn't be reset
+    pub peak_rss_kb: Option<u64>,
+    pub peak_reset: bool,
+}
+
+#[derive(Serialize)]
+pub struct LoadTestReport {
+    pub workload: Workload,
+    pub iterations: usize,
+    pub concurrency: usize,
+    pub payload_bytes: usize,
+    pub failures: usize,
+    /// First failure, to tell a broken setup from an overloaded one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_error: Option<String>,
+    pub elapsed_ms: f64,
+    pub throughput_per_sec: f64,
+    /// Successful iterations only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency: Option<LatencySummary>,
+    pub memory: MemoryReport,
+}
+
+impl LatencySummary {
+    pub fn from_samples(mut samples: Vec<Duration>) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        samples.sort();
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        let percentile = |p: f64| {
+            let rank = ((samples.len() as f64 * p).ceil() as usize).clamp(1, samples.len());
+            ms(samples[rank - 1])
+        };
+        let total: Duration = samples.iter().sum();
+        Some(Self {
+            min_ms: ms(samples[0]),
+            mean_ms: ms(total) / samples.len() as f64,
+            p50_ms: percentile(0.50),
+            p95_ms: percentile(0.95),
+            p99_ms: percentile(0.99),
+            max_ms: ms(samples[samples.len() - 1]),
+        })
+    }
+}
+
+/// Reset the kernel's peak RSS for this process; true if it took
+pub fn reset_peak_memory() -> bool {
+    std::fs::write("/proc/self/clear_refs", "5").is_ok()
+}
+
+/// (current RSS, peak RSS) in kB from /proc/self/status
+pub fn memory_usage() -> (Option<u64>, Option<u64>) {
+    let Ok(status) = std::fs::read_to_string("/proc/self/status") else {
+        return (None, None);
+    };
+    let field = |name: &str| {
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix(name))
+            .and_then(|value| value.trim().trim_end_matches("kB").trim().parse().ok())
+    };
+    (field("VmRSS:"), field("VmHWM:"))
+}
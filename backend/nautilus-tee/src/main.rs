@@ -10,26 +10,186 @@
  */
 
 use axum::{
-    extract::State,
+    extract::{DefaultBodyLimit, State},
     http::StatusCode,
+    middleware,
     response::Json,
     routing::{get, post},
-    Router,
+    Extension, Router,
 };
+use base64::Engine;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::sync::Arc;
-use tower_http::cors::CorsLayer;
 use tracing::{info, warn};
 
+mod allocator;
+mod allowlist;
+mod analytics;
+mod approvals;
+mod archive;
 mod attestation;
+mod audit;
+mod auth;
 mod biometric;
+mod blob_stream;
+mod breaker;
+mod bootstrap;
+mod build_info;
+mod bus;
+mod calibration;
+mod capability;
+mod channel;
+mod check_in;
+mod chain;
+mod circuits;
+mod claim_definitions;
+mod clock;
+mod cms;
+mod co_owners;
+mod compliance;
+mod config;
+mod cors;
+mod credentials;
+mod crypto;
+mod ct;
+mod data_export;
+mod dead_letters;
+mod deletion;
+mod devices;
+mod duress;
+mod dkim;
+mod embedding;
+mod erasure;
+mod error;
+mod escalation;
+mod escrow;
+mod events;
+mod exchange;
+mod face;
+mod field;
+mod formats;
+mod groth16;
+mod heartbeat;
+mod integrity;
+mod items;
+mod jurisdictions;
+mod keys;
+mod legal_hold;
 mod liveness;
+#[cfg(feature = "load-test")]
+mod load_test;
+mod manifest;
+mod memory_budget;
+mod migration;
+mod msm;
+mod nitro;
+mod notifications;
+mod outbox;
+mod pagination;
+mod peer;
+mod policy;
+mod policy_bundles;
+mod profiling;
+mod proof_encoding;
+mod proof_jobs;
+mod quality;
+mod randomness;
+mod redaction;
+mod release;
+mod replay;
+mod retry;
+mod routes;
+mod schema;
+mod search;
+mod secrets;
+mod server;
+mod session;
+mod share_grants;
+mod statements;
+mod store;
+mod tasks;
+mod templates;
+mod threshold;
+mod timestamp;
+mod tls;
+mod validation;
+mod vault_index;
+mod vault_state;
+mod vault_templates;
+mod voice;
+mod webhooks;
 mod zk_proof;
+mod zkey;
 
-use attestation::AttestationService;
+use allowlist::MeasurementAllowlist;
+use analytics::{AnalyticsService, PrivacyParams};
+use approvals::ApprovalService;
+use archive::ArchiveService;
+use attestation::{AttestationService, Freshness};
+use audit::AuditTrail;
 use biometric::BiometricService;
+use bus::{BusEvent, EventBus};
+use calibration::CalibrationService;
+use capability::CapabilityService;
+use channel::ChannelService;
+use check_in::CheckInService;
+use claim_definitions::ClaimDefinition;
+use clock::HardenedClock;
+use co_owners::CoOwnerService;
+use compliance::ComplianceService;
+use config::{Config, Environment, Transport};
+use data_export::DataExportService;
+use dead_letters::DeadLetterQueue;
+use deletion::DeletionService;
+use devices::DeviceService;
+use duress::{DuressService, DuressTrigger};
+use embedding::EmbeddingSubmission;
+use erasure::ErasureService;
+use error::AppError;
+use escalation::EscalationService;
+use escrow::EscrowService;
+use events::EventLog;
+use exchange::{canonical_sha256, HashedJson};
+use face::FaceModel;
+use heartbeat::HeartbeatService;
+use integrity::IntegrityService;
+use items::ItemPolicyService;
+use jurisdictions::JurisdictionService;
+use keys::derive::KeyHierarchy;
+use keys::identity::EnclaveIdentity;
+use keys::release::{KeyRelease, KeyReleasePolicy};
+use keys::rotation::RotationLog;
+use legal_hold::LegalHoldService;
 use liveness::LivenessService;
-use zk_proof::ZKProofService;
+use memory_budget::{MemoryBudget, MemoryLimits, Refusal};
+use migration::{MigrationParty, MigrationService};
+use notifications::NotificationService;
+use outbox::ChainOutbox;
+use policy::PolicyEngine;
+use policy_bundles::PolicyBundles;
+use proof_encoding::ProofFormat;
+use proof_jobs::{Flight, Job, Priority, ProofJobs, QueueLimits, Stage};
+use randomness::RandomnessSource;
+use redaction::RedactionService;
+use release::ReleaseService;
+use replay::ReplayGuard;
+use schema::SchemaRegistry;
+use secrets::SecretsService;
+use session::SessionService;
+use share_grants::ShareGrantService;
+use store::Store;
+use tasks::TaskScheduler;
+use templates::{LoadError, LoadedTemplate, TemplateService};
+use threshold::ThresholdService;
+use timestamp::TimestampService;
+use tls::TlsReloader;
+use validation::{BiometricMethod, ClaimType, SuiAddress, VaultId};
+use vault_index::VaultIndexService;
+use vault_templates::VaultTemplateService;
+use voice::VoiceGuard;
+use webhooks::WebhookService;
+use zk_proof::{ProofPayload, ZKProofService};
 
 #[derive(Clone)]
 struct AppState {
@@ -37,49 +197,177 @@ struct AppState {
     biometric: Arc<BiometricService>,
     liveness: Arc<LivenessService>,
     zk_proof: Arc<ZKProofService>,
+    policy: Arc<PolicyEngine>,
+    policy_bundles: Arc<PolicyBundles>,
+    secrets: Arc<SecretsService>,
+    threshold: Arc<ThresholdService>,
+    approvals: Arc<ApprovalService>,
+    items: Arc<ItemPolicyService>,
+    release: Arc<ReleaseService>,
+    redaction: Arc<RedactionService>,
+    integrity: Arc<IntegrityService>,
+    capabilities: Arc<CapabilityService>,
+    share_grants: Arc<ShareGrantService>,
+    co_owners: Arc<CoOwnerService>,
+    vault_templates: Arc<VaultTemplateService>,
+    jurisdictions: Arc<JurisdictionService>,
+    vault_index: Arc<VaultIndexService>,
+    legal_holds: Arc<LegalHoldService>,
+    audit: Arc<AuditTrail>,
+    migrations: Arc<MigrationService>,
+    deletions: Arc<DeletionService>,
+    data_exports: Arc<DataExportService>,
+    erasures: Arc<ErasureService>,
+    escrow: Arc<EscrowService>,
+    keys: Arc<KeyHierarchy>,
+    identity: Arc<EnclaveIdentity>,
+    rotations: Arc<RotationLog>,
+    compliance: Arc<ComplianceService>,
+    sessions: Arc<SessionService>,
+    templates: Arc<TemplateService>,
+    calibration: Arc<CalibrationService>,
+    voice: Arc<VoiceGuard>,
+    bus: Arc<EventBus>,
+    events: Arc<EventLog>,
+    webhooks: Arc<WebhookService>,
+    dead_letters: Arc<DeadLetterQueue>,
+    notifications: Arc<NotificationService>,
+    outbox: Arc<ChainOutbox>,
+    archive: Arc<ArchiveService>,
+    tasks: Arc<TaskScheduler>,
+    check_ins: Arc<CheckInService>,
+    escalation: Arc<EscalationService>,
+    heartbeats: Arc<HeartbeatService>,
+    devices: Arc<DeviceService>,
+    duress: Arc<DuressService>,
+    randomness: Arc<RandomnessSource>,
+    analytics: Arc<AnalyticsService>,
+    clock: Arc<HardenedClock>,
+    timestamps: Arc<TimestampService>,
+    channels: Arc<ChannelService>,
+    allowlist: Arc<MeasurementAllowlist>,
+    proof_jobs: Arc<ProofJobs>,
+    memory: Arc<MemoryBudget>,
+    schemas: Arc<SchemaRegistry>,
 }
 
 #[derive(Deserialize)]
 struct BiometricVerifyRequest {
-    vault_id: String,
-    biometric_data: String, // Base64 encoded
-    method: String, // fingerprint, face, voice
+    vault_id: VaultId,
+    biometric_data: Option<String>, // Base64 encoded
+    method: BiometricMethod,
+    /// Privacy mode: a client-side embedding instead of biometric_data
+    embedding: Option<EmbeddingSubmission>,
+    /// Voice only: the phrase challenge the recording answers
+    challenge_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct VoiceChallengeRequest {
+    vault_id: VaultId,
 }
 
 #[derive(Serialize)]
 struct BiometricVerifyResponse {
-    verified: bool,
+    #[serde(flatten)]
+    verdict: BiometricVerdict,
+    /// Bound to the request and the verdict (see exchange)
     attestation: attestation::Attestation,
+}
+
+#[derive(Serialize)]
+struct BiometricVerdict {
+    verified: bool,
     confidence: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    quality: Option<quality::QualityReport>, // Raw samples only
+    /// Best-matching template, when matched by embedding
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    template: Option<TemplateMatch>,
+    /// Voice only; a failed anti-spoof check fails verification
+    #[serde(skip_serializing_if = "Option::is_none")]
+    anti_spoof: Option<voice::AntiSpoofReport>,
+}
+
+#[derive(Serialize)]
+struct TemplateMatch {
+    template_id: String,
+    /// Refresh deadline, when the vault has a refresh policy
+    #[serde(skip_serializing_if = "Option::is_none")]
+    template_expires_at: Option<u64>,
+    /// Matched past its deadline, with confidence capped
+    template_stale: bool,
 }
 
 #[derive(Deserialize)]
 struct LivenessCheckRequest {
-    vault_id: String,
-    user_address: String,
+    vault_id: VaultId,
+    user_address: SuiAddress,
 }
 
 #[derive(Serialize)]
 struct LivenessCheckResponse {
+    #[serde(flatten)]
+    verdict: LivenessVerdict,
+    /// Bound to the request and the verdict (see exchange)
+    attestation: Option<attestation::Attestation>,
+}
+
+#[derive(Serialize)]
+struct LivenessVerdict {
     alive: bool,
     last_seen: String,
     confidence: f64,
-    attestation: Option<attestation::Attestation>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    devices: Option<devices::DeviceSignals>,
 }
 
 #[derive(Deserialize)]
 struct ZKProofRequest {
-    vault_id: String,
-    claim_type: String,
+    vault_id: VaultId,
+    claim_type: ClaimType,
     claim_value: serde_json::Value,
     encrypted_data: String, // Base64 encoded encrypted blob
+    /// Pins a circuit version; the current one when omitted
+    #[serde(default)]
+    circuit_version: Option<u32>,
+    /// `compact` for the on-chain encoding; snarkjs JSON by default
+    #[serde(default)]
+    format: ProofFormat,
+    /// Seed the prover from the request so it proves to the same bytes
+    /// every time; development only, for snapshot tests
+    #[serde(default)]
+    deterministic: bool,
+    /// `critical` when an unlock waits on the proof
+    #[serde(default)]
+    priority: Priority,
 }
 
 #[derive(Serialize)]
 struct ZKProofResponse {
+    #[serde(flatten)]
+    claim: ProvenClaim,
+    /// Bound to the request and the proof (see exchange)
+    attestation: attestation::Attestation,
+}
+
+#[derive(Serialize)]
+struct ProvenClaim {
+    /// snarkjs JSON, or the compact encoding's proof points and inputs
     proof: serde_json::Value,
+    format: ProofFormat,
     public_signals: Vec<String>,
-    attestation: attestation::Attestation,
+    claim_type: String,
+    circuit_version: u32,
+    /// Hex SHA-256 of the proving key; None only in development
+    zkey_sha256: Option<String>,
+    /// Seeded from the request, so its inputs reproduce it; tests only
+    deterministic: bool,
+}
+
+#[derive(Serialize)]
+struct CircuitsResponse {
+    circuits: Vec<circuits::CircuitVersion>,
 }
 
 #[tokio::main]
@@ -89,38 +377,609 @@ async fn main() {
 
     info!("Starting Nautilus TEE Server");
 
+    let mut config = Config::from_env().expect("Invalid server configuration");
+    info!("Environment: {:?}", config.environment);
+    retry::configure(&config.retry_policies).expect("Invalid retry policy configuration");
+    breaker::configure(&config.circuit_breakers).expect("Invalid circuit breaker configuration");
+
+    let store = Arc::new(
+        Store::open(&config.data_dir)
+            .expect("Failed to open enclave store")
+            .with_compression(&config.store_compressed),
+    );
+    let replay = Arc::new(
+        ReplayGuard::new(store.clone(), config.signature_window_secs)
+            .expect("Failed to load nonce cache"),
+    );
+
+    // Loaded before attestation so every document pins the model hash
+    let face_model = config.face_model.as_ref().map(|face| {
+        let model = FaceModel::load(face).expect("Failed to load face model");
+        info!(
+            "Face model {} {} loaded (sha256 {})",
+            model.model().name,
+            model.model().version,
+            model.hash()
+        );
+        model
+    });
+
     // Initialize services
-    let attestation = Arc::new(AttestationService::new());
-    let biometric = Arc::new(BiometricService::new());
-    let liveness = Arc::new(LivenessService::new());
-    let zk_proof = Arc::new(ZKProofService::new());
+    let randomness = Arc::new(RandomnessSource::new());
+    let mut attestation = AttestationService::new()
+        .with_freshness(Freshness::new(
+            config.attestation_ttl,
+            &config.attestation_max_age,
+        ))
+        .with_placeholder_signatures(config.environment == Environment::Development);
+    if let Some(model) = &face_model {
+        attestation = attestation.with_face_model(model.hash());
+    }
+    if let Some(root) = &config.nitro_root_sha256 {
+        let root = hex::decode(root)
+            .ok()
+            .and_then(|root| root.try_into().ok())
+            .expect("TEE_NITRO_ROOT_SHA256 must be a hex SHA-256");
+        attestation = attestation.with_nitro_root(root);
+    }
+    let attestation = Arc::new(attestation);
+
+    // Secrets are only released to this enclave after it attests
+    if let Some(source) = &config.bootstrap {
+        config.secrets = bootstrap::fetch_secrets(source, &attestation)
+            .await
+            .expect("Secrets bootstrap failed");
+    }
+    let keys = Arc::new(match &config.secrets.root_key {
+        Some(root) => KeyHierarchy::from_base64(root).expect("Invalid root key"),
+        None => {
+            warn!("No root key delivered; per-vault keys will not survive a restart");
+            KeyHierarchy::ephemeral()
+        }
+    });
+
+    // Same image and role recover the same identity from the sealed seed
+    let measurement = attestation
+        .get_pcr_measurements()
+        .expect("Failed to read PCR measurements")
+        .pcr0;
+    let identity = Arc::new(match &config.secrets.identity_seed {
+        Some(seed) => EnclaveIdentity::from_base64_seed(seed, &measurement, &config.role)
+            .expect("Invalid identity seed"),
+        None => {
+            warn!("No identity seed delivered; enclave identity will change on restart");
+            EnclaveIdentity::ephemeral(&measurement, &config.role)
+        }
+    });
+    info!("Enclave identity {} (role {})", identity.key_id(), identity.role());
+
+    let allowlist = Arc::new(
+        MeasurementAllowlist::new(
+            store.clone(),
+            config.allowlist_key.as_deref(),
+            config.peer_measurements.clone(),
+            measurement.clone(),
+        )
+        .expect("Invalid measurement allowlist configuration"),
+    );
+    let channels = Arc::new(ChannelService::new(
+        attestation.clone(),
+        identity.clone(),
+        allowlist.clone(),
+    ));
+    let clock = Arc::new(HardenedClock::new());
+    let timestamps = Arc::new(TimestampService::new(store.clone(), identity.clone()));
+    let rotations = Arc::new(RotationLog::new(store.clone()));
+    rotations
+        .observe("identity", &config.role, &identity.key_id(), "startup derivation")
+        .expect("Failed to record identity key");
+    rotations
+        .observe("root", "enclave", &keys.root_key_id(), "startup")
+        .expect("Failed to record root key");
+
+    let biometric = Arc::new(BiometricService::new(face_model));
+    let bus = Arc::new(EventBus::new(store.clone()).expect("Failed to open event bus"));
+    let check_ins = Arc::new(CheckInService::new(store.clone(), bus.clone()));
+    let devices = Arc::new(DeviceService::new(store.clone()));
+    let liveness = Arc::new(LivenessService::new(
+        check_ins.clone(),
+        devices.clone(),
+        config.chain_relay.clone(),
+    ));
+    let circuits = circuits::CircuitRegistry::new(
+        &config.circuit_support,
+        config.circuit_artifacts.as_ref(),
+        config.environment,
+    )
+    .expect("Failed to load circuit registry");
+    let schemas = Arc::new(
+        SchemaRegistry::new(&config.deprecated_routes).expect("Invalid TEE_DEPRECATED_ROUTES"),
+    );
+    let msm = msm::Msm::select(&config.msm_backend).expect("Failed to select MSM backend");
+    info!("Proving MSMs on the {} backend", msm);
+    let zk_proof = Arc::new(ZKProofService::new(
+        circuits,
+        msm,
+        config.environment == Environment::Development,
+    ));
+    let policy = Arc::new(PolicyEngine::new(
+        config.admins.clone(),
+        config.signing_purposes.clone(),
+    ));
+    let policy_bundles = Arc::new(
+        PolicyBundles::new(store.clone(), config.policy_bundle_key.as_deref())
+            .expect("Invalid policy bundle configuration"),
+    );
+    let secrets = Arc::new(SecretsService::new(store.clone()));
+    let approvals = Arc::new(ApprovalService::new(store.clone()));
+    let redaction = Arc::new(RedactionService::new(store.clone()));
+    let items = Arc::new(ItemPolicyService::new(store.clone(), redaction.clone()));
+    let release = Arc::new(ReleaseService::new(store.clone()));
+    let integrity = Arc::new(IntegrityService::new(store.clone()));
+    let threshold = Arc::new(ThresholdService::new(store.clone(), integrity.clone()));
+    let capabilities = Arc::new(CapabilityService::new(identity.clone(), store.clone()));
+    let share_grants = Arc::new(ShareGrantService::new(
+        identity.clone(),
+        store.clone(),
+        capabilities.clone(),
+    ));
+    let co_owners = Arc::new(CoOwnerService::new(store.clone()));
+    let vault_templates = Arc::new(
+        VaultTemplateService::new(store.clone()).expect("Failed to compile vault template schemas"),
+    );
+    let jurisdictions = Arc::new(
+        JurisdictionService::new(store.clone(), &config.rule_packs).expect("Invalid TEE_RULE_PACKS"),
+    );
+    let legal_holds = Arc::new(LegalHoldService::new(store.clone()));
+    let audit = Arc::new(AuditTrail::new(store.clone(), keys.clone()));
+    let compliance = Arc::new(ComplianceService::new(
+        &config,
+        identity.clone(),
+        rotations.clone(),
+        audit.clone(),
+    ));
+    let key_release = Arc::new(KeyRelease::new(
+        KeyReleasePolicy {
+            allow_debug: config.key_release_allow_debug,
+            pcr8_signers: config.key_release_pcr8_signers.clone(),
+        },
+        attestation.nsm_measurements(),
+        audit.clone(),
+    ));
+    let vault_index = Arc::new(VaultIndexService::new(
+        store.clone(),
+        keys.clone(),
+        key_release.clone(),
+    ));
+    let sessions = Arc::new(SessionService::new(
+        config.session_ttl,
+        randomness.clone(),
+    ));
+    let backup_key = config.secrets.template_backup_key.as_deref().map(|encoded| {
+        let bytes: [u8; 32] = crypto::decode(encoded)
+            .ok()
+            .and_then(|b| b.try_into().ok())
+            .expect("Template backup key must be 32 base64-encoded bytes");
+        x25519_dalek::StaticSecret::from(bytes)
+    });
+    let mut import_measurements = config.template_import_measurements.clone();
+    if import_measurements.is_empty() {
+        import_measurements.push(measurement.clone());
+    }
+    let templates = Arc::new(TemplateService::new(
+        store.clone(),
+        keys.clone(),
+        key_release.clone(),
+        backup_key,
+        import_measurements,
+    ));
+    let mut migration_measurements = config.migration_measurements.clone();
+    if migration_measurements.is_empty() {
+        migration_measurements.push(measurement.clone());
+    }
+    let migrations = Arc::new(MigrationService::new(
+        store.clone(),
+        keys.clone(),
+        templates.clone(),
+        MigrationParty {
+            measurement: measurement.clone(),
+            identity: identity.key_id(),
+        },
+        migration_measurements,
+    ));
+    let events = Arc::new(
+        EventLog::new(
+            store.clone(),
+            identity.clone(),
+            config.secrets.webhook_signing_secret.as_deref(),
+        )
+        .expect("Failed to open event log"),
+    );
+    // Vault events reach webhooks and the drain through the event log
+    bus.consume("events", {
+        let events = events.clone();
+        move |event| match event.vault_id() {
+            Some(vault_id) => events.emit(event.kind(), vault_id, event.data()).map(|_| ()),
+            None => Ok(()),
+        }
+    });
+    bus.consume("audit", {
+        let audit = audit.clone();
+        move |event| {
+            let BusEvent::CheckedIn(check_in) = event else {
+                return Ok(());
+            };
+            let mut details = serde_json::json!({
+                "expires_at": check_in.expires_at,
+                "snoozed": check_in.snoozed,
+                "source": check_in.source,
+            });
+            if let Some(heartbeat) = &check_in.heartbeat {
+                details["tx_digest"] = serde_json::json!(heartbeat.tx_digest);
+                details["event_seq"] = serde_json::json!(heartbeat.event_seq);
+            }
+            audit
+                .record(&check_in.vault_id, "check_in", &check_in.checked_in_by, details)
+                .map(|_| ())
+        }
+    });
+    let dead_letters = Arc::new(DeadLetterQueue::new(
+        store.clone(),
+        config.dead_letter_alert,
+    ));
+    let notifications = Arc::new(NotificationService::new(
+        store.clone(),
+        identity.clone(),
+        config.notification_relay.clone(),
+        dead_letters.clone(),
+    ));
+    let tasks = Arc::new(
+        TaskScheduler::new(store.clone(), &config.task_schedules, &config.task_jitter)
+            .expect("Invalid task schedule configuration"),
+    );
+    dead_letters
+        .clone()
+        .schedule_watch(&tasks)
+        .expect("Failed to schedule dead-letter watch");
+    let webhooks = Arc::new(WebhookService::new(store.clone(), events.clone()));
+    webhooks
+        .clone()
+        .schedule_fanout(&tasks)
+        .expect("Failed to schedule webhook fan-out");
+    notifications
+        .clone()
+        .schedule_dispatch(&tasks, config.notification_dispatch)
+        .expect("Failed to schedule notification dispatch");
+    templates
+        .clone()
+        .schedule_refresh_sweep(
+            &tasks,
+            bus.clone(),
+            notifications.clone(),
+            config.template_refresh_sweep,
+        )
+        .expect("Failed to schedule template refresh sweep");
+    check_ins
+        .clone()
+        .schedule_reminder_sweep(&tasks, notifications.clone(), config.check_in_sweep)
+        .expect("Failed to schedule check-in reminders");
+    let duress = Arc::new(DuressService::new(store.clone(), notifications.clone()));
+    let heartbeats = Arc::new(HeartbeatService::new(store.clone(), check_ins.clone()));
+    if let Some(heartbeat) = config.heartbeat.clone() {
+        info!(
+            "Polling {} for heartbeats every {:?}",
+            heartbeat.event_type, heartbeat.poll
+        );
+        heartbeats
+            .clone()
+            .schedule_poller(&tasks, heartbeat)
+            .expect("Failed to schedule heartbeat poller");
+    }
+    let outbox = Arc::new(ChainOutbox::new(
+        store.clone(),
+        identity.clone(),
+        config.outbox.clone(),
+        dead_letters.clone(),
+    ));
+    outbox
+        .clone()
+        .schedule(&tasks)
+        .expect("Failed to schedule chain outbox");
+    let archive = Arc::new(ArchiveService::new(
+        store.clone(),
+        keys.clone(),
+        key_release.clone(),
+        audit.clone(),
+        notifications.clone(),
+        outbox.clone(),
+        config.archive.clone(),
+    ));
+    archive
+        .clone()
+        .schedule(&tasks)
+        .expect("Failed to schedule archival");
+    let escalation = Arc::new(EscalationService::new(
+        store.clone(),
+        check_ins.clone(),
+        approvals.clone(),
+        outbox.clone(),
+    ));
+    escalation
+        .clone()
+        .schedule_sweep(
+            &tasks,
+            policy.clone(),
+            bus.clone(),
+            notifications.clone(),
+            config.check_in_sweep,
+        )
+        .expect("Failed to schedule escalation sweep");
+    tasks.clone().spawn(config.task_tick);
+    let voice = Arc::new(VoiceGuard::new(store.clone(), randomness.clone()));
+    let analytics = Arc::new(AnalyticsService::new(
+        check_ins.clone(),
+        randomness.clone(),
+        PrivacyParams {
+            epsilon: config.analytics_epsilon,
+            min_count: config.analytics_min_count,
+        },
+    ));
+    analytics.clone().follow(&bus);
+    let calibration = Arc::new(CalibrationService::new(
+        store.clone(),
+        config.environment == Environment::Development,
+    ));
+    let escrow = Arc::new(EscrowService::new(
+        store.clone(),
+        config.escrow.clone(),
+        config.admins.clone(),
+        config.secrets.clone(),
+    ));
+    let deletions = Arc::new(DeletionService::new(
+        store.clone(),
+        keys.clone(),
+        secrets.clone(),
+        threshold.clone(),
+        voice.clone(),
+        templates.clone(),
+        capabilities.clone(),
+        share_grants.clone(),
+    ));
+    let data_exports = Arc::new(DataExportService::new(
+        store.clone(),
+        templates.clone(),
+        audit.clone(),
+    ));
+    let erasures = Arc::new(ErasureService::new(
+        store.clone(),
+        deletions.clone(),
+        audit.clone(),
+        notifications.clone(),
+        config.erasure_grace,
+    ));
 
     let state = AppState {
         attestation,
         biometric,
         liveness,
         zk_proof,
+        policy,
+        policy_bundles,
+        secrets,
+        threshold,
+        approvals,
+        items,
+        release,
+        redaction,
+        integrity,
+        capabilities: capabilities.clone(),
+        share_grants,
+        co_owners,
+        vault_templates,
+        jurisdictions,
+        vault_index,
+        legal_holds,
+        audit,
+        migrations,
+        deletions,
+        data_exports,
+        erasures,
+        escrow,
+        keys,
+        identity,
+        rotations,
+        compliance,
+        sessions: sessions.clone(),
+        templates,
+        calibration,
+        voice,
+        bus,
+        events,
+        webhooks,
+        dead_letters,
+        notifications,
+        outbox,
+        archive,
+        tasks,
+        check_ins,
+        escalation,
+        heartbeats,
+        devices,
+        duress,
+        randomness,
+        analytics,
+        clock,
+        timestamps,
+        channels,
+        allowlist,
+        proof_jobs: Arc::new(ProofJobs::new(QueueLimits {
+            workers: config.proof_workers,
+            capacity: config.proof_queue_capacity,
+            watermark: config.proof_queue_watermark,
+        })),
+        memory: Arc::new(MemoryBudget::new(
+            MemoryLimits {
+                global_bytes: config.memory_budget_bytes,
+                per_request_bytes: config.request_memory_bytes,
+                wait: config.memory_wait,
+                proof_witness_bytes: config.proof_witness_bytes,
+            },
+            store.clone(),
+        )),
+        schemas: schemas.clone(),
     };
 
+    // Routes carrying vault blobs take bodies past axum's 2 MB default
+    let payload_limit = DefaultBodyLimit::max(config.max_payload_bytes);
+
+    // Routes that must be signed by the key behind user_address
+    let signed = Router::new()
+        .route("/liveness/check", post(liveness_check))
+        .merge(routes::guardians::signed_routes())
+        .merge(routes::threshold::signed_routes())
+        .merge(routes::approvals::signed_routes())
+        .merge(routes::session::signed_routes())
+        .merge(routes::escrow::signed_routes())
+        .merge(routes::signing::signed_routes())
+        .merge(routes::templates::signed_routes())
+        .merge(routes::calibration::signed_routes())
+        .merge(routes::events::signed_routes())
+        .merge(routes::items::signed_routes())
+        .merge(routes::release::signed_routes())
+        .merge(routes::redaction::signed_routes())
+        .merge(routes::capabilities::signed_routes())
+        .merge(routes::share_grants::signed_routes())
+        .merge(routes::co_owners::signed_routes())
+        .merge(routes::vault_templates::signed_routes())
+        .merge(routes::jurisdictions::signed_routes())
+        .merge(routes::vault_index::signed_routes())
+        .merge(routes::legal_hold::signed_routes())
+        .merge(routes::audit::signed_routes())
+        .merge(routes::migration::signed_routes())
+        .merge(routes::deletion::signed_routes())
+        .merge(routes::data_export::signed_routes())
+        .merge(routes::erasure::signed_routes())
+        .merge(routes::notifications::signed_routes())
+        .merge(routes::check_in::signed_routes())
+        .merge(routes::escalation::signed_routes())
+        .merge(routes::liveness::signed_routes())
+        .merge(routes::devices::signed_routes())
+        .merge(routes::duress::signed_routes())
+        .merge(routes::measurements::signed_routes())
+        .merge(routes::policy_bundles::signed_routes())
+        .merge(routes::memory::signed_routes())
+        .merge(routes::analytics::signed_routes())
+        .merge(routes::compliance::signed_routes())
+        .merge(routes::profiling::signed_routes())
+        .merge(routes::tasks::signed_routes())
+        .merge(routes::outbox::signed_routes())
+        .merge(routes::retries::signed_routes())
+        .merge(routes::dead_letters::signed_routes())
+        .merge(routes::webhooks::signed_routes())
+        .merge(routes::archive::signed_routes());
+    // Synthetic workloads for capacity planning; absent from release builds
+    #[cfg(feature = "load-test")]
+    let signed = signed.merge(routes::load_test::signed_routes());
+    // Signed routes carrying vault blobs, the only ones whose signature
+    // check buffers more than MAX_SIGNED_BODY_BYTES
+    let signed_payloads = Router::new()
+        .merge(routes::content::signed_routes())
+        .merge(routes::integrity::signed_routes())
+        .layer(payload_limit);
+    let require_signature = |router: Router<AppState>, max_body_bytes| {
+        // Inside the signature check, which provides the signer
+        router
+            .route_layer(middleware::from_fn_with_state(
+                state.clone(),
+                routes::policy_bundles::authorize_request,
+            ))
+            .route_layer(middleware::from_fn_with_state(
+                auth::AuthState {
+                    replay: replay.clone(),
+                    capabilities: capabilities.clone(),
+                    max_body_bytes,
+                },
+                auth::require_signature,
+            ))
+    };
+    let signed = require_signature(signed, auth::MAX_SIGNED_BODY_BYTES)
+        .merge(require_signature(signed_payloads, config.max_payload_bytes));
+
+    // Routes other enclaves call, authenticated by their attestation
+    let peers = routes::peer::peer_routes().route_layer(middleware::from_fn_with_state(
+        peer::PeerAuthState {
+            attestation: state.attestation.clone(),
+            replay,
+            allowlist: state.allowlist.clone(),
+        },
+        peer::require_peer_attestation,
+    ));
+
     // Build router
-    let app = Router::new()
+    let mut app = Router::new()
         .route("/health", get(health))
+        .merge(routes::health::routes())
         .route("/biometric/verify", post(biometric_verify))
-        .route("/liveness/check", post(liveness_check))
-        .route("/zk/generate", post(zk_generate))
-        .layer(CorsLayer::permissive())
-        .with_state(state);
-
-    // Listen on port 8080 (or VSOCK for Nitro Enclave)
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:8080")
-        .await
-        .expect("Failed to bind to port 8080");
+        .route("/biometric/voice/challenge", post(voice_challenge))
+        .route("/zk/generate", post(zk_generate).layer(payload_limit))
+        .route("/zk/circuits", get(zk_circuits))
+        .merge(routes::guardians::routes())
+        .merge(routes::approvals::routes())
+        .merge(routes::keys::routes())
+        .merge(routes::identity::routes())
+        .merge(routes::threshold::routes())
+        .merge(routes::proof_jobs::routes().layer(payload_limit))
+        .merge(routes::proof_verification::routes())
+        .merge(routes::verification_keys::routes())
+        .merge(routes::release::routes())
+        .merge(routes::redaction::routes())
+        .merge(routes::integrity::routes())
+        .merge(routes::legal_hold::routes())
+        .merge(routes::migration::routes())
+        .merge(routes::deletion::routes())
+        .merge(routes::erasure::routes())
+        .merge(routes::check_in::routes())
+        .merge(routes::vault_state::routes())
+        .merge(routes::vault_templates::routes())
+        .merge(routes::jurisdictions::routes())
+        .merge(routes::devices::routes())
+        .merge(routes::random::routes())
+        .merge(routes::timestamp::routes())
+        .merge(routes::version::routes())
+        .merge(routes::measurements::routes())
+        .merge(routes::policy_bundles::routes())
+        .merge(routes::memory::routes())
+        .merge(routes::schema::routes())
+        .merge(signed)
+        .merge(peers)
+        .layer(middleware::from_fn_with_state(schemas, schema::negotiate))
+        .with_state(state)
+        // Outside the signature check, which must see decrypted bodies
+        .layer(middleware::from_fn_with_state(
+            sessions,
+            session::encrypted_session,
+        ));
 
-    info!("Nautilus TEE Server listening on port 8080");
+    // No CORS layer means browsers only get same-origin access
+    if let Some(cors) = &config.cors {
+        app = app.layer(cors::cors_layer(cors).expect("Invalid CORS configuration"));
+    }
 
-    axum::serve(listener, app)
-        .await
-        .expect("Server failed to start");
+    // Listen on TCP (optionally TLS) or VSOCK for Nitro Enclave
+    match config.transport {
+        Transport::Tcp(addr) => {
+            let tls = config.tls.map(|tls| {
+                let reloader = Arc::new(TlsReloader::new(tls).expect("Failed to load TLS config"));
+                reloader.clone().spawn_reload();
+                reloader
+            });
+            if tls.is_none() {
+                warn!("TLS is not configured; serving plaintext HTTP in development");
+            }
+            server::serve_tcp(addr, app, tls, config.http).await
+        }
+        Transport::Vsock { port } => server::serve_vsock(port, app, config.http).await,
+    }
 }
 
 async fn health() -> StatusCode {
@@ -129,98 +988,444 @@ async fn health() -> StatusCode {
 
 async fn biometric_verify(
     State(state): State<AppState>,
-    Json(request): Json<BiometricVerifyRequest>,
-) -> Result<Json<BiometricVerifyResponse>, StatusCode> {
+    HashedJson {
+        value: request,
+        sha256: request_sha256,
+    }: HashedJson<BiometricVerifyRequest>,
+) -> Result<Json<BiometricVerifyResponse>, AppError> {
     info!("Biometric verification request: vault_id={}", request.vault_id);
 
-        // Decode biometric data
-        let biometric_bytes = base64::engine::general_purpose::STANDARD
-            .decode(&request.biometric_data)
-            .map_err(|_| StatusCode::BAD_REQUEST)?;
+    let mut anti_spoof = None;
+    let (result, quality, template) = match (&request.biometric_data, &request.embedding) {
+        (Some(biometric_data), None) => {
+            // Decode biometric data
+            let biometric_bytes = base64::engine::general_purpose::STANDARD
+                .decode(biometric_data)
+                .map_err(|_| AppError::bad_request("INVALID_BIOMETRIC_DATA", "Biometric data must be base64"))?;
 
-    // Process biometric in enclave (privacy-preserving)
-    let result = state
-        .biometric
-        .verify(&biometric_bytes, &request.method)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            // Reject samples too poor to match before they produce a score
+            let quality = quality::require_quality(&biometric_bytes, request.method.as_str())?;
+
+            // A recording of the owner's voice must not pass
+            if request.method == BiometricMethod::Voice {
+                anti_spoof = Some(state.voice.check(
+                    &request.vault_id,
+                    request.challenge_id.as_deref(),
+                    &biometric_bytes,
+                )?);
+            }
 
-    // Generate attestation
+            // Process biometric in enclave (privacy-preserving)
+            let probe = state
+                .biometric
+                .embed(&biometric_bytes, request.method.as_str())?;
+            match probe {
+                Some(probe) => {
+                    let (result, template) = match_templates(&state, &request, &probe)?;
+                    (result, Some(quality), Some(template))
+                }
+                None => {
+                    let result = state
+                        .biometric
+                        .verify(&biometric_bytes, request.method.as_str())
+                        .await?;
+                    (result, Some(quality), None)
+                }
+            }
+        }
+        (None, Some(_)) if request.method == BiometricMethod::Voice => {
+            return Err(AppError::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "ANTI_SPOOF_UNAVAILABLE",
+                "Voice verification needs the recording for replay detection",
+            ))
+        }
+        (None, Some(submission)) => {
+            let probe = submission
+                .decode()
+                .map_err(|e| AppError::bad_request("INVALID_EMBEDDING", e))?;
+            let (result, template) = match_templates(&state, &request, &probe)?;
+            (result, None, Some(template))
+        }
+        _ => {
+            return Err(AppError::bad_request(
+                "INVALID_VERIFICATION",
+                "Provide exactly one of biometric_data or embedding",
+            ))
+        }
+    };
+
+    let verified = result.verified && anti_spoof.as_ref().is_none_or(|a| a.passed);
+
+    // A duress template matches like any other; only the enclave knows
+    if let Some(template) = template.as_ref().filter(|_| verified) {
+        state
+            .duress
+            .check(&request.vault_id, DuressTrigger::Template(&template.template_id))
+            .map_err(AppError::internal)?;
+    }
+
+    let verdict = BiometricVerdict {
+        verified,
+        confidence: result.confidence,
+        quality,
+        template,
+        anti_spoof,
+    };
+    let verdict_sha256 = canonical_sha256(&verdict).map_err(AppError::internal)?;
     let attestation = state
         .attestation
-        .generate(&request.vault_id, "biometric_verification")
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .generate_for_exchange(
+            &request.vault_id,
+            "biometric_verification",
+            &request_sha256,
+            &verdict_sha256,
+            None,
+        )
+        .await?;
 
     Ok(Json(BiometricVerifyResponse {
-        verified: result.verified,
+        verdict,
         attestation,
-        confidence: result.confidence,
     }))
 }
 
+async fn voice_challenge(
+    State(state): State<AppState>,
+    Json(request): Json<VoiceChallengeRequest>,
+) -> Json<voice::VoiceChallenge> {
+    Json(state.voice.issue(&request.vault_id))
+}
+
+/// Match an embedding (client-side in privacy mode, or from the in-enclave
+/// face model) against every compatible template; the best score wins
+fn match_templates(
+    state: &AppState,
+    request: &BiometricVerifyRequest,
+    probe: &embedding::Embedding,
+) -> Result<(biometric::BiometricResult, TemplateMatch), AppError> {
+    let templates = state
+        .templates
+        .load_all(&request.vault_id, request.method.as_str())
+        .map_err(|e| match e {
+            LoadError::NotFound => AppError::new(
+                StatusCode::NOT_FOUND,
+                "TEMPLATE_NOT_FOUND",
+                "No template enrolled for this vault and method",
+            ),
+            LoadError::ReenrollmentRequired(reason) => {
+                AppError::new(StatusCode::CONFLICT, "REENROLLMENT_REQUIRED", reason)
+            }
+            LoadError::Failed(e) => AppError::internal(e),
+        })?;
+
+    let mut best: Option<(biometric::BiometricResult, &LoadedTemplate)> = None;
+    for template in &templates {
+        let compatible = template
+            .meta
+            .model
+            .as_ref()
+            .is_some_and(|model| model.check_compatible(&probe.model).is_ok());
+        if !compatible {
+            continue;
+        }
+        let enrolled = embedding::from_f32le(&template.features).map_err(AppError::internal)?;
+        let result =
+            state
+                .biometric
+                .match_embedding(&enrolled, &probe.vector, template.confidence_cap);
+        if best.as_ref().is_none_or(|(b, _)| result.confidence > b.confidence) {
+            best = Some((result, template));
+        }
+    }
+
+    let (result, template) = best.ok_or_else(|| {
+        let enrolled_models: Vec<_> = templates.iter().map(|t| &t.meta.model).collect();
+        AppError::new(
+            StatusCode::CONFLICT,
+            "MODEL_INCOMPATIBLE",
+            "No enrolled template is compatible with this embedding model",
+        )
+        .with_details(serde_json::json!({ "enrolled_models": enrolled_models }))
+    })?;
+    Ok((
+        result,
+        TemplateMatch {
+            template_id: template.meta.template_id.clone(),
+            template_expires_at: template.expires_at,
+            template_stale: template.confidence_cap.is_some(),
+        },
+    ))
+}
+
 async fn liveness_check(
     State(state): State<AppState>,
-    Json(request): Json<LivenessCheckRequest>,
-) -> Result<Json<LivenessCheckResponse>, StatusCode> {
-    info!("Liveness check request: vault_id={}", request.vault_id);
+    Extension(signer): Extension<auth::VerifiedSigner>,
+    HashedJson {
+        value: request,
+        sha256: request_sha256,
+    }: HashedJson<LivenessCheckRequest>,
+) -> Result<Json<LivenessCheckResponse>, AppError> {
+    info!(
+        "Liveness check request: vault_id={}, signer={}",
+        request.vault_id, signer.address
+    );
 
     let result = state
         .liveness
         .check(&request.vault_id, &request.user_address)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .await?;
+
+    let verdict = LivenessVerdict {
+        alive: result.alive,
+        last_seen: result.last_seen,
+        confidence: result.confidence,
+        devices: result.devices,
+    };
 
     // Generate attestation if alive
-    let attestation = if result.alive {
+    let attestation = if verdict.alive {
+        let verdict_sha256 = canonical_sha256(&verdict).map_err(AppError::internal)?;
         Some(
             state
                 .attestation
-                .generate(&request.vault_id, "liveness_check")
-                .await
-                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+                .generate_for_exchange(
+                    &request.vault_id,
+                    "liveness_check",
+                    &request_sha256,
+                    &verdict_sha256,
+                    None,
+                )
+                .await?,
         )
     } else {
         None
     };
 
     Ok(Json(LivenessCheckResponse {
-        alive: result.alive,
-        last_seen: result.last_seen,
-        confidence: result.confidence,
+        verdict,
         attestation,
     }))
 }
 
 async fn zk_generate(
     State(state): State<AppState>,
-    Json(request): Json<ZKProofRequest>,
-) -> Result<Json<ZKProofResponse>, StatusCode> {
+    HashedJson {
+        value: request,
+        sha256: request_sha256,
+    }: HashedJson<ZKProofRequest>,
+) -> Result<Json<serde_json::Value>, AppError> {
     info!("ZK proof generation request: vault_id={}, claim_type={}", request.vault_id, request.claim_type);
+    let job = join_or_prove(&state, request, request_sha256)?;
+    job.outcome().await.map(Json)
+}
+
+/// The job proving this request: an identical one already in flight, or a
+/// new one run on a worker task
+fn join_or_prove(
+    state: &AppState,
+    request: ZKProofRequest,
+    request_sha256: [u8; 32],
+) -> Result<Arc<Job>, AppError> {
+    let circuit = state
+        .zk_proof
+        .circuits()
+        .resolve(request.claim_type.as_str(), request.circuit_version)
+        .map_err(|e| AppError::bad_request("UNSUPPORTED_CIRCUIT", e))?;
+    check_template_fields(state, &request)?;
+    let flight = state
+        .proof_jobs
+        .start(
+            &request_sha256,
+            circuit.claim_type,
+            circuit.version,
+            request.priority,
+        )
+        .map_err(queue_full)?;
+    let (job, admission) = match flight {
+        Flight::Joined(job) => {
+            info!("Request joins proof job {}", job.id());
+            return Ok(job);
+        }
+        Flight::Leading(job, admission) => (job, admission),
+    };
+
+    let (state, worker) = (state.clone(), job.clone());
+    tokio::spawn(async move {
+        let _worker = admission.started().await;
+        // Proved on its own task so a panic still finishes the job for
+        // everyone waiting on it
+        let proof = {
+            let (state, worker) = (state.clone(), worker.clone());
+            tokio::spawn(async move {
+                prove_claim(&state, request, &request_sha256, &|stage| {
+                    worker.stage(stage)
+                })
+                .await
+            })
+        };
+        let outcome = match proof.await {
+            Ok(outcome) => outcome.and_then(|response| {
+                serde_json::to_value(response).map_err(|e| AppError::internal(e.to_string()))
+            }),
+            Err(e) => Err(AppError::internal(format!("Proof task failed: {}", e))),
+        };
+        state.proof_jobs.finish(&worker, outcome);
+    });
+    Ok(job)
+}
+
+/// Predicate claims over a vault registered from a template may only read
+/// the manifest fields the template opens to claims
+fn check_template_fields(state: &AppState, request: &ZKProofRequest) -> Result<(), AppError> {
+    if request.claim_type != ClaimType::Predicate {
+        return Ok(());
+    }
+    let Some(template) = state
+        .vault_templates
+        .template_of(&request.vault_id)
+        .map_err(AppError::internal)?
+    else {
+        return Ok(());
+    };
+    let definition: ClaimDefinition =
+        serde_json::from_value(request.claim_value.clone()).map_err(|e| {
+            AppError::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "INVALID_CLAIM",
+                format!("Invalid claim definition: {}", e),
+            )
+        })?;
+    let closed = template.closed_fields(definition.conditions.iter().map(|c| c.field.as_str()));
+    if !closed.is_empty() {
+        return Err(AppError::bad_request(
+            "CLAIM_FIELD_NOT_ALLOWED",
+            format!(
+                "Vault template {} doesn't open these fields to claims: {}",
+                template.id,
+                closed.join(", ")
+            ),
+        ));
+    }
+    Ok(())
+}
+
+fn queue_full(reason: String) -> AppError {
+    AppError::new(StatusCode::SERVICE_UNAVAILABLE, "PROVING_QUEUE_FULL", reason)
+}
+
+fn over_budget(refusal: Refusal) -> AppError {
+    match refusal {
+        Refusal::TooLarge(reason) => {
+            AppError::new(StatusCode::PAYLOAD_TOO_LARGE, "MEMORY_BUDGET_EXCEEDED", reason)
+        }
+        Refusal::Exhausted(reason) => {
+            AppError::new(StatusCode::SERVICE_UNAVAILABLE, "MEMORY_BUDGET_EXHAUSTED", reason)
+        }
+    }
+}
+
+/// The proving pipeline behind /zk/generate and proof jobs; `request_sha256`
+/// is the canonical hash of the request body, bound into the attestation.
+/// Takes the request so its encoded payload is decoded in place.
+async fn prove_claim(
+    state: &AppState,
+    mut request: ZKProofRequest,
+    request_sha256: &[u8; 32],
+    progress: &(dyn Fn(Stage) + Sync),
+) -> Result<ZKProofResponse, AppError> {
+    let circuit = state
+        .zk_proof
+        .circuits()
+        .resolve(request.claim_type.as_str(), request.circuit_version)
+        .map_err(|e| AppError::bad_request("UNSUPPORTED_CIRCUIT", e))?;
+    if request.deterministic && !state.zk_proof.allows_deterministic() {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "DETERMINISTIC_PROVING_DISABLED",
+            "Deterministic proving is only available in development",
+        ));
+    }
+
+    // Held until the proof is attested: the payload and a witness buffer
+    let _memory = state
+        .memory
+        .reserve(&[
+            (memory_budget::PAYLOAD, request.encrypted_data.len()),
+            (memory_budget::WITNESS, state.memory.proof_witness_bytes()),
+        ])
+        .await
+        .map_err(over_budget)?;
 
     // Decode encrypted data
-    let encrypted_bytes = base64::engine::general_purpose::STANDARD
-        .decode(&request.encrypted_data)
-        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    progress(Stage::Decrypting);
+    let payload = ProofPayload::for_circuit(circuit, std::mem::take(&mut request.encrypted_data))?;
 
     // Generate ZK proof in enclave (privacy-preserving - data never leaves enclave)
     let proof_result = state
         .zk_proof
-        .generate(&request.claim_type, &request.claim_value, &encrypted_bytes)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .generate(
+            circuit,
+            &request.claim_value,
+            &payload,
+            request.deterministic,
+            progress,
+        )
+        .await?;
+    if let Err(e) = state.bus.publish(BusEvent::ProofGenerated {
+        claim_type: circuit.claim_type.to_string(),
+    }) {
+        warn!("Failed to publish proof event: {}", e);
+    }
+
+    // Attest to the circuit version, proving key and public signals, so
+    // none can be swapped under the proof
+    progress(Stage::Finalizing);
+    let statement = serde_json::to_vec(&serde_json::json!({
+        "claim_type": circuit.claim_type,
+        "circuit_version": circuit.version,
+        "zkey_sha256": circuit.zkey_sha256,
+        "deterministic": request.deterministic,
+        "public_signals": proof_result.public_signals,
+    }))
+    .map_err(|e| AppError::internal(e.to_string()))?;
 
-    // Generate attestation
+    let proof = match request.format {
+        ProofFormat::Json => proof_result.proof,
+        ProofFormat::Compact => {
+            let compact = proof_encoding::compact(&proof_result.proof, &proof_result.public_signals)
+                .map_err(|e| AppError::new(StatusCode::UNPROCESSABLE_ENTITY, "PROOF_ENCODING_FAILED", e))?;
+            serde_json::to_value(compact).map_err(|e| AppError::internal(e.to_string()))?
+        }
+    };
+    let claim = ProvenClaim {
+        proof,
+        format: request.format,
+        public_signals: proof_result.public_signals,
+        claim_type: circuit.claim_type.to_string(),
+        circuit_version: circuit.version,
+        zkey_sha256: circuit.zkey_sha256.clone(),
+        deterministic: request.deterministic,
+    };
+    let claim_sha256 = canonical_sha256(&claim).map_err(AppError::internal)?;
     let attestation = state
         .attestation
-        .generate(&request.vault_id, "zk_proof_generation")
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .generate_for_exchange(
+            &request.vault_id,
+            "zk_proof_generation",
+            request_sha256,
+            &claim_sha256,
+            Some(&Sha256::digest(statement)),
+        )
+        .await?;
 
-    Ok(Json(ZKProofResponse {
-        proof: proof_result.proof,
-        public_signals: proof_result.public_signals,
-        attestation,
-    }))
+    Ok(ZKProofResponse { claim, attestation })
+}
+
+/// Which verification key checks proofs of each circuit version
+async fn zk_circuits(State(state): State<AppState>) -> Json<CircuitsResponse> {
+    Json(CircuitsResponse {
+        circuits: state.zk_proof.circuits().all().to_vec(),
+    })
 }
 
@@ -0,0 +1,367 @@
+/**
+ * Content Manifests
+ * What a vault payload holds, without what the files say
+ *
+ * The payload is unpacked in the enclave and each file listed with its
+ * size, SHA-256 and a content type sniffed from its bytes (names are not
+ * trusted for types). Owners and beneficiaries can then agree on a vault's
+ * contents, e.g. that it holds the PDF whose hash the lawyer has, without
+ * anyone reading them.
+ *
+ *   zip: stored and deflated entries, CRC-checked; no Zip64 or encryption
+ *   tar: ustar, with pax and GNU long names
+ *   anything else is one unnamed file
+ *
+ * Names can be shown, cut to their extension, or omitted. Sizes and
+ * hashes are always exact; a hash lets holders of a file confirm it is
+ * present, so hashes can be left out too.
+ */
+
+use flate2::read::DeflateDecoder;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use zeroize::Zeroizing;
+
+const MAX_FILES: usize = 10_000;
+/// Unpacked size cap, so a small archive can't expand without bound
+const MAX_UNPACKED_BYTES: u64 = 512 * 1024 * 1024;
+const TAR_BLOCK: usize = 512;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NameRedaction {
+    #[default]
+    Show,
+    /// Only the extension, e.g. "*.pdf"
+    Extension,
+    Omit,
+}
+
+#[derive(Default, Deserialize, Serialize)]
+pub struct ManifestOptions {
+    #[serde(default)]
+    pub names: NameRedaction,
+    #[serde(default)]
+    pub omit_hashes: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Container {
+    Zip,
+    Tar,
+    File,
+}
+
+#[derive(Serialize)]
+pub struct ManifestEntry {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub content_type: &'static str,
+    pub size: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct Manifest {
+    pub container: Container,
+    pub total_bytes: u64,
+    pub files: Vec<ManifestEntry>,
+}
+
+/// One unpacked file; unnamed when the payload is not an archive
+pub struct File {
+    pub name: Option<String>,
+    pub data: Zeroizing<Vec<u8>>,
+}
+
+/// The payload's files, in archive order
+pub fn unpack(payload: &[u8]) -> Result<(Container, Vec<File>), String> {
+    if payload.starts_with(b"PK\x03\x04") || payload.starts_with(b"PK\x05\x06") {
+        Ok((Container::Zip, unzip(payload)?))
+    } else if is_tar(payload) {
+        Ok((Container::Tar, untar(payload)?))
+    } else {
+        let file = File {
+            name: None,
+            data: Zeroizing::new(payload.to_vec()),
+        };
+        Ok((Container::File, vec![file]))
+    }
+}
+
+pub fn build(payload: &[u8], options: &ManifestOptions) -> Result<Manifest, String> {
+    let (container, files) = unpack(payload)?;
+
+    let files: Vec<ManifestEntry> = files
+        .into_iter()
+        .map(|file| ManifestEntry {
+            name: file.name.and_then(|name| redact(&name, options.names)),
+            content_type: sniff(&file.data),
+            size: file.data.len() as u64,
+            sha256: (!options.omit_hashes).then(|| hex::encode(Sha256::digest(&file.data[..]))),
+        })
+        .collect();
+    Ok(Manifest {
+        container,
+        total_bytes: files.iter().map(|f| f.size).sum(),
+        files,
+    })
+}
+
+fn redact(name: &str, redaction: NameRedaction) -> Option<String> {
+    match redaction {
+        NameRedaction::Show => Some(name.to_string()),
+        NameRedaction::Extension => {
+            let base = name.rsplit('/').next().unwrap_or(name);
+            match base.rsplit_once('.') {
+                Some((stem, extension)) if !stem.is_empty() => {
+                    Some(format!("*.{}", extension.to_lowercase()))
+                }
+                _ => Some("*".to_string()),
+            }
+        }
+        NameRedaction::Omit => None,
+    }
+}
+
+/// MIME type from magic bytes
+fn sniff(data: &[u8]) -> &'static str {
+    const MAGIC: &[(&[u8], &str)] = &[
+        (b"%PDF-", "application/pdf"),
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"OggS", "audio/ogg"),
+        (b"\x1f\x8b", "application/gzip"),
+        (b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+        (b"{\\rtf", "application/rtf"),
+    ];
+    if let Some((_, mime)) = MAGIC.iter().find(|(magic, _)| data.starts_with(magic)) {
+        return mime;
+    }
+    if data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WAVE" {
+        return "audio/wav";
+    }
+    if data.len() >= 12 && &data[4..8] == b"ftyp" {
+        return "video/mp4";
+    }
+    match std::str::from_utf8(data) {
+        Ok(text) if !text.contains('\0') => {
+            if serde_json::from_str::<serde_json::Value>(text).is_ok() {
+                "application/json"
+            } else {
+                "text/plain"
+            }
+        }
+        _ => "application/octet-stream",
+    }
+}
+
+/// Running totals checked against the caps
+struct Budget {
+    files: usize,
+    bytes: u64,
+}
+
+impl Budget {
+    fn take(&mut self, size: u64) -> Result<(), String> {
+        self.files += 1;
+        self.bytes = self.bytes.saturating_add(size);
+        if self.files > MAX_FILES {
+            return Err(format!("Payload holds more than {} files", MAX_FILES));
+        }
+        if self.bytes > MAX_UNPACKED_BYTES {
+            return Err(format!(
+                "Payload unpacks to more than {} bytes",
+                MAX_UNPACKED_BYTES
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn unzip(archive: &[u8]) -> Result<Vec<File>, String> {
+    let le16 = |at: usize| -> Result<usize, String> {
+        archive
+            .get(at..at + 2)
+            .map(|b| usize::from(u16::from_le_bytes([b[0], b[1]])))
+            .ok_or_else(|| "Truncated zip".to_string())
+    };
+    let le32 = |at: usize| -> Result<u32, String> {
+        archive
+            .get(at..at + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .ok_or_else(|| "Truncated zip".to_string())
+    };
+
+    // End of central directory: 22 bytes plus a comment of up to 64 KiB
+    let earliest = archive.len().saturating_sub(22 + 0xffff);
+    let end = (earliest..=archive.len().saturating_sub(22))
+        .rev()
+        .find(|&at| archive[at..].starts_with(b"PK\x05\x06"))
+        .ok_or_else(|| "Zip has no central directory".to_string())?;
+    let count = le16(end + 10)?;
+    let offset = le32(end + 16)?;
+    if offset == u32::MAX || count == 0xffff {
+        return Err("Zip64 archives are not supported".to_string());
+    }
+
+    let mut budget = Budget { files: 0, bytes: 0 };
+    let mut files = Vec::new();
+    let mut at = offset as usize;
+    for _ in 0..count {
+        if le32(at)? != 0x0201_4b50 {
+            return Err("Corrupt zip central directory".to_string());
+        }
+        let flags = le16(at + 8)?;
+        let method = le16(at + 10)?;
+        let crc = le32(at + 16)?;
+        let compressed = le32(at + 20)? as usize;
+        let size = le32(at + 24)?;
+        let name_len = le16(at + 28)?;
+        let entry_end = at + 46 + name_len + le16(at + 30)? + le16(at + 32)?;
+        let local = le32(at + 42)? as usize;
+        let name = archive
+            .get(at + 46..at + 46 + name_len)
+            .map(|name| String::from_utf8_lossy(name).into_owned())
+            .ok_or_else(|| "Truncated zip".to_string())?;
+        at = entry_end;
+        if name.ends_with('/') {
+            continue;
+        }
+        if flags & 1 != 0 {
+            return Err(format!("Zip entry {} is encrypted", name));
+        }
+        budget.take(u64::from(size))?;
+
+        if le32(local)? != 0x0403_4b50 {
+            return Err(format!("Corrupt zip entry {}", name));
+        }
+        let start = local + 30 + le16(local + 26)? + le16(local + 28)?;
+        let raw = archive
+            .get(start..start + compressed)
+            .ok_or_else(|| format!("Truncated zip entry {}", name))?;
+        let mut data = Zeroizing::new(Vec::new());
+        match method {
+            0 => data.extend_from_slice(raw),
+            8 => {
+                DeflateDecoder::new(raw)
+                    .take(u64::from(size) + 1)
+                    .read_to_end(&mut data)
+                    .map_err(|e| format!("Zip entry {} doesn't inflate: {}", name, e))?;
+            }
+            other => {
+                return Err(format!(
+                    "Zip entry {} uses compression method {}",
+                    name, other
+                ))
+            }
+        }
+        let mut checksum = flate2::Crc::new();
+        checksum.update(&data);
+        if data.len() as u64 != u64::from(size) || checksum.sum() != crc {
+            return Err(format!("Zip entry {} fails its size or CRC check", name));
+        }
+        files.push(File {
+            name: Some(name),
+            data,
+        });
+    }
+    Ok(files)
+}
+
+/// A ustar header checksum over the first block
+fn is_tar(data: &[u8]) -> bool {
+    let Some(header) = data.get(..TAR_BLOCK) else {
+        return false;
+    };
+    let Some(expected) = octal(&header[148..156]) else {
+        return false;
+    };
+    let sum: u64 = header
+        .iter()
+        .enumerate()
+        .map(|(i, &b)| {
+            if (148..156).contains(&i) {
+                32
+            } else {
+                u64::from(b)
+            }
+        })
+        .sum();
+    sum == expected && &header[257..262] == b"ustar"
+}
+
+fn untar(archive: &[u8]) -> Result<Vec<File>, String> {
+    let mut budget = Budget { files: 0, bytes: 0 };
+    let mut files = Vec::new();
+    let mut long_name = None;
+    let mut at = 0;
+    while let Some(header) = archive.get(at..at + TAR_BLOCK) {
+        if header.iter().all(|&b| b == 0) {
+            break;
+        }
+        let size = octal(&header[124..136]).ok_or_else(|| "Corrupt tar header".to_string())?;
+        let start = at + TAR_BLOCK;
+        let body = usize::try_from(size)
+            .ok()
+            .and_then(|size| archive.get(start..start.checked_add(size)?))
+            .ok_or_else(|| "Truncated tar".to_string())?;
+        at = start + body.len().div_ceil(TAR_BLOCK) * TAR_BLOCK;
+
+        match header[156] {
+            b'0' | 0 => {
+                budget.take(size)?;
+                let name = long_name.take().unwrap_or_else(|| {
+                    let prefix = field(&header[345..500]);
+                    let name = field(&header[..100]);
+                    if prefix.is_empty() {
+                        name
+                    } else {
+                        format!("{}/{}", prefix, name)
+                    }
+                });
+                files.push(File {
+                    name: Some(name),
+                    data: Zeroizing::new(body.to_vec()),
+                });
+            }
+            // GNU long name for the next entry
+            b'L' => long_name = Some(field(body)),
+            // pax header: "<len> path=<name>\n" records
+            b'x' => {
+                long_name = String::from_utf8_lossy(body)
+                    .lines()
+                    .find_map(|record| {
+                        record
+                            .split_once(" path=")
+                            .map(|(_, path)| path.to_string())
+                    })
+                    .or(long_name);
+            }
+            // Directories, links and global headers hold no file content
+            _ => long_name = None,
+        }
+    }
+    Ok(files)
+}
+
+/// NUL-terminated text field
+fn field(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+fn octal(bytes: &[u8]) -> Option<u64> {
+    let digits = field(bytes);
+    let digits = digits.trim_matches(|c: char| c == ' ' || c == '\0');
+    if digits.is_empty() {
+        return Some(0);
+    }
+    u64::from_str_radix(digits, 8).ok()
+}
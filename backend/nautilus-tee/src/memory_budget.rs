@@ -0,0 +1,212 @@
+/**
+ * Memory Budget
+ * Accounting for the memory large requests hold, against the enclave's fixed
+ * allocation
+ *
+ * A Nitro enclave has a fixed amount of memory and no swap; running out
+ * kills the enclave and every request in it. Work that holds large buffers
+ * (request payloads, witness buffers) reserves their size up front:
+ *
+ *   per request  one request's reservations together; larger is refused
+ *   global       all reservations plus the store's in-memory cache
+ *
+ * A request that would take the total past the global budget waits for
+ * others to release theirs, up to TEE_MEMORY_WAIT_MS, then is turned away.
+ * Reservations are released when dropped. GET /memory reports usage.
+ */
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tracing::warn;
+
+use crate::store::Store;
+
+/// What a reservation holds memory for
+pub const PAYLOAD: &str = "payload";
+pub const WITNESS: &str = "witness";
+
+#[derive(Clone, Copy, Debug)]
+pub struct MemoryLimits {
+    pub global_bytes: usize,
+    pub per_request_bytes: usize,
+    /// How long a request waits for memory before it is turned away
+    pub wait: Duration,
+    /// Witness buffer reserved alongside each proof's payload
+    pub proof_witness_bytes: usize,
+}
+
+#[derive(Debug)]
+pub enum Refusal {
+    /// More than one request may hold
+    TooLarge(String),
+    /// The budget stayed full for the whole wait
+    Exhausted(String),
+}
+
+#[derive(Serialize)]
+pub struct MemoryMetrics {
+    pub global_bytes: usize,
+    pub per_request_bytes: usize,
+    pub used_bytes: usize,
+    pub peak_bytes: usize,
+    /// Reserved by in-flight requests, by what it holds
+    pub reserved: BTreeMap<&'static str, usize>,
+    /// The store's cached namespaces, at their encoded size
+    pub cache_bytes: usize,
+    pub waiting: usize,
+    pub refused: u64,
+}
+
+#[derive(Default)]
+struct Usage {
+    reserved: BTreeMap<&'static str, usize>,
+    peak: usize,
+    waiting: usize,
+    refused: u64,
+}
+
+pub struct MemoryBudget {
+    limits: MemoryLimits,
+    store: Arc<Store>,
+    usage: Mutex<Usage>,
+    released: Notify,
+}
+
+/// Memory held for one request, given back on drop
+pub struct Reservation {
+    budget: Arc<MemoryBudget>,
+    charges: Vec<(&'static str, usize)>,
+}
+
+impl Usage {
+    fn reserved_bytes(&self) -> usize {
+        self.reserved.values().sum()
+    }
+}
+
+impl MemoryBudget {
+    pub fn new(limits: MemoryLimits, store: Arc<Store>) -> Self {
+        Self {
+            limits,
+            store,
+            usage: Mutex::new(Usage::default()),
+            released: Notify::new(),
+        }
+    }
+
+    /// Reserve `charges` for one request, waiting for room if the global
+    /// budget is full
+    pub async fn reserve(
+        self: &Arc<Self>,
+        charges: &[(&'static str, usize)],
+    ) -> Result<Reservation, Refusal> {
+        let total: usize = charges.iter().map(|(_, bytes)| bytes).sum();
+        if total > self.limits.per_request_bytes {
+            self.usage.lock().unwrap().refused += 1;
+            return Err(Refusal::TooLarge(format!(
+                "Request needs {} bytes; at most {} may be held per request",
+                total, self.limits.per_request_bytes
+            )));
+        }
+
+        let deadline = tokio::time::Instant::now() + self.limits.wait;
+        let waiting = Waiting::new(&self.usage);
+        loop {
+            // Registered before checking, so a release in between still wakes us
+            let released = self.released.notified();
+            tokio::pin!(released);
+            released.as_mut().enable();
+            if self.try_charge(charges, total) {
+                return Ok(Reservation {
+                    budget: self.clone(),
+                    charges: charges.to_vec(),
+                });
+            }
+            if tokio::time::timeout_at(deadline, released).await.is_err() {
+                break;
+            }
+        }
+        drop(waiting);
+
+        let mut usage = self.usage.lock().unwrap();
+        usage.refused += 1;
+        warn!(
+            "Memory budget exhausted: {} bytes wanted, {} of {} in use",
+            total,
+            usage.reserved_bytes() + self.store.cached_bytes(),
+            self.limits.global_bytes
+        );
+        Err(Refusal::Exhausted(format!(
+            "No room for {} bytes in the enclave memory budget",
+            total
+        )))
+    }
+
+    pub fn proof_witness_bytes(&self) -> usize {
+        self.limits.proof_witness_bytes
+    }
+
+    pub fn metrics(&self) -> MemoryMetrics {
+        let usage = self.usage.lock().unwrap();
+        let cache_bytes = self.store.cached_bytes();
+        MemoryMetrics {
+            global_bytes: self.limits.global_bytes,
+            per_request_bytes: self.limits.per_request_bytes,
+            used_bytes: usage.reserved_bytes() + cache_bytes,
+            peak_bytes: usage.peak,
+            reserved: usage.reserved.clone(),
+            cache_bytes,
+            waiting: usage.waiting,
+            refused: usage.refused,
+        }
+    }
+
+    fn try_charge(&self, charges: &[(&'static str, usize)], total: usize) -> bool {
+        let mut usage = self.usage.lock().unwrap();
+        let used = usage.reserved_bytes() + self.store.cached_bytes();
+        if used + total > self.limits.global_bytes {
+            return false;
+        }
+        for &(kind, bytes) in charges {
+            *usage.reserved.entry(kind).or_default() += bytes;
+        }
+        usage.peak = usage.peak.max(used + total);
+        true
+    }
+
+    fn release(&self, charges: &[(&'static str, usize)]) {
+        let mut usage = self.usage.lock().unwrap();
+        for &(kind, bytes) in charges {
+            if let Some(reserved) = usage.reserved.get_mut(kind) {
+                *reserved -= bytes;
+            }
+        }
+        drop(usage);
+        self.released.notify_waiters();
+    }
+}
+
+/// Counts a request as waiting until it is admitted, refused or cancelled
+struct Waiting<'a>(&'a Mutex<Usage>);
+
+impl<'a> Waiting<'a> {
+    fn new(usage: &'a Mutex<Usage>) -> Self {
+        usage.lock().unwrap().waiting += 1;
+        Self(usage)
+    }
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.0.lock().unwrap().waiting -= 1;
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        self.budget.release(&self.charges);
+    }
+}
@@ -0,0 +1,542 @@
+/**
+ * Vault Migration
+ * Moving a vault from one enclave to another with an attested handoff
+ *
+ * The enclaves never talk directly; an admin relays four messages:
+ *
+ *   offer     destination: a fresh X25519 key for this migration,
+ *             attested together with the vault id and an expiry
+ *   export    source: checks the offer's attestation and measurement,
+ *             freezes the vault and seals its state to the offered key
+ *   import    destination: checks the export's attestation, opens it
+ *             and re-seals templates under its own keys, then attests
+ *             an acknowledgement of exactly that bundle
+ *   complete  source: checks the acknowledgement, deletes the vault
+ *             and leaves a tombstone naming where it went
+ *
+ * Vault state is every record the vault's services keep, plus its
+ * templates as stored and the template key they're sealed under. The
+ * audit trail moves with the vault; entries from before the migration
+ * verify under the source's audit key, which the destination records in
+ * the chain's "migration.imported" entry. Capability tokens are signed
+ * by the source identity and don't move.
+ *
+ * An export stays frozen until it is completed. If the destination never
+ * imports it, admins can cancel it once the offer has expired, since the
+ * destination no longer accepts it after that.
+ */
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use x25519_dalek::{PublicKey, StaticSecret};
+use zeroize::Zeroizing;
+
+use crate::attestation::VerifiedAttestation;
+use crate::crypto::{self, Envelope};
+use crate::keys::derive::{AuditSigning, KeyHierarchy};
+use crate::store::Store;
+use crate::templates::{StoredTemplate, TemplateService};
+use crate::vault_state::{self, VaultRecord, VAULT_NAMESPACES};
+
+pub const OFFER_OPERATION: &str = "migration_offer";
+pub const EXPORT_OPERATION: &str = "migration_export";
+pub const ACK_OPERATION: &str = "migration_ack";
+pub const COMPLETE_OPERATION: &str = "migration_complete";
+
+const MIGRATIONS_NAMESPACE: &str = "vault_migrations";
+const TOMBSTONES_NAMESPACE: &str = "vault_tombstones";
+/// Offers lapse, and their keys are forgotten, after an hour
+const OFFER_TTL_SECS: u64 = 60 * 60;
+
+/// One end of a migration
+#[derive(Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MigrationParty {
+    pub measurement: String, // PCR0
+    pub identity: String,    // Identity key id
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MigrationOffer {
+    pub migration_id: String,
+    pub vault_id: String,
+    pub destination: MigrationParty,
+    pub public_key: String, // Base64 X25519 key the vault is sealed to
+    pub expires_at: u64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MigrationBundle {
+    pub offer: MigrationOffer,
+    pub source: MigrationParty,
+    /// Base64 Ed25519 key the vault's audit entries so far verify under
+    pub source_audit_key: String,
+    pub records: usize,
+    pub templates: usize,
+    pub payload_sha256: String, // Hex, of the sealed plaintext
+    pub exported_at: u64,
+    pub envelope: Envelope,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MigrationAck {
+    pub migration_id: String,
+    pub vault_id: String,
+    pub destination: MigrationParty,
+    pub bundle_sha256: String, // Hex, of the bundle that was imported
+    pub imported_at: u64,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MigrationStatus {
+    /// Sealed and frozen on the source
+    Exported,
+    /// Taken over by the destination
+    Imported,
+    /// Deleted and tombstoned on the source
+    Completed,
+    Cancelled,
+}
+
+/// Kept by both ends, keyed by migration id
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MigrationRecord {
+    pub migration_id: String,
+    pub vault_id: String,
+    pub status: MigrationStatus,
+    pub source: MigrationParty,
+    pub destination: MigrationParty,
+    pub admin: String,
+    pub payload_sha256: String,
+    pub bundle_sha256: String,
+    pub records: usize,
+    pub templates: usize,
+    pub offer_expires_at: u64,
+    pub exported_at: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub acknowledged_at: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub closed_at: Option<u64>,
+}
+
+/// Left on the source in place of a migrated vault
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Tombstone {
+    pub vault_id: String,
+    pub migration_id: String,
+    pub destination: MigrationParty,
+    pub tombstoned_at: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MigrationPayload {
+    vault_id: String,
+    records: Vec<VaultRecord>,
+    templates: Vec<StoredTemplate>,
+    template_key: String, // Base64
+}
+
+struct PendingOffer {
+    offer: MigrationOffer,
+    secret: StaticSecret,
+}
+
+pub struct MigrationService {
+    store: Arc<Store>,
+    keys: Arc<KeyHierarchy>,
+    templates: Arc<TemplateService>,
+    local: MigrationParty,
+    /// PCR0 values of enclaves vaults may move to or from
+    allowed_measurements: Vec<String>,
+    offers: Mutex<HashMap<String, PendingOffer>>,
+    // Serializes migration state changes
+    lock: Mutex<()>,
+}
+
+impl MigrationService {
+    pub fn new(
+        store: Arc<Store>,
+        keys: Arc<KeyHierarchy>,
+        templates: Arc<TemplateService>,
+        local: MigrationParty,
+        allowed_measurements: Vec<String>,
+    ) -> Self {
+        Self {
+            store,
+            keys,
+            templates,
+            local,
+            allowed_measurements,
+            offers: Mutex::new(HashMap::new()),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Destination: offer to take over a vault this enclave doesn't hold
+    pub fn offer(&self, vault_id: &str) -> Result<MigrationOffer, String> {
+        if vault_id.is_empty() {
+            return Err("A vault id is required".to_string());
+        }
+        if self.holds(vault_id)? {
+            return Err("Vault already exists in this enclave".to_string());
+        }
+
+        let mut id = [0u8; 16];
+        OsRng.fill_bytes(&mut id);
+        let secret = StaticSecret::random_from_rng(OsRng);
+        let offer = MigrationOffer {
+            migration_id: hex::encode(id),
+            vault_id: vault_id.to_string(),
+            destination: self.local.clone(),
+            public_key: STANDARD.encode(PublicKey::from(&secret).as_bytes()),
+            expires_at: now() + OFFER_TTL_SECS,
+        };
+
+        let mut offers = self.offers.lock().unwrap();
+        let now = now();
+        offers.retain(|_, pending| pending.offer.expires_at > now);
+        offers.insert(
+            offer.migration_id.clone(),
+            PendingOffer {
+                offer: offer.clone(),
+                secret,
+            },
+        );
+        Ok(offer)
+    }
+
+    /// Source: seal the vault to an attested offer and freeze it
+    pub fn export(
+        &self,
+        offer: &MigrationOffer,
+        destination: &VerifiedAttestation,
+        admin: &str,
+    ) -> Result<MigrationBundle, String> {
+        self.check_peer(
+            destination,
+            OFFER_OPERATION,
+            &digest(offer)?,
+            &offer.destination,
+        )?;
+        if offer.expires_at <= now() {
+            return Err("Migration offer has expired".to_string());
+        }
+        if offer.destination.identity == self.local.identity {
+            return Err("A vault can't migrate to the enclave it's in".to_string());
+        }
+
+        let _guard = self.lock.lock().unwrap();
+        if self.migrating(&offer.vault_id)?.is_some() {
+            return Err("Vault is already being migrated".to_string());
+        }
+        if self.record(&offer.migration_id)?.is_some() {
+            return Err("Offer has already been used".to_string());
+        }
+        if !self.holds(&offer.vault_id)? {
+            return Err("Vault does not exist in this enclave".to_string());
+        }
+
+        let records = self.vault_records(&offer.vault_id)?;
+        let (templates, template_key) = self.templates.sealed(&offer.vault_id)?;
+        let (record_count, template_count) = (records.len(), templates.len());
+        let payload = Zeroizing::new(
+            serde_json::to_vec(&MigrationPayload {
+                vault_id: offer.vault_id.clone(),
+                records,
+                templates,
+                template_key: STANDARD.encode(*template_key),
+            })
+            .map_err(|e| format!("Failed to serialize vault state: {}", e))?,
+        );
+
+        let public_key = crypto::decode_public_key(&offer.public_key)?;
+        let bundle = MigrationBundle {
+            offer: offer.clone(),
+            source: self.local.clone(),
+            source_audit_key: STANDARD.encode(
+                self.keys
+                    .derive::<AuditSigning>(&offer.vault_id)
+                    .verifying_key()
+                    .as_bytes(),
+            ),
+            records: record_count,
+            templates: template_count,
+            payload_sha256: hex::encode(Sha256::digest(payload.as_slice())),
+            exported_at: now(),
+            envelope: crypto::seal(
+                &public_key,
+                &payload,
+                payload_aad(&offer.migration_id).as_bytes(),
+            )?,
+        };
+
+        self.store.put(
+            MIGRATIONS_NAMESPACE,
+            &offer.migration_id,
+            &MigrationRecord {
+                migration_id: offer.migration_id.clone(),
+                vault_id: offer.vault_id.clone(),
+                status: MigrationStatus::Exported,
+                source: self.local.clone(),
+                destination: offer.destination.clone(),
+                admin: admin.to_string(),
+                payload_sha256: bundle.payload_sha256.clone(),
+                bundle_sha256: hex::encode(digest(&bundle)?),
+                records: record_count,
+                templates: template_count,
+                offer_expires_at: offer.expires_at,
+                exported_at: bundle.exported_at,
+                acknowledged_at: None,
+                closed_at: None,
+            },
+        )?;
+        Ok(bundle)
+    }
+
+    /// Destination: take over the vault from an attested export of one of
+    /// this enclave's offers
+    pub fn import(
+        &self,
+        bundle: &MigrationBundle,
+        source: &VerifiedAttestation,
+        admin: &str,
+    ) -> Result<(MigrationAck, MigrationRecord), String> {
+        let bundle_digest = digest(bundle)?;
+        self.check_peer(source, EXPORT_OPERATION, &bundle_digest, &bundle.source)?;
+
+        let _guard = self.lock.lock().unwrap();
+        let pending = self
+            .offers
+            .lock()
+            .unwrap()
+            .remove(&bundle.offer.migration_id)
+            .ok_or("No pending offer for this migration")?;
+        if digest(&pending.offer)? != digest(&bundle.offer)? {
+            return Err("Bundle was sealed to a different offer".to_string());
+        }
+        if pending.offer.expires_at <= now() {
+            return Err("Migration offer has expired".to_string());
+        }
+        let vault_id = &pending.offer.vault_id;
+        if self.holds(vault_id)? {
+            return Err("Vault already exists in this enclave".to_string());
+        }
+
+        let plaintext = Zeroizing::new(crypto::open(
+            &pending.secret,
+            &bundle.envelope,
+            payload_aad(&pending.offer.migration_id).as_bytes(),
+        )?);
+        if hex::encode(Sha256::digest(plaintext.as_slice())) != bundle.payload_sha256 {
+            return Err("Vault state does not match the bundle".to_string());
+        }
+        let payload: MigrationPayload = serde_json::from_slice(&plaintext)
+            .map_err(|e| format!("Malformed vault state: {}", e))?;
+        if payload.vault_id != *vault_id
+            || payload.records.len() != bundle.records
+            || payload.templates.len() != bundle.templates
+        {
+            return Err("Vault state does not match the bundle".to_string());
+        }
+        if let Some(record) = payload
+            .records
+            .iter()
+            .find(|r| !VAULT_NAMESPACES.contains(&r.namespace.as_str()))
+        {
+            return Err(format!("Unexpected namespace {}", record.namespace));
+        }
+
+        let template_key: Zeroizing<[u8; 32]> = Zeroizing::new(
+            crypto::decode(&payload.template_key)?
+                .try_into()
+                .map_err(|_| "Template key must be 32 bytes".to_string())?,
+        );
+        self.templates
+            .adopt(vault_id, &payload.templates, &template_key)?;
+        for record in &payload.records {
+            self.store
+                .put(&record.namespace, &record.key, &record.value)?;
+        }
+        self.store.update(TOMBSTONES_NAMESPACE, |ns| {
+            ns.remove(vault_id);
+        })?;
+
+        let ack = MigrationAck {
+            migration_id: pending.offer.migration_id.clone(),
+            vault_id: vault_id.clone(),
+            destination: self.local.clone(),
+            bundle_sha256: hex::encode(bundle_digest),
+            imported_at: now(),
+        };
+        let record = MigrationRecord {
+            migration_id: ack.migration_id.clone(),
+            vault_id: vault_id.clone(),
+            status: MigrationStatus::Imported,
+            source: bundle.source.clone(),
+            destination: self.local.clone(),
+            admin: admin.to_string(),
+            payload_sha256: bundle.payload_sha256.clone(),
+            bundle_sha256: ack.bundle_sha256.clone(),
+            records: bundle.records,
+            templates: bundle.templates,
+            offer_expires_at: pending.offer.expires_at,
+            exported_at: bundle.exported_at,
+            acknowledged_at: Some(ack.imported_at),
+            closed_at: None,
+        };
+        self.store
+            .put(MIGRATIONS_NAMESPACE, &record.migration_id, &record)?;
+        Ok((ack, record))
+    }
+
+    /// Source: delete and tombstone the vault once the destination has
+    /// acknowledged exactly the bundle that was exported
+    pub fn complete(
+        &self,
+        migration_id: &str,
+        ack: &MigrationAck,
+        destination: &VerifiedAttestation,
+    ) -> Result<MigrationRecord, String> {
+        let _guard = self.lock.lock().unwrap();
+        let mut record = self.exported(migration_id)?;
+        self.check_peer(
+            destination,
+            ACK_OPERATION,
+            &digest(ack)?,
+            &record.destination,
+        )?;
+        if ack.migration_id != record.migration_id
+            || ack.vault_id != record.vault_id
+            || ack.destination != record.destination
+            || ack.bundle_sha256 != record.bundle_sha256
+        {
+            return Err("Acknowledgement is for a different export".to_string());
+        }
+
+        let vault_id = record.vault_id.clone();
+        vault_state::erase(&self.store, &vault_id, VAULT_NAMESPACES)?;
+        self.templates.remove_vault(&vault_id)?;
+
+        let now = now();
+        self.store.put(
+            TOMBSTONES_NAMESPACE,
+            &vault_id,
+            &Tombstone {
+                vault_id: vault_id.clone(),
+                migration_id: record.migration_id.clone(),
+                destination: record.destination.clone(),
+                tombstoned_at: now,
+            },
+        )?;
+        record.status = MigrationStatus::Completed;
+        record.acknowledged_at = Some(ack.imported_at);
+        record.closed_at = Some(now);
+        self.store
+            .put(MIGRATIONS_NAMESPACE, migration_id, &record)?;
+        Ok(record)
+    }
+
+    /// Source: unfreeze a vault whose offer lapsed before it was imported
+    pub fn cancel(&self, migration_id: &str) -> Result<MigrationRecord, String> {
+        let _guard = self.lock.lock().unwrap();
+        let mut record = self.exported(migration_id)?;
+        if record.offer_expires_at > now() {
+            return Err(
+                "The destination may still import this export; wait for its offer to expire"
+                    .to_string(),
+            );
+        }
+        record.status = MigrationStatus::Cancelled;
+        record.closed_at = Some(now());
+        self.store
+            .put(MIGRATIONS_NAMESPACE, migration_id, &record)?;
+        Ok(record)
+    }
+
+    pub fn record(&self, migration_id: &str) -> Result<Option<MigrationRecord>, String> {
+        self.store.get(MIGRATIONS_NAMESPACE, migration_id)
+    }
+
+    /// The vault's export awaiting acknowledgement, if any
+    pub fn migrating(&self, vault_id: &str) -> Result<Option<MigrationRecord>, String> {
+        Ok(self
+            .store
+            .list::<MigrationRecord>(MIGRATIONS_NAMESPACE)?
+            .into_iter()
+            .map(|(_, record)| record)
+            .find(|r| r.vault_id == vault_id && r.status == MigrationStatus::Exported))
+    }
+
+    pub fn tombstone(&self, vault_id: &str) -> Result<Option<Tombstone>, String> {
+        self.store.get(TOMBSTONES_NAMESPACE, vault_id)
+    }
+
+    fn exported(&self, migration_id: &str) -> Result<MigrationRecord, String> {
+        let record = self
+            .record(migration_id)?
+            .ok_or_else(|| format!("Unknown migration {}", migration_id))?;
+        if record.status != MigrationStatus::Exported {
+            return Err(format!("Migration is {:?}", record.status).to_lowercase());
+        }
+        Ok(record)
+    }
+
+    /// The attestation covers `digest` for `operation` and comes from the
+    /// claimed, accepted image
+    fn check_peer(
+        &self,
+        attestation: &VerifiedAttestation,
+        operation: &str,
+        digest: &[u8],
+        claimed: &MigrationParty,
+    ) -> Result<(), String> {
+        if attestation.operation != operation
+            || attestation.user_data.as_deref() != Some(hex::encode(digest).as_str())
+        {
+            return Err(format!("Attestation does not cover this {}", operation));
+        }
+        if attestation.pcr0 != claimed.measurement
+            || !self.allowed_measurements.contains(&attestation.pcr0)
+        {
+            return Err(format!(
+                "Migrations with measurement {} are not accepted",
+                attestation.pcr0
+            ));
+        }
+        Ok(())
+    }
+
+    fn holds(&self, vault_id: &str) -> Result<bool, String> {
+        Ok(!self.vault_records(vault_id)?.is_empty()
+            || self.templates.holds(vault_id)?)
+    }
+
+    fn vault_records(&self, vault_id: &str) -> Result<Vec<VaultRecord>, String> {
+        vault_state::records(&self.store, vault_id, VAULT_NAMESPACES)
+    }
+}
+
+/// Digest attested for each migration message
+pub fn digest<T: Serialize>(message: &T) -> Result<Vec<u8>, String> {
+    let bytes = serde_json::to_vec(message)
+        .map_err(|e| format!("Failed to serialize migration message: {}", e))?;
+    Ok(Sha256::digest(bytes).to_vec())
+}
+
+fn payload_aad(migration_id: &str) -> String {
+    format!("lumina-vault-migration:{}", migration_id)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
@@ -0,0 +1,260 @@
+/**
+ * Multi-Scalar Multiplication
+ * The backends a Groth16 prover's MSMs run on
+ *
+ * Proving is dominated by MSMs over the proving key's points, one per
+ * proof element and as large as the circuit. Backends:
+ *
+ *   serial:   arkworks' Pippenger on one thread; always available
+ *   parallel: the points split across threads, each chunk a Pippenger of
+ *             its own, the partial sums added (feature `msm-parallel`)
+ *
+ * GPU backends (e.g. icicle) need a device the enclave can't reach, so
+ * none is offered here.
+ *
+ * The backend is chosen at startup: TEE_MSM_BACKEND names one, or `auto`
+ * takes the parallel backend when it's built in and the host has more than
+ * one core. Whichever is chosen must first reproduce the serial result on
+ * a random instance, so a backend can change speed but never a proof.
+ */
+
+use ark_bn254::{Fr, G1Projective};
+use ark_ec::{CurveGroup, VariableBaseMSM};
+use ark_ff::UniformRand;
+use rand::rngs::OsRng;
+use std::fmt;
+
+/// Points in the startup self-check
+const SELF_CHECK_POINTS: usize = 512;
+/// Smallest chunk worth a thread; below it Pippenger's buckets don't
+/// amortize and a single thread is faster
+#[cfg(feature = "msm-parallel")]
+const MIN_CHUNK_POINTS: usize = 1 << 12;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Msm {
+    Serial,
+    #[cfg(feature = "msm-parallel")]
+    Parallel {
+        threads: usize,
+    },
+}
+
+impl Msm {
+    /// The backend for a `serial`, `parallel` or `auto` preference, once it
+    /// passes the self-check
+    pub fn select(preference: &str) -> Result<Self, String> {
+        let msm = match preference {
+            "serial" => Msm::Serial,
+            "parallel" => accelerated().ok_or_else(|| {
+                "The parallel MSM backend needs the `msm-parallel` feature and more than one core"
+                    .to_string()
+            })?,
+            "auto" => accelerated().unwrap_or(Msm::Serial),
+            other => return Err(format!("Unsupported TEE_MSM_BACKEND: {}", other)),
+        };
+        msm.self_check()?;
+        Ok(msm)
+    }
+
+    /// `sum(scalars[i] * bases[i])`
+    pub fn run<G: VariableBaseMSM>(
+        &self,
+        bases: &[G::MulBase],
+        scalars: &[G::ScalarField],
+    ) -> Result<G, String> {
+        if bases.len() != scalars.len() {
+            return Err(format!(
+                "MSM over {} points with {} scalars",
+                bases.len(),
+                scalars.len()
+            ));
+        }
+        match *self {
+            Msm::Serial => Ok(G::msm_unchecked(bases, scalars)),
+            #[cfg(feature = "msm-parallel")]
+            Msm::Parallel { threads } => Ok(chunked(bases, scalars, threads, MIN_CHUNK_POINTS)),
+        }
+    }
+
+    fn self_check(&self) -> Result<(), String> {
+        if *self == Msm::Serial {
+            return Ok(());
+        }
+        let bases = G1Projective::normalize_batch(
+            &(0..SELF_CHECK_POINTS)
+                .map(|_| G1Projective::rand(&mut OsRng))
+                .collect::<Vec<_>>(),
+        );
+        let scalars: Vec<Fr> = (0..SELF_CHECK_POINTS)
+            .map(|_| Fr::rand(&mut OsRng))
+            .collect();
+        let expected: G1Projective = Msm::Serial.run(&bases, &scalars)?;
+
+        let actual: G1Projective = match *self {
+            Msm::Serial => expected,
+            // Small chunks so the check exercises the split
+            #[cfg(feature = "msm-parallel")]
+            Msm::Parallel { threads } => chunked(&bases, &scalars, threads, 1),
+        };
+        if actual != expected {
+            return Err(format!(
+                "The {} MSM backend disagrees with the serial one",
+                self
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Msm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Msm::Serial => write!(f, "serial"),
+            #[cfg(feature = "msm-parallel")]
+            Msm::Parallel { threads } => write!(f, "parallel ({} threads)", threads),
+        }
+    }
+}
+
+#[cfg(feature = "msm-parallel")]
+fn accelerated() -> Option<Msm> {
+    let threads = std::thread::available_parallelism().ok()?.get();
+    (threads > 1).then_some(Msm::Parallel { threads })
+}
+
+#[cfg(not(feature = "msm-parallel"))]
+fn accelerated() -> Option<Msm> {
+    None
+}
+
+/// One Pippenger per chunk of at least `min_chunk` points, on up to
+/// `threads` threads
+#[cfg(feature = "msm-parallel")]
+fn chunked<G: VariableBaseMSM>(
+    bases: &[G::MulBase],
+    scalars: &[G::ScalarField],
+    threads: usize,
+    min_chunk: usize,
+) -> G {
+    let chunk = bases.len().div_ceil(threads).max(min_chunk);
+    if bases.len() <= chunk {
+        return G::msm_unchecked(bases, scalars);
+    }
+    std::thread::scope(|scope| {
+        let partials: Vec<_> = bases
+            .chunks(chunk)
+            .zip(scalars.chunks(chunk))
+            .map(|(bases, scalars)| scope.spawn(move || G::msm_unchecked(bases, scalars)))
+            .collect();
+        partials
+            .into_iter()
+            .map(|partial| partial.join().expect("MSM thread panicked"))
+            .sum()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bn254::{G1Affine, G2Projective};
+    use std::time::Instant;
+
+    fn instance<G: CurveGroup>(points: usize) -> (Vec<G::Affine>, Vec<G::ScalarField>) {
+        let bases =
+            G::normalize_batch(&(0..points).map(|_| G::rand(&mut OsRng)).collect::<Vec<_>>());
+        let scalars = (0..points)
+            .map(|_| G::ScalarField::rand(&mut OsRng))
+            .collect();
+        (bases, scalars)
+    }
+
+    fn backends() -> Vec<Msm> {
+        let mut backends = vec![Msm::Serial];
+        backends.extend(accelerated());
+        backends
+    }
+
+    #[test]
+    fn serial_matches_naive_sum() {
+        let (bases, scalars) = instance::<G1Projective>(33);
+        let naive: G1Projective = bases.iter().zip(&scalars).map(|(b, s)| *b * s).sum();
+        assert_eq!(
+            Msm::Serial.run::<G1Projective>(&bases, &scalars).unwrap(),
+            naive
+        );
+    }
+
+    #[test]
+    fn backends_agree() {
+        // The largest splits into several chunks on the parallel backend
+        for points in [0, 1, 7, 1000, 9000] {
+            let (bases, scalars) = instance::<G1Projective>(points);
+            let expected: G1Projective = Msm::Serial.run(&bases, &scalars).unwrap();
+            for msm in backends() {
+                assert_eq!(
+                    msm.run::<G1Projective>(&bases, &scalars).unwrap(),
+                    expected,
+                    "{}",
+                    msm
+                );
+            }
+        }
+        for points in [0, 1, 100] {
+            let (bases, scalars) = instance::<G2Projective>(points);
+            let expected: G2Projective = Msm::Serial.run(&bases, &scalars).unwrap();
+            for msm in backends() {
+                assert_eq!(
+                    msm.run::<G2Projective>(&bases, &scalars).unwrap(),
+                    expected,
+                    "{}",
+                    msm
+                );
+            }
+        }
+    }
+
+    #[cfg(feature = "msm-parallel")]
+    #[test]
+    fn uneven_chunks_agree() {
+        let (bases, scalars) = instance::<G1Projective>(1001);
+        let expected: G1Projective = Msm::Serial.run(&bases, &scalars).unwrap();
+        for threads in [2, 3, 7, 64] {
+            assert_eq!(
+                chunked::<G1Projective>(&bases, &scalars, threads, 1),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn selection() {
+        assert_eq!(Msm::select("serial").unwrap(), Msm::Serial);
+        assert_eq!(
+            Msm::select("auto").unwrap(),
+            accelerated().unwrap_or(Msm::Serial)
+        );
+        assert_eq!(Msm::select("parallel").ok(), accelerated());
+        assert!(Msm::select("gpu").is_err());
+        assert!(Msm::Serial
+            .run::<G1Projective>(&[G1Affine::default()], &[])
+            .is_err());
+    }
+
+    /// cargo test --release --features msm-parallel msm::tests::benchmark -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn benchmark() {
+        for log_points in [12, 14, 16, 18] {
+            let (bases, scalars) = instance::<G1Projective>(1 << log_points);
+            let mut results = Vec::new();
+            for msm in backends() {
+                let started = Instant::now();
+                let result: G1Projective = msm.run(&bases, &scalars).unwrap();
+                println!("2^{} points, {}: {:?}", log_points, msm, started.elapsed());
+                results.push(result);
+            }
+            assert!(results.windows(2).all(|pair| pair[0] == pair[1]));
+        }
+    }
+}
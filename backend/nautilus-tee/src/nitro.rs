@@ -0,0 +1,342 @@
+/**
+ * Nitro Attestation Documents
+ * COSE_Sign1 documents signed by the NSM, checked up to the AWS Nitro root
+ *
+ * The NSM signs a CBOR document (PCRs, and optionally a public key and
+ * user data) with a short-lived certificate, and carries that certificate
+ * with the CA bundle that issued it, root first. A document is trusted
+ * when its bundle starts at the pinned root, the chain verifies as of the
+ * moment the document was signed, and the COSE signature verifies under
+ * the leaf's P-384 key.
+ */
+
+use aws_nitro_enclaves_cose::COSESign1;
+use aws_nitro_enclaves_nsm_api::api::{AttestationDoc, Request, Response};
+use aws_nitro_enclaves_nsm_api::driver::{nsm_exit, nsm_init, nsm_process_request};
+use openssl::nid::Nid;
+use openssl::stack::Stack;
+use openssl::x509::store::X509StoreBuilder;
+use openssl::x509::verify::X509VerifyParam;
+use openssl::x509::{X509StoreContext, X509};
+use serde::de::IgnoredAny;
+use serde_bytes::ByteBuf;
+use sha2::{Digest, Sha256};
+
+/// SHA-256 of the DER root certificate of the commercial AWS partition
+/// (CN=aws.nitro-enclaves)
+pub const AWS_NITRO_ROOT_SHA256: &str =
+    "641a0321a3e244efe456463195d606317ed7cdcc3c1756e09893f3c68f79bb5b";
+
+/// ES384 signatures are r || s, 48 bytes each
+const SIGNATURE_LEN: usize = 96;
+
+/// A document from the NSM binding `user_data` and, for relying parties
+/// that encrypt to the enclave (e.g. KMS), `public_key`
+pub fn request_document(
+    user_data: Option<&[u8]>,
+    public_key: Option<&[u8]>,
+) -> Result<Vec<u8>, String> {
+    let fd = nsm_init();
+    if fd < 0 {
+        return Err("NSM is unavailable".to_string());
+    }
+    let response = nsm_process_request(
+        fd,
+        Request::Attestation {
+            user_data: user_data.map(|data| ByteBuf::from(data.to_vec())),
+            nonce: None,
+            public_key: public_key.map(|key| ByteBuf::from(key.to_vec())),
+        },
+    );
+    nsm_exit(fd);
+    match response {
+        Response::Attestation { document } => Ok(document),
+        other => Err(format!("NSM attestation failed: {:?}", other)),
+    }
+}
+
+/// The signed contents of `document`, once its signature and certificate
+/// chain check out against the root whose DER hashes to `root_sha256`
+pub fn verify(document: &[u8], root_sha256: &[u8; 32]) -> Result<AttestationDoc, String> {
+    let cose = COSESign1::from_bytes(document)
+        .map_err(|e| format!("Not a COSE_Sign1 document: {:?}", e))?;
+    // COSESign1 splits the signature at the key's length without checking
+    // there are that many bytes
+    let (_, _, _, signature): (IgnoredAny, IgnoredAny, IgnoredAny, ByteBuf) =
+        serde_cbor::from_slice(document).map_err(|e| format!("Malformed COSE_Sign1: {}", e))?;
+    if signature.len() != SIGNATURE_LEN {
+        return Err("Signature is not an ES384 signature".to_string());
+    }
+    let payload = cose
+        .get_payload(None)
+        .map_err(|e| format!("Unreadable payload: {:?}", e))?;
+    let doc = AttestationDoc::from_binary(&payload)
+        .map_err(|e| format!("Malformed attestation document: {:?}", e))?;
+
+    let (root, intermediates) = doc
+        .cabundle
+        .split_first()
+        .ok_or("Attestation document has no CA bundle")?;
+    if Sha256::digest(root).as_slice() != root_sha256 {
+        return Err("CA bundle does not start at the pinned Nitro root".to_string());
+    }
+    let leaf = X509::from_der(&doc.certificate).map_err(openssl_error)?;
+    check_chain(root, intermediates, &leaf, doc.timestamp / 1000)?;
+
+    let key = leaf
+        .public_key()
+        .and_then(|key| key.ec_key())
+        .map_err(|_| "Signing certificate does not hold an EC key".to_string())?;
+    if key.group().curve_name() != Some(Nid::SECP384R1) {
+        return Err("Signing certificate is not a P-384 key".to_string());
+    }
+    if !cose
+        .verify_signature(&key)
+        .map_err(|e| format!("Signature check failed: {:?}", e))?
+    {
+        return Err("Document signature does not verify".to_string());
+    }
+    Ok(doc)
+}
+
+/// Each certificate issued by the one before it, all valid at `at`, so a
+/// document outlives its short-lived signing certificate
+fn check_chain(root: &[u8], intermediates: &[ByteBuf], leaf: &X509, at: u64) -> Result<(), String> {
+    let mut store = X509StoreBuilder::new().map_err(openssl_error)?;
+    store
+        .add_cert(X509::from_der(root).map_err(openssl_error)?)
+        .map_err(openssl_error)?;
+    let mut param = X509VerifyParam::new().map_err(openssl_error)?;
+    param.set_time(at as _);
+    store.set_param(&param).map_err(openssl_error)?;
+    let store = store.build();
+
+    let mut chain = Stack::new().map_err(openssl_error)?;
+    for certificate in intermediates {
+        chain
+            .push(X509::from_der(certificate).map_err(openssl_error)?)
+            .map_err(openssl_error)?;
+    }
+
+    let mut context = X509StoreContext::new().map_err(openssl_error)?;
+    let failure = context
+        .init(&store, leaf, &chain, |context| {
+            let verified = context.verify_cert()?;
+            Ok((!verified).then(|| context.error().to_string()))
+        })
+        .map_err(openssl_error)?;
+    match failure {
+        None => Ok(()),
+        Some(reason) => Err(format!("Certificate chain does not verify: {}", reason)),
+    }
+}
+
+fn openssl_error(e: openssl::error::ErrorStack) -> String {
+    format!("Malformed certificate: {}", e)
+}
+
+/// A private CA standing in for the Nitro PKI, signing documents the way
+/// the NSM does
+#[cfg(test)]
+pub mod testing {
+    use super::*;
+    use aws_nitro_enclaves_nsm_api::api::Digest as PcrDigest;
+    use openssl::asn1::Asn1Time;
+    use openssl::bn::BigNum;
+    use openssl::ec::{EcGroup, EcKey};
+    use openssl::hash::MessageDigest;
+    use openssl::pkey::{PKey, Private};
+    use openssl::x509::extension::{BasicConstraints, KeyUsage};
+    use openssl::x509::{X509Builder, X509NameBuilder};
+    use std::collections::BTreeMap;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    pub struct TestPki {
+        root: X509,
+        intermediate: X509,
+        leaf: (X509, PKey<Private>),
+    }
+
+    impl TestPki {
+        /// Root, intermediate and a signing certificate valid for an hour
+        /// either side of now
+        pub fn generate() -> Self {
+            Self::valid_between(now() - 3600, now() + 3600)
+        }
+
+        /// A signing certificate valid only from `not_before` to `not_after`
+        pub fn valid_between(not_before: u64, not_after: u64) -> Self {
+            let root_key = key();
+            let root = certificate("root", &root_key, None, true, (now() - 3600, now() + 86400));
+            let intermediate_key = key();
+            let intermediate = certificate(
+                "intermediate",
+                &intermediate_key,
+                Some((&root, &root_key)),
+                true,
+                (now() - 3600, now() + 86400),
+            );
+            let leaf_key = key();
+            let leaf = certificate(
+                "enclave",
+                &leaf_key,
+                Some((&intermediate, &intermediate_key)),
+                false,
+                (not_before, not_after),
+            );
+            Self {
+                root,
+                intermediate,
+                leaf: (leaf, leaf_key),
+            }
+        }
+
+        pub fn root_sha256(&self) -> [u8; 32] {
+            Sha256::digest(self.root.to_der().unwrap()).into()
+        }
+
+        /// A COSE_Sign1 document over `pcrs` (index, value) and `user_data`
+        pub fn sign(&self, pcrs: &[(usize, Vec<u8>)], user_data: &[u8]) -> Vec<u8> {
+            self.sign_with(pcrs, user_data, &self.leaf.1)
+        }
+
+        /// As `sign`, but signed with `key` whatever the certificate says
+        pub fn sign_with(
+            &self,
+            pcrs: &[(usize, Vec<u8>)],
+            user_data: &[u8],
+            key: &PKey<Private>,
+        ) -> Vec<u8> {
+            let doc = AttestationDoc::new(
+                "i-test-enc0123456789abcdef".to_string(),
+                PcrDigest::SHA384,
+                now() * 1000,
+                pcrs.iter().cloned().collect::<BTreeMap<_, _>>(),
+                self.leaf.0.to_der().unwrap(),
+                vec![
+                    self.root.to_der().unwrap(),
+                    self.intermediate.to_der().unwrap(),
+                ],
+                Some(user_data.to_vec()),
+                None,
+                None,
+            );
+            COSESign1::new(
+                &doc.to_binary(),
+                &Default::default(),
+                &key.ec_key().unwrap(),
+            )
+            .unwrap()
+            .as_bytes(false)
+            .unwrap()
+        }
+    }
+
+    pub fn key() -> PKey<Private> {
+        let group = EcGroup::from_curve_name(Nid::SECP384R1).unwrap();
+        PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap()
+    }
+
+    fn certificate(
+        name: &str,
+        key: &PKey<Private>,
+        issuer: Option<(&X509, &PKey<Private>)>,
+        ca: bool,
+        (not_before, not_after): (u64, u64),
+    ) -> X509 {
+        let mut subject = X509NameBuilder::new().unwrap();
+        subject.append_entry_by_text("CN", name).unwrap();
+        let subject = subject.build();
+
+        let mut builder = X509Builder::new().unwrap();
+        builder.set_version(2).unwrap();
+        let serial = BigNum::from_u32(rand::random::<u32>()).unwrap();
+        builder
+            .set_serial_number(&serial.to_asn1_integer().unwrap())
+            .unwrap();
+        builder.set_subject_name(&subject).unwrap();
+        let issuer_name = issuer.map_or(&*subject, |(cert, _)| cert.subject_name());
+        builder.set_issuer_name(issuer_name).unwrap();
+        builder.set_pubkey(key).unwrap();
+        builder
+            .set_not_before(&Asn1Time::from_unix(not_before as _).unwrap())
+            .unwrap();
+        builder
+            .set_not_after(&Asn1Time::from_unix(not_after as _).unwrap())
+            .unwrap();
+        if ca {
+            builder
+                .append_extension(BasicConstraints::new().critical().ca().build().unwrap())
+                .unwrap();
+            builder
+                .append_extension(KeyUsage::new().critical().key_cert_sign().build().unwrap())
+                .unwrap();
+        } else {
+            builder
+                .append_extension(
+                    KeyUsage::new()
+                        .critical()
+                        .digital_signature()
+                        .build()
+                        .unwrap(),
+                )
+                .unwrap();
+        }
+        let signer = issuer.map_or(key, |(_, key)| key);
+        builder.sign(signer, MessageDigest::sha384()).unwrap();
+        builder.build()
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::{key, TestPki};
+    use super::*;
+
+    fn pcrs() -> Vec<(usize, Vec<u8>)> {
+        (0..3).map(|i| (i, vec![i as u8; 48])).collect()
+    }
+
+    #[test]
+    fn documents_signed_under_the_root_verify() {
+        let pki = TestPki::generate();
+        let document = pki.sign(&pcrs(), b"claims digest");
+
+        let doc = verify(&document, &pki.root_sha256()).unwrap();
+        assert_eq!(doc.user_data.unwrap().as_slice(), b"claims digest");
+        assert_eq!(doc.pcrs[&2].as_slice(), &[2u8; 48]);
+    }
+
+    #[test]
+    fn documents_outside_the_pinned_chain_are_refused() {
+        let pki = TestPki::generate();
+        let document = pki.sign(&pcrs(), b"claims digest");
+
+        // Another CA, however well formed
+        let other = TestPki::generate();
+        let error = verify(&document, &other.root_sha256()).err().unwrap();
+        assert!(error.contains("pinned Nitro root"));
+
+        // Signed with a key the certificate doesn't hold
+        let forged = pki.sign_with(&pcrs(), b"claims digest", &key());
+        let error = verify(&forged, &pki.root_sha256()).err().unwrap();
+        assert!(error.contains("does not verify"));
+
+        // A signing certificate that had expired when the document was signed
+        let expired = TestPki::valid_between(1_000_000_000, 1_000_003_600);
+        let document = expired.sign(&pcrs(), b"claims digest");
+        let error = verify(&document, &expired.root_sha256()).err().unwrap();
+        assert!(error.contains("chain does not verify"));
+
+        // Truncated, or not a document at all
+        assert!(verify(&document[..document.len() - 10], &pki.root_sha256()).is_err());
+        assert!(verify(b"not cbor", &pki.root_sha256()).is_err());
+    }
+}
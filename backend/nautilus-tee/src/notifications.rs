@@ -0,0 +1,658 @@
+/**
+ * Notifications
+ * Email, SMS and push notices to vault contacts, delivered by the parent
+ *
+ * The enclave can't reach mail, SMS or push providers. Owners register
+ * contacts per vault, for themselves or for its guardians; a notice (a
+ * template reminder, an escalation, a lockout) becomes one job per
+ * contact in its audience, rendered by that channel's adapter, signed
+ * with the enclave identity and queued in the store. A dispatcher hands
+ * pending jobs to the parent's relay as JSON lines over VSOCK (TCP in
+ * development) and reads one acknowledgment line back per job:
+ *
+ *   delivered: the provider accepted it
+ *   rejected:  it can never be delivered, e.g. the address bounced
+ *   retry:     try again later, with backoff, up to MAX_ATTEMPTS
+ *
+ * Rejected and exhausted jobs are dead-lettered (see dead_letters).
+ *
+ * A relay that can't be reached is retried under the notification retry
+ * policy (see retry) before the jobs wait for the next dispatch.
+ *
+ * The relay can drop or delay jobs but can't forge or alter them; the
+ * signature covers the job exactly as the provider-facing side sees it.
+ * Notices carry no vault content, since the relay and providers read them.
+ */
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tracing::{info, warn};
+
+use crate::auth::normalize_address;
+use crate::config::ParentRelay;
+use crate::dead_letters::{DeadLetterQueue, DeadLetterSource};
+use crate::keys::identity::EnclaveIdentity;
+use crate::pagination::Paged;
+use crate::retry::{self, Dependency, Failure, FailureKind};
+use crate::store::{Batch, Store};
+use crate::tasks::{Schedule, TaskScheduler};
+
+const CONTACTS_NAMESPACE: &str = "notification_contacts";
+pub const JOBS_NAMESPACE: &str = "notification_jobs";
+const DOMAIN: &[u8] = b"lumina-notification-v1:";
+const MAX_CONTACTS: usize = 8;
+const MAX_ATTEMPTS: u32 = 6;
+const RETRY_BASE_SECS: u64 = 60;
+/// Delivered and failed jobs are kept this long for owners to inspect
+const RETENTION_SECS: u64 = 7 * 24 * 60 * 60;
+const RELAY_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_BATCH: usize = 100;
+const SMS_MAX_CHARS: usize = 320;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Channel {
+    Email,
+    Sms,
+    Push,
+}
+
+/// Who a contact reaches
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Audience {
+    #[default]
+    Owner,
+    Guardians,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Contact {
+    pub channel: Channel,
+    /// Email address, E.164 phone number or push device token
+    pub address: String,
+    #[serde(default)]
+    pub audience: Audience,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ContactSet {
+    pub vault_id: String,
+    pub owner: String,
+    pub contacts: Vec<Contact>,
+    pub updated_at: u64,
+}
+
+/// What happened, before any channel renders it
+pub struct Notification {
+    /// Dotted name, e.g. "template.expiring"
+    pub kind: String,
+    pub title: String,
+    pub body: String,
+}
+
+/// Signed and handed to the relay as is
+#[derive(Clone, Serialize, Deserialize)]
+pub struct NotificationJob {
+    pub job_id: String,
+    pub vault_id: String,
+    pub kind: String,
+    pub channel: Channel,
+    /// Channel-specific payload from the adapter
+    pub message: Value,
+    pub created_at: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Pending,
+    Delivered,
+    Failed,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct JobRecord {
+    #[serde(flatten)]
+    pub job: NotificationJob,
+    pub status: JobStatus,
+    pub attempts: u32,
+    pub next_attempt_at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub acknowledged_at: Option<u64>,
+    /// The relay's reason for the last retry or rejection
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+#[derive(Serialize)]
+struct SignedJob<'a> {
+    #[serde(flatten)]
+    job: &'a NotificationJob,
+    key_id: String,
+    signature: String, // Base64 Ed25519 over DOMAIN || job JSON (fields in the order above)
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum AckStatus {
+    Delivered,
+    Rejected,
+    Retry,
+}
+
+#[derive(Deserialize)]
+struct Ack {
+    job_id: String,
+    status: AckStatus,
+    detail: Option<String>,
+}
+
+impl Paged for JobRecord {
+    const STATUSES: &'static [&'static str] = &["pending", "delivered", "failed"];
+
+    fn timestamp(&self) -> u64 {
+        self.job.created_at
+    }
+
+    fn id(&self) -> String {
+        self.job.job_id.clone()
+    }
+
+    fn status(&self) -> Option<&'static str> {
+        Some(match self.status {
+            JobStatus::Pending => "pending",
+            JobStatus::Delivered => "delivered",
+            JobStatus::Failed => "failed",
+        })
+    }
+}
+
+impl Channel {
+    fn validate(self, address: &str) -> Result<(), String> {
+        let (valid, what) = match self {
+            Channel::Email => (valid_email(address), "email address"),
+            Channel::Sms => (valid_phone(address), "E.164 phone number"),
+            Channel::Push => (
+                (16..=4096).contains(&address.len())
+                    && address.chars().all(|c| c.is_ascii_graphic()),
+                "push token",
+            ),
+        };
+        if !valid {
+            return Err(format!("Invalid {}: {}", what, address));
+        }
+        Ok(())
+    }
+
+    /// The adapter: what the relay hands this channel's provider
+    fn render(self, address: &str, notice: &Notification) -> Value {
+        match self {
+            Channel::Email => json!({
+                "to": address,
+                "subject": notice.title,
+                "text": notice.body,
+            }),
+            Channel::Sms => {
+                let text = format!("{}: {}", notice.title, notice.body);
+                json!({
+                    "to": address,
+                    "text": text.chars().take(SMS_MAX_CHARS).collect::<String>(),
+                })
+            }
+            Channel::Push => json!({
+                "token": address,
+                "title": notice.title,
+                "body": notice.body,
+                "data": { "kind": notice.kind },
+            }),
+        }
+    }
+}
+
+pub struct NotificationService {
+    store: Arc<Store>,
+    identity: Arc<EnclaveIdentity>,
+    relay: Option<ParentRelay>,
+    dead_letters: Arc<DeadLetterQueue>,
+    /// (vault, kind) -> last throttled notice
+    throttled: Mutex<HashMap<(String, String), Instant>>,
+}
+
+impl NotificationService {
+    pub fn new(
+        store: Arc<Store>,
+        identity: Arc<EnclaveIdentity>,
+        relay: Option<ParentRelay>,
+        dead_letters: Arc<DeadLetterQueue>,
+    ) -> Self {
+        if relay.is_none() {
+            warn!("No notification relay; notifications will queue undelivered");
+        }
+        Self {
+            store,
+            identity,
+            relay,
+            dead_letters,
+            throttled: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Replace the vault's contacts; an empty list clears them. The caller
+    /// has been authorized.
+    pub fn set_contacts(
+        &self,
+        vault_id: &str,
+        owner: &str,
+        contacts: Vec<Contact>,
+    ) -> Result<ContactSet, String> {
+        if contacts.len() > MAX_CONTACTS {
+            return Err(format!("At most {} contacts are allowed", MAX_CONTACTS));
+        }
+        let mut normalized: Vec<Contact> = Vec::with_capacity(contacts.len());
+        for contact in contacts {
+            let address = match contact.channel {
+                Channel::Email => contact.address.trim().to_lowercase(),
+                Channel::Sms | Channel::Push => contact.address.trim().to_string(),
+            };
+            contact.channel.validate(&address)?;
+            if !normalized.iter().any(|c| {
+                c.channel == contact.channel
+                    && c.address == address
+                    && c.audience == contact.audience
+            }) {
+                normalized.push(Contact {
+                    channel: contact.channel,
+                    address,
+                    audience: contact.audience,
+                });
+            }
+        }
+
+        let set = ContactSet {
+            vault_id: vault_id.to_string(),
+            owner: normalize_address(owner),
+            contacts: normalized,
+            updated_at: now(),
+        };
+        if set.contacts.is_empty() {
+            self.store.update(CONTACTS_NAMESPACE, |ns| {
+                ns.remove(vault_id);
+            })?;
+        } else {
+            self.store.put(CONTACTS_NAMESPACE, vault_id, &set)?;
+        }
+        Ok(set)
+    }
+
+    pub fn contacts(&self, vault_id: &str) -> Result<Option<ContactSet>, String> {
+        self.store.get(CONTACTS_NAMESPACE, vault_id)
+    }
+
+    /// Queue the notice for each of the vault's owner contacts; returns the
+    /// number of jobs queued
+    pub fn notify(&self, vault_id: &str, notice: &Notification) -> Result<usize, String> {
+        self.queue(vault_id, Audience::Owner, notice)
+    }
+
+    /// As `notify`, for the contacts the owner registered for guardians
+    pub fn notify_guardians(&self, vault_id: &str, notice: &Notification) -> Result<usize, String> {
+        self.queue(vault_id, Audience::Guardians, notice)
+    }
+
+    fn queue(
+        &self,
+        vault_id: &str,
+        audience: Audience,
+        notice: &Notification,
+    ) -> Result<usize, String> {
+        let Some(set) = self.contacts(vault_id)? else {
+            return Ok(0);
+        };
+        let created_at = now();
+        let mut jobs = Vec::new();
+        for contact in set.contacts.iter().filter(|c| c.audience == audience) {
+            let mut id = [0u8; 16];
+            OsRng.fill_bytes(&mut id);
+            let record = JobRecord {
+                job: NotificationJob {
+                    job_id: hex::encode(id),
+                    vault_id: vault_id.to_string(),
+                    kind: notice.kind.clone(),
+                    channel: contact.channel,
+                    message: contact.channel.render(&contact.address, notice),
+                    created_at,
+                },
+                status: JobStatus::Pending,
+                attempts: 0,
+                next_attempt_at: created_at,
+                acknowledged_at: None,
+                detail: None,
+            };
+            let value = serde_json::to_value(&record)
+                .map_err(|e| format!("Failed to serialize notification job: {}", e))?;
+            jobs.push((record.job.job_id, value));
+        }
+        let queued = jobs.len();
+        self.store.update(JOBS_NAMESPACE, |ns| ns.extend(jobs))?;
+        Ok(queued)
+    }
+
+    /// As `notify`, but at most once per `window` for the vault and kind,
+    /// for notices a caller can trigger repeatedly
+    pub fn notify_throttled(
+        &self,
+        vault_id: &str,
+        notice: &Notification,
+        window: Duration,
+    ) -> Result<usize, String> {
+        {
+            let mut throttled = self.throttled.lock().unwrap();
+            throttled.retain(|_, sent| sent.elapsed() < window);
+            let key = (vault_id.to_string(), notice.kind.clone());
+            if throttled.contains_key(&key) {
+                return Ok(0);
+            }
+            throttled.insert(key, Instant::now());
+        }
+        self.notify(vault_id, notice)
+    }
+
+    /// The vault's jobs, oldest first
+    pub fn jobs(&self, vault_id: &str) -> Result<Vec<JobRecord>, String> {
+        let mut jobs: Vec<JobRecord> = self
+            .store
+            .list::<JobRecord>(JOBS_NAMESPACE)?
+            .into_iter()
+            .map(|(_, record)| record)
+            .filter(|record| record.job.vault_id == vault_id)
+            .collect();
+        jobs.sort_by_key(|record| record.job.created_at);
+        Ok(jobs)
+    }
+
+    /// Hand due jobs to the relay on the task scheduler
+    pub fn schedule_dispatch(
+        self: Arc<Self>,
+        tasks: &TaskScheduler,
+        every: Duration,
+    ) -> Result<(), String> {
+        if self.relay.is_none() {
+            return Ok(());
+        }
+        let schedule = Schedule::every(every);
+        tasks.register("notifications.dispatch", &schedule, every / 10, move || {
+            let service = self.clone();
+            async move {
+                let sent = service.dispatch().await?;
+                if sent > 0 {
+                    info!("Notification dispatch handed {} jobs to the relay", sent);
+                }
+                Ok(())
+            }
+        })
+    }
+
+    async fn dispatch(&self) -> Result<usize, String> {
+        let now = now();
+        self.store.update(JOBS_NAMESPACE, |ns| {
+            ns.retain(|_, value| {
+                serde_json::from_value::<JobRecord>(value.clone()).is_ok_and(|record| {
+                    record.status == JobStatus::Pending
+                        || record.acknowledged_at.unwrap_or(record.job.created_at) + RETENTION_SECS
+                            > now
+                })
+            });
+        })?;
+        let due: Vec<JobRecord> = self
+            .store
+            .list::<JobRecord>(JOBS_NAMESPACE)?
+            .into_iter()
+            .map(|(_, record)| record)
+            .filter(|record| record.status == JobStatus::Pending && record.next_attempt_at <= now)
+            .take(MAX_BATCH)
+            .collect();
+        if due.is_empty() {
+            return Ok(0);
+        }
+        let Some(relay) = &self.relay else {
+            return Ok(0);
+        };
+
+        let signed = &due
+            .iter()
+            .map(|record| self.sign(&record.job))
+            .collect::<Result<Vec<_>, _>>()?;
+        let outcome = retry::run(Dependency::Notifications, move || async move {
+            let mut acks = Vec::with_capacity(signed.len());
+            let delivered = tokio::time::timeout(RELAY_TIMEOUT, deliver(relay, signed, &mut acks))
+                .await
+                .unwrap_or_else(|_| {
+                    Err(Failure::new(
+                        FailureKind::Timeout,
+                        "Timed out waiting for the notification relay",
+                    ))
+                });
+            match delivered {
+                // Only a batch the relay acknowledged none of may be retried
+                Err(failure) if acks.is_empty() => Err(failure),
+                delivered => Ok((acks, delivered.map_err(|f| f.message))),
+            }
+        })
+        .await;
+        let (acks, delivered) = outcome.unwrap_or_else(|e| (Vec::new(), Err(e)));
+
+        // Acks read before a failure still count
+        let handed = acks.len();
+        for (record, ack) in due.into_iter().zip(acks) {
+            self.acknowledge(record, ack, now)?;
+        }
+        delivered.map(|_| handed)
+    }
+
+    fn acknowledge(&self, mut record: JobRecord, ack: Ack, now: u64) -> Result<(), String> {
+        record.attempts += 1;
+        record.detail = ack.detail;
+        match ack.status {
+            AckStatus::Delivered => {
+                record.status = JobStatus::Delivered;
+                record.acknowledged_at = Some(now);
+            }
+            AckStatus::Rejected => {
+                record.status = JobStatus::Failed;
+                record.acknowledged_at = Some(now);
+            }
+            AckStatus::Retry if record.attempts >= MAX_ATTEMPTS => {
+                record.status = JobStatus::Failed;
+                record.acknowledged_at = Some(now);
+            }
+            AckStatus::Retry => {
+                record.next_attempt_at = now + (RETRY_BASE_SECS << record.attempts);
+            }
+        }
+        let mut batch = Batch::new();
+        if record.status == JobStatus::Failed {
+            warn!(
+                "Notification {} for {} failed after {} attempts",
+                record.job.job_id, record.job.vault_id, record.attempts
+            );
+            self.dead_letters.stage(
+                &mut batch,
+                DeadLetterSource::Notification,
+                &record.job.job_id,
+                &record.job.vault_id,
+                record.detail.clone(),
+                record.attempts,
+                &record,
+            )?;
+        }
+        batch.put(JOBS_NAMESPACE, &record.job.job_id, &record)?;
+        self.store.commit(batch)
+    }
+
+    /// Queue a dead-lettered job again, with fresh attempts
+    pub fn requeue(&self, mut record: JobRecord) -> Result<(), String> {
+        record.status = JobStatus::Pending;
+        record.attempts = 0;
+        record.next_attempt_at = now();
+        record.acknowledged_at = None;
+        self.store.put(JOBS_NAMESPACE, &record.job.job_id, &record)
+    }
+
+    /// Delivered and failed jobs acknowledged before `before`, for the
+    /// archive
+    pub fn settled(&self, before: u64) -> Result<Vec<JobRecord>, String> {
+        Ok(self
+            .store
+            .list::<JobRecord>(JOBS_NAMESPACE)?
+            .into_iter()
+            .map(|(_, record)| record)
+            .filter(|record| {
+                record.status != JobStatus::Pending
+                    && record.acknowledged_at.unwrap_or(record.job.created_at) < before
+            })
+            .collect())
+    }
+
+    /// Commit `batch` with the archived jobs removed, except any requeued
+    /// or acknowledged again since
+    pub fn remove_archived(&self, mut batch: Batch, records: &[JobRecord]) -> Result<(), String> {
+        for record in records {
+            let current: Option<JobRecord> = self.store.get(JOBS_NAMESPACE, &record.job.job_id)?;
+            if current.is_some_and(|current| {
+                current.status == record.status && current.acknowledged_at == record.acknowledged_at
+            }) {
+                batch.remove(JOBS_NAMESPACE, &record.job.job_id);
+            }
+        }
+        self.store.commit(batch)
+    }
+
+    fn sign(&self, job: &NotificationJob) -> Result<(String, Vec<u8>), String> {
+        let payload = serde_json::to_vec(job).map_err(|e| e.to_string())?;
+        let signature = self.identity.sign(&[DOMAIN, &payload].concat());
+        let mut line = serde_json::to_vec(&SignedJob {
+            job,
+            key_id: self.identity.key_id(),
+            signature: STANDARD.encode(signature.to_bytes()),
+        })
+        .map_err(|e| format!("Failed to encode notification job: {}", e))?;
+        line.push(b'\n');
+        Ok((job.job_id.clone(), line))
+    }
+}
+
+/// Sends each (job id, line) and collects its ack into `acks`, in order
+async fn deliver(
+    relay: &ParentRelay,
+    jobs: &[(String, Vec<u8>)],
+    acks: &mut Vec<Ack>,
+) -> Result<(), Failure> {
+    let unreachable = |message: String| Failure::new(FailureKind::Unreachable, message);
+    match relay {
+        ParentRelay::Tcp(addr) => {
+            let stream = tokio::net::TcpStream::connect(addr).await.map_err(|e| {
+                unreachable(format!(
+                    "Failed to reach notification relay at {}: {}",
+                    addr, e
+                ))
+            })?;
+            exchange(stream, jobs, acks).await
+        }
+        #[cfg(feature = "vsock")]
+        ParentRelay::Vsock { port } => {
+            let stream = tokio_vsock::VsockStream::connect(tokio_vsock::VsockAddr::new(
+                crate::bootstrap::PARENT_CID,
+                *port,
+            ))
+            .await
+            .map_err(|e| {
+                unreachable(format!(
+                    "Failed to reach notification relay on vsock port {}: {}",
+                    port, e
+                ))
+            })?;
+            exchange(stream, jobs, acks).await
+        }
+        #[cfg(not(feature = "vsock"))]
+        ParentRelay::Vsock { port } => Err(unreachable(format!(
+            "VSOCK notification relay (port {}) requires building with the `vsock` feature",
+            port
+        ))),
+    }
+}
+
+async fn exchange<S>(
+    stream: S,
+    jobs: &[(String, Vec<u8>)],
+    acks: &mut Vec<Ack>,
+) -> Result<(), Failure>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let transport = |message: String| Failure::new(FailureKind::Transport, message);
+    let malformed = |message: String| Failure::new(FailureKind::Malformed, message);
+    let mut stream = BufReader::new(stream);
+    for (job_id, line) in jobs {
+        stream
+            .get_mut()
+            .write_all(line)
+            .await
+            .map_err(|e| transport(format!("Failed to send notification job: {}", e)))?;
+
+        let mut response = String::new();
+        let read = stream
+            .read_line(&mut response)
+            .await
+            .map_err(|e| transport(format!("Failed to read relay ack: {}", e)))?;
+        if read == 0 {
+            return Err(transport(
+                "Notification relay closed the connection".to_string(),
+            ));
+        }
+        let ack: Ack = serde_json::from_str(&response)
+            .map_err(|e| malformed(format!("Malformed relay ack: {}", e)))?;
+        if ack.job_id != *job_id {
+            return Err(malformed(format!(
+                "Relay acknowledged {} while {} was outstanding",
+                ack.job_id, job_id
+            )));
+        }
+        acks.push(ack);
+    }
+    Ok(())
+}
+
+fn valid_email(address: &str) -> bool {
+    let Some((local, domain)) = address.split_once('@') else {
+        return false;
+    };
+    address.len() <= 254
+        && !local.is_empty()
+        && !domain.contains('@')
+        && domain.contains('.')
+        && !domain.starts_with('.')
+        && !domain.ends_with('.')
+        && !address.chars().any(|c| c.is_whitespace() || c.is_control())
+}
+
+fn valid_phone(address: &str) -> bool {
+    address.strip_prefix('+').is_some_and(|digits| {
+        (8..=15).contains(&digits.len()) && digits.chars().all(|c| c.is_ascii_digit())
+    })
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
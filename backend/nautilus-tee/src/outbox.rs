@@ -0,0 +1,503 @@
+/**
+ * Chain Outbox
+ * On-chain submissions recorded together with the decision that needs them
+ *
+ * When a decision should be reflected on chain (a vault's ladder reaching
+ * unlock), the caller stages an intent in the same store Batch as its own
+ * state change, so either both are recorded or neither is. An intent's id
+ * is derived from its kind, vault and an idempotency key, so staging the
+ * same decision twice yields one intent, and the contract can ignore a
+ * submission it has already seen.
+ *
+ * The dispatcher hands pending intents, signed with the enclave identity,
+ * to the submitter behind the parent's chain relay as `lumina_submitIntent`
+ * calls; failures retry with backoff, up to MAX_ATTEMPTS. A submitted
+ * intent is only confirmed once the contract's confirmation event (with an
+ * `intent_id` field) is observed; reconciliation reads those events from a
+ * cursor kept in the store. One submitted but unconfirmed for
+ * CONFIRM_TIMEOUT_SECS is submitted again. An intent that runs out of
+ * attempts is dead-lettered for admins to retry or discard.
+ *
+ * Without a relay configured, intents queue undelivered.
+ */
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+use crate::chain;
+use crate::config::OutboxConfig;
+use crate::dead_letters::{DeadLetterQueue, DeadLetterSource};
+use crate::keys::identity::EnclaveIdentity;
+use crate::store::{Batch, Store};
+use crate::tasks::{Schedule, TaskScheduler};
+
+const NAMESPACE: &str = "chain_outbox";
+const CURSORS_NAMESPACE: &str = "chain_outbox_cursors";
+const DOMAIN: &[u8] = b"lumina-chain-intent-v1:";
+const MAX_ATTEMPTS: u32 = 8;
+const RETRY_BASE_SECS: u64 = 30;
+const CONFIRM_TIMEOUT_SECS: u64 = 15 * 60;
+/// Confirmed and failed intents are kept this long for admins to inspect
+const RETENTION_SECS: u64 = 30 * 24 * 60 * 60;
+const MAX_BATCH: usize = 20;
+const PAGE_LIMIT: usize = 50;
+const MAX_PAGES: usize = 20;
+
+/// Signed and handed to the submitter as is
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ChainIntent {
+    pub intent_id: String,
+    /// What the contract should record, e.g. "vault_unlocked"
+    pub kind: String,
+    pub vault_id: String,
+    pub arguments: Value,
+    pub created_at: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IntentStatus {
+    Pending,
+    Submitted,
+    Confirmed,
+    Failed,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct IntentRecord {
+    #[serde(flatten)]
+    pub intent: ChainIntent,
+    pub status: IntentStatus,
+    pub attempts: u32,
+    pub next_attempt_at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub submitted_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confirmed_at: Option<u64>,
+    /// The submitter's transaction, then the one the confirmation came in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tx_digest: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct SignedIntent<'a> {
+    #[serde(flatten)]
+    intent: &'a ChainIntent,
+    key_id: String,
+    signature: String, // Base64 Ed25519 over DOMAIN || intent JSON (fields in the order above)
+}
+
+#[derive(Deserialize)]
+struct Submission {
+    digest: String,
+}
+
+pub struct ChainOutbox {
+    store: Arc<Store>,
+    identity: Arc<EnclaveIdentity>,
+    config: Option<OutboxConfig>,
+    dead_letters: Arc<DeadLetterQueue>,
+    /// Serializes read-modify-write of intent records
+    writes: Mutex<()>,
+}
+
+impl ChainOutbox {
+    pub fn new(
+        store: Arc<Store>,
+        identity: Arc<EnclaveIdentity>,
+        config: Option<OutboxConfig>,
+        dead_letters: Arc<DeadLetterQueue>,
+    ) -> Self {
+        if config.is_none() {
+            warn!("No chain outbox relay; on-chain intents will queue undelivered");
+        }
+        Self {
+            store,
+            identity,
+            config,
+            dead_letters,
+            writes: Mutex::new(()),
+        }
+    }
+
+    /// Add an intent to `batch`, unless the same one is already recorded;
+    /// returns its id. It is only queued once the batch is committed.
+    pub fn stage(
+        &self,
+        batch: &mut Batch,
+        kind: &str,
+        vault_id: &str,
+        idempotency_key: &str,
+        arguments: Value,
+    ) -> Result<String, String> {
+        let intent_id = intent_id(kind, vault_id, idempotency_key);
+        if self
+            .store
+            .get::<IntentRecord>(NAMESPACE, &intent_id)?
+            .is_some()
+        {
+            return Ok(intent_id);
+        }
+        let created_at = now();
+        let record = IntentRecord {
+            intent: ChainIntent {
+                intent_id: intent_id.clone(),
+                kind: kind.to_string(),
+                vault_id: vault_id.to_string(),
+                arguments,
+                created_at,
+            },
+            status: IntentStatus::Pending,
+            attempts: 0,
+            next_attempt_at: created_at,
+            submitted_at: None,
+            confirmed_at: None,
+            tx_digest: None,
+            last_error: None,
+        };
+        batch.put(NAMESPACE, &intent_id, &record)?;
+        Ok(intent_id)
+    }
+
+    /// Every recorded intent, newest first
+    pub fn intents(&self) -> Result<Vec<IntentRecord>, String> {
+        let mut intents: Vec<IntentRecord> = self
+            .store
+            .list::<IntentRecord>(NAMESPACE)?
+            .into_iter()
+            .map(|(_, record)| record)
+            .collect();
+        intents.sort_by_key(|record| std::cmp::Reverse(record.intent.created_at));
+        Ok(intents)
+    }
+
+    /// Queue a dead-lettered intent again, with fresh attempts
+    pub fn requeue(&self, mut record: IntentRecord) -> Result<(), String> {
+        let _writes = self.writes.lock().unwrap();
+        let intent_id = record.intent.intent_id.clone();
+        if let Some(current) = self.store.get::<IntentRecord>(NAMESPACE, &intent_id)? {
+            if current.status == IntentStatus::Confirmed {
+                return Err("Intent is already confirmed".to_string());
+            }
+        }
+        record.status = IntentStatus::Pending;
+        record.attempts = 0;
+        record.next_attempt_at = now();
+        record.submitted_at = None;
+        self.store.put(NAMESPACE, &intent_id, &record)
+    }
+
+    /// Confirmed and failed intents settled before `before`, for the
+    /// archive
+    pub fn settled(&self, before: u64) -> Result<Vec<IntentRecord>, String> {
+        Ok(self
+            .store
+            .list::<IntentRecord>(NAMESPACE)?
+            .into_iter()
+            .map(|(_, record)| record)
+            .filter(|record| settled_at(record).is_some_and(|since| since < before))
+            .collect())
+    }
+
+    /// Commit `batch` with the archived intents removed, except any
+    /// requeued since
+    pub fn remove_archived(
+        &self,
+        mut batch: Batch,
+        records: &[IntentRecord],
+    ) -> Result<(), String> {
+        let _writes = self.writes.lock().unwrap();
+        for record in records {
+            let intent_id = &record.intent.intent_id;
+            let current: Option<IntentRecord> = self.store.get(NAMESPACE, intent_id)?;
+            if current.is_some_and(|current| current.status == record.status) {
+                batch.remove(NAMESPACE, intent_id);
+            }
+        }
+        self.store.commit(batch)
+    }
+
+    /// Submit due intents and reconcile confirmations on the task scheduler
+    pub fn schedule(self: Arc<Self>, tasks: &TaskScheduler) -> Result<(), String> {
+        let Some(config) = self.config.clone() else {
+            return Ok(());
+        };
+        let outbox = self.clone();
+        let schedule = Schedule::every(config.dispatch);
+        tasks.register(
+            "outbox.dispatch",
+            &schedule,
+            config.dispatch / 10,
+            move || {
+                let outbox = outbox.clone();
+                async move {
+                    let submitted = outbox.dispatch().await?;
+                    if submitted > 0 {
+                        info!("Chain outbox submitted {} intents", submitted);
+                    }
+                    Ok(())
+                }
+            },
+        )?;
+        let schedule = Schedule::every(config.reconcile);
+        tasks.register(
+            "outbox.reconcile",
+            &schedule,
+            config.reconcile / 10,
+            move || {
+                let outbox = self.clone();
+                async move {
+                    let confirmed = outbox.reconcile().await?;
+                    if confirmed > 0 {
+                        info!("Chain outbox confirmed {} intents", confirmed);
+                    }
+                    Ok(())
+                }
+            },
+        )
+    }
+
+    async fn dispatch(&self) -> Result<usize, String> {
+        let Some(config) = &self.config else {
+            return Ok(0);
+        };
+        let now = now();
+        self.prune(now)?;
+        let mut due: Vec<IntentRecord> = self
+            .store
+            .list::<IntentRecord>(NAMESPACE)?
+            .into_iter()
+            .map(|(_, record)| record)
+            .filter(|record| match record.status {
+                IntentStatus::Pending => record.next_attempt_at <= now,
+                IntentStatus::Submitted => {
+                    record.submitted_at.unwrap_or(0) + CONFIRM_TIMEOUT_SECS <= now
+                }
+                IntentStatus::Confirmed | IntentStatus::Failed => false,
+            })
+            .collect();
+        due.sort_by_key(|record| record.intent.created_at);
+        due.truncate(MAX_BATCH);
+
+        let mut submitted = 0;
+        for record in due {
+            let result = self.submit(config, &record.intent).await;
+            if result.is_ok() {
+                submitted += 1;
+            }
+            self.record_attempt(&record.intent.intent_id, result, now)?;
+        }
+        Ok(submitted)
+    }
+
+    /// Drop settled intents past RETENTION_SECS
+    fn prune(&self, now: u64) -> Result<(), String> {
+        let _writes = self.writes.lock().unwrap();
+        self.store.update(NAMESPACE, |ns| {
+            ns.retain(|_, value| {
+                serde_json::from_value::<IntentRecord>(value.clone()).is_ok_and(|record| {
+                    settled_at(&record).is_none_or(|since| since + RETENTION_SECS > now)
+                })
+            });
+        })
+    }
+
+    async fn submit(&self, config: &OutboxConfig, intent: &ChainIntent) -> Result<String, String> {
+        let payload = serde_json::to_vec(intent).map_err(|e| e.to_string())?;
+        let signature = self.identity.sign(&[DOMAIN, &payload].concat());
+        let signed = SignedIntent {
+            intent,
+            key_id: self.identity.key_id(),
+            signature: STANDARD.encode(signature.to_bytes()),
+        };
+        let submission: Submission =
+            chain::call(&config.relay, "lumina_submitIntent", json!([signed])).await?;
+        Ok(submission.digest)
+    }
+
+    /// Apply a submission's outcome, unless the intent was confirmed while
+    /// it was in flight
+    fn record_attempt(
+        &self,
+        intent_id: &str,
+        result: Result<String, String>,
+        now: u64,
+    ) -> Result<(), String> {
+        let _writes = self.writes.lock().unwrap();
+        let Some(mut record) = self.store.get::<IntentRecord>(NAMESPACE, intent_id)? else {
+            return Ok(());
+        };
+        if record.status == IntentStatus::Confirmed {
+            return Ok(());
+        }
+        record.attempts += 1;
+        let mut batch = Batch::new();
+        match result {
+            Ok(digest) => {
+                record.status = IntentStatus::Submitted;
+                record.submitted_at = Some(now);
+                record.tx_digest = Some(digest);
+                record.last_error = None;
+            }
+            Err(e) if record.attempts >= MAX_ATTEMPTS => {
+                record.status = IntentStatus::Failed;
+                record.last_error = Some(e);
+                warn!(
+                    "Chain intent {} failed after {} attempts",
+                    intent_id, record.attempts
+                );
+                self.dead_letters.stage(
+                    &mut batch,
+                    DeadLetterSource::ChainIntent,
+                    intent_id,
+                    &record.intent.vault_id,
+                    record.last_error.clone(),
+                    record.attempts,
+                    &record,
+                )?;
+            }
+            Err(e) => {
+                record.status = IntentStatus::Pending;
+                record.next_attempt_at = now + (RETRY_BASE_SECS << record.attempts);
+                record.last_error = Some(e);
+            }
+        }
+        batch.put(NAMESPACE, intent_id, &record)?;
+        self.store.commit(batch)
+    }
+
+    async fn reconcile(&self) -> Result<usize, String> {
+        let Some(config) = &self.config else {
+            return Ok(0);
+        };
+        let event_type = &config.confirmed_event;
+        let mut cursor: Option<Value> = self.store.get(CURSORS_NAMESPACE, event_type)?;
+        let mut confirmed = 0;
+        for _ in 0..MAX_PAGES {
+            let page =
+                chain::query_events(&config.relay, event_type, cursor.as_ref(), PAGE_LIMIT).await?;
+            for event in &page.data {
+                let Some(intent_id) = chain::field_string(&event.parsed_json, "intent_id") else {
+                    warn!(
+                        "Confirmation {}:{} has no intent_id field",
+                        event.id.tx_digest, event.id.event_seq
+                    );
+                    continue;
+                };
+                if self.confirm(&intent_id, &event.id.tx_digest)? {
+                    confirmed += 1;
+                }
+            }
+            if let Some(next) = page.next_cursor {
+                self.store.put(CURSORS_NAMESPACE, event_type, &next)?;
+                cursor = Some(next);
+            }
+            if !page.has_next_page {
+                break;
+            }
+        }
+        Ok(confirmed)
+    }
+
+    /// Mark the intent confirmed, dropping its dead letter if it had one;
+    /// false for intents this enclave didn't stage or already confirmed
+    fn confirm(&self, intent_id: &str, tx_digest: &str) -> Result<bool, String> {
+        let _writes = self.writes.lock().unwrap();
+        let Some(mut record) = self.store.get::<IntentRecord>(NAMESPACE, intent_id)? else {
+            return Ok(false);
+        };
+        if record.status == IntentStatus::Confirmed {
+            return Ok(false);
+        }
+        let mut batch = Batch::new();
+        if record.status == IntentStatus::Failed {
+            self.dead_letters
+                .stage_removal(&mut batch, DeadLetterSource::ChainIntent, intent_id);
+        }
+        record.status = IntentStatus::Confirmed;
+        record.confirmed_at = Some(now());
+        record.tx_digest = Some(tx_digest.to_string());
+        record.last_error = None;
+        batch.put(NAMESPACE, intent_id, &record)?;
+        self.store.commit(batch)?;
+        Ok(true)
+    }
+}
+
+/// Hex, the first 16 bytes of SHA-256 over kind, vault and key
+pub fn intent_id(kind: &str, vault_id: &str, idempotency_key: &str) -> String {
+    let digest = Sha256::new()
+        .chain_update(kind.as_bytes())
+        .chain_update([0])
+        .chain_update(vault_id.as_bytes())
+        .chain_update([0])
+        .chain_update(idempotency_key.as_bytes())
+        .finalize();
+    hex::encode(&digest[..16])
+}
+
+/// When the intent settled; None while it may still change
+fn settled_at(record: &IntentRecord) -> Option<u64> {
+    matches!(
+        record.status,
+        IntentStatus::Confirmed | IntentStatus::Failed
+    )
+    .then(|| record.confirmed_at.unwrap_or(record.intent.created_at))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn staged_intents_commit_with_their_batch_once() {
+        let dir = std::env::temp_dir().join(format!("lumina-outbox-tests-{}", now()));
+        let store = Arc::new(Store::open(&dir).unwrap());
+        let identity = Arc::new(EnclaveIdentity::ephemeral("test-measurement", "test"));
+        let dead_letters = Arc::new(DeadLetterQueue::new(store.clone(), 25));
+        let outbox = ChainOutbox::new(store.clone(), identity, None, dead_letters);
+
+        let mut batch = Batch::new();
+        batch
+            .put("ladders", "vault-1", &json!({ "stage": 3 }))
+            .unwrap();
+        let id = outbox
+            .stage(&mut batch, "vault_unlocked", "vault-1", "100", json!({}))
+            .unwrap();
+        assert!(outbox.intents().unwrap().is_empty());
+        store.commit(batch).unwrap();
+        assert_eq!(outbox.intents().unwrap().len(), 1);
+
+        let mut again = Batch::new();
+        outbox
+            .stage(&mut again, "vault_unlocked", "vault-1", "100", json!({}))
+            .unwrap();
+        assert!(again.is_empty());
+
+        assert!(outbox.confirm(&id, "digest").unwrap());
+        assert!(!outbox.confirm(&id, "digest").unwrap());
+        assert_eq!(outbox.intents().unwrap()[0].status, IntentStatus::Confirmed);
+
+        // A fresh store sees both namespaces of the batch
+        let reopened = Store::open(&dir).unwrap();
+        assert!(reopened
+            .get::<Value>("ladders", "vault-1")
+            .unwrap()
+            .is_some());
+    }
+}
@@ -0,0 +1,216 @@
+/**
+ * Pagination
+ * Cursor pages, limit caps and time-range and status filters shared by list
+ * endpoints
+ *
+ * List endpoints take a PageQuery from their query string (signed along
+ * with the path on signed routes) and return at most `limit` items, oldest
+ * first, with a cursor for the next page when more remain:
+ *
+ *   cursor  opaque; the position after the last item returned
+ *   limit   1 to MAX_LIMIT items, DEFAULT_LIMIT when omitted
+ *   since   unix seconds, inclusive
+ *   until   unix seconds, exclusive
+ *   status  one of the list's statuses, for lists whose items have one
+ *
+ * Items are ordered by timestamp and then id, so a cursor keeps its place
+ * while items are added after it. Filters aren't part of the cursor; each
+ * page request repeats them.
+ */
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_LIMIT: usize = 100;
+pub const MAX_LIMIT: usize = 500;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PageQuery {
+    pub cursor: Option<String>,
+    pub limit: Option<usize>,
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+    pub status: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Absent on the last page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+/// An item of a paginated list
+pub trait Paged {
+    /// What `status` may filter by; lists without statuses refuse the filter
+    const STATUSES: &'static [&'static str] = &[];
+
+    /// Unix seconds the time range applies to
+    fn timestamp(&self) -> u64;
+
+    /// Unique within the list, ordering items with equal timestamps
+    fn id(&self) -> String;
+
+    fn status(&self) -> Option<&'static str> {
+        None
+    }
+}
+
+/// The page of `items` that `query` asks for
+pub fn paginate<T: Paged>(items: Vec<T>, query: &PageQuery) -> Result<Page<T>, String> {
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT);
+    if limit == 0 {
+        return Err("limit must be at least 1".to_string());
+    }
+    if let (Some(since), Some(until)) = (query.since, query.until) {
+        if since >= until {
+            return Err("since must be before until".to_string());
+        }
+    }
+    if let Some(status) = &query.status {
+        if !T::STATUSES.contains(&status.as_str()) {
+            return Err(match T::STATUSES {
+                [] => "This list can't be filtered by status".to_string(),
+                statuses => format!("status must be one of {}", statuses.join(", ")),
+            });
+        }
+    }
+    let after = query.cursor.as_deref().map(decode_cursor).transpose()?;
+
+    let mut items: Vec<(u64, String, T)> = items
+        .into_iter()
+        .filter(|item| query.since.is_none_or(|since| item.timestamp() >= since))
+        .filter(|item| query.until.is_none_or(|until| item.timestamp() < until))
+        .filter(|item| {
+            query
+                .status
+                .as_deref()
+                .is_none_or(|status| item.status() == Some(status))
+        })
+        .map(|item| (item.timestamp(), item.id(), item))
+        .collect();
+    items.sort_by(|a, b| (a.0, &a.1).cmp(&(b.0, &b.1)));
+
+    let mut page: Vec<(u64, String, T)> = items
+        .into_iter()
+        .filter(|(timestamp, id, _)| {
+            after
+                .as_ref()
+                .is_none_or(|(at, after_id)| (*timestamp, id) > (*at, after_id))
+        })
+        .take(limit + 1)
+        .collect();
+    let next_cursor = if page.len() > limit {
+        page.truncate(limit);
+        page.last()
+            .map(|(timestamp, id, _)| encode_cursor(*timestamp, id))
+    } else {
+        None
+    };
+    Ok(Page {
+        items: page.into_iter().map(|(_, _, item)| item).collect(),
+        next_cursor,
+    })
+}
+
+fn encode_cursor(timestamp: u64, id: &str) -> String {
+    URL_SAFE_NO_PAD.encode(format!("{}:{}", timestamp, id))
+}
+
+fn decode_cursor(cursor: &str) -> Result<(u64, String), String> {
+    let invalid = || "Invalid cursor".to_string();
+    let decoded = URL_SAFE_NO_PAD.decode(cursor).map_err(|_| invalid())?;
+    let decoded = String::from_utf8(decoded).map_err(|_| invalid())?;
+    let (timestamp, id) = decoded.split_once(':').ok_or_else(invalid)?;
+    Ok((timestamp.parse().map_err(|_| invalid())?, id.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Item(u64, &'static str, &'static str);
+
+    impl Paged for Item {
+        const STATUSES: &'static [&'static str] = &["open", "closed"];
+
+        fn timestamp(&self) -> u64 {
+            self.0
+        }
+
+        fn id(&self) -> String {
+            self.1.to_string()
+        }
+
+        fn status(&self) -> Option<&'static str> {
+            Some(self.2)
+        }
+    }
+
+    fn items() -> Vec<Item> {
+        vec![
+            Item(30, "c", "open"),
+            Item(10, "a", "closed"),
+            Item(20, "b", "open"),
+            Item(20, "a", "open"),
+            Item(40, "d", "closed"),
+        ]
+    }
+
+    fn ids(page: &Page<Item>) -> Vec<(u64, &'static str)> {
+        page.items.iter().map(|item| (item.0, item.1)).collect()
+    }
+
+    #[test]
+    fn cursors_walk_every_item_once() {
+        let mut query = PageQuery {
+            limit: Some(2),
+            ..Default::default()
+        };
+        let mut seen = Vec::new();
+        loop {
+            let page = paginate(items(), &query).unwrap();
+            seen.extend(ids(&page));
+            match page.next_cursor {
+                Some(cursor) => query.cursor = Some(cursor),
+                None => break,
+            }
+        }
+        assert_eq!(
+            seen,
+            vec![(10, "a"), (20, "a"), (20, "b"), (30, "c"), (40, "d")]
+        );
+    }
+
+    #[test]
+    fn filters_apply_before_paging() {
+        let query = PageQuery {
+            since: Some(20),
+            until: Some(40),
+            status: Some("open".to_string()),
+            ..Default::default()
+        };
+        let page = paginate(items(), &query).unwrap();
+        assert_eq!(ids(&page), vec![(20, "a"), (20, "b"), (30, "c")]);
+        assert!(page.next_cursor.is_none());
+
+        for bad in [
+            PageQuery {
+                status: Some("gone".to_string()),
+                ..Default::default()
+            },
+            PageQuery {
+                limit: Some(0),
+                ..Default::default()
+            },
+            PageQuery {
+                cursor: Some("not a cursor".to_string()),
+                ..Default::default()
+            },
+        ] {
+            assert!(paginate(items(), &bad).is_err());
+        }
+    }
+}
@@ -0,0 +1,131 @@
+/**
+ * Peer Authentication
+ * Verifies calls from other enclaves by their attestation
+ *
+ * Enclaves and attested services calling us (replication, shard
+ * forwarding) don't hold a Sui key; they prove what they are instead. A
+ * peer request carries an attestation for operation "peer_request" whose
+ * user_data is SHA-256 of the canonical request (see auth), plus the
+ * timestamp and nonce it covers:
+ *
+ *   x-lumina-peer-attestation  base64 JSON attestation
+ *   x-lumina-timestamp, x-lumina-nonce
+ *
+ * The attestation must verify, come from a build on the measurement
+ * allowlist (see allowlist) and commit to
+ * exactly this request, and its nonce is recorded by the replay guard, so
+ * an attestation can't be detached and replayed. Handlers read the
+ * verified peer from the `VerifiedPeer` extension.
+ */
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::Response,
+};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+use crate::allowlist::MeasurementAllowlist;
+use crate::attestation::{Attestation, AttestationService};
+use crate::auth::{canonical_request, NONCE_HEADER, TIMESTAMP_HEADER};
+use crate::error::AppError;
+use crate::replay::ReplayGuard;
+
+pub const PEER_ATTESTATION_HEADER: &str = "x-lumina-peer-attestation";
+pub const OPERATION: &str = "peer_request";
+
+const MAX_PEER_BODY_BYTES: usize = 8 * 1024 * 1024;
+
+/// The enclave behind a peer request, available to handlers via `Extension`
+#[derive(Clone, Debug)]
+pub struct VerifiedPeer {
+    pub measurement: String, // PCR0
+}
+
+#[derive(Clone)]
+pub struct PeerAuthState {
+    pub attestation: Arc<AttestationService>,
+    pub replay: Arc<ReplayGuard>,
+    pub allowlist: Arc<MeasurementAllowlist>,
+}
+
+pub async fn require_peer_attestation(
+    State(peers): State<PeerAuthState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let (parts, body) = request.into_parts();
+    let body = to_bytes(body, MAX_PEER_BODY_BYTES)
+        .await
+        .map_err(|_| AppError::bad_request("BODY_TOO_LARGE", "Request body too large to verify"))?;
+
+    let header = |name: &str| {
+        parts
+            .headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .ok_or_else(|| {
+                AppError::unauthorized(
+                    "MISSING_PEER_ATTESTATION",
+                    format!("Missing {} header", name),
+                )
+            })
+    };
+    let attestation: Attestation = STANDARD
+        .decode(header(PEER_ATTESTATION_HEADER)?)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .ok_or_else(|| {
+            AppError::bad_request("MALFORMED_PEER_ATTESTATION", "Malformed peer attestation header")
+        })?;
+    let timestamp: u64 = header(TIMESTAMP_HEADER)?.parse().map_err(|_| {
+        AppError::bad_request("INVALID_TIMESTAMP", "Timestamp must be unix seconds")
+    })?;
+    let nonce = header(NONCE_HEADER)?.to_string();
+    peers.replay.check_timestamp(timestamp)?;
+
+    let verified = peers
+        .attestation
+        .verify(&attestation)
+        .map_err(|e| AppError::unauthorized("PEER_ATTESTATION_INVALID", e.to_string()))?;
+    let path = parts
+        .uri
+        .path_and_query()
+        .map(|p| p.as_str())
+        .unwrap_or_else(|| parts.uri.path());
+    let message = canonical_request(parts.method.as_str(), path, &body, timestamp, &nonce);
+    let expected = hex::encode(Sha256::digest(message.as_bytes()));
+    if verified.operation != OPERATION || verified.user_data.as_deref() != Some(expected.as_str()) {
+        return Err(AppError::unauthorized(
+            "PEER_ATTESTATION_MISMATCH",
+            "Peer attestation does not cover this request",
+        ));
+    }
+    if !peers
+        .allowlist
+        .allows(&verified)
+        .map_err(AppError::internal)?
+    {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "PEER_NOT_ALLOWED",
+            format!("Measurement {} is not an allowed peer", verified.pcr0),
+        ));
+    }
+
+    // Recorded last, so unverified requests can't burn a peer's nonces
+    peers
+        .replay
+        .record(&format!("peer:{}", verified.pcr0), &nonce, timestamp)?;
+
+    let mut request = Request::from_parts(parts, Body::from(body));
+    request.extensions_mut().insert(VerifiedPeer {
+        measurement: verified.pcr0,
+    });
+    Ok(next.run(request).await)
+}
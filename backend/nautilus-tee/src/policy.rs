@@ -0,0 +1,645 @@
+/**
+ * Policy Engine
+ * Central place where the enclave decides whether sensitive operations
+ * (key reconstruction, release) may proceed
+ */
+
+use serde::Serialize;
+
+use crate::escalation::{Evidence, Stage};
+use crate::items::ItemPolicy;
+use crate::jurisdictions::JurisdictionStatus;
+use crate::policy_bundles::BundleDecision;
+use crate::release::BeneficiarySet;
+use crate::secrets::ShareSet;
+use crate::threshold::ThresholdKeySet;
+use crate::vault_templates::ReleaseRules;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Decision {
+    pub allowed: bool,
+    pub reason: String,
+}
+
+impl Decision {
+    fn allow(reason: impl Into<String>) -> Self {
+        Self {
+            allowed: true,
+            reason: reason.into(),
+        }
+    }
+
+    fn deny(reason: impl Into<String>) -> Self {
+        Self {
+            allowed: false,
+            reason: reason.into(),
+        }
+    }
+}
+
+pub struct PolicyEngine {
+    admins: Vec<String>,
+    signing_purposes: Vec<String>,
+}
+
+impl PolicyEngine {
+    pub fn new(admins: Vec<String>, signing_purposes: Vec<String>) -> Self {
+        Self {
+            admins,
+            signing_purposes,
+        }
+    }
+
+    /// Administrative operations are limited to configured admin addresses
+    pub fn evaluate_admin(&self, caller: &str) -> Decision {
+        if !self.admins.iter().any(|a| a == caller) {
+            return Decision::deny("Caller is not an enclave admin");
+        }
+        Decision::allow("Caller is an enclave admin")
+    }
+
+    /// Key material is dealt for a vault (its key split among guardians,
+    /// a threshold key, ...) only once the vault is registered, and only by
+    /// one of its owners; dealing never claims the vault
+    pub fn evaluate_owner_action(
+        &self,
+        caller: &str,
+        registered: bool,
+        owners: &[String],
+        action: &str,
+    ) -> Decision {
+        if !registered {
+            return Decision::deny(format!("Register the vault before {}", action));
+        }
+        if !owners.iter().any(|o| o == caller) {
+            return Decision::deny(format!(
+                "Caller is not a vault owner, as needed for {}",
+                action
+            ));
+        }
+        Decision::allow("Caller owns the registered vault")
+    }
+
+    /// A vault key may only be reassembled once a threshold of guardians
+    /// have resubmitted shares that verified against their commitments
+    pub fn evaluate_share_reconstruction(&self, share_set: &ShareSet, verified: usize) -> Decision {
+        if verified < share_set.threshold as usize {
+            return Decision::deny(format!(
+                "{} of {} required guardian shares verified",
+                verified, share_set.threshold
+            ));
+        }
+        Decision::allow("Guardian share threshold met")
+    }
+
+    /// Partial decryptions are combined once enough guardians have
+    /// submitted partials with valid proofs
+    pub fn evaluate_threshold_decryption(
+        &self,
+        key_set: &ThresholdKeySet,
+        verified: usize,
+    ) -> Decision {
+        if verified < key_set.threshold as usize {
+            return Decision::deny(format!(
+                "{} of {} required partial decryptions verified",
+                verified, key_set.threshold
+            ));
+        }
+        Decision::allow("Partial decryption threshold met")
+    }
+
+    /// Combined plaintext only goes to whoever opened the decryption session
+    pub fn evaluate_threshold_release(
+        &self,
+        requester: &str,
+        caller: &str,
+        combined: bool,
+    ) -> Decision {
+        if !combined {
+            return Decision::deny("Not enough partial decryptions have been combined");
+        }
+        if requester != caller {
+            return Decision::deny("Only the session requester may receive the plaintext");
+        }
+        Decision::allow("Threshold decryption complete")
+    }
+
+    /// A vault with item policies is only released item by item, so the
+    /// whole payload can't be taken around them
+    pub fn evaluate_full_release(&self, itemized: bool) -> Decision {
+        if itemized {
+            return Decision::deny("Vault items are released individually");
+        }
+        Decision::allow("Vault has no item policies")
+    }
+
+    /// Raw plaintext can't leave a vault whose releases are redacted
+    pub fn evaluate_unredacted_release(&self, redacted: bool) -> Decision {
+        if redacted {
+            return Decision::deny("Vault content is only released through redaction");
+        }
+        Decision::allow("Vault has no redaction policy")
+    }
+
+    /// An item goes to its listed recipients once its release time has
+    /// passed and, if it names one, its approval is current
+    pub fn evaluate_item_release(
+        &self,
+        item: &ItemPolicy,
+        caller: &str,
+        now: u64,
+        approved: bool,
+    ) -> Decision {
+        if !item.recipients.iter().any(|r| r == caller) {
+            return Decision::deny("Caller is not a recipient of this item");
+        }
+        if let Some(not_before) = item.not_before {
+            if now < not_before {
+                return Decision::deny(format!("Item is sealed until {}", not_before));
+            }
+        }
+        if let Some(operation) = &item.approval {
+            if !approved {
+                return Decision::deny(format!(
+                    "Item needs a current guardian approval for {}",
+                    operation
+                ));
+            }
+        }
+        Decision::allow("Item release conditions met")
+    }
+
+    /// Unlocked vault content goes only to registered beneficiaries, and
+    /// only its owner, guardians or beneficiaries may ask for that
+    pub fn evaluate_vault_release(
+        &self,
+        share_set: &ShareSet,
+        beneficiaries: &BeneficiarySet,
+        caller: &str,
+        itemized: bool,
+        reconstructed: bool,
+    ) -> Decision {
+        let whole = self.evaluate_full_release(itemized);
+        if !whole.allowed {
+            return whole;
+        }
+        if !reconstructed {
+            return Decision::deny("Guardians have not reassembled the vault key");
+        }
+        let party = share_set.owner == caller
+            || share_set.guardians.iter().any(|g| g.address == caller)
+            || beneficiaries
+                .beneficiaries
+                .iter()
+                .any(|b| b.address == caller);
+        if !party {
+            return Decision::deny("Caller is not the owner, a guardian or a beneficiary");
+        }
+        Decision::allow("Vault key reassembled; sealing to beneficiaries")
+    }
+
+    /// Only an owner the enclave has on record may delegate vault access
+    pub fn evaluate_capability_grant(&self, caller: &str, owners: &[String]) -> Decision {
+        if owners.is_empty() {
+            return Decision::deny("Vault has no owner registered with the enclave");
+        }
+        if !owners.iter().any(|o| o == caller) {
+            return Decision::deny("Only the vault owner may grant capabilities");
+        }
+        Decision::allow("Caller owns the vault")
+    }
+
+    /// Content queries on a vault with a registered owner are for that
+    /// owner, or a capability holder acting for them
+    pub fn evaluate_content_access(&self, caller: &str, owners: &[String]) -> Decision {
+        if !owners.is_empty() && !owners.iter().any(|o| o == caller) {
+            return Decision::deny("Only the vault owner or a capability holder may query it");
+        }
+        Decision::allow("Caller may query the vault")
+    }
+
+    /// Legal holds are placed and lifted by an enclave admin or a guardian
+    /// quorum, never by the owner alone
+    pub fn evaluate_legal_hold_change(&self, caller: &str, quorum_approved: bool) -> Decision {
+        if self.evaluate_admin(caller).allowed {
+            return Decision::allow("Caller is an enclave admin");
+        }
+        if quorum_approved {
+            return Decision::allow("Guardian quorum approved the change");
+        }
+        Decision::deny("Legal holds need an enclave admin or a guardian quorum approval")
+    }
+
+    /// A vault under legal hold only serves reads and audit exports
+    pub fn evaluate_vault_mutation(&self, held: bool) -> Decision {
+        if held {
+            return Decision::deny("Vault is under legal hold");
+        }
+        Decision::allow("Vault is not under legal hold")
+    }
+
+    /// Only a vault owner, signing as themselves, may delete the vault
+    pub fn evaluate_vault_deletion(
+        &self,
+        caller: &str,
+        owners: &[String],
+        delegated: bool,
+    ) -> Decision {
+        if delegated {
+            return Decision::deny("Vault deletion can't be delegated");
+        }
+        if owners.is_empty() {
+            return Decision::deny("Vault has no owner registered with the enclave");
+        }
+        if !owners.iter().any(|o| o == caller) {
+            return Decision::deny("Only the vault owner may delete it");
+        }
+        Decision::allow("Caller owns the vault")
+    }
+
+    /// A data-subject export goes only to an owner, signing as themselves
+    pub fn evaluate_data_export(
+        &self,
+        caller: &str,
+        owners: &[String],
+        delegated: bool,
+    ) -> Decision {
+        if delegated {
+            return Decision::deny("Data exports can't be requested by a delegate");
+        }
+        if owners.is_empty() {
+            return Decision::deny("Vault has no owner registered with the enclave");
+        }
+        if !owners.iter().any(|o| o == caller) {
+            return Decision::deny("Only a vault owner may export its data");
+        }
+        Decision::allow("Caller owns the vault")
+    }
+
+    /// Only a vault owner, signing as themselves, may change what its
+    /// releases redact
+    pub fn evaluate_redaction_change(
+        &self,
+        caller: &str,
+        owners: &[String],
+        delegated: bool,
+    ) -> Decision {
+        if delegated {
+            return Decision::deny("Redaction policies can't be changed by a delegate");
+        }
+        if owners.is_empty() {
+            return Decision::deny("Vault has no owner registered with the enclave");
+        }
+        if !owners.iter().any(|o| o == caller) {
+            return Decision::deny("Only the vault owner may set its redaction policy");
+        }
+        Decision::allow("Caller owns the vault")
+    }
+
+    /// Only a vault owner, signing as themselves, may see or change who
+    /// its notifications go to
+    pub fn evaluate_contact_change(
+        &self,
+        caller: &str,
+        owners: &[String],
+        delegated: bool,
+    ) -> Decision {
+        if delegated {
+            return Decision::deny("Notification contacts can't be changed by a delegate");
+        }
+        if owners.is_empty() {
+            return Decision::deny("Vault has no owner registered with the enclave");
+        }
+        if !owners.iter().any(|o| o == caller) {
+            return Decision::deny("Only the vault owner may set its notification contacts");
+        }
+        Decision::allow("Caller owns the vault")
+    }
+
+    /// Check-ins are proof of life, so only a vault owner signing as
+    /// themselves may check in or set the schedule
+    pub fn evaluate_check_in(&self, caller: &str, owners: &[String], delegated: bool) -> Decision {
+        if delegated {
+            return Decision::deny("Check-ins can't be delegated");
+        }
+        if owners.is_empty() {
+            return Decision::deny("Vault has no owner registered with the enclave");
+        }
+        if !owners.iter().any(|o| o == caller) {
+            return Decision::deny("Only the vault owner may check in");
+        }
+        Decision::allow("Caller owns the vault")
+    }
+
+    /// Only a vault owner, signing as themselves, may set how they signal
+    /// duress
+    pub fn evaluate_duress_change(
+        &self,
+        caller: &str,
+        owners: &[String],
+        delegated: bool,
+    ) -> Decision {
+        if delegated {
+            return Decision::deny("Duress signals can't be set by a delegate");
+        }
+        if owners.is_empty() {
+            return Decision::deny("Vault has no owner registered with the enclave");
+        }
+        if !owners.iter().any(|o| o == caller) {
+            return Decision::deny("Only the vault owner may set its duress signals");
+        }
+        Decision::allow("Caller owns the vault")
+    }
+
+    /// A duress freeze is visible to enclave admins and the vault's
+    /// guardians, not to the owner who may be coerced into asking
+    pub fn evaluate_duress_review(&self, caller: &str, guardians: &[String]) -> Decision {
+        if self.evaluate_admin(caller).allowed {
+            return Decision::allow("Caller is an enclave admin");
+        }
+        if guardians.iter().any(|g| g == caller) {
+            return Decision::allow("Caller is a vault guardian");
+        }
+        Decision::deny("Only enclave admins and vault guardians may review duress")
+    }
+
+    /// A duress freeze is lifted by an enclave admin or a guardian quorum,
+    /// never by the owner alone
+    pub fn evaluate_duress_clear(&self, caller: &str, quorum_approved: bool) -> Decision {
+        if self.evaluate_admin(caller).allowed {
+            return Decision::allow("Caller is an enclave admin");
+        }
+        if quorum_approved {
+            return Decision::allow("Guardian quorum approved clearing the freeze");
+        }
+        Decision::deny(
+            "Clearing a duress freeze needs an enclave admin or a guardian quorum approval",
+        )
+    }
+
+    /// Nothing leaves a vault frozen by a duress signal. The reason stays
+    /// vague since whoever asks may be the coercer.
+    pub fn evaluate_frozen_release(&self, frozen: bool) -> Decision {
+        if frozen {
+            return Decision::deny("Release is temporarily unavailable");
+        }
+        Decision::allow("Vault is not frozen")
+    }
+
+    /// Only a vault owner, signing as themselves, may enroll, rotate or
+    /// revoke the devices that vouch for them
+    pub fn evaluate_device_change(
+        &self,
+        caller: &str,
+        owners: &[String],
+        delegated: bool,
+    ) -> Decision {
+        if delegated {
+            return Decision::deny("Heartbeat devices can't be managed by a delegate");
+        }
+        if owners.is_empty() {
+            return Decision::deny("Vault has no owner registered with the enclave");
+        }
+        if !owners.iter().any(|o| o == caller) {
+            return Decision::deny("Only the vault owner may manage its heartbeat devices");
+        }
+        Decision::allow("Caller owns the vault")
+    }
+
+    /// Only a vault owner, signing as themselves, may set what happens
+    /// when they stop checking in
+    pub fn evaluate_escalation_change(
+        &self,
+        caller: &str,
+        owners: &[String],
+        delegated: bool,
+    ) -> Decision {
+        if delegated {
+            return Decision::deny("Escalation ladders can't be changed by a delegate");
+        }
+        if owners.is_empty() {
+            return Decision::deny("Vault has no owner registered with the enclave");
+        }
+        if !owners.iter().any(|o| o == caller) {
+            return Decision::deny("Only the vault owner may set its escalation ladder");
+        }
+        Decision::allow("Caller owns the vault")
+    }
+
+    /// An escalation stage is complete once it has lasted its duration and
+    /// any evidence it names is in
+    pub fn evaluate_escalation_stage(
+        &self,
+        stage: &Stage,
+        entered_at: u64,
+        now: u64,
+        evidence: bool,
+    ) -> Decision {
+        let ends_at = entered_at + stage.duration_secs();
+        if now < ends_at {
+            return Decision::deny(format!("Stage lasts until {}", ends_at));
+        }
+        if !evidence {
+            return match stage.evidence {
+                Evidence::GuardianApproval => {
+                    Decision::deny("Stage waits for a guardian confirmation approval")
+                }
+                Evidence::None => Decision::deny("Stage evidence is missing"),
+            };
+        }
+        Decision::allow("Stage complete")
+    }
+
+    /// A vault with an escalation ladder is only released once the ladder
+    /// has reached unlock
+    pub fn evaluate_escalated_release(&self, laddered: bool, unlocked: bool) -> Decision {
+        if laddered && !unlocked {
+            return Decision::deny("Vault's escalation ladder hasn't reached unlock");
+        }
+        Decision::allow("Vault is not held back by escalation")
+    }
+
+    /// A share link opens only once the vault's escalation ladder has
+    /// reached unlock, and only with the password and proof its grant asks
+    /// for
+    pub fn evaluate_share_redemption(
+        &self,
+        unlocked: Option<bool>,
+        password_ok: bool,
+        proof_ok: bool,
+    ) -> Decision {
+        let Some(unlocked) = unlocked else {
+            return Decision::deny("Vault has no escalation ladder to unlock it");
+        };
+        if !unlocked {
+            return Decision::deny("Vault's escalation ladder hasn't reached unlock");
+        }
+        if !password_ok {
+            return Decision::deny("Share link password is missing or wrong");
+        }
+        if !proof_ok {
+            return Decision::deny("Share link's required proof is missing or doesn't verify");
+        }
+        Decision::allow("Vault unlocked; share link requirements met")
+    }
+
+    /// Owners set a vault's co-owners; only its co-owners approve changes
+    pub fn evaluate_co_owner_change(&self, caller: &str, owners: &[String]) -> Decision {
+        if owners.is_empty() {
+            return Decision::deny("Vault has no owner registered with the enclave");
+        }
+        if !owners.iter().any(|o| o == caller) {
+            return Decision::deny("Only a vault owner may change its owners or approve changes");
+        }
+        Decision::allow("Caller owns the vault")
+    }
+
+    /// The first registration of a vault claims it for the caller; after
+    /// that only an owner may register it again
+    pub fn evaluate_vault_registration(&self, caller: &str, owners: &[String]) -> Decision {
+        if owners.is_empty() {
+            return Decision::allow("Vault has no owner yet; caller registers it");
+        }
+        if !owners.iter().any(|o| o == caller) {
+            return Decision::deny("Only a vault owner may register it from a template");
+        }
+        Decision::allow("Caller owns the vault")
+    }
+
+    /// Co-owned vaults change what protects them only with their owner
+    /// quorum
+    pub fn evaluate_owner_quorum(&self, approvals: usize, threshold: usize) -> Decision {
+        if approvals < threshold {
+            return Decision::deny(format!(
+                "{} of {} required owner approvals",
+                approvals, threshold
+            ));
+        }
+        Decision::allow("Owner quorum reached")
+    }
+
+    /// A vault registered from a template is released only the way the
+    /// template allows: behind an unlocked escalation ladder, or only item
+    /// by item
+    pub fn evaluate_template_release(
+        &self,
+        rules: Option<&ReleaseRules>,
+        whole: bool,
+        laddered: bool,
+        unlocked: bool,
+    ) -> Decision {
+        let Some(rules) = rules else {
+            return Decision::allow("Vault has no template release rules");
+        };
+        if rules.requires_itemized && whole {
+            return Decision::deny("Vault's template releases it item by item only");
+        }
+        if rules.requires_ladder && !laddered {
+            return Decision::deny("Vault's template requires an escalation ladder before release");
+        }
+        if rules.requires_ladder && !unlocked {
+            return Decision::deny("Vault's escalation ladder hasn't reached unlock");
+        }
+        Decision::allow("Release follows the vault's template")
+    }
+
+    /// A vault under a jurisdiction's rule pack is released only once its
+    /// ladder has unlocked, the contest window has passed, the required
+    /// claims are proven and no contest is open
+    pub fn evaluate_jurisdiction_release(
+        &self,
+        status: Option<&JurisdictionStatus>,
+        now: u64,
+    ) -> Decision {
+        let Some(status) = status else {
+            return Decision::allow("Vault has no jurisdiction rule pack");
+        };
+        let Some(window_ends_at) = status.window_ends_at else {
+            return Decision::deny(format!(
+                "Rule pack {} needs an unlocked escalation ladder before release",
+                status.pack.jurisdiction
+            ));
+        };
+        if now < window_ends_at {
+            return Decision::deny(format!("Contest window runs until {}", window_ends_at));
+        }
+        if let Some(rule) = status.missing_evidence.first() {
+            return Decision::deny(format!(
+                "Rule pack {} needs a {} v{} proof since the missed check-in",
+                status.pack.jurisdiction, rule.claim_type, rule.version
+            ));
+        }
+        if status.open_contests > 0 {
+            return Decision::deny(format!(
+                "{} contest(s) await resolution by an enclave admin",
+                status.open_contests
+            ));
+        }
+        Decision::allow("Jurisdiction rules met")
+    }
+
+    /// Evidence and contests under a rule pack come from the vault's
+    /// owners, guardians or beneficiaries
+    pub fn evaluate_jurisdiction_party(&self, caller: &str, parties: &[String]) -> Decision {
+        if !parties.iter().any(|p| p == caller) {
+            return Decision::deny("Caller is not an owner, guardian or beneficiary of the vault");
+        }
+        Decision::allow("Caller is a party to the vault")
+    }
+
+    /// An installed policy bundle can only narrow the checks above; a
+    /// policy that fails to evaluate denies rather than being skipped
+    pub fn evaluate_policy_bundle(&self, verdict: Option<&BundleDecision>) -> Decision {
+        let Some(verdict) = verdict else {
+            return Decision::allow("No policy bundle is installed");
+        };
+        if !verdict.errors.is_empty() {
+            return Decision::deny(format!(
+                "Policy bundle failed to evaluate: {}",
+                verdict.errors.join("; ")
+            ));
+        }
+        if !verdict.allowed {
+            if verdict.policies.is_empty() {
+                return Decision::deny("No policy in the installed bundle permits this");
+            }
+            return Decision::deny(format!(
+                "Forbidden by policy bundle ({})",
+                verdict.policies.join(", ")
+            ));
+        }
+        Decision::allow("Policy bundle permits this")
+    }
+
+    /// Escrow exports need sign-off from a quorum of distinct admins
+    pub fn evaluate_escrow_export(&self, approvals: usize, quorum: usize) -> Decision {
+        if approvals < quorum {
+            return Decision::deny(format!(
+                "{} of {} required admin approvals",
+                approvals, quorum
+            ));
+        }
+        Decision::allow("Admin quorum reached")
+    }
+
+    /// The enclave signs only configured purposes, and only for an owner
+    /// of the registered vault
+    pub fn evaluate_signing(
+        &self,
+        purpose: &str,
+        caller: &str,
+        registered: bool,
+        owners: &[String],
+    ) -> Decision {
+        if !self.signing_purposes.iter().any(|p| p == purpose) {
+            return Decision::deny(format!("Signing purpose {} is not allowed", purpose));
+        }
+        if !registered {
+            return Decision::deny("Vault is not registered");
+        }
+        if !owners.iter().any(|o| o == caller) {
+            return Decision::deny("Caller is not an owner of this vault");
+        }
+        Decision::allow("Signing purpose allowed for vault owner")
+    }
+}
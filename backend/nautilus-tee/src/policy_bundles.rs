@@ -0,0 +1,307 @@
+/**
+ * Policy Bundles
+ * Cedar policies layered over the enclave's own authorization checks
+ *
+ * The built-in checks (see policy) stay in force; an installed bundle can
+ * only narrow them. A bundle is a JSON document carrying Cedar source,
+ * signed in the release pipeline with the key in TEE_POLICY_BUNDLE_KEY
+ * (hex Ed25519, signature over the exact document bytes):
+ *
+ *   { "sequence": 3, "issued_at": 1760000000,
+ *     "policies": "permit(principal, action, resource);\nforbid(...) when {...};" }
+ *
+ * Admins install one through POST /admin/policy-bundle; its sequence must
+ * exceed the installed one, so an older bundle can't be replayed over a
+ * newer one. Until one is installed, no Cedar decision is made.
+ *
+ * Two kinds of request are evaluated, each with an empty entity store:
+ *   - every signed API call, as
+ *       principal = Lumina::User::"<signer>"
+ *       action    = Lumina::Action::"<METHOD> <route>"
+ *       resource  = Lumina::Vault::"<vault_id>", or Lumina::Enclave::"self"
+ *   - unlock decisions, with action Lumina::Action::"vault_release",
+ *     "item_release" or "threshold_release" and the vault as resource
+ * The context always has `delegated`, `admin` and `owner` (booleans);
+ * unlock decisions add the vault's state, e.g. `unlocked`. Cedar denies
+ * by default, so a bundle needs a permit for what it doesn't forbid.
+ *
+ * Release attestations under a bundle bind its hash (see bind), so a
+ * verifier can tell which policies allowed the release.
+ */
+
+use cedar_policy::{
+    Authorizer, Context, Decision as CedarDecision, Entities, EntityId, EntityTypeName, EntityUid,
+    PolicySet, Request,
+};
+use ed25519_dalek::{Signature, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::info;
+
+use crate::store::Store;
+
+const NAMESPACE: &str = "policy_bundles";
+const CURRENT: &str = "current";
+const BIND_DOMAIN: &[u8] = b"lumina-policy-decision";
+const MAX_POLICY_BYTES: usize = 64 * 1024;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PolicyBundleDocument {
+    pub sequence: u64,
+    pub issued_at: u64,
+    /// Cedar source
+    pub policies: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct InstalledPolicyBundle {
+    pub document: PolicyBundleDocument,
+    pub document_sha256: String, // Hex, over the signed bytes
+    pub installed_by: String,
+    pub installed_at: u64,
+}
+
+/// The installed bundle's answer to one request
+#[derive(Clone, Debug, Serialize)]
+pub struct BundleDecision {
+    pub allowed: bool,
+    /// Ids of the policies that decided it, e.g. "policy0"
+    pub policies: Vec<String>,
+    pub errors: Vec<String>,
+    pub policy_sha256: String,
+}
+
+struct ActiveBundle {
+    policies: PolicySet,
+    sha256: String,
+}
+
+pub struct PolicyBundles {
+    store: Arc<Store>,
+    /// Release key bundles must be signed with; None refuses every install
+    signer: Option<VerifyingKey>,
+    active: RwLock<Option<ActiveBundle>>,
+    install: Mutex<()>,
+}
+
+impl PolicyBundles {
+    pub fn new(store: Arc<Store>, signer: Option<&str>) -> Result<Self, String> {
+        let signer = signer
+            .map(|key| {
+                let key: [u8; 32] = hex::decode(key)
+                    .ok()
+                    .and_then(|k| k.try_into().ok())
+                    .ok_or("TEE_POLICY_BUNDLE_KEY must be a hex Ed25519 public key")?;
+                VerifyingKey::from_bytes(&key).map_err(|_| "Invalid policy bundle signing key")
+            })
+            .transpose()?;
+        let active = store
+            .get::<InstalledPolicyBundle>(NAMESPACE, CURRENT)?
+            .map(|installed| {
+                Ok::<_, String>(ActiveBundle {
+                    policies: parse(&installed.document.policies)?,
+                    sha256: installed.document_sha256,
+                })
+            })
+            .transpose()?;
+        Ok(Self {
+            store,
+            signer,
+            active: RwLock::new(active),
+            install: Mutex::new(()),
+        })
+    }
+
+    pub fn current(&self) -> Result<Option<InstalledPolicyBundle>, String> {
+        self.store.get(NAMESPACE, CURRENT)
+    }
+
+    pub fn installed(&self) -> bool {
+        self.active.read().unwrap().is_some()
+    }
+
+    /// Install a signed bundle in place of the current one
+    pub fn install(
+        &self,
+        document: &[u8],
+        signature: &str,
+        installed_by: &str,
+    ) -> Result<InstalledPolicyBundle, String> {
+        let signer = self
+            .signer
+            .as_ref()
+            .ok_or("No policy bundle signing key is configured")?;
+        let signature: [u8; 64] = hex::decode(signature.trim())
+            .ok()
+            .and_then(|s| s.try_into().ok())
+            .ok_or("Policy bundle signature must be 64 hex bytes")?;
+        signer
+            .verify_strict(document, &Signature::from_bytes(&signature))
+            .map_err(|_| "Policy bundle signature does not verify".to_string())?;
+        let parsed: PolicyBundleDocument = serde_json::from_slice(document)
+            .map_err(|e| format!("Invalid policy bundle document: {}", e))?;
+        if parsed.policies.len() > MAX_POLICY_BYTES {
+            return Err(format!(
+                "Policy bundles carry at most {} bytes of Cedar",
+                MAX_POLICY_BYTES
+            ));
+        }
+        let policies = parse(&parsed.policies)?;
+
+        let _guard = self.install.lock().unwrap();
+        if let Some(previous) = self.current()? {
+            if parsed.sequence <= previous.document.sequence {
+                return Err(format!(
+                    "Policy bundle sequence {} does not supersede installed sequence {}",
+                    parsed.sequence, previous.document.sequence
+                ));
+            }
+        }
+        let installed = InstalledPolicyBundle {
+            document_sha256: hex::encode(Sha256::digest(document)),
+            document: parsed,
+            installed_by: installed_by.to_string(),
+            installed_at: now(),
+        };
+        self.store.put(NAMESPACE, CURRENT, &installed)?;
+        *self.active.write().unwrap() = Some(ActiveBundle {
+            policies,
+            sha256: installed.document_sha256.clone(),
+        });
+        info!(
+            "Policy bundle {} ({}) installed by {}",
+            installed.document.sequence, installed.document_sha256, installed_by
+        );
+        Ok(installed)
+    }
+
+    /// The installed bundle's decision; None when no bundle is installed
+    pub fn authorize(
+        &self,
+        principal: &str,
+        action: &str,
+        vault_id: Option<&str>,
+        context: Value,
+    ) -> Result<Option<BundleDecision>, String> {
+        let active = self.active.read().unwrap();
+        let Some(active) = active.as_ref() else {
+            return Ok(None);
+        };
+        let resource = match vault_id {
+            Some(vault_id) => uid("Vault", vault_id)?,
+            None => uid("Enclave", "self")?,
+        };
+        let context = Context::from_json_value(context, None)
+            .map_err(|e| format!("Invalid policy context: {}", e))?;
+        let request = Request::new(
+            uid("User", principal)?,
+            uid("Action", action)?,
+            resource,
+            context,
+            None,
+        )
+        .map_err(|e| format!("Invalid policy request: {}", e))?;
+
+        let response =
+            Authorizer::new().is_authorized(&request, &active.policies, &Entities::empty());
+        Ok(Some(BundleDecision {
+            allowed: response.decision() == CedarDecision::Allow,
+            policies: response
+                .diagnostics()
+                .reason()
+                .map(ToString::to_string)
+                .collect(),
+            errors: response
+                .diagnostics()
+                .errors()
+                .map(ToString::to_string)
+                .collect(),
+            policy_sha256: active.sha256.clone(),
+        }))
+    }
+}
+
+/// The digest a release attestation covers: unchanged without a bundle,
+/// otherwise bound to the hash of the bundle that allowed it
+pub fn bind(digest: &[u8], policy_sha256: Option<&str>) -> Vec<u8> {
+    match policy_sha256 {
+        None => digest.to_vec(),
+        Some(policy_sha256) => Sha256::new()
+            .chain_update(BIND_DOMAIN)
+            .chain_update(digest)
+            .chain_update(policy_sha256.as_bytes())
+            .finalize()
+            .to_vec(),
+    }
+}
+
+fn parse(source: &str) -> Result<PolicySet, String> {
+    let policies =
+        PolicySet::from_str(source).map_err(|e| format!("Invalid Cedar policies: {}", e))?;
+    if policies.policies().next().is_none() {
+        return Err("A policy bundle needs at least one policy".to_string());
+    }
+    Ok(policies)
+}
+
+fn uid(kind: &str, id: &str) -> Result<EntityUid, String> {
+    let kind = EntityTypeName::from_str(&format!("Lumina::{}", kind))
+        .map_err(|e| format!("Invalid entity type: {}", e))?;
+    Ok(EntityUid::from_type_name_and_id(kind, EntityId::new(id)))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn bundles(name: &str, policies: &str) -> PolicyBundles {
+        let dir =
+            std::env::temp_dir().join(format!("lumina-policy-bundle-tests-{}-{}", name, now()));
+        let bundles = PolicyBundles::new(Arc::new(Store::open(dir).unwrap()), None).unwrap();
+        *bundles.active.write().unwrap() = Some(ActiveBundle {
+            policies: parse(policies).unwrap(),
+            sha256: "00".to_string(),
+        });
+        bundles
+    }
+
+    #[test]
+    fn bundle_narrows_by_action_and_context() {
+        let bundles = bundles(
+            "narrows",
+            r#"permit(principal, action, resource);
+               forbid(principal, action == Lumina::Action::"vault_release", resource)
+                 unless { context.owner && !context.delegated };"#,
+        );
+        let decide = |action: &str, delegated: bool| {
+            bundles
+                .authorize(
+                    "0xabc",
+                    action,
+                    Some("vault-1"),
+                    json!({ "delegated": delegated, "admin": false, "owner": true }),
+                )
+                .unwrap()
+                .unwrap()
+                .allowed
+        };
+
+        assert!(decide("vault_release", false));
+        assert!(!decide("vault_release", true));
+        assert!(decide("POST /vault/:vault_id/release", true));
+        assert_ne!(bind(b"digest", Some("00")), bind(b"digest", None));
+        assert!(parse("").is_err());
+    }
+}
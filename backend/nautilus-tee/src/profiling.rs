@@ -0,0 +1,117 @@
+/**
+ * CPU Profiling
+ * Sampled CPU profiles of the running enclave, for diagnosing slow proving
+ *
+ * Nothing can attach a debugger or perf to a production enclave, so the
+ * process profiles itself: a signal-driven sampler records stacks at a set
+ * frequency for a bounded duration, then the samples are rendered as a
+ * flamegraph SVG or encoded as a pprof protobuf for `go tool pprof`.
+ *
+ * The sampler is process-wide, so one profile runs at a time. Sampling
+ * costs little at the default frequency but isn't free; profiles are
+ * admin-only and capped at MAX_DURATION.
+ */
+
+use pprof::protos::Message;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+pub const MAX_DURATION: Duration = Duration::from_secs(60);
+pub const DEFAULT_FREQUENCY: i32 = 99;
+pub const MAX_FREQUENCY: i32 = 1000;
+
+/// Frames from the C runtime and the signal trampoline, which every
+/// sample would otherwise end in
+const BLOCKLIST: [&str; 4] = ["libc", "libgcc", "pthread", "vdso"];
+
+static PROFILING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProfileFormat {
+    /// Interactive SVG flamegraph
+    #[default]
+    Flamegraph,
+    /// Protobuf-encoded profile.proto
+    Pprof,
+}
+
+impl ProfileFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Flamegraph => "image/svg+xml",
+            Self::Pprof => "application/octet-stream",
+        }
+    }
+
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Self::Flamegraph => "cpu.svg",
+            Self::Pprof => "cpu.pb",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ProfileError {
+    /// Another profile holds the sampler
+    Busy,
+    Failed(String),
+}
+
+/// Sample every thread for `duration` at `frequency` Hz, both within the
+/// caps above; blocks the calling thread throughout
+pub fn profile(
+    duration: Duration,
+    frequency: i32,
+    format: ProfileFormat,
+) -> Result<Vec<u8>, ProfileError> {
+    let _running = Running::claim().ok_or(ProfileError::Busy)?;
+    sample(duration, frequency, format).map_err(ProfileError::Failed)
+}
+
+fn sample(duration: Duration, frequency: i32, format: ProfileFormat) -> Result<Vec<u8>, String> {
+    let guard = pprof::ProfilerGuardBuilder::default()
+        .frequency(frequency)
+        .blocklist(&BLOCKLIST)
+        .build()
+        .map_err(|e| format!("Failed to start the profiler: {}", e))?;
+    std::thread::sleep(duration);
+    let report = guard
+        .report()
+        .build()
+        .map_err(|e| format!("Failed to build the profile: {}", e))?;
+    drop(guard);
+
+    let mut encoded = Vec::new();
+    match format {
+        ProfileFormat::Flamegraph => report
+            .flamegraph(&mut encoded)
+            .map_err(|e| format!("Failed to render the flamegraph: {}", e))?,
+        ProfileFormat::Pprof => report
+            .pprof()
+            .map_err(|e| format!("Failed to convert the profile: {}", e))?
+            .encode(&mut encoded)
+            .map_err(|e| format!("Failed to encode the profile: {}", e))?,
+    }
+    Ok(encoded)
+}
+
+/// The one profile in progress, ended on drop
+struct Running;
+
+impl Running {
+    fn claim() -> Option<Self> {
+        PROFILING
+            .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+            .ok()
+            .map(|_| Self)
+    }
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        PROFILING.store(false, Ordering::Release);
+    }
+}
@@ -0,0 +1,271 @@
+/**
+ * Proof Encoding
+ * Compact Groth16 proofs for on-chain verification
+ *
+ * snarkjs writes proofs as JSON with decimal, projective coordinates:
+ * several hundred bytes of text per proof. On chain every byte of a
+ * transaction costs gas, so the compact encoding keeps only what the
+ * verifier reads, in the arkworks layout Sui's groth16 module takes:
+ *
+ *   proof_points:  A (G1) || B (G2) || C (G1), compressed: 128 bytes
+ *   public_inputs: each signal a 32-byte little-endian scalar
+ *
+ * A compressed point is its x coordinate with the sign of y and the
+ * infinity flag in the top bits of the last byte. Points are checked to be
+ * on the curve and in the prime-order subgroup before encoding, so a
+ * malformed proof fails here rather than in a transaction.
+ */
+
+use ark_bn254::{Fq, Fq2, Fr, G1Affine, G2Affine};
+use ark_ec::short_weierstrass::{Affine, SWCurveConfig};
+use ark_ff::{PrimeField, Zero};
+use ark_serialize::CanonicalSerialize;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// How /zk/generate encodes the proof it returns
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProofFormat {
+    /// snarkjs JSON
+    #[default]
+    Json,
+    Compact,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct CompactProof {
+    /// Hex A || B || C, compressed
+    pub proof_points: String,
+    /// Hex concatenated 32-byte little-endian scalars
+    pub public_inputs: String,
+}
+
+/// Encode a snarkjs Groth16 proof and its public signals compactly
+pub fn compact(proof: &Value, public_signals: &[String]) -> Result<CompactProof, String> {
+    let (a, b, c) = points(proof)?;
+    let mut points = Vec::with_capacity(128);
+    a.serialize_compressed(&mut points)
+        .and_then(|_| b.serialize_compressed(&mut points))
+        .and_then(|_| c.serialize_compressed(&mut points))
+        .map_err(|e| e.to_string())?;
+
+    let mut inputs = Vec::with_capacity(32 * public_signals.len());
+    for signal in scalars(public_signals)? {
+        signal
+            .serialize_compressed(&mut inputs)
+            .map_err(|e| e.to_string())?;
+    }
+
+    Ok(CompactProof {
+        proof_points: hex::encode(points),
+        public_inputs: hex::encode(inputs),
+    })
+}
+
+/// snarkjs JSON for a proof with these points
+pub fn snarkjs(a: G1Affine, b: G2Affine, c: G1Affine) -> Value {
+    let g1 = |p: G1Affine| serde_json::json!([decimal(p.x), decimal(p.y), "1"]);
+    serde_json::json!({
+        "pi_a": g1(a),
+        "pi_b": [
+            [decimal(b.x.c0), decimal(b.x.c1)],
+            [decimal(b.y.c0), decimal(b.y.c1)],
+            ["1", "0"]
+        ],
+        "pi_c": g1(c),
+        "protocol": "groth16",
+        "curve": "bn128"
+    })
+}
+
+/// A, B and C of a snarkjs Groth16 proof, checked
+pub fn points(proof: &Value) -> Result<(G1Affine, G2Affine, G1Affine), String> {
+    Ok((
+        g1(&proof["pi_a"]).map_err(|e| format!("pi_a: {}", e))?,
+        g2(&proof["pi_b"]).map_err(|e| format!("pi_b: {}", e))?,
+        g1(&proof["pi_c"]).map_err(|e| format!("pi_c: {}", e))?,
+    ))
+}
+
+/// Decimal public signals as scalars, each below the field modulus
+pub fn scalars(public_signals: &[String]) -> Result<Vec<Fr>, String> {
+    public_signals
+        .iter()
+        .map(|signal| {
+            canonical(signal).ok_or_else(|| format!("Public signal {:?} is not a scalar", signal))
+        })
+        .collect()
+}
+
+/// [x, y] or snarkjs's [x, y, "1"]
+pub fn g1(point: &Value) -> Result<G1Affine, String> {
+    let coordinates = affine(point)?;
+    let point = G1Affine::new_unchecked(fq(&coordinates[0])?, fq(&coordinates[1])?);
+    checked(point)
+}
+
+/// [[x.c0, x.c1], [y.c0, y.c1]] or with snarkjs's ["1", "0"]
+pub fn g2(point: &Value) -> Result<G2Affine, String> {
+    let coordinates = affine(point)?;
+    let fq2 = |value: &Value| match value.as_array().map(Vec::as_slice) {
+        Some([c0, c1]) => Ok(Fq2::new(fq(c0)?, fq(c1)?)),
+        _ => Err("Expected an Fq2 pair".to_string()),
+    };
+    let point = G2Affine::new_unchecked(fq2(&coordinates[0])?, fq2(&coordinates[1])?);
+    checked(point)
+}
+
+/// The x and y coordinates, once any projective z is confirmed to be one
+fn affine(point: &Value) -> Result<&[Value], String> {
+    let coordinates = point
+        .as_array()
+        .ok_or_else(|| "Expected a coordinate array".to_string())?;
+    let one = |z: &Value| match z {
+        Value::String(z) => z == "1",
+        Value::Array(z) => {
+            matches!(z.as_slice(), [Value::String(c0), Value::String(c1)] if c0 == "1" && c1 == "0")
+        }
+        _ => false,
+    };
+    match coordinates.as_slice() {
+        [_, _] => Ok(coordinates),
+        [_, _, z] if one(z) => Ok(&coordinates[..2]),
+        [_, _, _] => Err("Point is not normalized to affine".to_string()),
+        _ => Err("Expected two or three coordinates".to_string()),
+    }
+}
+
+fn fq(value: &Value) -> Result<Fq, String> {
+    let value = value
+        .as_str()
+        .ok_or_else(|| "Coordinates must be decimal strings".to_string())?;
+    canonical(value).ok_or_else(|| format!("{:?} is not a base field element", value))
+}
+
+/// A decimal below the modulus; parsing alone would reduce larger ones,
+/// letting several JSON proofs share one encoding
+fn canonical<F: PrimeField>(value: &str) -> Option<F> {
+    // arkworks prints zero as an empty string
+    F::from_str(value)
+        .ok()
+        .filter(|element| element.to_string() == value || (value == "0" && element.is_zero()))
+}
+
+fn decimal(element: Fq) -> String {
+    if element.is_zero() {
+        return "0".to_string();
+    }
+    element.to_string()
+}
+
+fn checked<P: SWCurveConfig>(point: Affine<P>) -> Result<Affine<P>, String> {
+    if !point.is_on_curve() {
+        return Err("Point is not on the curve".to_string());
+    }
+    if !point.is_in_correct_subgroup_assuming_on_curve() {
+        return Err("Point is not in the prime-order subgroup".to_string());
+    }
+    Ok(point)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_ec::{AffineRepr, CurveGroup};
+    use ark_ff::UniformRand;
+    use ark_serialize::CanonicalDeserialize;
+    use rand::rngs::OsRng;
+
+    /// Decode the compact form back into snarkjs JSON and decimal signals
+    fn expand(compact: &CompactProof) -> (Value, Vec<String>) {
+        let points = hex::decode(&compact.proof_points).unwrap();
+        let mut reader = points.as_slice();
+        let a = G1Affine::deserialize_compressed(&mut reader).unwrap();
+        let b = G2Affine::deserialize_compressed(&mut reader).unwrap();
+        let c = G1Affine::deserialize_compressed(&mut reader).unwrap();
+        assert!(reader.is_empty());
+
+        let signals = hex::decode(&compact.public_inputs)
+            .unwrap()
+            .chunks(32)
+            .map(|chunk| Fr::deserialize_compressed(chunk).unwrap().to_string())
+            .collect();
+        (snarkjs(a, b, c), signals)
+    }
+
+    fn random_proof() -> Value {
+        let g1 = || (G1Affine::generator() * Fr::rand(&mut OsRng)).into_affine();
+        let b = (G2Affine::generator() * Fr::rand(&mut OsRng)).into_affine();
+        snarkjs(g1(), b, g1())
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        for _ in 0..16 {
+            let proof = random_proof();
+            let signals: Vec<String> = (0..3).map(|_| Fr::rand(&mut OsRng).to_string()).collect();
+            let compact = compact(&proof, &signals).unwrap();
+            assert_eq!(expand(&compact), (proof, signals));
+        }
+    }
+
+    #[test]
+    fn encoding_is_minimal() {
+        let signals = vec!["0".to_string(), "1".to_string()];
+        let compact = compact(&random_proof(), &signals).unwrap();
+        assert_eq!(compact.proof_points.len(), 2 * 128);
+        assert_eq!(compact.public_inputs.len(), 2 * 32 * 2);
+        assert_eq!(&compact.public_inputs[64..66], "01");
+    }
+
+    #[test]
+    fn sign_of_y_survives_compression() {
+        let a = (G1Affine::generator() * Fr::rand(&mut OsRng)).into_affine();
+        let b = G2Affine::generator();
+        let proof = snarkjs(a, b, a);
+        let negated = snarkjs(-a, -b, -a);
+        let (compact, compact_negated) = (
+            compact(&proof, &[]).unwrap(),
+            compact(&negated, &[]).unwrap(),
+        );
+        assert_ne!(compact, compact_negated);
+        assert_eq!(expand(&compact).0, proof);
+        assert_eq!(expand(&compact_negated).0, negated);
+    }
+
+    #[test]
+    fn accepts_affine_coordinates_without_z() {
+        let mut proof = random_proof();
+        let expected = compact(&proof, &[]).unwrap();
+        for key in ["pi_a", "pi_b", "pi_c"] {
+            proof[key].as_array_mut().unwrap().pop();
+        }
+        assert_eq!(compact(&proof, &[]).unwrap(), expected);
+    }
+
+    #[test]
+    fn rejects_malformed_proofs() {
+        let mut off_curve = random_proof();
+        off_curve["pi_a"][1] = "3".into();
+        assert!(compact(&off_curve, &[])
+            .unwrap_err()
+            .contains("not on the curve"));
+
+        let mut projective = random_proof();
+        projective["pi_c"][2] = "2".into();
+        assert!(compact(&projective, &[]).is_err());
+
+        let placeholder = serde_json::json!({
+            "pi_a": ["0x1234", "0x5678"],
+            "pi_b": [["0xabcd", "0xef01"], ["0x2345", "0x6789"]],
+            "pi_c": ["0x9876", "0x5432"]
+        });
+        assert!(compact(&placeholder, &[]).is_err());
+
+        // The scalar field modulus itself is out of range
+        let modulus =
+            "21888242871839275222246405745257275088548364400416034343698204186575808495617";
+        assert!(compact(&random_proof(), &[modulus.to_string()]).is_err());
+    }
+}
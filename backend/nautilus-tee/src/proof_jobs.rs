@@ -0,0 +1,522 @@
+/**
+ * Proof Jobs
+ * Background proof generation with stage-level progress
+ *
+ * Large circuits take minutes to prove. A job runs the /zk/generate
+ * pipeline on a worker task and reports the stage it is in:
+ *
+ *   decrypting  reading the submitted data
+ *   witness     canonicalizing it into circuit inputs
+ *   msm         the multi-scalar multiplications that dominate proving
+ *   finalizing  checking the public signals and attesting to them
+ *
+ * The ETA comes from how long earlier jobs on the same circuit version
+ * took, so the first job on a circuit has none. Jobs live in memory and
+ * are dropped an hour after they finish.
+ *
+ * Jobs and synchronous /zk/generate calls alike wait for a worker in the
+ * proving queue, which is bounded so a burst can't hold unbounded request
+ * payloads in memory. Unlock-critical proofs go ahead of exploratory ones
+ * and are turned away only when the queue is full; exploratory proofs are
+ * turned away once its depth reaches the watermark, leaving the rest of
+ * the queue for unlocks. GET /zk/queue reports depth and wait times.
+ *
+ * Identical requests share one proof: a request whose canonical hash
+ * matches one still running joins that job instead of taking a place in
+ * the queue, and every caller gets its result. Since others may be waiting
+ * on it, a proof runs to the end even if the request that started it goes
+ * away.
+ */
+
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{oneshot, watch};
+
+use crate::error::AppError;
+
+const FINISHED_JOB_TTL: Duration = Duration::from_secs(60 * 60);
+/// Weight of the newest run in a circuit's expected duration
+const DURATION_WEIGHT: f64 = 0.3;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+    /// Proofs an unlock decision is waiting on
+    Critical,
+    #[default]
+    Exploratory,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    Queued,
+    Decrypting,
+    Witness,
+    Msm,
+    Finalizing,
+    Done,
+    Failed,
+}
+
+impl Stage {
+    pub fn is_finished(self) -> bool {
+        matches!(self, Stage::Done | Stage::Failed)
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct StageTiming {
+    pub stage: Stage,
+    /// Milliseconds after the job was created
+    pub started_ms: u64,
+}
+
+#[derive(Clone, Serialize)]
+pub struct JobError {
+    pub code: String,
+    pub message: String,
+    pub retryable: bool,
+}
+
+#[derive(Clone, Serialize)]
+pub struct JobStatus {
+    pub job_id: String,
+    pub claim_type: String,
+    pub circuit_version: u32,
+    pub priority: Priority,
+    pub stage: Stage,
+    pub stages: Vec<StageTiming>,
+    pub elapsed_ms: u64,
+    /// Milliseconds left, from earlier jobs on the circuit
+    pub eta_ms: Option<u64>,
+    /// The /zk/generate response, once done
+    pub result: Option<Value>,
+    pub error: Option<JobError>,
+}
+
+pub struct Job {
+    status: watch::Sender<JobStatus>,
+    /// Canonical hash of the request it proves
+    request_sha256: [u8; 32],
+    created: Instant,
+    expected: Option<Duration>,
+    finished: Mutex<Option<Instant>>,
+    /// The error as the pipeline raised it, for callers that join the job
+    failure: Mutex<Option<AppError>>,
+}
+
+/// How a request got its job
+pub enum Flight {
+    /// A new job; the caller runs the proof once the admission starts
+    Leading(Arc<Job>, Admission),
+    /// An identical request's job, already queued or running
+    Joined(Arc<Job>),
+}
+
+pub struct ProofJobs {
+    jobs: Mutex<HashMap<String, Arc<Job>>>,
+    /// Request hash -> the unfinished job proving it
+    in_flight: Mutex<HashMap<[u8; 32], Arc<Job>>>,
+    /// Circuit -> expected duration of a job
+    durations: Mutex<HashMap<String, Duration>>,
+    queue: Arc<ProvingQueue>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct QueueLimits {
+    /// Proofs that run at once
+    pub workers: usize,
+    /// Proofs running or waiting, past which even critical ones are refused
+    pub capacity: usize,
+    /// Depth past which exploratory proofs are refused
+    pub watermark: usize,
+}
+
+pub struct ProvingQueue {
+    limits: QueueLimits,
+    state: Mutex<QueueState>,
+}
+
+#[derive(Default)]
+struct QueueState {
+    running: usize,
+    /// Indexed by priority, critical first
+    waiting: [VecDeque<oneshot::Sender<()>>; 2],
+    classes: [ClassStats; 2],
+}
+
+#[derive(Default)]
+struct ClassStats {
+    admitted: u64,
+    rejected: u64,
+    started: u64,
+    total_wait: Duration,
+    max_wait: Duration,
+}
+
+/// A place in the queue; `started` waits for a worker
+pub struct Admission {
+    queue: Arc<ProvingQueue>,
+    priority: Priority,
+    enqueued: Instant,
+    turn: Option<Turn>,
+    /// Held from admission when a worker was free
+    slot: Option<WorkerSlot>,
+}
+
+/// A waiter's place in line; dropped after being handed a worker, it
+/// passes the worker on
+struct Turn {
+    wait: Option<oneshot::Receiver<()>>,
+    queue: Arc<ProvingQueue>,
+}
+
+/// A busy worker, freed for the next waiting proof on drop
+pub struct WorkerSlot {
+    queue: Arc<ProvingQueue>,
+}
+
+#[derive(Serialize)]
+pub struct QueueMetrics {
+    pub workers: usize,
+    pub capacity: usize,
+    pub watermark: usize,
+    pub running: usize,
+    pub depth: usize,
+    pub classes: Vec<ClassMetrics>,
+}
+
+#[derive(Serialize)]
+pub struct ClassMetrics {
+    pub priority: Priority,
+    pub waiting: usize,
+    pub admitted: u64,
+    pub rejected: u64,
+    pub started: u64,
+    pub mean_wait_ms: Option<u64>,
+    pub max_wait_ms: u64,
+}
+
+impl ProofJobs {
+    pub fn new(limits: QueueLimits) -> Self {
+        Self {
+            jobs: Mutex::new(HashMap::new()),
+            in_flight: Mutex::new(HashMap::new()),
+            durations: Mutex::new(HashMap::new()),
+            queue: Arc::new(ProvingQueue {
+                limits,
+                state: Mutex::new(QueueState::default()),
+            }),
+        }
+    }
+
+    pub fn queue(&self) -> &Arc<ProvingQueue> {
+        &self.queue
+    }
+
+    /// Join the job already proving `request_sha256`, or take a place in
+    /// the queue and start a new one
+    pub fn start(
+        &self,
+        request_sha256: &[u8; 32],
+        claim_type: &str,
+        circuit_version: u32,
+        priority: Priority,
+    ) -> Result<Flight, String> {
+        // Held until the new job is registered, so identical requests
+        // arriving together can't both lead
+        let mut in_flight = self.in_flight.lock().unwrap();
+        if let Some(job) = in_flight.get(request_sha256) {
+            return Ok(Flight::Joined(job.clone()));
+        }
+        let admission = self.queue.admit(priority)?;
+
+        let mut jobs = self.jobs.lock().unwrap();
+        jobs.retain(|_, job| {
+            job.finished
+                .lock()
+                .unwrap()
+                .is_none_or(|at| at.elapsed() < FINISHED_JOB_TTL)
+        });
+
+        let mut id = [0u8; 16];
+        OsRng.fill_bytes(&mut id);
+        let job_id = hex::encode(id);
+        let (status, _) = watch::channel(JobStatus {
+            job_id: job_id.clone(),
+            claim_type: claim_type.to_string(),
+            circuit_version,
+            priority,
+            stage: Stage::Queued,
+            stages: vec![StageTiming {
+                stage: Stage::Queued,
+                started_ms: 0,
+            }],
+            elapsed_ms: 0,
+            eta_ms: None,
+            result: None,
+            error: None,
+        });
+        let job = Arc::new(Job {
+            status,
+            request_sha256: *request_sha256,
+            created: Instant::now(),
+            expected: self
+                .durations
+                .lock()
+                .unwrap()
+                .get(&circuit_key(claim_type, circuit_version))
+                .copied(),
+            finished: Mutex::new(None),
+            failure: Mutex::new(None),
+        });
+        jobs.insert(job_id, job.clone());
+        in_flight.insert(*request_sha256, job.clone());
+        Ok(Flight::Leading(job, admission))
+    }
+
+    pub fn get(&self, job_id: &str) -> Option<Arc<Job>> {
+        self.jobs.lock().unwrap().get(job_id).cloned()
+    }
+
+    /// Record the outcome; successful runs update the circuit's ETA
+    pub fn finish(&self, job: &Job, outcome: Result<Value, AppError>) {
+        self.in_flight.lock().unwrap().remove(&job.request_sha256);
+        let elapsed = job.created.elapsed();
+        *job.finished.lock().unwrap() = Some(Instant::now());
+        let mut key = None;
+        job.status.send_modify(|status| {
+            status.elapsed_ms = elapsed.as_millis() as u64;
+            status.eta_ms = None;
+            let stage = match outcome {
+                Ok(result) => {
+                    status.result = Some(result);
+                    key = Some(circuit_key(&status.claim_type, status.circuit_version));
+                    Stage::Done
+                }
+                Err(e) => {
+                    status.error = Some(JobError {
+                        code: e.code.to_string(),
+                        message: e.message.clone(),
+                        retryable: e.retryable,
+                    });
+                    *job.failure.lock().unwrap() = Some(e);
+                    Stage::Failed
+                }
+            };
+            enter(status, stage, elapsed);
+        });
+
+        if let Some(key) = key {
+            let mut durations = self.durations.lock().unwrap();
+            let expected = match durations.get(&key) {
+                Some(previous) => {
+                    previous.mul_f64(1.0 - DURATION_WEIGHT) + elapsed.mul_f64(DURATION_WEIGHT)
+                }
+                None => elapsed,
+            };
+            durations.insert(key, expected);
+        }
+    }
+}
+
+impl ProvingQueue {
+    /// Take a place in the queue, or refuse if it is too deep for `priority`
+    pub fn admit(self: &Arc<Self>, priority: Priority) -> Result<Admission, String> {
+        let mut state = self.state.lock().unwrap();
+        // Waiters whose request went away don't count towards the depth
+        for waiting in &mut state.waiting {
+            waiting.retain(|turn| !turn.is_closed());
+        }
+        let depth = state.running + state.waiting.iter().map(VecDeque::len).sum::<usize>();
+        let limit = match priority {
+            Priority::Critical => self.limits.capacity,
+            Priority::Exploratory => self.limits.watermark,
+        };
+        let class = &mut state.classes[priority as usize];
+        if depth >= limit {
+            class.rejected += 1;
+            return Err(format!(
+                "Proving queue is at {} of {} for {:?} proofs",
+                depth, limit, priority
+            ));
+        }
+        class.admitted += 1;
+
+        let mut admission = Admission {
+            queue: self.clone(),
+            priority,
+            enqueued: Instant::now(),
+            turn: None,
+            slot: None,
+        };
+        let ahead = state.waiting[..=priority as usize]
+            .iter()
+            .any(|waiting| !waiting.is_empty());
+        if state.running < self.limits.workers && !ahead {
+            state.running += 1;
+            admission.slot = Some(WorkerSlot {
+                queue: self.clone(),
+            });
+        } else {
+            let (turn, wait) = oneshot::channel();
+            state.waiting[priority as usize].push_back(turn);
+            admission.turn = Some(Turn {
+                wait: Some(wait),
+                queue: self.clone(),
+            });
+        }
+        Ok(admission)
+    }
+
+    pub fn metrics(&self) -> QueueMetrics {
+        let state = self.state.lock().unwrap();
+        let classes = [Priority::Critical, Priority::Exploratory]
+            .into_iter()
+            .map(|priority| {
+                let stats = &state.classes[priority as usize];
+                ClassMetrics {
+                    priority,
+                    waiting: state.waiting[priority as usize].len(),
+                    admitted: stats.admitted,
+                    rejected: stats.rejected,
+                    started: stats.started,
+                    mean_wait_ms: (stats.started > 0)
+                        .then(|| (stats.total_wait / stats.started as u32).as_millis() as u64),
+                    max_wait_ms: stats.max_wait.as_millis() as u64,
+                }
+            })
+            .collect::<Vec<_>>();
+        QueueMetrics {
+            workers: self.limits.workers,
+            capacity: self.limits.capacity,
+            watermark: self.limits.watermark,
+            running: state.running,
+            depth: state.running + classes.iter().map(|c| c.waiting).sum::<usize>(),
+            classes,
+        }
+    }
+
+    /// Hand the worker to the next live waiter, critical first
+    fn release(&self) {
+        let mut state = self.state.lock().unwrap();
+        for waiting in &mut state.waiting {
+            while let Some(turn) = waiting.pop_front() {
+                if turn.send(()).is_ok() {
+                    return;
+                }
+            }
+        }
+        state.running -= 1;
+    }
+}
+
+impl Admission {
+    /// Wait for a worker; the proof runs while the slot is held
+    pub async fn started(mut self) -> WorkerSlot {
+        let slot = match (self.slot.take(), self.turn.take()) {
+            (Some(slot), _) => slot,
+            (None, Some(mut turn)) => {
+                if let Some(wait) = turn.wait.as_mut() {
+                    // Waiters are only dropped unsent once their receiver closed
+                    let _ = wait.await;
+                }
+                turn.wait = None;
+                WorkerSlot {
+                    queue: self.queue.clone(),
+                }
+            }
+            (None, None) => unreachable!("admission holds a slot or a turn"),
+        };
+        let waited = self.enqueued.elapsed();
+        let mut state = self.queue.state.lock().unwrap();
+        let class = &mut state.classes[self.priority as usize];
+        class.started += 1;
+        class.total_wait += waited;
+        class.max_wait = class.max_wait.max(waited);
+        slot
+    }
+}
+
+impl Drop for WorkerSlot {
+    fn drop(&mut self) {
+        self.queue.release();
+    }
+}
+
+impl Drop for Turn {
+    fn drop(&mut self) {
+        if let Some(mut wait) = self.wait.take() {
+            wait.close();
+            if wait.try_recv().is_ok() {
+                self.queue.release();
+            }
+        }
+    }
+}
+
+impl Job {
+    pub fn id(&self) -> String {
+        self.status.borrow().job_id.clone()
+    }
+
+    pub fn stage(&self, stage: Stage) {
+        let elapsed = self.created.elapsed();
+        self.status
+            .send_modify(|status| enter(status, stage, elapsed));
+    }
+
+    /// Current status with live elapsed time and ETA
+    pub fn snapshot(&self) -> JobStatus {
+        let mut status = self.status.borrow().clone();
+        if !status.stage.is_finished() {
+            let elapsed = self.created.elapsed();
+            status.elapsed_ms = elapsed.as_millis() as u64;
+            status.eta_ms = self
+                .expected
+                .map(|expected| expected.saturating_sub(elapsed).as_millis() as u64);
+        }
+        status
+    }
+
+    /// Notified on every stage change
+    pub fn subscribe(&self) -> watch::Receiver<JobStatus> {
+        self.status.subscribe()
+    }
+
+    /// Wait for the job to finish; the /zk/generate response or its error
+    pub async fn outcome(&self) -> Result<Value, AppError> {
+        let mut updates = self.subscribe();
+        let result = updates
+            .wait_for(|status| status.stage.is_finished())
+            .map_err(|_| AppError::internal("Proof job ended without an outcome"))?
+            .result
+            .clone();
+        match result {
+            Some(result) => Ok(result),
+            None => Err(self
+                .failure
+                .lock()
+                .unwrap()
+                .clone()
+                .unwrap_or_else(|| AppError::internal("Proof job failed"))),
+        }
+    }
+}
+
+fn enter(status: &mut JobStatus, stage: Stage, elapsed: Duration) {
+    status.stage = stage;
+    status.stages.push(StageTiming {
+        stage,
+        started_ms: elapsed.as_millis() as u64,
+    });
+}
+
+fn circuit_key(claim_type: &str, version: u32) -> String {
+    format!("{}@{}", claim_type, version)
+}
@@ -0,0 +1,227 @@
+/**
+ * Sample Quality
+ * Per-method quality checks that reject samples too poor to match reliably
+ *
+ * Face and fingerprint images are judged on resolution and on detail:
+ * compressed bits per pixel, which drops sharply for blurred or flat
+ * captures, or mean gradient for raw pixels. Voice samples are judged on
+ * duration and, when PCM is available, on an SNR estimate from the spread
+ * between loud and quiet 20 ms frames. Failing
+ * samples are rejected with their metrics so clients can prompt for a
+ * recapture instead of getting a confident-looking mismatch.
+ */
+
+use axum::http::StatusCode;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::error::AppError;
+use crate::formats::{self, FormatError, Sample, SampleFormat};
+
+#[derive(Clone, Copy)]
+struct ImageRequirements {
+    min_side: u32,
+    /// For compressed formats
+    min_bits_per_pixel: f64,
+    /// For raw pixels
+    min_mean_gradient: f64,
+}
+
+const FACE: ImageRequirements = ImageRequirements {
+    min_side: 200,
+    min_bits_per_pixel: 0.4,
+    min_mean_gradient: 0.0,
+};
+
+// WSQ compresses ridge detail to roughly 0.5-0.8 bits per pixel
+const FINGERPRINT: ImageRequirements = ImageRequirements {
+    min_side: 256,
+    min_bits_per_pixel: 0.25,
+    min_mean_gradient: 4.0,
+};
+
+const VOICE_MIN_DURATION_SECS: f64 = 1.5;
+const VOICE_MIN_SAMPLE_RATE: u32 = 8_000;
+const VOICE_MIN_SNR_DB: f64 = 15.0;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct QualityReport {
+    pub method: String,
+    pub format: SampleFormat,
+    pub passed: bool,
+    pub metrics: BTreeMap<&'static str, f64>,
+    /// Failed checks, empty when the sample passed
+    pub issues: Vec<String>,
+}
+
+pub fn assess(data: &[u8], method: &str, sample: &Sample) -> QualityReport {
+    let mut report = QualityReport {
+        method: method.to_string(),
+        format: sample.format(),
+        passed: true,
+        metrics: BTreeMap::new(),
+        issues: Vec::new(),
+    };
+
+    match sample {
+        Sample::Image {
+            width,
+            height,
+            pixels,
+            ..
+        } => {
+            let requirements = if method == "fingerprint" {
+                FINGERPRINT
+            } else {
+                FACE
+            };
+            assess_image(
+                data.len(),
+                *width,
+                *height,
+                pixels.as_deref(),
+                requirements,
+                &mut report,
+            )
+        }
+        Sample::Audio {
+            sample_rate,
+            duration_secs,
+            pcm,
+            ..
+        } => assess_voice(*sample_rate, *duration_secs, pcm.as_deref(), &mut report),
+    }
+
+    report.passed = report.issues.is_empty();
+    report
+}
+
+fn assess_image(
+    encoded_len: usize,
+    width: u32,
+    height: u32,
+    pixels: Option<&[u8]>,
+    requirements: ImageRequirements,
+    report: &mut QualityReport,
+) {
+    report.metrics.insert("width", width as f64);
+    report.metrics.insert("height", height as f64);
+
+    if width.min(height) < requirements.min_side {
+        report.issues.push(format!(
+            "Resolution {}x{} is below the {}px minimum",
+            width, height, requirements.min_side
+        ));
+    }
+
+    match pixels {
+        Some(pixels) => {
+            let gradient = mean_gradient(pixels, width as usize);
+            report.metrics.insert("mean_gradient", gradient);
+            if gradient < requirements.min_mean_gradient {
+                report.issues.push(format!(
+                    "Mean gradient {:.2} suggests blur or an empty frame (minimum {:.2})",
+                    gradient, requirements.min_mean_gradient
+                ));
+            }
+        }
+        None => {
+            let bits_per_pixel = (encoded_len as f64 * 8.0) / (width as f64 * height as f64);
+            report.metrics.insert("bits_per_pixel", bits_per_pixel);
+            if bits_per_pixel < requirements.min_bits_per_pixel {
+                report.issues.push(format!(
+                    "Image detail {:.2} bits/pixel suggests blur or an empty frame (minimum {:.2})",
+                    bits_per_pixel, requirements.min_bits_per_pixel
+                ));
+            }
+        }
+    }
+}
+
+fn assess_voice(sample_rate: u32, duration: f64, pcm: Option<&[i16]>, report: &mut QualityReport) {
+    report.metrics.insert("sample_rate", sample_rate as f64);
+    report.metrics.insert("duration_secs", duration);
+
+    if sample_rate < VOICE_MIN_SAMPLE_RATE {
+        report.issues.push(format!(
+            "Sample rate {} Hz is below {} Hz",
+            sample_rate, VOICE_MIN_SAMPLE_RATE
+        ));
+    }
+    if duration < VOICE_MIN_DURATION_SECS {
+        report.issues.push(format!(
+            "Recording is {:.2}s; at least {:.1}s of speech is required",
+            duration, VOICE_MIN_DURATION_SECS
+        ));
+    }
+    // Compressed audio isn't decoded here, so SNR is only checked for PCM
+    if let Some(pcm) = pcm {
+        let snr_db = estimate_snr_db(pcm, sample_rate);
+        report.metrics.insert("snr_db", snr_db);
+        if snr_db < VOICE_MIN_SNR_DB {
+            report.issues.push(format!(
+                "Estimated SNR {:.1} dB is below {:.1} dB",
+                snr_db, VOICE_MIN_SNR_DB
+            ));
+        }
+    }
+}
+
+/// Decode and assess a sample, turning failures into client errors
+/// (with the quality report attached when quality is the problem)
+pub fn require_quality(data: &[u8], method: &str) -> Result<QualityReport, AppError> {
+    let sample = formats::decode(data, method).map_err(|e| {
+        let status = match e {
+            FormatError::TooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            _ => StatusCode::BAD_REQUEST,
+        };
+        AppError::new(status, e.code(), e.message())
+    })?;
+    let report = assess(data, method, &sample);
+    if !report.passed {
+        return Err(AppError::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "LOW_QUALITY_SAMPLE",
+            report.issues.join("; "),
+        )
+        .with_details(&report));
+    }
+    Ok(report)
+}
+
+/// Mean absolute horizontal and vertical difference between neighbours
+fn mean_gradient(pixels: &[u8], width: usize) -> f64 {
+    if width < 2 || pixels.len() < 2 * width {
+        return 0.0;
+    }
+    let mut total = 0u64;
+    let mut count = 0u64;
+    for (i, &p) in pixels.iter().enumerate() {
+        if (i + 1) % width != 0 {
+            total += p.abs_diff(pixels[i + 1]) as u64;
+            count += 1;
+        }
+        if let Some(&below) = pixels.get(i + width) {
+            total += p.abs_diff(below) as u64;
+            count += 1;
+        }
+    }
+    total as f64 / count as f64
+}
+
+/// Ratio of loud-frame to quiet-frame energy over 20 ms frames
+fn estimate_snr_db(samples: &[i16], sample_rate: u32) -> f64 {
+    let frame = (sample_rate as usize / 50).max(1);
+    let mut energies: Vec<f64> = samples
+        .chunks(frame)
+        .map(|chunk| chunk.iter().map(|&s| (s as f64).powi(2)).sum::<f64>() / chunk.len() as f64)
+        .collect();
+    if energies.len() < 2 {
+        return 0.0;
+    }
+    energies.sort_by(|a, b| a.total_cmp(b));
+
+    let noise = energies[energies.len() / 10].max(1.0);
+    let signal = energies[energies.len() * 9 / 10];
+    10.0 * (signal / noise).log10()
+}
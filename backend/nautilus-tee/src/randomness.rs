@@ -0,0 +1,138 @@
+/**
+ * Randomness
+ * Unbiased random bytes from the Nitro Security Module
+ *
+ * Inside an enclave the NSM answers GetRandom requests from its hardware
+ * generator. Bytes handed out are NSM output XORed with the kernel pool,
+ * so neither source alone can bias them; outside an enclave (or if the
+ * NSM stops answering) the kernel pool is used on its own and the source
+ * reports as much. Challenges and session keys draw from here, and
+ * GET /random returns draws bound into an attestation with the caller's
+ * nonce, for guardian selection or audit sampling a third party can check.
+ */
+
+use aws_nitro_enclaves_nsm_api::api::{Request, Response};
+use aws_nitro_enclaves_nsm_api::driver::{nsm_exit, nsm_init, nsm_process_request};
+use rand::rngs::OsRng;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::{info, warn};
+use zeroize::Zeroizing;
+
+const DOMAIN: &[u8] = b"lumina-random-v1";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntropySource {
+    /// NSM output mixed with the kernel pool
+    Nsm,
+    /// Kernel pool only
+    Os,
+}
+
+pub struct RandomnessSource {
+    /// NSM device descriptor, when running in an enclave
+    nsm: Option<i32>,
+    /// Set once the NSM fails, so the failure is logged once
+    nsm_failed: AtomicBool,
+}
+
+impl RandomnessSource {
+    pub fn new() -> Self {
+        let fd = nsm_init();
+        if fd < 0 {
+            warn!("NSM unavailable; randomness comes from the kernel pool only");
+            return Self {
+                nsm: None,
+                nsm_failed: AtomicBool::new(false),
+            };
+        }
+        info!("Drawing randomness from the NSM");
+        Self {
+            nsm: Some(fd),
+            nsm_failed: AtomicBool::new(false),
+        }
+    }
+
+    /// Fill `dest`, returning where the bytes came from
+    pub fn fill(&self, dest: &mut [u8]) -> EntropySource {
+        OsRng.fill_bytes(dest);
+        let Some(fd) = self.nsm.filter(|_| !self.nsm_failed.load(Ordering::Relaxed)) else {
+            return EntropySource::Os;
+        };
+
+        let mut filled = 0;
+        while filled < dest.len() {
+            match nsm_process_request(fd, Request::GetRandom) {
+                Response::GetRandom { random } if !random.is_empty() => {
+                    let random = Zeroizing::new(random);
+                    for (d, r) in dest[filled..].iter_mut().zip(random.iter()) {
+                        *d ^= r;
+                    }
+                    filled += random.len();
+                }
+                _ => {
+                    if !self.nsm_failed.swap(true, Ordering::Relaxed) {
+                        warn!("NSM GetRandom failed; falling back to the kernel pool");
+                    }
+                    return EntropySource::Os;
+                }
+            }
+        }
+        EntropySource::Nsm
+    }
+
+    pub fn bytes(&self, len: usize) -> Vec<u8> {
+        let mut bytes = vec![0u8; len];
+        self.fill(&mut bytes);
+        bytes
+    }
+
+    /// Hex-encoded random identifier of `len` bytes
+    pub fn id(&self, len: usize) -> String {
+        hex::encode(self.bytes(len))
+    }
+
+    /// Uniform in `0..n`, by rejection so no value is favored
+    pub fn below(&self, n: u32) -> u32 {
+        assert!(n > 0, "below() needs a non-empty range");
+        let zone = u32::MAX - (u32::MAX % n);
+        loop {
+            let mut bytes = [0u8; 4];
+            self.fill(&mut bytes);
+            let value = u32::from_le_bytes(bytes);
+            if value < zone {
+                return value % n;
+            }
+        }
+    }
+
+    /// A generator seeded from this source, for APIs that take an `RngCore`
+    pub fn rng(&self) -> ChaCha20Rng {
+        let mut seed = Zeroizing::new([0u8; 32]);
+        self.fill(seed.as_mut());
+        ChaCha20Rng::from_seed(*seed)
+    }
+}
+
+impl Drop for RandomnessSource {
+    fn drop(&mut self) {
+        if let Some(fd) = self.nsm {
+            nsm_exit(fd);
+        }
+    }
+}
+
+/// What GET /random attests to: SHA-256 of DOMAIN || len(nonce) || nonce ||
+/// random, so the draw can't be reused for another caller's nonce
+pub fn binding(nonce: &[u8], random: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(DOMAIN);
+    hasher.update((nonce.len() as u64).to_be_bytes());
+    hasher.update(nonce);
+    hasher.update(random);
+    hasher.finalize().to_vec()
+}
@@ -0,0 +1,350 @@
+/**
+ * Redaction
+ * Masking third-party PII in vault content before it is released
+ *
+ * A vault's redaction policy names the detectors to run and any literal
+ * terms (e.g. a third party's name) to mask. Every release path applies
+ * it inside the enclave, so recipients only ever receive the redacted
+ * copy. Matches are masked character for character with '*', leaving
+ * the text's layout intact, and the summary records what was masked
+ * where, never the masked text itself:
+ *
+ *   ssn:            123-45-6789 or 123 45 6789, valid area/group/serial
+ *   card_number:    13-19 digits, optionally grouped, passing Luhn
+ *   account_number: a standalone run of 8-17 digits
+ *   email:          local@domain.tld
+ *
+ * Only UTF-8 text can be redacted; releasing anything else from a vault
+ * with a redaction policy fails rather than leaking unmasked content.
+ */
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use zeroize::Zeroizing;
+
+use crate::store::Store;
+
+const NAMESPACE: &str = "redaction_policies";
+const MAX_TERMS: usize = 64;
+const MAX_TERM_CHARS: usize = 128;
+const MASK: char = '*';
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Detector {
+    Ssn,
+    CardNumber,
+    AccountNumber,
+    Email,
+}
+
+impl Detector {
+    fn name(self) -> &'static str {
+        match self {
+            Detector::Ssn => "ssn",
+            Detector::CardNumber => "card_number",
+            Detector::AccountNumber => "account_number",
+            Detector::Email => "email",
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RedactionPolicy {
+    pub vault_id: String,
+    pub owner: String,
+    pub detectors: Vec<Detector>,
+    /// Literal phrases masked wherever they appear, case-insensitive
+    pub terms: Vec<String>,
+    pub updated_at: u64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MaskedSpan {
+    pub detector: String, // Detector name, or "term"
+    pub start: usize,     // Character offset
+    pub len: usize,       // Characters
+}
+
+/// What was masked in one released document
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RedactionSummary {
+    /// Matches per detector, including detectors that found nothing
+    pub counts: BTreeMap<String, usize>,
+    pub spans: Vec<MaskedSpan>,
+    pub redacted_sha256: String, // Hex, of the redacted copy
+}
+
+pub struct RedactionService {
+    store: Arc<Store>,
+}
+
+impl RedactionService {
+    pub fn new(store: Arc<Store>) -> Self {
+        Self { store }
+    }
+
+    /// Replace the vault's redaction policy; no detectors and no terms
+    /// clears it. The caller has been authorized.
+    pub fn set(
+        &self,
+        vault_id: &str,
+        owner: &str,
+        mut detectors: Vec<Detector>,
+        terms: Vec<String>,
+    ) -> Result<Option<RedactionPolicy>, String> {
+        let mut terms: Vec<String> = terms
+            .into_iter()
+            .map(|term| term.trim().to_string())
+            .collect();
+        if terms.len() > MAX_TERMS {
+            return Err(format!("At most {} terms are allowed", MAX_TERMS));
+        }
+        if let Some(term) = terms
+            .iter()
+            .find(|t| t.is_empty() || t.chars().count() > MAX_TERM_CHARS)
+        {
+            return Err(format!(
+                "Terms must be 1 to {} characters: {:?}",
+                MAX_TERM_CHARS, term
+            ));
+        }
+        detectors.sort();
+        detectors.dedup();
+        terms.sort();
+        terms.dedup();
+
+        if detectors.is_empty() && terms.is_empty() {
+            self.store.update(NAMESPACE, |ns| {
+                ns.remove(vault_id);
+            })?;
+            return Ok(None);
+        }
+        let policy = RedactionPolicy {
+            vault_id: vault_id.to_string(),
+            owner: owner.to_string(),
+            detectors,
+            terms,
+            updated_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+        };
+        self.store.put(NAMESPACE, vault_id, &policy)?;
+        Ok(Some(policy))
+    }
+
+    pub fn policy(&self, vault_id: &str) -> Result<Option<RedactionPolicy>, String> {
+        self.store.get(NAMESPACE, vault_id)
+    }
+}
+
+/// The redacted copy of `content` and a summary of what was masked
+pub fn redact(
+    policy: &RedactionPolicy,
+    content: &[u8],
+) -> Result<(Zeroizing<Vec<u8>>, RedactionSummary), String> {
+    let text = std::str::from_utf8(content)
+        .map_err(|_| "Content is not UTF-8 text and can't be redacted".to_string())?;
+    let mut chars = Zeroizing::new(text.chars().collect::<Vec<char>>());
+    let mut masked = vec![false; chars.len()];
+    let mut counts = BTreeMap::new();
+    let mut spans = Vec::new();
+
+    let mut found: Vec<(&str, Vec<(usize, usize)>)> = policy
+        .detectors
+        .iter()
+        .map(|detector| (detector.name(), detect(*detector, &chars)))
+        .collect();
+    if !policy.terms.is_empty() {
+        let lowered: Vec<char> = chars.iter().map(|c| fold(*c)).collect();
+        let matches = policy
+            .terms
+            .iter()
+            .flat_map(|term| find_term(&lowered, term))
+            .collect();
+        found.push(("term", matches));
+    }
+
+    for (detector, matches) in found {
+        let count = counts.entry(detector.to_string()).or_insert(0);
+        for (start, end) in matches {
+            // A match overlapping one already masked was found by an
+            // earlier detector
+            if masked[start..end].iter().any(|m| *m) {
+                continue;
+            }
+            masked[start..end].fill(true);
+            *count += 1;
+            spans.push(MaskedSpan {
+                detector: detector.to_string(),
+                start,
+                len: end - start,
+            });
+        }
+    }
+    for (c, masked) in chars.iter_mut().zip(&masked) {
+        if *masked {
+            *c = MASK;
+        }
+    }
+    spans.sort_by_key(|span| span.start);
+
+    let redacted = Zeroizing::new(chars.iter().collect::<String>().into_bytes());
+    let summary = RedactionSummary {
+        counts,
+        spans,
+        redacted_sha256: hex::encode(Sha256::digest(&redacted[..])),
+    };
+    Ok((redacted, summary))
+}
+
+/// Character ranges matched by one detector
+fn detect(detector: Detector, chars: &[char]) -> Vec<(usize, usize)> {
+    match detector {
+        Detector::Email => emails(chars),
+        Detector::Ssn => digit_groups(chars)
+            .into_iter()
+            .filter(|&(start, end)| is_ssn(&chars[start..end]))
+            .collect(),
+        Detector::CardNumber => digit_groups(chars)
+            .into_iter()
+            .filter(|&(start, end)| is_card_number(&chars[start..end]))
+            .collect(),
+        Detector::AccountNumber => digit_groups(chars)
+            .into_iter()
+            .flat_map(|(start, end)| runs(chars, start, end))
+            .filter(|&(start, end)| (8..=17).contains(&(end - start)))
+            .collect(),
+    }
+}
+
+/// Standalone numbers: digits joined by single spaces or dashes, not
+/// touching letters or other digits
+fn digit_groups(chars: &[char]) -> Vec<(usize, usize)> {
+    let mut groups = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if !chars[i].is_ascii_digit() || (i > 0 && chars[i - 1].is_alphanumeric()) {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        let mut end = i + 1;
+        loop {
+            if end < chars.len() && chars[end].is_ascii_digit() {
+                end += 1;
+            } else if end + 1 < chars.len()
+                && matches!(chars[end], ' ' | '-')
+                && chars[end + 1].is_ascii_digit()
+            {
+                end += 2;
+            } else {
+                break;
+            }
+        }
+        if end == chars.len() || !chars[end].is_alphanumeric() {
+            groups.push((start, end));
+        }
+        i = end;
+    }
+    groups
+}
+
+/// Unseparated digit runs within a group
+fn runs(chars: &[char], start: usize, end: usize) -> Vec<(usize, usize)> {
+    let mut runs = Vec::new();
+    let mut run_start = start;
+    for (i, c) in chars[start..end].iter().enumerate() {
+        if !c.is_ascii_digit() {
+            runs.push((run_start, start + i));
+            run_start = start + i + 1;
+        }
+    }
+    runs.push((run_start, end));
+    runs
+}
+
+fn is_ssn(group: &[char]) -> bool {
+    if group.len() != 11 || group[3] != group[6] || !matches!(group[3], ' ' | '-') {
+        return false;
+    }
+    let part = |range: std::ops::Range<usize>| -> Option<u32> {
+        group[range].iter().collect::<String>().parse().ok()
+    };
+    let (Some(area), Some(group_no), Some(serial)) = (part(0..3), part(4..6), part(7..11)) else {
+        return false;
+    };
+    area != 0 && area != 666 && area < 900 && group_no != 0 && serial != 0
+}
+
+fn is_card_number(group: &[char]) -> bool {
+    let digits: Vec<u32> = group.iter().filter_map(|c| c.to_digit(10)).collect();
+    if !(13..=19).contains(&digits.len()) {
+        return false;
+    }
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &d)| match (i % 2, d * 2) {
+            (0, _) => d,
+            (_, doubled) if doubled > 9 => doubled - 9,
+            (_, doubled) => doubled,
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+fn emails(chars: &[char]) -> Vec<(usize, usize)> {
+    let local = |c: char| c.is_ascii_alphanumeric() || "._%+-".contains(c);
+    let domain = |c: char| c.is_ascii_alphanumeric() || ".-".contains(c);
+
+    let mut found = Vec::new();
+    for at in (0..chars.len()).filter(|&i| chars[i] == '@') {
+        let mut start = at;
+        while start > 0 && local(chars[start - 1]) {
+            start -= 1;
+        }
+        let mut end = at + 1;
+        while end < chars.len() && domain(chars[end]) {
+            end += 1;
+        }
+        // A sentence-ending period isn't part of the domain
+        while end > at + 1 && chars[end - 1] == '.' {
+            end -= 1;
+        }
+        let host = &chars[at + 1..end];
+        let dotted = host
+            .iter()
+            .rposition(|c| *c == '.')
+            .is_some_and(|dot| dot > 0 && dot + 1 < host.len());
+        if start < at && dotted && found.last().is_none_or(|&(_, e)| e <= start) {
+            found.push((start, end));
+        }
+    }
+    found
+}
+
+fn find_term(lowered: &[char], term: &str) -> Vec<(usize, usize)> {
+    let term: Vec<char> = term.chars().map(fold).collect();
+    if term.is_empty() || term.len() > lowered.len() {
+        return Vec::new();
+    }
+    (0..=lowered.len() - term.len())
+        .filter(|&i| lowered[i..i + term.len()] == term[..])
+        .map(|i| (i, i + term.len()))
+        .collect()
+}
+
+/// Case folding that keeps one character per character
+fn fold(c: char) -> char {
+    let mut lower = c.to_lowercase();
+    match (lower.next(), lower.next()) {
+        (Some(l), None) => l,
+        _ => c,
+    }
+}
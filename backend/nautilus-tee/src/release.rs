@@ -0,0 +1,258 @@
+/**
+ * Vault Release
+ * Re-encrypting unlocked vault content to its beneficiaries
+ *
+ * Once guardians have reassembled a vault's content key (see secrets),
+ * the enclave decrypts the vault content and immediately seals it to
+ * every registered beneficiary with HPKE (RFC 9180, base mode,
+ * DHKEM(X25519, HKDF-SHA256) / HKDF-SHA256 / ChaCha20-Poly1305). The
+ * plaintext never leaves the enclave; whoever triggers the release only
+ * ever handles ciphertexts.
+ *
+ * Vault content is ChaCha20-Poly1305 under the content key with AAD
+ * "lumina-vault-content:<vault_id>". Each beneficiary ciphertext uses
+ * AAD "lumina-vault-release:<release_id>:<address>", tying it to the
+ * persisted release record.
+ */
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use hpke::aead::ChaCha20Poly1305;
+use hpke::kdf::HkdfSha256;
+use hpke::kem::X25519HkdfSha256;
+use hpke::{Deserializable, Kem, OpModeS, Serializable};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use zeroize::Zeroizing;
+
+use crate::auth::normalize_address;
+use crate::crypto;
+use crate::redaction::{self, RedactionPolicy, RedactionSummary};
+use crate::secrets::ContentKey;
+use crate::store::Store;
+
+const BENEFICIARIES_NAMESPACE: &str = "vault_beneficiaries";
+const RELEASES_NAMESPACE: &str = "vault_releases";
+const HPKE_INFO: &[u8] = b"lumina-vault-release-v1";
+const MAX_BENEFICIARIES: usize = 64;
+
+type HpkePublicKey = <X25519HkdfSha256 as Kem>::PublicKey;
+
+#[derive(Deserialize)]
+pub struct BeneficiaryKey {
+    pub address: String,
+    pub public_key: String, // Base64 X25519 key releases are sealed to
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Beneficiary {
+    pub address: String,
+    pub public_key: String,
+    pub fingerprint: String, // Hex sha256 of the key bytes
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BeneficiarySet {
+    pub vault_id: String,
+    pub owner: String,
+    pub beneficiaries: Vec<Beneficiary>,
+    pub updated_at: u64,
+}
+
+#[derive(Deserialize)]
+pub struct VaultCiphertext {
+    pub nonce: String,      // Base64 12-byte nonce
+    pub ciphertext: String, // Base64 content + tag
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ReleaseRecipient {
+    pub address: String,
+    pub fingerprint: String,
+    pub ciphertext_sha256: String, // Hex, over encapsulated key || ciphertext
+}
+
+/// Persisted; states what was released to whom, without any ciphertext
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ReleaseRecord {
+    pub release_id: String,
+    pub vault_id: String,
+    pub requester: String,
+    pub content_sha256: String, // Hex, of the vault ciphertext
+    pub recipients: Vec<ReleaseRecipient>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redaction: Option<RedactionSummary>, // What was masked before sealing
+    pub created_at: u64,
+}
+
+impl ReleaseRecord {
+    pub fn digest(&self) -> Result<[u8; 32], String> {
+        let bytes = serde_json::to_vec(self).map_err(|e| e.to_string())?;
+        Ok(Sha256::digest(bytes).into())
+    }
+}
+
+#[derive(Serialize)]
+pub struct SealedRelease {
+    pub address: String,
+    pub encapsulated_key: String, // Base64 HPKE enc
+    pub ciphertext: String,       // Base64
+}
+
+pub struct ReleaseService {
+    store: Arc<Store>,
+}
+
+impl ReleaseService {
+    pub fn new(store: Arc<Store>) -> Self {
+        Self { store }
+    }
+
+    /// Replace the vault's beneficiaries. `owner` is the owner of the
+    /// vault's guardian shares.
+    pub fn set_beneficiaries(
+        &self,
+        vault_id: &str,
+        caller: &str,
+        owner: &str,
+        keys: &[BeneficiaryKey],
+    ) -> Result<BeneficiarySet, String> {
+        if caller != owner {
+            return Err("Only the vault owner may register beneficiaries".to_string());
+        }
+        if keys.is_empty() || keys.len() > MAX_BENEFICIARIES {
+            return Err(format!(
+                "Between 1 and {} beneficiaries are required",
+                MAX_BENEFICIARIES
+            ));
+        }
+
+        let mut seen = HashSet::new();
+        let mut beneficiaries = Vec::with_capacity(keys.len());
+        for key in keys {
+            let address = normalize_address(&key.address);
+            if !seen.insert(address.clone()) {
+                return Err(format!("Beneficiary {} is listed twice", address));
+            }
+            let bytes = crypto::decode_public_key(&key.public_key)?.to_bytes();
+            beneficiaries.push(Beneficiary {
+                address,
+                public_key: key.public_key.clone(),
+                fingerprint: hex::encode(Sha256::digest(bytes)),
+            });
+        }
+
+        let set = BeneficiarySet {
+            vault_id: vault_id.to_string(),
+            owner: owner.to_string(),
+            beneficiaries,
+            updated_at: now(),
+        };
+        self.store.put(BENEFICIARIES_NAMESPACE, vault_id, &set)?;
+        Ok(set)
+    }
+
+    pub fn beneficiaries(&self, vault_id: &str) -> Result<Option<BeneficiarySet>, String> {
+        self.store.get(BENEFICIARIES_NAMESPACE, vault_id)
+    }
+
+    /// Decrypt the vault content and seal it to every beneficiary. The
+    /// policy engine must already have allowed the release.
+    pub fn release(
+        &self,
+        set: &BeneficiarySet,
+        requester: &str,
+        key: &ContentKey,
+        content: &VaultCiphertext,
+        redaction: Option<&RedactionPolicy>,
+    ) -> Result<(ReleaseRecord, Vec<SealedRelease>), String> {
+        let nonce = crypto::decode(&content.nonce)?;
+        let ciphertext = crypto::decode(&content.ciphertext)?;
+        let plaintext = Zeroizing::new(crypto::aead_decrypt(
+            key,
+            &nonce,
+            &ciphertext,
+            content_aad(&set.vault_id).as_bytes(),
+        )?);
+        let (plaintext, redaction) = match redaction {
+            Some(policy) => {
+                let (redacted, summary) = redaction::redact(policy, &plaintext)?;
+                (redacted, Some(summary))
+            }
+            None => (plaintext, None),
+        };
+
+        let mut id = [0u8; 16];
+        OsRng.fill_bytes(&mut id);
+        let release_id = hex::encode(id);
+
+        let mut recipients = Vec::with_capacity(set.beneficiaries.len());
+        let mut sealed = Vec::with_capacity(set.beneficiaries.len());
+        for beneficiary in &set.beneficiaries {
+            let public_key =
+                HpkePublicKey::from_bytes(&crypto::decode(&beneficiary.public_key)?)
+                    .map_err(|_| format!("Invalid key for beneficiary {}", beneficiary.address))?;
+            let (encapsulated, sealed_content) =
+                hpke::single_shot_seal::<ChaCha20Poly1305, HkdfSha256, X25519HkdfSha256, _>(
+                    &OpModeS::Base,
+                    &public_key,
+                    HPKE_INFO,
+                    &plaintext,
+                    release_aad(&release_id, &beneficiary.address).as_bytes(),
+                    &mut OsRng,
+                )
+                .map_err(|e| format!("HPKE seal failed: {}", e))?;
+            let encapsulated = encapsulated.to_bytes();
+
+            let mut hasher = Sha256::new();
+            hasher.update(encapsulated);
+            hasher.update(&sealed_content);
+            recipients.push(ReleaseRecipient {
+                address: beneficiary.address.clone(),
+                fingerprint: beneficiary.fingerprint.clone(),
+                ciphertext_sha256: hex::encode(hasher.finalize()),
+            });
+            sealed.push(SealedRelease {
+                address: beneficiary.address.clone(),
+                encapsulated_key: STANDARD.encode(encapsulated),
+                ciphertext: STANDARD.encode(&sealed_content),
+            });
+        }
+
+        let record = ReleaseRecord {
+            release_id: release_id.clone(),
+            vault_id: set.vault_id.clone(),
+            requester: requester.to_string(),
+            content_sha256: hex::encode(Sha256::digest(&ciphertext)),
+            recipients,
+            redaction,
+            created_at: now(),
+        };
+        self.store.put(RELEASES_NAMESPACE, &release_id, &record)?;
+        Ok((record, sealed))
+    }
+
+    pub fn record(&self, release_id: &str) -> Result<Option<ReleaseRecord>, String> {
+        self.store.get(RELEASES_NAMESPACE, release_id)
+    }
+}
+
+fn content_aad(vault_id: &str) -> String {
+    format!("lumina-vault-content:{}", vault_id)
+}
+
+fn release_aad(release_id: &str, address: &str) -> String {
+    format!("lumina-vault-release:{}:{}", release_id, address)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
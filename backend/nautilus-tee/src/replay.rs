@@ -0,0 +1,199 @@
+/**
+ * Replay Protection
+ * Accepts each signed request at most once within its validity window
+ *
+ * Requests carry a timestamp and a caller-chosen nonce. Timestamps outside
+ * the window are rejected as expired; nonces are remembered (per signer)
+ * until their timestamp leaves the window, so a captured request can't be
+ * resubmitted even across restarts.
+ *
+ * Nonces are persisted in shards, one store namespace per window of
+ * request timestamps, so recording one rewrites only its own shard. Once
+ * a shard's timestamps can no longer pass check_timestamp it is dropped
+ * whole.
+ */
+
+use axum::http::StatusCode;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::AppError;
+use crate::store::Store;
+
+/// Shard namespaces are this followed by the zero-padded shard number
+const SHARD_PREFIX: &str = "nonces.";
+
+/// Longest nonce accepted, to bound the size of the persisted cache
+const MAX_NONCE_LEN: usize = 128;
+
+pub struct ReplayGuard {
+    store: Arc<Store>,
+    window_secs: u64,
+    seen: Mutex<Seen>,
+}
+
+struct Seen {
+    // "signer:nonce" -> request timestamp
+    nonces: HashMap<String, u64>,
+    /// Shards below this one have been dropped
+    oldest_shard: u64,
+}
+
+impl ReplayGuard {
+    pub fn new(store: Arc<Store>, window_secs: u64) -> Result<Self, String> {
+        let guard = Self {
+            store,
+            window_secs,
+            seen: Mutex::new(Seen {
+                nonces: HashMap::new(),
+                oldest_shard: 0,
+            }),
+        };
+
+        let mut seen = guard.seen.lock().unwrap();
+        for shard in guard.store.namespaces(SHARD_PREFIX)? {
+            seen.nonces.extend(guard.store.list::<u64>(&shard)?);
+        }
+        guard.prune(&mut seen)?;
+        drop(seen);
+        Ok(guard)
+    }
+
+    /// Reject timestamps too far from the enclave clock in either direction
+    pub fn check_timestamp(&self, timestamp: u64) -> Result<(), AppError> {
+        let now = now();
+        if timestamp.abs_diff(now) > self.window_secs {
+            return Err(AppError::unauthorized(
+                "REQUEST_EXPIRED",
+                format!(
+                    "Request timestamp is outside the {}s validity window",
+                    self.window_secs
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Record a nonce, failing if the signer has already used it
+    pub fn record(&self, signer: &str, nonce: &str, timestamp: u64) -> Result<(), AppError> {
+        if nonce.is_empty() || nonce.len() > MAX_NONCE_LEN {
+            return Err(AppError::bad_request(
+                "INVALID_NONCE",
+                format!("Nonce must be 1-{} characters", MAX_NONCE_LEN),
+            ));
+        }
+
+        let key = format!("{}:{}", signer, nonce);
+
+        let mut seen = self.seen.lock().unwrap();
+        if seen.nonces.contains_key(&key) {
+            return Err(AppError::new(
+                StatusCode::CONFLICT,
+                "REQUEST_REPLAYED",
+                "Request nonce has already been used",
+            ));
+        }
+        self.prune(&mut seen).map_err(AppError::internal)?;
+
+        self.store
+            .update(&shard_namespace(self.shard(timestamp)), |ns| {
+                ns.insert(key.clone(), timestamp.into());
+            })
+            .map_err(AppError::internal)?;
+        seen.nonces.insert(key, timestamp);
+        Ok(())
+    }
+
+    /// Forget nonces older than the window, dropping shards that hold
+    /// nothing else
+    fn prune(&self, seen: &mut Seen) -> Result<(), String> {
+        // Anything older than the window can no longer pass check_timestamp,
+        // so its nonce no longer needs remembering
+        let cutoff = now().saturating_sub(self.window_secs);
+        seen.nonces.retain(|_, ts| *ts >= cutoff);
+
+        // Every timestamp in a shard below cutoff's is older than cutoff
+        let oldest = self.shard(cutoff);
+        if oldest <= seen.oldest_shard {
+            return Ok(());
+        }
+        let oldest_namespace = shard_namespace(oldest);
+        for shard in self.store.namespaces(SHARD_PREFIX)? {
+            if shard < oldest_namespace {
+                self.store.drop_namespace(&shard)?;
+            }
+        }
+        seen.oldest_shard = oldest;
+        Ok(())
+    }
+
+    fn shard(&self, timestamp: u64) -> u64 {
+        timestamp / self.window_secs.max(1)
+    }
+}
+
+/// Zero-padded so namespace order is shard order
+fn shard_namespace(shard: u64) -> String {
+    format!("{}{:016}", SHARD_PREFIX, shard)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW: u64 = 300;
+
+    fn guard(name: &str) -> (Arc<Store>, ReplayGuard) {
+        let dir = std::env::temp_dir().join(format!("lumina-replay-tests-{}-{}", name, now()));
+        let store = Arc::new(Store::open(&dir).unwrap());
+        (store.clone(), ReplayGuard::new(store, WINDOW).unwrap())
+    }
+
+    #[test]
+    fn nonces_are_accepted_once_per_signer_even_across_restarts() {
+        let (store, replay) = guard("once");
+        let ts = now();
+
+        replay.record("0xa", "n1", ts).unwrap();
+        let replayed = replay.record("0xa", "n1", ts).err().unwrap();
+        assert_eq!(replayed.code, "REQUEST_REPLAYED");
+        replay.record("0xb", "n1", ts).unwrap();
+        replay.record("0xa", "n2", ts).unwrap();
+
+        let restarted = ReplayGuard::new(store, WINDOW).unwrap();
+        assert!(restarted.record("0xa", "n1", ts).is_err());
+        assert!(restarted.record("0xb", "n1", ts).is_err());
+
+        assert_eq!(
+            replay.record("0xa", "", ts).err().unwrap().code,
+            "INVALID_NONCE"
+        );
+        assert!(replay.check_timestamp(ts - WINDOW - 1).is_err());
+        assert!(replay.check_timestamp(ts + WINDOW + 1).is_err());
+    }
+
+    #[test]
+    fn expired_shards_are_dropped() {
+        let dir = std::env::temp_dir().join(format!("lumina-replay-tests-prune-{}", now()));
+        let store = Arc::new(Store::open(&dir).unwrap());
+        let ts = now();
+        let expired = ts - 3 * WINDOW;
+        let expired_shard = shard_namespace(expired / WINDOW);
+        store.put(&expired_shard, "0xa:n1", &expired).unwrap();
+
+        let replay = ReplayGuard::new(store.clone(), WINDOW).unwrap();
+        replay.record("0xa", "n1", ts).unwrap();
+
+        let shards = store.namespaces(SHARD_PREFIX).unwrap();
+        assert_eq!(shards, vec![shard_namespace(replay.shard(ts))]);
+        assert_eq!(store.list::<u64>(&shards[0]).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,364 @@
+/**
+ * Retry Policies
+ * Backoff for the enclave's calls out through the parent
+ *
+ * Every call the enclave makes leaves through a relay on the parent, and
+ * relays restart, nodes hiccup and KMS throttles. Each dependency has a
+ * policy: how many attempts, the exponential backoff between them (capped,
+ * with a random part so restarted enclaves don't retry in step), and which
+ * kinds of failure are worth retrying at all. A call fails with a Failure
+ * naming its kind, so a malformed answer isn't retried like a refused
+ * connection, and a relay that may already have acted on a request isn't
+ * sent it twice.
+ *
+ * Policies are overridden with TEE_RETRY_POLICIES entries of the form
+ * `dependency=attempts/base_ms/max_ms[/jitter_pct]`, e.g. "chain=6/500/10000".
+ * Which failures are retried stays fixed per dependency. Counts of calls,
+ * retries and exhausted policies go to admins through POST /admin/retries.
+ * Each attempt also passes the dependency's circuit breaker (see breaker).
+ *
+ * Walrus and Seal key servers are reached by the backend, not the
+ * enclave, which retries them itself.
+ */
+
+use rand::Rng;
+use serde::Serialize;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::breaker;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Dependency {
+    /// Sui JSON-RPC through the chain relay
+    Chain,
+    /// The secrets bootstrap, which the parent answers through KMS
+    Kms,
+    /// The notification relay
+    Notifications,
+    /// The archive agent in front of S3
+    Archive,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    /// The relay couldn't be reached; nothing was sent
+    Unreachable,
+    Timeout,
+    /// The connection failed part way through an exchange
+    Transport,
+    /// The far side answered with an error
+    Remote,
+    /// The far side answered with something undecodable
+    Malformed,
+    /// Not attempted: the dependency's circuit breaker is open (see breaker)
+    CircuitOpen,
+}
+
+/// A failed attempt, classified for the retry policy
+#[derive(Debug)]
+pub struct Failure {
+    pub kind: FailureKind,
+    pub message: String,
+}
+
+impl Failure {
+    pub fn new(kind: FailureKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+    /// Up to this share of each delay is taken off at random
+    pub jitter_pct: u8,
+    pub retry_on: &'static [FailureKind],
+}
+
+#[derive(Serialize)]
+pub struct RetryStats {
+    pub dependency: Dependency,
+    pub policy: RetryPolicy,
+    pub calls: u64,
+    pub retries: u64,
+    /// Calls that succeeded after at least one retry
+    pub recovered: u64,
+    /// Calls that failed on their last allowed attempt
+    pub exhausted: u64,
+    /// Calls that failed in a way the policy doesn't retry
+    pub not_retried: u64,
+}
+
+struct Counters {
+    calls: AtomicU64,
+    retries: AtomicU64,
+    recovered: AtomicU64,
+    exhausted: AtomicU64,
+    not_retried: AtomicU64,
+}
+
+impl Counters {
+    const fn new() -> Self {
+        Self {
+            calls: AtomicU64::new(0),
+            retries: AtomicU64::new(0),
+            recovered: AtomicU64::new(0),
+            exhausted: AtomicU64::new(0),
+            not_retried: AtomicU64::new(0),
+        }
+    }
+}
+
+static POLICIES: OnceLock<Vec<RetryPolicy>> = OnceLock::new();
+static COUNTERS: [Counters; Dependency::ALL.len()] = [
+    Counters::new(),
+    Counters::new(),
+    Counters::new(),
+    Counters::new(),
+];
+
+impl Dependency {
+    pub const ALL: [Dependency; 4] = [
+        Dependency::Chain,
+        Dependency::Kms,
+        Dependency::Notifications,
+        Dependency::Archive,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Dependency::Chain => "chain",
+            Dependency::Kms => "kms",
+            Dependency::Notifications => "notifications",
+            Dependency::Archive => "archive",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|d| d.name() == name)
+    }
+
+    pub fn index(self) -> usize {
+        self as usize
+    }
+
+    fn default_policy(self) -> RetryPolicy {
+        use FailureKind::{Timeout, Transport, Unreachable};
+        match self {
+            Dependency::Chain => RetryPolicy {
+                max_attempts: 4,
+                base_delay_ms: 250,
+                max_delay_ms: 4_000,
+                jitter_pct: 50,
+                retry_on: &[Unreachable, Timeout, Transport],
+            },
+            // The parent may still be starting its relay when the enclave boots
+            Dependency::Kms => RetryPolicy {
+                max_attempts: 6,
+                base_delay_ms: 1_000,
+                max_delay_ms: 15_000,
+                jitter_pct: 25,
+                retry_on: &[Unreachable, Timeout, Transport],
+            },
+            // Once jobs are sent they may be delivered, and jobs retry
+            // themselves, so only a connection that never opened is retried
+            Dependency::Notifications => RetryPolicy {
+                max_attempts: 3,
+                base_delay_ms: 500,
+                max_delay_ms: 4_000,
+                jitter_pct: 50,
+                retry_on: &[Unreachable],
+            },
+            // Objects are stored under fixed keys, so sending one twice is
+            // harmless, and a sweep that gives up runs again later
+            Dependency::Archive => RetryPolicy {
+                max_attempts: 3,
+                base_delay_ms: 1_000,
+                max_delay_ms: 8_000,
+                jitter_pct: 50,
+                retry_on: &[Unreachable, Timeout, Transport],
+            },
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// How long to wait after failed attempt `attempt` (from 1)
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(32);
+        let full = self
+            .base_delay_ms
+            .saturating_mul(1u64 << exponent)
+            .min(self.max_delay_ms);
+        let jitter = full * u64::from(self.jitter_pct.min(100)) / 100;
+        let taken = if jitter == 0 {
+            0
+        } else {
+            rand::thread_rng().gen_range(0..=jitter)
+        };
+        Duration::from_millis(full - taken)
+    }
+}
+
+/// Install policy overrides; call once, before any call is made
+pub fn configure(overrides: &[String]) -> Result<(), String> {
+    let policies = parse_overrides(overrides)?;
+    POLICIES
+        .set(policies)
+        .map_err(|_| "Retry policies are already configured".to_string())
+}
+
+pub fn policy(dependency: Dependency) -> RetryPolicy {
+    match POLICIES.get() {
+        Some(policies) => policies[dependency.index()].clone(),
+        None => dependency.default_policy(),
+    }
+}
+
+/// Make `call` under the dependency's policy and circuit breaker; the
+/// error is the last attempt's
+pub async fn run<T, F, Fut>(dependency: Dependency, mut call: F) -> Result<T, String>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, Failure>>,
+{
+    let policy = policy(dependency);
+    let counters = &COUNTERS[dependency.index()];
+    counters.calls.fetch_add(1, Ordering::Relaxed);
+    let mut attempt = 1;
+    loop {
+        if let Err(failure) = breaker::admit(dependency) {
+            counters.not_retried.fetch_add(1, Ordering::Relaxed);
+            return Err(failure.message);
+        }
+        let outcome = call().await;
+        breaker::record(dependency, outcome.as_ref().err().map(|f| f.kind));
+        let failure = match outcome {
+            Ok(value) => {
+                if attempt > 1 {
+                    counters.recovered.fetch_add(1, Ordering::Relaxed);
+                }
+                return Ok(value);
+            }
+            Err(failure) => failure,
+        };
+        if !policy.retry_on.contains(&failure.kind) {
+            counters.not_retried.fetch_add(1, Ordering::Relaxed);
+            return Err(failure.message);
+        }
+        if attempt >= policy.max_attempts {
+            counters.exhausted.fetch_add(1, Ordering::Relaxed);
+            warn!(
+                "{} call failed after {} attempts: {}",
+                dependency.name(),
+                attempt,
+                failure.message
+            );
+            return Err(failure.message);
+        }
+
+        let delay = policy.delay(attempt);
+        warn!(
+            "{} call failed ({:?}: {}); attempt {} of {} in {:?}",
+            dependency.name(),
+            failure.kind,
+            failure.message,
+            attempt + 1,
+            policy.max_attempts,
+            delay
+        );
+        counters.retries.fetch_add(1, Ordering::Relaxed);
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+pub fn stats() -> Vec<RetryStats> {
+    Dependency::ALL
+        .iter()
+        .map(|&dependency| {
+            let counters = &COUNTERS[dependency.index()];
+            RetryStats {
+                dependency,
+                policy: policy(dependency),
+                calls: counters.calls.load(Ordering::Relaxed),
+                retries: counters.retries.load(Ordering::Relaxed),
+                recovered: counters.recovered.load(Ordering::Relaxed),
+                exhausted: counters.exhausted.load(Ordering::Relaxed),
+                not_retried: counters.not_retried.load(Ordering::Relaxed),
+            }
+        })
+        .collect()
+}
+
+/// `dependency=attempts/base_ms/max_ms[/jitter_pct]` entries over the
+/// defaults, in Dependency::ALL order
+fn parse_overrides(overrides: &[String]) -> Result<Vec<RetryPolicy>, String> {
+    let mut policies: Vec<RetryPolicy> =
+        Dependency::ALL.iter().map(|d| d.default_policy()).collect();
+    for entry in overrides {
+        let invalid = || format!("Invalid TEE_RETRY_POLICIES entry: {}", entry);
+        let (name, spec) = entry.split_once('=').ok_or_else(invalid)?;
+        let dependency = Dependency::from_name(name.trim())
+            .ok_or_else(|| format!("Unknown retry dependency: {}", name.trim()))?;
+        let fields: Vec<u64> = spec
+            .split('/')
+            .map(|f| f.trim().parse().map_err(|_| invalid()))
+            .collect::<Result<_, _>>()?;
+        let (attempts, base, max, jitter) = match fields[..] {
+            [attempts, base, max] => (attempts, base, max, None),
+            [attempts, base, max, jitter] => (attempts, base, max, Some(jitter)),
+            _ => return Err(invalid()),
+        };
+        let policy = &mut policies[dependency.index()];
+        policy.max_attempts = u32::try_from(attempts).map_err(|_| invalid())?;
+        policy.base_delay_ms = base;
+        policy.max_delay_ms = max;
+        if let Some(jitter) = jitter {
+            policy.jitter_pct = u8::try_from(jitter)
+                .ok()
+                .filter(|j| *j <= 100)
+                .ok_or_else(invalid)?;
+        }
+        if policy.max_attempts == 0 || policy.base_delay_ms > policy.max_delay_ms {
+            return Err(invalid());
+        }
+    }
+    Ok(policies)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overrides_and_backoff_stay_within_bounds() {
+        let policies = parse_overrides(&["chain=5/100/1000/0".to_string()]).unwrap();
+        let chain = &policies[Dependency::Chain.index()];
+        assert_eq!(chain.max_attempts, 5);
+        let delays: Vec<u128> = (1..=5).map(|a| chain.delay(a).as_millis()).collect();
+        assert_eq!(delays, vec![100, 200, 400, 800, 1000]);
+
+        let kms = &policies[Dependency::Kms.index()];
+        for attempt in 1..10 {
+            let delay = kms.delay(attempt).as_millis() as u64;
+            let full = (kms.base_delay_ms << (attempt - 1)).min(kms.max_delay_ms);
+            assert!(delay <= full && delay >= full * 3 / 4);
+        }
+
+        assert!(parse_overrides(&["chain=0/100/1000".to_string()]).is_err());
+        assert!(parse_overrides(&["walrus=3/100/1000".to_string()]).is_err());
+        assert!(parse_overrides(&["chain=3/100/1000/101".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,44 @@
+/**
+ * Analytics Routes
+ * Differentially private usage reports for admins, attested so product
+ * teams can check a report came from the enclave unaltered
+ */
+
+use axum::{extract::State, response::Json, routing::post, Extension, Router};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::analytics::AnalyticsReport;
+use crate::attestation::Attestation;
+use crate::auth::VerifiedSigner;
+use crate::error::AppError;
+use crate::routes::require_admin;
+use crate::AppState;
+
+#[derive(Serialize)]
+struct ReportResponse {
+    report: AnalyticsReport,
+    attestation: Attestation, // Over the report's JSON
+}
+
+pub fn signed_routes() -> Router<AppState> {
+    Router::new().route("/admin/analytics/report", post(report))
+}
+
+async fn report(
+    State(state): State<AppState>,
+    Extension(signer): Extension<VerifiedSigner>,
+) -> Result<Json<ReportResponse>, AppError> {
+    require_admin(&state, &signer)?;
+    let report = state.analytics.report().map_err(AppError::internal)?;
+    let statement = serde_json::to_vec(&report).map_err(|e| AppError::internal(e.to_string()))?;
+    let attestation = state
+        .attestation
+        .generate_with_user_data("", "analytics_report", &Sha256::digest(statement))
+        .await?;
+
+    Ok(Json(ReportResponse {
+        report,
+        attestation,
+    }))
+}
@@ -0,0 +1,152 @@
+/**
+ * Guardian Approval Routes
+ * BLS guardian registration and aggregated approvals
+ */
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    routing::{get, post},
+    Extension, Router,
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::approvals::{
+    ApprovalGuardianSet, ApprovalRecord, ApprovalSignatures, BlsGuardianRegistration,
+};
+use crate::attestation::Attestation;
+use crate::auth::VerifiedSigner;
+use crate::error::AppError;
+use crate::validation::VaultId;
+use crate::AppState;
+
+#[derive(Deserialize)]
+struct RegisterGuardiansRequest {
+    threshold: u8,
+    guardians: Vec<BlsGuardianRegistration>,
+}
+
+#[derive(Deserialize)]
+struct ApproveRequest {
+    operation: String,
+    expires_at: u64,
+    #[serde(flatten)]
+    signatures: ApprovalSignatures,
+}
+
+#[derive(Serialize)]
+struct ApprovalResponse {
+    approval: ApprovalRecord,
+    attestation: Attestation,
+}
+
+/// Guardian registration is an owner operation
+pub fn signed_routes() -> Router<AppState> {
+    Router::new().route(
+        "/vault/:vault_id/approvals/guardians",
+        post(register_guardians),
+    )
+}
+
+/// Approvals authenticate through the BLS signatures they carry
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/vault/:vault_id/approvals", post(approve))
+        .route(
+            "/vault/:vault_id/approvals/:operation",
+            get(current_approval),
+        )
+}
+
+async fn register_guardians(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<RegisterGuardiansRequest>,
+) -> Result<Json<ApprovalGuardianSet>, AppError> {
+    super::require_registered_owner(
+        &state,
+        &vault_id,
+        &signer,
+        "registering its approval guardians",
+    )?;
+    super::require_mutable(&state, &vault_id, &signer, "approvals.guardians")?;
+    info!(
+        "BLS guardian registration: vault_id={}, guardians={}, threshold={}",
+        vault_id,
+        request.guardians.len(),
+        request.threshold
+    );
+
+    let owners = super::vault_owners(&state, &vault_id)?;
+    state
+        .approvals
+        .register_guardians(
+            &vault_id,
+            &signer.address,
+            &owners,
+            request.threshold,
+            &request.guardians,
+        )
+        .map(Json)
+        .map_err(|e| AppError::bad_request("GUARDIAN_REGISTRATION_FAILED", e))
+}
+
+async fn approve(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Json(request): Json<ApproveRequest>,
+) -> Result<Json<ApprovalResponse>, AppError> {
+    info!(
+        "Guardian approval: vault_id={}, operation={}",
+        vault_id, request.operation
+    );
+
+    let approval = state
+        .approvals
+        .approve(
+            &vault_id,
+            &request.operation,
+            request.expires_at,
+            request.signatures,
+        )
+        .map_err(|e| AppError::bad_request("APPROVAL_REJECTED", e))?;
+
+    attested(&state, approval).await
+}
+
+async fn current_approval(
+    State(state): State<AppState>,
+    Path((vault_id, operation)): Path<(VaultId, String)>,
+) -> Result<Json<ApprovalResponse>, AppError> {
+    let approval = state
+        .approvals
+        .current_approval(&vault_id, &operation)
+        .map_err(AppError::internal)?
+        .ok_or_else(|| {
+            AppError::new(
+                StatusCode::NOT_FOUND,
+                "APPROVAL_NOT_FOUND",
+                "No unexpired approval for this operation",
+            )
+        })?;
+
+    attested(&state, approval).await
+}
+
+async fn attested(
+    state: &AppState,
+    approval: ApprovalRecord,
+) -> Result<Json<ApprovalResponse>, AppError> {
+    let attestation = state
+        .attestation
+        .generate_with_user_data(&approval.vault_id, "guardian_approval", &approval.digest())
+        .await?;
+
+    Ok(Json(ApprovalResponse {
+        approval,
+        attestation,
+    }))
+}
@@ -0,0 +1,105 @@
+/**
+ * Archive Routes
+ * Manifests of what was archived to S3, fetching an object back against
+ * its manifest, and taking a snapshot on demand
+ */
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    routing::post,
+    Extension, Router,
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::archive::{ArchiveKind, ArchiveManifest, Retrieved};
+use crate::auth::VerifiedSigner;
+use crate::error::AppError;
+use crate::routes::require_admin;
+use crate::validation::VaultId;
+use crate::AppState;
+
+#[derive(Deserialize)]
+struct ListRequest {
+    kind: Option<ArchiveKind>,
+    vault_id: Option<VaultId>,
+}
+
+#[derive(Serialize)]
+struct ListResponse {
+    archives: Vec<ArchiveManifest>,
+}
+
+pub fn signed_routes() -> Router<AppState> {
+    Router::new()
+        .route("/admin/archives", post(list))
+        .route("/admin/archives/snapshot", post(snapshot))
+        .route("/admin/archives/:archive_id/retrieve", post(retrieve))
+}
+
+async fn list(
+    State(state): State<AppState>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<ListRequest>,
+) -> Result<Json<ListResponse>, AppError> {
+    require_admin(&state, &signer)?;
+    let archives = state
+        .archive
+        .manifests(request.kind, request.vault_id.as_deref())
+        .map_err(AppError::internal)?;
+    Ok(Json(ListResponse { archives }))
+}
+
+async fn snapshot(
+    State(state): State<AppState>,
+    Extension(signer): Extension<VerifiedSigner>,
+) -> Result<Json<ArchiveManifest>, AppError> {
+    require_admin(&state, &signer)?;
+    require_enabled(&state)?;
+    let manifest = state
+        .archive
+        .snapshot()
+        .await
+        .map_err(|e| AppError::new(StatusCode::BAD_GATEWAY, "ARCHIVE_FAILED", e))?;
+    info!(
+        "Snapshot {} archived on request of {}",
+        manifest.archive_id, signer.address
+    );
+    Ok(Json(manifest))
+}
+
+async fn retrieve(
+    State(state): State<AppState>,
+    Path(archive_id): Path<String>,
+    Extension(signer): Extension<VerifiedSigner>,
+) -> Result<Json<Retrieved>, AppError> {
+    require_admin(&state, &signer)?;
+    require_enabled(&state)?;
+    let retrieved = state
+        .archive
+        .retrieve(&archive_id)
+        .await
+        .map_err(|e| AppError::new(StatusCode::BAD_GATEWAY, "ARCHIVE_UNVERIFIED", e))?
+        .ok_or_else(|| {
+            AppError::new(
+                StatusCode::NOT_FOUND,
+                "ARCHIVE_NOT_FOUND",
+                format!("No archive {}", archive_id),
+            )
+        })?;
+    info!("Archive {} retrieved by {}", archive_id, signer.address);
+    Ok(Json(retrieved))
+}
+
+fn require_enabled(state: &AppState) -> Result<(), AppError> {
+    if !state.archive.enabled() {
+        return Err(AppError::new(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "ARCHIVE_DISABLED",
+            "Archival is not configured",
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,102 @@
+/**
+ * Audit Routes
+ * Paging through a vault's audit trail, and exporting it whole, attested
+ * at its head
+ */
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+    routing::post,
+    Extension, Router,
+};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::attestation::Attestation;
+use crate::audit::AuditEntry;
+use crate::auth::VerifiedSigner;
+use crate::error::AppError;
+use crate::keys::derive::AuditSigning;
+use crate::pagination::{self, Page, PageQuery};
+use crate::validation::VaultId;
+use crate::AppState;
+
+#[derive(Serialize)]
+struct AuditExport {
+    vault_id: String,
+    entries: Vec<AuditEntry>,
+    audit_public_key: String, // Base64 Ed25519 key the entries verify under
+    attestation: Attestation, // Over sha256 of the vault id and head hash
+}
+
+/// Open to admins, the vault's owners and their delegates; both are
+/// allowed under legal hold
+pub fn signed_routes() -> Router<AppState> {
+    Router::new()
+        .route("/vault/:vault_id/audit", post(entries))
+        .route("/vault/:vault_id/audit/export", post(export))
+}
+
+/// A page of entries; each verifies under the key in GET /vault/:vault_id/keys
+async fn entries(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Query(query): Query<PageQuery>,
+    Extension(signer): Extension<VerifiedSigner>,
+) -> Result<Json<Page<AuditEntry>>, AppError> {
+    require_audit_reader(&state, &vault_id, &signer)?;
+    let entries = state.audit.entries(&vault_id).map_err(AppError::internal)?;
+    pagination::paginate(entries, &query)
+        .map(Json)
+        .map_err(|e| AppError::bad_request("INVALID_PAGE", e))
+}
+
+/// The whole trail, since a relying party checks the chain from genesis
+async fn export(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Extension(signer): Extension<VerifiedSigner>,
+) -> Result<Json<AuditExport>, AppError> {
+    require_audit_reader(&state, &vault_id, &signer)?;
+
+    let entries = state.audit.entries(&vault_id).map_err(AppError::internal)?;
+    let head = entries.last().map_or("", |entry| entry.hash.as_str());
+    let mut hasher = Sha256::new();
+    hasher.update(vault_id.as_bytes());
+    hasher.update([0]);
+    hasher.update(head.as_bytes());
+    let attestation = state
+        .attestation
+        .generate_with_user_data(&vault_id, "audit_export", &hasher.finalize())
+        .await?;
+
+    let key = state.keys.derive::<AuditSigning>(&vault_id);
+    Ok(Json(AuditExport {
+        vault_id: vault_id.into(),
+        entries,
+        audit_public_key: STANDARD.encode(key.verifying_key().as_bytes()),
+        attestation,
+    }))
+}
+
+fn require_audit_reader(
+    state: &AppState,
+    vault_id: &str,
+    signer: &VerifiedSigner,
+) -> Result<(), AppError> {
+    let owners = super::vault_owners(state, vault_id)?;
+    if !owners.contains(&signer.address) {
+        super::require_admin(state, signer).map_err(|_| {
+            AppError::new(
+                StatusCode::FORBIDDEN,
+                "AUDIT_EXPORT_DENIED",
+                "Only admins and the vault owner may read its audit trail",
+            )
+        })?;
+    }
+    Ok(())
+}
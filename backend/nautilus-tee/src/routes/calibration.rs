@@ -0,0 +1,174 @@
+/**
+ * Calibration Routes
+ * Admin ingest of labeled sample sets and the resulting FAR/FRR reports
+ */
+
+use axum::{extract::State, http::StatusCode, response::Json, routing::post, Extension, Router};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::auth::VerifiedSigner;
+use crate::biometric::{EMBEDDING_MATCH_THRESHOLD, MATCH_THRESHOLD};
+use crate::calibration::{CalibrationReport, Matcher, DEFAULT_TARGET_FAR};
+use crate::embedding::{self, EmbeddingSubmission};
+use crate::error::AppError;
+use crate::routes::require_admin;
+use crate::AppState;
+
+/// Largest labeled set accepted in one request
+const MAX_PAIRS: usize = 10_000;
+
+#[derive(Deserialize)]
+struct CalibrateRequest {
+    method: String,
+    target_far: Option<f64>,
+    pairs: Vec<LabeledPair>,
+}
+
+#[derive(Deserialize)]
+struct LabeledPair {
+    /// Same subject (genuine) or different subjects (impostor)
+    genuine: bool,
+    reference: PairInput,
+    probe: PairInput,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PairInput {
+    Embedding(EmbeddingSubmission),
+    Sample(String), // Base64
+}
+
+#[derive(Deserialize)]
+struct ReportsRequest {
+    method: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ReportsResponse {
+    reports: Vec<CalibrationReport>,
+}
+
+pub fn signed_routes() -> Router<AppState> {
+    Router::new()
+        .route("/admin/calibration", post(calibrate))
+        .route("/admin/calibration/reports", post(reports))
+}
+
+async fn calibrate(
+    State(state): State<AppState>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<CalibrateRequest>,
+) -> Result<Json<CalibrationReport>, AppError> {
+    require_admin(&state, &signer)?;
+    if !state.calibration.ingest_enabled() {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "CALIBRATION_DISABLED",
+            "Calibration ingest is only available in development",
+        ));
+    }
+    if request.pairs.is_empty() || request.pairs.len() > MAX_PAIRS {
+        return Err(AppError::bad_request(
+            "INVALID_CALIBRATION_SET",
+            format!("Submit between 1 and {} labeled pairs", MAX_PAIRS),
+        ));
+    }
+
+    let matcher = match request.pairs[0].reference {
+        PairInput::Sample(_) => Matcher::Sample,
+        PairInput::Embedding(_) => Matcher::Embedding,
+    };
+    let scores = request
+        .pairs
+        .iter()
+        .enumerate()
+        .map(|(i, pair)| {
+            score(&state, &request.method, matcher, pair).map_err(|e| {
+                AppError::bad_request("INVALID_CALIBRATION_SET", format!("Pair {}: {}", i, e))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Samples for methods with an in-enclave model are matched as embeddings
+    let current_threshold = match matcher {
+        Matcher::Sample if state.biometric.feature_model(&request.method).is_none() => {
+            MATCH_THRESHOLD
+        }
+        Matcher::Sample | Matcher::Embedding => EMBEDDING_MATCH_THRESHOLD,
+    };
+    let report = state
+        .calibration
+        .calibrate(
+            &request.method,
+            matcher,
+            &scores,
+            request.target_far.unwrap_or(DEFAULT_TARGET_FAR),
+            current_threshold,
+            &signer.address,
+        )
+        .map_err(|e| AppError::bad_request("CALIBRATION_FAILED", e))?;
+    info!(
+        "Calibration by {}: method={}, pairs={}, recommended threshold={:.2}, EER={:.3}",
+        signer.address,
+        report.method,
+        scores.len(),
+        report.recommended_threshold,
+        report.equal_error_rate
+    );
+
+    Ok(Json(report))
+}
+
+async fn reports(
+    State(state): State<AppState>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<ReportsRequest>,
+) -> Result<Json<ReportsResponse>, AppError> {
+    require_admin(&state, &signer)?;
+
+    let reports = state
+        .calibration
+        .reports(request.method.as_deref())
+        .map_err(AppError::internal)?;
+    Ok(Json(ReportsResponse { reports }))
+}
+
+/// Score one pair with the matcher verification uses; every pair in a set
+/// must go through the same matcher
+fn score(
+    state: &AppState,
+    method: &str,
+    matcher: Matcher,
+    pair: &LabeledPair,
+) -> Result<(f64, bool), String> {
+    let score = match (matcher, &pair.reference, &pair.probe) {
+        (Matcher::Sample, PairInput::Sample(reference), PairInput::Sample(probe)) => {
+            let reference = STANDARD
+                .decode(reference)
+                .map_err(|_| "Reference must be base64".to_string())?;
+            let probe = STANDARD
+                .decode(probe)
+                .map_err(|_| "Probe must be base64".to_string())?;
+            let features = state
+                .biometric
+                .extract_features(&reference, method)
+                .map_err(|e| e.to_string())?;
+            state
+                .biometric
+                .compare(&features, &probe, method)
+                .map_err(|e| e.to_string())?
+        }
+        (Matcher::Embedding, PairInput::Embedding(reference), PairInput::Embedding(probe)) => {
+            let reference = reference.decode()?;
+            let probe = probe.decode()?;
+            reference.model.check_compatible(&probe.model)?;
+            embedding::similarity(&reference.vector, &probe.vector)
+        }
+        _ => return Err("Pairs must all be samples or all be embeddings".to_string()),
+    };
+    Ok((score, pair.genuine))
+}
@@ -0,0 +1,95 @@
+/**
+ * Capability Routes
+ * Minting and revoking delegated access to a vault
+ */
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    routing::post,
+    Extension, Router,
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::auth::VerifiedSigner;
+use crate::capability::{CapabilityClaims, CapabilityRecord};
+use crate::error::AppError;
+use crate::validation::{SuiAddress, VaultId};
+use crate::AppState;
+
+#[derive(Deserialize)]
+struct MintRequest {
+    holder: SuiAddress,
+    operations: Vec<String>,
+    ttl_secs: u64,
+}
+
+#[derive(Serialize)]
+struct MintResponse {
+    token: String,
+    claims: CapabilityClaims,
+}
+
+pub fn signed_routes() -> Router<AppState> {
+    Router::new()
+        .route("/vault/:vault_id/capabilities", post(mint))
+        .route(
+            "/vault/:vault_id/capabilities/:token_id/revoke",
+            post(revoke),
+        )
+}
+
+async fn mint(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<MintRequest>,
+) -> Result<Json<MintResponse>, AppError> {
+    let owners = super::vault_owners(&state, &vault_id)?;
+    let decision = state
+        .policy
+        .evaluate_capability_grant(&signer.address, &owners);
+    if !decision.allowed {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "NOT_VAULT_OWNER",
+            decision.reason,
+        ));
+    }
+
+    let (token, claims) = state
+        .capabilities
+        .mint(
+            &vault_id,
+            &signer.address,
+            &request.holder,
+            &request.operations,
+            request.ttl_secs,
+        )
+        .map_err(|e| AppError::bad_request("CAPABILITY_REJECTED", e))?;
+    info!(
+        "Capability {} minted: vault_id={}, holder={}, operations={:?}, expires_at={}",
+        claims.token_id, vault_id, claims.holder, claims.operations, claims.expires_at
+    );
+
+    Ok(Json(MintResponse { token, claims }))
+}
+
+async fn revoke(
+    State(state): State<AppState>,
+    Path((vault_id, token_id)): Path<(VaultId, String)>,
+    Extension(signer): Extension<VerifiedSigner>,
+) -> Result<Json<CapabilityRecord>, AppError> {
+    info!(
+        "Capability {} revoked: vault_id={}, by={}",
+        token_id, vault_id, signer.address
+    );
+
+    state
+        .capabilities
+        .revoke(&vault_id, &token_id, &signer.address)
+        .map(Json)
+        .map_err(|e| AppError::new(StatusCode::FORBIDDEN, "REVOCATION_REJECTED", e))
+}
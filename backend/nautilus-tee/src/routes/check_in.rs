@@ -0,0 +1,224 @@
+/**
+ * Check-In Routes
+ * Owner check-ins, their reminder schedule, the on-chain objects whose
+ * heartbeats count as check-ins, and a live feed of check-ins and reminders
+ */
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        Json,
+    },
+    routing::{get, post},
+    Extension, Router,
+};
+use futures_util::stream::{self, Stream};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::info;
+
+use crate::auth::VerifiedSigner;
+use crate::check_in::{CheckIn, CheckInEvent, CheckInSchedule};
+use crate::duress::DuressTrigger;
+use crate::error::AppError;
+use crate::heartbeat::ObjectBinding;
+use crate::validation::VaultId;
+use crate::AppState;
+
+#[derive(Deserialize)]
+struct ScheduleRequest {
+    interval_days: u32,
+    #[serde(default)]
+    reminder_days: Vec<u32>,
+}
+
+#[derive(Deserialize)]
+struct CheckInRequest {
+    /// Compared against the owner's duress code, if they set one
+    #[serde(default)]
+    code: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ObjectBindingRequest {
+    object_id: String,
+}
+
+#[derive(Serialize)]
+struct ScheduleStatus {
+    #[serde(flatten)]
+    schedule: CheckInSchedule,
+    expires_at: u64,
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/vault/:vault_id/check-in", get(schedule))
+        .route("/vault/:vault_id/check-in/events", get(events))
+}
+
+pub fn signed_routes() -> Router<AppState> {
+    Router::new()
+        .route("/vault/:vault_id/check-in", post(check_in))
+        .route("/vault/:vault_id/check-in/schedule", post(set_schedule))
+        .route(
+            "/vault/:vault_id/check-in/heartbeat-object",
+            post(bind_heartbeat_object),
+        )
+}
+
+async fn set_schedule(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<ScheduleRequest>,
+) -> Result<Json<ScheduleStatus>, AppError> {
+    super::require_mutable(&state, &vault_id, &signer, "check_in.schedule")?;
+    require_check_in_owner(&state, &vault_id, &signer)?;
+
+    let schedule = state
+        .check_ins
+        .set_schedule(
+            &vault_id,
+            &signer.address,
+            request.interval_days,
+            request.reminder_days,
+        )
+        .map_err(|e| AppError::bad_request("CHECK_IN_SCHEDULE_REJECTED", e))?;
+    info!(
+        "Check-in schedule for {}: every {} days, reminders {:?}",
+        vault_id, schedule.interval_days, schedule.reminder_days
+    );
+    Ok(Json(ScheduleStatus {
+        expires_at: schedule.expires_at(),
+        schedule,
+    }))
+}
+
+async fn bind_heartbeat_object(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<ObjectBindingRequest>,
+) -> Result<Json<ObjectBinding>, AppError> {
+    super::require_mutable(&state, &vault_id, &signer, "check_in.heartbeat_object")?;
+    require_check_in_owner(&state, &vault_id, &signer)?;
+
+    let binding = state
+        .heartbeats
+        .bind_object(&vault_id, &signer.address, &request.object_id)
+        .map_err(|e| AppError::bad_request("HEARTBEAT_OBJECT_REJECTED", e))?;
+    info!(
+        "Heartbeats from object {} now count for {}",
+        binding.object_id, vault_id
+    );
+    state
+        .audit
+        .record(
+            &vault_id,
+            "check_in.heartbeat_object",
+            &signer.address,
+            serde_json::json!({ "object_id": binding.object_id }),
+        )
+        .map_err(AppError::internal)?;
+    Ok(Json(binding))
+}
+
+async fn check_in(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Extension(signer): Extension<VerifiedSigner>,
+    request: Option<Json<CheckInRequest>>,
+) -> Result<Json<CheckIn>, AppError> {
+    super::require_mutable(&state, &vault_id, &signer, "check_in")?;
+    require_check_in_owner(&state, &vault_id, &signer)?;
+
+    let check_in = state
+        .check_ins
+        .check_in(&vault_id, &signer.address)
+        .map_err(|e| AppError::new(StatusCode::NOT_FOUND, "NO_CHECK_IN_SCHEDULE", e))?;
+    info!(
+        "Check-in for {} by {}; {} reminders snoozed",
+        vault_id,
+        signer.address,
+        check_in.snoozed.len()
+    );
+
+    if let Some(code) = request.as_ref().and_then(|r| r.code.as_deref()) {
+        state
+            .duress
+            .check(&vault_id, DuressTrigger::Code(code))
+            .map_err(AppError::internal)?;
+    }
+    Ok(Json(check_in))
+}
+
+async fn schedule(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+) -> Result<Json<ScheduleStatus>, AppError> {
+    let schedule = state
+        .check_ins
+        .schedule(&vault_id)
+        .map_err(AppError::internal)?
+        .ok_or_else(|| {
+            AppError::new(
+                StatusCode::NOT_FOUND,
+                "NO_CHECK_IN_SCHEDULE",
+                "Vault has no check-in schedule",
+            )
+        })?;
+    Ok(Json(ScheduleStatus {
+        expires_at: schedule.expires_at(),
+        schedule,
+    }))
+}
+
+/// A `reminder` or `checked_in` event as each happens for the vault
+async fn events(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    let feed = state.bus.subscribe();
+    let stream = stream::unfold((feed, vault_id), |(mut feed, vault_id)| async move {
+        loop {
+            match feed.recv().await {
+                Ok(envelope) => {
+                    let Some(event) = CheckInEvent::from_bus(&envelope.event) else {
+                        continue;
+                    };
+                    if event.vault_id() != &*vault_id {
+                        continue;
+                    }
+                    let sse = Event::default().event(event.name()).json_data(&event);
+                    return Some((sse, (feed, vault_id)));
+                }
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+fn require_check_in_owner(
+    state: &AppState,
+    vault_id: &str,
+    signer: &VerifiedSigner,
+) -> Result<(), AppError> {
+    let owners = super::vault_owners(state, vault_id)?;
+    let decision =
+        state
+            .policy
+            .evaluate_check_in(&signer.address, &owners, signer.delegate.is_some());
+    if !decision.allowed {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "CHECK_IN_DENIED",
+            decision.reason,
+        ));
+    }
+    Ok(())
+}
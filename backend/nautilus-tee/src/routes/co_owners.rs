@@ -0,0 +1,167 @@
+/**
+ * Co-owner Routes
+ * Setting a vault's co-owners and approving changes that need their quorum
+ */
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    routing::post,
+    Extension, Router,
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::auth::VerifiedSigner;
+use crate::co_owners::{CoOwnerSet, OwnerApproval};
+use crate::error::AppError;
+use crate::validation::{SuiAddress, VaultId};
+use crate::AppState;
+
+#[derive(Deserialize)]
+struct CoOwnersRequest {
+    owners: Vec<SuiAddress>,
+    threshold: usize,
+}
+
+#[derive(Deserialize)]
+struct ApprovalRequest {
+    action: String,
+    /// Hex SHA-256 of the body the submitting owner will send
+    request_sha256: String,
+}
+
+#[derive(Serialize)]
+struct ApprovalResponse {
+    approval: OwnerApproval,
+    /// Owners who have approved the request so far; the owner who submits
+    /// it counts on top
+    approvers: Vec<String>,
+    threshold: usize,
+}
+
+pub fn signed_routes() -> Router<AppState> {
+    Router::new()
+        .route("/vault/:vault_id/co-owners", post(set_co_owners))
+        .route("/vault/:vault_id/owner-approvals", post(approve))
+}
+
+async fn set_co_owners(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<CoOwnersRequest>,
+) -> Result<Json<CoOwnerSet>, AppError> {
+    require_owner(&state, &vault_id, &signer)?;
+    super::require_mutable(&state, &vault_id, &signer, "co_owners.set")?;
+
+    let owners: Vec<String> = request.owners.into_iter().map(String::from).collect();
+    let set = state
+        .co_owners
+        .set_owners(&vault_id, &signer.address, &owners, request.threshold)
+        .map_err(|e| AppError::bad_request("CO_OWNERS_REJECTED", e))?;
+    info!(
+        "Co-owners of {} set by {}: {} of {}",
+        vault_id,
+        signer.address,
+        set.threshold,
+        set.owners.len()
+    );
+
+    state
+        .audit
+        .record(
+            &vault_id,
+            "co_owners.updated",
+            &signer.address,
+            serde_json::json!({ "owners": set.owners, "threshold": set.threshold }),
+        )
+        .map_err(AppError::internal)?;
+    Ok(Json(set))
+}
+
+async fn approve(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<ApprovalRequest>,
+) -> Result<Json<ApprovalResponse>, AppError> {
+    require_owner(&state, &vault_id, &signer)?;
+    let set = state
+        .co_owners
+        .co_owners(&vault_id)
+        .map_err(AppError::internal)?
+        .filter(|set| set.owners.contains(&signer.address))
+        .ok_or_else(|| {
+            AppError::new(
+                StatusCode::FORBIDDEN,
+                "NOT_CO_OWNER",
+                "Only a co-owner of a co-owned vault may approve changes",
+            )
+        })?;
+
+    let approval = state
+        .co_owners
+        .approve(
+            &vault_id,
+            &signer.address,
+            &request.action,
+            &request.request_sha256,
+        )
+        .map_err(|e| AppError::bad_request("APPROVAL_REJECTED", e))?;
+    let approvers = state
+        .co_owners
+        .approvers(
+            &vault_id,
+            &approval.action,
+            &approval.request_sha256,
+            &set.owners,
+        )
+        .map_err(AppError::internal)?;
+    info!(
+        "Owner approval for {} on {}: {} of {} ({})",
+        approval.action,
+        vault_id,
+        approvers.len(),
+        set.threshold,
+        signer.address
+    );
+
+    state
+        .audit
+        .record(
+            &vault_id,
+            "co_owners.approved",
+            &signer.address,
+            serde_json::json!({
+                "action": approval.action,
+                "request_sha256": approval.request_sha256,
+            }),
+        )
+        .map_err(AppError::internal)?;
+    Ok(Json(ApprovalResponse {
+        approval,
+        approvers,
+        threshold: set.threshold,
+    }))
+}
+
+fn require_owner(
+    state: &AppState,
+    vault_id: &str,
+    signer: &VerifiedSigner,
+) -> Result<(), AppError> {
+    let owners = super::vault_owners(state, vault_id)?;
+    let decision = state
+        .policy
+        .evaluate_co_owner_change(&signer.address, &owners);
+    if !decision.allowed {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "NOT_VAULT_OWNER",
+            decision.reason,
+        ));
+    }
+    Ok(())
+}
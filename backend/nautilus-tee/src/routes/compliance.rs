@@ -0,0 +1,49 @@
+/**
+ * Compliance Routes
+ * Signed, attested evidence reports for admins to hand to auditors
+ */
+
+use axum::{extract::State, response::Json, routing::post, Extension, Router};
+use serde::Serialize;
+
+use crate::attestation::Attestation;
+use crate::auth::VerifiedSigner;
+use crate::compliance::{self, SignedReport};
+use crate::error::AppError;
+use crate::routes::require_admin;
+use crate::AppState;
+
+#[derive(Serialize)]
+struct ReportResponse {
+    #[serde(flatten)]
+    signed: SignedReport,
+    /// user_data is the report's SHA-256
+    attestation: Attestation,
+}
+
+pub fn signed_routes() -> Router<AppState> {
+    Router::new().route("/admin/compliance/report", post(report))
+}
+
+async fn report(
+    State(state): State<AppState>,
+    Extension(signer): Extension<VerifiedSigner>,
+) -> Result<Json<ReportResponse>, AppError> {
+    require_admin(&state, &signer)?;
+    let measurements = state.attestation.get_pcr_measurements()?;
+    let signed = state
+        .compliance
+        .report(measurements, state.zk_proof.circuits().all())
+        .map_err(AppError::internal)?;
+    let digest =
+        hex::decode(&signed.report_sha256).map_err(|e| AppError::internal(e.to_string()))?;
+    let attestation = state
+        .attestation
+        .generate_with_user_data("", compliance::OPERATION, &digest)
+        .await?;
+
+    Ok(Json(ReportResponse {
+        signed,
+        attestation,
+    }))
+}
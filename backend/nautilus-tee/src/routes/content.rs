@@ -0,0 +1,258 @@
+/**
+ * Vault Content Routes
+ * Attested answers about a vault payload that never return the payload
+ *
+ *   search:   whether keywords and metadata match
+ *   manifest: the files it holds, their types, sizes and hashes
+ *
+ * Each attestation commits to SHA-256 of a JSON statement naming the
+ * vault, the requester, the blob and the answer, so an answer can't be
+ * lifted onto another blob or request. The blob digest is of the payload
+ * as submitted.
+ *
+ * The enclave doesn't decrypt the payload, so on its own an answer would
+ * only describe whatever bytes the caller sent. A payload is therefore
+ * only answered for when its leaf is among the vault's committed blobs
+ * (see integrity), and the statement names the committed root, which
+ * verifiers can check against the vault's on-chain object.
+ *
+ * Vaults with a registered owner answer only the owner, or a capability
+ * holder acting for them, who the statement names as "delegate".
+ */
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    routing::post,
+    Extension, Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tracing::info;
+use zeroize::Zeroizing;
+
+use crate::attestation::Attestation;
+use crate::auth::VerifiedSigner;
+use crate::crypto;
+use crate::error::AppError;
+use crate::integrity;
+use crate::manifest::{self, Manifest, ManifestOptions};
+use crate::memory_budget::{self, Reservation};
+use crate::search::{self, SearchQuery, SearchResults};
+use crate::validation::VaultId;
+use crate::{over_budget, AppState};
+
+#[derive(Deserialize)]
+struct SearchRequest {
+    encrypted_data: String, // Base64 encoded encrypted blob
+    query: SearchQuery,
+}
+
+#[derive(Serialize)]
+struct SearchResponse {
+    vault_id: String,
+    blob_sha256: String,
+    root: String, // Committed Merkle root the blob belongs to
+    results: SearchResults,
+    attestation: Attestation,
+}
+
+#[derive(Deserialize)]
+struct ManifestRequest {
+    encrypted_data: String, // Base64 encoded encrypted blob
+    #[serde(default)]
+    options: ManifestOptions,
+}
+
+#[derive(Serialize)]
+struct ManifestResponse {
+    vault_id: String,
+    blob_sha256: String,
+    root: String, // Committed Merkle root the blob belongs to
+    manifest: Manifest,
+    attestation: Attestation,
+}
+
+/// Routes that must go through signature verification
+pub fn signed_routes() -> Router<AppState> {
+    Router::new()
+        .route("/vault/:vault_id/search", post(search_vault))
+        .route("/vault/:vault_id/manifest", post(vault_manifest))
+}
+
+/// Statement: {"vault_id", "requester", "delegate", "blob_sha256", "root",
+/// "query", "results"}
+async fn search_vault(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(mut request): Json<SearchRequest>,
+) -> Result<Json<SearchResponse>, AppError> {
+    info!(
+        "Vault search: vault_id={}, requester={}, terms={}",
+        vault_id,
+        signer.address,
+        request.query.keywords.len() + request.query.metadata.len()
+    );
+
+    require_access(&state, &vault_id, &signer)?;
+    let _memory = reserve_payload(&state, &request.encrypted_data).await?;
+    let (payload, blob_sha256, root) = open_payload(
+        &state,
+        &vault_id,
+        std::mem::take(&mut request.encrypted_data),
+    )?;
+    let results = search::search(&payload, &request.query)
+        .map_err(|e| AppError::bad_request("INVALID_SEARCH", e))?;
+
+    let attestation = attest(
+        &state,
+        &vault_id,
+        "vault_search",
+        serde_json::json!({
+            "vault_id": vault_id,
+            "requester": signer.address,
+            "delegate": signer.delegate,
+            "blob_sha256": blob_sha256,
+            "root": root,
+            "query": request.query,
+            "results": results,
+        }),
+    )
+    .await?;
+
+    Ok(Json(SearchResponse {
+        vault_id: vault_id.into(),
+        blob_sha256,
+        root,
+        results,
+        attestation,
+    }))
+}
+
+/// Statement: {"vault_id", "requester", "delegate", "blob_sha256", "root",
+/// "options", "manifest"}
+async fn vault_manifest(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(mut request): Json<ManifestRequest>,
+) -> Result<Json<ManifestResponse>, AppError> {
+    info!(
+        "Vault manifest: vault_id={}, requester={}",
+        vault_id, signer.address
+    );
+
+    require_access(&state, &vault_id, &signer)?;
+    let _memory = reserve_payload(&state, &request.encrypted_data).await?;
+    let (payload, blob_sha256, root) = open_payload(
+        &state,
+        &vault_id,
+        std::mem::take(&mut request.encrypted_data),
+    )?;
+    let manifest = manifest::build(&payload, &request.options)
+        .map_err(|e| AppError::bad_request("INVALID_PAYLOAD", e))?;
+
+    let attestation = attest(
+        &state,
+        &vault_id,
+        "vault_manifest",
+        serde_json::json!({
+            "vault_id": vault_id,
+            "requester": signer.address,
+            "delegate": signer.delegate,
+            "blob_sha256": blob_sha256,
+            "root": root,
+            "options": request.options,
+            "manifest": manifest,
+        }),
+    )
+    .await?;
+
+    Ok(Json(ManifestResponse {
+        vault_id: vault_id.into(),
+        blob_sha256,
+        root,
+        manifest,
+        attestation,
+    }))
+}
+
+fn require_access(
+    state: &AppState,
+    vault_id: &str,
+    signer: &VerifiedSigner,
+) -> Result<(), AppError> {
+    let owners = super::vault_owners(state, vault_id)?;
+    let decision = state
+        .policy
+        .evaluate_content_access(&signer.address, &owners);
+    if !decision.allowed {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "VAULT_ACCESS_DENIED",
+            decision.reason,
+        ));
+    }
+    Ok(())
+}
+
+/// Held while the payload is, its decoded copy included
+async fn reserve_payload(state: &AppState, encrypted_data: &str) -> Result<Reservation, AppError> {
+    state
+        .memory
+        .reserve(&[(memory_budget::PAYLOAD, encrypted_data.len())])
+        .await
+        .map_err(over_budget)
+}
+
+/// The payload, the hex digest of the blob as submitted and the root it
+/// is committed under, decoded in place so the blob is held once
+fn open_payload(
+    state: &AppState,
+    vault_id: &str,
+    encrypted_data: String,
+) -> Result<(Zeroizing<Vec<u8>>, String, String), AppError> {
+    let invalid =
+        || AppError::bad_request("INVALID_ENCRYPTED_DATA", "encrypted_data must be base64");
+    let leaf = hex::encode(integrity::leaf(&encrypted_data).map_err(|_| invalid())?);
+    let commitment = state
+        .integrity
+        .commitment(vault_id)
+        .map_err(AppError::internal)?
+        .ok_or_else(|| {
+            AppError::new(
+                StatusCode::CONFLICT,
+                "VAULT_NOT_COMMITTED",
+                "Vault has no integrity commitment to check the blob against",
+            )
+        })?;
+    if !commitment.leaves.contains(&leaf) {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "BLOB_NOT_COMMITTED",
+            "Blob is not one of the vault's committed blobs",
+        ));
+    }
+
+    let blob = crypto::decode_in_place(encrypted_data).map_err(|_| invalid())?;
+    let blob_sha256 = hex::encode(Sha256::digest(&blob));
+    Ok((Zeroizing::new(blob), blob_sha256, commitment.root))
+}
+
+async fn attest(
+    state: &AppState,
+    vault_id: &str,
+    operation: &str,
+    statement: Value,
+) -> Result<Attestation, AppError> {
+    let statement =
+        serde_json::to_vec(&statement).map_err(|e| AppError::internal(e.to_string()))?;
+    state
+        .attestation
+        .generate_with_user_data(vault_id, operation, &Sha256::digest(statement))
+        .await
+        .map_err(AppError::from)
+}
@@ -0,0 +1,92 @@
+/**
+ * Data Export Routes
+ * Data-subject exports for vault owners, with an attested manifest of
+ * what each one included
+ */
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    routing::post,
+    Extension, Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::info;
+
+use crate::attestation::Attestation;
+use crate::auth::VerifiedSigner;
+use crate::crypto::Envelope;
+use crate::data_export::{ExportManifest, OPERATION};
+use crate::error::AppError;
+use crate::validation::VaultId;
+use crate::AppState;
+
+#[derive(Deserialize)]
+struct ExportRequest {
+    recipient_public_key: String, // Base64 X25519 key to seal the archive to
+}
+
+#[derive(Serialize)]
+struct ExportResponse {
+    manifest: ExportManifest,
+    archive: Envelope,
+    attestation: Attestation, // Over the manifest's digest
+}
+
+pub fn signed_routes() -> Router<AppState> {
+    Router::new().route("/vault/:vault_id/data-export", post(export))
+}
+
+async fn export(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<ExportRequest>,
+) -> Result<Json<ExportResponse>, AppError> {
+    let owners = super::vault_owners(&state, &vault_id)?;
+    let decision =
+        state
+            .policy
+            .evaluate_data_export(&signer.address, &owners, signer.delegate.is_some());
+    if !decision.allowed {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "DATA_EXPORT_DENIED",
+            decision.reason,
+        ));
+    }
+
+    let export = state
+        .data_exports
+        .export(&vault_id, &signer.address, &request.recipient_public_key)
+        .map_err(|e| AppError::bad_request("DATA_EXPORT_FAILED", e))?;
+    let digest = export.manifest.digest().map_err(AppError::internal)?;
+    state
+        .audit
+        .record(
+            &vault_id,
+            "data_export.created",
+            &signer.address,
+            json!({
+                "export_id": export.manifest.export_id,
+                "archive_sha256": export.manifest.archive_sha256,
+            }),
+        )
+        .map_err(AppError::internal)?;
+    info!(
+        "Data export {} of vault {} for {}",
+        export.manifest.export_id, vault_id, signer.address
+    );
+
+    let attestation = state
+        .attestation
+        .generate_with_user_data(&vault_id, OPERATION, &digest)
+        .await?;
+    Ok(Json(ExportResponse {
+        manifest: export.manifest,
+        archive: export.archive,
+        attestation,
+    }))
+}
@@ -0,0 +1,176 @@
+/**
+ * Dead Letter Routes
+ * Failed intents, notifications and webhook deliveries, for admins to
+ * inspect and then retry or discard
+ */
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    routing::post,
+    Extension, Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::info;
+
+use crate::auth::VerifiedSigner;
+use crate::dead_letters::{DeadLetter, DeadLetterSource, REDELIVERY_EVENT};
+use crate::error::AppError;
+use crate::events::Event;
+use crate::routes::require_admin;
+use crate::AppState;
+
+#[derive(Deserialize)]
+struct ListRequest {
+    source: Option<DeadLetterSource>,
+}
+
+#[derive(Serialize)]
+struct ListResponse {
+    letters: Vec<DeadLetter>,
+    /// Across all sources
+    pending: usize,
+    alerting: bool,
+}
+
+pub fn signed_routes() -> Router<AppState> {
+    Router::new()
+        .route("/admin/dead-letters", post(list))
+        .route("/admin/dead-letters/:letter_id", post(inspect))
+        .route("/admin/dead-letters/:letter_id/retry", post(retry))
+        .route("/admin/dead-letters/:letter_id/discard", post(discard))
+}
+
+async fn list(
+    State(state): State<AppState>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<ListRequest>,
+) -> Result<Json<ListResponse>, AppError> {
+    require_admin(&state, &signer)?;
+    let letters = state
+        .dead_letters
+        .list(request.source)
+        .map_err(AppError::internal)?;
+    let health = state.dead_letters.health().map_err(AppError::internal)?;
+    Ok(Json(ListResponse {
+        letters,
+        pending: health.pending,
+        alerting: health.alerting,
+    }))
+}
+
+async fn inspect(
+    State(state): State<AppState>,
+    Path(letter_id): Path<String>,
+    Extension(signer): Extension<VerifiedSigner>,
+) -> Result<Json<DeadLetter>, AppError> {
+    require_admin(&state, &signer)?;
+    letter(&state, &letter_id).map(Json)
+}
+
+/// Queue the letter's snapshot again and remove the letter
+async fn retry(
+    State(state): State<AppState>,
+    Path(letter_id): Path<String>,
+    Extension(signer): Extension<VerifiedSigner>,
+) -> Result<Json<DeadLetter>, AppError> {
+    require_admin(&state, &signer)?;
+    let letter = letter(&state, &letter_id)?;
+    let malformed = |e: serde_json::Error| {
+        AppError::internal(format!("Dead letter {} is malformed: {}", letter_id, e))
+    };
+    match letter.source {
+        DeadLetterSource::ChainIntent => {
+            let record = serde_json::from_value(letter.payload.clone()).map_err(malformed)?;
+            state
+                .outbox
+                .requeue(record)
+                .map_err(|e| AppError::bad_request("RETRY_FAILED", e))?;
+        }
+        DeadLetterSource::Notification => {
+            let record = serde_json::from_value(letter.payload.clone()).map_err(malformed)?;
+            state
+                .notifications
+                .requeue(record)
+                .map_err(AppError::internal)?;
+        }
+        DeadLetterSource::Webhook => {
+            let event: Event = serde_json::from_value(letter.payload.clone()).map_err(malformed)?;
+            let endpoint = letter
+                .reference
+                .split_once('@')
+                .map(|(_, endpoint)| endpoint)
+                .unwrap_or_default();
+            // A failed redelivery is retried as the original event
+            let original = match event.kind.as_str() {
+                REDELIVERY_EVENT => event.data["event"].clone(),
+                _ => json!(event),
+            };
+            state
+                .events
+                .emit(
+                    REDELIVERY_EVENT,
+                    &letter.vault_id,
+                    json!({ "event": original, "endpoint": endpoint }),
+                )
+                .map_err(AppError::internal)?;
+        }
+    }
+    settle(&state, &letter, "dead_letter.retried", &signer)?;
+    Ok(Json(letter))
+}
+
+async fn discard(
+    State(state): State<AppState>,
+    Path(letter_id): Path<String>,
+    Extension(signer): Extension<VerifiedSigner>,
+) -> Result<Json<DeadLetter>, AppError> {
+    require_admin(&state, &signer)?;
+    let letter = letter(&state, &letter_id)?;
+    settle(&state, &letter, "dead_letter.discarded", &signer)?;
+    Ok(Json(letter))
+}
+
+fn letter(state: &AppState, letter_id: &str) -> Result<DeadLetter, AppError> {
+    state
+        .dead_letters
+        .get(letter_id)
+        .map_err(AppError::internal)?
+        .ok_or_else(|| {
+            AppError::new(
+                StatusCode::NOT_FOUND,
+                "DEAD_LETTER_NOT_FOUND",
+                format!("No dead letter {}", letter_id),
+            )
+        })
+}
+
+/// Remove the letter and record what the admin did with it
+fn settle(
+    state: &AppState,
+    letter: &DeadLetter,
+    action: &str,
+    signer: &VerifiedSigner,
+) -> Result<(), AppError> {
+    state
+        .dead_letters
+        .remove(&letter.letter_id)
+        .map_err(AppError::internal)?;
+    state
+        .audit
+        .record(
+            &letter.vault_id,
+            action,
+            &signer.address,
+            json!({
+                "letter_id": letter.letter_id,
+                "source": letter.source,
+                "reference": letter.reference,
+            }),
+        )
+        .map_err(AppError::internal)?;
+    info!("{} {} by {}", action, letter.letter_id, signer.address);
+    Ok(())
+}
@@ -0,0 +1,95 @@
+/**
+ * Vault Deletion Routes
+ * Owner-initiated, attested destruction of a vault
+ */
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    routing::{delete, get},
+    Extension, Router,
+};
+use serde::Serialize;
+use tracing::info;
+
+use crate::attestation::Attestation;
+use crate::auth::VerifiedSigner;
+use crate::deletion::{VaultDeletion, OPERATION};
+use crate::error::AppError;
+use crate::validation::VaultId;
+use crate::AppState;
+
+#[derive(Serialize)]
+struct DeletionResponse {
+    deletion: VaultDeletion,
+    attestation: Attestation, // Over the deletion record's digest
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/vault/:vault_id/deletion", get(deletion))
+}
+
+pub fn signed_routes() -> Router<AppState> {
+    Router::new().route("/vault/:vault_id", delete(delete_vault))
+}
+
+async fn delete_vault(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Extension(signer): Extension<VerifiedSigner>,
+) -> Result<Json<DeletionResponse>, AppError> {
+    super::require_mutable(&state, &vault_id, &signer, "vault_delete")?;
+    let owners = super::vault_owners(&state, &vault_id)?;
+    let decision =
+        state
+            .policy
+            .evaluate_vault_deletion(&signer.address, &owners, signer.delegate.is_some());
+    if !decision.allowed {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "VAULT_DELETE_DENIED",
+            decision.reason,
+        ));
+    }
+
+    let deletion = state
+        .deletions
+        .delete(&vault_id, &signer.address)
+        .map_err(|e| AppError::new(StatusCode::CONFLICT, "VAULT_DELETE_FAILED", e))?;
+    info!(
+        "Vault {} deleted by {}: {} artifacts destroyed",
+        vault_id,
+        signer.address,
+        deletion.artifacts.len()
+    );
+
+    let digest = deletion.digest().map_err(AppError::internal)?;
+    let attestation = state
+        .attestation
+        .generate_with_user_data(&vault_id, OPERATION, &digest)
+        .await?;
+
+    Ok(Json(DeletionResponse {
+        deletion,
+        attestation,
+    }))
+}
+
+async fn deletion(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+) -> Result<Json<VaultDeletion>, AppError> {
+    state
+        .deletions
+        .deletion(&vault_id)
+        .map_err(AppError::internal)?
+        .map(Json)
+        .ok_or_else(|| {
+            AppError::new(
+                StatusCode::NOT_FOUND,
+                "DELETION_NOT_FOUND",
+                "Vault has not been deleted",
+            )
+        })
+}
@@ -0,0 +1,232 @@
+/**
+ * Heartbeat Device Routes
+ * Enrolling, rotating and revoking device keys, and the heartbeats they sign
+ */
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    routing::post,
+    Extension, Router,
+};
+use serde::Deserialize;
+use serde_json::json;
+use tracing::info;
+
+use crate::auth::VerifiedSigner;
+use crate::devices::{Device, DeviceSet};
+use crate::duress::DuressTrigger;
+use crate::error::AppError;
+use crate::validation::VaultId;
+use crate::AppState;
+
+#[derive(Deserialize)]
+struct EnrollRequest {
+    name: String,
+    public_key: String,
+    proof: String,
+}
+
+#[derive(Deserialize)]
+struct RotateRequest {
+    public_key: String,
+    proof: String,
+}
+
+#[derive(Deserialize)]
+struct RequirementRequest {
+    min_devices: u32,
+    #[serde(default)]
+    window_hours: u32,
+}
+
+#[derive(Deserialize)]
+struct HeartbeatRequest {
+    timestamp: u64,
+    signature: String,
+}
+
+/// Heartbeats are signed by the device key rather than the owner's wallet
+pub fn routes() -> Router<AppState> {
+    Router::new().route(
+        "/vault/:vault_id/devices/:device_id/heartbeat",
+        post(heartbeat),
+    )
+}
+
+pub fn signed_routes() -> Router<AppState> {
+    Router::new()
+        .route("/vault/:vault_id/devices", post(devices))
+        .route("/vault/:vault_id/devices/enroll", post(enroll))
+        .route(
+            "/vault/:vault_id/devices/requirement",
+            post(set_requirement),
+        )
+        .route("/vault/:vault_id/devices/:device_id/rotate", post(rotate))
+        .route("/vault/:vault_id/devices/:device_id/revoke", post(revoke))
+}
+
+async fn devices(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Extension(signer): Extension<VerifiedSigner>,
+) -> Result<Json<DeviceSet>, AppError> {
+    require_device_owner(&state, &vault_id, &signer)?;
+    state
+        .devices
+        .devices(&vault_id)
+        .map_err(AppError::internal)?
+        .map(Json)
+        .ok_or_else(|| {
+            AppError::new(
+                StatusCode::NOT_FOUND,
+                "NO_DEVICES",
+                "Vault has no heartbeat devices",
+            )
+        })
+}
+
+async fn enroll(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<EnrollRequest>,
+) -> Result<Json<Device>, AppError> {
+    super::require_mutable(&state, &vault_id, &signer, "device.enroll")?;
+    require_device_owner(&state, &vault_id, &signer)?;
+
+    let device = state
+        .devices
+        .enroll(
+            &vault_id,
+            &signer.address,
+            &request.name,
+            &request.public_key,
+            &request.proof,
+        )
+        .map_err(|e| AppError::bad_request("DEVICE_REJECTED", e))?;
+    info!("Device {} enrolled for {}", device.device_id, vault_id);
+    record(&state, &vault_id, "device.enrolled", &signer, &device)?;
+    Ok(Json(device))
+}
+
+async fn rotate(
+    State(state): State<AppState>,
+    Path((vault_id, device_id)): Path<(VaultId, String)>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<RotateRequest>,
+) -> Result<Json<Device>, AppError> {
+    super::require_mutable(&state, &vault_id, &signer, "device.rotate")?;
+    require_device_owner(&state, &vault_id, &signer)?;
+
+    let device = state
+        .devices
+        .rotate(&vault_id, &device_id, &request.public_key, &request.proof)
+        .map_err(|e| AppError::bad_request("DEVICE_REJECTED", e))?;
+    info!("Device {} of {} rotated its key", device_id, vault_id);
+    record(&state, &vault_id, "device.rotated", &signer, &device)?;
+    Ok(Json(device))
+}
+
+async fn revoke(
+    State(state): State<AppState>,
+    Path((vault_id, device_id)): Path<(VaultId, String)>,
+    Extension(signer): Extension<VerifiedSigner>,
+) -> Result<Json<Device>, AppError> {
+    super::require_mutable(&state, &vault_id, &signer, "device.revoke")?;
+    require_device_owner(&state, &vault_id, &signer)?;
+
+    let device = state
+        .devices
+        .revoke(&vault_id, &device_id)
+        .map_err(|e| AppError::bad_request("DEVICE_REJECTED", e))?;
+    info!("Device {} of {} revoked", device_id, vault_id);
+    record(&state, &vault_id, "device.revoked", &signer, &device)?;
+    Ok(Json(device))
+}
+
+async fn set_requirement(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<RequirementRequest>,
+) -> Result<Json<DeviceSet>, AppError> {
+    super::require_mutable(&state, &vault_id, &signer, "device.requirement")?;
+    require_device_owner(&state, &vault_id, &signer)?;
+
+    let set = state
+        .devices
+        .set_requirement(
+            &vault_id,
+            &signer.address,
+            request.min_devices,
+            request.window_hours,
+        )
+        .map_err(|e| AppError::bad_request("DEVICE_REQUIREMENT_REJECTED", e))?;
+    state
+        .audit
+        .record(
+            &vault_id,
+            "device.requirement",
+            &signer.address,
+            json!({ "requirement": set.requirement }),
+        )
+        .map_err(AppError::internal)?;
+    Ok(Json(set))
+}
+
+async fn heartbeat(
+    State(state): State<AppState>,
+    Path((vault_id, device_id)): Path<(VaultId, String)>,
+    Json(request): Json<HeartbeatRequest>,
+) -> Result<Json<Device>, AppError> {
+    let device = state
+        .devices
+        .heartbeat(&vault_id, &device_id, request.timestamp, &request.signature)
+        .map_err(|e| AppError::new(StatusCode::FORBIDDEN, "HEARTBEAT_REJECTED", e))?;
+    state
+        .duress
+        .check(&vault_id, DuressTrigger::Device(&device_id))
+        .map_err(AppError::internal)?;
+    Ok(Json(device))
+}
+
+fn record(
+    state: &AppState,
+    vault_id: &str,
+    action: &str,
+    signer: &VerifiedSigner,
+    device: &Device,
+) -> Result<(), AppError> {
+    state
+        .audit
+        .record(
+            vault_id,
+            action,
+            &signer.address,
+            json!({ "device_id": device.device_id, "public_key": device.public_key }),
+        )
+        .map_err(AppError::internal)?;
+    Ok(())
+}
+
+fn require_device_owner(
+    state: &AppState,
+    vault_id: &str,
+    signer: &VerifiedSigner,
+) -> Result<(), AppError> {
+    let owners = super::vault_owners(state, vault_id)?;
+    let decision =
+        state
+            .policy
+            .evaluate_device_change(&signer.address, &owners, signer.delegate.is_some());
+    if !decision.allowed {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "DEVICES_DENIED",
+            decision.reason,
+        ));
+    }
+    Ok(())
+}
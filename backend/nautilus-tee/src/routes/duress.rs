@@ -0,0 +1,203 @@
+/**
+ * Duress Routes
+ * Setting duress signals, and reviewing and clearing the freeze they cause
+ */
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    routing::post,
+    Extension, Router,
+};
+use serde::Deserialize;
+use serde_json::json;
+use tracing::info;
+
+use crate::auth::VerifiedSigner;
+use crate::duress::{DuressLock, DuressSummary, CLEAR_OPERATION};
+use crate::error::AppError;
+use crate::notifications::Notification;
+use crate::validation::VaultId;
+use crate::AppState;
+
+#[derive(Deserialize)]
+struct DuressRequest {
+    #[serde(default)]
+    code: Option<String>,
+    #[serde(default)]
+    devices: Vec<String>,
+    #[serde(default)]
+    templates: Vec<String>,
+}
+
+pub fn signed_routes() -> Router<AppState> {
+    Router::new()
+        .route("/vault/:vault_id/duress", post(configure))
+        .route("/vault/:vault_id/duress/status", post(status))
+        .route("/vault/:vault_id/duress/clear", post(clear))
+}
+
+async fn configure(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<DuressRequest>,
+) -> Result<Json<DuressSummary>, AppError> {
+    super::require_mutable(&state, &vault_id, &signer, "duress.configure")?;
+    let owners = super::vault_owners(&state, &vault_id)?;
+    let decision =
+        state
+            .policy
+            .evaluate_duress_change(&signer.address, &owners, signer.delegate.is_some());
+    if !decision.allowed {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "DURESS_DENIED",
+            decision.reason,
+        ));
+    }
+
+    let enrolled = state
+        .devices
+        .devices(&vault_id)
+        .map_err(AppError::internal)?
+        .map(|set| set.devices)
+        .unwrap_or_default();
+    if let Some(device_id) = request.devices.iter().find(|id| {
+        !enrolled
+            .iter()
+            .any(|d| d.device_id == **id && d.revoked_at.is_none())
+    }) {
+        return Err(AppError::bad_request(
+            "DURESS_REJECTED",
+            format!("Device {} is not enrolled for this vault", device_id),
+        ));
+    }
+    if !request.templates.is_empty() {
+        let templates = state
+            .templates
+            .list(&vault_id, &signer.address)
+            .map_err(|e| AppError::new(StatusCode::FORBIDDEN, "DURESS_DENIED", e))?;
+        if let Some(template_id) = request
+            .templates
+            .iter()
+            .find(|id| !templates.iter().any(|t| t.template_id == **id))
+        {
+            return Err(AppError::bad_request(
+                "DURESS_REJECTED",
+                format!("Template {} is not enrolled for this vault", template_id),
+            ));
+        }
+    }
+
+    let settings = state
+        .duress
+        .configure(
+            &vault_id,
+            &signer.address,
+            request.code.as_deref(),
+            request.devices,
+            request.templates,
+        )
+        .map_err(|e| AppError::bad_request("DURESS_REJECTED", e))?;
+    info!("Duress signals for {} updated", vault_id);
+    // Records that signals exist, never which ones
+    state
+        .audit
+        .record(&vault_id, "duress.configured", &signer.address, json!({}))
+        .map_err(AppError::internal)?;
+    Ok(Json(DuressSummary::from(&settings)))
+}
+
+async fn status(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Extension(signer): Extension<VerifiedSigner>,
+) -> Result<Json<Option<DuressLock>>, AppError> {
+    let mut guardians: Vec<String> = state
+        .approvals
+        .guardian_set(&vault_id)
+        .map_err(AppError::internal)?
+        .map(|set| set.guardians.into_iter().map(|g| g.address).collect())
+        .unwrap_or_default();
+    if let Some(share_set) = state
+        .secrets
+        .share_set(&vault_id)
+        .map_err(AppError::internal)?
+    {
+        guardians.extend(share_set.guardians.into_iter().map(|g| g.address));
+    }
+    let decision = state
+        .policy
+        .evaluate_duress_review(&signer.address, &guardians);
+    if !decision.allowed {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "DURESS_DENIED",
+            decision.reason,
+        ));
+    }
+
+    state
+        .duress
+        .lock(&vault_id)
+        .map(Json)
+        .map_err(AppError::internal)
+}
+
+async fn clear(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Extension(signer): Extension<VerifiedSigner>,
+) -> Result<Json<DuressLock>, AppError> {
+    let approved = state
+        .approvals
+        .current_approval(&vault_id, CLEAR_OPERATION)
+        .map_err(AppError::internal)?
+        .is_some();
+    let decision = state
+        .policy
+        .evaluate_duress_clear(&signer.address, approved);
+    if !decision.allowed {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "DURESS_DENIED",
+            decision.reason,
+        ));
+    }
+
+    let lock = state
+        .duress
+        .clear(&vault_id)
+        .map_err(|e| AppError::new(StatusCode::CONFLICT, "NOT_FROZEN", e))?;
+    info!(
+        "Duress freeze on {} cleared by {}",
+        vault_id, signer.address
+    );
+    state
+        .audit
+        .record(
+            &vault_id,
+            "duress.cleared",
+            &signer.address,
+            json!({
+                "triggered_at": lock.triggered_at,
+                "source": lock.source,
+                "signals": lock.signals,
+            }),
+        )
+        .map_err(AppError::internal)?;
+    state
+        .notifications
+        .notify_guardians(
+            &vault_id,
+            &Notification {
+                kind: "duress.cleared".to_string(),
+                title: "Duress freeze cleared".to_string(),
+                body: format!("Releases from vault {} are no longer frozen.", vault_id),
+            },
+        )
+        .map_err(AppError::internal)?;
+    Ok(Json(lock))
+}
@@ -0,0 +1,156 @@
+/**
+ * Erasure Routes
+ * Requesting, cancelling and executing a vault's erasure, and its
+ * attested record
+ */
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    routing::{get, post},
+    Extension, Router,
+};
+use serde::Serialize;
+use tracing::info;
+
+use crate::attestation::Attestation;
+use crate::auth::VerifiedSigner;
+use crate::erasure::{ErasureRequest, VaultErasure, OPERATION};
+use crate::error::AppError;
+use crate::validation::VaultId;
+use crate::AppState;
+
+#[derive(Serialize)]
+struct ErasureStatusResponse {
+    request: ErasureRequest,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    erasure: Option<VaultErasure>,
+}
+
+#[derive(Serialize)]
+struct ErasureResponse {
+    erasure: VaultErasure,
+    attestation: Attestation, // Over the erasure record's digest
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/vault/:vault_id/erasure", get(status))
+}
+
+pub fn signed_routes() -> Router<AppState> {
+    Router::new()
+        .route("/vault/:vault_id/erasure", post(request))
+        .route("/vault/:vault_id/erasure/cancel", post(cancel))
+        .route("/vault/:vault_id/erasure/execute", post(execute))
+}
+
+async fn request(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Extension(signer): Extension<VerifiedSigner>,
+) -> Result<Json<ErasureRequest>, AppError> {
+    require_owner(&state, &vault_id, &signer)?;
+    super::require_mutable(&state, &vault_id, &signer, "vault.erasure")?;
+
+    let request = state
+        .erasures
+        .request(&vault_id, &signer.address)
+        .map_err(|e| AppError::new(StatusCode::CONFLICT, "ERASURE_REJECTED", e))?;
+    info!(
+        "Erasure of vault {} requested by {}; executable from {}",
+        vault_id, signer.address, request.execute_after
+    );
+    Ok(Json(request))
+}
+
+async fn cancel(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Extension(signer): Extension<VerifiedSigner>,
+) -> Result<Json<ErasureRequest>, AppError> {
+    require_owner(&state, &vault_id, &signer)?;
+    let request = state
+        .erasures
+        .cancel(&vault_id, &signer.address)
+        .map_err(|e| AppError::new(StatusCode::CONFLICT, "ERASURE_NOT_PENDING", e))?;
+    info!(
+        "Erasure of vault {} cancelled by {}",
+        vault_id, signer.address
+    );
+    Ok(Json(request))
+}
+
+async fn execute(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Extension(signer): Extension<VerifiedSigner>,
+) -> Result<Json<ErasureResponse>, AppError> {
+    require_owner(&state, &vault_id, &signer)?;
+    super::require_mutable(&state, &vault_id, &signer, "vault_delete")?;
+
+    let erasure = state
+        .erasures
+        .execute(&vault_id, &signer.address)
+        .map_err(|e| AppError::new(StatusCode::CONFLICT, "ERASURE_FAILED", e))?;
+    info!(
+        "Vault {} erased by {}: {} artifacts destroyed, {} record sets retained",
+        vault_id,
+        signer.address,
+        erasure.deletion.artifacts.len(),
+        erasure.retained.len()
+    );
+
+    let digest = erasure.digest().map_err(AppError::internal)?;
+    let attestation = state
+        .attestation
+        .generate_with_user_data(&vault_id, OPERATION, &digest)
+        .await?;
+    Ok(Json(ErasureResponse {
+        erasure,
+        attestation,
+    }))
+}
+
+async fn status(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+) -> Result<Json<ErasureStatusResponse>, AppError> {
+    let request = state
+        .erasures
+        .request_of(&vault_id)
+        .map_err(AppError::internal)?
+        .ok_or_else(|| {
+            AppError::new(
+                StatusCode::NOT_FOUND,
+                "ERASURE_NOT_FOUND",
+                "Erasure has not been requested",
+            )
+        })?;
+    let erasure = state
+        .erasures
+        .erasure(&vault_id)
+        .map_err(AppError::internal)?;
+    Ok(Json(ErasureStatusResponse { request, erasure }))
+}
+
+/// Each step is for an owner signing as themselves, as deletion is
+fn require_owner(
+    state: &AppState,
+    vault_id: &str,
+    signer: &VerifiedSigner,
+) -> Result<(), AppError> {
+    let owners = super::vault_owners(state, vault_id)?;
+    let decision =
+        state
+            .policy
+            .evaluate_vault_deletion(&signer.address, &owners, signer.delegate.is_some());
+    if !decision.allowed {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "ERASURE_DENIED",
+            decision.reason,
+        ));
+    }
+    Ok(())
+}
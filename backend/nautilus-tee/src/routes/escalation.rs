@@ -0,0 +1,72 @@
+/**
+ * Escalation Routes
+ * Setting the ladder a vault climbs after a missed check-in
+ */
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    routing::post,
+    Extension, Router,
+};
+use serde::Deserialize;
+use tracing::info;
+
+use crate::auth::VerifiedSigner;
+use crate::error::AppError;
+use crate::escalation::{EscalationLadder, Stage};
+use crate::validation::VaultId;
+use crate::AppState;
+
+#[derive(Deserialize)]
+struct LadderRequest {
+    stages: Vec<Stage>,
+}
+
+pub fn signed_routes() -> Router<AppState> {
+    Router::new().route("/vault/:vault_id/escalation", post(set_ladder))
+}
+
+async fn set_ladder(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<LadderRequest>,
+) -> Result<Json<EscalationLadder>, AppError> {
+    super::require_mutable(&state, &vault_id, &signer, "escalation.set")?;
+    let owners = super::vault_owners(&state, &vault_id)?;
+    let decision = state.policy.evaluate_escalation_change(
+        &signer.address,
+        &owners,
+        signer.delegate.is_some(),
+    );
+    if !decision.allowed {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "ESCALATION_DENIED",
+            decision.reason,
+        ));
+    }
+
+    let ladder = state
+        .escalation
+        .set_ladder(&vault_id, &signer.address, request.stages)
+        .map_err(|e| AppError::bad_request("ESCALATION_REJECTED", e))?;
+    let actions = ladder.stages.iter().map(|s| s.action).collect::<Vec<_>>();
+    info!(
+        "Escalation ladder for {} set by {}: {:?}",
+        vault_id, signer.address, actions
+    );
+
+    state
+        .audit
+        .record(
+            &vault_id,
+            "escalation.updated",
+            &signer.address,
+            serde_json::json!({ "stages": ladder.stages }),
+        )
+        .map_err(AppError::internal)?;
+    Ok(Json(ladder))
+}
@@ -0,0 +1,87 @@
+/**
+ * Key Escrow Routes
+ * Admin-authorized disaster recovery exports
+ */
+
+use axum::{
+    extract::{Path, State},
+    response::Json,
+    routing::post,
+    Extension, Router,
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::attestation::Attestation;
+use crate::auth::VerifiedSigner;
+use crate::error::AppError;
+use crate::escrow::{EscrowExport, ExportStatus};
+use crate::AppState;
+
+#[derive(Deserialize)]
+struct RequestExportRequest {
+    reason: String,
+}
+
+#[derive(Serialize)]
+struct ExportResponse {
+    export: EscrowExport,
+    /// Present once the export has run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    attestation: Option<Attestation>,
+}
+
+/// Every escrow operation is signed by an admin
+pub fn signed_routes() -> Router<AppState> {
+    Router::new()
+        .route("/admin/escrow/exports", post(request_export))
+        .route(
+            "/admin/escrow/exports/:export_id/approve",
+            post(approve_export),
+        )
+}
+
+async fn request_export(
+    State(state): State<AppState>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<RequestExportRequest>,
+) -> Result<Json<ExportResponse>, AppError> {
+    info!("Escrow export requested: admin={}", signer.address);
+
+    let export = state.escrow.request(&signer.address, &request.reason)?;
+
+    Ok(Json(ExportResponse {
+        export,
+        attestation: None,
+    }))
+}
+
+async fn approve_export(
+    State(state): State<AppState>,
+    Path(export_id): Path<String>,
+    Extension(signer): Extension<VerifiedSigner>,
+) -> Result<Json<ExportResponse>, AppError> {
+    info!(
+        "Escrow export approval: export_id={}, admin={}",
+        export_id, signer.address
+    );
+
+    let export = state
+        .escrow
+        .approve(&export_id, &signer.address, &state.policy)?;
+
+    let attestation = match export.status {
+        ExportStatus::Exported => Some(
+            state
+                .attestation
+                .generate_with_user_data("", "escrow_export", &export.digest())
+                .await?,
+        ),
+        ExportStatus::Pending => None,
+    };
+
+    Ok(Json(ExportResponse {
+        export,
+        attestation,
+    }))
+}
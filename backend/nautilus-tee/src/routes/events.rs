@@ -0,0 +1,97 @@
+/**
+ * Event Routes
+ * Drain of the enclave's event outbox by the host's webhook relay, and
+ * its reports of deliveries it gave up on
+ */
+
+use axum::{extract::State, http::StatusCode, response::Json, routing::post, Extension, Router};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::VerifiedSigner;
+use crate::dead_letters::{DeadLetter, DeadLetterSource};
+use crate::error::AppError;
+use crate::events::{SignedEvent, MAX_BATCH};
+use crate::routes::require_admin;
+use crate::AppState;
+
+#[derive(Deserialize)]
+struct EventsRequest {
+    after: Option<u64>, // Id of the last event already delivered
+    limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct EventsResponse {
+    events: Vec<SignedEvent>,
+    /// Cursor for the next drain
+    next: u64,
+}
+
+#[derive(Deserialize)]
+struct FailedDeliveryRequest {
+    event_id: u64,
+    /// The webhook URL the relay gave up on
+    endpoint: String,
+    error: Option<String>,
+    attempts: u32,
+}
+
+pub fn signed_routes() -> Router<AppState> {
+    Router::new()
+        .route("/admin/events", post(drain))
+        .route("/admin/events/failed", post(report_failed))
+}
+
+async fn drain(
+    State(state): State<AppState>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<EventsRequest>,
+) -> Result<Json<EventsResponse>, AppError> {
+    require_admin(&state, &signer)?;
+
+    let after = request.after.unwrap_or(0);
+    let events = state
+        .events
+        .after(after, request.limit.unwrap_or(MAX_BATCH).min(MAX_BATCH))
+        .map_err(AppError::internal)?;
+    let next = events.last().map(|e| e.event.id).unwrap_or(after);
+    Ok(Json(EventsResponse { events, next }))
+}
+
+/// Dead-letter a delivery the relay exhausted its retries on
+async fn report_failed(
+    State(state): State<AppState>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<FailedDeliveryRequest>,
+) -> Result<Json<DeadLetter>, AppError> {
+    require_admin(&state, &signer)?;
+    if request.endpoint.is_empty() {
+        return Err(AppError::bad_request(
+            "INVALID_ENDPOINT",
+            "A failed delivery needs its endpoint",
+        ));
+    }
+    let event = state
+        .events
+        .get(request.event_id)
+        .map_err(AppError::internal)?
+        .ok_or_else(|| {
+            AppError::new(
+                StatusCode::NOT_FOUND,
+                "EVENT_NOT_FOUND",
+                format!("Event {} has been dropped", request.event_id),
+            )
+        })?;
+    let letter = state
+        .dead_letters
+        .add(
+            DeadLetterSource::Webhook,
+            &format!("{}@{}", event.id, request.endpoint),
+            &event.vault_id,
+            request.error,
+            request.attempts,
+            &event,
+        )
+        .map_err(AppError::internal)?;
+    Ok(Json(letter))
+}
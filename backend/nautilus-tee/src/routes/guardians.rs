@@ -0,0 +1,153 @@
+/**
+ * Guardian Share Routes
+ * Splitting vault keys among guardians and collecting resubmitted shares
+ */
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    routing::{get, post},
+    Extension, Router,
+};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use zeroize::Zeroizing;
+
+use crate::attestation::Attestation;
+use crate::auth::VerifiedSigner;
+use crate::crypto::KeyExchange;
+use crate::error::AppError;
+use crate::secrets::{self, EncryptedShare, GuardianKey, ShareStatus};
+use crate::validation::VaultId;
+use crate::AppState;
+
+#[derive(Deserialize)]
+struct SplitRequest {
+    threshold: u8,
+    guardians: Vec<GuardianKey>,
+    content_key: Option<String>, // Base64; generated in-enclave when omitted
+    #[serde(default)]
+    key_exchange: KeyExchange,
+}
+
+#[derive(Serialize)]
+struct SplitResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content_key: Option<String>,
+    shares: Vec<EncryptedShare>,
+    attestation: Attestation,
+}
+
+#[derive(Deserialize)]
+struct SubmitShareRequest {
+    share: String, // Base64 decrypted share
+}
+
+/// Routes that must go through signature verification
+pub fn signed_routes() -> Router<AppState> {
+    Router::new()
+        .route("/vault/:vault_id/guardians/shares", post(split_shares))
+        .route(
+            "/vault/:vault_id/guardians/shares/submit",
+            post(submit_share),
+        )
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/vault/:vault_id/guardians/shares", get(share_status))
+}
+
+async fn split_shares(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<SplitRequest>,
+) -> Result<Json<SplitResponse>, AppError> {
+    super::require_registered_owner(&state, &vault_id, &signer, "splitting its key")?;
+    super::require_mutable(&state, &vault_id, &signer, "guardians.split")?;
+    info!(
+        "Guardian share split: vault_id={}, guardians={}, threshold={}",
+        vault_id,
+        request.guardians.len(),
+        request.threshold
+    );
+
+    let content_key = request
+        .content_key
+        .as_deref()
+        .map(|encoded| {
+            let bytes = Zeroizing::new(STANDARD.decode(encoded).unwrap_or_default());
+            <[u8; 32]>::try_from(bytes.as_slice())
+                .map(Zeroizing::new)
+                .map_err(|_| {
+                    AppError::bad_request("INVALID_CONTENT_KEY", "Content key must be 32 bytes")
+                })
+        })
+        .transpose()?;
+
+    let (generated, shares) = state
+        .secrets
+        .split(
+            &vault_id,
+            &signer.address,
+            content_key,
+            request.threshold,
+            &request.guardians,
+            request.key_exchange,
+        )
+        .map_err(|e| AppError::bad_request("SHARE_SPLIT_FAILED", e))?;
+
+    let attestation = state
+        .attestation
+        .generate(&vault_id, "guardian_share_split")
+        .await?;
+
+    Ok(Json(SplitResponse {
+        content_key: generated.map(|key| STANDARD.encode(key.as_ref())),
+        shares,
+        attestation,
+    }))
+}
+
+async fn submit_share(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<SubmitShareRequest>,
+) -> Result<Json<ShareStatus>, AppError> {
+    super::require_mutable(&state, &vault_id, &signer, "guardians.submit")?;
+    info!(
+        "Guardian share submitted: vault_id={}, guardian={}",
+        vault_id, signer.address
+    );
+
+    let share = secrets::decode_share(&request.share)
+        .map_err(|e| AppError::bad_request("INVALID_SHARE", e))?;
+
+    let status = state
+        .secrets
+        .submit(&vault_id, &signer.address, share, &state.policy)
+        .map_err(|e| AppError::bad_request("SHARE_REJECTED", e))?;
+
+    Ok(Json(status))
+}
+
+async fn share_status(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+) -> Result<Json<ShareStatus>, AppError> {
+    state
+        .secrets
+        .status(&vault_id)
+        .map_err(AppError::internal)?
+        .map(Json)
+        .ok_or_else(|| {
+            AppError::new(
+                StatusCode::NOT_FOUND,
+                "SHARES_NOT_FOUND",
+                "No guardian shares for this vault",
+            )
+        })
+}
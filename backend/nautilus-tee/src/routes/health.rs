@@ -0,0 +1,36 @@
+/**
+ * Health Routes
+ * Dependency health beyond the bare liveness probe at /health
+ */
+
+use axum::{extract::State, response::Json, routing::get, Router};
+use serde::Serialize;
+
+use crate::breaker::{self, BreakerState, BreakerStatus};
+use crate::dead_letters::DeadLetterHealth;
+use crate::error::AppError;
+use crate::AppState;
+
+#[derive(Serialize)]
+struct HealthDetail {
+    /// "degraded" while any breaker isn't closed or dead letters alert
+    status: &'static str,
+    breakers: Vec<BreakerStatus>,
+    dead_letters: DeadLetterHealth,
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/health/detail", get(detail))
+}
+
+async fn detail(State(state): State<AppState>) -> Result<Json<HealthDetail>, AppError> {
+    let breakers = breaker::statuses();
+    let dead_letters = state.dead_letters.health().map_err(AppError::internal)?;
+    let degraded =
+        dead_letters.alerting || breakers.iter().any(|b| b.state != BreakerState::Closed);
+    Ok(Json(HealthDetail {
+        status: if degraded { "degraded" } else { "ok" },
+        breakers,
+        dead_letters,
+    }))
+}
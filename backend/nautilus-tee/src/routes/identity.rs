@@ -0,0 +1,47 @@
+/**
+ * Identity Routes
+ * The enclave's identity key, attested, with its rotation history
+ */
+
+use axum::{extract::State, response::Json, routing::get, Router};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::Serialize;
+
+use crate::attestation::Attestation;
+use crate::error::AppError;
+use crate::keys::rotation::RotationEvent;
+use crate::AppState;
+
+#[derive(Serialize)]
+struct IdentityResponse {
+    public_key: String, // Base64 Ed25519
+    key_id: String,
+    role: String,
+    attestation: Attestation,
+    rotations: Vec<RotationEvent>,
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/identity", get(identity))
+}
+
+async fn identity(State(state): State<AppState>) -> Result<Json<IdentityResponse>, AppError> {
+    let public_key = state.identity.public_key();
+    let attestation = state
+        .attestation
+        .generate_for_key("enclave_identity", public_key.as_bytes())
+        .await?;
+    let rotations = state
+        .rotations
+        .history("identity", state.identity.role())
+        .map_err(AppError::internal)?;
+
+    Ok(Json(IdentityResponse {
+        public_key: STANDARD.encode(public_key.as_bytes()),
+        key_id: state.identity.key_id(),
+        role: state.identity.role().to_string(),
+        attestation,
+        rotations,
+    }))
+}
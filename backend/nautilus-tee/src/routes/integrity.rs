@@ -0,0 +1,162 @@
+/**
+ * Vault Integrity Routes
+ * Committing a vault's blobs and attested integrity checks against the
+ * committed Merkle root
+ */
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    routing::post,
+    Extension, Router,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+use crate::attestation::Attestation;
+use crate::auth::VerifiedSigner;
+use crate::error::AppError;
+use crate::integrity::{self, IntegrityVerdict, VaultCommitment};
+use crate::memory_budget::{self, Reservation};
+use crate::validation::VaultId;
+use crate::{over_budget, AppState};
+
+#[derive(Deserialize)]
+struct CommitRequest {
+    blobs: Vec<String>, // Base64 encrypted blobs, in vault order
+}
+
+#[derive(Serialize)]
+struct CommitResponse {
+    commitment: VaultCommitment,
+    attestation: Attestation, // Over the root
+}
+
+#[derive(Deserialize)]
+struct VerifyRequest {
+    /// Base64 encrypted blobs; the registered leaves are used when omitted
+    #[serde(default)]
+    blobs: Option<Vec<String>>,
+    /// Hex root recorded on the vault's on-chain object
+    #[serde(default)]
+    on_chain_root: Option<String>,
+}
+
+#[derive(Serialize)]
+struct VerifyResponse {
+    verdict: IntegrityVerdict,
+    attestation: Attestation, // Over SHA-256 of the verdict JSON
+}
+
+/// Anyone may check a vault's integrity
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/vault/:vault_id/verify-integrity", post(verify_integrity))
+}
+
+pub fn signed_routes() -> Router<AppState> {
+    Router::new().route("/vault/:vault_id/commitment", post(commit_blobs))
+}
+
+async fn commit_blobs(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<CommitRequest>,
+) -> Result<Json<CommitResponse>, AppError> {
+    super::require_registered_owner(&state, &vault_id, &signer, "committing its blobs")?;
+    super::require_mutable(&state, &vault_id, &signer, "integrity.commit")?;
+    let _memory = reserve_blobs(&state, &request.blobs).await?;
+    let leaves = blob_leaves(&request.blobs)?;
+    let commitment = state
+        .integrity
+        .commit(&vault_id, &signer.address, &leaves)
+        .map_err(|e| AppError::bad_request("COMMITMENT_REJECTED", e))?;
+    info!(
+        "Vault commitment: vault_id={}, blobs={}, root={}",
+        vault_id,
+        commitment.leaves.len(),
+        commitment.root
+    );
+
+    let root = hex::decode(&commitment.root).map_err(|e| AppError::internal(e.to_string()))?;
+    let attestation = state
+        .attestation
+        .generate_with_user_data(&vault_id, "vault_commitment", &root)
+        .await?;
+
+    Ok(Json(CommitResponse {
+        commitment,
+        attestation,
+    }))
+}
+
+async fn verify_integrity(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Json(request): Json<VerifyRequest>,
+) -> Result<Json<VerifyResponse>, AppError> {
+    let commitment = state
+        .integrity
+        .commitment(&vault_id)
+        .map_err(AppError::internal)?
+        .ok_or_else(|| {
+            AppError::new(
+                StatusCode::NOT_FOUND,
+                "COMMITMENT_NOT_FOUND",
+                "Vault has no committed root",
+            )
+        })?;
+    let _memory = match &request.blobs {
+        Some(blobs) => Some(reserve_blobs(&state, blobs).await?),
+        None => None,
+    };
+    let leaves = request.blobs.as_deref().map(blob_leaves).transpose()?;
+
+    let verdict = state
+        .integrity
+        .verify(
+            &commitment,
+            leaves.as_deref(),
+            request.on_chain_root.as_deref(),
+        )
+        .map_err(|e| AppError::bad_request("INVALID_INTEGRITY_REQUEST", e))?;
+    info!(
+        "Integrity check for {}: {}",
+        vault_id,
+        if verdict.intact { "intact" } else { "mismatch" }
+    );
+
+    let statement = serde_json::to_vec(&verdict).map_err(|e| AppError::internal(e.to_string()))?;
+    let attestation = state
+        .attestation
+        .generate_with_user_data(&vault_id, "vault_integrity", &Sha256::digest(statement))
+        .await?;
+
+    Ok(Json(VerifyResponse {
+        verdict,
+        attestation,
+    }))
+}
+
+/// Held while the submitted blobs are
+async fn reserve_blobs(state: &AppState, blobs: &[String]) -> Result<Reservation, AppError> {
+    let bytes = blobs.iter().map(String::len).sum();
+    state
+        .memory
+        .reserve(&[(memory_budget::PAYLOAD, bytes)])
+        .await
+        .map_err(over_budget)
+}
+
+/// Hashed from the base64, so no blob is ever held decoded
+fn blob_leaves(blobs: &[String]) -> Result<Vec<[u8; 32]>, AppError> {
+    blobs
+        .iter()
+        .map(|blob| {
+            integrity::leaf(blob)
+                .map_err(|_| AppError::bad_request("INVALID_BLOB", "Blobs must be base64"))
+        })
+        .collect()
+}
@@ -0,0 +1,176 @@
+/**
+ * Item Release Routes
+ * Per-file policies on a vault bundle and the release of single files
+ * from a combined threshold decryption
+ *
+ * An item release consumes the decryption session like a whole release,
+ * but returns no decryption proof: the proof lets anyone check the full
+ * plaintext, which the requester doesn't get. The attestation instead
+ * commits to SHA-256 of {"vault_id", "session_id", "requester",
+ * "released": [{"item", "size", "sha256"}], "withheld"}, bound to the
+ * installed policy bundle if there is one (see policy_bundles::bind).
+ */
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    routing::post,
+    Extension, Router,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+use crate::attestation::Attestation;
+use crate::auth::VerifiedSigner;
+use crate::error::AppError;
+use crate::items::{ItemPolicy, ItemPolicySet, ItemRecipient, ReleasedItem, WithheldItem};
+use crate::policy_bundles;
+use crate::validation::VaultId;
+use crate::AppState;
+
+#[derive(Deserialize)]
+struct SetPoliciesRequest {
+    items: Vec<ItemPolicy>,
+}
+
+#[derive(Serialize)]
+struct ItemReleaseResponse {
+    vault_id: String,
+    released: Vec<ReleasedItem>,
+    withheld: Vec<WithheldItem>,
+    /// Installed policy bundle the release was decided under
+    #[serde(skip_serializing_if = "Option::is_none")]
+    policy_sha256: Option<String>,
+    attestation: Attestation,
+}
+
+pub fn signed_routes() -> Router<AppState> {
+    Router::new()
+        .route("/vault/:vault_id/items/policies", post(set_policies))
+        .route(
+            "/vault/:vault_id/threshold/sessions/:session_id/release/items",
+            post(release_items),
+        )
+}
+
+async fn set_policies(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<SetPoliciesRequest>,
+) -> Result<Json<ItemPolicySet>, AppError> {
+    super::require_mutable(&state, &vault_id, &signer, "items.policies")?;
+    info!(
+        "Item policies: vault_id={}, items={}",
+        vault_id,
+        request.items.len()
+    );
+
+    let key_set = state
+        .threshold
+        .key_set(&vault_id)
+        .map_err(AppError::internal)?
+        .ok_or_else(|| {
+            AppError::new(
+                StatusCode::NOT_FOUND,
+                "NO_THRESHOLD_KEY",
+                "Vault has no threshold key",
+            )
+        })?;
+    state
+        .items
+        .set(&vault_id, &signer.address, &key_set.owner, request.items)
+        .map(Json)
+        .map_err(|e| AppError::bad_request("ITEM_POLICIES_REJECTED", e))
+}
+
+async fn release_items(
+    State(state): State<AppState>,
+    Path((vault_id, session_id)): Path<(VaultId, String)>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(recipient): Json<ItemRecipient>,
+) -> Result<Json<ItemReleaseResponse>, AppError> {
+    super::require_mutable(&state, &vault_id, &signer, "items.release")?;
+    super::require_unfrozen(&state, &vault_id)?;
+    let unlocked = super::require_escalated_release(&state, &vault_id)?;
+    super::require_template_release(&state, &vault_id, false, unlocked)?;
+    super::require_jurisdiction_release(&state, &vault_id)?;
+    // Checked before the session is consumed
+    let set = state
+        .items
+        .addressed_to(&vault_id, &signer.address)
+        .map_err(AppError::internal)?
+        .ok_or_else(|| {
+            AppError::new(
+                StatusCode::FORBIDDEN,
+                "NO_ITEMS",
+                "No vault items are addressed to the caller",
+            )
+        })?;
+    let key = recipient
+        .decode()
+        .map_err(|e| AppError::bad_request("INVALID_RECIPIENT_KEY", e))?;
+    let policy_sha256 = super::require_unlock_policy(&state, &signer, "item_release", &vault_id)?;
+
+    let (plaintext, _) = state
+        .threshold
+        .release(&vault_id, &session_id, &signer.address, &state.policy)
+        .map_err(|e| AppError::new(StatusCode::FORBIDDEN, "RELEASE_DENIED", e))?;
+    let release = state
+        .items
+        .release(
+            &set,
+            &signer.address,
+            &plaintext,
+            &key,
+            &state.policy,
+            &state.approvals,
+        )
+        .map_err(|e| AppError::new(StatusCode::UNPROCESSABLE_ENTITY, "ITEM_RELEASE_FAILED", e))?;
+    info!(
+        "Item release: vault_id={}, session={}, released={}, withheld={}",
+        vault_id,
+        session_id,
+        release.released.len(),
+        release.withheld.len()
+    );
+
+    let released: Vec<_> = release
+        .released
+        .iter()
+        .map(|item| {
+            serde_json::json!({
+                "item": item.item,
+                "size": item.size,
+                "sha256": item.sha256,
+                "redaction": item.redaction,
+            })
+        })
+        .collect();
+    let statement = serde_json::to_vec(&serde_json::json!({
+        "vault_id": vault_id,
+        "session_id": session_id,
+        "requester": signer.address,
+        "released": released,
+        "withheld": release.withheld,
+    }))
+    .map_err(|e| AppError::internal(e.to_string()))?;
+    let attestation = state
+        .attestation
+        .generate_with_user_data(
+            &vault_id,
+            "item_release",
+            &policy_bundles::bind(&Sha256::digest(statement), policy_sha256.as_deref()),
+        )
+        .await?;
+
+    Ok(Json(ItemReleaseResponse {
+        vault_id: vault_id.into(),
+        released: release.released,
+        withheld: release.withheld,
+        policy_sha256,
+        attestation,
+    }))
+}
@@ -0,0 +1,228 @@
+/**
+ * Jurisdiction Routes
+ * The configured rule packs, a vault's standing against its pack, and
+ * the evidence and contests that move it
+ *
+ * Evidence and contests are signed by an owner, guardian or beneficiary
+ * of the vault. Contests are resolved by an enclave admin.
+ */
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    routing::{get, post},
+    Extension, Router,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::info;
+
+use crate::auth::VerifiedSigner;
+use crate::error::AppError;
+use crate::jurisdictions::{Contest, EvidenceRecord, EvidenceRule, JurisdictionStatus};
+use crate::validation::VaultId;
+use crate::AppState;
+
+#[derive(Deserialize)]
+struct EvidenceRequest {
+    #[serde(flatten)]
+    rule: EvidenceRule,
+    /// snarkjs proof
+    proof: Value,
+    public_signals: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct ContestRequest {
+    reason: String,
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/jurisdictions", get(list_packs))
+        .route("/vault/:vault_id/jurisdiction", get(status))
+}
+
+pub fn signed_routes() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/vault/:vault_id/jurisdiction/evidence",
+            post(submit_evidence),
+        )
+        .route(
+            "/vault/:vault_id/jurisdiction/contests",
+            post(lodge_contest),
+        )
+        .route(
+            "/vault/:vault_id/jurisdiction/contests/:contest_id/resolve",
+            post(resolve_contest),
+        )
+}
+
+async fn list_packs(State(state): State<AppState>) -> Json<Value> {
+    Json(json!({ "packs": state.jurisdictions.packs() }))
+}
+
+async fn status(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+) -> Result<Json<JurisdictionStatus>, AppError> {
+    super::jurisdiction_status(&state, &vault_id)?
+        .map(Json)
+        .ok_or_else(no_rule_pack)
+}
+
+async fn submit_evidence(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<EvidenceRequest>,
+) -> Result<Json<EvidenceRecord>, AppError> {
+    require_party(&state, &vault_id, &signer)?;
+    let status = super::jurisdiction_status(&state, &vault_id)?.ok_or_else(no_rule_pack)?;
+    if !status.pack.evidence.contains(&request.rule) {
+        return Err(AppError::bad_request(
+            "EVIDENCE_NOT_REQUIRED",
+            format!(
+                "Rule pack {} doesn't ask for a {} v{} proof",
+                status.pack.jurisdiction, request.rule.claim_type, request.rule.version
+            ),
+        ));
+    }
+    let verified = super::verify_pinned_proof(
+        &state,
+        request.rule.claim_type,
+        request.rule.version,
+        request.proof,
+        request.public_signals.clone(),
+    )
+    .await?;
+    if !verified {
+        return Err(AppError::bad_request(
+            "EVIDENCE_REJECTED",
+            "Proof doesn't verify against the pinned verification key",
+        ));
+    }
+
+    let record = state
+        .jurisdictions
+        .record_evidence(
+            &vault_id,
+            request.rule,
+            request.public_signals,
+            &signer.address,
+        )
+        .map_err(AppError::internal)?;
+    state
+        .audit
+        .record(
+            &vault_id,
+            "jurisdiction.evidence",
+            &signer.address,
+            json!({ "claim_type": record.rule.claim_type, "version": record.rule.version }),
+        )
+        .map_err(AppError::internal)?;
+    info!(
+        "Evidence {} v{} recorded for vault {} by {}",
+        record.rule.claim_type, record.rule.version, vault_id, signer.address
+    );
+    Ok(Json(record))
+}
+
+async fn lodge_contest(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<ContestRequest>,
+) -> Result<Json<Contest>, AppError> {
+    require_party(&state, &vault_id, &signer)?;
+    super::jurisdiction_status(&state, &vault_id)?.ok_or_else(no_rule_pack)?;
+
+    let contest = state
+        .jurisdictions
+        .lodge_contest(&vault_id, &signer.address, &request.reason)
+        .map_err(|e| AppError::bad_request("CONTEST_REJECTED", e))?;
+    state
+        .audit
+        .record(
+            &vault_id,
+            "jurisdiction.contested",
+            &signer.address,
+            json!({ "contest_id": contest.contest_id }),
+        )
+        .map_err(AppError::internal)?;
+    info!(
+        "Contest {} lodged against release of vault {} by {}",
+        contest.contest_id, vault_id, signer.address
+    );
+    Ok(Json(contest))
+}
+
+async fn resolve_contest(
+    State(state): State<AppState>,
+    Path((vault_id, contest_id)): Path<(VaultId, String)>,
+    Extension(signer): Extension<VerifiedSigner>,
+) -> Result<Json<Contest>, AppError> {
+    super::require_admin(&state, &signer)?;
+    let contest = state
+        .jurisdictions
+        .resolve_contest(&vault_id, &contest_id, &signer.address)
+        .map_err(|e| AppError::new(StatusCode::CONFLICT, "CONTEST_NOT_OPEN", e))?;
+    state
+        .audit
+        .record(
+            &vault_id,
+            "jurisdiction.resolved",
+            &signer.address,
+            json!({ "contest_id": contest.contest_id }),
+        )
+        .map_err(AppError::internal)?;
+    info!(
+        "Contest {} on vault {} resolved by {}",
+        contest_id, vault_id, signer.address
+    );
+    Ok(Json(contest))
+}
+
+/// Owners, guardians and beneficiaries of the vault
+fn require_party(
+    state: &AppState,
+    vault_id: &str,
+    signer: &VerifiedSigner,
+) -> Result<(), AppError> {
+    let mut parties = super::vault_owners(state, vault_id)?;
+    if let Some(share_set) = state
+        .secrets
+        .share_set(vault_id)
+        .map_err(AppError::internal)?
+    {
+        parties.extend(share_set.guardians.into_iter().map(|g| g.address));
+    }
+    if let Some(set) = state
+        .release
+        .beneficiaries(vault_id)
+        .map_err(AppError::internal)?
+    {
+        parties.extend(set.beneficiaries.into_iter().map(|b| b.address));
+    }
+    let decision = state
+        .policy
+        .evaluate_jurisdiction_party(&signer.address, &parties);
+    if !decision.allowed {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "NOT_VAULT_PARTY",
+            decision.reason,
+        ));
+    }
+    Ok(())
+}
+
+fn no_rule_pack() -> AppError {
+    AppError::new(
+        StatusCode::NOT_FOUND,
+        "NO_RULE_PACK",
+        "Vault isn't registered under a jurisdiction rule pack",
+    )
+}
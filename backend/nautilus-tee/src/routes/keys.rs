@@ -0,0 +1,59 @@
+/**
+ * Key Routes
+ * Public identifiers of a vault's derived keys
+ */
+
+use axum::{
+    extract::{Path, State},
+    response::Json,
+    routing::get,
+    Router,
+};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::Serialize;
+
+use crate::keys::derive::{AuditSigning, KeyPurpose, Storage, TemplateEncryption};
+use crate::validation::VaultId;
+use crate::AppState;
+
+#[derive(Serialize)]
+struct DerivedKeyInfo {
+    purpose: &'static str,
+    key_id: String,
+}
+
+#[derive(Serialize)]
+struct VaultKeysResponse {
+    root_key_id: String,
+    keys: Vec<DerivedKeyInfo>,
+    audit_public_key: String, // Base64 Ed25519 key verifying the vault's audit trail
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/vault/:vault_id/keys", get(vault_keys))
+}
+
+async fn vault_keys(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+) -> Json<VaultKeysResponse> {
+    let audit = state.keys.derive::<AuditSigning>(&vault_id);
+
+    Json(VaultKeysResponse {
+        root_key_id: state.keys.root_key_id(),
+        keys: vec![
+            describe::<TemplateEncryption>(&state, &vault_id),
+            describe::<AuditSigning>(&state, &vault_id),
+            describe::<Storage>(&state, &vault_id),
+        ],
+        audit_public_key: STANDARD.encode(audit.verifying_key().as_bytes()),
+    })
+}
+
+fn describe<P: KeyPurpose>(state: &AppState, vault_id: &str) -> DerivedKeyInfo {
+    DerivedKeyInfo {
+        purpose: P::LABEL,
+        key_id: state.keys.key_id::<P>(vault_id),
+    }
+}
@@ -0,0 +1,174 @@
+/**
+ * Legal Hold Routes
+ * Placing, lifting and reading a vault's legal hold
+ */
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    routing::{get, post},
+    Extension, Router,
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::attestation::Attestation;
+use crate::audit::AuditEntry;
+use crate::auth::VerifiedSigner;
+use crate::error::AppError;
+use crate::legal_hold::{HoldAuthority, LegalHold, LIFT_OPERATION, PLACE_OPERATION};
+use crate::notifications::Notification;
+use crate::validation::VaultId;
+use crate::AppState;
+
+#[derive(Deserialize)]
+struct LegalHoldRequest {
+    active: bool,
+    reason: String,
+}
+
+#[derive(Serialize)]
+struct LegalHoldResponse {
+    hold: LegalHold,
+    audit_entry: AuditEntry,
+    attestation: Attestation, // Over the audit entry's hash
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/vault/:vault_id/legal-hold", get(legal_hold))
+}
+
+pub fn signed_routes() -> Router<AppState> {
+    Router::new().route("/vault/:vault_id/legal-hold", post(set_legal_hold))
+}
+
+async fn set_legal_hold(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<LegalHoldRequest>,
+) -> Result<Json<LegalHoldResponse>, AppError> {
+    let operation = if request.active {
+        PLACE_OPERATION
+    } else {
+        LIFT_OPERATION
+    };
+    let approved = state
+        .approvals
+        .current_approval(&vault_id, operation)
+        .map_err(AppError::internal)?
+        .is_some();
+    let decision = state
+        .policy
+        .evaluate_legal_hold_change(&signer.address, approved);
+    if !decision.allowed {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "LEGAL_HOLD_DENIED",
+            decision.reason,
+        ));
+    }
+    let authority = if state.policy.evaluate_admin(&signer.address).allowed {
+        HoldAuthority::Admin
+    } else {
+        HoldAuthority::GuardianQuorum
+    };
+
+    let hold = state
+        .legal_holds
+        .set(
+            &vault_id,
+            request.active,
+            &request.reason,
+            authority,
+            &signer.address,
+        )
+        .map_err(|e| AppError::new(StatusCode::CONFLICT, "LEGAL_HOLD_UNCHANGED", e))?;
+    let action = if hold.active {
+        "legal_hold.placed"
+    } else {
+        "legal_hold.lifted"
+    };
+    info!(
+        "Legal hold {} for {} by {} ({:?})",
+        if hold.active { "placed" } else { "lifted" },
+        vault_id,
+        signer.address,
+        authority
+    );
+
+    let audit_entry = state
+        .audit
+        .record(
+            &vault_id,
+            action,
+            &signer.address,
+            serde_json::json!({ "reason": hold.reason, "authority": hold.authority }),
+        )
+        .map_err(AppError::internal)?;
+    state
+        .events
+        .emit(
+            action,
+            &vault_id,
+            serde_json::json!({ "reason": hold.reason }),
+        )
+        .map_err(AppError::internal)?;
+    let (title, body) = if hold.active {
+        (
+            "Legal hold placed",
+            format!(
+                "Vault {} is under legal hold; it can be read but not changed until the hold \
+                 is lifted.",
+                vault_id
+            ),
+        )
+    } else {
+        (
+            "Legal hold lifted",
+            format!("The legal hold on vault {} has been lifted.", vault_id),
+        )
+    };
+    state
+        .notifications
+        .notify(
+            &vault_id,
+            &Notification {
+                kind: action.to_string(),
+                title: title.to_string(),
+                body,
+            },
+        )
+        .map_err(AppError::internal)?;
+
+    let hash = hex::decode(&audit_entry.hash).map_err(|e| AppError::internal(e.to_string()))?;
+    let attestation = state
+        .attestation
+        .generate_with_user_data(&vault_id, "legal_hold", &hash)
+        .await?;
+
+    Ok(Json(LegalHoldResponse {
+        hold,
+        audit_entry,
+        attestation,
+    }))
+}
+
+async fn legal_hold(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+) -> Result<Json<LegalHold>, AppError> {
+    state
+        .legal_holds
+        .hold(&vault_id)
+        .map_err(AppError::internal)?
+        .map(Json)
+        .ok_or_else(|| {
+            AppError::new(
+                StatusCode::NOT_FOUND,
+                "LEGAL_HOLD_NOT_FOUND",
+                "Vault has never been under legal hold",
+            )
+        })
+}
@@ -0,0 +1,133 @@
+/**
+ * Batch Liveness Routes
+ * Liveness for many vaults at once, under one attestation
+ */
+
+use axum::{extract::State, response::Json, routing::post, Extension, Router};
+use futures_util::future::join_all;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+use crate::attestation::Attestation;
+use crate::auth::VerifiedSigner;
+use crate::error::AppError;
+use crate::liveness::LivenessResult;
+use crate::validation::VaultId;
+use crate::AppState;
+
+const MAX_BATCH: usize = 100;
+
+#[derive(Deserialize)]
+struct BatchRequest {
+    vault_ids: Vec<VaultId>,
+}
+
+#[derive(Serialize)]
+struct VaultLiveness {
+    vault_id: String,
+    /// The registered owner whose liveness was checked
+    #[serde(skip_serializing_if = "Option::is_none")]
+    owner: Option<String>,
+    #[serde(flatten)]
+    result: Option<LivenessResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct BatchResponse {
+    results: Vec<VaultLiveness>,
+    alive: usize,
+    results_sha256: String,
+    attestation: Attestation, // Over results_sha256
+}
+
+pub fn signed_routes() -> Router<AppState> {
+    Router::new().route("/liveness/check-batch", post(check_batch))
+}
+
+async fn check_batch(
+    State(state): State<AppState>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<BatchRequest>,
+) -> Result<Json<BatchResponse>, AppError> {
+    let mut vault_ids: Vec<String> = request.vault_ids.into_iter().map(String::from).collect();
+    vault_ids.sort();
+    vault_ids.dedup();
+    if vault_ids.is_empty() || vault_ids.len() > MAX_BATCH {
+        return Err(AppError::bad_request(
+            "INVALID_BATCH",
+            format!("A batch checks 1 to {} vaults", MAX_BATCH),
+        ));
+    }
+
+    let results = join_all(
+        vault_ids
+            .into_iter()
+            .map(|vault_id| check(&state, vault_id)),
+    )
+    .await;
+    let alive = results
+        .iter()
+        .filter(|r| r.result.as_ref().is_some_and(|r| r.alive))
+        .count();
+    info!(
+        "Batch liveness check by {}: {} of {} vaults alive",
+        signer.address,
+        alive,
+        results.len()
+    );
+
+    let encoded = serde_json::to_vec(&results).map_err(|e| AppError::internal(e.to_string()))?;
+    let digest = Sha256::digest(&encoded);
+    let attestation = state
+        .attestation
+        .generate_with_user_data("", "liveness_check_batch", &digest)
+        .await?;
+
+    Ok(Json(BatchResponse {
+        results,
+        alive,
+        results_sha256: hex::encode(digest),
+        attestation,
+    }))
+}
+
+/// Liveness of the vault's owner; with several owners on record, the one
+/// seen most confidently
+async fn check(state: &AppState, vault_id: String) -> VaultLiveness {
+    let owners = match super::vault_owners(state, &vault_id) {
+        Ok(owners) => owners,
+        Err(e) => return failed(vault_id, e.message),
+    };
+
+    let mut checked = Vec::new();
+    for owner in owners {
+        match state.liveness.check(&vault_id, &owner).await {
+            Ok(result) => checked.push((owner, result)),
+            Err(e) => return failed(vault_id, e.to_string()),
+        }
+    }
+    let Some((owner, result)) = checked
+        .into_iter()
+        .max_by(|(_, a), (_, b)| a.confidence.total_cmp(&b.confidence))
+    else {
+        return failed(vault_id, "Vault has no owner registered with the enclave");
+    };
+    VaultLiveness {
+        vault_id,
+        owner: Some(owner),
+        result: Some(result),
+        error: None,
+    }
+}
+
+fn failed(vault_id: String, error: impl Into<String>) -> VaultLiveness {
+    VaultLiveness {
+        vault_id,
+        owner: None,
+        result: None,
+        error: Some(error.into()),
+    }
+}
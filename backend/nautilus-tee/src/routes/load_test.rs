@@ -0,0 +1,176 @@
+/**
+ * Load Test Routes
+ * Admin-run synthetic workloads; only in builds with the `load-test` feature
+ */
+
+use axum::{extract::State, response::Json, routing::post, Extension, Router};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use rand::RngCore;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tracing::info;
+
+use crate::auth::VerifiedSigner;
+use crate::error::AppError;
+use crate::exchange::canonical_sha256;
+use crate::load_test::{
+    self, LatencySummary, LoadTestReport, MemoryReport, Workload, MAX_CONCURRENCY,
+    MAX_ITERATIONS, MAX_PAYLOAD_BYTES,
+};
+use crate::routes::require_admin;
+use crate::{prove_claim, AppState, ZKProofRequest};
+
+const LOAD_TEST_VAULT: &str = "load-test";
+
+#[derive(Deserialize)]
+struct LoadTestRequest {
+    workload: Workload,
+    iterations: usize,
+    concurrency: usize,
+    /// Size of the synthetic encrypted blob or biometric sample
+    payload_bytes: usize,
+    /// Proofs only; a file_hash claim over the payload by default
+    #[serde(default)]
+    claim_type: Option<String>,
+    #[serde(default)]
+    claim_value: Option<Value>,
+    /// Biometric only; fingerprint by default
+    #[serde(default)]
+    method: Option<String>,
+}
+
+pub fn signed_routes() -> Router<AppState> {
+    Router::new().route("/debug/load-test", post(run))
+}
+
+async fn run(
+    State(state): State<AppState>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<LoadTestRequest>,
+) -> Result<Json<LoadTestReport>, AppError> {
+    require_admin(&state, &signer)?;
+    if !(1..=MAX_ITERATIONS).contains(&request.iterations)
+        || !(1..=MAX_CONCURRENCY).contains(&request.concurrency)
+        || !(1..=MAX_PAYLOAD_BYTES).contains(&request.payload_bytes)
+    {
+        return Err(AppError::bad_request(
+            "INVALID_LOAD_TEST",
+            format!(
+                "iterations must be 1..={}, concurrency 1..={}, payload_bytes 1..={}",
+                MAX_ITERATIONS, MAX_CONCURRENCY, MAX_PAYLOAD_BYTES
+            ),
+        ));
+    }
+    info!(
+        "Load test by {}: {:?} x{} at concurrency {}, {} byte payloads",
+        signer.address,
+        request.workload,
+        request.iterations,
+        request.concurrency,
+        request.payload_bytes
+    );
+
+    // One payload shared by every iteration, so generating it isn't timed
+    let mut payload = vec![0u8; request.payload_bytes];
+    state.randomness.rng().fill_bytes(&mut payload);
+    let payload = Arc::new(payload);
+    let workload = Arc::new(request);
+
+    let (rss_before_kb, _) = load_test::memory_usage();
+    let peak_reset = load_test::reset_peak_memory();
+    let permits = Arc::new(Semaphore::new(workload.concurrency));
+    let started = Instant::now();
+
+    let tasks: Vec<_> = (0..workload.iterations)
+        .map(|_| {
+            let (state, payload, workload) = (state.clone(), payload.clone(), workload.clone());
+            let permits = permits.clone();
+            tokio::spawn(async move {
+                let _permit = permits.acquire_owned().await.map_err(|e| e.to_string())?;
+                let began = Instant::now();
+                iteration(&state, &workload, &payload).await?;
+                Ok::<Duration, String>(began.elapsed())
+            })
+        })
+        .collect();
+
+    let mut latencies = Vec::with_capacity(tasks.len());
+    let mut failures = 0;
+    let mut first_error = None;
+    for task in tasks {
+        match task.await.map_err(|e| e.to_string()).and_then(|r| r) {
+            Ok(latency) => latencies.push(latency),
+            Err(e) => {
+                failures += 1;
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+    let elapsed = started.elapsed();
+    let (_, peak_rss_kb) = load_test::memory_usage();
+
+    Ok(Json(LoadTestReport {
+        workload: workload.workload,
+        iterations: workload.iterations,
+        concurrency: workload.concurrency,
+        payload_bytes: workload.payload_bytes,
+        failures,
+        first_error,
+        elapsed_ms: elapsed.as_secs_f64() * 1000.0,
+        throughput_per_sec: latencies.len() as f64 / elapsed.as_secs_f64(),
+        latency: LatencySummary::from_samples(latencies),
+        memory: MemoryReport {
+            rss_before_kb,
+            peak_rss_kb,
+            peak_reset,
+        },
+    }))
+}
+
+async fn iteration(
+    state: &AppState,
+    workload: &LoadTestRequest,
+    payload: &[u8],
+) -> Result<(), String> {
+    match workload.workload {
+        Workload::Proof => {
+            // Built as a request body would arrive, so decoding and the
+            // canonical hash are part of the measured path
+            let body = json!({
+                "vault_id": LOAD_TEST_VAULT,
+                "claim_type": workload.claim_type.as_deref().unwrap_or("file_hash"),
+                "claim_value": workload.claim_value.clone().unwrap_or_else(|| {
+                    json!({ "hash": hex::encode(Sha256::digest(payload)) })
+                }),
+                "encrypted_data": STANDARD.encode(payload),
+            });
+            let request_sha256 = canonical_sha256(&body)?;
+            let request: ZKProofRequest =
+                serde_json::from_value(body).map_err(|e| e.to_string())?;
+            let _worker = state.proof_jobs.queue().admit(request.priority)?.started().await;
+            prove_claim(state, request, &request_sha256, &|_| {})
+                .await
+                .map_err(|e| e.message)?;
+        }
+        Workload::Biometric => {
+            let method = workload.method.as_deref().unwrap_or("fingerprint");
+            let biometric = &state.biometric;
+            let features = biometric
+                .extract_features(payload, method)
+                .map_err(|e| e.to_string())?;
+            biometric
+                .compare(&features, payload, method)
+                .map_err(|e| e.to_string())?;
+            biometric
+                .verify(payload, method)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,64 @@
+/**
+ * Measurement Allowlist Routes
+ * The builds this enclave accepts as peers, and admin rotation of the list
+ */
+
+use axum::{
+    extract::State,
+    response::Json,
+    routing::{get, post},
+    Extension, Router,
+};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+use crate::allowlist::InstalledAllowlist;
+use crate::auth::VerifiedSigner;
+use crate::error::AppError;
+use crate::routes::require_admin;
+use crate::AppState;
+
+#[derive(Deserialize)]
+struct InstallRequest {
+    document: String,  // Base64 of the exact signed JSON bytes
+    signature: String, // Hex Ed25519 under TEE_ALLOWLIST_KEY
+}
+
+#[derive(Serialize)]
+struct AllowlistResponse {
+    /// None until a signed list is installed
+    allowlist: Option<InstalledAllowlist>,
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/measurements", get(current))
+}
+
+pub fn signed_routes() -> Router<AppState> {
+    Router::new().route("/admin/measurements", post(install))
+}
+
+async fn current(State(state): State<AppState>) -> Result<Json<AllowlistResponse>, AppError> {
+    let allowlist = state.allowlist.current().map_err(AppError::internal)?;
+    Ok(Json(AllowlistResponse { allowlist }))
+}
+
+async fn install(
+    State(state): State<AppState>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<InstallRequest>,
+) -> Result<Json<AllowlistResponse>, AppError> {
+    require_admin(&state, &signer)?;
+    let document = STANDARD
+        .decode(&request.document)
+        .map_err(|_| AppError::bad_request("INVALID_ALLOWLIST", "Document must be base64"))?;
+
+    let installed = state
+        .allowlist
+        .install(&document, &request.signature, &signer.address)
+        .map_err(|e| AppError::bad_request("ALLOWLIST_REJECTED", e))?;
+    Ok(Json(AllowlistResponse {
+        allowlist: Some(installed),
+    }))
+}
@@ -0,0 +1,52 @@
+/**
+ * Memory Routes
+ * Current use of the enclave memory budget, and allocation statistics for
+ * admins sizing enclave memory
+ */
+
+use axum::{
+    extract::State,
+    response::Json,
+    routing::{get, post},
+    Extension, Router,
+};
+use serde::Deserialize;
+
+use crate::allocator::{self, AllocationStats};
+use crate::auth::VerifiedSigner;
+use crate::error::AppError;
+use crate::memory_budget::MemoryMetrics;
+use crate::routes::require_admin;
+use crate::AppState;
+
+#[derive(Deserialize)]
+struct AllocationsRequest {
+    /// Start peak tracking over after reading it, to measure a workload
+    #[serde(default)]
+    reset_peak: bool,
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/memory", get(memory))
+}
+
+pub fn signed_routes() -> Router<AppState> {
+    Router::new().route("/admin/memory/allocations", post(allocations))
+}
+
+async fn memory(State(state): State<AppState>) -> Json<MemoryMetrics> {
+    Json(state.memory.metrics())
+}
+
+async fn allocations(
+    State(state): State<AppState>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<AllocationsRequest>,
+) -> Result<Json<AllocationStats>, AppError> {
+    require_admin(&state, &signer)?;
+    let stats = allocator::stats();
+    if request.reset_peak {
+        allocator::reset_peak();
+    }
+    Ok(Json(stats))
+}
@@ -0,0 +1,279 @@
+/**
+ * Vault Migration Routes
+ * Admin-relayed handoff of a vault between enclaves
+ */
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    routing::{get, post},
+    Extension, Router,
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use super::require_admin;
+use crate::attestation::{Attestation, VerifiedAttestation};
+use crate::auth::VerifiedSigner;
+use crate::error::AppError;
+use crate::migration::{
+    self, MigrationAck, MigrationBundle, MigrationOffer, MigrationRecord, Tombstone, ACK_OPERATION,
+    COMPLETE_OPERATION, EXPORT_OPERATION, OFFER_OPERATION,
+};
+use crate::validation::VaultId;
+use crate::AppState;
+
+#[derive(Deserialize)]
+struct OfferRequest {
+    vault_id: VaultId,
+}
+
+#[derive(Serialize, Deserialize)]
+struct AttestedOffer {
+    offer: MigrationOffer,
+    attestation: Attestation,
+}
+
+#[derive(Serialize, Deserialize)]
+struct AttestedBundle {
+    bundle: MigrationBundle,
+    attestation: Attestation,
+}
+
+#[derive(Serialize, Deserialize)]
+struct AttestedAck {
+    ack: MigrationAck,
+    attestation: Attestation,
+}
+
+#[derive(Serialize)]
+struct ImportResponse {
+    #[serde(flatten)]
+    ack: AttestedAck,
+    record: MigrationRecord,
+}
+
+#[derive(Serialize)]
+struct CompletedMigration {
+    record: MigrationRecord,
+    attestation: Attestation, // Over the record's digest
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/migrations/:migration_id", get(migration_record))
+        .route("/vault/:vault_id/tombstone", get(tombstone))
+}
+
+/// Every migration step is signed by an admin
+pub fn signed_routes() -> Router<AppState> {
+    Router::new()
+        .route("/admin/migrations/offers", post(offer))
+        .route("/admin/migrations/export", post(export))
+        .route("/admin/migrations/import", post(import))
+        .route("/admin/migrations/:migration_id/complete", post(complete))
+        .route("/admin/migrations/:migration_id/cancel", post(cancel))
+}
+
+/// Destination, step 1
+async fn offer(
+    State(state): State<AppState>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<OfferRequest>,
+) -> Result<Json<AttestedOffer>, AppError> {
+    require_admin(&state, &signer)?;
+
+    let offer = state
+        .migrations
+        .offer(&request.vault_id)
+        .map_err(|e| AppError::bad_request("MIGRATION_OFFER_FAILED", e))?;
+    info!(
+        "Migration {} offered for {} by {}",
+        offer.migration_id, offer.vault_id, signer.address
+    );
+
+    let digest = migration::digest(&offer).map_err(AppError::internal)?;
+    let attestation = state
+        .attestation
+        .generate_with_user_data(&offer.vault_id, OFFER_OPERATION, &digest)
+        .await?;
+
+    Ok(Json(AttestedOffer { offer, attestation }))
+}
+
+/// Source, step 2
+async fn export(
+    State(state): State<AppState>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<AttestedOffer>,
+) -> Result<Json<AttestedBundle>, AppError> {
+    require_admin(&state, &signer)?;
+    super::require_mutable(&state, &request.offer.vault_id, &signer, "migration_export")?;
+
+    let destination = verify_enclave(&state, &request.attestation)?;
+    let bundle = state
+        .migrations
+        .export(&request.offer, &destination, &signer.address)
+        .map_err(|e| AppError::bad_request("MIGRATION_EXPORT_FAILED", e))?;
+    info!(
+        "Migration {} exported {} to {}: {} records, {} templates",
+        bundle.offer.migration_id,
+        bundle.offer.vault_id,
+        bundle.offer.destination.identity,
+        bundle.records,
+        bundle.templates
+    );
+
+    let digest = migration::digest(&bundle).map_err(AppError::internal)?;
+    let attestation = state
+        .attestation
+        .generate_with_user_data(&bundle.offer.vault_id, EXPORT_OPERATION, &digest)
+        .await?;
+
+    Ok(Json(AttestedBundle {
+        bundle,
+        attestation,
+    }))
+}
+
+/// Destination, step 3
+async fn import(
+    State(state): State<AppState>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<AttestedBundle>,
+) -> Result<Json<ImportResponse>, AppError> {
+    require_admin(&state, &signer)?;
+
+    let source = verify_enclave(&state, &request.attestation)?;
+    let (ack, record) = state
+        .migrations
+        .import(&request.bundle, &source, &signer.address)
+        .map_err(|e| AppError::bad_request("MIGRATION_IMPORT_REJECTED", e))?;
+    info!(
+        "Migration {} imported {} from {}",
+        record.migration_id, record.vault_id, record.source.identity
+    );
+
+    state
+        .audit
+        .record(
+            &record.vault_id,
+            "migration.imported",
+            &signer.address,
+            serde_json::json!({
+                "migration_id": record.migration_id,
+                "source": record.source,
+                "source_audit_key": request.bundle.source_audit_key,
+            }),
+        )
+        .map_err(AppError::internal)?;
+
+    let digest = migration::digest(&ack).map_err(AppError::internal)?;
+    let attestation = state
+        .attestation
+        .generate_with_user_data(&ack.vault_id, ACK_OPERATION, &digest)
+        .await?;
+
+    Ok(Json(ImportResponse {
+        ack: AttestedAck { ack, attestation },
+        record,
+    }))
+}
+
+/// Source, step 4
+async fn complete(
+    State(state): State<AppState>,
+    Path(migration_id): Path<String>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<AttestedAck>,
+) -> Result<Json<CompletedMigration>, AppError> {
+    require_admin(&state, &signer)?;
+
+    let destination = verify_enclave(&state, &request.attestation)?;
+    let record = state
+        .migrations
+        .complete(&migration_id, &request.ack, &destination)
+        .map_err(|e| AppError::bad_request("MIGRATION_COMPLETE_FAILED", e))?;
+    info!(
+        "Migration {} complete: {} tombstoned, now at {}",
+        migration_id, record.vault_id, record.destination.identity
+    );
+
+    let digest = migration::digest(&record).map_err(AppError::internal)?;
+    let attestation = state
+        .attestation
+        .generate_with_user_data(&record.vault_id, COMPLETE_OPERATION, &digest)
+        .await?;
+
+    Ok(Json(CompletedMigration {
+        record,
+        attestation,
+    }))
+}
+
+async fn cancel(
+    State(state): State<AppState>,
+    Path(migration_id): Path<String>,
+    Extension(signer): Extension<VerifiedSigner>,
+) -> Result<Json<MigrationRecord>, AppError> {
+    require_admin(&state, &signer)?;
+
+    let record = state
+        .migrations
+        .cancel(&migration_id)
+        .map_err(|e| AppError::bad_request("MIGRATION_CANCEL_FAILED", e))?;
+    info!(
+        "Migration {} of {} cancelled by {}",
+        migration_id, record.vault_id, signer.address
+    );
+
+    Ok(Json(record))
+}
+
+async fn migration_record(
+    State(state): State<AppState>,
+    Path(migration_id): Path<String>,
+) -> Result<Json<MigrationRecord>, AppError> {
+    state
+        .migrations
+        .record(&migration_id)
+        .map_err(AppError::internal)?
+        .map(Json)
+        .ok_or_else(|| {
+            AppError::new(
+                StatusCode::NOT_FOUND,
+                "MIGRATION_NOT_FOUND",
+                "Unknown migration",
+            )
+        })
+}
+
+async fn tombstone(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+) -> Result<Json<Tombstone>, AppError> {
+    state
+        .migrations
+        .tombstone(&vault_id)
+        .map_err(AppError::internal)?
+        .map(Json)
+        .ok_or_else(|| {
+            AppError::new(
+                StatusCode::NOT_FOUND,
+                "TOMBSTONE_NOT_FOUND",
+                "Vault has not migrated away from this enclave",
+            )
+        })
+}
+
+/// The other enclave's attestation, up to the Nitro root (see attestation)
+fn verify_enclave(
+    state: &AppState,
+    attestation: &Attestation,
+) -> Result<VerifiedAttestation, AppError> {
+    state
+        .attestation
+        .verify(attestation)
+        .map_err(|e| AppError::bad_request("MIGRATION_ATTESTATION_INVALID", e.to_string()))
+}
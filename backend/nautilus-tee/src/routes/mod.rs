@@ -0,0 +1,517 @@
+/**
+ * API Routes
+ * Handlers for endpoints beyond the core verify/check/generate set
+ */
+
+pub mod analytics;
+pub mod approvals;
+pub mod archive;
+pub mod audit;
+pub mod calibration;
+pub mod capabilities;
+pub mod check_in;
+pub mod co_owners;
+pub mod compliance;
+pub mod content;
+pub mod data_export;
+pub mod dead_letters;
+pub mod deletion;
+pub mod devices;
+pub mod duress;
+pub mod erasure;
+pub mod escalation;
+pub mod escrow;
+pub mod events;
+pub mod guardians;
+pub mod health;
+pub mod identity;
+pub mod integrity;
+pub mod items;
+pub mod jurisdictions;
+pub mod keys;
+pub mod legal_hold;
+pub mod liveness;
+#[cfg(feature = "load-test")]
+pub mod load_test;
+pub mod measurements;
+pub mod memory;
+pub mod migration;
+pub mod notifications;
+pub mod outbox;
+pub mod peer;
+pub mod policy_bundles;
+pub mod profiling;
+pub mod proof_jobs;
+pub mod proof_verification;
+pub mod random;
+pub mod redaction;
+pub mod release;
+pub mod retries;
+pub mod schema;
+pub mod session;
+pub mod share_grants;
+pub mod signing;
+pub mod tasks;
+pub mod templates;
+pub mod threshold;
+pub mod timestamp;
+pub mod vault_index;
+pub mod vault_state;
+pub mod vault_templates;
+pub mod verification_keys;
+pub mod version;
+pub mod webhooks;
+
+use axum::http::StatusCode;
+use serde_json::Value;
+use std::time::Duration;
+
+use crate::auth::VerifiedSigner;
+use crate::co_owners;
+use crate::error::AppError;
+use crate::groth16;
+use crate::jurisdictions::JurisdictionStatus;
+use crate::notifications::Notification;
+use crate::validation::ClaimType;
+use crate::AppState;
+
+/// Repeated attempts on a held vault notify its contacts once per window
+const LOCKOUT_NOTICE_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// Admin-only endpoints: the verified signer must be a configured admin
+pub fn require_admin(state: &AppState, signer: &VerifiedSigner) -> Result<(), AppError> {
+    let decision = state.policy.evaluate_admin(&signer.address);
+    if !decision.allowed {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "NOT_ADMIN",
+            decision.reason,
+        ));
+    }
+    Ok(())
+}
+
+/// Everyone the enclave records as owning the vault: the owners of its
+/// guardian shares, threshold key, blob commitment and template
+/// registration, and its co-owners
+pub fn vault_owners(state: &AppState, vault_id: &str) -> Result<Vec<String>, AppError> {
+    let co_owners = state
+        .co_owners
+        .co_owners(vault_id)
+        .map_err(AppError::internal)?
+        .map(|set| set.owners)
+        .unwrap_or_default();
+    let mut owners = vec![
+        state
+            .secrets
+            .share_set(vault_id)
+            .map_err(AppError::internal)?
+            .map(|s| s.owner),
+        state
+            .threshold
+            .key_set(vault_id)
+            .map_err(AppError::internal)?
+            .map(|k| k.owner),
+        state
+            .integrity
+            .commitment(vault_id)
+            .map_err(AppError::internal)?
+            .map(|c| c.owner),
+        state
+            .vault_templates
+            .registration(vault_id)
+            .map_err(AppError::internal)?
+            .map(|r| r.owner),
+    ]
+    .into_iter()
+    .flatten()
+    .chain(co_owners)
+    .collect::<Vec<_>>();
+    owners.sort();
+    owners.dedup();
+    Ok(owners)
+}
+
+/// The vault must be registered from a template and the signer among its
+/// owners; `action` completes "Register the vault before ..."
+pub fn require_registered_owner(
+    state: &AppState,
+    vault_id: &str,
+    signer: &VerifiedSigner,
+    action: &str,
+) -> Result<(), AppError> {
+    let registered = state
+        .vault_templates
+        .registration(vault_id)
+        .map_err(AppError::internal)?
+        .is_some();
+    let owners = vault_owners(state, vault_id)?;
+    let decision = state
+        .policy
+        .evaluate_owner_action(&signer.address, registered, &owners, action);
+    if !decision.allowed {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "NOT_VAULT_OWNER",
+            decision.reason,
+        ));
+    }
+    Ok(())
+}
+
+/// Mutations are refused while the vault is under legal hold, being
+/// migrated, gone to another enclave or deleted. Legal hold refusals go into the
+/// vault's audit trail, and its contacts hear about them at most hourly. On a
+/// co-owned vault, quorum actions also need their owner approvals.
+pub fn require_mutable(
+    state: &AppState,
+    vault_id: &str,
+    signer: &VerifiedSigner,
+    action: &str,
+) -> Result<(), AppError> {
+    if let Some(tombstone) = state
+        .migrations
+        .tombstone(vault_id)
+        .map_err(AppError::internal)?
+    {
+        return Err(AppError::new(
+            StatusCode::GONE,
+            "VAULT_MIGRATED",
+            format!(
+                "Vault moved to enclave {} in migration {}",
+                tombstone.destination.identity, tombstone.migration_id
+            ),
+        ));
+    }
+    if let Some(deletion) = state
+        .deletions
+        .deletion(vault_id)
+        .map_err(AppError::internal)?
+    {
+        return Err(AppError::new(
+            StatusCode::GONE,
+            "VAULT_DELETED",
+            format!("Vault was deleted at {}", deletion.deleted_at),
+        ));
+    }
+    if state
+        .migrations
+        .migrating(vault_id)
+        .map_err(AppError::internal)?
+        .is_some()
+    {
+        return Err(AppError::new(
+            StatusCode::LOCKED,
+            "VAULT_MIGRATING",
+            "Vault is being migrated to another enclave",
+        ));
+    }
+
+    let held = state
+        .legal_holds
+        .is_held(vault_id)
+        .map_err(AppError::internal)?;
+    let decision = state.policy.evaluate_vault_mutation(held);
+    if !decision.allowed {
+        state
+            .audit
+            .record(
+                vault_id,
+                "legal_hold.blocked",
+                &signer.address,
+                serde_json::json!({ "action": action }),
+            )
+            .map_err(AppError::internal)?;
+        state
+            .notifications
+            .notify_throttled(
+                vault_id,
+                &Notification {
+                    kind: "legal_hold.blocked".to_string(),
+                    title: "Vault change blocked".to_string(),
+                    body: format!(
+                        "A change to vault {} was refused because it is under legal hold.",
+                        vault_id
+                    ),
+                },
+                LOCKOUT_NOTICE_WINDOW,
+            )
+            .map_err(AppError::internal)?;
+        return Err(AppError::new(
+            StatusCode::LOCKED,
+            "LEGAL_HOLD",
+            decision.reason,
+        ));
+    }
+    require_owner_quorum(state, vault_id, signer, action)
+}
+
+/// Co-owned vaults take quorum actions only when enough owners approved
+/// this exact request; the submitting owner counts as one
+fn require_owner_quorum(
+    state: &AppState,
+    vault_id: &str,
+    signer: &VerifiedSigner,
+    action: &str,
+) -> Result<(), AppError> {
+    if !co_owners::QUORUM_ACTIONS.contains(&action) {
+        return Ok(());
+    }
+    let Some(set) = state
+        .co_owners
+        .co_owners(vault_id)
+        .map_err(AppError::internal)?
+    else {
+        return Ok(());
+    };
+
+    let mut approvers = state
+        .co_owners
+        .approvers(vault_id, action, &signer.request_sha256, &set.owners)
+        .map_err(AppError::internal)?;
+    if set.owners.contains(&signer.address) && !approvers.contains(&signer.address) {
+        approvers.push(signer.address.clone());
+    }
+    let decision = state
+        .policy
+        .evaluate_owner_quorum(approvers.len(), set.threshold);
+    if !decision.allowed {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "OWNER_QUORUM_REQUIRED",
+            decision.reason,
+        )
+        .with_details(serde_json::json!({
+            "action": action,
+            "request_sha256": signer.request_sha256,
+            "approvers": approvers,
+            "threshold": set.threshold,
+        })));
+    }
+    Ok(())
+}
+
+/// Consult the installed policy bundle, if any, after the built-in checks
+/// have passed. Returns the bundle's hash, for the decision's attestation.
+pub fn require_bundle_policy(
+    state: &AppState,
+    signer: &VerifiedSigner,
+    action: &str,
+    vault_id: Option<&str>,
+    extra: Value,
+) -> Result<Option<String>, AppError> {
+    if !state.policy_bundles.installed() {
+        return Ok(None);
+    }
+    let owner = match vault_id {
+        Some(vault_id) => vault_owners(state, vault_id)?.contains(&signer.address),
+        None => false,
+    };
+    let mut context = serde_json::json!({
+        "delegated": signer.delegate.is_some(),
+        "admin": state.policy.evaluate_admin(&signer.address).allowed,
+        "owner": owner,
+    });
+    if let (Some(context), Value::Object(extra)) = (context.as_object_mut(), extra) {
+        context.extend(extra);
+    }
+    let verdict = state
+        .policy_bundles
+        .authorize(&signer.address, action, vault_id, context)
+        .map_err(AppError::internal)?;
+    let decision = state.policy.evaluate_policy_bundle(verdict.as_ref());
+    if !decision.allowed {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "POLICY_DENIED",
+            decision.reason,
+        ));
+    }
+    Ok(verdict.map(|v| v.policy_sha256))
+}
+
+/// Unlock decisions go to the bundle as `operation` on the vault, with
+/// its escalation and itemization in the context
+pub fn require_unlock_policy(
+    state: &AppState,
+    signer: &VerifiedSigner,
+    operation: &str,
+    vault_id: &str,
+) -> Result<Option<String>, AppError> {
+    if !state.policy_bundles.installed() {
+        return Ok(None);
+    }
+    let unlocked = state
+        .escalation
+        .unlocked(vault_id)
+        .map_err(AppError::internal)?;
+    let itemized = state
+        .items
+        .itemized(vault_id)
+        .map_err(AppError::internal)?;
+    require_bundle_policy(
+        state,
+        signer,
+        operation,
+        Some(vault_id),
+        serde_json::json!({
+            "laddered": unlocked.is_some(),
+            "unlocked": unlocked.unwrap_or(false),
+            "itemized": itemized,
+        }),
+    )
+}
+
+/// Release paths refuse vaults whose escalation ladder hasn't reached
+/// unlock; returns the ladder state, None for vaults without one
+pub fn require_escalated_release(
+    state: &AppState,
+    vault_id: &str,
+) -> Result<Option<bool>, AppError> {
+    let unlocked = state
+        .escalation
+        .unlocked(vault_id)
+        .map_err(AppError::internal)?;
+    let decision = state
+        .policy
+        .evaluate_escalated_release(unlocked.is_some(), unlocked.unwrap_or(false));
+    if !decision.allowed {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "RELEASE_DENIED",
+            decision.reason,
+        ));
+    }
+    Ok(unlocked)
+}
+
+/// Release paths refuse vaults frozen by a duress signal
+pub fn require_unfrozen(state: &AppState, vault_id: &str) -> Result<(), AppError> {
+    let frozen = state
+        .duress
+        .is_frozen(vault_id)
+        .map_err(AppError::internal)?;
+    let decision = state.policy.evaluate_frozen_release(frozen);
+    if !decision.allowed {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "RELEASE_DENIED",
+            decision.reason,
+        ));
+    }
+    Ok(())
+}
+
+/// Vaults registered from a template are released only the way it allows;
+/// `unlocked` is the vault's escalation ladder state, if it has one
+pub fn require_template_release(
+    state: &AppState,
+    vault_id: &str,
+    whole: bool,
+    unlocked: Option<bool>,
+) -> Result<(), AppError> {
+    let template = state
+        .vault_templates
+        .template_of(vault_id)
+        .map_err(AppError::internal)?;
+    let decision = state.policy.evaluate_template_release(
+        template.map(|t| &t.release),
+        whole,
+        unlocked.is_some(),
+        unlocked.unwrap_or(false),
+    );
+    if !decision.allowed {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "RELEASE_DENIED",
+            decision.reason,
+        ));
+    }
+    Ok(())
+}
+
+/// Vaults registered under a jurisdiction's rule pack are released only
+/// once it is satisfied, timed by the hardened clock so the host can't cut
+/// the contest window short
+pub fn require_jurisdiction_release(state: &AppState, vault_id: &str) -> Result<(), AppError> {
+    let status = jurisdiction_status(state, vault_id)?;
+    let now = state
+        .clock
+        .now()
+        .map_err(|e| AppError::new(StatusCode::SERVICE_UNAVAILABLE, "CLOCK_UNRELIABLE", e))?;
+    let decision = state
+        .policy
+        .evaluate_jurisdiction_release(status.as_ref(), now.as_secs());
+    if !decision.allowed {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "RELEASE_DENIED",
+            decision.reason,
+        ));
+    }
+    Ok(())
+}
+
+/// None for vaults not registered under a rule pack
+pub fn jurisdiction_status(
+    state: &AppState,
+    vault_id: &str,
+) -> Result<Option<JurisdictionStatus>, AppError> {
+    let Some(pack) = state
+        .vault_templates
+        .registration(vault_id)
+        .map_err(AppError::internal)?
+        .and_then(|r| r.rule_pack)
+    else {
+        return Ok(None);
+    };
+    let unlocked_at = state
+        .escalation
+        .status(vault_id)
+        .map_err(AppError::internal)?
+        .filter(|s| s.unlocked)
+        .and_then(|s| s.entered_at);
+    // The deadline whose miss started the ladder
+    let since = state
+        .check_ins
+        .schedule(vault_id)
+        .map_err(AppError::internal)?
+        .map(|s| s.expires_at());
+    state
+        .jurisdictions
+        .status(vault_id, pack, unlocked_at, since)
+        .map(Some)
+        .map_err(AppError::internal)
+}
+
+/// Whether a snarkjs proof verifies against the pinned key of the circuit
+/// version
+pub async fn verify_pinned_proof(
+    state: &AppState,
+    claim_type: ClaimType,
+    version: u32,
+    proof: Value,
+    public_signals: Vec<String>,
+) -> Result<bool, AppError> {
+    let key = state
+        .zk_proof
+        .circuits()
+        .get(claim_type.as_str(), version)
+        .and_then(|c| c.verifying_key.clone())
+        .ok_or_else(|| {
+            AppError::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "VERIFICATION_KEY_UNAVAILABLE",
+                format!(
+                    "No pinned verification key is loaded for {} circuit version {}",
+                    claim_type, version
+                ),
+            )
+        })?;
+
+    let verified =
+        tokio::task::spawn_blocking(move || groth16::verify(&key, &proof, &public_signals))
+            .await
+            .map_err(|e| AppError::internal(format!("Verification task failed: {}", e)))?;
+    Ok(verified.unwrap_or(false))
+}
@@ -0,0 +1,111 @@
+/**
+ * Notification Routes
+ * Owner-managed notification contacts and the delivery status of jobs
+ */
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+    routing::post,
+    Extension, Router,
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::auth::VerifiedSigner;
+use crate::error::AppError;
+use crate::notifications::{Contact, ContactSet, JobRecord};
+use crate::pagination::{self, PageQuery};
+use crate::validation::VaultId;
+use crate::AppState;
+
+#[derive(Deserialize)]
+struct ContactsRequest {
+    contacts: Vec<Contact>,
+}
+
+#[derive(Serialize)]
+struct NotificationsResponse {
+    contacts: Option<ContactSet>,
+    /// Paged by the query string, filterable by delivery status
+    jobs: Vec<JobRecord>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_cursor: Option<String>,
+}
+
+/// Contacts are personal data, so even reading them is signed
+pub fn signed_routes() -> Router<AppState> {
+    Router::new()
+        .route("/vault/:vault_id/notifications", post(notifications))
+        .route(
+            "/vault/:vault_id/notifications/contacts",
+            post(set_contacts),
+        )
+}
+
+async fn set_contacts(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<ContactsRequest>,
+) -> Result<Json<ContactSet>, AppError> {
+    super::require_mutable(&state, &vault_id, &signer, "notifications.contacts")?;
+    require_contact_owner(&state, &vault_id, &signer)?;
+
+    let set = state
+        .notifications
+        .set_contacts(&vault_id, &signer.address, request.contacts)
+        .map_err(|e| AppError::bad_request("CONTACTS_REJECTED", e))?;
+    info!(
+        "Notification contacts for {} set by {}: {}",
+        vault_id,
+        signer.address,
+        set.contacts.len()
+    );
+    Ok(Json(set))
+}
+
+async fn notifications(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Query(query): Query<PageQuery>,
+    Extension(signer): Extension<VerifiedSigner>,
+) -> Result<Json<NotificationsResponse>, AppError> {
+    require_contact_owner(&state, &vault_id, &signer)?;
+
+    let jobs = state
+        .notifications
+        .jobs(&vault_id)
+        .map_err(AppError::internal)?;
+    let page =
+        pagination::paginate(jobs, &query).map_err(|e| AppError::bad_request("INVALID_PAGE", e))?;
+    Ok(Json(NotificationsResponse {
+        contacts: state
+            .notifications
+            .contacts(&vault_id)
+            .map_err(AppError::internal)?,
+        jobs: page.items,
+        next_cursor: page.next_cursor,
+    }))
+}
+
+fn require_contact_owner(
+    state: &AppState,
+    vault_id: &str,
+    signer: &VerifiedSigner,
+) -> Result<(), AppError> {
+    let owners = super::vault_owners(state, vault_id)?;
+    let decision =
+        state
+            .policy
+            .evaluate_contact_change(&signer.address, &owners, signer.delegate.is_some());
+    if !decision.allowed {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "NOTIFICATIONS_DENIED",
+            decision.reason,
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,45 @@
+/**
+ * Outbox Routes
+ * On-chain intents, newest first, for admins checking that submissions
+ * reach the chain and are confirmed
+ */
+
+use axum::{extract::State, response::Json, routing::post, Extension, Router};
+use serde::Serialize;
+
+use crate::auth::VerifiedSigner;
+use crate::error::AppError;
+use crate::outbox::{IntentRecord, IntentStatus};
+use crate::routes::require_admin;
+use crate::AppState;
+
+#[derive(Serialize)]
+struct OutboxResponse {
+    intents: Vec<IntentRecord>,
+    /// Staged or submitted, not yet confirmed
+    unconfirmed: usize,
+    failed: usize,
+}
+
+pub fn signed_routes() -> Router<AppState> {
+    Router::new().route("/admin/outbox", post(list))
+}
+
+async fn list(
+    State(state): State<AppState>,
+    Extension(signer): Extension<VerifiedSigner>,
+) -> Result<Json<OutboxResponse>, AppError> {
+    require_admin(&state, &signer)?;
+    let intents = state.outbox.intents().map_err(AppError::internal)?;
+    let count = |statuses: &[IntentStatus]| {
+        intents
+            .iter()
+            .filter(|i| statuses.contains(&i.status))
+            .count()
+    };
+    Ok(Json(OutboxResponse {
+        unconfirmed: count(&[IntentStatus::Pending, IntentStatus::Submitted]),
+        failed: count(&[IntentStatus::Failed]),
+        intents,
+    }))
+}
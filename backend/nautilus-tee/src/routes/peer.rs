@@ -0,0 +1,122 @@
+/**
+ * Peer Routes
+ * Endpoints other enclaves call, authenticated by their attestation
+ */
+
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{header, StatusCode},
+    response::{Json, Response},
+    routing::post,
+    Extension, Router,
+};
+use hyper::upgrade::OnUpgrade;
+use hyper_util::rt::TokioIo;
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::attestation::Attestation;
+use crate::channel::UPGRADE_PROTOCOL;
+use crate::error::AppError;
+use crate::peer::VerifiedPeer;
+use crate::AppState;
+
+/// Channel purpose that echoes every message back, so a peer can check a
+/// channel end to end before anything depends on it
+const PING_PURPOSE: &str = "ping";
+
+#[derive(Serialize)]
+struct PingResponse {
+    /// The caller's PCR0, as its attestation showed it
+    peer_measurement: String,
+    identity: String, // Our identity key id
+    attestation: Attestation,
+}
+
+pub fn peer_routes() -> Router<AppState> {
+    Router::new()
+        .route("/peer/ping", post(ping))
+        .route("/peer/channel", post(open_channel))
+}
+
+/// Lets a peer check it reaches this enclave and is accepted by it
+async fn ping(
+    State(state): State<AppState>,
+    Extension(peer): Extension<VerifiedPeer>,
+) -> Result<Json<PingResponse>, AppError> {
+    let attestation = state
+        .attestation
+        .generate_with_user_data("", "peer_ping", peer.measurement.as_bytes())
+        .await?;
+    Ok(Json(PingResponse {
+        peer_measurement: peer.measurement,
+        identity: state.identity.key_id(),
+        attestation,
+    }))
+}
+
+/// Switches the connection to an attested channel (see channel). The
+/// handshake attests both ends again; its peer must be the enclave whose
+/// attestation let this request through.
+async fn open_channel(
+    State(state): State<AppState>,
+    Extension(peer): Extension<VerifiedPeer>,
+    mut request: Request,
+) -> Result<Response, AppError> {
+    let requested = request
+        .headers()
+        .get(header::UPGRADE)
+        .and_then(|value| value.to_str().ok());
+    if requested != Some(UPGRADE_PROTOCOL) {
+        return Err(AppError::bad_request(
+            "UPGRADE_REQUIRED",
+            format!("Channels are opened by upgrading to {}", UPGRADE_PROTOCOL),
+        ));
+    }
+    let upgrade = hyper::upgrade::on(&mut request);
+
+    tokio::spawn(async move {
+        if let Err(e) = run_channel(&state, &peer, upgrade).await {
+            warn!("Channel from {} ended: {}", peer.measurement, e);
+        }
+    });
+
+    Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header(header::CONNECTION, "upgrade")
+        .header(header::UPGRADE, UPGRADE_PROTOCOL)
+        .body(Body::empty())
+        .map_err(|e| AppError::internal(e.to_string()))
+}
+
+/// Runs the protocol the peer's hello asked for until either end closes
+async fn run_channel(
+    state: &AppState,
+    peer: &VerifiedPeer,
+    upgrade: OnUpgrade,
+) -> Result<(), String> {
+    let stream = upgrade
+        .await
+        .map_err(|e| format!("Upgrade failed: {}", e))?;
+    let mut channel = state.channels.accept(TokioIo::new(stream)).await?;
+    if channel.peer().measurement != peer.measurement {
+        return Err(format!(
+            "Channel peer {} is not the attested caller",
+            channel.peer().measurement
+        ));
+    }
+    let purpose = channel.peer().purpose.clone();
+    info!(
+        "Channel opened: peer={}, purpose={}",
+        peer.measurement, purpose
+    );
+
+    match purpose.as_str() {
+        PING_PURPOSE => loop {
+            let message = channel.recv().await?;
+            channel.send(&message).await?;
+        },
+        purpose => Err(format!("No protocol for channel purpose {}", purpose)),
+    }
+}
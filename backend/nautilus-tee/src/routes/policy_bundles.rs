@@ -0,0 +1,89 @@
+/**
+ * Policy Bundle Routes
+ * The installed Cedar bundle, admin installs, and the check every signed
+ * call passes through
+ */
+
+use axum::{
+    extract::{MatchedPath, RawPathParams, Request, State},
+    middleware::Next,
+    response::{Json, Response},
+    routing::{get, post},
+    Extension, Router,
+};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::auth::VerifiedSigner;
+use crate::error::AppError;
+use crate::policy_bundles::InstalledPolicyBundle;
+use crate::routes::require_admin;
+use crate::AppState;
+
+#[derive(Deserialize)]
+struct InstallRequest {
+    document: String,  // Base64 of the exact signed JSON bytes
+    signature: String, // Hex Ed25519 under TEE_POLICY_BUNDLE_KEY
+}
+
+#[derive(Serialize)]
+struct BundleResponse {
+    /// None until a signed bundle is installed
+    bundle: Option<InstalledPolicyBundle>,
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/policy-bundle", get(current))
+}
+
+pub fn signed_routes() -> Router<AppState> {
+    Router::new().route("/admin/policy-bundle", post(install))
+}
+
+/// Runs inside auth::require_signature, as "<METHOD> <route>" on the
+/// route's vault. Installs themselves aren't gated, so a bundle that locks
+/// admins out can still be replaced.
+pub async fn authorize_request(
+    State(state): State<AppState>,
+    matched: MatchedPath,
+    params: RawPathParams,
+    Extension(signer): Extension<VerifiedSigner>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    if matched.as_str() != "/admin/policy-bundle" {
+        let action = format!("{} {}", request.method(), matched.as_str());
+        let vault_id = params
+            .iter()
+            .find(|(name, _)| *name == "vault_id")
+            .map(|(_, value)| value);
+        super::require_bundle_policy(&state, &signer, &action, vault_id, json!({}))?;
+    }
+    Ok(next.run(request).await)
+}
+
+async fn current(State(state): State<AppState>) -> Result<Json<BundleResponse>, AppError> {
+    let bundle = state.policy_bundles.current().map_err(AppError::internal)?;
+    Ok(Json(BundleResponse { bundle }))
+}
+
+async fn install(
+    State(state): State<AppState>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<InstallRequest>,
+) -> Result<Json<BundleResponse>, AppError> {
+    require_admin(&state, &signer)?;
+    let document = STANDARD
+        .decode(&request.document)
+        .map_err(|_| AppError::bad_request("INVALID_POLICY_BUNDLE", "Document must be base64"))?;
+
+    let installed = state
+        .policy_bundles
+        .install(&document, &request.signature, &signer.address)
+        .map_err(|e| AppError::bad_request("POLICY_BUNDLE_REJECTED", e))?;
+    Ok(Json(BundleResponse {
+        bundle: Some(installed),
+    }))
+}
@@ -0,0 +1,89 @@
+/**
+ * Profiling Routes
+ * Admin-requested CPU profiles of the running enclave
+ */
+
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::{IntoResponse, Json, Response},
+    routing::post,
+    Extension, Router,
+};
+use serde::Deserialize;
+use std::time::Duration;
+use tracing::info;
+
+use crate::auth::VerifiedSigner;
+use crate::error::AppError;
+use crate::profiling::{
+    self, ProfileError, ProfileFormat, DEFAULT_FREQUENCY, MAX_DURATION, MAX_FREQUENCY,
+};
+use crate::routes::require_admin;
+use crate::AppState;
+
+#[derive(Deserialize)]
+struct ProfileRequest {
+    seconds: u64,
+    /// Samples per second; DEFAULT_FREQUENCY if unset
+    #[serde(default)]
+    frequency: Option<i32>,
+    #[serde(default)]
+    format: ProfileFormat,
+}
+
+pub fn signed_routes() -> Router<AppState> {
+    Router::new().route("/admin/profile/cpu", post(cpu))
+}
+
+async fn cpu(
+    State(state): State<AppState>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<ProfileRequest>,
+) -> Result<Response, AppError> {
+    require_admin(&state, &signer)?;
+    let frequency = request.frequency.unwrap_or(DEFAULT_FREQUENCY);
+    if !(1..=MAX_DURATION.as_secs()).contains(&request.seconds)
+        || !(1..=MAX_FREQUENCY).contains(&frequency)
+    {
+        return Err(AppError::bad_request(
+            "INVALID_PROFILE",
+            format!(
+                "seconds must be 1..={}, frequency 1..={}",
+                MAX_DURATION.as_secs(),
+                MAX_FREQUENCY
+            ),
+        ));
+    }
+    info!(
+        "CPU profile by {}: {}s as {:?}",
+        signer.address, request.seconds, request.format
+    );
+
+    let format = request.format;
+    let duration = Duration::from_secs(request.seconds);
+    let profile =
+        tokio::task::spawn_blocking(move || profiling::profile(duration, frequency, format))
+            .await
+            .map_err(|e| AppError::internal(e.to_string()))?
+            .map_err(|e| match e {
+                ProfileError::Busy => AppError::new(
+                    StatusCode::CONFLICT,
+                    "PROFILE_IN_PROGRESS",
+                    "A CPU profile is already running",
+                ),
+                ProfileError::Failed(reason) => AppError::internal(reason),
+            })?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", format.file_name()),
+            ),
+        ],
+        profile,
+    )
+        .into_response())
+}
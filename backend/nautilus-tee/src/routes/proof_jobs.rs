@@ -0,0 +1,102 @@
+/**
+ * Proof Job Routes
+ * Start proofs in the background, then poll them or follow their progress
+ * as server-sent events
+ */
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        Json,
+    },
+    routing::{get, post},
+    Router,
+};
+use futures_util::stream::{self, Stream};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::info;
+
+use crate::error::AppError;
+use crate::exchange::HashedJson;
+use crate::proof_jobs::{Job, JobStatus, QueueMetrics};
+use crate::{join_or_prove, AppState, ZKProofRequest};
+
+/// How often a running job's stream refreshes elapsed time and ETA
+const PROGRESS_TICK: Duration = Duration::from_secs(1);
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/zk/jobs", post(start))
+        .route("/zk/jobs/:job_id", get(status))
+        .route("/zk/jobs/:job_id/events", get(events))
+        .route("/zk/queue", get(queue))
+}
+
+async fn start(
+    State(state): State<AppState>,
+    HashedJson {
+        value: request,
+        sha256: request_sha256,
+    }: HashedJson<ZKProofRequest>,
+) -> Result<(StatusCode, Json<JobStatus>), AppError> {
+    let (vault_id, claim_type) = (request.vault_id.clone(), request.claim_type);
+    let job = join_or_prove(&state, request, request_sha256)?;
+    info!(
+        "ZK proof job {}: vault_id={}, claim_type={}",
+        job.id(),
+        vault_id,
+        claim_type
+    );
+
+    Ok((StatusCode::ACCEPTED, Json(job.snapshot())))
+}
+
+async fn status(
+    State(state): State<AppState>,
+    Path(job_id): Path<String>,
+) -> Result<Json<JobStatus>, AppError> {
+    Ok(Json(find(&state, &job_id)?.snapshot()))
+}
+
+/// A `progress` event on every stage change and each tick, ending with
+/// the done or failed status
+async fn events(
+    State(state): State<AppState>,
+    Path(job_id): Path<String>,
+) -> Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>, AppError> {
+    let job = find(&state, &job_id)?;
+    let updates = job.subscribe();
+
+    let stream = stream::unfold(Some((job, updates, true)), |cursor| async move {
+        let (job, mut updates, first) = cursor?;
+        if !first {
+            // Closed means the job was dropped before it finished
+            if let Ok(Err(_)) = tokio::time::timeout(PROGRESS_TICK, updates.changed()).await {
+                return None;
+            }
+        }
+        let status = job.snapshot();
+        let finished = status.stage.is_finished();
+        let event = Event::default().event("progress").json_data(&status);
+        Some((event, (!finished).then_some((job, updates, false))))
+    });
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+/// Depth, limits and wait times of the proving queue
+async fn queue(State(state): State<AppState>) -> Json<QueueMetrics> {
+    Json(state.proof_jobs.queue().metrics())
+}
+
+fn find(state: &AppState, job_id: &str) -> Result<Arc<Job>, AppError> {
+    state.proof_jobs.get(job_id).ok_or_else(|| {
+        AppError::new(
+            StatusCode::NOT_FOUND,
+            "JOB_NOT_FOUND",
+            format!("No proof job {}", job_id),
+        )
+    })
+}
@@ -0,0 +1,169 @@
+/**
+ * Proof Verification Routes
+ * Verdicts on externally supplied proofs, many at once, under one attestation
+ *
+ * Each item names the circuit version its proof claims to come from and
+ * is checked against that version's pinned verification key, retired
+ * versions included. Versions whose key isn't loaded (placeholder
+ * circuits, or no signed manifest) get an `unverifiable` verdict rather
+ * than a guess. Items are verified in parallel on the blocking pool.
+ */
+
+use axum::{extract::State, response::Json, routing::post, Router};
+use futures_util::future::join_all;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+use crate::attestation::Attestation;
+use crate::error::AppError;
+use crate::exchange::canonical_sha256;
+use crate::groth16;
+use crate::validation::ClaimType;
+use crate::AppState;
+
+const MAX_BATCH: usize = 256;
+
+#[derive(Deserialize)]
+struct BatchRequest {
+    proofs: Vec<ProofItem>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ProofItem {
+    proof: Value, // snarkjs JSON
+    public_signals: Vec<String>,
+    claim_type: ClaimType,
+    version: u32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Verdict {
+    Valid,
+    Invalid,
+    /// No verification key is loaded for the version
+    Unverifiable,
+}
+
+#[derive(Serialize)]
+struct ItemVerdict {
+    claim_type: ClaimType,
+    version: u32,
+    verdict: Verdict,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+    /// Hex sha256 of the item's canonical JSON, so the attestation covers
+    /// what was verified and not just the verdicts
+    item_sha256: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    verification_key_sha256: Option<String>,
+}
+
+#[derive(Serialize)]
+struct BatchResponse {
+    results: Vec<ItemVerdict>, // In request order
+    valid: usize,
+    results_sha256: String,
+    attestation: Attestation, // Over results_sha256
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/zk/verify-batch", post(verify_batch))
+}
+
+async fn verify_batch(
+    State(state): State<AppState>,
+    Json(request): Json<BatchRequest>,
+) -> Result<Json<BatchResponse>, AppError> {
+    if request.proofs.is_empty() || request.proofs.len() > MAX_BATCH {
+        return Err(AppError::bad_request(
+            "INVALID_BATCH",
+            format!("A batch verifies 1 to {} proofs", MAX_BATCH),
+        ));
+    }
+
+    let results = join_all(request.proofs.into_iter().map(|item| verify(&state, item)))
+        .await
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?;
+    let valid = results
+        .iter()
+        .filter(|r| r.verdict == Verdict::Valid)
+        .count();
+    info!(
+        "Batch proof verification: {} of {} proofs valid",
+        valid,
+        results.len()
+    );
+
+    let encoded = serde_json::to_vec(&results).map_err(|e| AppError::internal(e.to_string()))?;
+    let digest = Sha256::digest(&encoded);
+    let attestation = state
+        .attestation
+        .generate_with_user_data("", "zk_verify_batch", &digest)
+        .await?;
+
+    Ok(Json(BatchResponse {
+        results,
+        valid,
+        results_sha256: hex::encode(digest),
+        attestation,
+    }))
+}
+
+async fn verify(state: &AppState, item: ProofItem) -> Result<ItemVerdict, AppError> {
+    let item_sha256 = hex::encode(canonical_sha256(&item).map_err(AppError::internal)?);
+    let circuit = state
+        .zk_proof
+        .circuits()
+        .get(item.claim_type.as_str(), item.version);
+    let mut verdict = ItemVerdict {
+        claim_type: item.claim_type,
+        version: item.version,
+        verdict: Verdict::Unverifiable,
+        reason: None,
+        item_sha256,
+        verification_key_sha256: circuit.and_then(|c| c.verification_key_sha256.clone()),
+    };
+    let Some(circuit) = circuit else {
+        verdict.verdict = Verdict::Invalid;
+        verdict.reason = Some(format!(
+            "{} has no circuit version {}",
+            item.claim_type, item.version
+        ));
+        return Ok(verdict);
+    };
+    let Some(key) = circuit.verifying_key.clone() else {
+        verdict.reason = Some("No verification key is loaded for this version".to_string());
+        return Ok(verdict);
+    };
+    if item.public_signals.len() != circuit.public_signals {
+        verdict.verdict = Verdict::Invalid;
+        verdict.reason = Some(format!(
+            "{} public signals given; the circuit has {}",
+            item.public_signals.len(),
+            circuit.public_signals
+        ));
+        return Ok(verdict);
+    }
+
+    let checked = tokio::task::spawn_blocking(move || {
+        groth16::verify(&key, &item.proof, &item.public_signals)
+    })
+    .await
+    .map_err(|e| AppError::internal(format!("Verification task failed: {}", e)))?;
+    match checked {
+        Ok(true) => verdict.verdict = Verdict::Valid,
+        Ok(false) => {
+            verdict.verdict = Verdict::Invalid;
+            verdict.reason = Some("Proof does not verify".to_string());
+        }
+        Err(e) => {
+            verdict.verdict = Verdict::Invalid;
+            verdict.reason = Some(e);
+        }
+    }
+    Ok(verdict)
+}
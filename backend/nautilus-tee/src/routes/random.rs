@@ -0,0 +1,81 @@
+/**
+ * Randomness Routes
+ * Attested random draws for guardian selection, sampling and challenges
+ */
+
+use axum::{
+    extract::{Query, State},
+    response::Json,
+    routing::get,
+    Router,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::attestation::Attestation;
+use crate::error::AppError;
+use crate::randomness::{self, EntropySource};
+use crate::AppState;
+
+const DEFAULT_BYTES: usize = 32;
+const MAX_BYTES: usize = 1024;
+const MIN_NONCE_BYTES: usize = 16;
+const MAX_NONCE_BYTES: usize = 64;
+
+#[derive(Deserialize)]
+struct RandomQuery {
+    nonce: String, // Hex, chosen by the caller
+    #[serde(default)]
+    bytes: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct RandomResponse {
+    random: String, // Hex
+    nonce: String,  // Hex, as sent
+    source: EntropySource,
+    /// user_data is SHA-256("lumina-random-v1" || len(nonce) || nonce || random)
+    attestation: Attestation,
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/random", get(random))
+}
+
+async fn random(
+    State(state): State<AppState>,
+    Query(query): Query<RandomQuery>,
+) -> Result<Json<RandomResponse>, AppError> {
+    let nonce = hex::decode(query.nonce.trim_start_matches("0x"))
+        .ok()
+        .filter(|n| (MIN_NONCE_BYTES..=MAX_NONCE_BYTES).contains(&n.len()))
+        .ok_or_else(|| {
+            AppError::bad_request(
+                "INVALID_NONCE",
+                format!(
+                    "Nonce must be {} to {} hex-encoded bytes",
+                    MIN_NONCE_BYTES, MAX_NONCE_BYTES
+                ),
+            )
+        })?;
+    let len = query.bytes.unwrap_or(DEFAULT_BYTES);
+    if !(1..=MAX_BYTES).contains(&len) {
+        return Err(AppError::bad_request(
+            "INVALID_LENGTH",
+            format!("Between 1 and {} bytes may be drawn at once", MAX_BYTES),
+        ));
+    }
+
+    let mut random = vec![0u8; len];
+    let source = state.randomness.fill(&mut random);
+    let attestation = state
+        .attestation
+        .generate_with_user_data("", "random", &randomness::binding(&nonce, &random))
+        .await?;
+
+    Ok(Json(RandomResponse {
+        random: hex::encode(random),
+        nonce: hex::encode(nonce),
+        source,
+        attestation,
+    }))
+}
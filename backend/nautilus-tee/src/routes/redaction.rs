@@ -0,0 +1,121 @@
+/**
+ * Redaction Routes
+ * Setting and reading what a vault's releases mask
+ */
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    routing::{get, post},
+    Extension, Router,
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::auth::VerifiedSigner;
+use crate::error::AppError;
+use crate::redaction::{Detector, RedactionPolicy};
+use crate::validation::VaultId;
+use crate::AppState;
+
+#[derive(Deserialize)]
+struct RedactionRequest {
+    #[serde(default)]
+    detectors: Vec<Detector>,
+    #[serde(default)]
+    terms: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct RedactionResponse {
+    vault_id: String,
+    policy: Option<RedactionPolicy>, // None once cleared
+}
+
+/// Public view; the terms can name the very people being protected
+#[derive(Serialize)]
+struct PublicRedactionPolicy {
+    vault_id: String,
+    detectors: Vec<Detector>,
+    terms: usize,
+    updated_at: Option<u64>,
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/vault/:vault_id/redaction", get(redaction_policy))
+}
+
+pub fn signed_routes() -> Router<AppState> {
+    Router::new().route("/vault/:vault_id/redaction", post(set_redaction_policy))
+}
+
+async fn set_redaction_policy(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<RedactionRequest>,
+) -> Result<Json<RedactionResponse>, AppError> {
+    super::require_mutable(&state, &vault_id, &signer, "redaction.set")?;
+    let owners = super::vault_owners(&state, &vault_id)?;
+    let decision =
+        state
+            .policy
+            .evaluate_redaction_change(&signer.address, &owners, signer.delegate.is_some());
+    if !decision.allowed {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "REDACTION_DENIED",
+            decision.reason,
+        ));
+    }
+
+    let policy = state
+        .redaction
+        .set(&vault_id, &signer.address, request.detectors, request.terms)
+        .map_err(|e| AppError::bad_request("REDACTION_POLICY_REJECTED", e))?;
+    info!(
+        "Redaction policy for {} {} by {}",
+        vault_id,
+        if policy.is_some() { "set" } else { "cleared" },
+        signer.address
+    );
+
+    // Terms themselves stay out of the audit trail, which is exportable
+    state
+        .audit
+        .record(
+            &vault_id,
+            "redaction.updated",
+            &signer.address,
+            serde_json::json!({
+                "detectors": policy.as_ref().map(|p| p.detectors.clone()).unwrap_or_default(),
+                "terms": policy.as_ref().map_or(0, |p| p.terms.len()),
+            }),
+        )
+        .map_err(AppError::internal)?;
+
+    Ok(Json(RedactionResponse {
+        vault_id: vault_id.into(),
+        policy,
+    }))
+}
+
+async fn redaction_policy(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+) -> Result<Json<PublicRedactionPolicy>, AppError> {
+    let policy = state
+        .redaction
+        .policy(&vault_id)
+        .map_err(AppError::internal)?;
+    Ok(Json(PublicRedactionPolicy {
+        vault_id: vault_id.into(),
+        detectors: policy
+            .as_ref()
+            .map(|p| p.detectors.clone())
+            .unwrap_or_default(),
+        terms: policy.as_ref().map_or(0, |p| p.terms.len()),
+        updated_at: policy.map(|p| p.updated_at),
+    }))
+}
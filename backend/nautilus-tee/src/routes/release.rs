@@ -0,0 +1,199 @@
+/**
+ * Vault Release Routes
+ * Beneficiary registration and sealing unlocked content to beneficiaries
+ */
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    routing::{get, post},
+    Extension, Router,
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::attestation::Attestation;
+use crate::auth::VerifiedSigner;
+use crate::error::AppError;
+use crate::policy_bundles;
+use crate::release::{
+    BeneficiaryKey, BeneficiarySet, ReleaseRecord, SealedRelease, VaultCiphertext,
+};
+use crate::validation::VaultId;
+use crate::AppState;
+
+#[derive(Deserialize)]
+struct BeneficiariesRequest {
+    beneficiaries: Vec<BeneficiaryKey>,
+}
+
+#[derive(Deserialize)]
+struct ReleaseRequest {
+    content: VaultCiphertext,
+}
+
+#[derive(Serialize)]
+struct ReleaseResponse {
+    record: ReleaseRecord,
+    ciphertexts: Vec<SealedRelease>,
+    /// Installed policy bundle the release was decided under
+    #[serde(skip_serializing_if = "Option::is_none")]
+    policy_sha256: Option<String>,
+    attestation: Attestation, // Over the record's digest, bound to the bundle
+}
+
+/// Release records hold no secrets; beneficiaries check theirs here
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/vault/releases/:release_id", get(release_record))
+}
+
+pub fn signed_routes() -> Router<AppState> {
+    Router::new()
+        .route("/vault/:vault_id/beneficiaries", post(set_beneficiaries))
+        .route("/vault/:vault_id/release", post(release_vault))
+}
+
+async fn set_beneficiaries(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<BeneficiariesRequest>,
+) -> Result<Json<BeneficiarySet>, AppError> {
+    super::require_mutable(&state, &vault_id, &signer, "beneficiaries.set")?;
+    info!(
+        "Beneficiaries: vault_id={}, count={}",
+        vault_id,
+        request.beneficiaries.len()
+    );
+
+    let share_set = state
+        .secrets
+        .share_set(&vault_id)
+        .map_err(AppError::internal)?
+        .ok_or_else(no_shares)?;
+    state
+        .release
+        .set_beneficiaries(
+            &vault_id,
+            &signer.address,
+            &share_set.owner,
+            &request.beneficiaries,
+        )
+        .map(Json)
+        .map_err(|e| AppError::bad_request("BENEFICIARIES_REJECTED", e))
+}
+
+async fn release_vault(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<ReleaseRequest>,
+) -> Result<Json<ReleaseResponse>, AppError> {
+    super::require_mutable(&state, &vault_id, &signer, "vault.release")?;
+    super::require_unfrozen(&state, &vault_id)?;
+    let share_set = state
+        .secrets
+        .share_set(&vault_id)
+        .map_err(AppError::internal)?
+        .ok_or_else(no_shares)?;
+    let beneficiaries = state
+        .release
+        .beneficiaries(&vault_id)
+        .map_err(AppError::internal)?
+        .ok_or_else(|| {
+            AppError::new(
+                StatusCode::NOT_FOUND,
+                "NO_BENEFICIARIES",
+                "Vault has no registered beneficiaries",
+            )
+        })?;
+
+    let itemized = state
+        .items
+        .itemized(&vault_id)
+        .map_err(AppError::internal)?;
+    let redaction = state
+        .redaction
+        .policy(&vault_id)
+        .map_err(AppError::internal)?;
+    let unlocked = super::require_escalated_release(&state, &vault_id)?;
+    super::require_template_release(&state, &vault_id, true, unlocked)?;
+    super::require_jurisdiction_release(&state, &vault_id)?;
+    let key = state.secrets.reconstructed_key(&vault_id);
+    let decision = state.policy.evaluate_vault_release(
+        &share_set,
+        &beneficiaries,
+        &signer.address,
+        itemized,
+        key.is_some(),
+    );
+    let key = match key {
+        Some(key) if decision.allowed => key,
+        _ => {
+            return Err(AppError::new(
+                StatusCode::FORBIDDEN,
+                "RELEASE_DENIED",
+                decision.reason,
+            ))
+        }
+    };
+    let policy_sha256 = super::require_unlock_policy(&state, &signer, "vault_release", &vault_id)?;
+
+    let (record, ciphertexts) = state
+        .release
+        .release(
+            &beneficiaries,
+            &signer.address,
+            &key,
+            &request.content,
+            redaction.as_ref(),
+        )
+        .map_err(|e| AppError::new(StatusCode::UNPROCESSABLE_ENTITY, "RELEASE_FAILED", e))?;
+    info!(
+        "Vault release {}: vault_id={}, beneficiaries={}",
+        record.release_id,
+        vault_id,
+        record.recipients.len()
+    );
+
+    let digest = record.digest().map_err(AppError::internal)?;
+    let digest = policy_bundles::bind(&digest, policy_sha256.as_deref());
+    let attestation = state
+        .attestation
+        .generate_with_user_data(&vault_id, "vault_release", &digest)
+        .await?;
+
+    Ok(Json(ReleaseResponse {
+        record,
+        ciphertexts,
+        policy_sha256,
+        attestation,
+    }))
+}
+
+async fn release_record(
+    State(state): State<AppState>,
+    Path(release_id): Path<String>,
+) -> Result<Json<ReleaseRecord>, AppError> {
+    state
+        .release
+        .record(&release_id)
+        .map_err(AppError::internal)?
+        .map(Json)
+        .ok_or_else(|| {
+            AppError::new(
+                StatusCode::NOT_FOUND,
+                "RELEASE_NOT_FOUND",
+                "No such release",
+            )
+        })
+}
+
+fn no_shares() -> AppError {
+    AppError::new(
+        StatusCode::NOT_FOUND,
+        "SHARES_NOT_FOUND",
+        "No guardian shares for this vault",
+    )
+}
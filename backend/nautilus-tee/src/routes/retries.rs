@@ -0,0 +1,33 @@
+/**
+ * Retry Routes
+ * Retry policies and counts per dependency, for admins telling a flaky
+ * relay from a down one
+ */
+
+use axum::{extract::State, response::Json, routing::post, Extension, Router};
+use serde::Serialize;
+
+use crate::auth::VerifiedSigner;
+use crate::error::AppError;
+use crate::retry::{self, RetryStats};
+use crate::routes::require_admin;
+use crate::AppState;
+
+#[derive(Serialize)]
+struct RetriesResponse {
+    dependencies: Vec<RetryStats>,
+}
+
+pub fn signed_routes() -> Router<AppState> {
+    Router::new().route("/admin/retries", post(list))
+}
+
+async fn list(
+    State(state): State<AppState>,
+    Extension(signer): Extension<VerifiedSigner>,
+) -> Result<Json<RetriesResponse>, AppError> {
+    require_admin(&state, &signer)?;
+    Ok(Json(RetriesResponse {
+        dependencies: retry::stats(),
+    }))
+}
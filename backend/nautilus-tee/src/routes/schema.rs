@@ -0,0 +1,18 @@
+/**
+ * Schema Routes
+ * The response schema versions each route serves, and which routes are
+ * deprecated, for SDK capability negotiation
+ */
+
+use axum::{extract::State, response::Json, routing::get, Router};
+
+use crate::schema::SchemaListing;
+use crate::AppState;
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/schemas", get(schemas))
+}
+
+async fn schemas(State(state): State<AppState>) -> Json<SchemaListing> {
+    Json(state.schemas.listing())
+}
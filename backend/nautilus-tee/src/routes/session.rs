@@ -0,0 +1,76 @@
+/**
+ * Session Routes
+ * Attested ECDH session establishment, signed so each signer's share of
+ * sessions is bounded (see session)
+ */
+
+use axum::{extract::State, response::Json, routing::post, Extension, Router};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::attestation::Attestation;
+use crate::auth::VerifiedSigner;
+use crate::crypto::KeyExchange;
+use crate::error::AppError;
+use crate::AppState;
+
+#[derive(Deserialize)]
+struct EstablishRequest {
+    public_key: String, // Base64 client ephemeral X25519 key
+    #[serde(default)]
+    key_exchange: KeyExchange,
+    kem_public_key: Option<String>, // Base64 ML-KEM-768 key for hybrid mode
+}
+
+#[derive(Serialize)]
+struct EstablishResponse {
+    session_id: String,
+    public_key: String, // Base64 enclave ephemeral X25519 key, bound in the attestation
+    key_exchange: KeyExchange,
+    /// Base64 ML-KEM ciphertext; the attestation binds public_key || kem_ciphertext
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kem_ciphertext: Option<String>,
+    expires_in_secs: u64,
+    attestation: Attestation,
+}
+
+pub fn signed_routes() -> Router<AppState> {
+    Router::new().route("/session/establish", post(establish))
+}
+
+async fn establish(
+    State(state): State<AppState>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<EstablishRequest>,
+) -> Result<Json<EstablishResponse>, AppError> {
+    let established = state.sessions.establish(
+        &signer.address,
+        &request.public_key,
+        request.key_exchange,
+        request.kem_public_key.as_deref(),
+    )?;
+    info!(
+        "Session established: {} by {}",
+        established.session_id, signer.address
+    );
+
+    let mut bound_key = established.enclave_public_key.as_bytes().to_vec();
+    if let Some(ciphertext) = &established.kem_ciphertext {
+        bound_key.extend_from_slice(ciphertext);
+    }
+    let attestation = state
+        .attestation
+        .generate_for_key("session_establish", &bound_key)
+        .await?;
+
+    Ok(Json(EstablishResponse {
+        session_id: established.session_id,
+        public_key: STANDARD.encode(established.enclave_public_key.as_bytes()),
+        key_exchange: established.key_exchange,
+        kem_ciphertext: established.kem_ciphertext.map(|c| STANDARD.encode(c)),
+        expires_in_secs: established.expires_in.as_secs(),
+        attestation,
+    }))
+}
@@ -0,0 +1,227 @@
+/**
+ * Share Grant Routes
+ * Creating and revoking share links, and redeeming them for a capability
+ *
+ * Redemption is signed by the redeeming address, which the capability is
+ * minted for. A required proof is checked here against the pinned
+ * verification key of the version the grant names.
+ */
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    routing::post,
+    Extension, Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::info;
+
+use crate::auth::VerifiedSigner;
+use crate::capability::CapabilityClaims;
+use crate::error::AppError;
+use crate::share_grants::{GrantTerms, ProofRequirement, ShareGrant};
+use crate::validation::VaultId;
+use crate::AppState;
+
+#[derive(Deserialize)]
+struct CreateRequest {
+    operations: Vec<String>,
+    link_ttl_secs: u64,
+    capability_ttl_secs: u64,
+    password: Option<String>,
+    proof: Option<ProofRequirement>,
+}
+
+#[derive(Serialize)]
+struct CreateResponse {
+    link: String,
+    grant: GrantSummary,
+}
+
+/// A grant without its password hash
+#[derive(Serialize)]
+struct GrantSummary {
+    grant_id: String,
+    vault_id: String,
+    operations: Vec<String>,
+    capability_ttl_secs: u64,
+    created_at: u64,
+    expires_at: u64,
+    password_required: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    proof: Option<ProofRequirement>,
+    redeemed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    revoked_at: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct RedeemRequest {
+    link: String,
+    password: Option<String>,
+    /// snarkjs proof and its public signals, when the grant requires one
+    proof: Option<Value>,
+    #[serde(default)]
+    public_signals: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct RedeemResponse {
+    token: String,
+    claims: CapabilityClaims,
+}
+
+pub fn signed_routes() -> Router<AppState> {
+    Router::new()
+        .route("/vault/:vault_id/shares", post(create))
+        .route("/vault/:vault_id/shares/:grant_id/revoke", post(revoke))
+        .route("/shares/redeem", post(redeem))
+}
+
+async fn create(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<CreateRequest>,
+) -> Result<Json<CreateResponse>, AppError> {
+    super::require_mutable(&state, &vault_id, &signer, "shares.create")?;
+    let owners = super::vault_owners(&state, &vault_id)?;
+    let decision = state
+        .policy
+        .evaluate_capability_grant(&signer.address, &owners);
+    if !decision.allowed {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "NOT_VAULT_OWNER",
+            decision.reason,
+        ));
+    }
+
+    let terms = GrantTerms {
+        operations: request.operations,
+        link_ttl_secs: request.link_ttl_secs,
+        capability_ttl_secs: request.capability_ttl_secs,
+        password: request.password,
+        proof: request.proof,
+    };
+    let (link, grant) = state
+        .share_grants
+        .create(&vault_id, &signer.address, terms)
+        .map_err(|e| AppError::bad_request("SHARE_REJECTED", e))?;
+    info!(
+        "Share grant {} created: vault_id={}, operations={:?}, expires_at={}",
+        grant.grant_id, vault_id, grant.operations, grant.expires_at
+    );
+
+    Ok(Json(CreateResponse {
+        link,
+        grant: GrantSummary::from(grant),
+    }))
+}
+
+async fn revoke(
+    State(state): State<AppState>,
+    Path((vault_id, grant_id)): Path<(VaultId, String)>,
+    Extension(signer): Extension<VerifiedSigner>,
+) -> Result<Json<GrantSummary>, AppError> {
+    let grant = state
+        .share_grants
+        .revoke(&vault_id, &grant_id, &signer.address)
+        .map_err(|e| AppError::new(StatusCode::FORBIDDEN, "REVOCATION_REJECTED", e))?;
+    info!(
+        "Share grant {} revoked: vault_id={}, by={}",
+        grant_id, vault_id, signer.address
+    );
+
+    Ok(Json(GrantSummary::from(grant)))
+}
+
+async fn redeem(
+    State(state): State<AppState>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<RedeemRequest>,
+) -> Result<Json<RedeemResponse>, AppError> {
+    let grant = state
+        .share_grants
+        .open(&request.link)
+        .map_err(|e| AppError::new(StatusCode::FORBIDDEN, "SHARE_LINK_INVALID", e))?;
+    super::require_unfrozen(&state, &grant.vault_id)?;
+
+    let password_ok = state
+        .share_grants
+        .check_password(&grant, request.password.as_deref());
+    let proof_ok = match (&grant.proof, request.proof) {
+        (None, _) => true,
+        (Some(_), None) => false,
+        (Some(required), Some(proof)) => {
+            proof_satisfies(&state, required, proof, request.public_signals).await?
+        }
+    };
+    let unlocked = state
+        .escalation
+        .unlocked(&grant.vault_id)
+        .map_err(AppError::internal)?;
+    let decision = state
+        .policy
+        .evaluate_share_redemption(unlocked, password_ok, proof_ok);
+    if !decision.allowed {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "SHARE_DENIED",
+            decision.reason,
+        ));
+    }
+    // A capability isn't scoped to items, so it counts as a whole release
+    super::require_template_release(&state, &grant.vault_id, true, unlocked)?;
+    super::require_jurisdiction_release(&state, &grant.vault_id)?;
+
+    let (token, claims) = state
+        .share_grants
+        .redeem(&grant.grant_id, &signer.address)
+        .map_err(|e| AppError::new(StatusCode::CONFLICT, "SHARE_NOT_REDEEMED", e))?;
+    info!(
+        "Share grant {} redeemed: vault_id={}, holder={}, capability={}",
+        grant.grant_id, grant.vault_id, claims.holder, claims.token_id
+    );
+
+    Ok(Json(RedeemResponse { token, claims }))
+}
+
+/// Whether the proof verifies with exactly the required public signals
+async fn proof_satisfies(
+    state: &AppState,
+    required: &ProofRequirement,
+    proof: Value,
+    public_signals: Vec<String>,
+) -> Result<bool, AppError> {
+    if public_signals != required.public_signals {
+        return Ok(false);
+    }
+    super::verify_pinned_proof(
+        state,
+        required.claim_type,
+        required.version,
+        proof,
+        public_signals,
+    )
+    .await
+}
+
+impl From<ShareGrant> for GrantSummary {
+    fn from(grant: ShareGrant) -> Self {
+        Self {
+            grant_id: grant.grant_id,
+            vault_id: grant.vault_id,
+            operations: grant.operations,
+            capability_ttl_secs: grant.capability_ttl_secs,
+            created_at: grant.created_at,
+            expires_at: grant.expires_at,
+            password_required: grant.password.is_some(),
+            proof: grant.proof,
+            redeemed: grant.redemption.is_some(),
+            revoked_at: grant.revoked_at,
+        }
+    }
+}
@@ -0,0 +1,105 @@
+/**
+ * Signing Routes
+ * Enclave-witnessed signatures over caller-supplied digests
+ *
+ * The identity key signs "lumina-sign-v1" || len(purpose) || purpose ||
+ * len(vault_id) || vault_id || digest, so a signature for one purpose or
+ * vault can never be replayed as another. Move contracts verify it with
+ * ed25519::ed25519_verify against the pinned identity key.
+ */
+
+use axum::{extract::State, http::StatusCode, response::Json, routing::post, Extension, Router};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+use crate::attestation::Attestation;
+use crate::auth::VerifiedSigner;
+use crate::error::AppError;
+use crate::validation::VaultId;
+use crate::AppState;
+
+const DOMAIN: &[u8] = b"lumina-sign-v1";
+
+#[derive(Deserialize)]
+struct SignRequest {
+    vault_id: VaultId,
+    purpose: String,
+    digest: String, // Hex 32-byte digest
+}
+
+#[derive(Serialize)]
+struct SignResponse {
+    message: String,    // Hex bytes that were signed
+    signature: String,  // Base64 Ed25519 signature
+    public_key: String, // Base64 enclave identity key
+    key_id: String,
+    attestation: Attestation,
+}
+
+pub fn signed_routes() -> Router<AppState> {
+    Router::new().route("/sign", post(sign))
+}
+
+async fn sign(
+    State(state): State<AppState>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<SignRequest>,
+) -> Result<Json<SignResponse>, AppError> {
+    info!(
+        "Signing request: vault_id={}, purpose={}, signer={}",
+        request.vault_id, request.purpose, signer.address
+    );
+
+    let digest = hex::decode(request.digest.trim_start_matches("0x"))
+        .ok()
+        .filter(|d| d.len() == 32)
+        .ok_or_else(|| {
+            AppError::bad_request("INVALID_DIGEST", "Digest must be 32 hex-encoded bytes")
+        })?;
+
+    let registered = state
+        .vault_templates
+        .registration(&request.vault_id)
+        .map_err(AppError::internal)?
+        .is_some();
+    let owners = super::vault_owners(&state, &request.vault_id)?;
+    let decision =
+        state
+            .policy
+            .evaluate_signing(&request.purpose, &signer.address, registered, &owners);
+    if !decision.allowed {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "SIGNING_DENIED",
+            decision.reason,
+        ));
+    }
+
+    let message = signing_message(&request.purpose, &request.vault_id, &digest);
+    let signature = state.identity.sign(&message);
+    let attestation = state
+        .attestation
+        .generate_with_user_data(&request.vault_id, "sign", &Sha256::digest(&message))
+        .await?;
+
+    Ok(Json(SignResponse {
+        message: hex::encode(&message),
+        signature: STANDARD.encode(signature.to_bytes()),
+        public_key: STANDARD.encode(state.identity.public_key().as_bytes()),
+        key_id: state.identity.key_id(),
+        attestation,
+    }))
+}
+
+fn signing_message(purpose: &str, vault_id: &str, digest: &[u8]) -> Vec<u8> {
+    let mut message = DOMAIN.to_vec();
+    for field in [purpose.as_bytes(), vault_id.as_bytes()] {
+        message.extend_from_slice(&(field.len() as u64).to_be_bytes());
+        message.extend_from_slice(field);
+    }
+    message.extend_from_slice(digest);
+    message
+}
@@ -0,0 +1,36 @@
+/**
+ * Task Routes
+ * Scheduled background tasks, soonest first, for admins checking that
+ * sweeps and pollers keep up
+ */
+
+use axum::{extract::State, response::Json, routing::post, Extension, Router};
+use serde::Serialize;
+
+use crate::auth::VerifiedSigner;
+use crate::error::AppError;
+use crate::routes::require_admin;
+use crate::tasks::TaskStatus;
+use crate::AppState;
+
+#[derive(Serialize)]
+struct TasksResponse {
+    tasks: Vec<TaskStatus>,
+    overdue: usize,
+}
+
+pub fn signed_routes() -> Router<AppState> {
+    Router::new().route("/admin/tasks", post(list))
+}
+
+async fn list(
+    State(state): State<AppState>,
+    Extension(signer): Extension<VerifiedSigner>,
+) -> Result<Json<TasksResponse>, AppError> {
+    require_admin(&state, &signer)?;
+    let tasks = state.tasks.list().map_err(AppError::internal)?;
+    Ok(Json(TasksResponse {
+        overdue: tasks.iter().filter(|t| t.overdue).count(),
+        tasks,
+    }))
+}
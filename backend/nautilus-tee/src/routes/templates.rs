@@ -0,0 +1,324 @@
+/**
+ * Template Routes
+ * Biometric enrollment, refresh policies and admin backup/restore of templates
+ */
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+    routing::post,
+    Extension, Router,
+};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::attestation::Attestation;
+use crate::auth::VerifiedSigner;
+use crate::embedding::{self, EmbeddingSubmission};
+use crate::error::AppError;
+use crate::pagination::{self, PageQuery};
+use crate::quality;
+use crate::routes::require_admin;
+use crate::templates::refresh::RefreshPolicy;
+use crate::templates::{TemplateBackup, TemplateInfo, VersionReport, BACKUP_OPERATION};
+use crate::validation::{BiometricMethod, VaultId};
+use crate::AppState;
+
+#[derive(Deserialize)]
+struct EnrollRequest {
+    vault_id: VaultId,
+    method: BiometricMethod,
+    /// Exactly one of a raw sample or a client-side embedding
+    biometric_data: Option<String>, // Base64
+    embedding: Option<EmbeddingSubmission>,
+    label: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct LabelRequest {
+    label: Option<String>, // Cleared when omitted
+}
+
+#[derive(Deserialize)]
+struct RefreshPolicyRequest {
+    refresh_months: Option<u32>, // Policy cleared when omitted
+    warn_days: Option<u32>,
+    stale_confidence_cap: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct RefreshPolicyResponse {
+    policy: Option<RefreshPolicy>,
+}
+
+#[derive(Serialize)]
+struct TemplateListResponse {
+    templates: Vec<TemplateInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_cursor: Option<String>,
+}
+
+#[derive(Serialize)]
+struct DeleteResponse {
+    deleted: String,
+}
+
+#[derive(Deserialize)]
+struct ExportRequest {
+    vault_id: Option<VaultId>, // All templates when omitted
+}
+
+#[derive(Serialize, Deserialize)]
+struct BackupBundle {
+    backup: TemplateBackup,
+    attestation: Attestation,
+}
+
+#[derive(Serialize)]
+struct ImportResponse {
+    imported: Vec<TemplateInfo>,
+}
+
+pub fn signed_routes() -> Router<AppState> {
+    Router::new()
+        .route("/biometric/enroll", post(enroll))
+        .route("/vault/:vault_id/templates", post(list_templates))
+        .route(
+            "/vault/:vault_id/templates/refresh-policy",
+            post(set_refresh_policy),
+        )
+        .route(
+            "/vault/:vault_id/templates/:method/:template_id/label",
+            post(label_template),
+        )
+        .route(
+            "/vault/:vault_id/templates/:method/:template_id/delete",
+            post(delete_template),
+        )
+        .route("/admin/templates/export", post(export_templates))
+        .route("/admin/templates/import", post(import_templates))
+        .route("/admin/templates/versions", post(template_versions))
+}
+
+async fn enroll(
+    State(state): State<AppState>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<EnrollRequest>,
+) -> Result<Json<TemplateInfo>, AppError> {
+    super::require_mutable(&state, &request.vault_id, &signer, "templates.enroll")?;
+    info!(
+        "Biometric enrollment: vault_id={}, method={}",
+        request.vault_id, request.method
+    );
+
+    let (features, model) = match (&request.biometric_data, &request.embedding) {
+        (Some(data), None) => {
+            let sample = STANDARD.decode(data).map_err(|_| {
+                AppError::bad_request("INVALID_BIOMETRIC_DATA", "Biometric data must be base64")
+            })?;
+            let method = request.method.as_str();
+            quality::require_quality(&sample, method)?;
+            if request.method == BiometricMethod::Voice {
+                // So this recording can't later be replayed to verify
+                state
+                    .voice
+                    .remember(&request.vault_id, &sample)
+                    .map_err(AppError::internal)?;
+            }
+            let features = state.biometric.extract_features(&sample, method)?;
+            (features, state.biometric.feature_model(method))
+        }
+        (None, Some(submission)) => {
+            let embedding = submission
+                .decode()
+                .map_err(|e| AppError::bad_request("INVALID_EMBEDDING", e))?;
+            (
+                embedding::to_f32le(&embedding.vector),
+                Some(embedding.model),
+            )
+        }
+        _ => {
+            return Err(AppError::bad_request(
+                "INVALID_ENROLLMENT",
+                "Provide exactly one of biometric_data or embedding",
+            ))
+        }
+    };
+
+    state
+        .templates
+        .enroll(
+            &request.vault_id,
+            &signer.address,
+            request.method.as_str(),
+            &features,
+            model,
+            request.label,
+        )
+        .map(Json)
+        .map_err(|e| AppError::bad_request("ENROLLMENT_FAILED", e))
+}
+
+async fn list_templates(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Query(query): Query<PageQuery>,
+    Extension(signer): Extension<VerifiedSigner>,
+) -> Result<Json<TemplateListResponse>, AppError> {
+    let templates = state
+        .templates
+        .list(&vault_id, &signer.address)
+        .map_err(|e| AppError::new(StatusCode::FORBIDDEN, "NOT_TEMPLATE_OWNER", e))?;
+    let page = pagination::paginate(templates, &query)
+        .map_err(|e| AppError::bad_request("INVALID_PAGE", e))?;
+    Ok(Json(TemplateListResponse {
+        templates: page.items,
+        next_cursor: page.next_cursor,
+    }))
+}
+
+async fn set_refresh_policy(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<RefreshPolicyRequest>,
+) -> Result<Json<RefreshPolicyResponse>, AppError> {
+    super::require_mutable(&state, &vault_id, &signer, "templates.refresh_policy")?;
+    let policy = match request.refresh_months {
+        Some(months) => state
+            .templates
+            .set_refresh_policy(
+                &vault_id,
+                &signer.address,
+                months,
+                request.warn_days,
+                request.stale_confidence_cap,
+            )
+            .map(Some),
+        None => state
+            .templates
+            .clear_refresh_policy(&vault_id, &signer.address)
+            .map(|_| None),
+    }
+    .map_err(|e| AppError::bad_request("REFRESH_POLICY_FAILED", e))?;
+    info!(
+        "Template refresh policy for {} set by {}: {:?} months",
+        vault_id, signer.address, request.refresh_months
+    );
+
+    Ok(Json(RefreshPolicyResponse { policy }))
+}
+
+async fn label_template(
+    State(state): State<AppState>,
+    Path((vault_id, method, template_id)): Path<(VaultId, String, String)>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<LabelRequest>,
+) -> Result<Json<TemplateInfo>, AppError> {
+    super::require_mutable(&state, &vault_id, &signer, "templates.label")?;
+    state
+        .templates
+        .set_label(
+            &vault_id,
+            &method,
+            &template_id,
+            &signer.address,
+            request.label,
+        )
+        .map(Json)
+        .map_err(|e| AppError::bad_request("TEMPLATE_UPDATE_FAILED", e))
+}
+
+async fn delete_template(
+    State(state): State<AppState>,
+    Path((vault_id, method, template_id)): Path<(VaultId, String, String)>,
+    Extension(signer): Extension<VerifiedSigner>,
+) -> Result<Json<DeleteResponse>, AppError> {
+    super::require_mutable(&state, &vault_id, &signer, "templates.delete")?;
+    state
+        .templates
+        .delete(&vault_id, &method, &template_id, &signer.address)
+        .map_err(|e| AppError::bad_request("TEMPLATE_DELETE_FAILED", e))?;
+    info!(
+        "Template deleted: vault_id={}, method={}, template_id={}",
+        vault_id, method, template_id
+    );
+    Ok(Json(DeleteResponse {
+        deleted: template_id,
+    }))
+}
+
+async fn export_templates(
+    State(state): State<AppState>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<ExportRequest>,
+) -> Result<Json<BackupBundle>, AppError> {
+    require_admin(&state, &signer)?;
+
+    let measurement = state.attestation.get_pcr_measurements()?.pcr0;
+    let backup = state
+        .templates
+        .export(
+            request.vault_id.as_deref(),
+            &measurement,
+            &state.identity.key_id(),
+        )
+        .map_err(|e| AppError::bad_request("TEMPLATE_EXPORT_FAILED", e))?;
+    info!(
+        "Template backup exported by {}: {} templates",
+        signer.address,
+        backup.templates.len()
+    );
+
+    let digest = backup.digest().map_err(AppError::internal)?;
+    let attestation = state
+        .attestation
+        .generate_with_user_data("", BACKUP_OPERATION, &digest)
+        .await?;
+
+    Ok(Json(BackupBundle {
+        backup,
+        attestation,
+    }))
+}
+
+async fn import_templates(
+    State(state): State<AppState>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(bundle): Json<BackupBundle>,
+) -> Result<Json<ImportResponse>, AppError> {
+    require_admin(&state, &signer)?;
+
+    let source = state
+        .attestation
+        .verify(&bundle.attestation)
+        .map_err(|e| AppError::bad_request("BACKUP_ATTESTATION_INVALID", e.to_string()))?;
+    let imported = state
+        .templates
+        .import(&bundle.backup, &source)
+        .map_err(|e| AppError::bad_request("TEMPLATE_IMPORT_REJECTED", e))?;
+    info!(
+        "Template backup imported by {}: {} templates from {}",
+        signer.address,
+        imported.len(),
+        bundle.backup.source_identity
+    );
+
+    Ok(Json(ImportResponse { imported }))
+}
+
+async fn template_versions(
+    State(state): State<AppState>,
+    Extension(signer): Extension<VerifiedSigner>,
+) -> Result<Json<VersionReport>, AppError> {
+    require_admin(&state, &signer)?;
+
+    state
+        .templates
+        .version_report()
+        .map(Json)
+        .map_err(AppError::internal)
+}
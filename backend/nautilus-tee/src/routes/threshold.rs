@@ -0,0 +1,244 @@
+/**
+ * Threshold Decryption Routes
+ * Key dealing, decryption sessions, guardian partial decryptions and
+ * verification of releases
+ */
+
+use axum::{
+    extract::{Path, State},
+    response::Json,
+    routing::post,
+    Extension, Router,
+};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::attestation::Attestation;
+use crate::auth::VerifiedSigner;
+use crate::error::AppError;
+use crate::policy_bundles;
+use crate::secrets::GuardianKey;
+use crate::threshold::{
+    DecryptionProof, PartialDecryption, SealedKeyShare, SessionStatus, ThresholdCiphertext,
+    ThresholdKeySet,
+};
+use crate::validation::VaultId;
+use crate::AppState;
+
+#[derive(Deserialize)]
+struct SetupRequest {
+    threshold: u8,
+    guardians: Vec<GuardianKey>,
+}
+
+#[derive(Serialize)]
+struct SetupResponse {
+    key_set: ThresholdKeySet,
+    shares: Vec<SealedKeyShare>,
+    attestation: Attestation,
+}
+
+#[derive(Deserialize)]
+struct OpenSessionRequest {
+    ciphertext: ThresholdCiphertext,
+}
+
+#[derive(Serialize)]
+struct ReleaseResponse {
+    plaintext: String, // Base64
+    proof: DecryptionProof,
+    /// Installed policy bundle the release was decided under
+    #[serde(skip_serializing_if = "Option::is_none")]
+    policy_sha256: Option<String>,
+    attestation: Attestation, // Over the proof's digest, bound to the bundle
+}
+
+#[derive(Deserialize)]
+struct VerifyReleaseRequest {
+    proof: DecryptionProof,
+    plaintext: String, // Base64
+}
+
+#[derive(Serialize)]
+struct VerifyReleaseResponse {
+    valid: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+}
+
+/// Checking a release needs no signature; anyone settling a dispute may
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/threshold/releases/verify", post(verify_release))
+}
+
+/// Every other threshold operation is signed
+pub fn signed_routes() -> Router<AppState> {
+    Router::new()
+        .route("/vault/:vault_id/threshold/keys", post(setup_key))
+        .route("/vault/:vault_id/threshold/sessions", post(open_session))
+        .route(
+            "/vault/:vault_id/threshold/sessions/:session_id/partials",
+            post(submit_partial),
+        )
+        .route(
+            "/vault/:vault_id/threshold/sessions/:session_id/release",
+            post(release),
+        )
+}
+
+async fn setup_key(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<SetupRequest>,
+) -> Result<Json<SetupResponse>, AppError> {
+    super::require_registered_owner(&state, &vault_id, &signer, "dealing its threshold key")?;
+    super::require_mutable(&state, &vault_id, &signer, "threshold.setup")?;
+    info!(
+        "Threshold key setup: vault_id={}, guardians={}, threshold={}",
+        vault_id,
+        request.guardians.len(),
+        request.threshold
+    );
+
+    let (key_set, shares) = state
+        .threshold
+        .setup(
+            &vault_id,
+            &signer.address,
+            request.threshold,
+            &request.guardians,
+        )
+        .map_err(|e| AppError::bad_request("THRESHOLD_SETUP_FAILED", e))?;
+
+    let attestation = state
+        .attestation
+        .generate(&vault_id, "threshold_key_setup")
+        .await?;
+
+    Ok(Json(SetupResponse {
+        key_set,
+        shares,
+        attestation,
+    }))
+}
+
+async fn open_session(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<OpenSessionRequest>,
+) -> Result<Json<SessionStatus>, AppError> {
+    super::require_mutable(&state, &vault_id, &signer, "threshold.session")?;
+    state
+        .threshold
+        .open_session(&vault_id, &signer.address, request.ciphertext)
+        .map(Json)
+        .map_err(|e| AppError::bad_request("SESSION_REJECTED", e))
+}
+
+async fn submit_partial(
+    State(state): State<AppState>,
+    Path((vault_id, session_id)): Path<(VaultId, String)>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(partial): Json<PartialDecryption>,
+) -> Result<Json<SessionStatus>, AppError> {
+    super::require_mutable(&state, &vault_id, &signer, "threshold.partial")?;
+    info!(
+        "Partial decryption submitted: vault_id={}, session={}, guardian={}",
+        vault_id, session_id, signer.address
+    );
+
+    state
+        .threshold
+        .submit_partial(
+            &vault_id,
+            &session_id,
+            &signer.address,
+            &partial,
+            &state.policy,
+        )
+        .map(Json)
+        .map_err(|e| AppError::bad_request("PARTIAL_REJECTED", e))
+}
+
+async fn release(
+    State(state): State<AppState>,
+    Path((vault_id, session_id)): Path<(VaultId, String)>,
+    Extension(signer): Extension<VerifiedSigner>,
+) -> Result<Json<ReleaseResponse>, AppError> {
+    super::require_mutable(&state, &vault_id, &signer, "threshold.release")?;
+    super::require_unfrozen(&state, &vault_id)?;
+    let unlocked = super::require_escalated_release(&state, &vault_id)?;
+    super::require_template_release(&state, &vault_id, true, unlocked)?;
+    super::require_jurisdiction_release(&state, &vault_id)?;
+    let itemized = state
+        .items
+        .itemized(&vault_id)
+        .map_err(AppError::internal)?;
+    let decision = state.policy.evaluate_full_release(itemized);
+    if !decision.allowed {
+        return Err(AppError::new(
+            axum::http::StatusCode::FORBIDDEN,
+            "RELEASE_DENIED",
+            decision.reason,
+        ));
+    }
+    let redacted = state
+        .redaction
+        .policy(&vault_id)
+        .map_err(AppError::internal)?
+        .is_some();
+    let decision = state.policy.evaluate_unredacted_release(redacted);
+    if !decision.allowed {
+        return Err(AppError::new(
+            axum::http::StatusCode::FORBIDDEN,
+            "RELEASE_DENIED",
+            decision.reason,
+        ));
+    }
+    let policy_sha256 =
+        super::require_unlock_policy(&state, &signer, "threshold_release", &vault_id)?;
+
+    let (plaintext, proof) = state
+        .threshold
+        .release(&vault_id, &session_id, &signer.address, &state.policy)
+        .map_err(|e| AppError::new(axum::http::StatusCode::FORBIDDEN, "RELEASE_DENIED", e))?;
+
+    let digest = proof.digest().map_err(AppError::internal)?;
+    let digest = policy_bundles::bind(&digest, policy_sha256.as_deref());
+    let attestation = state
+        .attestation
+        .generate_with_user_data(&vault_id, "threshold_release", &digest)
+        .await?;
+
+    Ok(Json(ReleaseResponse {
+        plaintext: STANDARD.encode(plaintext.as_slice()),
+        proof,
+        policy_sha256,
+        attestation,
+    }))
+}
+
+async fn verify_release(
+    State(state): State<AppState>,
+    Json(request): Json<VerifyReleaseRequest>,
+) -> Result<Json<VerifyReleaseResponse>, AppError> {
+    let plaintext = STANDARD
+        .decode(&request.plaintext)
+        .map_err(|_| AppError::bad_request("INVALID_PLAINTEXT", "Plaintext must be base64"))?;
+
+    let result = state.threshold.verify_release(&request.proof, &plaintext);
+    info!(
+        "Release verification for {}: {}",
+        request.proof.vault_id,
+        if result.is_ok() { "valid" } else { "invalid" }
+    );
+
+    Ok(Json(VerifyReleaseResponse {
+        valid: result.is_ok(),
+        reason: result.err(),
+    }))
+}
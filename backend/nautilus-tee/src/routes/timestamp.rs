@@ -0,0 +1,79 @@
+/**
+ * Timestamp Routes
+ * Enclave-signed proof that a digest existed by a given time
+ */
+
+use axum::{extract::State, http::StatusCode, response::Json, routing::post, Router};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+use crate::attestation::Attestation;
+use crate::error::AppError;
+use crate::timestamp::{self, TimestampToken};
+use crate::AppState;
+
+#[derive(Deserialize)]
+struct TimestampRequest {
+    digest: String, // Hex
+    #[serde(default = "default_hash_algorithm")]
+    hash_algorithm: String,
+    nonce: Option<String>, // Hex
+}
+
+#[derive(Serialize)]
+struct TimestampResponse {
+    token: TimestampToken,
+    public_key: String, // Base64 enclave identity key
+    /// user_data is SHA-256 of the signed message
+    attestation: Attestation,
+}
+
+fn default_hash_algorithm() -> String {
+    "sha256".to_string()
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/timestamp", post(issue))
+}
+
+async fn issue(
+    State(state): State<AppState>,
+    Json(request): Json<TimestampRequest>,
+) -> Result<Json<TimestampResponse>, AppError> {
+    let digest = hex::decode(request.digest.trim_start_matches("0x"))
+        .map_err(|_| AppError::bad_request("INVALID_DIGEST", "Digest must be hex-encoded"))?;
+    let nonce = request
+        .nonce
+        .as_deref()
+        .map(|n| hex::decode(n.trim_start_matches("0x")))
+        .transpose()
+        .map_err(|_| AppError::bad_request("INVALID_NONCE", "Nonce must be hex-encoded"))?;
+
+    let now = state
+        .clock
+        .now()
+        .map_err(|e| AppError::new(StatusCode::SERVICE_UNAVAILABLE, "CLOCK_UNRELIABLE", e))?;
+    let token = state
+        .timestamps
+        .issue(now.as_secs(), &request.hash_algorithm, &digest, nonce.as_deref())
+        .map_err(|e| AppError::bad_request("TIMESTAMP_REJECTED", e))?;
+    info!(
+        "Timestamp {} issued at {}",
+        token.tst_info.serial_number, token.tst_info.gen_time
+    );
+
+    let message = timestamp::signed_message(&token.tst_info).map_err(AppError::internal)?;
+    let attestation = state
+        .attestation
+        .generate_with_user_data("", "timestamp", &Sha256::digest(&message))
+        .await?;
+
+    Ok(Json(TimestampResponse {
+        token,
+        public_key: STANDARD.encode(state.identity.public_key().as_bytes()),
+        attestation,
+    }))
+}
@@ -0,0 +1,98 @@
+/**
+ * Vault Index Routes
+ * Labelling and tagging vaults, and listing the caller's vaults by tag
+ *
+ * Both are signed. GET /vaults carries the usual signed body naming
+ * user_address, and the signature covers the query. Listings only
+ * include vaults the caller still owns.
+ */
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+    routing::{get, post},
+    Extension, Router,
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::auth::VerifiedSigner;
+use crate::error::AppError;
+use crate::validation::VaultId;
+use crate::vault_index::IndexedVault;
+use crate::AppState;
+
+#[derive(Deserialize)]
+struct IndexRequest {
+    label: String,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct SearchQuery {
+    tag: Option<String>,
+}
+
+#[derive(Serialize)]
+struct SearchResponse {
+    vaults: Vec<IndexedVault>,
+}
+
+pub fn signed_routes() -> Router<AppState> {
+    Router::new()
+        .route("/vault/:vault_id/index", post(index))
+        .route("/vaults", get(search))
+}
+
+async fn index(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<IndexRequest>,
+) -> Result<Json<IndexedVault>, AppError> {
+    let owners = super::vault_owners(&state, &vault_id)?;
+    let decision = state
+        .policy
+        .evaluate_capability_grant(&signer.address, &owners);
+    if !decision.allowed {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "NOT_VAULT_OWNER",
+            decision.reason,
+        ));
+    }
+    super::require_mutable(&state, &vault_id, &signer, "vault.index")?;
+
+    let indexed = state
+        .vault_index
+        .index(&vault_id, &signer.address, &request.label, &request.tags)
+        .map_err(|e| AppError::bad_request("INDEX_REJECTED", e))?;
+    info!(
+        "Vault {} indexed by {}: {} tags",
+        vault_id,
+        signer.address,
+        indexed.tags.len()
+    );
+    Ok(Json(indexed))
+}
+
+async fn search(
+    State(state): State<AppState>,
+    Query(query): Query<SearchQuery>,
+    Extension(signer): Extension<VerifiedSigner>,
+) -> Result<Json<SearchResponse>, AppError> {
+    let found = state
+        .vault_index
+        .search(&signer.address, query.tag.as_deref())
+        .map_err(|e| AppError::bad_request("INVALID_QUERY", e))?;
+
+    let mut vaults = Vec::with_capacity(found.len());
+    for vault in found {
+        if super::vault_owners(&state, &vault.vault_id)?.contains(&signer.address) {
+            vaults.push(vault);
+        }
+    }
+    Ok(Json(SearchResponse { vaults }))
+}
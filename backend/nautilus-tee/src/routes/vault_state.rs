@@ -0,0 +1,70 @@
+/**
+ * Vault State Routes
+ * One place to see whether a vault is held, moving, gone or escalating
+ */
+
+use axum::{
+    extract::{Path, State},
+    response::Json,
+    routing::get,
+    Router,
+};
+use serde::Serialize;
+
+use crate::error::AppError;
+use crate::escalation::EscalationStatus;
+use crate::validation::VaultId;
+use crate::AppState;
+
+#[derive(Serialize)]
+struct VaultStateResponse {
+    vault_id: String,
+    legal_hold: bool,
+    migrating: bool,
+    /// Enclave the vault moved to, once migrated
+    migrated_to: Option<String>,
+    deleted_at: Option<u64>,
+    check_in_expires_at: Option<u64>,
+    escalation: Option<EscalationStatus>,
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/vault/:vault_id/state", get(vault_state))
+}
+
+async fn vault_state(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+) -> Result<Json<VaultStateResponse>, AppError> {
+    Ok(Json(VaultStateResponse {
+        legal_hold: state
+            .legal_holds
+            .is_held(&vault_id)
+            .map_err(AppError::internal)?,
+        migrating: state
+            .migrations
+            .migrating(&vault_id)
+            .map_err(AppError::internal)?
+            .is_some(),
+        migrated_to: state
+            .migrations
+            .tombstone(&vault_id)
+            .map_err(AppError::internal)?
+            .map(|t| t.destination.identity),
+        deleted_at: state
+            .deletions
+            .deletion(&vault_id)
+            .map_err(AppError::internal)?
+            .map(|d| d.deleted_at),
+        check_in_expires_at: state
+            .check_ins
+            .schedule(&vault_id)
+            .map_err(AppError::internal)?
+            .map(|s| s.expires_at()),
+        escalation: state
+            .escalation
+            .status(&vault_id)
+            .map_err(AppError::internal)?,
+        vault_id: vault_id.into(),
+    }))
+}
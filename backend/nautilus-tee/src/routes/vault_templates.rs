@@ -0,0 +1,125 @@
+/**
+ * Vault Template Routes
+ * Listing the vault templates and registering a vault from one
+ *
+ * The first registration of a vault makes the signer its owner; after
+ * that only an owner may register it again, with the owner quorum on a
+ * co-owned vault. A manifest that fails its template's schema is refused
+ * with every violation listed.
+ */
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::Json,
+    routing::{get, post},
+    Extension, Router,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::info;
+
+use crate::auth::VerifiedSigner;
+use crate::error::AppError;
+use crate::validation::VaultId;
+use crate::vault_templates::VaultRegistration;
+use crate::AppState;
+
+#[derive(Deserialize)]
+struct RegisterRequest {
+    vault_id: VaultId,
+    template_id: String,
+    manifest: Value,
+    /// Rule pack to register the vault under (see jurisdictions)
+    jurisdiction: Option<String>,
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/vault/templates", get(list_templates))
+}
+
+pub fn signed_routes() -> Router<AppState> {
+    Router::new().route("/vault/register", post(register))
+}
+
+async fn list_templates(State(state): State<AppState>) -> Json<Value> {
+    Json(serde_json::json!({ "templates": state.vault_templates.all() }))
+}
+
+async fn register(
+    State(state): State<AppState>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<RegisterRequest>,
+) -> Result<Json<VaultRegistration>, AppError> {
+    let vault_id = request.vault_id.to_string();
+    let owners = super::vault_owners(&state, &vault_id)?;
+    let decision = state
+        .policy
+        .evaluate_vault_registration(&signer.address, &owners);
+    if !decision.allowed {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "NOT_VAULT_OWNER",
+            decision.reason,
+        ));
+    }
+    super::require_mutable(&state, &vault_id, &signer, "vault.register")?;
+    let rule_pack = request
+        .jurisdiction
+        .as_deref()
+        .map(|jurisdiction| {
+            state
+                .jurisdictions
+                .pack(jurisdiction)
+                .cloned()
+                .ok_or_else(|| {
+                    AppError::new(
+                        StatusCode::UNPROCESSABLE_ENTITY,
+                        "UNKNOWN_JURISDICTION",
+                        format!("No rule pack is configured for {}", jurisdiction),
+                    )
+                })
+        })
+        .transpose()?;
+
+    let registration = state
+        .vault_templates
+        .register(
+            &vault_id,
+            &signer.address,
+            &request.template_id,
+            request.manifest,
+            rule_pack,
+        )
+        .map_err(|errors| {
+            AppError::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "MANIFEST_REJECTED",
+                format!(
+                    "Manifest doesn't match vault template {}",
+                    request.template_id
+                ),
+            )
+            .with_details(serde_json::json!({ "errors": errors }))
+        })?;
+    info!(
+        "Vault {} registered from template {} v{} by {}",
+        vault_id, registration.template_id, registration.template_version, signer.address
+    );
+
+    state
+        .audit
+        .record(
+            &vault_id,
+            "vault.registered",
+            &signer.address,
+            serde_json::json!({
+                "template_id": registration.template_id,
+                "template_version": registration.template_version,
+                "manifest_sha256": registration.manifest_sha256,
+                "jurisdiction": registration.rule_pack.as_ref().map(|p| &p.jurisdiction),
+            }),
+        )
+        .map_err(AppError::internal)?;
+    Ok(Json(registration))
+}
@@ -0,0 +1,127 @@
+/**
+ * Verification Key Routes
+ * Pinned verification keys exported for deploying on-chain verifiers
+ *
+ * A Move verifier has to be deployed with exactly the key the enclave
+ * checks proofs against. This serves a circuit version's pinned key as
+ * snarkjs shipped it and in the encodings Sui's groth16 module takes.
+ *
+ * The identity key signs "lumina-vkey-v1" || len(claim_type) ||
+ * claim_type || version (u32 big-endian) || the compressed arkworks key,
+ * so a contract pinning the identity key can check, on deployment, that a
+ * key is the one serving that claim type and version. The attestation
+ * covers the same message.
+ */
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+    routing::get,
+    Router,
+};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+use crate::attestation::Attestation;
+use crate::circuits::Support;
+use crate::error::AppError;
+use crate::groth16::{self, SuiVerifyingKey};
+use crate::AppState;
+
+const DOMAIN: &[u8] = b"lumina-vkey-v1";
+
+#[derive(Deserialize)]
+struct VkeyQuery {
+    /// The current version when omitted
+    version: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct VkeyResponse {
+    claim_type: &'static str,
+    version: u32,
+    support: Support,
+    verification_key_sha256: Option<String>,
+    snarkjs: Arc<Value>,
+    sui: SuiVerifyingKey,
+    message: String,    // Hex bytes that were signed
+    signature: String,  // Base64 Ed25519 signature
+    public_key: String, // Base64 enclave identity key
+    key_id: String,
+    attestation: Attestation,
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/zk/circuits/:claim_type/vkey", get(verification_key))
+}
+
+async fn verification_key(
+    State(state): State<AppState>,
+    Path(claim_type): Path<String>,
+    Query(query): Query<VkeyQuery>,
+) -> Result<Json<VkeyResponse>, AppError> {
+    let circuits = state.zk_proof.circuits();
+    let circuit = match query.version {
+        Some(version) => circuits.get(&claim_type, version),
+        None => circuits
+            .all()
+            .iter()
+            .find(|c| c.claim_type == claim_type && c.support == Support::Current),
+    }
+    .ok_or_else(|| {
+        AppError::new(
+            StatusCode::NOT_FOUND,
+            "CIRCUIT_NOT_FOUND",
+            format!("No such {} circuit version", claim_type),
+        )
+    })?;
+    let (Some(key), Some(snarkjs)) = (&circuit.verifying_key, &circuit.verification_key_json)
+    else {
+        return Err(AppError::new(
+            StatusCode::NOT_FOUND,
+            "VERIFICATION_KEY_UNAVAILABLE",
+            format!(
+                "No pinned verification key is loaded for {} circuit version {}",
+                circuit.claim_type, circuit.version
+            ),
+        ));
+    };
+
+    let sui = groth16::sui_verifying_key(key).map_err(AppError::internal)?;
+    let compressed =
+        hex::decode(&sui.verifying_key).map_err(|e| AppError::internal(e.to_string()))?;
+    let message = signing_message(circuit.claim_type, circuit.version, &compressed);
+    let signature = state.identity.sign(&message);
+    let attestation = state
+        .attestation
+        .generate_with_user_data("", "zk_verification_key", &Sha256::digest(&message))
+        .await?;
+
+    Ok(Json(VkeyResponse {
+        claim_type: circuit.claim_type,
+        version: circuit.version,
+        support: circuit.support,
+        verification_key_sha256: circuit.verification_key_sha256.clone(),
+        snarkjs: snarkjs.clone(),
+        sui,
+        message: hex::encode(&message),
+        signature: STANDARD.encode(signature.to_bytes()),
+        public_key: STANDARD.encode(state.identity.public_key().as_bytes()),
+        key_id: state.identity.key_id(),
+        attestation,
+    }))
+}
+
+fn signing_message(claim_type: &str, version: u32, key: &[u8]) -> Vec<u8> {
+    let mut message = DOMAIN.to_vec();
+    message.extend_from_slice(&(claim_type.len() as u64).to_be_bytes());
+    message.extend_from_slice(claim_type.as_bytes());
+    message.extend_from_slice(&version.to_be_bytes());
+    message.extend_from_slice(key);
+    message
+}
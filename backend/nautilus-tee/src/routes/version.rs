@@ -0,0 +1,72 @@
+/**
+ * Version Routes
+ * Build metadata, circuit digests and measurements, attested together
+ */
+
+use axum::{extract::State, response::Json, routing::get, Router};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::attestation::{Attestation, Measurements};
+use crate::build_info::{self, BuildInfo};
+use crate::circuits::Support;
+use crate::error::AppError;
+use crate::AppState;
+
+#[derive(Serialize)]
+struct CircuitDigest {
+    claim_type: &'static str,
+    version: u32,
+    support: Support,
+    zkey_sha256: Option<String>,
+    verification_key_sha256: Option<String>,
+}
+
+#[derive(Serialize)]
+struct VersionInfo {
+    build: BuildInfo,
+    circuits: Vec<CircuitDigest>,
+    measurements: Measurements,
+}
+
+#[derive(Serialize)]
+struct VersionResponse {
+    #[serde(flatten)]
+    info: VersionInfo,
+    /// user_data is SHA-256 of the JSON of every other field, in order
+    attestation: Attestation,
+}
+
+pub fn routes() -> Router<AppState> {
+    Router::new().route("/version", get(version))
+}
+
+async fn version(State(state): State<AppState>) -> Result<Json<VersionResponse>, AppError> {
+    let circuits = state
+        .zk_proof
+        .circuits()
+        .all()
+        .iter()
+        .map(|c| CircuitDigest {
+            claim_type: c.claim_type,
+            version: c.version,
+            support: c.support,
+            zkey_sha256: c.zkey_sha256.clone(),
+            verification_key_sha256: c.verification_key_sha256.clone(),
+        })
+        .collect();
+    let measurements = state.attestation.get_pcr_measurements()?;
+    let info = VersionInfo {
+        build: build_info::build_info(),
+        circuits,
+        measurements,
+    };
+
+    let statement = serde_json::to_vec(&info).map_err(|e| AppError::internal(e.to_string()))?;
+    let attestation = state
+        .attestation
+        .generate_with_user_data("", "version", &Sha256::digest(statement))
+        .await?;
+
+    Ok(Json(VersionResponse { info, attestation }))
+}
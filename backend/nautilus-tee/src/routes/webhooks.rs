@@ -0,0 +1,210 @@
+/**
+ * Webhook Routes
+ * Owner-managed webhook endpoints and replays, and the host relay's
+ * polling for deliveries and acknowledging them
+ */
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Json,
+    routing::post,
+    Extension, Router,
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::auth::VerifiedSigner;
+use crate::error::AppError;
+use crate::routes::require_admin;
+use crate::validation::VaultId;
+use crate::webhooks::{DeliveryAck, DueDelivery, EndpointStatus, WebhookEndpoint, MAX_BATCH};
+use crate::AppState;
+
+#[derive(Deserialize)]
+struct RegisterRequest {
+    url: String,
+}
+
+#[derive(Deserialize)]
+struct ReplayRequest {
+    /// Id of the last event the receiver processed
+    after: u64,
+}
+
+#[derive(Serialize)]
+struct ReplayResponse {
+    replayed: usize,
+}
+
+#[derive(Serialize)]
+struct WebhooksResponse {
+    webhooks: Vec<EndpointStatus>,
+}
+
+#[derive(Deserialize)]
+struct DueRequest {
+    limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct DueResponse {
+    deliveries: Vec<DueDelivery>,
+}
+
+#[derive(Deserialize)]
+struct AcksRequest {
+    acks: Vec<DeliveryAck>,
+}
+
+#[derive(Serialize)]
+struct AcksResponse {
+    acknowledged: usize,
+}
+
+/// Endpoint URLs are personal data, so even reading them is signed
+pub fn signed_routes() -> Router<AppState> {
+    Router::new()
+        .route("/vault/:vault_id/webhooks", post(list))
+        .route("/vault/:vault_id/webhooks/register", post(register))
+        .route(
+            "/vault/:vault_id/webhooks/:endpoint_id/remove",
+            post(remove),
+        )
+        .route(
+            "/vault/:vault_id/webhooks/:endpoint_id/replay",
+            post(replay),
+        )
+        .route("/admin/webhooks/due", post(due))
+        .route("/admin/webhooks/acks", post(acknowledge))
+}
+
+async fn list(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Extension(signer): Extension<VerifiedSigner>,
+) -> Result<Json<WebhooksResponse>, AppError> {
+    require_webhook_owner(&state, &vault_id, &signer)?;
+    let webhooks = state
+        .webhooks
+        .endpoints(&vault_id)
+        .map_err(AppError::internal)?;
+    Ok(Json(WebhooksResponse { webhooks }))
+}
+
+async fn register(
+    State(state): State<AppState>,
+    Path(vault_id): Path<VaultId>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<RegisterRequest>,
+) -> Result<Json<WebhookEndpoint>, AppError> {
+    super::require_mutable(&state, &vault_id, &signer, "webhooks.register")?;
+    require_webhook_owner(&state, &vault_id, &signer)?;
+
+    let endpoint = state
+        .webhooks
+        .register(&vault_id, &request.url, &signer.address)
+        .map_err(|e| AppError::bad_request("WEBHOOK_REJECTED", e))?;
+    info!(
+        "Webhook {} registered for {} by {}",
+        endpoint.endpoint_id, vault_id, signer.address
+    );
+    Ok(Json(endpoint))
+}
+
+async fn remove(
+    State(state): State<AppState>,
+    Path((vault_id, endpoint_id)): Path<(VaultId, String)>,
+    Extension(signer): Extension<VerifiedSigner>,
+) -> Result<Json<WebhooksResponse>, AppError> {
+    require_webhook_owner(&state, &vault_id, &signer)?;
+    if !state
+        .webhooks
+        .remove(&vault_id, &endpoint_id)
+        .map_err(AppError::internal)?
+    {
+        return Err(no_webhook(&endpoint_id));
+    }
+    info!(
+        "Webhook {} of {} removed by {}",
+        endpoint_id, vault_id, signer.address
+    );
+    let webhooks = state
+        .webhooks
+        .endpoints(&vault_id)
+        .map_err(AppError::internal)?;
+    Ok(Json(WebhooksResponse { webhooks }))
+}
+
+async fn replay(
+    State(state): State<AppState>,
+    Path((vault_id, endpoint_id)): Path<(VaultId, String)>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<ReplayRequest>,
+) -> Result<Json<ReplayResponse>, AppError> {
+    require_webhook_owner(&state, &vault_id, &signer)?;
+    let replayed = state
+        .webhooks
+        .replay(&vault_id, &endpoint_id, request.after)
+        .map_err(AppError::internal)?
+        .ok_or_else(|| no_webhook(&endpoint_id))?;
+    info!(
+        "Replayed {} events after {} to webhook {} of {}",
+        replayed, request.after, endpoint_id, vault_id
+    );
+    Ok(Json(ReplayResponse { replayed }))
+}
+
+async fn due(
+    State(state): State<AppState>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<DueRequest>,
+) -> Result<Json<DueResponse>, AppError> {
+    require_admin(&state, &signer)?;
+    let deliveries = state
+        .webhooks
+        .due(request.limit.unwrap_or(MAX_BATCH).min(MAX_BATCH))
+        .map_err(AppError::internal)?;
+    Ok(Json(DueResponse { deliveries }))
+}
+
+async fn acknowledge(
+    State(state): State<AppState>,
+    Extension(signer): Extension<VerifiedSigner>,
+    Json(request): Json<AcksRequest>,
+) -> Result<Json<AcksResponse>, AppError> {
+    require_admin(&state, &signer)?;
+    let acknowledged = state
+        .webhooks
+        .acknowledge(request.acks)
+        .map_err(AppError::internal)?;
+    Ok(Json(AcksResponse { acknowledged }))
+}
+
+fn require_webhook_owner(
+    state: &AppState,
+    vault_id: &str,
+    signer: &VerifiedSigner,
+) -> Result<(), AppError> {
+    let owners = super::vault_owners(state, vault_id)?;
+    let decision =
+        state
+            .policy
+            .evaluate_contact_change(&signer.address, &owners, signer.delegate.is_some());
+    if !decision.allowed {
+        return Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            "WEBHOOKS_DENIED",
+            decision.reason,
+        ));
+    }
+    Ok(())
+}
+
+fn no_webhook(endpoint_id: &str) -> AppError {
+    AppError::new(
+        StatusCode::NOT_FOUND,
+        "WEBHOOK_NOT_FOUND",
+        format!("No webhook {}", endpoint_id),
+    )
+}
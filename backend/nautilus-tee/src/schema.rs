@@ -0,0 +1,252 @@
+/**
+ * Response Schemas
+ * Schema versions per route, negotiated by header, and deprecation notices
+ *
+ * Every response names the schema version of its body in X-Lumina-Schema.
+ * A client may send the same header to ask for a version; one the route
+ * doesn't serve is refused with 406 UNSUPPORTED_SCHEMA and the versions it
+ * does, so an SDK finds out before misreading a body. Routes not listed
+ * below serve SCHEMA_VERSION only. GET /schemas enumerates all of it, for
+ * SDKs negotiating capabilities at startup.
+ *
+ * Routes being retired are configured with when they were deprecated and
+ * when they go away (TEE_DEPRECATED_ROUTES, `route=deprecated_at/sunset_at`
+ * in unix seconds). Their responses carry Deprecation (RFC 9745) and
+ * Sunset (RFC 8594) headers, and a successor-version Link where one exists.
+ */
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::error::AppError;
+
+pub const SCHEMA_HEADER: &str = "x-lumina-schema";
+/// Schema of every route without an entry in ROUTES
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Routes SDKs depend on, with the schema versions each can serve
+const ROUTES: &[RouteSchema] = &[
+    RouteSchema::new("/health", None),
+    RouteSchema::new("/biometric/verify", None),
+    RouteSchema::new("/biometric/voice/challenge", None),
+    RouteSchema::new("/biometric/enroll", None),
+    RouteSchema::new("/liveness/check", None),
+    RouteSchema::new("/liveness/check-batch", None),
+    RouteSchema::new("/zk/generate", Some("/zk/jobs")),
+    RouteSchema::new("/zk/circuits", None),
+    RouteSchema::new("/zk/circuits/:claim_type/vkey", None),
+    RouteSchema::new("/zk/jobs", None),
+    RouteSchema::new("/zk/jobs/:job_id", None),
+    RouteSchema::new("/zk/jobs/:job_id/events", None),
+    RouteSchema::new("/zk/queue", None),
+    RouteSchema::new("/zk/verify-batch", None),
+    RouteSchema::new("/vault/:vault_id/data-export", None),
+    RouteSchema::new("/vault/register", None),
+    RouteSchema::new("/vault/templates", None),
+    RouteSchema::new("/vaults", None),
+    RouteSchema::new("/version", None),
+];
+
+#[derive(Clone, Copy)]
+struct RouteSchema {
+    route: &'static str,
+    /// What responses are written in
+    current: u32,
+    /// What clients may ask for, current included
+    supported: &'static [u32],
+    /// Where clients move once the route is deprecated
+    successor: Option<&'static str>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct Deprecation {
+    /// Unix seconds
+    pub deprecated_at: u64,
+    pub sunset_at: u64,
+}
+
+#[derive(Serialize)]
+pub struct SchemaListing {
+    pub header: &'static str,
+    pub default_version: u32,
+    pub routes: Vec<RouteListing>,
+}
+
+#[derive(Serialize)]
+pub struct RouteListing {
+    pub route: String,
+    pub current: u32,
+    pub supported: &'static [u32],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub successor: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deprecation: Option<Deprecation>,
+}
+
+pub struct SchemaRegistry {
+    /// By axum route pattern
+    deprecations: BTreeMap<String, Deprecation>,
+}
+
+impl RouteSchema {
+    const fn new(route: &'static str, successor: Option<&'static str>) -> Self {
+        Self {
+            route,
+            current: SCHEMA_VERSION,
+            supported: &[SCHEMA_VERSION],
+            successor,
+        }
+    }
+
+    fn of(route: &str) -> Self {
+        ROUTES
+            .iter()
+            .find(|schema| schema.route == route)
+            .copied()
+            .unwrap_or(Self::new("", None))
+    }
+}
+
+impl SchemaRegistry {
+    /// `deprecations` are `route=deprecated_at/sunset_at` entries
+    pub fn new(deprecations: &[String]) -> Result<Self, String> {
+        let deprecations = deprecations
+            .iter()
+            .map(|entry| {
+                let invalid = || format!("Invalid deprecated route entry: {}", entry);
+                let (route, window) = entry.split_once('=').ok_or_else(invalid)?;
+                let (deprecated_at, sunset_at) = window.split_once('/').ok_or_else(invalid)?;
+                let deprecation = Deprecation {
+                    deprecated_at: deprecated_at.trim().parse().map_err(|_| invalid())?,
+                    sunset_at: sunset_at.trim().parse().map_err(|_| invalid())?,
+                };
+                if !route.trim().starts_with('/')
+                    || deprecation.sunset_at < deprecation.deprecated_at
+                {
+                    return Err(invalid());
+                }
+                Ok((route.trim().to_string(), deprecation))
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Self { deprecations })
+    }
+
+    /// Every listed route, plus deprecated ones that aren't
+    pub fn listing(&self) -> SchemaListing {
+        let mut routes: Vec<RouteListing> = ROUTES
+            .iter()
+            .map(|schema| self.route_listing(schema.route.to_string(), schema))
+            .collect();
+        for route in self.deprecations.keys() {
+            if ROUTES.iter().all(|schema| schema.route != route) {
+                routes.push(self.route_listing(route.clone(), &RouteSchema::of(route)));
+            }
+        }
+        SchemaListing {
+            header: SCHEMA_HEADER,
+            default_version: SCHEMA_VERSION,
+            routes,
+        }
+    }
+
+    fn route_listing(&self, route: String, schema: &RouteSchema) -> RouteListing {
+        RouteListing {
+            deprecation: self.deprecations.get(&route).copied(),
+            route,
+            current: schema.current,
+            supported: schema.supported,
+            successor: schema.successor,
+        }
+    }
+}
+
+/// Refuse schema versions the route doesn't serve, and label responses
+/// with theirs and any deprecation
+pub async fn negotiate(
+    State(registry): State<Arc<SchemaRegistry>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_default();
+    let schema = RouteSchema::of(&route);
+
+    if let Some(requested) = request.headers().get(SCHEMA_HEADER) {
+        let version = requested
+            .to_str()
+            .ok()
+            .and_then(|v| v.trim().parse::<u32>().ok());
+        if !version.is_some_and(|v| schema.supported.contains(&v)) {
+            return Err(AppError::new(
+                StatusCode::NOT_ACCEPTABLE,
+                "UNSUPPORTED_SCHEMA",
+                format!("{} does not serve the requested schema version", route),
+            )
+            .with_details(serde_json::json!({ "supported": schema.supported })));
+        }
+    }
+
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+    headers.insert(SCHEMA_HEADER, HeaderValue::from(schema.current));
+    if let Some(deprecation) = registry.deprecations.get(&route) {
+        if let Ok(value) = HeaderValue::from_str(&format!("@{}", deprecation.deprecated_at)) {
+            headers.insert("deprecation", value);
+        }
+        let sunset = UNIX_EPOCH + Duration::from_secs(deprecation.sunset_at);
+        if let Ok(value) = HeaderValue::from_str(&httpdate::fmt_http_date(sunset)) {
+            headers.insert("sunset", value);
+        }
+        if let Some(successor) = schema.successor {
+            if let Ok(value) =
+                HeaderValue::from_str(&format!("<{}>; rel=\"successor-version\"", successor))
+            {
+                headers.append(header::LINK, value);
+            }
+        }
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deprecations_are_parsed_and_listed() {
+        let registry = SchemaRegistry::new(&[
+            "/zk/generate=1700000000/1800000000".to_string(),
+            "/vault/:vault_id=1700000000/1700000000".to_string(),
+        ])
+        .unwrap();
+        let listing = registry.listing();
+        let generate = listing
+            .routes
+            .iter()
+            .find(|r| r.route == "/zk/generate")
+            .unwrap();
+        assert_eq!(generate.deprecation.unwrap().sunset_at, 1_800_000_000);
+        assert_eq!(generate.successor, Some("/zk/jobs"));
+        assert!(listing.routes.iter().any(|r| r.route == "/vault/:vault_id"));
+
+        for bad in [
+            "/zk/generate",
+            "zk/generate=1/2",
+            "/zk/generate=2/1",
+            "/x=a/b",
+        ] {
+            assert!(SchemaRegistry::new(&[bad.to_string()]).is_err());
+        }
+    }
+}
@@ -0,0 +1,171 @@
+/**
+ * Vault Search
+ * Keyword and metadata queries over a decrypted vault payload
+ *
+ * Answers questions like "does the vault contain a will?" without
+ * revealing the content: results are one matched flag per term, and an
+ * occurrence count only when asked for. Positions, snippets and the text
+ * around a match never leave this module.
+ *
+ *   keywords: whole words or phrases, case-insensitive, in the payload's
+ *             text (a JSON payload's string values, otherwise the payload
+ *             as UTF-8)
+ *   metadata: `field = value` on a JSON payload, with dotted paths into
+ *             nested objects (e.g. "document.type"), case-insensitive
+ */
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use zeroize::Zeroizing;
+
+const MAX_TERMS: usize = 16;
+const MAX_TERM_CHARS: usize = 128;
+
+#[derive(Deserialize, Serialize)]
+pub struct SearchQuery {
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    /// Field path -> expected value
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+    /// Also return how often each keyword occurs
+    #[serde(default)]
+    pub counts: bool,
+}
+
+#[derive(Serialize)]
+pub struct KeywordMatch {
+    pub keyword: String,
+    pub matched: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub struct MetadataMatch {
+    pub field: String,
+    pub matched: bool,
+}
+
+#[derive(Serialize)]
+pub struct SearchResults {
+    /// Every keyword and metadata filter matched
+    pub matched: bool,
+    pub keywords: Vec<KeywordMatch>,
+    pub metadata: Vec<MetadataMatch>,
+}
+
+impl SearchQuery {
+    fn validate(&self) -> Result<(), String> {
+        let terms = self.keywords.len() + self.metadata.len();
+        if terms == 0 {
+            return Err("A search needs at least one keyword or metadata filter".to_string());
+        }
+        if terms > MAX_TERMS {
+            return Err(format!("At most {} search terms", MAX_TERMS));
+        }
+        let terms = self
+            .keywords
+            .iter()
+            .chain(self.metadata.keys())
+            .chain(self.metadata.values());
+        for term in terms {
+            if term.trim().is_empty() || term.chars().count() > MAX_TERM_CHARS {
+                return Err(format!(
+                    "Search terms must be 1 to {} characters",
+                    MAX_TERM_CHARS
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn search(payload: &[u8], query: &SearchQuery) -> Result<SearchResults, String> {
+    query.validate()?;
+
+    let document: Option<Value> = serde_json::from_slice(payload).ok();
+    let text = Zeroizing::new(match &document {
+        Some(document) => {
+            let mut text = String::new();
+            collect_text(document, &mut text);
+            text.to_lowercase()
+        }
+        None => String::from_utf8_lossy(payload).to_lowercase(),
+    });
+
+    let keywords: Vec<KeywordMatch> = query
+        .keywords
+        .iter()
+        .map(|keyword| {
+            let count = occurrences(&text, &keyword.trim().to_lowercase());
+            KeywordMatch {
+                keyword: keyword.clone(),
+                matched: count > 0,
+                count: query.counts.then_some(count),
+            }
+        })
+        .collect();
+    let metadata: Vec<MetadataMatch> = query
+        .metadata
+        .iter()
+        .map(|(field, expected)| MetadataMatch {
+            field: field.clone(),
+            matched: document
+                .as_ref()
+                .and_then(|document| lookup(document, field))
+                .is_some_and(|value| value.trim().eq_ignore_ascii_case(expected.trim())),
+        })
+        .collect();
+
+    Ok(SearchResults {
+        matched: keywords.iter().all(|k| k.matched) && metadata.iter().all(|m| m.matched),
+        keywords,
+        metadata,
+    })
+}
+
+/// String values, one per line
+fn collect_text(value: &Value, text: &mut String) {
+    match value {
+        Value::String(s) => {
+            text.push_str(s);
+            text.push('\n');
+        }
+        Value::Array(items) => items.iter().for_each(|item| collect_text(item, text)),
+        Value::Object(fields) => fields.values().for_each(|field| collect_text(field, text)),
+        _ => {}
+    }
+}
+
+/// Scalar at a dotted path, as text
+fn lookup(document: &Value, path: &str) -> Option<String> {
+    let value = path
+        .split('.')
+        .try_fold(document, |value, key| value.as_object()?.get(key))?;
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// Non-overlapping occurrences of `needle` not inside a longer word
+fn occurrences(text: &str, needle: &str) -> usize {
+    let is_word = |c: Option<char>| c.is_some_and(char::is_alphanumeric);
+    let mut count = 0;
+    let mut from = 0;
+    while let Some(at) = text[from..].find(needle) {
+        let start = from + at;
+        let end = start + needle.len();
+        if !is_word(text[..start].chars().next_back()) && !is_word(text[end..].chars().next()) {
+            count += 1;
+            from = end;
+        } else {
+            from = start + text[start..].chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    count
+}
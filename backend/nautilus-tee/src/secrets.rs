@@ -0,0 +1,461 @@
+/**
+ * Guardian Secret Sharing
+ * Splits vault content keys into Shamir shares held by guardians
+ *
+ * The enclave splits the key over GF(256), seals each share to one
+ * guardian's X25519 key and keeps only commitments. Guardians later
+ * resubmit their shares; once the policy engine confirms a verified
+ * threshold, the key is reassembled in enclave memory for release.
+ */
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::info;
+use zeroize::Zeroizing;
+
+use crate::auth::normalize_address;
+use crate::crypto::{self, Envelope, KeyExchange};
+use crate::ct;
+use crate::policy::PolicyEngine;
+use crate::store::Store;
+
+const NAMESPACE: &str = "guardian_shares";
+
+pub type ContentKey = Zeroizing<[u8; 32]>;
+
+/// Guardian address -> resubmitted share
+type SubmittedShares = HashMap<String, Zeroizing<Vec<u8>>>;
+
+#[derive(Clone, Deserialize)]
+pub struct GuardianKey {
+    pub address: String,    // Guardian's Sui address (authorizes resubmission)
+    pub public_key: String, // Base64 X25519 key the share is sealed to
+    /// Base64 ML-KEM-768 key, required for hybrid key exchange
+    #[serde(default)]
+    pub kem_public_key: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GuardianShareRecord {
+    pub address: String,
+    pub index: u8,
+    pub commitment: String,
+}
+
+/// Persisted per vault; holds commitments only, never share material
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ShareSet {
+    pub vault_id: String,
+    pub owner: String,
+    pub threshold: u8,
+    pub key_commitment: String,
+    /// How shares were wrapped to guardians
+    #[serde(default)]
+    pub key_exchange: KeyExchange,
+    pub guardians: Vec<GuardianShareRecord>,
+    pub created_at: u64,
+}
+
+#[derive(Serialize)]
+pub struct EncryptedShare {
+    pub guardian_address: String,
+    pub index: u8,
+    pub envelope: Envelope,
+}
+
+#[derive(Serialize)]
+pub struct ShareStatus {
+    pub threshold: u8,
+    pub total: usize,
+    pub verified: usize,
+    pub reconstructed: bool,
+}
+
+pub struct SecretsService {
+    store: Arc<Store>,
+    // Resubmitted shares are held in memory only, keyed by vault_id
+    submitted: Mutex<HashMap<String, SubmittedShares>>,
+    reconstructed: Mutex<HashMap<String, ContentKey>>,
+}
+
+impl SecretsService {
+    pub fn new(store: Arc<Store>) -> Self {
+        Self {
+            store,
+            submitted: Mutex::new(HashMap::new()),
+            reconstructed: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Split `content_key` (or a freshly generated key) among guardians.
+    /// Returns the generated key, if any, so the owner can encrypt with it.
+    /// `owner` must already own the registered vault; a split never claims it.
+    pub fn split(
+        &self,
+        vault_id: &str,
+        owner: &str,
+        content_key: Option<ContentKey>,
+        threshold: u8,
+        guardians: &[GuardianKey],
+        key_exchange: KeyExchange,
+    ) -> Result<(Option<ContentKey>, Vec<EncryptedShare>), String> {
+        if guardians.is_empty() || guardians.len() > 255 {
+            return Err("Between 1 and 255 guardians are required".to_string());
+        }
+        if threshold < 2 || threshold as usize > guardians.len() {
+            return Err(format!(
+                "Threshold must be between 2 and {}",
+                guardians.len()
+            ));
+        }
+        if let Some(existing) = self.share_set(vault_id)? {
+            if existing.owner != owner {
+                return Err("Vault shares belong to a different owner".to_string());
+            }
+        }
+
+        let generated = content_key.is_none();
+        let key = content_key.unwrap_or_else(|| {
+            let mut key = Zeroizing::new([0u8; 32]);
+            OsRng.fill_bytes(key.as_mut());
+            key
+        });
+
+        let shares = split_secret(key.as_ref(), threshold, guardians.len() as u8);
+        let mut records = Vec::with_capacity(guardians.len());
+        let mut encrypted = Vec::with_capacity(guardians.len());
+
+        for (guardian, share) in guardians.iter().zip(shares.iter()) {
+            let address = normalize_address(&guardian.address);
+            let public_key = crypto::decode_public_key(&guardian.public_key)?;
+            let kem_key = guardian
+                .kem_public_key
+                .as_deref()
+                .map(crypto::decode_kem_public_key)
+                .transpose()?;
+            let index = share[0];
+
+            records.push(GuardianShareRecord {
+                address: address.clone(),
+                index,
+                commitment: share_commitment(vault_id, share),
+            });
+            encrypted.push(EncryptedShare {
+                envelope: crypto::seal_with(
+                    key_exchange,
+                    &public_key,
+                    kem_key.as_ref(),
+                    share,
+                    share_aad(vault_id, &address).as_bytes(),
+                )?,
+                guardian_address: address,
+                index,
+            });
+        }
+
+        let share_set = ShareSet {
+            vault_id: vault_id.to_string(),
+            owner: owner.to_string(),
+            threshold,
+            key_commitment: key_commitment(vault_id, key.as_ref()),
+            key_exchange,
+            guardians: records,
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+        };
+        self.store.put(NAMESPACE, vault_id, &share_set)?;
+
+        // A re-split invalidates anything gathered for the previous shares
+        self.submitted.lock().unwrap().remove(vault_id);
+        self.reconstructed.lock().unwrap().remove(vault_id);
+
+        Ok((generated.then_some(key), encrypted))
+    }
+
+    /// Accept a guardian's decrypted share, verifying it against its commitment.
+    /// Reassembles the key once the policy engine allows it.
+    pub fn submit(
+        &self,
+        vault_id: &str,
+        guardian_address: &str,
+        share: Zeroizing<Vec<u8>>,
+        policy: &PolicyEngine,
+    ) -> Result<ShareStatus, String> {
+        let share_set = self
+            .share_set(vault_id)?
+            .ok_or_else(|| format!("No guardian shares for vault {}", vault_id))?;
+
+        let record = share_set
+            .guardians
+            .iter()
+            .find(|g| g.address == guardian_address)
+            .ok_or("Caller is not a guardian of this vault")?;
+
+        if share.first() != Some(&record.index)
+            || !ct::str_eq(&share_commitment(vault_id, &share), &record.commitment)
+        {
+            return Err("Share does not match its commitment".to_string());
+        }
+
+        let verified = {
+            let mut submitted = self.submitted.lock().unwrap();
+            let shares = submitted.entry(vault_id.to_string()).or_default();
+            shares.insert(record.address.clone(), share);
+            shares.len()
+        };
+
+        let decision = policy.evaluate_share_reconstruction(&share_set, verified);
+        info!("Share reconstruction for {}: {}", vault_id, decision.reason);
+        if decision.allowed && !self.reconstructed.lock().unwrap().contains_key(vault_id) {
+            let key = self.reassemble(&share_set)?;
+            self.reconstructed
+                .lock()
+                .unwrap()
+                .insert(vault_id.to_string(), key);
+        }
+
+        self.status(vault_id)?
+            .ok_or_else(|| format!("No guardian shares for vault {}", vault_id))
+    }
+
+    pub fn status(&self, vault_id: &str) -> Result<Option<ShareStatus>, String> {
+        let Some(share_set) = self.share_set(vault_id)? else {
+            return Ok(None);
+        };
+
+        let verified = self
+            .submitted
+            .lock()
+            .unwrap()
+            .get(vault_id)
+            .map_or(0, |s| s.len());
+
+        Ok(Some(ShareStatus {
+            threshold: share_set.threshold,
+            total: share_set.guardians.len(),
+            verified,
+            reconstructed: self.reconstructed.lock().unwrap().contains_key(vault_id),
+        }))
+    }
+
+    pub fn share_set(&self, vault_id: &str) -> Result<Option<ShareSet>, String> {
+        self.store.get(NAMESPACE, vault_id)
+    }
+
+    /// The vault's content key, once guardians have reassembled it
+    pub fn reconstructed_key(&self, vault_id: &str) -> Option<ContentKey> {
+        self.reconstructed.lock().unwrap().get(vault_id).cloned()
+    }
+
+    /// Drop the vault's resubmitted shares and reassembled key from memory;
+    /// returns how many shares were held and whether the key was
+    pub fn forget(&self, vault_id: &str) -> (usize, bool) {
+        let shares = self
+            .submitted
+            .lock()
+            .unwrap()
+            .remove(vault_id)
+            .map_or(0, |s| s.len());
+        let key = self
+            .reconstructed
+            .lock()
+            .unwrap()
+            .remove(vault_id)
+            .is_some();
+        (shares, key)
+    }
+
+    fn reassemble(&self, share_set: &ShareSet) -> Result<ContentKey, String> {
+        let submitted = self.submitted.lock().unwrap();
+        let shares: Vec<&[u8]> = submitted
+            .get(&share_set.vault_id)
+            .map(|s| s.values().map(|v| v.as_slice()).collect())
+            .unwrap_or_default();
+
+        let secret = combine_shares(&shares)?;
+        let key: [u8; 32] = secret
+            .as_slice()
+            .try_into()
+            .map_err(|_| "Reassembled key has wrong length".to_string())?;
+        let key = Zeroizing::new(key);
+
+        if !ct::str_eq(
+            &key_commitment(&share_set.vault_id, key.as_ref()),
+            &share_set.key_commitment,
+        ) {
+            return Err("Reassembled key does not match the vault's key commitment".to_string());
+        }
+        Ok(key)
+    }
+}
+
+pub fn decode_share(encoded: &str) -> Result<Zeroizing<Vec<u8>>, String> {
+    STANDARD
+        .decode(encoded)
+        .map(Zeroizing::new)
+        .map_err(|e| format!("Invalid share encoding: {}", e))
+}
+
+fn share_aad(vault_id: &str, guardian_address: &str) -> String {
+    format!("lumina-guardian-share:{}:{}", vault_id, guardian_address)
+}
+
+fn share_commitment(vault_id: &str, share: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(b"lumina-share");
+    hasher.update(vault_id.as_bytes());
+    hasher.update(share);
+    hex::encode(hasher.finalize())
+}
+
+fn key_commitment(vault_id: &str, key: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(b"lumina-content-key");
+    hasher.update(vault_id.as_bytes());
+    hasher.update(key);
+    hex::encode(hasher.finalize())
+}
+
+/// Shares are `x || y_0 .. y_n`, one polynomial per secret byte, x in 1..=n
+pub fn split_secret(secret: &[u8], threshold: u8, count: u8) -> Vec<Zeroizing<Vec<u8>>> {
+    let mut shares: Vec<Zeroizing<Vec<u8>>> = (1..=count)
+        .map(|x| {
+            let mut share = Vec::with_capacity(secret.len() + 1);
+            share.push(x);
+            Zeroizing::new(share)
+        })
+        .collect();
+
+    let mut coefficients = Zeroizing::new(vec![0u8; threshold as usize]);
+    for &byte in secret {
+        coefficients[0] = byte;
+        OsRng.fill_bytes(&mut coefficients[1..]);
+
+        for share in shares.iter_mut() {
+            let x = share[0];
+            // Horner evaluation from the highest coefficient down
+            let y = coefficients
+                .iter()
+                .rev()
+                .fold(0u8, |acc, &c| gf_mul(acc, x) ^ c);
+            share.push(y);
+        }
+    }
+
+    shares
+}
+
+/// Lagrange interpolation at x = 0
+fn combine_shares(shares: &[&[u8]]) -> Result<Zeroizing<Vec<u8>>, String> {
+    let len = shares.first().map(|s| s.len()).ok_or("No shares")?;
+    if len < 2 || shares.iter().any(|s| s.len() != len) {
+        return Err("Shares have inconsistent lengths".to_string());
+    }
+
+    let xs: Vec<u8> = shares.iter().map(|s| s[0]).collect();
+    for (i, x) in xs.iter().enumerate() {
+        if *x == 0 || xs[..i].contains(x) {
+            return Err("Shares have invalid or duplicate indices".to_string());
+        }
+    }
+
+    let mut secret = Zeroizing::new(vec![0u8; len - 1]);
+    for (i, &xi) in xs.iter().enumerate() {
+        let mut basis = 1u8;
+        for (j, &xj) in xs.iter().enumerate() {
+            if i != j {
+                // l_i(0) = prod x_j / (x_j - x_i); subtraction is XOR in GF(256)
+                basis = gf_mul(basis, gf_mul(xj, gf_inv(xj ^ xi)));
+            }
+        }
+        for (byte, &y) in secret.iter_mut().zip(&shares[i][1..]) {
+            *byte ^= gf_mul(basis, y);
+        }
+    }
+
+    Ok(secret)
+}
+
+/// Multiplication in GF(2^8) with the AES polynomial x^8 + x^4 + x^3 + x + 1
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0u8;
+    for _ in 0..8 {
+        // Branch-free: mask is 0xff when the low bit of b is set
+        product ^= a & 0u8.wrapping_sub(b & 1);
+        let carry = 0u8.wrapping_sub(a >> 7);
+        a = (a << 1) ^ (0x1b & carry);
+        b >>= 1;
+    }
+    product
+}
+
+/// a^254 = a^-1 for non-zero a
+fn gf_inv(a: u8) -> u8 {
+    let mut result = 1u8;
+    let mut base = a;
+    let mut exp = 254u8;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = gf_mul(result, base);
+        }
+        base = gf_mul(base, base);
+        exp >>= 1;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn field_arithmetic_matches_gf256() {
+        // FIPS-197 worked example
+        assert_eq!(gf_mul(0x57, 0x83), 0xc1);
+        assert_eq!(gf_mul(0x57, 0x13), 0xfe);
+        for a in 1..=255u8 {
+            assert_eq!(gf_mul(a, gf_inv(a)), 1, "inverse of {:#04x}", a);
+            assert_eq!(gf_mul(a, 1), a);
+            assert_eq!(gf_mul(a, 0), 0);
+        }
+    }
+
+    #[test]
+    fn any_threshold_of_shares_recovers_the_secret() {
+        let secret: Vec<u8> = (0..32).collect();
+        let shares = split_secret(&secret, 3, 5);
+        assert!(shares.iter().all(|s| s.len() == secret.len() + 1));
+
+        for subset in [[0, 1, 2], [0, 2, 4], [4, 3, 1], [1, 2, 3]] {
+            let picked: Vec<&[u8]> = subset.iter().map(|&i| shares[i].as_slice()).collect();
+            assert_eq!(combine_shares(&picked).unwrap().as_slice(), secret);
+        }
+        let all: Vec<&[u8]> = shares.iter().map(|s| s.as_slice()).collect();
+        assert_eq!(combine_shares(&all).unwrap().as_slice(), secret);
+
+        // Below the threshold the interpolation lands somewhere else
+        let two: Vec<&[u8]> = shares[..2].iter().map(|s| s.as_slice()).collect();
+        assert_ne!(combine_shares(&two).unwrap().as_slice(), secret);
+    }
+
+    #[test]
+    fn malformed_share_sets_are_rejected() {
+        let shares = split_secret(b"content key", 2, 3);
+        let duplicate = [shares[0].as_slice(), shares[0].as_slice()];
+        assert!(combine_shares(&duplicate).is_err());
+        let truncated = [shares[0].as_slice(), &shares[1][..4]];
+        assert!(combine_shares(&truncated).is_err());
+        let zero_index = [&[0u8, 1, 2][..], &[1u8, 1, 2][..]];
+        assert!(combine_shares(&zero_index).is_err());
+        assert!(combine_shares(&[]).is_err());
+    }
+}
@@ -0,0 +1,130 @@
+/**
+ * Listener Loops
+ * Accepts connections on the configured transport and hands them to hyper
+ *
+ * Connections speak HTTP/1.1 or, unless TEE_HTTP2 is off, HTTP/2, so SDKs
+ * can multiplex many small calls (liveness checks, polling) over one
+ * connection. Over TLS the protocol is negotiated by ALPN; over plaintext
+ * TCP and VSOCK clients use HTTP/2 with prior knowledge.
+ */
+
+use axum::Router;
+use hyper_util::rt::{TokioExecutor, TokioIo, TokioTimer};
+use hyper_util::server::conn::auto::Builder;
+use hyper_util::service::TowerToHyperService;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+use tracing::{info, warn};
+
+use crate::config::HttpConfig;
+use crate::tls::TlsReloader;
+
+/// hyper's HTTP/1.1 read buffer can't be made smaller than this
+const MIN_HTTP1_BUFFER: usize = 8192;
+
+pub async fn serve_tcp(
+    addr: SocketAddr,
+    app: Router,
+    tls: Option<Arc<TlsReloader>>,
+    http: HttpConfig,
+) {
+    let listener = TcpListener::bind(addr)
+        .await
+        .unwrap_or_else(|e| panic!("Failed to bind to {}: {}", addr, e));
+
+    info!(
+        "Nautilus TEE Server listening on {} ({}{})",
+        addr,
+        if tls.is_some() { "https" } else { "http" },
+        if http.http2 { ", h2" } else { "" }
+    );
+    let builder = connection_builder(&http);
+
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                warn!("Failed to accept connection: {}", e);
+                continue;
+            }
+        };
+
+        let (app, builder) = (app.clone(), builder.clone());
+        match &tls {
+            Some(tls) => {
+                let acceptor = tls.acceptor();
+                tokio::spawn(async move {
+                    match acceptor.accept(stream).await {
+                        Ok(stream) => serve_connection(stream, app, builder).await,
+                        Err(e) => warn!("TLS handshake with {} failed: {}", peer, e),
+                    }
+                });
+            }
+            None => {
+                tokio::spawn(serve_connection(stream, app, builder));
+            }
+        }
+    }
+}
+
+#[cfg(feature = "vsock")]
+pub async fn serve_vsock(port: u32, app: Router, http: HttpConfig) {
+    use tokio_vsock::{VsockAddr, VsockListener};
+
+    // VMADDR_CID_ANY: accept from the parent instance on any CID
+    let mut listener = VsockListener::bind(VsockAddr::new(u32::MAX, port))
+        .unwrap_or_else(|e| panic!("Failed to bind to vsock port {}: {}", port, e));
+
+    info!("Nautilus TEE Server listening on vsock port {}", port);
+    let builder = connection_builder(&http);
+
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tokio::spawn(serve_connection(stream, app.clone(), builder.clone()));
+            }
+            Err(e) => warn!("Failed to accept vsock connection: {}", e),
+        }
+    }
+}
+
+#[cfg(not(feature = "vsock"))]
+pub async fn serve_vsock(_port: u32, _app: Router, _http: HttpConfig) {
+    panic!("VSOCK transport requires building with the `vsock` feature");
+}
+
+fn connection_builder(http: &HttpConfig) -> Builder<TokioExecutor> {
+    let mut builder = Builder::new(TokioExecutor::new());
+    builder
+        .http1()
+        .timer(TokioTimer::new())
+        .keep_alive(http.keep_alive)
+        .header_read_timeout(http.header_read_timeout)
+        .max_buf_size(http.max_header_bytes.max(MIN_HTTP1_BUFFER));
+    builder
+        .http2()
+        .timer(TokioTimer::new())
+        .keep_alive_interval(http.h2_keep_alive_interval)
+        .keep_alive_timeout(http.h2_keep_alive_timeout)
+        .max_concurrent_streams(http.h2_max_concurrent_streams)
+        .max_header_list_size(http.max_header_bytes as u32);
+    if !http.http2 {
+        builder = builder.http1_only();
+    }
+    builder
+}
+
+async fn serve_connection<I>(io: I, app: Router, builder: Builder<TokioExecutor>)
+where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let service = TowerToHyperService::new(app);
+    if let Err(e) = builder
+        .serve_connection_with_upgrades(TokioIo::new(io), service)
+        .await
+    {
+        warn!("Connection error: {}", e);
+    }
+}
@@ -0,0 +1,434 @@
+/**
+ * Encrypted Sessions
+ * X25519 ECDH sessions that encrypt request and response bodies end to end
+ *
+ * The client sends an ephemeral public key; the enclave answers with its
+ * own ephemeral key inside an attestation, so the client knows it is
+ * talking to a measured enclave. Both sides derive directional keys with
+ * HKDF(shared, salt = client_pk || enclave_pk). The enclave's ephemeral
+ * secret is dropped immediately, giving forward secrecy once a session
+ * expires. Clients may negotiate hybrid X25519 + ML-KEM-768 by also
+ * sending an ML-KEM public key; the enclave encapsulates to it and both
+ * shared secrets feed the KDF.
+ *
+ * Requests carrying `x-lumina-session` have a body of
+ * `{"nonce", "ciphertext"}`; the middleware decrypts it before any other
+ * layer (including signature checks) sees it and encrypts the response
+ * under the reverse-direction key.
+ *
+ * Establishing a session is signed (see auth), and what it can cost is
+ * bounded: each signer holds at most MAX_CLIENT_SESSIONS live sessions, at
+ * most MAX_SESSIONS are live at once, and each carries at most
+ * MAX_SESSION_REQUESTS requests, which also bounds the nonces it remembers
+ * for replay detection. A client that uses one up establishes another.
+ */
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use hkdf::Hkdf;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use x25519_dalek::{EphemeralSecret, PublicKey};
+use zeroize::Zeroizing;
+
+use crate::crypto::{self, KeyExchange};
+use crate::error::AppError;
+use crate::randomness::RandomnessSource;
+
+pub const SESSION_HEADER: &str = "x-lumina-session";
+
+const SESSION_INFO_REQUEST: &[u8] = b"lumina-session-v1 client->enclave";
+const SESSION_INFO_RESPONSE: &[u8] = b"lumina-session-v1 enclave->client";
+const MAX_ENCRYPTED_BODY_BYTES: usize = 1024 * 1024;
+const MAX_RESPONSE_BODY_BYTES: usize = 8 * 1024 * 1024;
+const MAX_SESSIONS: usize = 4096;
+const MAX_CLIENT_SESSIONS: usize = 16;
+const MAX_SESSION_REQUESTS: usize = 4096;
+
+/// Wire form of an encrypted request or response body
+#[derive(Serialize, Deserialize)]
+pub struct EncryptedBody {
+    pub nonce: String,      // Base64 12-byte nonce
+    pub ciphertext: String, // Base64 ciphertext + tag
+}
+
+struct Session {
+    client: String, // Signer that established it
+    request_key: Zeroizing<[u8; 32]>,
+    response_key: Zeroizing<[u8; 32]>,
+    expires_at: Instant,
+    // Request nonces already accepted, so captured bodies can't be replayed
+    seen_nonces: HashSet<String>,
+}
+
+pub struct SessionService {
+    ttl: Duration,
+    randomness: Arc<RandomnessSource>,
+    sessions: Mutex<HashMap<String, Session>>,
+}
+
+pub struct Established {
+    pub session_id: String,
+    pub enclave_public_key: PublicKey,
+    pub key_exchange: KeyExchange,
+    /// ML-KEM ciphertext for the client, in hybrid mode
+    pub kem_ciphertext: Option<Vec<u8>>,
+    pub expires_in: Duration,
+}
+
+impl SessionService {
+    pub fn new(ttl: Duration, randomness: Arc<RandomnessSource>) -> Self {
+        Self {
+            ttl,
+            randomness,
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn establish(
+        &self,
+        client: &str,
+        client_public_key: &str,
+        key_exchange: KeyExchange,
+        client_kem_key: Option<&str>,
+    ) -> Result<Established, AppError> {
+        let failed = |e: String| AppError::bad_request("SESSION_ESTABLISH_FAILED", e);
+        let client_public = crypto::decode_public_key(client_public_key).map_err(failed)?;
+        let secret = EphemeralSecret::random_from_rng(self.randomness.rng());
+        let enclave_public = PublicKey::from(&secret);
+        let shared = secret.diffie_hellman(&client_public);
+        if !shared.was_contributory() {
+            return Err(failed("Client public key is a low-order point".to_string()));
+        }
+
+        let mut ikm = Zeroizing::new(shared.as_bytes().to_vec());
+        let mut salt = [
+            client_public.as_bytes().as_slice(),
+            enclave_public.as_bytes(),
+        ]
+        .concat();
+        let kem_ciphertext = match key_exchange {
+            KeyExchange::X25519 => None,
+            KeyExchange::HybridMlKem768 => {
+                let kem_key = client_kem_key
+                    .ok_or_else(|| "Hybrid key exchange requires an ML-KEM public key".to_string())
+                    .and_then(crypto::decode_kem_public_key)
+                    .map_err(failed)?;
+                let (ciphertext, kem_shared) = crypto::kem_encapsulate(&kem_key).map_err(failed)?;
+                ikm.extend_from_slice(kem_shared.as_ref());
+                salt.extend_from_slice(&ciphertext);
+                Some(ciphertext)
+            }
+        };
+        let hkdf = Hkdf::<Sha256>::new(Some(&salt), &ikm);
+
+        let mut request_key = Zeroizing::new([0u8; 32]);
+        let mut response_key = Zeroizing::new([0u8; 32]);
+        hkdf.expand(SESSION_INFO_REQUEST, request_key.as_mut())
+            .and_then(|_| hkdf.expand(SESSION_INFO_RESPONSE, response_key.as_mut()))
+            .map_err(|_| AppError::internal("Session key derivation failed"))?;
+
+        let session_id = self.randomness.id(16);
+
+        let now = Instant::now();
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|_, s| s.expires_at > now);
+        if sessions.values().filter(|s| s.client == client).count() >= MAX_CLIENT_SESSIONS {
+            return Err(AppError::new(
+                StatusCode::TOO_MANY_REQUESTS,
+                "CLIENT_SESSIONS_EXHAUSTED",
+                "Too many open sessions for this signer; let one expire first",
+            ));
+        }
+        if sessions.len() >= MAX_SESSIONS {
+            return Err(AppError::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "SESSIONS_EXHAUSTED",
+                "Too many open sessions; try again later",
+            ));
+        }
+        sessions.insert(
+            session_id.clone(),
+            Session {
+                client: client.to_string(),
+                request_key,
+                response_key,
+                expires_at: now + self.ttl,
+                seen_nonces: HashSet::new(),
+            },
+        );
+
+        Ok(Established {
+            session_id,
+            enclave_public_key: enclave_public,
+            key_exchange,
+            kem_ciphertext,
+            expires_in: self.ttl,
+        })
+    }
+
+    fn decrypt_request(
+        &self,
+        session_id: &str,
+        body: &EncryptedBody,
+        aad: &[u8],
+    ) -> Result<(Vec<u8>, Zeroizing<[u8; 32]>), AppError> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .get_mut(session_id)
+            .filter(|s| s.expires_at > Instant::now())
+            .ok_or_else(|| {
+                AppError::unauthorized("SESSION_EXPIRED", "Unknown or expired session")
+            })?;
+        if session.seen_nonces.len() >= MAX_SESSION_REQUESTS {
+            return Err(AppError::unauthorized(
+                "SESSION_EXHAUSTED",
+                "Session has carried its last request; establish a new one",
+            ));
+        }
+
+        let invalid =
+            || AppError::bad_request("SESSION_DECRYPT_FAILED", "Encrypted body is invalid");
+        let plaintext = crypto::aead_decrypt(
+            &session.request_key,
+            &crypto::decode(&body.nonce).map_err(|_| invalid())?,
+            &crypto::decode(&body.ciphertext).map_err(|_| invalid())?,
+            aad,
+        )
+        .map_err(|_| invalid())?;
+
+        // Only authenticated nonces are remembered
+        if !session.seen_nonces.insert(body.nonce.clone()) {
+            return Err(AppError::new(
+                StatusCode::CONFLICT,
+                "REQUEST_REPLAYED",
+                "Encrypted request was already received",
+            ));
+        }
+
+        Ok((plaintext, session.response_key.clone()))
+    }
+}
+
+/// Decrypts session-encrypted requests and encrypts their responses;
+/// requests without a session header pass through untouched
+pub async fn encrypted_session(
+    State(sessions): State<Arc<SessionService>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let Some(session_id) = request
+        .headers()
+        .get(SESSION_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+    else {
+        return Ok(next.run(request).await);
+    };
+
+    let (mut parts, body) = request.into_parts();
+    let body = to_bytes(body, MAX_ENCRYPTED_BODY_BYTES)
+        .await
+        .map_err(|_| AppError::bad_request("BODY_TOO_LARGE", "Encrypted body too large"))?;
+    let encrypted: EncryptedBody = serde_json::from_slice(&body).map_err(|_| {
+        AppError::bad_request(
+            "SESSION_DECRYPT_FAILED",
+            "Session requests must carry an encrypted body",
+        )
+    })?;
+
+    let path = parts.uri.path().to_string();
+    let request_aad = format!("{}\n{}\n{}", session_id, parts.method.as_str(), path);
+    let (plaintext, response_key) =
+        sessions.decrypt_request(&session_id, &encrypted, request_aad.as_bytes())?;
+
+    parts.headers.remove(header::CONTENT_LENGTH);
+    let response = next
+        .run(Request::from_parts(parts, Body::from(plaintext)))
+        .await;
+
+    // Responses are bound to the request they answer
+    let response_aad = format!("{}\nresponse\n{}", session_id, encrypted.nonce);
+    let (mut parts, body) = response.into_parts();
+    let body = to_bytes(body, MAX_RESPONSE_BODY_BYTES)
+        .await
+        .map_err(|_| AppError::internal("Response too large to encrypt"))?;
+    let (nonce, ciphertext) = crypto::aead_encrypt(&response_key, &body, response_aad.as_bytes())
+        .map_err(AppError::internal)?;
+    let encrypted = serde_json::to_vec(&EncryptedBody {
+        nonce: STANDARD.encode(nonce),
+        ciphertext: STANDARD.encode(ciphertext),
+    })
+    .map_err(|e| AppError::internal(e.to_string()))?;
+
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    Ok(Response::from_parts(parts, Body::from(encrypted)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ml_kem::kem::Decapsulate;
+    use ml_kem::{Ciphertext, EncodedSizeUser, KemCore, MlKem768};
+    use rand::rngs::OsRng;
+
+    fn service() -> SessionService {
+        SessionService::new(Duration::from_secs(60), Arc::new(RandomnessSource::new()))
+    }
+
+    fn establish(sessions: &SessionService, client: &str) -> Result<Established, AppError> {
+        let public = PublicKey::from(&EphemeralSecret::random_from_rng(OsRng));
+        sessions.establish(
+            client,
+            &STANDARD.encode(public.as_bytes()),
+            KeyExchange::X25519,
+            None,
+        )
+    }
+
+    /// A request body encrypted under the session's own request key
+    fn request(sessions: &SessionService, session_id: &str) -> EncryptedBody {
+        let key = sessions.sessions.lock().unwrap()[session_id]
+            .request_key
+            .clone();
+        let (nonce, ciphertext) = crypto::aead_encrypt(&key, b"{}", b"aad").unwrap();
+        EncryptedBody {
+            nonce: STANDARD.encode(nonce),
+            ciphertext: STANDARD.encode(ciphertext),
+        }
+    }
+
+    #[test]
+    fn open_sessions_are_capped() {
+        let sessions = service();
+        for i in 0..MAX_SESSIONS {
+            establish(&sessions, &format!("0x{}", i)).unwrap();
+        }
+        assert!(establish(&sessions, "0xanother").is_err());
+
+        // Expired sessions make room again
+        for session in sessions.sessions.lock().unwrap().values_mut() {
+            session.expires_at = Instant::now();
+        }
+        establish(&sessions, "0xanother").unwrap();
+    }
+
+    #[test]
+    fn one_signer_cannot_take_every_session() {
+        let sessions = service();
+        for _ in 0..MAX_CLIENT_SESSIONS {
+            establish(&sessions, "0xgreedy").unwrap();
+        }
+        let refused = establish(&sessions, "0xgreedy").err().unwrap();
+        assert_eq!(refused.code, "CLIENT_SESSIONS_EXHAUSTED");
+        establish(&sessions, "0xpatient").unwrap();
+
+        for session in sessions.sessions.lock().unwrap().values_mut() {
+            session.expires_at = Instant::now();
+        }
+        establish(&sessions, "0xgreedy").unwrap();
+    }
+
+    #[test]
+    fn requests_are_not_replayed_and_a_session_is_used_up() {
+        let sessions = service();
+        let session_id = establish(&sessions, "0xclient").unwrap().session_id;
+
+        let body = request(&sessions, &session_id);
+        sessions
+            .decrypt_request(&session_id, &body, b"aad")
+            .unwrap();
+        assert!(sessions
+            .decrypt_request(&session_id, &body, b"aad")
+            .is_err());
+
+        sessions
+            .sessions
+            .lock()
+            .unwrap()
+            .get_mut(&session_id)
+            .unwrap()
+            .seen_nonces
+            .extend((0..MAX_SESSION_REQUESTS).map(|i| i.to_string()));
+        let body = request(&sessions, &session_id);
+        assert!(sessions
+            .decrypt_request(&session_id, &body, b"aad")
+            .is_err());
+        let remembered = sessions.sessions.lock().unwrap()[&session_id]
+            .seen_nonces
+            .len();
+        assert_eq!(remembered, MAX_SESSION_REQUESTS + 1);
+    }
+
+    #[test]
+    fn hybrid_sessions_agree_on_keys_with_the_client() {
+        let sessions = service();
+        let client = EphemeralSecret::random_from_rng(OsRng);
+        let client_public = PublicKey::from(&client);
+        let (kem_secret, kem_public) = MlKem768::generate(&mut OsRng);
+        let established = sessions
+            .establish(
+                "0xclient",
+                &STANDARD.encode(client_public.as_bytes()),
+                KeyExchange::HybridMlKem768,
+                Some(STANDARD.encode(kem_public.as_bytes()).as_str()),
+            )
+            .unwrap();
+        let kem_ciphertext = established.kem_ciphertext.unwrap();
+
+        // The client's side of the derivation
+        let mut ikm = client
+            .diffie_hellman(&established.enclave_public_key)
+            .as_bytes()
+            .to_vec();
+        let ciphertext: &Ciphertext<MlKem768> = kem_ciphertext.as_slice().try_into().unwrap();
+        ikm.extend_from_slice(&kem_secret.decapsulate(ciphertext).unwrap());
+        let salt = [
+            client_public.as_bytes().as_slice(),
+            established.enclave_public_key.as_bytes(),
+            &kem_ciphertext,
+        ]
+        .concat();
+        let hkdf = Hkdf::<Sha256>::new(Some(&salt), &ikm);
+        let (mut request_key, mut response_key) = ([0u8; 32], [0u8; 32]);
+        hkdf.expand(SESSION_INFO_REQUEST, &mut request_key).unwrap();
+        hkdf.expand(SESSION_INFO_RESPONSE, &mut response_key)
+            .unwrap();
+
+        let (nonce, ciphertext) = crypto::aead_encrypt(&request_key, b"{}", b"aad").unwrap();
+        let body = EncryptedBody {
+            nonce: STANDARD.encode(nonce),
+            ciphertext: STANDARD.encode(ciphertext),
+        };
+        let (plaintext, enclave_response_key) = sessions
+            .decrypt_request(&established.session_id, &body, b"aad")
+            .unwrap();
+        assert_eq!(plaintext, b"{}");
+        assert_eq!(*enclave_response_key, response_key);
+
+        // Without the KEM public key there is no hybrid session
+        let client = PublicKey::from(&EphemeralSecret::random_from_rng(OsRng));
+        assert!(sessions
+            .establish(
+                "0xclient",
+                &STANDARD.encode(client.as_bytes()),
+                KeyExchange::HybridMlKem768,
+                None,
+            )
+            .is_err());
+    }
+}
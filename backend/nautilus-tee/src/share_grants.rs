@@ -0,0 +1,473 @@
+/**
+ * Share Grants
+ * Links a vault owner hands a beneficiary, redeemable for a capability
+ * once the vault unlocks
+ *
+ * The owner creates a grant with the operations it opens, how long the
+ * link stays redeemable and how long the resulting capability lasts, and
+ * optionally a password and a proof the redeemer must present. The
+ * enclave signs the link with its identity key:
+ *
+ *   link = base64url(claims JSON) "." base64url(ed25519(DOMAIN || claims))
+ *
+ * The grant itself stays in the store, holding only a PBKDF2 hash of the
+ * password. Redeeming checks the link, then the policy engine decides
+ * (the vault's escalation ladder must have reached unlock); an approved
+ * redemption mints a capability for the redeeming address. A link is
+ * redeemed once; the issuer can revoke it until then.
+ */
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use ed25519_dalek::Signature;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use ring::pbkdf2;
+use serde::{Deserialize, Serialize};
+use std::num::NonZeroU32;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::capability::{self, CapabilityClaims, CapabilityService};
+use crate::keys::identity::EnclaveIdentity;
+use crate::store::Store;
+use crate::validation::ClaimType;
+
+const DOMAIN: &[u8] = b"lumina-share-v1";
+const NAMESPACE: &str = "share_grants";
+pub const MAX_LINK_TTL_SECS: u64 = 365 * 24 * 60 * 60;
+const MIN_PASSWORD_LEN: usize = 8;
+const PBKDF2_ITERATIONS: u32 = 100_000;
+const SALT_BYTES: usize = 16;
+
+/// What the signed link carries
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LinkClaims {
+    pub grant_id: String,
+    pub vault_id: String,
+    pub expires_at: u64,
+}
+
+/// A proof the redeemer must present: one that verifies against the
+/// version's pinned key with exactly these public signals
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ProofRequirement {
+    pub claim_type: ClaimType,
+    pub version: u32,
+    pub public_signals: Vec<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PasswordHash {
+    pub salt: String, // Hex
+    pub hash: String, // Hex PBKDF2-HMAC-SHA256
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Redemption {
+    pub holder: String,
+    pub token_id: String,
+    pub redeemed_at: u64,
+}
+
+/// Persisted per grant
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ShareGrant {
+    pub grant_id: String,
+    pub vault_id: String,
+    pub issuer: String,
+    pub operations: Vec<String>,
+    pub capability_ttl_secs: u64,
+    pub created_at: u64,
+    pub expires_at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<PasswordHash>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proof: Option<ProofRequirement>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub redemption: Option<Redemption>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revoked_at: Option<u64>,
+}
+
+/// What the owner chooses for a new grant
+pub struct GrantTerms {
+    pub operations: Vec<String>,
+    pub link_ttl_secs: u64,
+    pub capability_ttl_secs: u64,
+    pub password: Option<String>,
+    pub proof: Option<ProofRequirement>,
+}
+
+pub struct ShareGrantService {
+    identity: Arc<EnclaveIdentity>,
+    store: Arc<Store>,
+    capabilities: Arc<CapabilityService>,
+    /// Serializes redemptions, so a link can't be redeemed twice at once
+    redeeming: Mutex<()>,
+}
+
+impl ShareGrantService {
+    pub fn new(
+        identity: Arc<EnclaveIdentity>,
+        store: Arc<Store>,
+        capabilities: Arc<CapabilityService>,
+    ) -> Self {
+        Self {
+            identity,
+            store,
+            capabilities,
+            redeeming: Mutex::new(()),
+        }
+    }
+
+    /// Create a grant and its link; the caller must already be allowed to
+    /// grant on the vault
+    pub fn create(
+        &self,
+        vault_id: &str,
+        issuer: &str,
+        terms: GrantTerms,
+    ) -> Result<(String, ShareGrant), String> {
+        if terms.link_ttl_secs == 0 || terms.link_ttl_secs > MAX_LINK_TTL_SECS {
+            return Err(format!(
+                "Share links last between 1 and {} seconds",
+                MAX_LINK_TTL_SECS
+            ));
+        }
+        if terms.capability_ttl_secs == 0 || terms.capability_ttl_secs > capability::MAX_TTL_SECS {
+            return Err(format!(
+                "Capabilities last between 1 and {} seconds",
+                capability::MAX_TTL_SECS
+            ));
+        }
+        let operations = capability::check_operations(&terms.operations)?;
+        let password = terms.password.as_deref().map(hash_password).transpose()?;
+        if terms
+            .proof
+            .as_ref()
+            .is_some_and(|p| p.public_signals.is_empty())
+        {
+            return Err("A proof requirement names its public signals".to_string());
+        }
+
+        let created_at = now();
+        let grant = ShareGrant {
+            grant_id: random_id(),
+            vault_id: vault_id.to_string(),
+            issuer: issuer.to_string(),
+            operations,
+            capability_ttl_secs: terms.capability_ttl_secs,
+            created_at,
+            expires_at: created_at + terms.link_ttl_secs,
+            password,
+            proof: terms.proof,
+            redemption: None,
+            revoked_at: None,
+        };
+        let claims = LinkClaims {
+            grant_id: grant.grant_id.clone(),
+            vault_id: grant.vault_id.clone(),
+            expires_at: grant.expires_at,
+        };
+        let payload = serde_json::to_vec(&claims).map_err(|e| e.to_string())?;
+        let signature = self.identity.sign(&signed_message(&payload));
+        let link = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(&payload),
+            URL_SAFE_NO_PAD.encode(signature.to_bytes())
+        );
+        self.store.put(NAMESPACE, &grant.grant_id, &grant)?;
+        Ok((link, grant))
+    }
+
+    /// The grant behind a link this enclave signed, while it can still be
+    /// redeemed
+    pub fn open(&self, link: &str) -> Result<ShareGrant, String> {
+        let malformed = || "Malformed share link".to_string();
+        let (payload, signature) = link.split_once('.').ok_or_else(malformed)?;
+        let payload = URL_SAFE_NO_PAD.decode(payload).map_err(|_| malformed())?;
+        let signature: [u8; 64] = URL_SAFE_NO_PAD
+            .decode(signature)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(malformed)?;
+
+        self.identity
+            .public_key()
+            .verify_strict(
+                &signed_message(&payload),
+                &Signature::from_bytes(&signature),
+            )
+            .map_err(|_| "Share link was not issued by this enclave".to_string())?;
+        let claims: LinkClaims = serde_json::from_slice(&payload).map_err(|_| malformed())?;
+
+        if claims.expires_at <= now() {
+            return Err("Share link has expired".to_string());
+        }
+        let grant: ShareGrant = self
+            .store
+            .get(NAMESPACE, &claims.grant_id)?
+            .filter(|g: &ShareGrant| g.vault_id == claims.vault_id)
+            .ok_or("Unknown share link")?;
+        if grant.revoked_at.is_some() {
+            return Err("Share link has been revoked".to_string());
+        }
+        if grant.redemption.is_some() {
+            return Err("Share link has already been redeemed".to_string());
+        }
+        Ok(grant)
+    }
+
+    /// Whether `password` satisfies the grant; true when it needs none
+    pub fn check_password(&self, grant: &ShareGrant, password: Option<&str>) -> bool {
+        let Some(stored) = &grant.password else {
+            return true;
+        };
+        let (Some(password), Ok(salt), Ok(hash)) = (
+            password,
+            hex::decode(&stored.salt),
+            hex::decode(&stored.hash),
+        ) else {
+            return false;
+        };
+        pbkdf2::verify(
+            pbkdf2::PBKDF2_HMAC_SHA256,
+            iterations(),
+            &salt,
+            password.as_bytes(),
+            &hash,
+        )
+        .is_ok()
+    }
+
+    /// Mint the grant's capability for `holder`; the policy engine must
+    /// already have approved the redemption
+    pub fn redeem(
+        &self,
+        grant_id: &str,
+        holder: &str,
+    ) -> Result<(String, CapabilityClaims), String> {
+        let _redeeming = self.redeeming.lock().unwrap();
+        let mut grant: ShareGrant = self
+            .store
+            .get(NAMESPACE, grant_id)?
+            .ok_or("Unknown share link")?;
+        if grant.revoked_at.is_some() || grant.redemption.is_some() {
+            return Err("Share link can no longer be redeemed".to_string());
+        }
+
+        let (token, claims) = self.capabilities.mint(
+            &grant.vault_id,
+            &grant.issuer,
+            holder,
+            &grant.operations,
+            grant.capability_ttl_secs,
+        )?;
+        grant.redemption = Some(Redemption {
+            holder: claims.holder.clone(),
+            token_id: claims.token_id.clone(),
+            redeemed_at: claims.issued_at,
+        });
+        self.store.put(NAMESPACE, grant_id, &grant)?;
+        Ok((token, claims))
+    }
+
+    /// Only the issuer may revoke; revoking twice is harmless. Revoking
+    /// doesn't reach a capability already redeemed, which is revoked on its
+    /// own
+    pub fn revoke(
+        &self,
+        vault_id: &str,
+        grant_id: &str,
+        caller: &str,
+    ) -> Result<ShareGrant, String> {
+        let mut grant: ShareGrant = self
+            .store
+            .get(NAMESPACE, grant_id)?
+            .filter(|g: &ShareGrant| g.vault_id == vault_id)
+            .ok_or("Unknown share grant")?;
+        if grant.issuer != caller {
+            return Err("Only the issuer may revoke a share grant".to_string());
+        }
+        if grant.revoked_at.is_none() {
+            grant.revoked_at = Some(now());
+            self.store.put(NAMESPACE, grant_id, &grant)?;
+        }
+        Ok(grant)
+    }
+
+    /// Erase every grant made for the vault; returns their ids
+    pub fn erase_vault(&self, vault_id: &str) -> Result<Vec<String>, String> {
+        let ids: Vec<String> = self
+            .store
+            .list::<ShareGrant>(NAMESPACE)?
+            .into_iter()
+            .filter(|(_, grant)| grant.vault_id == vault_id)
+            .map(|(id, _)| id)
+            .collect();
+        if !ids.is_empty() {
+            self.store.erase(NAMESPACE, &ids)?;
+        }
+        Ok(ids)
+    }
+}
+
+fn hash_password(password: &str) -> Result<PasswordHash, String> {
+    if password.chars().count() < MIN_PASSWORD_LEN {
+        return Err(format!(
+            "Share passwords are at least {} characters",
+            MIN_PASSWORD_LEN
+        ));
+    }
+    let mut salt = [0u8; SALT_BYTES];
+    OsRng.fill_bytes(&mut salt);
+    let mut hash = [0u8; 32];
+    pbkdf2::derive(
+        pbkdf2::PBKDF2_HMAC_SHA256,
+        iterations(),
+        &salt,
+        password.as_bytes(),
+        &mut hash,
+    );
+    Ok(PasswordHash {
+        salt: hex::encode(salt),
+        hash: hex::encode(hash),
+    })
+}
+
+fn iterations() -> NonZeroU32 {
+    NonZeroU32::new(PBKDF2_ITERATIONS).unwrap()
+}
+
+fn signed_message(payload: &[u8]) -> Vec<u8> {
+    [DOMAIN, payload].concat()
+}
+
+fn random_id() -> String {
+    let mut id = [0u8; 16];
+    OsRng.fill_bytes(&mut id);
+    hex::encode(id)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AUDIT_ROUTE: &str = "/vault/:vault_id/audit/export";
+    const AUDIT_PATH: &str = "/vault/vault-1/audit/export";
+
+    fn service(name: &str) -> ShareGrantService {
+        let dir = std::env::temp_dir().join(format!("lumina-share-grant-tests-{}-{}", name, now()));
+        let identity = Arc::new(EnclaveIdentity::ephemeral(&"00".repeat(32), "test"));
+        let store = Arc::new(Store::open(dir).unwrap());
+        let capabilities = Arc::new(CapabilityService::new(identity.clone(), store.clone()));
+        ShareGrantService::new(identity, store, capabilities)
+    }
+
+    fn terms(password: Option<&str>) -> GrantTerms {
+        GrantTerms {
+            operations: vec!["audit".to_string()],
+            link_ttl_secs: 60,
+            capability_ttl_secs: 60,
+            password: password.map(str::to_string),
+            proof: None,
+        }
+    }
+
+    /// A link over `claims` signed by `identity`, as create would produce
+    fn link_for(identity: &EnclaveIdentity, claims: &LinkClaims) -> String {
+        let payload = serde_json::to_vec(claims).unwrap();
+        let signature = identity.sign(&signed_message(&payload));
+        format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(&payload),
+            URL_SAFE_NO_PAD.encode(signature.to_bytes())
+        )
+    }
+
+    #[test]
+    fn links_redeem_once_for_a_capability_on_the_granted_vault() {
+        let shares = service("redeem");
+        let (link, _) = shares
+            .create("vault-1", "0xowner", terms(Some("correct horse")))
+            .unwrap();
+
+        let grant = shares.open(&link).unwrap();
+        assert!(grant
+            .password
+            .as_ref()
+            .is_some_and(|p| !p.hash.contains("horse")));
+        assert!(shares.check_password(&grant, Some("correct horse")));
+        assert!(!shares.check_password(&grant, Some("wrong horse")));
+        assert!(!shares.check_password(&grant, None));
+
+        let (token, claims) = shares.redeem(&grant.grant_id, "0xb0b").unwrap();
+        assert_eq!(
+            (claims.vault_id.as_str(), claims.issuer.as_str()),
+            ("vault-1", "0xowner")
+        );
+        shares
+            .capabilities
+            .authorize(&token, &claims.holder, Some(AUDIT_ROUTE), AUDIT_PATH)
+            .unwrap();
+
+        assert!(shares
+            .open(&link)
+            .err()
+            .unwrap()
+            .contains("already been redeemed"));
+        assert!(shares.redeem(&grant.grant_id, "0xeve").is_err());
+    }
+
+    #[test]
+    fn forged_expired_and_revoked_links_are_refused() {
+        let shares = service("refused");
+        let (link, grant) = shares.create("vault-1", "0xowner", terms(None)).unwrap();
+        let claims = LinkClaims {
+            grant_id: grant.grant_id.clone(),
+            vault_id: grant.vault_id.clone(),
+            expires_at: grant.expires_at,
+        };
+        let refused = |link: &str| shares.open(link).err().unwrap();
+
+        // Pointing the link at another vault breaks the enclave's signature
+        let mut moved = claims.clone();
+        moved.vault_id = "vault-2".to_string();
+        let (_, signature) = link.split_once('.').unwrap();
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&moved).unwrap());
+        let forged = format!("{}.{}", payload, signature);
+        assert!(refused(&forged).contains("not issued by this enclave"));
+
+        let elsewhere = EnclaveIdentity::ephemeral(&"00".repeat(32), "test");
+        assert!(refused(&link_for(&elsewhere, &claims)).contains("not issued by this enclave"));
+
+        let mut expired = claims.clone();
+        expired.expires_at = now() - 1;
+        assert!(refused(&link_for(&shares.identity, &expired)).contains("expired"));
+
+        let moved = link_for(&shares.identity, &moved);
+        assert!(refused(&moved).contains("Unknown share link"));
+        assert!(refused("not-a-link").contains("Malformed"));
+
+        assert!(shares.revoke("vault-1", &grant.grant_id, "0xb0b").is_err());
+        shares
+            .revoke("vault-1", &grant.grant_id, "0xowner")
+            .unwrap();
+        assert!(refused(&link).contains("revoked"));
+        assert!(shares.redeem(&grant.grant_id, "0xb0b").is_err());
+
+        let weak = shares.create("vault-1", "0xowner", terms(Some("short")));
+        assert!(weak.err().unwrap().contains("at least"));
+        let mut forever = terms(None);
+        forever.link_ttl_secs = MAX_LINK_TTL_SECS + 1;
+        assert!(shares.create("vault-1", "0xowner", forever).is_err());
+    }
+}
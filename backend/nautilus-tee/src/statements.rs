@@ -0,0 +1,296 @@
+/**
+ * Financial Statements
+ * Account balances from common statement formats, canonicalized for the
+ * solvency circuit
+ *
+ *   csv:  a header row with a balance column; with an account column, each
+ *         account's last row is its closing balance, otherwise the last row
+ *   ofx:  LEDGERBAL/BALAMT of each bank and card statement (SGML or XML)
+ *   json: {"currency"?, "accounts": [{"balance", "currency"?}]}
+ *
+ * Amounts become integer minor units (at most two decimal places).
+ * Positive balances are assets and negative ones liabilities. Accounts
+ * must all be in the claim's currency; nothing is converted. Account
+ * identifiers are only used to pick closing balances and never reach the
+ * circuit.
+ */
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::field;
+
+/// Account slots in the solvency circuit
+pub const MAX_ACCOUNTS: usize = 32;
+const BALANCE_COLUMNS: &[&str] = &[
+    "balance",
+    "closing balance",
+    "ledger balance",
+    "current balance",
+    "available balance",
+];
+const ACCOUNT_COLUMNS: &[&str] = &["account", "account id", "account number", "iban"];
+const CURRENCY_COLUMNS: &[&str] = &["currency", "ccy"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatementFormat {
+    Csv,
+    Ofx,
+    Json,
+}
+
+#[derive(Deserialize)]
+pub struct SolvencyClaim {
+    /// Decimal amount in `currency`, e.g. "25000.00"
+    pub threshold: String,
+    pub currency: String, // ISO 4217
+    /// Detected from the content when omitted
+    pub format: Option<StatementFormat>,
+}
+
+/// Witness for the solvency circuit
+pub struct SolvencyWitness {
+    /// Circuit input signals, by name
+    pub inputs: Value,
+    /// Public signals in the order the verifier sees them: the result,
+    /// then the public inputs in declaration order
+    pub public_signals: Vec<String>,
+}
+
+struct Balance {
+    currency: Option<String>,
+    minor_units: i64,
+}
+
+#[derive(Deserialize)]
+struct JsonStatement {
+    currency: Option<String>,
+    accounts: Vec<JsonAccount>,
+}
+
+#[derive(Deserialize)]
+struct JsonAccount {
+    balance: Value, // String or number
+    currency: Option<String>,
+}
+
+impl SolvencyClaim {
+    pub fn witness(&self, statement: &[u8]) -> Result<SolvencyWitness, String> {
+        let threshold = parse_amount(&self.threshold)?;
+        if threshold < 0 {
+            return Err("Threshold must not be negative".to_string());
+        }
+
+        let text = std::str::from_utf8(statement)
+            .map_err(|_| "Statement must be UTF-8 text".to_string())?;
+        let balances = match self.format.unwrap_or_else(|| detect(text)) {
+            StatementFormat::Csv => csv_balances(text)?,
+            StatementFormat::Ofx => ofx_balances(text)?,
+            StatementFormat::Json => json_balances(text)?,
+        };
+        if balances.is_empty() || balances.len() > MAX_ACCOUNTS {
+            return Err(format!(
+                "Statement must hold 1 to {} accounts, found {}",
+                MAX_ACCOUNTS,
+                balances.len()
+            ));
+        }
+        if let Some(other) = balances
+            .iter()
+            .filter_map(|b| b.currency.as_deref())
+            .find(|c| !c.eq_ignore_ascii_case(&self.currency))
+        {
+            return Err(format!(
+                "Statement has {} balances; the claim is in {}",
+                other, self.currency
+            ));
+        }
+
+        let mut assets = vec![0u64; MAX_ACCOUNTS];
+        let mut liabilities = vec![0u64; MAX_ACCOUNTS];
+        for (i, balance) in balances.iter().enumerate() {
+            if balance.minor_units >= 0 {
+                assets[i] = balance.minor_units.unsigned_abs();
+            } else {
+                liabilities[i] = balance.minor_units.unsigned_abs();
+            }
+        }
+        let net = assets.iter().map(|&a| i128::from(a)).sum::<i128>()
+            - liabilities.iter().map(|&l| i128::from(l)).sum::<i128>();
+        let solvent = net >= i128::from(threshold);
+        let digest = field::truncated(&Sha256::digest(statement));
+
+        let to_strings =
+            |amounts: &[u64]| -> Vec<String> { amounts.iter().map(u64::to_string).collect() };
+        Ok(SolvencyWitness {
+            inputs: json!({
+                "threshold": threshold.to_string(),
+                "statement_digest": digest,
+                "assets": to_strings(&assets),
+                "liabilities": to_strings(&liabilities),
+            }),
+            public_signals: vec![
+                u32::from(solvent).to_string(),
+                threshold.to_string(),
+                digest,
+            ],
+        })
+    }
+}
+
+fn detect(text: &str) -> StatementFormat {
+    let start = text.trim_start();
+    if start.starts_with('{') {
+        StatementFormat::Json
+    } else if start.starts_with("OFXHEADER") || text.contains("<OFX>") {
+        StatementFormat::Ofx
+    } else {
+        StatementFormat::Csv
+    }
+}
+
+fn csv_balances(text: &str) -> Result<Vec<Balance>, String> {
+    let mut rows = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(csv_fields);
+    let header: Vec<String> = rows
+        .next()
+        .ok_or_else(|| "Empty CSV statement".to_string())?
+        .iter()
+        .map(|h| h.trim().to_ascii_lowercase().replace('_', " "))
+        .collect();
+    let column = |names: &[&str]| header.iter().position(|h| names.contains(&h.as_str()));
+    let balance_col =
+        column(BALANCE_COLUMNS).ok_or_else(|| "CSV statement has no balance column".to_string())?;
+    let account_col = column(ACCOUNT_COLUMNS);
+    let currency_col = column(CURRENCY_COLUMNS);
+
+    // Closing balance per account, in order of first appearance
+    let mut accounts: Vec<(String, Balance)> = Vec::new();
+    for (line, fields) in rows.enumerate() {
+        let field = |col: usize| fields.get(col).map(|f| f.trim()).unwrap_or("");
+        let balance = Balance {
+            currency: currency_col
+                .map(field)
+                .filter(|c| !c.is_empty())
+                .map(str::to_string),
+            minor_units: parse_amount(field(balance_col))
+                .map_err(|e| format!("CSV row {}: {}", line + 2, e))?,
+        };
+        let account = account_col.map(field).unwrap_or("").to_string();
+        match accounts.iter_mut().find(|(a, _)| *a == account) {
+            Some((_, closing)) => *closing = balance,
+            None => accounts.push((account, balance)),
+        }
+    }
+    Ok(accounts.into_iter().map(|(_, balance)| balance).collect())
+}
+
+/// One CSV record, honoring double-quoted fields
+fn csv_fields(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                current.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    fields.push(current);
+    fields
+}
+
+fn ofx_balances(text: &str) -> Result<Vec<Balance>, String> {
+    let mut balances = Vec::new();
+    for tag in ["<STMTRS>", "<CCSTMTRS>"] {
+        for (start, _) in text.match_indices(tag) {
+            let block = &text[start + tag.len()..];
+            let block = &block[..block.find("</STMTRS>").unwrap_or(block.len())];
+            let block = &block[..block.find("</CCSTMTRS>").unwrap_or(block.len())];
+            let ledger = block
+                .find("<LEDGERBAL>")
+                .map(|i| &block[i..])
+                .ok_or_else(|| "OFX statement has no LEDGERBAL".to_string())?;
+            let amount = ofx_value(ledger, "BALAMT")
+                .ok_or_else(|| "OFX LEDGERBAL has no BALAMT".to_string())?;
+            balances.push(Balance {
+                currency: ofx_value(block, "CURDEF").map(str::to_string),
+                minor_units: parse_amount(amount)?,
+            });
+        }
+    }
+    Ok(balances)
+}
+
+/// Value of an OFX element; SGML elements have no closing tag, so it runs
+/// to the next tag or line end
+fn ofx_value<'a>(text: &'a str, tag: &str) -> Option<&'a str> {
+    let open = format!("<{}>", tag);
+    let start = text.find(&open)? + open.len();
+    let rest = &text[start..];
+    let end = rest.find(['<', '\n', '\r']).unwrap_or(rest.len());
+    Some(rest[..end].trim()).filter(|v| !v.is_empty())
+}
+
+fn json_balances(text: &str) -> Result<Vec<Balance>, String> {
+    let statement: JsonStatement =
+        serde_json::from_str(text).map_err(|e| format!("Invalid JSON statement: {}", e))?;
+    statement
+        .accounts
+        .into_iter()
+        .map(|account| {
+            let amount = match &account.balance {
+                Value::String(s) => s.clone(),
+                Value::Number(n) => n.to_string(),
+                _ => return Err("Account balances must be strings or numbers".to_string()),
+            };
+            Ok(Balance {
+                currency: account.currency.or_else(|| statement.currency.clone()),
+                minor_units: parse_amount(&amount)?,
+            })
+        })
+        .collect()
+}
+
+/// Decimal amount to minor units: "1,234.5" -> 123450, "(12.00)" -> -1200
+fn parse_amount(amount: &str) -> Result<i64, String> {
+    let invalid = || format!("Invalid amount {:?}", amount);
+    let trimmed = amount.trim();
+    let (negative, digits) =
+        if let Some(inner) = trimmed.strip_prefix('(').and_then(|s| s.strip_suffix(')')) {
+            (true, inner)
+        } else if let Some(rest) = trimmed.strip_prefix('-') {
+            (true, rest)
+        } else {
+            (false, trimmed.strip_prefix('+').unwrap_or(trimmed))
+        };
+    let digits: String = digits.chars().filter(|&c| c != ',').collect();
+    let (whole, fraction) = digits.split_once('.').unwrap_or((&digits, ""));
+    if whole.is_empty()
+        || fraction.len() > 2
+        || !whole
+            .bytes()
+            .chain(fraction.bytes())
+            .all(|b| b.is_ascii_digit())
+    {
+        return Err(invalid());
+    }
+
+    let whole: i64 = whole.parse().map_err(|_| invalid())?;
+    let cents: i64 = format!("{:0<2}", fraction).parse().map_err(|_| invalid())?;
+    let minor_units = whole
+        .checked_mul(100)
+        .and_then(|w| w.checked_add(cents))
+        .ok_or_else(invalid)?;
+    Ok(if negative { -minor_units } else { minor_units })
+}
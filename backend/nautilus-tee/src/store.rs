@@ -0,0 +1,422 @@
+/**
+ * Enclave Store
+ * Namespaced key-value persistence for state that must survive restarts
+ *
+ * Each namespace is a JSON file under the data directory, rewritten
+ * atomically (temp file + rename) on every change and cached in memory.
+ * Erasing records also overwrites the replaced file's contents in place
+ * and evicts the namespace from the cache.
+ *
+ * Namespaces that grow without bound and are mostly read back in bulk
+ * (TEE_STORE_COMPRESS, by default the audit trail and escrow exports) are
+ * written zstd-compressed as <namespace>.json.zst. Either format is read,
+ * so a namespace moves between them on its next write.
+ *
+ * A Batch changes several namespaces together. Their new contents go to a
+ * journal file first, then each namespace is written and the journal
+ * removed; a journal still there at open is replayed, so a crash part way
+ * through never leaves some of a batch applied and the rest lost.
+ */
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+use tracing::warn;
+
+type Namespace = BTreeMap<String, Value>;
+
+const COMPRESSION_LEVEL: i32 = 9;
+const JOURNAL: &str = ".batch-journal";
+
+pub struct Store {
+    dir: PathBuf,
+    namespaces: RwLock<HashMap<String, Namespace>>,
+    /// Encoded size of each namespace as last read or written, for the
+    /// memory budget
+    sizes: Mutex<HashMap<String, usize>>,
+    compressed: HashSet<String>,
+}
+
+/// Changes to apply across namespaces all at once (see Store::commit)
+#[derive(Default)]
+pub struct Batch {
+    /// (namespace, key, new value or None to remove), in order
+    changes: Vec<(String, String, Option<Value>)>,
+}
+
+impl Batch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn put<T: Serialize>(
+        &mut self,
+        namespace: &str,
+        key: &str,
+        value: &T,
+    ) -> Result<(), String> {
+        let value = serde_json::to_value(value)
+            .map_err(|e| format!("Failed to serialize {}/{}: {}", namespace, key, e))?;
+        self.changes
+            .push((namespace.to_string(), key.to_string(), Some(value)));
+        Ok(())
+    }
+
+    pub fn remove(&mut self, namespace: &str, key: &str) {
+        self.changes
+            .push((namespace.to_string(), key.to_string(), None));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl Store {
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self, String> {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create data dir {}: {}", dir.display(), e))?;
+
+        let store = Self {
+            dir,
+            namespaces: RwLock::new(HashMap::new()),
+            sizes: Mutex::new(HashMap::new()),
+            compressed: HashSet::new(),
+        };
+        store.replay_journal()?;
+        Ok(store)
+    }
+
+    /// Persist these namespaces zstd-compressed
+    pub fn with_compression(mut self, namespaces: &[String]) -> Self {
+        self.compressed = namespaces.iter().cloned().collect();
+        self
+    }
+
+    pub fn get<T: DeserializeOwned>(
+        &self,
+        namespace: &str,
+        key: &str,
+    ) -> Result<Option<T>, String> {
+        self.with_namespace(namespace, |ns| {
+            ns.get(key)
+                .map(|v| serde_json::from_value(v.clone()))
+                .transpose()
+                .map_err(|e| format!("Corrupt {}/{}: {}", namespace, key, e))
+        })
+    }
+
+    pub fn put<T: Serialize>(&self, namespace: &str, key: &str, value: &T) -> Result<(), String> {
+        let value = serde_json::to_value(value)
+            .map_err(|e| format!("Failed to serialize {}/{}: {}", namespace, key, e))?;
+        self.update(namespace, |ns| {
+            ns.insert(key.to_string(), value);
+        })
+    }
+
+    /// All entries in a namespace, ordered by key
+    pub fn list<T: DeserializeOwned>(&self, namespace: &str) -> Result<Vec<(String, T)>, String> {
+        self.with_namespace(namespace, |ns| {
+            ns.iter()
+                .map(|(k, v)| {
+                    serde_json::from_value(v.clone())
+                        .map(|v| (k.clone(), v))
+                        .map_err(|e| format!("Corrupt {}/{}: {}", namespace, k, e))
+                })
+                .collect()
+        })
+    }
+
+    /// Apply several changes to a namespace and persist them in one write
+    pub fn update<F>(&self, namespace: &str, f: F) -> Result<(), String>
+    where
+        F: FnOnce(&mut Namespace),
+    {
+        let mut namespaces = self.namespaces.write().unwrap();
+        if !namespaces.contains_key(namespace) {
+            let loaded = self.load(namespace)?;
+            namespaces.insert(namespace.to_string(), loaded);
+        }
+
+        let ns = namespaces.get_mut(namespace).unwrap();
+        let mut updated = ns.clone();
+        f(&mut updated);
+        self.persist(namespace, &updated)?;
+        *ns = updated;
+        Ok(())
+    }
+
+    /// Apply a batch so that after a crash either all of it or none of it
+    /// is on disk
+    pub fn commit(&self, batch: Batch) -> Result<(), String> {
+        let mut namespaces = self.namespaces.write().unwrap();
+        let mut updated: BTreeMap<String, Namespace> = BTreeMap::new();
+        for (namespace, key, value) in batch.changes {
+            if !updated.contains_key(&namespace) {
+                let current = match namespaces.get(&namespace) {
+                    Some(ns) => ns.clone(),
+                    None => self.load(&namespace)?,
+                };
+                updated.insert(namespace.clone(), current);
+            }
+            let ns = updated.get_mut(&namespace).unwrap();
+            match value {
+                Some(value) => ns.insert(key, value),
+                None => ns.remove(&key),
+            };
+        }
+
+        // One namespace is already written atomically
+        let journaled = updated.len() > 1;
+        if journaled {
+            self.write_journal(&updated)?;
+        }
+        for (namespace, ns) in &updated {
+            if let Err(e) = self.persist(namespace, ns) {
+                // Some files may hold the batch already; reload them from
+                // disk, and leave the journal to finish the rest at open
+                for namespace in updated.keys() {
+                    namespaces.remove(namespace);
+                }
+                return Err(e);
+            }
+        }
+        if journaled {
+            std::fs::remove_file(self.dir.join(JOURNAL))
+                .map_err(|e| format!("Failed to clear store journal: {}", e))?;
+        }
+        namespaces.extend(updated);
+        Ok(())
+    }
+
+    /// Remove `keys` for good: once the remaining records are written,
+    /// the replaced file is zeroed and synced, and the cached copy is
+    /// dropped so the next read reloads from disk
+    pub fn erase(&self, namespace: &str, keys: &[String]) -> Result<(), String> {
+        let failed = |e: std::io::Error| format!("Failed to erase from {}: {}", namespace, e);
+        let mut namespaces = self.namespaces.write().unwrap();
+        let mut ns = match namespaces.remove(namespace) {
+            Some(ns) => ns,
+            None => self.load(namespace)?,
+        };
+        for key in keys {
+            ns.remove(key);
+        }
+
+        // Held open across the rename, so the old contents stay reachable
+        // for overwriting without the namespace ever being unreadable
+        let mut previous = None;
+        for path in [self.path(namespace), self.alternate_path(namespace)] {
+            match OpenOptions::new().write(true).open(path) {
+                Ok(file) => {
+                    previous = Some(file);
+                    break;
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(failed(e)),
+            }
+        }
+        self.persist(namespace, &ns)?;
+        if let Some(mut file) = previous {
+            let len = file.metadata().map_err(failed)?.len() as usize;
+            file.write_all(&vec![0u8; len]).map_err(failed)?;
+            file.sync_all().map_err(failed)?;
+        }
+        self.sizes.lock().unwrap().remove(namespace);
+        Ok(())
+    }
+
+    fn with_namespace<R>(
+        &self,
+        namespace: &str,
+        f: impl FnOnce(&Namespace) -> Result<R, String>,
+    ) -> Result<R, String> {
+        if let Some(ns) = self.namespaces.read().unwrap().get(namespace) {
+            return f(ns);
+        }
+
+        let mut namespaces = self.namespaces.write().unwrap();
+        if !namespaces.contains_key(namespace) {
+            let loaded = self.load(namespace)?;
+            namespaces.insert(namespace.to_string(), loaded);
+        }
+        f(&namespaces[namespace])
+    }
+
+    /// Every namespace on disk, read while no write can land, so no batch
+    /// is half in it
+    pub fn snapshot(&self) -> Result<BTreeMap<String, BTreeMap<String, Value>>, String> {
+        let failed = |e: std::io::Error| format!("Failed to list data dir: {}", e);
+        let namespaces = self.namespaces.read().unwrap();
+        let mut snapshot = BTreeMap::new();
+        for entry in std::fs::read_dir(&self.dir).map_err(failed)? {
+            let name = entry.map_err(failed)?.file_name();
+            let name = name.to_string_lossy();
+            let Some(namespace) = name
+                .strip_suffix(".json.zst")
+                .or_else(|| name.strip_suffix(".json"))
+            else {
+                continue;
+            };
+            let ns = match namespaces.get(namespace) {
+                Some(ns) => ns.clone(),
+                None => {
+                    // Read past the cache, so not held afterwards
+                    let ns = self.load(namespace)?;
+                    self.sizes.lock().unwrap().remove(namespace);
+                    ns
+                }
+            };
+            snapshot.insert(namespace.to_string(), ns);
+        }
+        Ok(snapshot)
+    }
+
+    /// Namespaces on disk whose names start with `prefix`, in order
+    pub fn namespaces(&self, prefix: &str) -> Result<Vec<String>, String> {
+        let failed = |e: std::io::Error| format!("Failed to list data dir: {}", e);
+        let mut names = Vec::new();
+        for entry in std::fs::read_dir(&self.dir).map_err(failed)? {
+            let name = entry.map_err(failed)?.file_name();
+            let name = name.to_string_lossy();
+            if let Some(namespace) = name
+                .strip_suffix(".json.zst")
+                .or_else(|| name.strip_suffix(".json"))
+                .filter(|namespace| namespace.starts_with(prefix))
+            {
+                names.push(namespace.to_string());
+            }
+        }
+        names.sort();
+        names.dedup();
+        Ok(names)
+    }
+
+    /// Delete a whole namespace, in either format
+    pub fn drop_namespace(&self, namespace: &str) -> Result<(), String> {
+        let mut namespaces = self.namespaces.write().unwrap();
+        for path in [self.path(namespace), self.alternate_path(namespace)] {
+            match std::fs::remove_file(path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(format!("Failed to drop namespace {}: {}", namespace, e)),
+            }
+        }
+        namespaces.remove(namespace);
+        self.sizes.lock().unwrap().remove(namespace);
+        Ok(())
+    }
+
+    /// Approximate memory held by cached namespaces
+    pub fn cached_bytes(&self) -> usize {
+        self.sizes.lock().unwrap().values().sum()
+    }
+
+    fn path(&self, namespace: &str) -> PathBuf {
+        if self.compressed.contains(namespace) {
+            self.dir.join(format!("{}.json.zst", namespace))
+        } else {
+            self.dir.join(format!("{}.json", namespace))
+        }
+    }
+
+    /// Where the namespace lives in the format it isn't written in
+    fn alternate_path(&self, namespace: &str) -> PathBuf {
+        if self.compressed.contains(namespace) {
+            self.dir.join(format!("{}.json", namespace))
+        } else {
+            self.dir.join(format!("{}.json.zst", namespace))
+        }
+    }
+
+    fn write_journal(&self, namespaces: &BTreeMap<String, Namespace>) -> Result<(), String> {
+        let failed = |e: std::io::Error| format!("Failed to write store journal: {}", e);
+        let json = serde_json::to_vec(namespaces)
+            .map_err(|e| format!("Failed to serialize store journal: {}", e))?;
+        let path = self.dir.join(JOURNAL);
+        let tmp = PathBuf::from(format!("{}.tmp", path.display()));
+        let mut file = std::fs::File::create(&tmp).map_err(failed)?;
+        file.write_all(&json)
+            .and_then(|_| file.sync_all())
+            .and_then(|_| std::fs::rename(&tmp, &path))
+            .map_err(failed)
+    }
+
+    /// Finish a batch a crash interrupted
+    fn replay_journal(&self) -> Result<(), String> {
+        let path = self.dir.join(JOURNAL);
+        let bytes = match std::fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(format!("Failed to read store journal: {}", e)),
+        };
+        let namespaces: BTreeMap<String, Namespace> =
+            serde_json::from_slice(&bytes).map_err(|e| format!("Corrupt store journal: {}", e))?;
+        for (namespace, ns) in &namespaces {
+            self.persist(namespace, ns)?;
+        }
+        std::fs::remove_file(&path).map_err(|e| format!("Failed to clear store journal: {}", e))?;
+        warn!(
+            "Replayed an interrupted store batch across {} namespaces",
+            namespaces.len()
+        );
+        Ok(())
+    }
+
+    fn load(&self, namespace: &str) -> Result<Namespace, String> {
+        for path in [self.path(namespace), self.alternate_path(namespace)] {
+            let bytes = match std::fs::read(&path) {
+                Ok(bytes) if path.extension().is_some_and(|ext| ext == "zst") => {
+                    zstd::decode_all(bytes.as_slice())
+                        .map_err(|e| format!("Corrupt namespace {}: {}", namespace, e))?
+                }
+                Ok(bytes) => bytes,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(format!("Failed to read namespace {}: {}", namespace, e)),
+            };
+            self.record_size(namespace, bytes.len());
+            return serde_json::from_slice(&bytes)
+                .map_err(|e| format!("Corrupt namespace {}: {}", namespace, e));
+        }
+        Ok(Namespace::new())
+    }
+
+    fn persist(&self, namespace: &str, ns: &Namespace) -> Result<(), String> {
+        let json = serde_json::to_vec(ns)
+            .map_err(|e| format!("Failed to serialize namespace {}: {}", namespace, e))?;
+        let size = json.len();
+        let bytes = if self.compressed.contains(namespace) {
+            zstd::encode_all(json.as_slice(), COMPRESSION_LEVEL)
+                .map_err(|e| format!("Failed to compress namespace {}: {}", namespace, e))?
+        } else {
+            json
+        };
+        let path = self.path(namespace);
+        let tmp = PathBuf::from(format!("{}.tmp", path.display()));
+
+        let failed = |e: std::io::Error| format!("Failed to write namespace {}: {}", namespace, e);
+        std::fs::write(&tmp, bytes)
+            .and_then(|_| std::fs::rename(&tmp, &path))
+            .map_err(failed)?;
+        // A namespace that changed format leaves its old file behind
+        match std::fs::remove_file(self.alternate_path(namespace)) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(failed(e)),
+        }
+        self.record_size(namespace, size);
+        Ok(())
+    }
+
+    fn record_size(&self, namespace: &str, bytes: usize) {
+        self.sizes
+            .lock()
+            .unwrap()
+            .insert(namespace.to_string(), bytes);
+    }
+}
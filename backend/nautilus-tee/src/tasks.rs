@@ -0,0 +1,466 @@
+/**
+ * Task Scheduler
+ * Durable background tasks on cron-like schedules
+ *
+ * Services register their recurring work (reminder sweeps, pollers,
+ * re-evaluations) under a name, a schedule and a jitter. A schedule is a
+ * five-field cron expression in UTC (minute hour day-of-month month
+ * day-of-week; each `*`, `n` or `a-b`, optionally stepped with `/n`, or a
+ * comma list of those), one of @hourly, @daily, @weekly and @monthly, or
+ * `@every <n>s|m|h`.
+ * Deployments override a task's schedule with TEE_TASK_SCHEDULES entries
+ * of the form `<name>=<schedule>`, separated by semicolons (e.g.
+ * `check_in.reminders=0 9,17 * * *`), and its jitter with TEE_TASK_JITTER
+ * entries `<name>=<secs>`.
+ *
+ * Each task's next run, and its last start and finish, are kept in the
+ * store, so execution is at least once across restarts:
+ *   - a run the enclave was down for happens as soon as it is back
+ *   - a run that started but never finished is run again
+ * After each run, the next one is drawn from the schedule plus a random
+ * delay of up to the task's jitter, so enclaves sharing a relay don't
+ * all poll it at the same second.
+ */
+
+use futures_util::future::{BoxFuture, FutureExt};
+use rand::rngs::OsRng;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+use crate::credentials::civil_date;
+use crate::store::Store;
+
+const NAMESPACE: &str = "scheduled_tasks";
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+/// How far ahead a cron expression is searched for its next match
+const MAX_SEARCH_DAYS: u64 = 5 * 366;
+
+/// When a task runs
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Schedule {
+    Every(u64),
+    Cron(Cron),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Whether day-of-month and day-of-week were both restricted; a day
+    /// then matches either, as in Vixie cron
+    either_day: bool,
+}
+
+/// A task's persisted state
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TaskRecord {
+    pub name: String,
+    pub schedule: String,
+    pub jitter_secs: u64,
+    pub next_run_at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_started_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_finished_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    pub runs: u64,
+    pub failures: u64,
+}
+
+/// A task as listed to admins
+#[derive(Clone, Serialize)]
+pub struct TaskStatus {
+    #[serde(flatten)]
+    pub record: TaskRecord,
+    pub running: bool,
+    /// Due and not yet started
+    pub overdue: bool,
+}
+
+type Run = Arc<dyn Fn() -> BoxFuture<'static, Result<(), String>> + Send + Sync>;
+
+struct Registered {
+    schedule: Schedule,
+    run: Run,
+}
+
+pub struct TaskScheduler {
+    store: Arc<Store>,
+    schedules: BTreeMap<String, String>,
+    jitters: BTreeMap<String, Duration>,
+    tasks: Mutex<BTreeMap<String, Registered>>,
+    running: Mutex<HashSet<String>>,
+}
+
+impl Schedule {
+    /// The schedule source for a fixed interval
+    pub fn every(interval: Duration) -> String {
+        format!("@every {}s", interval.as_secs().max(1))
+    }
+
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let source = source.trim();
+        let invalid = || format!("Invalid schedule {:?}", source);
+        if let Some(interval) = source.strip_prefix("@every ") {
+            let interval = interval.trim();
+            let (count, unit) = interval.split_at(interval.len().saturating_sub(1));
+            let unit = match unit {
+                "s" => 1,
+                "m" => 60,
+                "h" => 60 * 60,
+                _ => return Err(invalid()),
+            };
+            let count: u64 = count.parse().map_err(|_| invalid())?;
+            if count == 0 {
+                return Err(invalid());
+            }
+            return Ok(Self::Every(count * unit));
+        }
+        let expression = match source {
+            "@hourly" => "0 * * * *",
+            "@daily" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            other => other,
+        };
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields.as_slice() else {
+            return Err(invalid());
+        };
+        let field = |f: &str, min, max| parse_field(f, min, max).ok_or_else(invalid);
+        let mut cron = Cron {
+            minutes: field(minutes, 0, 59)?,
+            hours: field(hours, 0, 23)?,
+            days: field(days, 1, 31)?,
+            months: field(months, 1, 12)?,
+            weekdays: field(weekdays, 0, 7)?,
+            either_day: !days.starts_with('*') && !weekdays.starts_with('*'),
+        };
+        // 7 is Sunday too
+        if cron.weekdays & (1 << 7) != 0 {
+            cron.weekdays |= 1;
+        }
+        Ok(Self::Cron(cron))
+    }
+
+    /// The first time after `after` the schedule fires
+    pub fn next_after(&self, after: u64) -> Option<u64> {
+        let cron = match self {
+            Self::Every(secs) => return Some(after + secs),
+            Self::Cron(cron) => cron,
+        };
+        let start = (after / 60 + 1) * 60;
+        let first_day = start / SECS_PER_DAY;
+        for day in first_day..first_day + MAX_SEARCH_DAYS {
+            if !cron.matches_day(day) {
+                continue;
+            }
+            for hour in (0..24).filter(|h| cron.hours & (1 << h) != 0) {
+                for minute in (0..60).filter(|m| cron.minutes & (1 << m) != 0) {
+                    let at = day * SECS_PER_DAY + hour * 60 * 60 + minute * 60;
+                    if at >= start {
+                        return Some(at);
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+impl Cron {
+    fn matches_day(&self, day: u64) -> bool {
+        let date = civil_date(day as i64);
+        let (month, day_of_month) = ((date / 100) % 100, date % 100);
+        // 1970-01-01 was a Thursday
+        let weekday = (day + 4) % 7;
+        if self.months & (1 << month) == 0 {
+            return false;
+        }
+        let by_date = self.days & (1 << day_of_month) != 0;
+        let by_weekday = self.weekdays & (1 << weekday) != 0;
+        if self.either_day {
+            by_date || by_weekday
+        } else {
+            by_date && by_weekday
+        }
+    }
+}
+
+/// A cron field as a bit set of the values it allows
+fn parse_field(field: &str, min: u64, max: u64) -> Option<u64> {
+    let mut bits = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u64>().ok().filter(|s| *s > 0)?),
+            None => (part, 1),
+        };
+        let (low, high) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((low, high)) => (low.parse().ok()?, high.parse().ok()?),
+                None if step > 1 => (range.parse().ok()?, max),
+                None => {
+                    let value = range.parse().ok()?;
+                    (value, value)
+                }
+            },
+        };
+        if low < min || high > max || low > high {
+            return None;
+        }
+        for value in (low..=high).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Some(bits)
+}
+
+impl TaskScheduler {
+    pub fn new(
+        store: Arc<Store>,
+        schedules: &[String],
+        jitters: &[String],
+    ) -> Result<Self, String> {
+        let mut parsed_schedules = BTreeMap::new();
+        for entry in schedules {
+            let (name, schedule) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid task schedule entry: {}", entry))?;
+            Schedule::parse(schedule)?;
+            parsed_schedules.insert(name.trim().to_string(), schedule.trim().to_string());
+        }
+        let mut parsed_jitters = BTreeMap::new();
+        for entry in jitters {
+            let (name, secs) = entry
+                .split_once('=')
+                .and_then(|(name, secs)| Some((name.trim(), secs.trim().parse::<u64>().ok()?)))
+                .ok_or_else(|| format!("Invalid task jitter entry: {}", entry))?;
+            parsed_jitters.insert(name.to_string(), Duration::from_secs(secs));
+        }
+        Ok(Self {
+            store,
+            schedules: parsed_schedules,
+            jitters: parsed_jitters,
+            tasks: Mutex::new(BTreeMap::new()),
+            running: Mutex::new(HashSet::new()),
+        })
+    }
+
+    /// Add a task under `schedule` and `jitter`, unless TEE_TASK_SCHEDULES
+    /// or TEE_TASK_JITTER override them. A task already in the store keeps
+    /// its next run unless its schedule changed, and is due at once if its
+    /// last run never finished.
+    pub fn register<F, Fut>(
+        &self,
+        name: &str,
+        schedule: &str,
+        jitter: Duration,
+        run: F,
+    ) -> Result<(), String>
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), String>> + Send + 'static,
+    {
+        let source = self
+            .schedules
+            .get(name)
+            .map(String::as_str)
+            .unwrap_or(schedule);
+        let jitter = self.jitters.get(name).copied().unwrap_or(jitter);
+        let schedule = Schedule::parse(source)?;
+        let now = now();
+        let record = match self.record(name)? {
+            Some(mut record) => {
+                if record.schedule != source {
+                    record.schedule = source.to_string();
+                    record.next_run_at = next_run(&schedule, now, jitter.as_secs())?;
+                }
+                if record.last_started_at > record.last_finished_at {
+                    warn!("Task {} was interrupted; running it again", name);
+                    record.next_run_at = now;
+                }
+                record.jitter_secs = jitter.as_secs();
+                record
+            }
+            None => TaskRecord {
+                name: name.to_string(),
+                schedule: source.to_string(),
+                jitter_secs: jitter.as_secs(),
+                next_run_at: next_run(&schedule, now, jitter.as_secs())?,
+                last_started_at: None,
+                last_finished_at: None,
+                last_error: None,
+                runs: 0,
+                failures: 0,
+            },
+        };
+        self.store.put(NAMESPACE, name, &record)?;
+        info!(
+            "Task {} scheduled {:?}; next run at {}",
+            name, record.schedule, record.next_run_at
+        );
+
+        let run: Run = Arc::new(move || run().boxed());
+        let mut tasks = self.tasks.lock().unwrap();
+        if tasks.contains_key(name) {
+            return Err(format!("Task {} is registered twice", name));
+        }
+        tasks.insert(name.to_string(), Registered { schedule, run });
+        Ok(())
+    }
+
+    /// Registered tasks, soonest first
+    pub fn list(&self) -> Result<Vec<TaskStatus>, String> {
+        let names: Vec<String> = self.tasks.lock().unwrap().keys().cloned().collect();
+        let running = self.running.lock().unwrap().clone();
+        let now = now();
+        let mut tasks = Vec::new();
+        for name in names {
+            let Some(record) = self.record(&name)? else {
+                continue;
+            };
+            let running = running.contains(&name);
+            tasks.push(TaskStatus {
+                overdue: !running && record.next_run_at < now,
+                running,
+                record,
+            });
+        }
+        tasks.sort_by_key(|t| t.record.next_run_at);
+        Ok(tasks)
+    }
+
+    /// Check for due tasks on an interval and run each on its own task
+    pub fn spawn(self: Arc<Self>, tick: Duration) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(tick);
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.clone().run_due() {
+                    warn!("Task scheduler failed: {}", e);
+                }
+            }
+        });
+    }
+
+    fn run_due(self: Arc<Self>) -> Result<(), String> {
+        let now = now();
+        let due: Vec<(String, Run)> = self
+            .tasks
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, task)| (name.clone(), task.run.clone()))
+            .collect();
+        for (name, run) in due {
+            let Some(mut record) = self.record(&name)? else {
+                continue;
+            };
+            if record.next_run_at > now || !self.running.lock().unwrap().insert(name.clone()) {
+                continue;
+            }
+            // Recorded first, so a crash mid-run is seen at the next start
+            record.last_started_at = Some(now);
+            if let Err(e) = self.store.put(NAMESPACE, &name, &record) {
+                self.running.lock().unwrap().remove(&name);
+                return Err(e);
+            }
+
+            let scheduler = self.clone();
+            tokio::spawn(async move {
+                let outcome = match tokio::spawn(run()).await {
+                    Ok(outcome) => outcome,
+                    Err(e) => Err(format!("Task panicked: {}", e)),
+                };
+                if let Err(e) = scheduler.finish(&name, outcome) {
+                    warn!("Failed to record task {}: {}", name, e);
+                }
+                scheduler.running.lock().unwrap().remove(&name);
+            });
+        }
+        Ok(())
+    }
+
+    fn finish(&self, name: &str, outcome: Result<(), String>) -> Result<(), String> {
+        let schedule = match self.tasks.lock().unwrap().get(name) {
+            Some(task) => task.schedule.clone(),
+            None => return Ok(()),
+        };
+        let Some(mut record) = self.record(name)? else {
+            return Ok(());
+        };
+        let now = now();
+        record.runs += 1;
+        record.last_finished_at = Some(now);
+        record.last_error = match outcome {
+            Ok(()) => None,
+            Err(e) => {
+                warn!("Task {} failed: {}", name, e);
+                record.failures += 1;
+                Some(e)
+            }
+        };
+        record.next_run_at = next_run(&schedule, now, record.jitter_secs)?;
+        self.store.put(NAMESPACE, name, &record)
+    }
+
+    fn record(&self, name: &str) -> Result<Option<TaskRecord>, String> {
+        self.store.get(NAMESPACE, name)
+    }
+}
+
+fn next_run(schedule: &Schedule, after: u64, jitter_secs: u64) -> Result<u64, String> {
+    let at = schedule
+        .next_after(after)
+        .ok_or("Schedule never fires (e.g. February 30th)")?;
+    let jitter = if jitter_secs == 0 {
+        0
+    } else {
+        OsRng.gen_range(0..=jitter_secs)
+    };
+    Ok(at + jitter)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cron_schedules_find_their_next_run() {
+        // 2024-01-01 00:00:00 UTC, a Monday
+        let monday = 1_704_067_200;
+        let at = |source: &str, after| Schedule::parse(source).unwrap().next_after(after);
+
+        assert_eq!(at("*/15 * * * *", monday), Some(monday + 15 * 60));
+        assert_eq!(at("0 9 * * *", monday), Some(monday + 9 * 60 * 60));
+        assert_eq!(
+            at("30 8 * * 5", monday),
+            Some(monday + 4 * SECS_PER_DAY + 510 * 60)
+        );
+        assert_eq!(at("0 0 1 * *", monday), Some(monday + 31 * SECS_PER_DAY));
+        // Restricted day-of-month and day-of-week match either
+        assert_eq!(at("0 0 15 * 3", monday), Some(monday + 2 * SECS_PER_DAY));
+        assert_eq!(at("@every 5m", monday), Some(monday + 300));
+        assert_eq!(at("0 0 30 2 *", monday), None);
+        assert!(Schedule::parse("60 * * * *").is_err());
+        assert!(Schedule::parse("* * * *").is_err());
+        assert!(Schedule::parse("@every 0s").is_err());
+    }
+}
@@ -0,0 +1,56 @@
+/**
+ * Template Migrations
+ * Step-by-step upgrades of stored templates to the current format version
+ *
+ * Each step takes a template's decrypted features at one version and
+ * returns them at the next; the service re-seals the result under the new
+ * version. A step that can't carry a template forward (the features it
+ * needs were never stored, or the stored ones fail validation) reports why,
+ * and the template is flagged for re-enrollment instead.
+ *
+ *   v1 -> v2: the owner is bound into the at-rest AAD; embedding
+ *             templates must match their model's declared dimension
+ */
+
+use zeroize::Zeroizing;
+
+use super::TemplateMeta;
+
+type Step = fn(&TemplateMeta, Zeroizing<Vec<u8>>) -> Result<Zeroizing<Vec<u8>>, String>;
+
+/// Indexed by the version a step upgrades from
+const STEPS: &[(u32, Step)] = &[(1, v1_to_v2)];
+
+/// Upgrade `features` from `meta.version` to `target`, or explain why the
+/// template has to be re-enrolled
+pub fn upgrade(
+    meta: &TemplateMeta,
+    features: Zeroizing<Vec<u8>>,
+    target: u32,
+) -> Result<Zeroizing<Vec<u8>>, String> {
+    let mut features = features;
+    for version in meta.version..target {
+        let step = STEPS
+            .iter()
+            .find(|(from, _)| *from == version)
+            .map(|(_, step)| step)
+            .ok_or_else(|| format!("No migration from template version {}", version))?;
+        features = step(meta, features)?;
+    }
+    Ok(features)
+}
+
+fn v1_to_v2(
+    meta: &TemplateMeta,
+    features: Zeroizing<Vec<u8>>,
+) -> Result<Zeroizing<Vec<u8>>, String> {
+    if let Some(model) = &meta.model {
+        if features.len() != model.dimension * 4 {
+            return Err(format!(
+                "Embedding template does not match {} dimension {}",
+                model.name, model.dimension
+            ));
+        }
+    }
+    Ok(features)
+}
@@ -0,0 +1,753 @@
+/**
+ * Biometric Templates
+ * Enrolled templates, encrypted at rest, with attested backup and restore
+ *
+ * Templates are stored under the vault's template-encryption key from the
+ * key hierarchy. Backups re-wrap each template to the template backup key,
+ * an X25519 secret that only reaches enclaves through the KMS-released
+ * secrets bundle, and carry an attestation over the backup's digest. An
+ * enclave importing a backup checks that attestation, the source image
+ * measurement and every template's format version before accepting it.
+ *
+ * A vault can hold several templates per method (different fingers, face
+ * angles); verification matches against all of them and keeps the best.
+ *
+ * Templates from older format versions are migrated when they're next
+ * loaded. Those that can't be are flagged and must be re-enrolled. Vaults
+ * can also retire templates by age under a refresh policy (see refresh).
+ */
+
+pub mod migration;
+pub mod refresh;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+use x25519_dalek::{PublicKey, StaticSecret};
+use zeroize::Zeroizing;
+
+use crate::attestation::VerifiedAttestation;
+use crate::crypto::{self, Envelope};
+use crate::embedding::EmbeddingModel;
+use crate::keys::derive::{KeyHierarchy, TemplateEncryption};
+use crate::keys::release::KeyRelease;
+use crate::pagination::Paged;
+use crate::store::Store;
+use refresh::{Freshness, RefreshPolicy};
+
+const NAMESPACE: &str = "biometric_templates";
+pub const BACKUP_OPERATION: &str = "template_backup";
+
+/// Current template format; backups with newer versions are rejected
+pub const TEMPLATE_VERSION: u32 = 2;
+pub const MAX_TEMPLATES_PER_METHOD: usize = 10;
+const MAX_LABEL_LEN: usize = 64;
+/// Id of templates enrolled when a vault held one template per method
+const LEGACY_TEMPLATE_ID: &str = "default";
+
+/// Everything about a template except its features
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TemplateMeta {
+    #[serde(default = "legacy_template_id")]
+    pub template_id: String,
+    pub vault_id: String,
+    pub owner: String,
+    pub method: String,
+    pub version: u32,
+    pub enrolled_at: u64,
+    /// Owner-chosen name, e.g. "left index"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// Embedding model, for templates enrolled from client-side embeddings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<EmbeddingModel>,
+    /// Set when migration failed; the template can't be matched until the
+    /// owner enrolls again
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reenrollment_reason: Option<String>,
+}
+
+pub enum LoadError {
+    NotFound,
+    ReenrollmentRequired(String),
+    Failed(String),
+}
+
+/// Template versions in use, for admins planning a format change
+#[derive(Serialize)]
+pub struct VersionReport {
+    pub current_version: u32,
+    pub versions: BTreeMap<u32, usize>,
+    pub reenrollment_required: usize,
+    /// Templates upgraded on access since the enclave started
+    pub migrated_since_start: u64,
+}
+
+/// An enrolled template with its features decrypted
+pub struct LoadedTemplate {
+    pub meta: TemplateMeta,
+    pub features: Zeroizing<Vec<u8>>,
+    /// Refresh deadline under the vault's policy
+    pub expires_at: Option<u64>,
+    /// Set for expired templates still allowed to match
+    pub confidence_cap: Option<f64>,
+}
+
+/// A template as persisted, sealed under its vault's template key
+#[derive(Clone, Serialize, Deserialize)]
+pub struct StoredTemplate {
+    #[serde(flatten)]
+    meta: TemplateMeta,
+    nonce: String,      // Base64
+    ciphertext: String, // Base64, under the vault's template key
+}
+
+#[derive(Serialize)]
+pub struct TemplateInfo {
+    pub template_id: String,
+    pub vault_id: String,
+    pub method: String,
+    pub version: u32,
+    pub enrolled_at: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<EmbeddingModel>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reenrollment_reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TemplateBackupEntry {
+    #[serde(flatten)]
+    pub meta: TemplateMeta,
+    pub envelope: Envelope, // Sealed to the template backup key
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TemplateBackup {
+    pub source_measurement: String, // PCR0 of the exporting enclave
+    pub source_identity: String,    // Identity key id of the exporting enclave
+    pub exported_at: u64,
+    pub templates: Vec<TemplateBackupEntry>,
+}
+
+impl TemplateBackup {
+    /// Digest bound into the backup attestation
+    pub fn digest(&self) -> Result<Vec<u8>, String> {
+        let bytes = serde_json::to_vec(self)
+            .map_err(|e| format!("Failed to serialize template backup: {}", e))?;
+        Ok(Sha256::digest(bytes).to_vec())
+    }
+}
+
+pub struct TemplateService {
+    store: Arc<Store>,
+    keys: Arc<KeyHierarchy>,
+    release: Arc<KeyRelease>,
+    backup_key: Option<StaticSecret>,
+    /// PCR0 values of images whose backups may be imported
+    allowed_measurements: Vec<String>,
+    migrated: AtomicU64,
+}
+
+impl Paged for TemplateInfo {
+    fn timestamp(&self) -> u64 {
+        self.enrolled_at
+    }
+
+    fn id(&self) -> String {
+        self.template_id.clone()
+    }
+}
+
+impl TemplateService {
+    pub fn new(
+        store: Arc<Store>,
+        keys: Arc<KeyHierarchy>,
+        release: Arc<KeyRelease>,
+        backup_key: Option<StaticSecret>,
+        allowed_measurements: Vec<String>,
+    ) -> Self {
+        Self {
+            store,
+            keys,
+            release,
+            backup_key,
+            allowed_measurements,
+            migrated: AtomicU64::new(0),
+        }
+    }
+
+    /// Add a template for `method`. `model` is set when `features` is a
+    /// client-side embedding. Templates flagged for re-enrollment or past
+    /// the vault's refresh deadline are replaced by the new one.
+    pub fn enroll(
+        &self,
+        vault_id: &str,
+        owner: &str,
+        method: &str,
+        features: &[u8],
+        model: Option<EmbeddingModel>,
+        label: Option<String>,
+    ) -> Result<TemplateInfo, String> {
+        let existing = self.entries(vault_id, Some(method))?;
+        if existing.iter().any(|t| t.meta.owner != owner) {
+            return Err("Templates belong to a different owner".to_string());
+        }
+        let policy = self.refresh_policy(vault_id)?;
+        let now = now();
+        let (flagged, active): (Vec<_>, Vec<_>) = existing.into_iter().partition(|t| {
+            t.meta.reenrollment_reason.is_some()
+                || policy
+                    .as_ref()
+                    .is_some_and(|p| p.freshness(t.meta.enrolled_at, now) == Freshness::Expired)
+        });
+        if active.len() >= MAX_TEMPLATES_PER_METHOD {
+            return Err(format!(
+                "At most {} templates per method; delete one first",
+                MAX_TEMPLATES_PER_METHOD
+            ));
+        }
+
+        let meta = TemplateMeta {
+            template_id: new_template_id(),
+            vault_id: vault_id.to_string(),
+            owner: owner.to_string(),
+            method: method.to_string(),
+            version: TEMPLATE_VERSION,
+            enrolled_at: now,
+            label: validate_label(label)?,
+            model,
+            reenrollment_reason: None,
+        };
+        let stored = self.seal_local(meta, features)?;
+        self.store.put(NAMESPACE, &key_of(&stored.meta), &stored)?;
+        for old in &flagged {
+            self.remove(&old.meta)?;
+        }
+        Ok(info(&stored, policy.as_ref()))
+    }
+
+    /// Every usable template for `method`, migrated to the current version.
+    /// Expired templates are usable only under a confidence ceiling.
+    pub fn load_all(&self, vault_id: &str, method: &str) -> Result<Vec<LoadedTemplate>, LoadError> {
+        let entries = self
+            .entries(vault_id, Some(method))
+            .map_err(LoadError::Failed)?;
+        if entries.is_empty() {
+            return Err(LoadError::NotFound);
+        }
+        let policy = self.refresh_policy(vault_id).map_err(LoadError::Failed)?;
+        let now = now();
+
+        let mut loaded = Vec::with_capacity(entries.len());
+        let mut reenrollment = None;
+        for stored in entries {
+            let (expires_at, confidence_cap) = match &policy {
+                Some(policy) => {
+                    let expired =
+                        policy.freshness(stored.meta.enrolled_at, now) == Freshness::Expired;
+                    if expired && policy.stale_confidence_cap.is_none() {
+                        reenrollment = Some(
+                            "Template has expired under the vault's refresh policy".to_string(),
+                        );
+                        continue;
+                    }
+                    (
+                        Some(policy.expires_at(stored.meta.enrolled_at)),
+                        policy.stale_confidence_cap.filter(|_| expired),
+                    )
+                }
+                None => (None, None),
+            };
+            match self.load(stored) {
+                Ok(template) => loaded.push(LoadedTemplate {
+                    expires_at,
+                    confidence_cap,
+                    ..template
+                }),
+                Err(LoadError::ReenrollmentRequired(reason)) => reenrollment = Some(reason),
+                Err(e) => return Err(e),
+            }
+        }
+        match reenrollment {
+            Some(reason) if loaded.is_empty() => Err(LoadError::ReenrollmentRequired(reason)),
+            _ => Ok(loaded),
+        }
+    }
+
+    /// The vault's templates across methods; only its owner may list them
+    pub fn list(&self, vault_id: &str, owner: &str) -> Result<Vec<TemplateInfo>, String> {
+        let entries = self.entries(vault_id, None)?;
+        if entries.iter().any(|t| t.meta.owner != owner) {
+            return Err("Templates belong to a different owner".to_string());
+        }
+        let policy = self.refresh_policy(vault_id)?;
+        Ok(entries.iter().map(|t| info(t, policy.as_ref())).collect())
+    }
+
+    pub fn set_label(
+        &self,
+        vault_id: &str,
+        method: &str,
+        template_id: &str,
+        owner: &str,
+        label: Option<String>,
+    ) -> Result<TemplateInfo, String> {
+        let mut stored = self.owned(vault_id, method, template_id, owner)?;
+        stored.meta.label = validate_label(label)?;
+        self.store.put(NAMESPACE, &key_of(&stored.meta), &stored)?;
+        Ok(info(&stored, self.refresh_policy(vault_id)?.as_ref()))
+    }
+
+    pub fn delete(
+        &self,
+        vault_id: &str,
+        method: &str,
+        template_id: &str,
+        owner: &str,
+    ) -> Result<(), String> {
+        let stored = self.owned(vault_id, method, template_id, owner)?;
+        self.remove(&stored.meta)
+    }
+
+    /// Prepare one template for matching, migrating it to the current
+    /// version first when it's older
+    fn load(&self, mut stored: StoredTemplate) -> Result<LoadedTemplate, LoadError> {
+        if let Some(reason) = &stored.meta.reenrollment_reason {
+            return Err(LoadError::ReenrollmentRequired(reason.clone()));
+        }
+        if stored.meta.version > TEMPLATE_VERSION {
+            return Err(LoadError::Failed(format!(
+                "Template version {} is newer than this enclave supports",
+                stored.meta.version
+            )));
+        }
+
+        let features = self.open_local(&stored).map_err(LoadError::Failed)?;
+        if stored.meta.version == TEMPLATE_VERSION {
+            return Ok(LoadedTemplate {
+                meta: stored.meta,
+                features,
+                expires_at: None,
+                confidence_cap: None,
+            });
+        }
+
+        let key = key_of(&stored.meta);
+        match migration::upgrade(&stored.meta, features, TEMPLATE_VERSION) {
+            Ok(features) => {
+                let from = stored.meta.version;
+                let mut meta = stored.meta;
+                meta.version = TEMPLATE_VERSION;
+                let upgraded = self
+                    .seal_local(meta, &features)
+                    .map_err(LoadError::Failed)?;
+                self.store
+                    .put(NAMESPACE, &key, &upgraded)
+                    .map_err(LoadError::Failed)?;
+                self.migrated.fetch_add(1, Ordering::Relaxed);
+                info!(
+                    "Migrated template {} from version {} to {}",
+                    key, from, TEMPLATE_VERSION
+                );
+                Ok(LoadedTemplate {
+                    meta: upgraded.meta,
+                    features,
+                    expires_at: None,
+                    confidence_cap: None,
+                })
+            }
+            Err(reason) => {
+                warn!("Template {} needs re-enrollment: {}", key, reason);
+                stored.meta.reenrollment_reason = Some(reason.clone());
+                self.store
+                    .put(NAMESPACE, &key, &stored)
+                    .map_err(LoadError::Failed)?;
+                Err(LoadError::ReenrollmentRequired(reason))
+            }
+        }
+    }
+
+    pub fn version_report(&self) -> Result<VersionReport, String> {
+        let mut versions = BTreeMap::new();
+        let mut reenrollment_required = 0;
+        for (_, stored) in self.store.list::<StoredTemplate>(NAMESPACE)? {
+            *versions.entry(stored.meta.version).or_insert(0) += 1;
+            if stored.meta.reenrollment_reason.is_some() {
+                reenrollment_required += 1;
+            }
+        }
+        Ok(VersionReport {
+            current_version: TEMPLATE_VERSION,
+            versions,
+            reenrollment_required,
+            migrated_since_start: self.migrated.load(Ordering::Relaxed),
+        })
+    }
+
+    /// Re-wrap templates (all, or those of `vault_id`) to the backup key
+    pub fn export(
+        &self,
+        vault_id: Option<&str>,
+        source_measurement: &str,
+        source_identity: &str,
+    ) -> Result<TemplateBackup, String> {
+        let backup_public = PublicKey::from(self.backup_key()?);
+
+        let mut templates = Vec::new();
+        for (_, stored) in self.store.list::<StoredTemplate>(NAMESPACE)? {
+            if vault_id.is_some_and(|v| v != stored.meta.vault_id) {
+                continue;
+            }
+            let features = self.open_local(&stored)?;
+            templates.push(TemplateBackupEntry {
+                envelope: crypto::seal(
+                    &backup_public,
+                    &features,
+                    backup_aad(&stored.meta).as_bytes(),
+                )?,
+                meta: stored.meta,
+            });
+        }
+
+        Ok(TemplateBackup {
+            source_measurement: source_measurement.to_string(),
+            source_identity: source_identity.to_string(),
+            exported_at: now(),
+            templates,
+        })
+    }
+
+    /// Re-wrap `owner`'s templates in the vault to a key of their own, for
+    /// a data-subject export
+    pub fn export_for_owner(
+        &self,
+        vault_id: &str,
+        owner: &str,
+        recipient: &PublicKey,
+    ) -> Result<Vec<TemplateBackupEntry>, String> {
+        let mut templates = Vec::new();
+        for stored in self.entries(vault_id, None)? {
+            if stored.meta.owner != owner {
+                continue;
+            }
+            let features = self.open_local(&stored)?;
+            templates.push(TemplateBackupEntry {
+                envelope: crypto::seal(
+                    recipient,
+                    &features,
+                    subject_aad(&stored.meta).as_bytes(),
+                )?,
+                meta: stored.meta,
+            });
+        }
+        Ok(templates)
+    }
+
+    /// Validate a backup end to end before accepting any of its templates
+    pub fn import(
+        &self,
+        backup: &TemplateBackup,
+        source: &VerifiedAttestation,
+    ) -> Result<Vec<TemplateInfo>, String> {
+        if source.operation != BACKUP_OPERATION
+            || source.user_data.as_deref() != Some(hex::encode(backup.digest()?).as_str())
+        {
+            return Err("Attestation does not cover this backup".to_string());
+        }
+        if source.pcr0 != backup.source_measurement
+            || !self.allowed_measurements.contains(&source.pcr0)
+        {
+            return Err(format!(
+                "Backups from measurement {} are not accepted",
+                source.pcr0
+            ));
+        }
+
+        let backup_key = self.backup_key()?;
+        let mut accepted = Vec::with_capacity(backup.templates.len());
+        for entry in &backup.templates {
+            let meta = &entry.meta;
+            if meta.version == 0 || meta.version > TEMPLATE_VERSION {
+                return Err(format!(
+                    "Unsupported template version {} for {}/{}",
+                    meta.version, meta.vault_id, meta.method
+                ));
+            }
+            if self
+                .store
+                .get::<StoredTemplate>(NAMESPACE, &key_of(meta))?
+                .is_some()
+            {
+                return Err(format!(
+                    "Template {}/{}/{} is already enrolled",
+                    meta.vault_id, meta.method, meta.template_id
+                ));
+            }
+
+            let features = Zeroizing::new(crypto::open(
+                backup_key,
+                &entry.envelope,
+                backup_aad(meta).as_bytes(),
+            )?);
+            accepted.push(self.seal_local(meta.clone(), &features)?);
+        }
+
+        // Nothing is written unless every template validated
+        let mut imported = Vec::with_capacity(accepted.len());
+        for stored in &accepted {
+            self.store.put(NAMESPACE, &key_of(&stored.meta), stored)?;
+            let policy = self.refresh_policy(&stored.meta.vault_id)?;
+            imported.push(info(stored, policy.as_ref()));
+        }
+        Ok(imported)
+    }
+
+    /// The vault's templates as stored, and the key they're sealed under,
+    /// for handing the vault over to another enclave
+    pub fn sealed(
+        &self,
+        vault_id: &str,
+    ) -> Result<(Vec<StoredTemplate>, Zeroizing<[u8; 32]>), String> {
+        let grant = self.release.grant(vault_id, "templates.export_key")?;
+        let key = self.keys.unwrap::<TemplateEncryption>(&grant, vault_id);
+        Ok((
+            self.entries(vault_id, None)?,
+            Zeroizing::new(*key.as_bytes()),
+        ))
+    }
+
+    /// Re-seal templates handed over by another enclave under this
+    /// enclave's key; nothing is written unless every one opens
+    pub fn adopt(
+        &self,
+        vault_id: &str,
+        templates: &[StoredTemplate],
+        source_key: &[u8; 32],
+    ) -> Result<usize, String> {
+        let mut accepted = Vec::with_capacity(templates.len());
+        for stored in templates {
+            if stored.meta.vault_id != vault_id {
+                return Err(format!(
+                    "Template {} belongs to a different vault",
+                    stored.meta.template_id
+                ));
+            }
+            let features = Zeroizing::new(crypto::aead_decrypt(
+                source_key,
+                &crypto::decode(&stored.nonce)?,
+                &crypto::decode(&stored.ciphertext)?,
+                local_aad(&stored.meta).as_bytes(),
+            )?);
+            accepted.push(self.seal_local(stored.meta.clone(), &features)?);
+        }
+
+        for stored in &accepted {
+            self.store.put(NAMESPACE, &key_of(&stored.meta), stored)?;
+        }
+        Ok(accepted.len())
+    }
+
+    /// Whether the vault has any templates enrolled
+    pub fn holds(&self, vault_id: &str) -> Result<bool, String> {
+        Ok(!self.entries(vault_id, None)?.is_empty())
+    }
+
+    /// Erase every template of the vault; returns their store keys
+    pub fn remove_vault(&self, vault_id: &str) -> Result<Vec<String>, String> {
+        let keys: Vec<String> = self
+            .entries(vault_id, None)?
+            .iter()
+            .map(|stored| key_of(&stored.meta))
+            .collect();
+        if !keys.is_empty() {
+            self.store.erase(NAMESPACE, &keys)?;
+        }
+        Ok(keys)
+    }
+
+    /// A vault's templates, for one method or all of them
+    fn entries(&self, vault_id: &str, method: Option<&str>) -> Result<Vec<StoredTemplate>, String> {
+        Ok(self
+            .store
+            .list::<StoredTemplate>(NAMESPACE)?
+            .into_iter()
+            .map(|(_, stored)| stored)
+            .filter(|t| t.meta.vault_id == vault_id && method.is_none_or(|m| m == t.meta.method))
+            .collect())
+    }
+
+    fn owned(
+        &self,
+        vault_id: &str,
+        method: &str,
+        template_id: &str,
+        owner: &str,
+    ) -> Result<StoredTemplate, String> {
+        let stored = self
+            .store
+            .get::<StoredTemplate>(NAMESPACE, &template_key(vault_id, method, template_id))?
+            .ok_or_else(|| "Template not found".to_string())?;
+        if stored.meta.owner != owner {
+            return Err("Template belongs to a different owner".to_string());
+        }
+        Ok(stored)
+    }
+
+    fn remove(&self, meta: &TemplateMeta) -> Result<(), String> {
+        let key = key_of(meta);
+        self.store.update(NAMESPACE, |ns| {
+            ns.remove(&key);
+        })
+    }
+
+    fn backup_key(&self) -> Result<&StaticSecret, String> {
+        self.backup_key
+            .as_ref()
+            .ok_or_else(|| "Template backup key is not configured".to_string())
+    }
+
+    fn seal_local(&self, meta: TemplateMeta, features: &[u8]) -> Result<StoredTemplate, String> {
+        let grant = self.release.grant(&meta.vault_id, "templates.seal")?;
+        let key = self
+            .keys
+            .unwrap::<TemplateEncryption>(&grant, &meta.vault_id);
+        let (nonce, ciphertext) =
+            crypto::aead_encrypt(key.as_bytes(), features, local_aad(&meta).as_bytes())?;
+
+        Ok(StoredTemplate {
+            meta,
+            nonce: STANDARD.encode(nonce),
+            ciphertext: STANDARD.encode(ciphertext),
+        })
+    }
+
+    fn open_local(&self, stored: &StoredTemplate) -> Result<Zeroizing<Vec<u8>>, String> {
+        let grant = self.release.grant(&stored.meta.vault_id, "templates.open")?;
+        let key = self
+            .keys
+            .unwrap::<TemplateEncryption>(&grant, &stored.meta.vault_id);
+        crypto::aead_decrypt(
+            key.as_bytes(),
+            &crypto::decode(&stored.nonce)?,
+            &crypto::decode(&stored.ciphertext)?,
+            local_aad(&stored.meta).as_bytes(),
+        )
+        .map(Zeroizing::new)
+    }
+}
+
+fn info(stored: &StoredTemplate, policy: Option<&RefreshPolicy>) -> TemplateInfo {
+    TemplateInfo {
+        template_id: stored.meta.template_id.clone(),
+        vault_id: stored.meta.vault_id.clone(),
+        method: stored.meta.method.clone(),
+        version: stored.meta.version,
+        enrolled_at: stored.meta.enrolled_at,
+        label: stored.meta.label.clone(),
+        model: stored.meta.model.clone(),
+        reenrollment_reason: stored.meta.reenrollment_reason.clone(),
+        expires_at: policy.map(|p| p.expires_at(stored.meta.enrolled_at)),
+    }
+}
+
+/// Legacy templates keep the key they were stored under
+fn template_key(vault_id: &str, method: &str, template_id: &str) -> String {
+    if template_id == LEGACY_TEMPLATE_ID {
+        format!("{}:{}", vault_id, method)
+    } else {
+        format!("{}:{}:{}", vault_id, method, template_id)
+    }
+}
+
+fn key_of(meta: &TemplateMeta) -> String {
+    template_key(&meta.vault_id, &meta.method, &meta.template_id)
+}
+
+fn legacy_template_id() -> String {
+    LEGACY_TEMPLATE_ID.to_string()
+}
+
+fn new_template_id() -> String {
+    let mut id = [0u8; 8];
+    OsRng.fill_bytes(&mut id);
+    hex::encode(id)
+}
+
+fn validate_label(label: Option<String>) -> Result<Option<String>, String> {
+    let label = label
+        .map(|l| l.trim().to_string())
+        .filter(|l| !l.is_empty());
+    if label
+        .as_ref()
+        .is_some_and(|l| l.chars().count() > MAX_LABEL_LEN || l.chars().any(char::is_control))
+    {
+        return Err(format!(
+            "Labels are at most {} printable characters",
+            MAX_LABEL_LEN
+        ));
+    }
+    Ok(label)
+}
+
+/// The embedding model is bound in too, so a template can't be relabelled
+/// to a different model without failing to decrypt; from version 2 on, so
+/// is the owner
+fn local_aad(meta: &TemplateMeta) -> String {
+    let owner = if meta.version >= 2 {
+        format!(":{}", meta.owner)
+    } else {
+        String::new()
+    };
+    format!(
+        "lumina-template:{}:{}:{}{}{}",
+        meta.vault_id,
+        meta.method,
+        meta.version,
+        model_aad(meta),
+        owner
+    )
+}
+
+fn backup_aad(meta: &TemplateMeta) -> String {
+    format!(
+        "lumina-template-backup:{}:{}:{}{}",
+        meta.vault_id,
+        meta.method,
+        meta.version,
+        model_aad(meta)
+    )
+}
+
+fn subject_aad(meta: &TemplateMeta) -> String {
+    format!(
+        "lumina-template-export:{}:{}:{}:{}",
+        meta.vault_id, meta.method, meta.template_id, meta.owner
+    )
+}
+
+fn model_aad(meta: &TemplateMeta) -> String {
+    meta.model
+        .as_ref()
+        .map(|m| format!(":{}:{}:{}", m.name, m.version, m.dimension))
+        .unwrap_or_default()
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
@@ -0,0 +1,251 @@
+/**
+ * Template Refresh
+ * Per-vault policies that retire templates after a fixed age
+ *
+ * Biometric templates degrade as their owners age, heal or change
+ * sensors. A vault owner can require templates to be refreshed every N
+ * months. The refresh sweep publishes `template.expiring` once a template
+ * is within the warning window of its deadline and `template.expired` once
+ * it passes it, one event of each per template, and sends the same
+ * reminder to the vault's notification contacts.
+ *
+ * Expired templates need re-enrollment before they match again, unless the
+ * policy sets a confidence ceiling: then they keep matching with scores
+ * capped at it until the owner enrolls a fresh template. A ceiling below
+ * the match threshold means a stale template alone can never verify.
+ */
+
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::info;
+
+use super::{key_of, now, StoredTemplate, TemplateService, NAMESPACE};
+use crate::bus::{BusEvent, EventBus, TemplateNotice};
+use crate::notifications::{Notification, NotificationService};
+use crate::tasks::{Schedule, TaskScheduler};
+
+const POLICY_NAMESPACE: &str = "template_refresh_policies";
+/// Template key -> last stage announced for it
+const NOTICE_NAMESPACE: &str = "template_refresh_notices";
+const SECS_PER_MONTH: u64 = 30 * 24 * 60 * 60;
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+const MAX_REFRESH_MONTHS: u32 = 120;
+pub const DEFAULT_WARN_DAYS: u32 = 30;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RefreshPolicy {
+    pub refresh_months: u32,
+    /// How long before the deadline owners are warned
+    pub warn_days: u32,
+    /// Score ceiling for expired templates; without one they can't match
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stale_confidence_cap: Option<f64>,
+    pub set_by: String,
+    pub set_at: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Freshness {
+    Fresh,
+    Expiring,
+    Expired,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Notice {
+    enrolled_at: u64,
+    stage: Freshness,
+}
+
+impl RefreshPolicy {
+    pub fn expires_at(&self, enrolled_at: u64) -> u64 {
+        enrolled_at.saturating_add(u64::from(self.refresh_months) * SECS_PER_MONTH)
+    }
+
+    pub fn freshness(&self, enrolled_at: u64, now: u64) -> Freshness {
+        let expires_at = self.expires_at(enrolled_at);
+        if now >= expires_at {
+            Freshness::Expired
+        } else if now + u64::from(self.warn_days) * SECS_PER_DAY >= expires_at {
+            Freshness::Expiring
+        } else {
+            Freshness::Fresh
+        }
+    }
+}
+
+impl TemplateService {
+    pub fn refresh_policy(&self, vault_id: &str) -> Result<Option<RefreshPolicy>, String> {
+        self.store.get(POLICY_NAMESPACE, vault_id)
+    }
+
+    /// Set the vault's policy; only the owner of its templates may
+    pub fn set_refresh_policy(
+        &self,
+        vault_id: &str,
+        owner: &str,
+        refresh_months: u32,
+        warn_days: Option<u32>,
+        stale_confidence_cap: Option<f64>,
+    ) -> Result<RefreshPolicy, String> {
+        self.require_vault_owner(vault_id, owner)?;
+        if refresh_months == 0 || refresh_months > MAX_REFRESH_MONTHS {
+            return Err(format!(
+                "refresh_months must be between 1 and {}",
+                MAX_REFRESH_MONTHS
+            ));
+        }
+        let warn_days = warn_days.unwrap_or(DEFAULT_WARN_DAYS);
+        if u64::from(warn_days) * SECS_PER_DAY >= u64::from(refresh_months) * SECS_PER_MONTH {
+            return Err("warn_days must be shorter than the refresh period".to_string());
+        }
+        if stale_confidence_cap.is_some_and(|cap| !(0.0..=1.0).contains(&cap)) {
+            return Err("stale_confidence_cap must be in [0, 1]".to_string());
+        }
+
+        let policy = RefreshPolicy {
+            refresh_months,
+            warn_days,
+            stale_confidence_cap,
+            set_by: owner.to_string(),
+            set_at: now(),
+        };
+        self.store.put(POLICY_NAMESPACE, vault_id, &policy)?;
+        Ok(policy)
+    }
+
+    pub fn clear_refresh_policy(&self, vault_id: &str, owner: &str) -> Result<(), String> {
+        self.require_vault_owner(vault_id, owner)?;
+        self.store.update(POLICY_NAMESPACE, |ns| {
+            ns.remove(vault_id);
+        })
+    }
+
+    /// Check template ages on the task scheduler and publish expiry events
+    pub fn schedule_refresh_sweep(
+        self: Arc<Self>,
+        tasks: &TaskScheduler,
+        bus: Arc<EventBus>,
+        notifications: Arc<NotificationService>,
+        every: Duration,
+    ) -> Result<(), String> {
+        tasks.register(
+            "templates.refresh",
+            &Schedule::every(every),
+            every / 10,
+            move || {
+                let (service, bus, notifications) =
+                    (self.clone(), bus.clone(), notifications.clone());
+                async move {
+                    let sent = service.sweep_refresh(&bus, &notifications)?;
+                    if sent > 0 {
+                        info!("Template refresh sweep published {} events", sent);
+                    }
+                    Ok(())
+                }
+            },
+        )
+    }
+
+    fn sweep_refresh(
+        &self,
+        bus: &EventBus,
+        notifications: &NotificationService,
+    ) -> Result<usize, String> {
+        let policies = self.store.list::<RefreshPolicy>(POLICY_NAMESPACE)?;
+        if policies.is_empty() {
+            return Ok(0);
+        }
+
+        let now = now();
+        let mut sent = 0;
+        for (_, stored) in self.store.list::<StoredTemplate>(NAMESPACE)? {
+            let Some((_, policy)) = policies.iter().find(|(v, _)| *v == stored.meta.vault_id)
+            else {
+                continue;
+            };
+            let stage = policy.freshness(stored.meta.enrolled_at, now);
+            if stage == Freshness::Fresh || stored.meta.reenrollment_reason.is_some() {
+                continue;
+            }
+
+            let key = key_of(&stored.meta);
+            let announced = self
+                .store
+                .get::<Notice>(NOTICE_NAMESPACE, &key)?
+                .filter(|n| n.enrolled_at == stored.meta.enrolled_at)
+                .map(|n| n.stage);
+            if announced.is_some_and(|a| a >= stage) {
+                continue;
+            }
+
+            let expires_at = policy.expires_at(stored.meta.enrolled_at);
+            let template = match &stored.meta.label {
+                Some(label) => format!("{} template \"{}\"", stored.meta.method, label),
+                None => format!("{} template", stored.meta.method),
+            };
+            let notice = TemplateNotice {
+                vault_id: stored.meta.vault_id.clone(),
+                owner: stored.meta.owner.clone(),
+                method: stored.meta.method.clone(),
+                template_id: stored.meta.template_id.clone(),
+                label: stored.meta.label.clone(),
+                expires_at,
+                stale_confidence_cap: policy.stale_confidence_cap,
+            };
+            let (event, title, body) = match stage {
+                Freshness::Expired => (
+                    BusEvent::TemplateExpired(notice),
+                    "Biometric template expired",
+                    format!(
+                        "Your {} has passed its refresh deadline; enroll a new one to keep \
+                         verifying with it.",
+                        template
+                    ),
+                ),
+                Freshness::Expiring | Freshness::Fresh => (
+                    BusEvent::TemplateExpiring(notice),
+                    "Biometric template expiring",
+                    format!(
+                        "Your {} must be refreshed within {} days.",
+                        template,
+                        expires_at.saturating_sub(now).div_ceil(SECS_PER_DAY)
+                    ),
+                ),
+            };
+            let kind = event.kind();
+            bus.publish(event)?;
+            notifications.notify(
+                &stored.meta.vault_id,
+                &Notification {
+                    kind: kind.to_string(),
+                    title: title.to_string(),
+                    body,
+                },
+            )?;
+            self.store.put(
+                NOTICE_NAMESPACE,
+                &key,
+                &Notice {
+                    enrolled_at: stored.meta.enrolled_at,
+                    stage,
+                },
+            )?;
+            sent += 1;
+        }
+        Ok(sent)
+    }
+
+    fn require_vault_owner(&self, vault_id: &str, owner: &str) -> Result<(), String> {
+        let entries = self.entries(vault_id, None)?;
+        if entries.is_empty() {
+            return Err("Enroll a template before setting a refresh policy".to_string());
+        }
+        if entries.iter().any(|t| t.meta.owner != owner) {
+            return Err("Templates belong to a different owner".to_string());
+        }
+        Ok(())
+    }
+}
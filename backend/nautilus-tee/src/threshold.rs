@@ -0,0 +1,788 @@
+/**
+ * Threshold Decryption
+ * t-of-n ElGamal over Ristretto255 where no single party holds the
+ * decryption key after setup
+ *
+ * Setup: the enclave draws a random polynomial f of degree t-1, so it
+ * knows the decryption key f(0) for as long as setup runs. It deals
+ * x_i = f(i), seals each x_i to its guardian, publishes Y = f(0)·G and the
+ * verification keys Y_i = x_i·G, then drops the polynomial; f(0) is never
+ * stored and never reassembled afterwards.
+ *
+ * Ciphertexts are hybrid: R = r·G, K = r·Y, and the payload is
+ * ChaCha20-Poly1305 under HKDF(K). To decrypt, each guardian submits
+ * D_i = x_i·R with a Chaum-Pedersen proof that log_G(Y_i) = log_R(D_i);
+ * the enclave combines K = Σ λ_i·D_i in the exponent, which opens this
+ * one ciphertext and nothing else, and only releases the plaintext under
+ * policy. Sessions collecting partials expire after SESSION_TTL, and at
+ * most MAX_SESSIONS are open at once.
+ *
+ * Only the vault's own ciphertexts are decrypted: a ciphertext's blob,
+ * R || nonce || payload, must be among the vault's committed blobs (see
+ * integrity) for a session to open, and for a release to verify, so
+ * guardians' partials can't be spent on an arbitrary ciphertext.
+ *
+ * Releases are verifiable: the release carries the ciphertext and the
+ * proven partials that were combined. Anyone holding the vault's public
+ * key set can check each proof, recombine K and decrypt, so a dispute over
+ * whether the plaintext was tampered with doesn't rest on trusting the
+ * enclave. Since K opens that ciphertext, sharing the proof shares the
+ * plaintext.
+ */
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use curve25519_dalek::constants::RISTRETTO_BASEPOINT_POINT as G;
+use curve25519_dalek::ristretto::{CompressedRistretto, RistrettoPoint};
+use curve25519_dalek::scalar::Scalar;
+use hkdf::Hkdf;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha512};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use subtle::ConstantTimeEq;
+use tracing::info;
+use zeroize::Zeroizing;
+
+use crate::auth::normalize_address;
+use crate::crypto::{self, Envelope};
+use crate::ct;
+use crate::integrity::{self, IntegrityService, VaultCommitment};
+use crate::policy::PolicyEngine;
+use crate::secrets::GuardianKey;
+use crate::store::Store;
+
+const NAMESPACE: &str = "threshold_keys";
+const CIPHERTEXT_INFO: &[u8] = b"lumina-threshold-elgamal-v1";
+/// Guardians are gathered out of band, so a session waits a day for them
+const SESSION_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+const MAX_SESSIONS: usize = 1024;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct VerificationKey {
+    pub address: String,
+    pub index: u64,
+    pub key: String, // Base64 compressed Y_i
+}
+
+/// Public parameters for a vault's threshold key; no secret material
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ThresholdKeySet {
+    pub vault_id: String,
+    pub owner: String,
+    pub threshold: u8,
+    pub public_key: String, // Base64 compressed Y
+    pub verification_keys: Vec<VerificationKey>,
+    pub created_at: u64,
+}
+
+#[derive(Serialize)]
+pub struct SealedKeyShare {
+    pub guardian_address: String,
+    pub index: u64,
+    pub envelope: Envelope,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ThresholdCiphertext {
+    pub ephemeral: String,  // Base64 compressed R
+    pub nonce: String,      // Base64 12-byte nonce
+    pub ciphertext: String, // Base64 payload + tag
+}
+
+impl ThresholdCiphertext {
+    /// Integrity leaf of the ciphertext's blob, R || nonce || payload
+    pub fn leaf(&self) -> Result<[u8; 32], String> {
+        let blob = [
+            crypto::decode(&self.ephemeral)?,
+            crypto::decode(&self.nonce)?,
+            crypto::decode(&self.ciphertext)?,
+        ]
+        .concat();
+        Ok(integrity::blob_leaf(&blob))
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PartialDecryption {
+    pub partial: String,   // Base64 compressed D_i
+    pub challenge: String, // Base64 scalar c
+    pub response: String,  // Base64 scalar s
+}
+
+/// A guardian's verified partial decryption, as published in release proofs
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PartialRecord {
+    pub index: u64,
+    pub guardian_address: String,
+    #[serde(flatten)]
+    pub partial: PartialDecryption,
+}
+
+/// Evidence that released plaintext is the decryption of a ciphertext
+/// under the vault's threshold key
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DecryptionProof {
+    pub vault_id: String,
+    pub public_key: String, // Base64 compressed Y
+    pub ciphertext: ThresholdCiphertext,
+    /// Exactly the partials that were combined
+    pub partials: Vec<PartialRecord>,
+    pub plaintext_digest: String, // Hex SHA-256 of the released plaintext
+}
+
+impl DecryptionProof {
+    /// Digest bound into the release attestation
+    pub fn digest(&self) -> Result<Vec<u8>, String> {
+        let bytes = serde_json::to_vec(self)
+            .map_err(|e| format!("Failed to serialize decryption proof: {}", e))?;
+        Ok(Sha256::digest(bytes).to_vec())
+    }
+}
+
+#[derive(Serialize)]
+pub struct SessionStatus {
+    pub session_id: String,
+    pub ephemeral: String, // R, which guardians raise to their share
+    pub threshold: u8,
+    pub verified: usize,
+    pub combined: bool,
+}
+
+struct DecryptionSession {
+    vault_id: String,
+    requester: String,
+    ciphertext: ThresholdCiphertext,
+    partials: HashMap<u64, RistrettoPoint>,
+    records: Vec<PartialRecord>,
+    plaintext: Option<(Zeroizing<Vec<u8>>, DecryptionProof)>,
+    expires_at: Instant,
+}
+
+pub struct ThresholdService {
+    store: Arc<Store>,
+    integrity: Arc<IntegrityService>,
+    sessions: Mutex<HashMap<String, DecryptionSession>>,
+}
+
+impl ThresholdService {
+    pub fn new(store: Arc<Store>, integrity: Arc<IntegrityService>) -> Self {
+        Self {
+            store,
+            integrity,
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Deal key shares to guardians and publish the vault's threshold public key
+    pub fn setup(
+        &self,
+        vault_id: &str,
+        owner: &str,
+        threshold: u8,
+        guardians: &[GuardianKey],
+    ) -> Result<(ThresholdKeySet, Vec<SealedKeyShare>), String> {
+        if threshold < 2 || threshold as usize > guardians.len() || guardians.len() > 255 {
+            return Err(format!(
+                "Threshold must be between 2 and the number of guardians ({})",
+                guardians.len()
+            ));
+        }
+        if let Some(existing) = self.key_set(vault_id)? {
+            if existing.owner != owner {
+                return Err("Threshold key belongs to a different owner".to_string());
+            }
+        }
+
+        // f(x) = a_0 + a_1 x + ... + a_{t-1} x^{t-1}; a_0 is the decryption key
+        let coefficients: Zeroizing<Vec<Scalar>> =
+            Zeroizing::new((0..threshold).map(|_| random_scalar()).collect());
+        let public_key = coefficients[0] * G;
+
+        let mut verification_keys = Vec::with_capacity(guardians.len());
+        let mut sealed = Vec::with_capacity(guardians.len());
+
+        for (i, guardian) in guardians.iter().enumerate() {
+            let index = i as u64 + 1;
+            let x = Scalar::from(index);
+            let share = Zeroizing::new(
+                coefficients
+                    .iter()
+                    .rev()
+                    .fold(Scalar::ZERO, |acc, c| acc * x + c),
+            );
+            let address = normalize_address(&guardian.address);
+            let recipient = crypto::decode_public_key(&guardian.public_key)?;
+
+            verification_keys.push(VerificationKey {
+                address: address.clone(),
+                index,
+                key: encode_point(&(*share * G)),
+            });
+            sealed.push(SealedKeyShare {
+                envelope: crypto::seal(
+                    &recipient,
+                    share.as_bytes(),
+                    share_aad(vault_id, &address).as_bytes(),
+                )?,
+                guardian_address: address,
+                index,
+            });
+        }
+
+        let key_set = ThresholdKeySet {
+            vault_id: vault_id.to_string(),
+            owner: owner.to_string(),
+            threshold,
+            public_key: encode_point(&public_key),
+            verification_keys,
+            created_at: now(),
+        };
+        self.store.put(NAMESPACE, vault_id, &key_set)?;
+
+        Ok((key_set, sealed))
+    }
+
+    /// Open a decryption session for a ciphertext under the vault's key
+    pub fn open_session(
+        &self,
+        vault_id: &str,
+        requester: &str,
+        ciphertext: ThresholdCiphertext,
+    ) -> Result<SessionStatus, String> {
+        let key_set = self
+            .key_set(vault_id)?
+            .ok_or_else(|| format!("No threshold key for vault {}", vault_id))?;
+        decode_point(&ciphertext.ephemeral)?;
+        require_committed(&self.commitment(vault_id)?, vault_id, &ciphertext)?;
+        let ephemeral = ciphertext.ephemeral.clone();
+
+        let mut id = [0u8; 16];
+        OsRng.fill_bytes(&mut id);
+        let session_id = hex::encode(id);
+
+        let now = Instant::now();
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|_, s| s.expires_at > now);
+        if sessions.len() >= MAX_SESSIONS {
+            return Err("Too many decryption sessions are open".to_string());
+        }
+        sessions.insert(
+            session_id.clone(),
+            DecryptionSession {
+                vault_id: vault_id.to_string(),
+                requester: requester.to_string(),
+                ciphertext,
+                partials: HashMap::new(),
+                records: Vec::new(),
+                plaintext: None,
+                expires_at: now + SESSION_TTL,
+            },
+        );
+
+        Ok(SessionStatus {
+            session_id,
+            ephemeral,
+            threshold: key_set.threshold,
+            verified: 0,
+            combined: false,
+        })
+    }
+
+    /// Verify a guardian's partial decryption and combine once policy allows
+    pub fn submit_partial(
+        &self,
+        vault_id: &str,
+        session_id: &str,
+        guardian_address: &str,
+        partial: &PartialDecryption,
+        policy: &PolicyEngine,
+    ) -> Result<SessionStatus, String> {
+        let key_set = self
+            .key_set(vault_id)?
+            .ok_or_else(|| format!("No threshold key for vault {}", vault_id))?;
+        let verification_key = key_set
+            .verification_keys
+            .iter()
+            .find(|k| k.address == guardian_address)
+            .ok_or("Caller is not a guardian of this vault")?;
+
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .get_mut(session_id)
+            .filter(|s| s.vault_id == vault_id && s.expires_at > Instant::now())
+            .ok_or("Unknown decryption session")?;
+
+        let ephemeral = decode_point(&session.ciphertext.ephemeral)?;
+        let share_point = decode_point(&partial.partial)?;
+        verify_dleq(
+            &decode_point(&verification_key.key)?,
+            &ephemeral,
+            &share_point,
+            &decode_scalar(&partial.challenge)?,
+            &decode_scalar(&partial.response)?,
+        )?;
+        if session
+            .partials
+            .insert(verification_key.index, share_point)
+            .is_none()
+        {
+            session.records.push(PartialRecord {
+                index: verification_key.index,
+                guardian_address: verification_key.address.clone(),
+                partial: partial.clone(),
+            });
+        }
+
+        let decision = policy.evaluate_threshold_decryption(&key_set, session.partials.len());
+        info!(
+            "Threshold decryption {} for {}: {}",
+            session_id, vault_id, decision.reason
+        );
+        if decision.allowed && session.plaintext.is_none() {
+            let shared = combine_partials(&session.partials);
+            let plaintext = decrypt(&shared, &session.ciphertext)?;
+            let proof = DecryptionProof {
+                vault_id: vault_id.to_string(),
+                public_key: key_set.public_key.clone(),
+                ciphertext: session.ciphertext.clone(),
+                partials: session.records.clone(),
+                plaintext_digest: hex::encode(Sha256::digest(plaintext.as_slice())),
+            };
+            session.plaintext = Some((plaintext, proof));
+        }
+
+        Ok(SessionStatus {
+            session_id: session_id.to_string(),
+            ephemeral: session.ciphertext.ephemeral.clone(),
+            threshold: key_set.threshold,
+            verified: session.partials.len(),
+            combined: session.plaintext.is_some(),
+        })
+    }
+
+    /// Hand the combined plaintext and its decryption proof to the
+    /// session's requester, once
+    pub fn release(
+        &self,
+        vault_id: &str,
+        session_id: &str,
+        requester: &str,
+        policy: &PolicyEngine,
+    ) -> Result<(Zeroizing<Vec<u8>>, DecryptionProof), String> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .get(session_id)
+            .filter(|s| s.vault_id == vault_id && s.expires_at > Instant::now())
+            .ok_or("Unknown decryption session")?;
+
+        let decision = policy.evaluate_threshold_release(
+            &session.requester,
+            requester,
+            session.plaintext.is_some(),
+        );
+        if !decision.allowed {
+            return Err(decision.reason);
+        }
+
+        sessions
+            .remove(session_id)
+            .and_then(|s| s.plaintext)
+            .ok_or_else(|| "Session has no plaintext".to_string())
+    }
+
+    /// Check a release against the vault's published key set
+    pub fn verify_release(&self, proof: &DecryptionProof, plaintext: &[u8]) -> Result<(), String> {
+        let key_set = self
+            .key_set(&proof.vault_id)?
+            .ok_or_else(|| format!("No threshold key for vault {}", proof.vault_id))?;
+        let commitment = self.commitment(&proof.vault_id)?;
+        verify_decryption(&key_set, &commitment, proof, plaintext)
+    }
+
+    fn commitment(&self, vault_id: &str) -> Result<VaultCommitment, String> {
+        self.integrity
+            .commitment(vault_id)?
+            .ok_or_else(|| format!("Vault {} has no integrity commitment", vault_id))
+    }
+
+    pub fn key_set(&self, vault_id: &str) -> Result<Option<ThresholdKeySet>, String> {
+        self.store.get(NAMESPACE, vault_id)
+    }
+
+    /// Drop the vault's decryption sessions, with any partials and
+    /// plaintext they hold; returns their ids
+    pub fn forget(&self, vault_id: &str) -> Vec<String> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let ids: Vec<String> = sessions
+            .iter()
+            .filter(|(_, s)| s.vault_id == vault_id)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in &ids {
+            sessions.remove(id);
+        }
+        ids
+    }
+}
+
+/// Recompute the decryption from the proven partials alone; succeeds only
+/// if the ciphertext is one of the vault's committed blobs and `plaintext`
+/// is exactly what it decrypts to
+pub fn verify_decryption(
+    key_set: &ThresholdKeySet,
+    commitment: &VaultCommitment,
+    proof: &DecryptionProof,
+    plaintext: &[u8],
+) -> Result<(), String> {
+    if proof.public_key != key_set.public_key {
+        return Err("Proof is for a different threshold key".to_string());
+    }
+    require_committed(commitment, &proof.vault_id, &proof.ciphertext)?;
+    if proof.partials.len() < key_set.threshold as usize {
+        return Err(format!(
+            "Proof has {} partials; the key needs {}",
+            proof.partials.len(),
+            key_set.threshold
+        ));
+    }
+
+    let ephemeral = decode_point(&proof.ciphertext.ephemeral)?;
+    let mut partials = HashMap::with_capacity(proof.partials.len());
+    for record in &proof.partials {
+        let verification_key = key_set
+            .verification_keys
+            .iter()
+            .find(|k| k.index == record.index && k.address == record.guardian_address)
+            .ok_or_else(|| format!("Partial {} is not from a guardian", record.index))?;
+        let point = decode_point(&record.partial.partial)?;
+        verify_dleq(
+            &decode_point(&verification_key.key)?,
+            &ephemeral,
+            &point,
+            &decode_scalar(&record.partial.challenge)?,
+            &decode_scalar(&record.partial.response)?,
+        )?;
+        if partials.insert(record.index, point).is_some() {
+            return Err(format!("Partial {} appears twice", record.index));
+        }
+    }
+
+    let decrypted = decrypt(&combine_partials(&partials), &proof.ciphertext)?;
+    let digest = hex::encode(Sha256::digest(plaintext));
+    if !ct::str_eq(&digest, &proof.plaintext_digest)
+        || !bool::from(decrypted.as_slice().ct_eq(plaintext))
+    {
+        return Err("Plaintext is not the decryption of this ciphertext".to_string());
+    }
+    Ok(())
+}
+
+fn require_committed(
+    commitment: &VaultCommitment,
+    vault_id: &str,
+    ciphertext: &ThresholdCiphertext,
+) -> Result<(), String> {
+    let leaf = hex::encode(ciphertext.leaf()?);
+    if commitment.vault_id != vault_id || !commitment.leaves.contains(&leaf) {
+        return Err("Ciphertext is not one of the vault's committed blobs".to_string());
+    }
+    Ok(())
+}
+
+/// Σ λ_i·D_i with Lagrange coefficients at zero, i.e. r·Y without a_0
+fn combine_partials(partials: &HashMap<u64, RistrettoPoint>) -> RistrettoPoint {
+    partials
+        .iter()
+        .map(|(&i, point)| {
+            let xi = Scalar::from(i);
+            let lambda = partials
+                .keys()
+                .filter(|&&j| j != i)
+                .fold(Scalar::ONE, |acc, &j| {
+                    let xj = Scalar::from(j);
+                    acc * xj * (xj - xi).invert()
+                });
+            lambda * point
+        })
+        .sum()
+}
+
+fn decrypt(
+    shared: &RistrettoPoint,
+    ciphertext: &ThresholdCiphertext,
+) -> Result<Zeroizing<Vec<u8>>, String> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    Hkdf::<Sha256>::new(
+        Some(&crypto::decode(&ciphertext.ephemeral)?),
+        shared.compress().as_bytes(),
+    )
+    .expand(CIPHERTEXT_INFO, key.as_mut())
+    .map_err(|_| "Key derivation failed".to_string())?;
+
+    crypto::aead_decrypt(
+        &key,
+        &crypto::decode(&ciphertext.nonce)?,
+        &crypto::decode(&ciphertext.ciphertext)?,
+        CIPHERTEXT_INFO,
+    )
+    .map(Zeroizing::new)
+}
+
+/// Chaum-Pedersen: the same x_i links G→Y_i and R→D_i
+fn verify_dleq(
+    verification_key: &RistrettoPoint,
+    ephemeral: &RistrettoPoint,
+    partial: &RistrettoPoint,
+    challenge: &Scalar,
+    response: &Scalar,
+) -> Result<(), String> {
+    let a = response * G - challenge * verification_key;
+    let b = response * ephemeral - challenge * partial;
+
+    let mut hasher = Sha512::new();
+    for point in [&G, verification_key, ephemeral, partial, &a, &b] {
+        hasher.update(point.compress().as_bytes());
+    }
+    let expected = Scalar::from_bytes_mod_order_wide(&hasher.finalize().into());
+
+    if !bool::from(expected.ct_eq(challenge)) {
+        return Err("Partial decryption proof is invalid".to_string());
+    }
+    Ok(())
+}
+
+fn share_aad(vault_id: &str, guardian_address: &str) -> String {
+    format!("lumina-threshold-share:{}:{}", vault_id, guardian_address)
+}
+
+fn random_scalar() -> Scalar {
+    let mut bytes = Zeroizing::new([0u8; 64]);
+    OsRng.fill_bytes(bytes.as_mut());
+    Scalar::from_bytes_mod_order_wide(&bytes)
+}
+
+fn encode_point(point: &RistrettoPoint) -> String {
+    STANDARD.encode(point.compress().as_bytes())
+}
+
+fn decode_point(encoded: &str) -> Result<RistrettoPoint, String> {
+    let bytes = crypto::decode(encoded)?;
+    CompressedRistretto::from_slice(&bytes)
+        .ok()
+        .and_then(|c| c.decompress())
+        .ok_or_else(|| "Invalid Ristretto point".to_string())
+}
+
+fn decode_scalar(encoded: &str) -> Result<Scalar, String> {
+    let bytes: [u8; 32] = crypto::decode(encoded)?
+        .try_into()
+        .map_err(|_| "Scalar must be 32 bytes".to_string())?;
+    Option::from(Scalar::from_canonical_bytes(bytes))
+        .ok_or_else(|| "Non-canonical scalar".to_string())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A dealt 3-of-5 key, returned with the shares guardians would hold
+    fn dealt() -> (ThresholdKeySet, Vec<Scalar>) {
+        let coefficients: Vec<Scalar> = (0..3).map(|_| random_scalar()).collect();
+        let shares: Vec<Scalar> = (1..=5u64)
+            .map(|i| {
+                let x = Scalar::from(i);
+                coefficients
+                    .iter()
+                    .rev()
+                    .fold(Scalar::ZERO, |acc, c| acc * x + c)
+            })
+            .collect();
+        let key_set = ThresholdKeySet {
+            vault_id: "vault-1".to_string(),
+            owner: "0xowner".to_string(),
+            threshold: 3,
+            public_key: encode_point(&(coefficients[0] * G)),
+            verification_keys: shares
+                .iter()
+                .enumerate()
+                .map(|(i, share)| VerificationKey {
+                    address: format!("0x{}", i + 1),
+                    index: i as u64 + 1,
+                    key: encode_point(&(share * G)),
+                })
+                .collect(),
+            created_at: 0,
+        };
+        (key_set, shares)
+    }
+
+    fn encrypt(key_set: &ThresholdKeySet, plaintext: &[u8]) -> ThresholdCiphertext {
+        let r = random_scalar();
+        let ephemeral = encode_point(&(r * G));
+        let shared = r * decode_point(&key_set.public_key).unwrap();
+        let mut key = [0u8; 32];
+        Hkdf::<Sha256>::new(
+            Some(&crypto::decode(&ephemeral).unwrap()),
+            shared.compress().as_bytes(),
+        )
+        .expand(CIPHERTEXT_INFO, &mut key)
+        .unwrap();
+        let (nonce, ciphertext) = crypto::aead_encrypt(&key, plaintext, CIPHERTEXT_INFO).unwrap();
+        ThresholdCiphertext {
+            ephemeral,
+            nonce: STANDARD.encode(nonce),
+            ciphertext: STANDARD.encode(ciphertext),
+        }
+    }
+
+    /// What guardian `index` submits: D_i = x_i·R with its Chaum-Pedersen proof
+    fn partial(share: &Scalar, ciphertext: &ThresholdCiphertext, index: u64) -> PartialRecord {
+        let ephemeral = decode_point(&ciphertext.ephemeral).unwrap();
+        let (verification_key, point) = (share * G, share * ephemeral);
+        let k = random_scalar();
+        let mut hasher = Sha512::new();
+        for p in [
+            &G,
+            &verification_key,
+            &ephemeral,
+            &point,
+            &(k * G),
+            &(k * ephemeral),
+        ] {
+            hasher.update(p.compress().as_bytes());
+        }
+        let challenge = Scalar::from_bytes_mod_order_wide(&hasher.finalize().into());
+        PartialRecord {
+            index,
+            guardian_address: format!("0x{}", index),
+            partial: PartialDecryption {
+                partial: encode_point(&point),
+                challenge: STANDARD.encode(challenge.as_bytes()),
+                response: STANDARD.encode((k + challenge * share).as_bytes()),
+            },
+        }
+    }
+
+    fn committed(key_set: &ThresholdKeySet, ciphertext: &ThresholdCiphertext) -> VaultCommitment {
+        VaultCommitment {
+            vault_id: key_set.vault_id.clone(),
+            owner: key_set.owner.clone(),
+            root: String::new(),
+            leaves: vec![hex::encode(ciphertext.leaf().unwrap())],
+            committed_at: 0,
+        }
+    }
+
+    fn proof(
+        key_set: &ThresholdKeySet,
+        ciphertext: &ThresholdCiphertext,
+        partials: Vec<PartialRecord>,
+        plaintext: &[u8],
+    ) -> DecryptionProof {
+        DecryptionProof {
+            vault_id: key_set.vault_id.clone(),
+            public_key: key_set.public_key.clone(),
+            ciphertext: ciphertext.clone(),
+            partials,
+            plaintext_digest: hex::encode(Sha256::digest(plaintext)),
+        }
+    }
+
+    #[test]
+    fn any_threshold_of_guardians_decrypts() {
+        let (key_set, shares) = dealt();
+        let ciphertext = encrypt(&key_set, b"vault contents");
+        let commitment = committed(&key_set, &ciphertext);
+
+        for subset in [[1u64, 2, 3], [2, 4, 5], [5, 1, 3]] {
+            let partials = subset
+                .iter()
+                .map(|&i| partial(&shares[i as usize - 1], &ciphertext, i))
+                .collect();
+            let proof = proof(&key_set, &ciphertext, partials, b"vault contents");
+            verify_decryption(&key_set, &commitment, &proof, b"vault contents").unwrap();
+            assert!(verify_decryption(&key_set, &commitment, &proof, b"other contents").is_err());
+        }
+
+        let partials = [1u64, 2]
+            .iter()
+            .map(|&i| partial(&shares[i as usize - 1], &ciphertext, i))
+            .collect();
+        let proof = proof(&key_set, &ciphertext, partials, b"vault contents");
+        assert!(verify_decryption(&key_set, &commitment, &proof, b"vault contents").is_err());
+    }
+
+    #[test]
+    fn only_committed_ciphertexts_verify() {
+        let (key_set, shares) = dealt();
+        let ciphertext = encrypt(&key_set, b"vault contents");
+        let partials = [1u64, 2, 3]
+            .iter()
+            .map(|&i| partial(&shares[i as usize - 1], &ciphertext, i))
+            .collect();
+        let proof = proof(&key_set, &ciphertext, partials, b"vault contents");
+
+        // A ciphertext under the vault's key, but never committed as its blob
+        let other = committed(&key_set, &encrypt(&key_set, b"vault contents"));
+        let error = verify_decryption(&key_set, &other, &proof, b"vault contents").unwrap_err();
+        assert!(error.contains("committed blobs"));
+
+        let mut elsewhere = committed(&key_set, &ciphertext);
+        elsewhere.vault_id = "vault-2".to_string();
+        assert!(verify_decryption(&key_set, &elsewhere, &proof, b"vault contents").is_err());
+    }
+
+    #[test]
+    fn partials_from_the_wrong_share_are_rejected() {
+        let (key_set, shares) = dealt();
+        let ciphertext = encrypt(&key_set, b"vault contents");
+        let commitment = committed(&key_set, &ciphertext);
+
+        // Guardian 3 proves against its own key but claims index 2
+        let mut forged = partial(&shares[2], &ciphertext, 2);
+        forged.guardian_address = "0x2".to_string();
+        let ephemeral = decode_point(&ciphertext.ephemeral).unwrap();
+        let point = decode_point(&forged.partial.partial).unwrap();
+        assert!(verify_dleq(
+            &decode_point(&key_set.verification_keys[1].key).unwrap(),
+            &ephemeral,
+            &point,
+            &decode_scalar(&forged.partial.challenge).unwrap(),
+            &decode_scalar(&forged.partial.response).unwrap(),
+        )
+        .is_err());
+
+        let partials = vec![
+            partial(&shares[0], &ciphertext, 1),
+            forged,
+            partial(&shares[3], &ciphertext, 4),
+        ];
+        let proof = proof(&key_set, &ciphertext, partials, b"vault contents");
+        assert!(verify_decryption(&key_set, &commitment, &proof, b"vault contents").is_err());
+    }
+
+    #[test]
+    fn duplicate_partials_are_rejected() {
+        let (key_set, shares) = dealt();
+        let ciphertext = encrypt(&key_set, b"vault contents");
+        let commitment = committed(&key_set, &ciphertext);
+        let first = partial(&shares[0], &ciphertext, 1);
+        let partials = vec![first.clone(), first, partial(&shares[1], &ciphertext, 2)];
+
+        let proof = proof(&key_set, &ciphertext, partials, b"vault contents");
+        let error =
+            verify_decryption(&key_set, &commitment, &proof, b"vault contents").unwrap_err();
+        assert!(error.contains("appears twice"));
+    }
+}
@@ -0,0 +1,141 @@
+/**
+ * Timestamping
+ * RFC 3161-style tokens proving a digest existed by a given time
+ *
+ * The enclave plays the time-stamping authority. A token carries the
+ * fields of an RFC 3161 TSTInfo (policy, message imprint, serial number,
+ * generation time, accuracy, nonce) as JSON and is signed by the enclave
+ * identity over DOMAIN || TSTInfo JSON, so anyone holding the pinned
+ * identity key can check it without the ZK pipeline.
+ *
+ * Generation time comes from the hardened clock, and serial numbers and
+ * times never go backwards across restarts. If the clock reads earlier
+ * than the last token issued, the last token's time is used instead: a
+ * later time only weakens the claim that the digest existed before it.
+ */
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+use crate::clock::MAX_SKEW;
+use crate::keys::identity::EnclaveIdentity;
+use crate::store::Store;
+
+const NAMESPACE: &str = "timestamps";
+const STATE_KEY: &str = "authority";
+const DOMAIN: &[u8] = b"lumina-timestamp-v1:";
+pub const POLICY: &str = "lumina-tsa-v1";
+const MAX_NONCE_BYTES: usize = 32;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MessageImprint {
+    /// sha256, sha384 or sha512
+    pub hash_algorithm: String,
+    pub hashed_message: String, // Hex
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TstInfo {
+    pub version: u32,
+    pub policy: String,
+    pub message_imprint: MessageImprint,
+    pub serial_number: u64,
+    /// Unix seconds
+    pub gen_time: u64,
+    pub accuracy_secs: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>, // Hex, echoed from the request
+    /// Key id of the enclave identity that signed
+    pub tsa: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TimestampToken {
+    pub tst_info: TstInfo,
+    pub signature: String, // Base64 Ed25519 over DOMAIN || tst_info JSON (fields in the order above)
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct AuthorityState {
+    serial: u64,
+    last_gen_time: u64,
+}
+
+pub struct TimestampService {
+    store: Arc<Store>,
+    identity: Arc<EnclaveIdentity>,
+    /// Held while a serial number is taken
+    issuing: Mutex<()>,
+}
+
+impl TimestampService {
+    pub fn new(store: Arc<Store>, identity: Arc<EnclaveIdentity>) -> Self {
+        Self {
+            store,
+            identity,
+            issuing: Mutex::new(()),
+        }
+    }
+
+    /// Issue a token over `digest`, which must match `hash_algorithm`'s
+    /// output length, at `now` (Unix seconds) from the hardened clock
+    pub fn issue(
+        &self,
+        now: u64,
+        hash_algorithm: &str,
+        digest: &[u8],
+        nonce: Option<&[u8]>,
+    ) -> Result<TimestampToken, String> {
+        let expected = match hash_algorithm {
+            "sha256" => 32,
+            "sha384" => 48,
+            "sha512" => 64,
+            other => return Err(format!("Unsupported hash algorithm {}", other)),
+        };
+        if digest.len() != expected {
+            return Err(format!(
+                "A {} digest is {} bytes, got {}",
+                hash_algorithm,
+                expected,
+                digest.len()
+            ));
+        }
+        if nonce.is_some_and(|n| n.is_empty() || n.len() > MAX_NONCE_BYTES) {
+            return Err(format!("Nonce must be 1 to {} bytes", MAX_NONCE_BYTES));
+        }
+
+        let _issuing = self.issuing.lock().unwrap();
+        let mut state: AuthorityState = self.store.get(NAMESPACE, STATE_KEY)?.unwrap_or_default();
+        state.serial += 1;
+        state.last_gen_time = state.last_gen_time.max(now);
+        self.store.put(NAMESPACE, STATE_KEY, &state)?;
+
+        let tst_info = TstInfo {
+            version: 1,
+            policy: POLICY.to_string(),
+            message_imprint: MessageImprint {
+                hash_algorithm: hash_algorithm.to_string(),
+                hashed_message: hex::encode(digest),
+            },
+            serial_number: state.serial,
+            gen_time: state.last_gen_time,
+            accuracy_secs: MAX_SKEW.as_secs(),
+            nonce: nonce.map(hex::encode),
+            tsa: self.identity.key_id(),
+        };
+        let signature = self.identity.sign(&signed_message(&tst_info)?);
+        Ok(TimestampToken {
+            tst_info,
+            signature: STANDARD.encode(signature.to_bytes()),
+        })
+    }
+}
+
+/// The bytes a token's signature covers
+pub fn signed_message(tst_info: &TstInfo) -> Result<Vec<u8>, String> {
+    let json = serde_json::to_vec(tst_info)
+        .map_err(|e| format!("Failed to serialize TSTInfo: {}", e))?;
+    Ok([DOMAIN, json.as_slice()].concat())
+}
@@ -0,0 +1,137 @@
+/**
+ * TLS Termination
+ * rustls server config for the TCP listener, with optional client
+ * certificate verification and hot reload when certificates rotate
+ */
+
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+use tokio_rustls::TlsAcceptor;
+use tracing::{info, warn};
+
+use crate::config::TlsConfig;
+
+pub struct TlsReloader {
+    config: TlsConfig,
+    acceptor: RwLock<TlsAcceptor>,
+    loaded_at: RwLock<Vec<Option<SystemTime>>>,
+}
+
+impl TlsReloader {
+    pub fn new(config: TlsConfig) -> Result<Self, String> {
+        let server_config = load_server_config(&config)?;
+        let loaded_at = file_mtimes(&config);
+
+        Ok(Self {
+            config,
+            acceptor: RwLock::new(TlsAcceptor::from(Arc::new(server_config))),
+            loaded_at: RwLock::new(loaded_at),
+        })
+    }
+
+    /// Acceptor for the next connection; existing connections keep the
+    /// config they were established with
+    pub fn acceptor(&self) -> TlsAcceptor {
+        self.acceptor.read().unwrap().clone()
+    }
+
+    /// Poll certificate files and swap in a new config when any of them change.
+    /// A broken rotation keeps the previous config serving.
+    pub fn spawn_reload(self: Arc<Self>) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.config.reload_interval);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                self.reload_if_changed();
+            }
+        });
+    }
+
+    fn reload_if_changed(&self) {
+        let current = file_mtimes(&self.config);
+        if *self.loaded_at.read().unwrap() == current {
+            return;
+        }
+
+        match load_server_config(&self.config) {
+            Ok(server_config) => {
+                *self.acceptor.write().unwrap() = TlsAcceptor::from(Arc::new(server_config));
+                *self.loaded_at.write().unwrap() = current;
+                info!("Reloaded TLS certificates from {}", self.config.cert_path.display());
+            }
+            Err(e) => warn!("TLS reload failed, keeping previous certificates: {}", e),
+        }
+    }
+}
+
+fn load_server_config(config: &TlsConfig) -> Result<ServerConfig, String> {
+    let certs = load_certs(&config.cert_path)?;
+    let key = load_key(&config.key_path)?;
+
+    let builder = match &config.client_ca_path {
+        Some(ca_path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in load_certs(ca_path)? {
+                roots
+                    .add(cert)
+                    .map_err(|e| format!("Invalid client CA certificate: {}", e))?;
+            }
+
+            let verifier = WebPkiClientVerifier::builder(Arc::new(roots));
+            let verifier = if config.client_auth_required {
+                verifier.build()
+            } else {
+                verifier.allow_unauthenticated().build()
+            }
+            .map_err(|e| format!("Failed to build client verifier: {}", e))?;
+
+            ServerConfig::builder().with_client_cert_verifier(verifier)
+        }
+        None => ServerConfig::builder().with_no_client_auth(),
+    };
+
+    let mut server_config = builder
+        .with_single_cert(certs, key)
+        .map_err(|e| format!("Invalid TLS certificate/key: {}", e))?;
+    server_config.alpn_protocols = if config.http2 {
+        vec![b"h2".to_vec(), b"http/1.1".to_vec()]
+    } else {
+        vec![b"http/1.1".to_vec()]
+    };
+
+    Ok(server_config)
+}
+
+fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+
+    if certs.is_empty() {
+        return Err(format!("No certificates found in {}", path.display()));
+    }
+    Ok(certs)
+}
+
+fn load_key(path: &Path) -> Result<PrivateKeyDer<'static>, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    rustls_pemfile::private_key(&mut BufReader::new(file))
+        .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?
+        .ok_or_else(|| format!("No private key found in {}", path.display()))
+}
+
+fn file_mtimes(config: &TlsConfig) -> Vec<Option<SystemTime>> {
+    [Some(&config.cert_path), Some(&config.key_path), config.client_ca_path.as_ref()]
+        .into_iter()
+        .flatten()
+        .map(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+        .collect()
+}
@@ -0,0 +1,200 @@
+/**
+ * Request Validation
+ * Domain types that check themselves as request bodies are deserialized
+ *
+ * Identifiers and enumerated fields arrive as free-form strings. Request
+ * types and path parameters declare them with these instead, so a
+ * malformed value fails deserialization (422 INVALID_REQUEST naming the
+ * field, or 400 for a path) and handlers only ever see valid ones:
+ *
+ *   VaultId          1-128 ASCII letters, digits, '-' or '_'
+ *   SuiAddress       0x and up to 64 hex digits; normalized to all 64
+ *   ClaimType        a claim with a registered circuit
+ *   BiometricMethod  fingerprint, face or voice
+ *
+ * The canonical request hash is taken over the body as sent, so
+ * normalizing here doesn't change what attestations bind.
+ */
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::Deref;
+
+use crate::auth::normalize_address;
+
+const MAX_VAULT_ID_LEN: usize = 128;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct VaultId(String);
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct SuiAddress(String);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClaimType {
+    Keyword,
+    Timestamp,
+    FileHash,
+    IdentityAttribute,
+    Solvency,
+    DkimEmail,
+    Predicate,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BiometricMethod {
+    Fingerprint,
+    Face,
+    Voice,
+}
+
+impl TryFrom<String> for VaultId {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, String> {
+        if value.is_empty() || value.len() > MAX_VAULT_ID_LEN {
+            return Err(format!(
+                "vault_id must be 1 to {} characters",
+                MAX_VAULT_ID_LEN
+            ));
+        }
+        if !value
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+        {
+            return Err("vault_id may only contain letters, digits, '-' and '_'".to_string());
+        }
+        Ok(Self(value))
+    }
+}
+
+impl TryFrom<String> for SuiAddress {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, String> {
+        let hex = value.strip_prefix("0x").unwrap_or(&value);
+        if hex.is_empty() || hex.len() > 64 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(format!("{:?} is not a Sui address", value));
+        }
+        Ok(Self(normalize_address(hex)))
+    }
+}
+
+impl From<VaultId> for String {
+    fn from(id: VaultId) -> Self {
+        id.0
+    }
+}
+
+impl From<SuiAddress> for String {
+    fn from(address: SuiAddress) -> Self {
+        address.0
+    }
+}
+
+impl Deref for VaultId {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Deref for SuiAddress {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for VaultId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl fmt::Display for SuiAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl ClaimType {
+    /// The name circuits are registered under
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Keyword => "keyword",
+            Self::Timestamp => "timestamp",
+            Self::FileHash => "file_hash",
+            Self::IdentityAttribute => "identity_attribute",
+            Self::Solvency => "solvency",
+            Self::DkimEmail => "dkim_email",
+            Self::Predicate => "predicate",
+        }
+    }
+}
+
+impl BiometricMethod {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Fingerprint => "fingerprint",
+            Self::Face => "face",
+            Self::Voice => "voice",
+        }
+    }
+}
+
+impl fmt::Display for ClaimType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Display for BiometricMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn vault_ids_are_checked() {
+        assert!(serde_json::from_value::<VaultId>(json!("vault-1_a")).is_ok());
+        for bad in [json!(""), json!("a/b"), json!("x".repeat(129)), json!(7)] {
+            assert!(serde_json::from_value::<VaultId>(bad).is_err());
+        }
+    }
+
+    #[test]
+    fn sui_addresses_are_normalized() {
+        let address: SuiAddress = serde_json::from_value(json!("0xAB")).unwrap();
+        assert_eq!(&*address, format!("0x{:0>64}", "ab"));
+        for bad in [
+            json!("0x"),
+            json!("0xzz"),
+            json!(format!("0x{}", "1".repeat(65))),
+        ] {
+            assert!(serde_json::from_value::<SuiAddress>(bad).is_err());
+        }
+    }
+
+    #[test]
+    fn enums_round_trip_their_wire_names() {
+        for claim in ["keyword", "file_hash", "dkim_email"] {
+            let parsed: ClaimType = serde_json::from_value(json!(claim)).unwrap();
+            assert_eq!(parsed.as_str(), claim);
+        }
+        assert!(serde_json::from_value::<ClaimType>(json!("FileHash")).is_err());
+        let method: BiometricMethod = serde_json::from_value(json!("voice")).unwrap();
+        assert_eq!(method, BiometricMethod::Voice);
+    }
+}
@@ -0,0 +1,254 @@
+/**
+ * Vault Index
+ * Encrypted, searchable labels and tags over an owner's vaults
+ *
+ * Each owner indexes their vaults with a label and tags. Entries are
+ * stored under a key derived for the owner and unwrapped under the key
+ * release policy (see keys::release): the label
+ * and tags are encrypted, and each tag is also stored as a search token,
+ *
+ *   token = hex(HMAC-SHA256(owner key, "tag" || 0x00 || tag))
+ *
+ * so a tag query compares tokens without decrypting every entry, and the
+ * store never holds a tag in the clear. Only entries whose tokens match
+ * are decrypted for the response. Owners are told apart by a token of
+ * their own, so one owner's tokens say nothing about another's.
+ *
+ * Tags are trimmed and lowercased before tokenizing. Entries are keyed
+ * per vault and owner, so co-owners keep separate entries for one vault.
+ */
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ring::hmac;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::crypto;
+use crate::keys::derive::{KeyHierarchy, SearchIndex, VaultKey};
+use crate::keys::release::KeyRelease;
+use crate::store::Store;
+
+pub const NAMESPACE: &str = "vault_index";
+const MAX_TAGS: usize = 32;
+const MAX_TAG_LEN: usize = 64;
+const MAX_LABEL_LEN: usize = 128;
+
+/// Persisted per vault and owner
+#[derive(Clone, Serialize, Deserialize)]
+pub struct IndexEntry {
+    pub vault_id: String,
+    pub owner_token: String,     // Hex
+    pub tag_tokens: Vec<String>, // Hex, sorted
+    pub nonce: String,           // Base64 12-byte nonce
+    pub ciphertext: String,      // Base64 IndexedMetadata + tag
+    pub updated_at: u64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct IndexedMetadata {
+    pub label: String,
+    pub tags: Vec<String>,
+}
+
+/// A decrypted entry, for its owner
+#[derive(Clone, Serialize)]
+pub struct IndexedVault {
+    pub vault_id: String,
+    pub label: String,
+    pub tags: Vec<String>,
+    pub updated_at: u64,
+}
+
+pub struct VaultIndexService {
+    store: Arc<Store>,
+    keys: Arc<KeyHierarchy>,
+    release: Arc<KeyRelease>,
+}
+
+impl VaultIndexService {
+    pub fn new(store: Arc<Store>, keys: Arc<KeyHierarchy>, release: Arc<KeyRelease>) -> Self {
+        Self {
+            store,
+            keys,
+            release,
+        }
+    }
+
+    /// Replace `owner`'s entry for the vault; the caller must already be
+    /// one of its owners
+    pub fn index(
+        &self,
+        vault_id: &str,
+        owner: &str,
+        label: &str,
+        tags: &[String],
+    ) -> Result<IndexedVault, String> {
+        let label = label.trim();
+        if label.is_empty() || label.chars().count() > MAX_LABEL_LEN {
+            return Err(format!("Labels are 1 to {} characters", MAX_LABEL_LEN));
+        }
+        if tags.len() > MAX_TAGS {
+            return Err(format!("A vault has at most {} tags", MAX_TAGS));
+        }
+        let mut tags = tags
+            .iter()
+            .map(|tag| normalize_tag(tag))
+            .collect::<Result<Vec<_>, _>>()?;
+        tags.sort();
+        tags.dedup();
+
+        let key = self.key(owner, "vault_index.index")?;
+        let mut tag_tokens: Vec<String> = tags.iter().map(|tag| tag_token(&key, tag)).collect();
+        tag_tokens.sort();
+        let metadata = IndexedMetadata {
+            label: label.to_string(),
+            tags,
+        };
+        let plaintext = serde_json::to_vec(&metadata)
+            .map_err(|e| format!("Failed to serialize index entry: {}", e))?;
+        let (nonce, ciphertext) =
+            crypto::aead_encrypt(key.as_bytes(), &plaintext, entry_aad(vault_id).as_bytes())?;
+
+        let entry = IndexEntry {
+            vault_id: vault_id.to_string(),
+            owner_token: owner_token(&key),
+            tag_tokens,
+            nonce: STANDARD.encode(nonce),
+            ciphertext: STANDARD.encode(ciphertext),
+            updated_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+        };
+        self.store
+            .put(NAMESPACE, &entry_key(vault_id, &entry.owner_token), &entry)?;
+        Ok(IndexedVault {
+            vault_id: entry.vault_id,
+            label: metadata.label,
+            tags: metadata.tags,
+            updated_at: entry.updated_at,
+        })
+    }
+
+    /// `owner`'s entries carrying `tag`, or all of them without one
+    pub fn search(&self, owner: &str, tag: Option<&str>) -> Result<Vec<IndexedVault>, String> {
+        let key = self.key(owner, "vault_index.search")?;
+        let owner_token = owner_token(&key);
+        let token = tag
+            .map(normalize_tag)
+            .transpose()?
+            .map(|tag| tag_token(&key, &tag));
+
+        let mut vaults = Vec::new();
+        for (_, entry) in self.store.list::<IndexEntry>(NAMESPACE)? {
+            if entry.owner_token != owner_token
+                || token
+                    .as_ref()
+                    .is_some_and(|t| entry.tag_tokens.binary_search(t).is_err())
+            {
+                continue;
+            }
+            let metadata = decrypt(&key, &entry)?;
+            vaults.push(IndexedVault {
+                vault_id: entry.vault_id,
+                label: metadata.label,
+                tags: metadata.tags,
+                updated_at: entry.updated_at,
+            });
+        }
+        vaults.sort_by(|a, b| a.label.cmp(&b.label).then(a.vault_id.cmp(&b.vault_id)));
+        Ok(vaults)
+    }
+
+    /// The key is per owner, so refusals are recorded under the owner
+    fn key(&self, owner: &str, operation: &str) -> Result<VaultKey<SearchIndex>, String> {
+        let grant = self.release.grant(owner, operation)?;
+        Ok(self.keys.unwrap::<SearchIndex>(&grant, owner))
+    }
+}
+
+fn normalize_tag(tag: &str) -> Result<String, String> {
+    let tag = tag.trim().to_lowercase();
+    if tag.is_empty() || tag.chars().count() > MAX_TAG_LEN {
+        return Err(format!("Tags are 1 to {} characters", MAX_TAG_LEN));
+    }
+    Ok(tag)
+}
+
+fn tag_token(key: &VaultKey<SearchIndex>, tag: &str) -> String {
+    token(key, &[b"tag", &[0], tag.as_bytes()].concat())
+}
+
+fn owner_token(key: &VaultKey<SearchIndex>) -> String {
+    token(key, b"owner")
+}
+
+fn token(key: &VaultKey<SearchIndex>, message: &[u8]) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, key.as_bytes());
+    hex::encode(hmac::sign(&key, message))
+}
+
+fn decrypt(key: &VaultKey<SearchIndex>, entry: &IndexEntry) -> Result<IndexedMetadata, String> {
+    let nonce = crypto::decode(&entry.nonce)?;
+    let ciphertext = crypto::decode(&entry.ciphertext)?;
+    let plaintext = crypto::aead_decrypt(
+        key.as_bytes(),
+        &nonce,
+        &ciphertext,
+        entry_aad(&entry.vault_id).as_bytes(),
+    )?;
+    serde_json::from_slice(&plaintext).map_err(|e| format!("Corrupt index entry: {}", e))
+}
+
+fn entry_key(vault_id: &str, owner_token: &str) -> String {
+    format!("{}:{}", vault_id, owner_token)
+}
+
+fn entry_aad(vault_id: &str) -> String {
+    format!("lumina-vault-index:{}", vault_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::AuditTrail;
+    use crate::keys::release::KeyReleasePolicy;
+    use zeroize::Zeroizing;
+
+    fn service() -> VaultIndexService {
+        let dir = std::env::temp_dir().join(format!("lumina-vault-index-{}", std::process::id()));
+        let store = Arc::new(Store::open(dir).unwrap());
+        let keys = Arc::new(KeyHierarchy::new(Zeroizing::new([3; 32])));
+        let policy = KeyReleasePolicy {
+            allow_debug: true,
+            pcr8_signers: Vec::new(),
+        };
+        let audit = Arc::new(AuditTrail::new(store.clone(), keys.clone()));
+        let release = Arc::new(KeyRelease::new(policy, None, audit));
+        VaultIndexService::new(store, keys, release)
+    }
+
+    #[test]
+    fn owners_find_only_their_own_tagged_vaults() {
+        let index = service();
+        let tags = |t: &[&str]| t.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        index
+            .index("vault-1", "0xa", "Will", &tags(&["Family", "legal"]))
+            .unwrap();
+        index
+            .index("vault-2", "0xa", "Keys", &tags(&["crypto"]))
+            .unwrap();
+        index
+            .index("vault-3", "0xb", "Other", &tags(&["family"]))
+            .unwrap();
+
+        let found = index.search("0xa", Some(" FAMILY ")).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].vault_id, "vault-1");
+        assert_eq!(found[0].tags, tags(&["family", "legal"]));
+        assert_eq!(index.search("0xa", None).unwrap().len(), 2);
+        assert!(index.search("0xb", Some("crypto")).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,119 @@
+/**
+ * Vault State
+ * Finding and erasing the records the enclave keeps for one vault
+ *
+ * Services persist per-vault records under their own namespaces. Most
+ * records carry a vault_id field; the rest are keyed by the vault id,
+ * alone or as a "<vault_id>:" prefix. Templates, capability tokens and
+ * share links are sealed or signed by this enclave and are handled by
+ * their own services.
+ */
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::store::Store;
+
+/// Namespaces holding per-vault state that moves with the vault
+pub const VAULT_NAMESPACES: &[&str] = &[
+    "approval_guardians",
+    "approvals",
+    "audit_trail",
+    "check_in_schedules",
+    "duress_locks",
+    "duress_settings",
+    "escalation_ladders",
+    "guardian_shares",
+    "heartbeat_devices",
+    "heartbeat_objects",
+    "item_policies",
+    "jurisdiction_contests",
+    "jurisdiction_evidence",
+    "legal_holds",
+    "notification_contacts",
+    "owner_approvals",
+    "redaction_policies",
+    "template_refresh_notices",
+    "template_refresh_policies",
+    "threshold_keys",
+    "vault_beneficiaries",
+    "vault_co_owners",
+    "vault_commitments",
+    "vault_registrations",
+    "vault_releases",
+    "voice_fingerprints",
+];
+
+#[derive(Serialize, Deserialize)]
+pub struct VaultRecord {
+    pub namespace: String,
+    pub key: String,
+    pub value: Value,
+}
+
+/// Keys erased from one namespace
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ErasedRecords {
+    pub namespace: String,
+    pub keys: Vec<String>,
+}
+
+/// The vault's records in `namespaces`
+pub fn records(
+    store: &Store,
+    vault_id: &str,
+    namespaces: &[&str],
+) -> Result<Vec<VaultRecord>, String> {
+    let mut records = Vec::new();
+    for namespace in namespaces {
+        for (key, value) in store.list::<Value>(namespace)? {
+            if belongs_to(vault_id, &key, &value) {
+                records.push(VaultRecord {
+                    namespace: namespace.to_string(),
+                    key,
+                    value,
+                });
+            }
+        }
+    }
+    Ok(records)
+}
+
+/// Erase the vault's records in `namespaces` (see Store::erase); only
+/// namespaces that held any are reported
+pub fn erase(
+    store: &Store,
+    vault_id: &str,
+    namespaces: &[&str],
+) -> Result<Vec<ErasedRecords>, String> {
+    let mut erased = Vec::new();
+    for namespace in namespaces {
+        let keys: Vec<String> = store
+            .list::<Value>(namespace)?
+            .into_iter()
+            .filter(|(key, value)| belongs_to(vault_id, key, value))
+            .map(|(key, _)| key)
+            .collect();
+        if keys.is_empty() {
+            continue;
+        }
+        store.erase(namespace, &keys)?;
+        erased.push(ErasedRecords {
+            namespace: namespace.to_string(),
+            keys,
+        });
+    }
+    Ok(erased)
+}
+
+fn belongs_to(vault_id: &str, key: &str, value: &Value) -> bool {
+    match value.get("vault_id").and_then(Value::as_str) {
+        Some(owner) => owner == vault_id,
+        None => {
+            key == vault_id
+                || key
+                    .strip_prefix(vault_id)
+                    .is_some_and(|rest| rest.starts_with(':'))
+        }
+    }
+}
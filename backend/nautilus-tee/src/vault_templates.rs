@@ -0,0 +1,386 @@
+/**
+ * Vault Templates
+ * Predefined vault types whose manifests are checked against a JSON Schema
+ *
+ * Registering a vault from a template records its type and a manifest
+ * describing what it holds (not the content itself). The manifest must
+ * validate against the template's schema, so claims and release rules can
+ * rely on its shape:
+ *
+ *   digital_will            testator, executors and bequests
+ *   credential_store        accounts and how to reach them
+ *   crypto_key_inheritance  wallets and the heirs they go to
+ *
+ * Predicate claims (see claim_definitions) over a registered vault may
+ * only read the manifest fields its template opens to claims, and the
+ * vault is released only the way the template allows: behind an
+ * escalation ladder, or item by item. A vault may also be registered
+ * under a jurisdiction's rule pack (see jurisdictions).
+ */
+
+use jsonschema::JSONSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::exchange::canonical_sha256;
+use crate::jurisdictions::RulePack;
+use crate::store::Store;
+
+const NAMESPACE: &str = "vault_registrations";
+/// Validation errors reported per rejected manifest
+const MAX_ERRORS: usize = 20;
+
+/// How a template's vaults may be released
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct ReleaseRules {
+    /// Only once an escalation ladder has reached unlock
+    pub requires_ladder: bool,
+    /// Only item by item, never whole
+    pub requires_itemized: bool,
+}
+
+#[derive(Clone, Serialize)]
+pub struct VaultTemplate {
+    pub id: &'static str,
+    pub version: u32,
+    pub title: &'static str,
+    /// Top-level manifest fields predicate claims may read
+    pub claim_fields: &'static [&'static str],
+    pub release: ReleaseRules,
+    pub schema: Value,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct VaultRegistration {
+    pub vault_id: String,
+    pub owner: String,
+    pub template_id: String,
+    pub template_version: u32,
+    pub manifest: Value,
+    pub manifest_sha256: String, // Hex, of the canonical manifest
+    /// Copied from the configuration when the vault was registered
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule_pack: Option<RulePack>,
+    pub registered_at: u64,
+}
+
+pub struct VaultTemplateService {
+    store: Arc<Store>,
+    templates: Vec<(VaultTemplate, JSONSchema)>,
+}
+
+impl VaultTemplateService {
+    pub fn new(store: Arc<Store>) -> Result<Self, String> {
+        let templates = builtin()
+            .into_iter()
+            .map(|template| {
+                let schema = JSONSchema::compile(&template.schema)
+                    .map_err(|e| format!("Invalid schema for template {}: {}", template.id, e))?;
+                Ok((template, schema))
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Self { store, templates })
+    }
+
+    pub fn all(&self) -> Vec<&VaultTemplate> {
+        self.templates
+            .iter()
+            .map(|(template, _)| template)
+            .collect()
+    }
+
+    pub fn template(&self, id: &str) -> Option<&VaultTemplate> {
+        self.templates
+            .iter()
+            .map(|(template, _)| template)
+            .find(|template| template.id == id)
+    }
+
+    /// Validate the manifest and record the vault's template; the caller
+    /// must already be allowed to register the vault. Registering again
+    /// replaces the template and manifest but keeps the first owner.
+    pub fn register(
+        &self,
+        vault_id: &str,
+        owner: &str,
+        template_id: &str,
+        manifest: Value,
+        rule_pack: Option<RulePack>,
+    ) -> Result<VaultRegistration, Vec<String>> {
+        let (template, schema) = self
+            .templates
+            .iter()
+            .find(|(template, _)| template.id == template_id)
+            .ok_or_else(|| vec![format!("Unknown vault template {}", template_id)])?;
+        if let Err(errors) = schema.validate(&manifest) {
+            return Err(errors
+                .take(MAX_ERRORS)
+                .map(|e| {
+                    let path = e.instance_path.to_string();
+                    format!("{}: {}", if path.is_empty() { "/" } else { &path }, e)
+                })
+                .collect());
+        }
+
+        let owner = self
+            .registration(vault_id)
+            .map_err(|e| vec![e])?
+            .map_or_else(|| owner.to_string(), |r| r.owner);
+        let registration = VaultRegistration {
+            vault_id: vault_id.to_string(),
+            owner,
+            template_id: template.id.to_string(),
+            template_version: template.version,
+            manifest_sha256: hex::encode(canonical_sha256(&manifest).map_err(|e| vec![e])?),
+            manifest,
+            rule_pack,
+            registered_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+        };
+        self.store
+            .put(NAMESPACE, vault_id, &registration)
+            .map_err(|e| vec![e])?;
+        Ok(registration)
+    }
+
+    pub fn registration(&self, vault_id: &str) -> Result<Option<VaultRegistration>, String> {
+        self.store.get(NAMESPACE, vault_id)
+    }
+
+    /// The template of a registered vault
+    pub fn template_of(&self, vault_id: &str) -> Result<Option<&VaultTemplate>, String> {
+        Ok(self
+            .registration(vault_id)?
+            .and_then(|r| self.template(&r.template_id)))
+    }
+}
+
+impl VaultTemplate {
+    /// Fields, as dotted paths, the template doesn't open to claims
+    pub fn closed_fields<'a>(&self, fields: impl IntoIterator<Item = &'a str>) -> Vec<&'a str> {
+        fields
+            .into_iter()
+            .filter(|field| {
+                let top = field.split('.').next().unwrap_or_default();
+                !self.claim_fields.contains(&top)
+            })
+            .collect()
+    }
+}
+
+fn builtin() -> Vec<VaultTemplate> {
+    let person = json!({
+        "type": "object",
+        "required": ["name"],
+        "properties": {
+            "name": { "type": "string", "minLength": 1 },
+            "address": { "type": "string", "pattern": "^0x[0-9a-fA-F]{1,64}$" },
+            "contact": { "type": "string" }
+        }
+    });
+
+    vec![
+        VaultTemplate {
+            id: "digital_will",
+            version: 1,
+            title: "Digital will",
+            claim_fields: &["testator", "jurisdiction", "witnessed", "signed_on"],
+            release: ReleaseRules {
+                requires_ladder: true,
+                requires_itemized: false,
+            },
+            schema: json!({
+                "$schema": "http://json-schema.org/draft-07/schema#",
+                "type": "object",
+                "required": ["testator", "executors", "bequests"],
+                "properties": {
+                    "testator": {
+                        "type": "object",
+                        "required": ["name"],
+                        "properties": {
+                            "name": { "type": "string", "minLength": 1 },
+                            "birth_year": { "type": "integer", "minimum": 1900 }
+                        }
+                    },
+                    "jurisdiction": { "type": "string", "minLength": 2 },
+                    "witnessed": { "type": "boolean" },
+                    "signed_on": { "type": "integer", "description": "Unix seconds" },
+                    "executors": { "type": "array", "minItems": 1, "items": person },
+                    "bequests": {
+                        "type": "array",
+                        "minItems": 1,
+                        "items": {
+                            "type": "object",
+                            "required": ["beneficiary", "description"],
+                            "properties": {
+                                "beneficiary": person,
+                                "description": { "type": "string", "minLength": 1 },
+                                "share_percent": { "type": "number", "minimum": 0, "maximum": 100 }
+                            }
+                        }
+                    }
+                }
+            }),
+        },
+        VaultTemplate {
+            id: "credential_store",
+            version: 1,
+            title: "Credential store",
+            claim_fields: &["owner"],
+            release: ReleaseRules {
+                requires_ladder: false,
+                requires_itemized: true,
+            },
+            schema: json!({
+                "$schema": "http://json-schema.org/draft-07/schema#",
+                "type": "object",
+                "required": ["owner", "credentials"],
+                "properties": {
+                    "owner": person,
+                    "credentials": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "required": ["service", "kind"],
+                            "properties": {
+                                "service": { "type": "string", "minLength": 1 },
+                                "username": { "type": "string" },
+                                "kind": {
+                                    "enum": [
+                                        "password",
+                                        "totp",
+                                        "recovery_codes",
+                                        "api_key",
+                                        "other"
+                                    ]
+                                },
+                                "item_id": {
+                                    "type": "string",
+                                    "description": "Vault item holding the secret"
+                                }
+                            }
+                        }
+                    }
+                }
+            }),
+        },
+        VaultTemplate {
+            id: "crypto_key_inheritance",
+            version: 1,
+            title: "Crypto key inheritance",
+            claim_fields: &["wallets", "heirs"],
+            release: ReleaseRules {
+                requires_ladder: true,
+                requires_itemized: false,
+            },
+            schema: json!({
+                "$schema": "http://json-schema.org/draft-07/schema#",
+                "type": "object",
+                "required": ["wallets", "heirs"],
+                "properties": {
+                    "wallets": {
+                        "type": "array",
+                        "minItems": 1,
+                        "items": {
+                            "type": "object",
+                            "required": ["network", "key_kind"],
+                            "properties": {
+                                "network": {
+                                    "enum": ["sui", "ethereum", "bitcoin", "solana", "other"]
+                                },
+                                "address": { "type": "string" },
+                                "key_kind": {
+                                    "enum": [
+                                        "seed_phrase",
+                                        "private_key",
+                                        "hardware_wallet",
+                                        "multisig_share"
+                                    ]
+                                },
+                                "derivation_path": {
+                                    "type": "string",
+                                    "pattern": "^m(/[0-9]+'?)*$"
+                                }
+                            }
+                        }
+                    },
+                    "heirs": {
+                        "type": "array",
+                        "minItems": 1,
+                        "items": {
+                            "type": "object",
+                            "required": ["name", "share_percent"],
+                            "properties": {
+                                "name": { "type": "string", "minLength": 1 },
+                                "address": { "type": "string", "pattern": "^0x[0-9a-fA-F]{1,64}$" },
+                                "share_percent": {
+                                    "type": "number",
+                                    "exclusiveMinimum": 0,
+                                    "maximum": 100
+                                }
+                            }
+                        }
+                    },
+                    "instructions": { "type": "string" }
+                }
+            }),
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn templates(name: &str) -> VaultTemplateService {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let dir =
+            std::env::temp_dir().join(format!("lumina-vault-template-tests-{}-{}", name, now));
+        VaultTemplateService::new(Arc::new(Store::open(dir).unwrap())).unwrap()
+    }
+
+    #[test]
+    fn manifests_are_checked_against_their_schema() {
+        let templates = templates("manifests");
+        let will = json!({
+            "testator": { "name": "Ada" },
+            "executors": [{ "name": "Bea" }],
+            "bequests": [{ "beneficiary": { "name": "Cy" }, "description": "Everything" }],
+        });
+        let registration = templates
+            .register("vault-1", "0x1", "digital_will", will, None)
+            .unwrap();
+        assert_eq!(registration.template_version, 1);
+
+        let errors = templates
+            .register(
+                "vault-2",
+                "0x1",
+                "digital_will",
+                json!({ "testator": {}, "executors": [] }),
+                None,
+            )
+            .unwrap_err();
+        assert!(errors.len() >= 3);
+        assert!(templates
+            .register("vault-3", "0x1", "treasure_map", json!({}), None)
+            .is_err());
+    }
+
+    #[test]
+    fn claims_read_only_open_fields() {
+        let templates = templates("claims");
+        let will = templates.template("digital_will").unwrap();
+        assert_eq!(
+            will.closed_fields(["testator.birth_year", "bequests.0.share_percent"]),
+            vec!["bequests.0.share_percent"]
+        );
+    }
+}
@@ -0,0 +1,603 @@
+/**
+ * Voice Anti-Replay
+ * Challenge phrases and spoof detection that gate voice verification
+ *
+ * A recording of the owner must not unlock anything, so a voice sample
+ * only verifies when it also passes three checks:
+ *
+ * - Prompted phrase: the enclave issues a single-use random digit phrase
+ *   bound to the vault, and the utterance must fit it. Until speech
+ *   recognition runs in the enclave this is judged by counting voiced
+ *   segments, one per digit read with a short pause between.
+ * - Replay: every sample's spectral fingerprint is kept per vault, and a
+ *   sample that matches an earlier one (same recording, possibly
+ *   re-encoded) is rejected outright.
+ * - Playback artifacts: loudspeakers lose energy below ~250 Hz and
+ *   re-recorded audio tends to be band-limited above 4 kHz; clipping is a
+ *   sign of a speaker driven into a microphone.
+ *
+ * Analysis needs PCM, so voice verification accepts WAV only.
+ */
+
+use axum::http::StatusCode;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::f32::consts::PI;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::error::AppError;
+use crate::formats::{self, Sample};
+use crate::randomness::RandomnessSource;
+use crate::store::Store;
+
+const NAMESPACE: &str = "voice_fingerprints";
+const CHALLENGE_TTL: Duration = Duration::from_secs(120);
+const PHRASE_DIGITS: usize = 6;
+const DIGIT_WORDS: [&str; 10] = [
+    "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
+];
+
+const FFT_SIZE: usize = 512;
+const HOP: usize = FFT_SIZE / 2;
+/// Pause between words, in analysis frames (~80 ms at 16 kHz)
+const MIN_PAUSE_FRAMES: usize = 5;
+
+/// Fingerprints: 32 band-energy sign bits per frame, as in Haitsma-Kalker
+const FINGERPRINT_BANDS: usize = 33;
+const MAX_FINGERPRINT_FRAMES: usize = 512;
+const FINGERPRINTS_PER_VAULT: usize = 32;
+/// Frames of misalignment tolerated when comparing fingerprints
+const MAX_ALIGN_SHIFT: isize = 8;
+/// Unrelated utterances differ in ~50% of bits; the same recording in <10%
+const REPLAY_MAX_BIT_ERROR: f64 = 0.2;
+
+// Initial guesses, to be replaced by calibrated values
+const MIN_LOW_BAND_RATIO: f64 = 0.01;
+const MIN_HIGH_BAND_RATIO: f64 = 0.002;
+const MAX_CLIPPING_RATIO: f64 = 0.01;
+/// Minimum anti-spoof score for a voice sample to verify
+pub const ANTI_SPOOF_THRESHOLD: f64 = 0.6;
+
+#[derive(Serialize)]
+pub struct VoiceChallenge {
+    pub challenge_id: String,
+    pub phrase: String,
+    pub instructions: &'static str,
+    pub expires_in: u64,
+}
+
+#[derive(Clone, Serialize)]
+pub struct AntiSpoofReport {
+    pub passed: bool,
+    pub score: f64,
+    pub phrase_matched: bool,
+    pub replayed: bool,
+    pub metrics: BTreeMap<&'static str, f64>,
+    pub issues: Vec<String>,
+}
+
+struct PendingChallenge {
+    vault_id: String,
+    words: usize,
+    expires_at: Instant,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct VaultFingerprints {
+    /// Base64 little-endian u32 frames, newest last
+    recent: Vec<String>,
+}
+
+pub struct VoiceGuard {
+    store: Arc<Store>,
+    randomness: Arc<RandomnessSource>,
+    challenges: Mutex<HashMap<String, PendingChallenge>>,
+}
+
+impl VoiceGuard {
+    pub fn new(store: Arc<Store>, randomness: Arc<RandomnessSource>) -> Self {
+        Self {
+            store,
+            randomness,
+            challenges: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Drop the vault's outstanding challenges; returns how many there were
+    pub fn forget(&self, vault_id: &str) -> usize {
+        let mut challenges = self.challenges.lock().unwrap();
+        let before = challenges.len();
+        challenges.retain(|_, c| c.vault_id != vault_id);
+        before - challenges.len()
+    }
+
+    pub fn issue(&self, vault_id: &str) -> VoiceChallenge {
+        let words: Vec<&str> = (0..PHRASE_DIGITS)
+            .map(|_| DIGIT_WORDS[self.randomness.below(DIGIT_WORDS.len() as u32) as usize])
+            .collect();
+        let challenge_id = self.randomness.id(16);
+
+        let mut challenges = self.challenges.lock().unwrap();
+        let now = Instant::now();
+        challenges.retain(|_, c| c.expires_at > now);
+        challenges.insert(
+            challenge_id.clone(),
+            PendingChallenge {
+                vault_id: vault_id.to_string(),
+                words: words.len(),
+                expires_at: now + CHALLENGE_TTL,
+            },
+        );
+
+        VoiceChallenge {
+            challenge_id,
+            phrase: words.join(" "),
+            instructions: "Read the digits aloud with a short pause between each",
+            expires_in: CHALLENGE_TTL.as_secs(),
+        }
+    }
+
+    /// Consume the challenge and score the sample; the sample's fingerprint
+    /// is remembered whether or not it passes
+    pub fn check(
+        &self,
+        vault_id: &str,
+        challenge_id: Option<&str>,
+        data: &[u8],
+    ) -> Result<AntiSpoofReport, AppError> {
+        let challenge_id = challenge_id.ok_or_else(|| {
+            AppError::bad_request(
+                "VOICE_CHALLENGE_REQUIRED",
+                "Voice verification needs a challenge_id from /biometric/voice/challenge",
+            )
+        })?;
+        let challenge = self
+            .challenges
+            .lock()
+            .unwrap()
+            .remove(challenge_id)
+            .filter(|c| c.vault_id == vault_id && c.expires_at > Instant::now())
+            .ok_or_else(|| {
+                AppError::unauthorized(
+                    "VOICE_CHALLENGE_INVALID",
+                    "Voice challenge is unknown, expired or for another vault",
+                )
+            })?;
+
+        let (sample_rate, pcm) = decode_pcm(data)?;
+        let analysis = Analysis::run(&pcm, sample_rate);
+        let replayed = self
+            .remember_fingerprint(vault_id, &analysis.fingerprint)
+            .map_err(AppError::internal)?;
+
+        Ok(score(&analysis, sample_rate, challenge.words, replayed))
+    }
+
+    /// Record an enrollment sample, so replaying it later is caught
+    pub fn remember(&self, vault_id: &str, data: &[u8]) -> Result<(), String> {
+        let Ok((sample_rate, pcm)) = decode_pcm(data) else {
+            // Enrollment may use Opus; there's nothing to fingerprint
+            return Ok(());
+        };
+        let analysis = Analysis::run(&pcm, sample_rate);
+        self.remember_fingerprint(vault_id, &analysis.fingerprint)
+            .map(|_| ())
+    }
+
+    /// Store `fingerprint` for the vault and report whether it matches one
+    /// seen before
+    fn remember_fingerprint(&self, vault_id: &str, fingerprint: &[u32]) -> Result<bool, String> {
+        let mut known: VaultFingerprints = self.store.get(NAMESPACE, vault_id)?.unwrap_or_default();
+        let replayed = known
+            .recent
+            .iter()
+            .filter_map(|encoded| decode_fingerprint(encoded))
+            .any(|previous| min_bit_error(&previous, fingerprint) <= REPLAY_MAX_BIT_ERROR);
+
+        known.recent.push(encode_fingerprint(fingerprint));
+        if known.recent.len() > FINGERPRINTS_PER_VAULT {
+            let excess = known.recent.len() - FINGERPRINTS_PER_VAULT;
+            known.recent.drain(..excess);
+        }
+        self.store.put(NAMESPACE, vault_id, &known)?;
+        Ok(replayed)
+    }
+}
+
+fn decode_pcm(data: &[u8]) -> Result<(u32, Vec<i16>), AppError> {
+    let sample =
+        formats::decode(data, "voice").map_err(|e| AppError::bad_request(e.code(), e.message()))?;
+    match sample {
+        Sample::Audio {
+            sample_rate,
+            pcm: Some(pcm),
+            ..
+        } => Ok((sample_rate, pcm)),
+        _ => Err(AppError::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "ANTI_SPOOF_UNAVAILABLE",
+            "Voice verification needs WAV (PCM) audio for replay detection",
+        )),
+    }
+}
+
+struct Analysis {
+    low_band_ratio: f64,
+    high_band_ratio: Option<f64>,
+    clipping_ratio: f64,
+    voiced_segments: usize,
+    fingerprint: Vec<u32>,
+}
+
+impl Analysis {
+    fn run(pcm: &[i16], sample_rate: u32) -> Self {
+        let window: Vec<f32> = (0..FFT_SIZE)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / FFT_SIZE as f32).cos())
+            .collect();
+        let bin_hz = sample_rate as f32 / FFT_SIZE as f32;
+        let bin = |hz: f32| ((hz / bin_hz) as usize).min(FFT_SIZE / 2);
+        let band_edges: Vec<usize> = (0..=FINGERPRINT_BANDS)
+            .map(|b| {
+                let top = 3000f32.min(sample_rate as f32 / 2.0);
+                bin(300.0 * (top / 300.0).powf(b as f32 / FINGERPRINT_BANDS as f32))
+            })
+            .collect();
+
+        let mut frame_energy = Vec::new();
+        let mut spectra = Vec::new();
+        let mut re = vec![0f32; FFT_SIZE];
+        let mut im = vec![0f32; FFT_SIZE];
+        for start in (0..pcm.len().saturating_sub(FFT_SIZE)).step_by(HOP) {
+            let frame = &pcm[start..start + FFT_SIZE];
+            for (i, &s) in frame.iter().enumerate() {
+                re[i] = f32::from(s) / 32768.0 * window[i];
+                im[i] = 0.0;
+            }
+            frame_energy.push(re.iter().map(|v| f64::from(v * v)).sum::<f64>());
+            fft(&mut re, &mut im);
+            spectra.push(
+                (0..=FFT_SIZE / 2)
+                    .map(|k| re[k] * re[k] + im[k] * im[k])
+                    .collect::<Vec<f32>>(),
+            );
+        }
+
+        // Speech frames: well above the quietest tenth of the recording
+        let mut sorted = frame_energy.clone();
+        sorted.sort_by(f64::total_cmp);
+        let floor = sorted.get(sorted.len() / 10).copied().unwrap_or(0.0);
+        let voiced: Vec<bool> = frame_energy
+            .iter()
+            .map(|&e| e > (floor * 10.0).max(1e-6))
+            .collect();
+
+        let band = |spectrum: &[f32], from: usize, to: usize| -> f64 {
+            spectrum[from.min(to)..to]
+                .iter()
+                .map(|&p| f64::from(p))
+                .sum()
+        };
+        let (mut low, mut high, mut total) = (0.0, 0.0, 0.0);
+        for (spectrum, _) in spectra.iter().zip(&voiced).filter(|(_, v)| **v) {
+            low += band(spectrum, bin(20.0), bin(250.0));
+            high += band(spectrum, bin(4000.0), FFT_SIZE / 2 + 1);
+            total += band(spectrum, bin(20.0), FFT_SIZE / 2 + 1);
+        }
+        let total = total.max(f64::MIN_POSITIVE);
+
+        let fingerprint = spectra
+            .iter()
+            .take(MAX_FINGERPRINT_FRAMES + 1)
+            .map(|spectrum| {
+                band_edges
+                    .windows(2)
+                    .map(|edge| band(spectrum, edge[0], edge[1].max(edge[0] + 1)))
+                    .collect::<Vec<f64>>()
+            })
+            .collect::<Vec<_>>()
+            .windows(2)
+            .map(|pair| {
+                (0..FINGERPRINT_BANDS - 1).fold(0u32, |bits, b| {
+                    let now = pair[1][b] - pair[1][b + 1];
+                    let before = pair[0][b] - pair[0][b + 1];
+                    bits | (u32::from(now - before > 0.0) << b)
+                })
+            })
+            .collect();
+
+        Self {
+            low_band_ratio: low / total,
+            high_band_ratio: (sample_rate >= 16_000).then_some(high / total),
+            clipping_ratio: pcm
+                .iter()
+                .filter(|&&s| s == i16::MAX || s <= -i16::MAX)
+                .count() as f64
+                / pcm.len().max(1) as f64,
+            voiced_segments: count_segments(&voiced),
+            fingerprint,
+        }
+    }
+}
+
+fn score(analysis: &Analysis, sample_rate: u32, words: usize, replayed: bool) -> AntiSpoofReport {
+    let mut report = AntiSpoofReport {
+        passed: false,
+        score: 1.0,
+        phrase_matched: analysis.voiced_segments.abs_diff(words) <= 1,
+        replayed,
+        metrics: BTreeMap::new(),
+        issues: Vec::new(),
+    };
+    report.metrics.insert("sample_rate", f64::from(sample_rate));
+    report
+        .metrics
+        .insert("low_band_ratio", analysis.low_band_ratio);
+    report
+        .metrics
+        .insert("clipping_ratio", analysis.clipping_ratio);
+    report
+        .metrics
+        .insert("voiced_segments", analysis.voiced_segments as f64);
+
+    if analysis.low_band_ratio < MIN_LOW_BAND_RATIO {
+        report.score -= 0.3;
+        report
+            .issues
+            .push("Little energy below 250 Hz, typical of loudspeaker playback".to_string());
+    }
+    if let Some(high) = analysis.high_band_ratio {
+        report.metrics.insert("high_band_ratio", high);
+        if high < MIN_HIGH_BAND_RATIO {
+            report.score -= 0.3;
+            report
+                .issues
+                .push("Band-limited above 4 kHz, typical of re-recorded audio".to_string());
+        }
+    }
+    if analysis.clipping_ratio > MAX_CLIPPING_RATIO {
+        report.score -= 0.2;
+        report.issues.push("Heavy clipping".to_string());
+    }
+    if !report.phrase_matched {
+        report.score -= 0.5;
+        report.issues.push(format!(
+            "Heard {} words, expected the {}-digit challenge phrase",
+            analysis.voiced_segments, words
+        ));
+    }
+    if replayed {
+        report.score = 0.0;
+        report
+            .issues
+            .push("Sample matches an earlier recording".to_string());
+    }
+
+    report.score = report.score.max(0.0);
+    report.passed = report.phrase_matched && !replayed && report.score >= ANTI_SPOOF_THRESHOLD;
+    report
+}
+
+/// Runs of voiced frames separated by at least MIN_PAUSE_FRAMES of silence
+fn count_segments(voiced: &[bool]) -> usize {
+    let mut segments = 0;
+    let mut silence = MIN_PAUSE_FRAMES;
+    for &v in voiced {
+        if v {
+            if silence >= MIN_PAUSE_FRAMES {
+                segments += 1;
+            }
+            silence = 0;
+        } else {
+            silence += 1;
+        }
+    }
+    segments
+}
+
+/// Lowest fraction of differing bits over small alignment shifts
+fn min_bit_error(a: &[u32], b: &[u32]) -> f64 {
+    (-MAX_ALIGN_SHIFT..=MAX_ALIGN_SHIFT)
+        .filter_map(|shift| {
+            let (a, b) = if shift >= 0 {
+                (a.get(shift as usize..)?, b)
+            } else {
+                (a, b.get((-shift) as usize..)?)
+            };
+            let n = a.len().min(b.len());
+            // Too little overlap to call anything a match
+            if n < 32 {
+                return None;
+            }
+            let differing: u32 = a[..n]
+                .iter()
+                .zip(&b[..n])
+                .map(|(x, y)| (x ^ y).count_ones())
+                .sum();
+            Some(f64::from(differing) / (n as f64 * 32.0))
+        })
+        .fold(1.0, f64::min)
+}
+
+fn encode_fingerprint(fingerprint: &[u32]) -> String {
+    STANDARD.encode(
+        fingerprint
+            .iter()
+            .flat_map(|f| f.to_le_bytes())
+            .collect::<Vec<u8>>(),
+    )
+}
+
+fn decode_fingerprint(encoded: &str) -> Option<Vec<u32>> {
+    let bytes = STANDARD.decode(encoded).ok()?;
+    Some(
+        bytes
+            .chunks_exact(4)
+            .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+            .collect(),
+    )
+}
+
+/// In-place iterative radix-2 FFT; `re.len()` must be a power of two
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let (a, b) = (start + k, start + k + len / 2);
+                let tr = re[b] * cos - im[b] * sin;
+                let ti = re[b] * sin + im[b] * cos;
+                re[b] = re[a] - tr;
+                im[b] = im[a] - ti;
+                re[a] += tr;
+                im[a] += ti;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    const RATE: u32 = 16_000;
+
+    fn guard(name: &str) -> VoiceGuard {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("lumina-voice-tests-{}-{}", name, nanos));
+        VoiceGuard::new(
+            Arc::new(Store::open(&dir).unwrap()),
+            Arc::new(RandomnessSource::new()),
+        )
+    }
+
+    /// A 16 kHz WAV of `words` voiced bursts with pauses between; the noise
+    /// in each depends on `seed`, so different seeds are different recordings
+    fn recording(words: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+        let mut noise = move |amplitude: f32| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ((state >> 33) as f32 / (1u64 << 31) as f32 - 0.5) * 2.0 * amplitude
+        };
+        let pause = RATE as usize / 5;
+        let word = RATE as usize * 3 / 10;
+        let mut pcm = Vec::new();
+        for _ in 0..words {
+            pcm.extend((0..pause).map(|_| noise(30.0) as i16));
+            pcm.extend((0..word).map(|i| {
+                let t = i as f32 / RATE as f32;
+                let voice =
+                    6000.0 * (2.0 * PI * 150.0 * t).sin() + 6000.0 * (2.0 * PI * 1000.0 * t).sin();
+                (voice + noise(4000.0)) as i16
+            }));
+        }
+        pcm.extend((0..pause).map(|_| noise(30.0) as i16));
+
+        let data: Vec<u8> = pcm.iter().flat_map(|s| s.to_le_bytes()).collect();
+        let mut wav = b"RIFF".to_vec();
+        wav.extend((36 + data.len() as u32).to_le_bytes());
+        wav.extend(b"WAVEfmt ");
+        wav.extend(16u32.to_le_bytes());
+        wav.extend(1u16.to_le_bytes());
+        wav.extend(1u16.to_le_bytes());
+        wav.extend(RATE.to_le_bytes());
+        wav.extend((RATE * 2).to_le_bytes());
+        wav.extend(2u16.to_le_bytes());
+        wav.extend(16u16.to_le_bytes());
+        wav.extend(b"data");
+        wav.extend((data.len() as u32).to_le_bytes());
+        wav.extend(data);
+        wav
+    }
+
+    #[test]
+    fn fresh_readings_of_the_phrase_pass() {
+        let voice = guard("fresh");
+
+        for seed in [1, 2] {
+            let challenge = voice.issue("vault");
+            assert_eq!(challenge.phrase.split(' ').count(), PHRASE_DIGITS);
+            let report = voice
+                .check("vault", Some(&challenge.challenge_id), &recording(6, seed))
+                .unwrap();
+            assert!(report.passed, "{:?}", report.issues);
+            assert!(report.phrase_matched && !report.replayed);
+        }
+    }
+
+    #[test]
+    fn replays_misused_challenges_and_wrong_phrases_are_refused() {
+        let voice = guard("refused");
+        let sample = recording(6, 1);
+        let code = |result: Result<AntiSpoofReport, AppError>| result.err().unwrap().code;
+
+        assert_eq!(
+            code(voice.check("vault", None, &sample)),
+            "VOICE_CHALLENGE_REQUIRED"
+        );
+        let elsewhere = voice.issue("other-vault").challenge_id;
+        assert_eq!(
+            code(voice.check("vault", Some(&elsewhere), &sample)),
+            "VOICE_CHALLENGE_INVALID"
+        );
+
+        let challenge = voice.issue("vault").challenge_id;
+        assert!(
+            voice
+                .check("vault", Some(&challenge), &sample)
+                .unwrap()
+                .passed
+        );
+        assert_eq!(
+            code(voice.check("vault", Some(&challenge), &sample)),
+            "VOICE_CHALLENGE_INVALID"
+        );
+
+        let challenge = voice.issue("vault").challenge_id;
+        let replayed = voice.check("vault", Some(&challenge), &sample).unwrap();
+        assert!(replayed.replayed && !replayed.passed);
+
+        // Enrollment samples are remembered, so replaying one is caught too
+        let enrolled = recording(6, 3);
+        voice.remember("vault", &enrolled).unwrap();
+        let challenge = voice.issue("vault").challenge_id;
+        assert!(
+            voice
+                .check("vault", Some(&challenge), &enrolled)
+                .unwrap()
+                .replayed
+        );
+
+        let challenge = voice.issue("vault").challenge_id;
+        let short = voice
+            .check("vault", Some(&challenge), &recording(2, 4))
+            .unwrap();
+        assert!(!short.phrase_matched && !short.passed);
+    }
+}
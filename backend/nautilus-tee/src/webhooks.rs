@@ -0,0 +1,494 @@
+/**
+ * Webhooks
+ * A vault's events delivered to its owners' endpoints, at least once and
+ * in order
+ *
+ * Owners register HTTPS endpoints per vault. A fan-out task reads the
+ * event log from a cursor kept in the store and queues a delivery of each
+ * event to each endpoint of its vault. The enclave can't reach the
+ * endpoints, so the host's webhook relay polls for due deliveries, POSTs
+ * each signed event (see events) and acknowledges it back.
+ *
+ * A delivery stays queued until it is acknowledged as delivered. Each
+ * endpoint is handed only its oldest unacknowledged delivery, so it
+ * receives a vault's events in order; the relay's lease on a delivery
+ * runs out after LEASE_SECS, so one the relay lost is handed out again.
+ * Deliveries the relay reports failed back off exponentially up to
+ * MAX_BACKOFF_SECS and are never dropped; removing the endpoint is what
+ * ends them. Receivers can see an event more than once and dedupe by its
+ * id.
+ *
+ * An owner whose receiver was down replays: every event of the vault
+ * after a given id that the log still holds is queued to the endpoint
+ * again, in order.
+ */
+
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::info;
+
+use crate::dead_letters::REDELIVERY_EVENT;
+use crate::events::{self, Event, EventLog, SignedEvent};
+use crate::store::{Batch, Store};
+use crate::tasks::{Schedule, TaskScheduler};
+
+pub const ENDPOINTS_NAMESPACE: &str = "webhook_endpoints";
+pub const DELIVERIES_NAMESPACE: &str = "webhook_deliveries";
+const CURSORS_NAMESPACE: &str = "webhook_cursors";
+const FANOUT_CURSOR: &str = "fanout";
+const FANOUT_EVERY: Duration = Duration::from_secs(15);
+const MAX_ENDPOINTS: usize = 5;
+const MAX_URL_LEN: usize = 2048;
+/// How long a delivery handed to the relay waits for its ack
+const LEASE_SECS: u64 = 5 * 60;
+const RETRY_BASE_SECS: u64 = 30;
+const MAX_BACKOFF_SECS: u64 = 60 * 60;
+/// Delivered deliveries are kept this long for owners to inspect
+const RETENTION_SECS: u64 = 7 * 24 * 60 * 60;
+pub const MAX_BATCH: usize = 100;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct WebhookEndpoint {
+    pub endpoint_id: String,
+    pub vault_id: String,
+    pub url: String,
+    pub created_by: String,
+    pub created_at: u64,
+}
+
+#[derive(Serialize)]
+pub struct EndpointStatus {
+    #[serde(flatten)]
+    pub endpoint: WebhookEndpoint,
+    /// Deliveries not yet acknowledged
+    pub pending: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    Pending,
+    Delivered,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Delivery {
+    pub delivery_id: String,
+    pub endpoint_id: String,
+    pub vault_id: String,
+    pub url: String,
+    /// Kept with the delivery, since the log drops its oldest events
+    pub event: Event,
+    pub status: DeliveryStatus,
+    pub attempts: u32,
+    pub next_attempt_at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delivered_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+/// Handed to the relay to POST `event` to `url`
+#[derive(Serialize)]
+pub struct DueDelivery {
+    pub delivery_id: String,
+    pub url: String,
+    /// 1 for the first attempt
+    pub attempt: u32,
+    pub event: SignedEvent,
+}
+
+#[derive(Deserialize)]
+pub struct DeliveryAck {
+    pub delivery_id: String,
+    pub delivered: bool,
+    /// Why it wasn't, e.g. the receiver's status code
+    pub detail: Option<String>,
+}
+
+pub struct WebhookService {
+    store: Arc<Store>,
+    events: Arc<EventLog>,
+    /// Serializes read-modify-write of deliveries and the fan-out cursor
+    writes: Mutex<()>,
+}
+
+impl WebhookService {
+    pub fn new(store: Arc<Store>, events: Arc<EventLog>) -> Self {
+        Self {
+            store,
+            events,
+            writes: Mutex::new(()),
+        }
+    }
+
+    /// Register an endpoint; the caller has been authorized
+    pub fn register(
+        &self,
+        vault_id: &str,
+        url: &str,
+        created_by: &str,
+    ) -> Result<WebhookEndpoint, String> {
+        let url = url.trim();
+        if !url.starts_with("https://") || url.len() > MAX_URL_LEN {
+            return Err(format!(
+                "Webhook URL must be HTTPS and at most {} characters",
+                MAX_URL_LEN
+            ));
+        }
+        let existing = self.endpoints_of(vault_id)?;
+        if existing.iter().any(|endpoint| endpoint.url == url) {
+            return Err("Webhook URL is already registered".to_string());
+        }
+        if existing.len() >= MAX_ENDPOINTS {
+            return Err(format!("At most {} webhooks are allowed", MAX_ENDPOINTS));
+        }
+        let mut id = [0u8; 16];
+        OsRng.fill_bytes(&mut id);
+        let endpoint = WebhookEndpoint {
+            endpoint_id: hex::encode(id),
+            vault_id: vault_id.to_string(),
+            url: url.to_string(),
+            created_by: created_by.to_string(),
+            created_at: now(),
+        };
+        self.store
+            .put(ENDPOINTS_NAMESPACE, &endpoint.endpoint_id, &endpoint)?;
+        Ok(endpoint)
+    }
+
+    /// The vault's endpoints with their backlog
+    pub fn endpoints(&self, vault_id: &str) -> Result<Vec<EndpointStatus>, String> {
+        let deliveries = self.store.list::<Delivery>(DELIVERIES_NAMESPACE)?;
+        Ok(self
+            .endpoints_of(vault_id)?
+            .into_iter()
+            .map(|endpoint| {
+                let pending: Vec<&Delivery> = deliveries
+                    .iter()
+                    .map(|(_, delivery)| delivery)
+                    .filter(|delivery| {
+                        delivery.endpoint_id == endpoint.endpoint_id
+                            && delivery.status == DeliveryStatus::Pending
+                    })
+                    .collect();
+                EndpointStatus {
+                    pending: pending.len(),
+                    last_error: pending.first().and_then(|d| d.last_error.clone()),
+                    endpoint,
+                }
+            })
+            .collect())
+    }
+
+    /// Remove the endpoint and everything queued for it; false if the
+    /// vault has no such endpoint
+    pub fn remove(&self, vault_id: &str, endpoint_id: &str) -> Result<bool, String> {
+        let _writes = self.writes.lock().unwrap();
+        if self.endpoint(vault_id, endpoint_id)?.is_none() {
+            return Ok(false);
+        }
+        let prefix = format!("{}:", endpoint_id);
+        let mut batch = Batch::new();
+        batch.remove(ENDPOINTS_NAMESPACE, endpoint_id);
+        for (key, _) in self.store.list::<Delivery>(DELIVERIES_NAMESPACE)? {
+            if key.starts_with(&prefix) {
+                batch.remove(DELIVERIES_NAMESPACE, &key);
+            }
+        }
+        self.store.commit(batch)?;
+        Ok(true)
+    }
+
+    /// Queue the vault's events after `after` to the endpoint again, up to
+    /// those fanned out so far; returns how many, or None if the vault has
+    /// no such endpoint
+    pub fn replay(
+        &self,
+        vault_id: &str,
+        endpoint_id: &str,
+        after: u64,
+    ) -> Result<Option<usize>, String> {
+        let _writes = self.writes.lock().unwrap();
+        let Some(endpoint) = self.endpoint(vault_id, endpoint_id)? else {
+            return Ok(None);
+        };
+        let fanned_out: u64 = self
+            .store
+            .get(CURSORS_NAMESPACE, FANOUT_CURSOR)?
+            .unwrap_or(0);
+        let now = now();
+        let mut batch = Batch::new();
+        let mut replayed = 0;
+        let mut cursor = after;
+        while cursor < fanned_out {
+            let page = self.events.after(cursor, events::MAX_BATCH)?;
+            let Some(last) = page.last() else {
+                break;
+            };
+            cursor = last.event.id;
+            for signed in page {
+                let event = signed.event;
+                if event.id > fanned_out {
+                    break;
+                }
+                if event.vault_id == vault_id && event.kind != REDELIVERY_EVENT {
+                    stage_delivery(&mut batch, &endpoint, event, now)?;
+                    replayed += 1;
+                }
+            }
+        }
+        self.store.commit(batch)?;
+        Ok(Some(replayed))
+    }
+
+    /// Fan out new events on the task scheduler
+    pub fn schedule_fanout(self: Arc<Self>, tasks: &TaskScheduler) -> Result<(), String> {
+        let schedule = Schedule::every(FANOUT_EVERY);
+        tasks.register("webhooks.fanout", &schedule, FANOUT_EVERY / 10, move || {
+            let webhooks = self.clone();
+            async move {
+                let queued = webhooks.fan_out()?;
+                if queued > 0 {
+                    info!("Queued {} webhook deliveries", queued);
+                }
+                Ok(())
+            }
+        })
+    }
+
+    /// Queue deliveries of events logged since the last fan-out
+    fn fan_out(&self) -> Result<usize, String> {
+        let _writes = self.writes.lock().unwrap();
+        let now = now();
+        self.store.update(DELIVERIES_NAMESPACE, |ns| {
+            ns.retain(|_, value| {
+                serde_json::from_value::<Delivery>(value.clone()).is_ok_and(|delivery| {
+                    delivery.status == DeliveryStatus::Pending
+                        || delivery.delivered_at.unwrap_or(0) + RETENTION_SECS > now
+                })
+            });
+        })?;
+
+        let mut endpoints: HashMap<String, Vec<WebhookEndpoint>> = HashMap::new();
+        for (_, endpoint) in self.store.list::<WebhookEndpoint>(ENDPOINTS_NAMESPACE)? {
+            endpoints
+                .entry(endpoint.vault_id.clone())
+                .or_default()
+                .push(endpoint);
+        }
+        let cursor: u64 = self
+            .store
+            .get(CURSORS_NAMESPACE, FANOUT_CURSOR)?
+            .unwrap_or(0);
+        let page = self.events.after(cursor, events::MAX_BATCH)?;
+        let Some(last) = page.last().map(|signed| signed.event.id) else {
+            return Ok(0);
+        };
+
+        let mut batch = Batch::new();
+        let mut queued = 0;
+        for signed in page {
+            let event = signed.event;
+            // Redeliveries are for the host's own webhooks
+            if event.kind == REDELIVERY_EVENT {
+                continue;
+            }
+            for endpoint in endpoints.get(&event.vault_id).into_iter().flatten() {
+                if endpoint.created_at <= event.created_at {
+                    stage_delivery(&mut batch, endpoint, event.clone(), now)?;
+                    queued += 1;
+                }
+            }
+        }
+        batch.put(CURSORS_NAMESPACE, FANOUT_CURSOR, &last)?;
+        self.store.commit(batch)?;
+        Ok(queued)
+    }
+
+    /// Lease up to `limit` due deliveries to the relay, at most one per
+    /// endpoint: its oldest unacknowledged
+    pub fn due(&self, limit: usize) -> Result<Vec<DueDelivery>, String> {
+        let _writes = self.writes.lock().unwrap();
+        let now = now();
+        let mut heads = HashSet::new();
+        let mut due = Vec::new();
+        let mut batch = Batch::new();
+        // Keys sort by endpoint, then event id
+        for (key, mut delivery) in self.store.list::<Delivery>(DELIVERIES_NAMESPACE)? {
+            if due.len() >= limit {
+                break;
+            }
+            if delivery.status == DeliveryStatus::Delivered
+                || !heads.insert(delivery.endpoint_id.clone())
+                || delivery.next_attempt_at > now
+            {
+                continue;
+            }
+            delivery.attempts += 1;
+            delivery.next_attempt_at = now + LEASE_SECS;
+            batch.put(DELIVERIES_NAMESPACE, &key, &delivery)?;
+            due.push(DueDelivery {
+                delivery_id: key,
+                url: delivery.url,
+                attempt: delivery.attempts,
+                event: self.events.sign(delivery.event)?,
+            });
+        }
+        self.store.commit(batch)?;
+        Ok(due)
+    }
+
+    /// Apply the relay's acks; returns how many matched a pending delivery
+    pub fn acknowledge(&self, acks: Vec<DeliveryAck>) -> Result<usize, String> {
+        let _writes = self.writes.lock().unwrap();
+        let now = now();
+        let mut batch = Batch::new();
+        let mut applied = 0;
+        for ack in acks {
+            let Some(mut delivery) = self
+                .store
+                .get::<Delivery>(DELIVERIES_NAMESPACE, &ack.delivery_id)?
+            else {
+                continue;
+            };
+            if delivery.status == DeliveryStatus::Delivered {
+                continue;
+            }
+            if ack.delivered {
+                delivery.status = DeliveryStatus::Delivered;
+                delivery.delivered_at = Some(now);
+                delivery.last_error = None;
+            } else {
+                delivery.next_attempt_at = now + backoff(delivery.attempts);
+                delivery.last_error = ack.detail;
+            }
+            batch.put(DELIVERIES_NAMESPACE, &ack.delivery_id, &delivery)?;
+            applied += 1;
+        }
+        self.store.commit(batch)?;
+        Ok(applied)
+    }
+
+    fn endpoint(
+        &self,
+        vault_id: &str,
+        endpoint_id: &str,
+    ) -> Result<Option<WebhookEndpoint>, String> {
+        Ok(self
+            .store
+            .get::<WebhookEndpoint>(ENDPOINTS_NAMESPACE, endpoint_id)?
+            .filter(|endpoint| endpoint.vault_id == vault_id))
+    }
+
+    fn endpoints_of(&self, vault_id: &str) -> Result<Vec<WebhookEndpoint>, String> {
+        let mut endpoints: Vec<WebhookEndpoint> = self
+            .store
+            .list::<WebhookEndpoint>(ENDPOINTS_NAMESPACE)?
+            .into_iter()
+            .map(|(_, endpoint)| endpoint)
+            .filter(|endpoint| endpoint.vault_id == vault_id)
+            .collect();
+        endpoints.sort_by_key(|endpoint| endpoint.created_at);
+        Ok(endpoints)
+    }
+}
+
+/// Queue (or queue again) the event to the endpoint
+fn stage_delivery(
+    batch: &mut Batch,
+    endpoint: &WebhookEndpoint,
+    event: Event,
+    now: u64,
+) -> Result<(), String> {
+    // Zero-padded so the store's key order is event order per endpoint
+    let delivery_id = format!("{}:{:020}", endpoint.endpoint_id, event.id);
+    let delivery = Delivery {
+        delivery_id: delivery_id.clone(),
+        endpoint_id: endpoint.endpoint_id.clone(),
+        vault_id: endpoint.vault_id.clone(),
+        url: endpoint.url.clone(),
+        event,
+        status: DeliveryStatus::Pending,
+        attempts: 0,
+        next_attempt_at: now,
+        delivered_at: None,
+        last_error: None,
+    };
+    batch.put(DELIVERIES_NAMESPACE, &delivery_id, &delivery)
+}
+
+fn backoff(attempts: u32) -> u64 {
+    (RETRY_BASE_SECS << attempts.min(16)).min(MAX_BACKOFF_SECS)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::identity::EnclaveIdentity;
+    use serde_json::json;
+
+    #[test]
+    fn deliveries_go_out_in_order_until_acknowledged() {
+        let dir = std::env::temp_dir().join(format!("lumina-webhook-tests-{}", now()));
+        let store = Arc::new(Store::open(&dir).unwrap());
+        let identity = Arc::new(EnclaveIdentity::ephemeral("test-measurement", "test"));
+        let events = Arc::new(EventLog::new(store.clone(), identity, None).unwrap());
+        let webhooks = WebhookService::new(store, events.clone());
+
+        assert!(webhooks
+            .register("vault-1", "http://example.com/hook", "0xowner")
+            .is_err());
+        let endpoint = webhooks
+            .register("vault-1", "https://example.com/hook", "0xowner")
+            .unwrap();
+        let first = events
+            .emit("check_in.missed", "vault-1", json!({}))
+            .unwrap();
+        let second = events
+            .emit("check_in.missed", "vault-1", json!({}))
+            .unwrap();
+        events
+            .emit("check_in.missed", "vault-2", json!({}))
+            .unwrap();
+        assert_eq!(webhooks.fan_out().unwrap(), 2);
+
+        // Only the oldest is handed out, and not again while leased
+        let due = webhooks.due(10).unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].event.event.id, first.id);
+        assert!(webhooks.due(10).unwrap().is_empty());
+
+        let ack = |delivered| DeliveryAck {
+            delivery_id: due[0].delivery_id.clone(),
+            delivered,
+            detail: None,
+        };
+        assert_eq!(webhooks.acknowledge(vec![ack(true)]).unwrap(), 1);
+        assert_eq!(webhooks.acknowledge(vec![ack(true)]).unwrap(), 0);
+        let due = webhooks.due(10).unwrap();
+        assert_eq!(due[0].event.event.id, second.id);
+
+        // A replay queues the delivered event again, ahead of the rest
+        let replayed = webhooks.replay("vault-1", &endpoint.endpoint_id, 0);
+        assert_eq!(replayed.unwrap(), Some(2));
+        assert_eq!(webhooks.due(10).unwrap()[0].event.event.id, first.id);
+        assert_eq!(
+            webhooks.replay("vault-2", &endpoint.endpoint_id, 0),
+            Ok(None)
+        );
+    }
+}
@@ -3,9 +3,52 @@
  * Generates zero-knowledge proofs in secure enclave (privacy-preserving)
  */
 
+use ark_bn254::{Fr, G1Affine, G2Affine};
+use ark_ec::{AffineRepr, CurveGroup};
+use ark_ff::UniformRand;
+use rand::{rngs::OsRng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use sha2::{Sha256, Digest};
+use thiserror::Error;
+use tracing::{debug, info};
+
+use crate::blob_stream::{self, HashAlgorithm};
+use crate::circuits::{CircuitRegistry, CircuitVersion};
+use crate::claim_definitions::ClaimDefinition;
+use crate::credentials::{self, AttributePredicate, IdentityCredential};
+use crate::crypto;
+use crate::dkim::DkimEmailClaim;
+use crate::msm::Msm;
+use crate::proof_encoding;
+use crate::proof_jobs::Stage;
+use crate::statements::SolvencyClaim;
+
+/// Separates deterministic proving seeds from other uses of the inputs
+const DETERMINISTIC_SEED_DOMAIN: &[u8] = b"lumina-deterministic-proof-v1";
+
+#[derive(Debug, Error)]
+pub enum ZKProofError {
+    #[error("encrypted_data must be base64: {0}")]
+    InvalidPayload(String),
+    /// claim_value doesn't fit the circuit, or the data doesn't satisfy it
+    #[error("{0}")]
+    InvalidClaim(String),
+    #[error("No prover for {claim_type} version {version}")]
+    NoProver { claim_type: String, version: u32 },
+    #[error("Proving failed: {0}")]
+    Proving(String),
+    #[error(
+        "{claim_type} version {version} produced {produced} public signals, expected {expected}"
+    )]
+    SignalMismatch {
+        claim_type: &'static str,
+        version: u32,
+        produced: usize,
+        expected: usize,
+    },
+}
 
 #[derive(Serialize)]
 pub struct ZKProofResult {
@@ -13,19 +56,80 @@ pub struct ZKProofResult {
     pub public_signals: Vec<String>,
 }
 
-pub struct ZKProofService;
+/// A request's encrypted payload. Circuits that only hash it read it from
+/// the base64 a chunk at a time; the rest have it decoded whole.
+pub enum ProofPayload {
+    Encoded(String),
+    Decoded(Vec<u8>),
+}
+
+impl ProofPayload {
+    pub fn for_circuit(circuit: &CircuitVersion, encoded: String) -> Result<Self, ZKProofError> {
+        match (circuit.claim_type, circuit.version) {
+            // Checked up front so bad base64 is the caller's error, not
+            // a proving failure
+            ("file_hash", 1) => {
+                blob_stream::decode_chunks(&encoded, |_| Ok(()))
+                    .map_err(ZKProofError::InvalidPayload)?;
+                Ok(Self::Encoded(encoded))
+            }
+            _ => crypto::decode_in_place(encoded)
+                .map(Self::Decoded)
+                .map_err(ZKProofError::InvalidPayload),
+        }
+    }
+
+    fn bytes(&self) -> Result<&[u8], ZKProofError> {
+        match self {
+            Self::Decoded(bytes) => Ok(bytes),
+            Self::Encoded(_) => Err(ZKProofError::Proving(
+                "Payload was not decoded for this circuit".to_string(),
+            )),
+        }
+    }
+
+    fn hash(&self, algorithm: HashAlgorithm) -> Result<[u8; 32], ZKProofError> {
+        match self {
+            Self::Encoded(encoded) => {
+                blob_stream::hash_base64(encoded, algorithm).map_err(ZKProofError::InvalidPayload)
+            }
+            Self::Decoded(bytes) => Ok(blob_stream::hash_chunks(bytes, algorithm)),
+        }
+    }
+}
+
+pub struct ZKProofService {
+    circuits: CircuitRegistry,
+    msm: Msm,
+    /// Whether requests may seed the prover (development only)
+    allow_deterministic: bool,
+}
 
 impl ZKProofService {
-    pub fn new() -> Self {
-        Self
+    pub fn new(circuits: CircuitRegistry, msm: Msm, allow_deterministic: bool) -> Self {
+        Self {
+            circuits,
+            msm,
+            allow_deterministic,
+        }
+    }
+
+    pub fn circuits(&self) -> &CircuitRegistry {
+        &self.circuits
+    }
+
+    pub fn allows_deterministic(&self) -> bool {
+        self.allow_deterministic
     }
 
     pub async fn generate(
         &self,
-        claim_type: &str,
+        circuit: &CircuitVersion,
         claim_value: &Value,
-        encrypted_data: &[u8],
-    ) -> Result<ZKProofResult, String> {
+        payload: &ProofPayload,
+        deterministic: bool,
+        progress: &(dyn Fn(Stage) + Sync),
+    ) -> Result<ZKProofResult, ZKProofError> {
         // In real implementation, this would:
         // 1. Decrypt data in enclave (using Seal session key)
         // 2. Load appropriate ZK circuit (compiled .wasm + .zkey)
@@ -40,19 +144,49 @@ impl ZKProofService {
         // - Call snarkjs.groth16.fullProve()
         // - Return proof object
         
-        match claim_type {
-            "keyword" => self.generate_keyword_proof(claim_value, encrypted_data).await,
-            "timestamp" => self.generate_timestamp_proof(claim_value, encrypted_data).await,
-            "file_hash" => self.generate_hash_proof(claim_value, encrypted_data).await,
-            _ => Err(format!("Unsupported claim type: {}", claim_type)),
+        // Each registered version keeps its own witness builder, so pinned
+        // older versions still prove alongside the current ones
+        progress(Stage::Witness);
+        let mut result = match (circuit.claim_type, circuit.version) {
+            ("keyword", 1) => self.generate_keyword_proof(claim_value, payload.bytes()?).await,
+            ("timestamp", 1) => self.generate_timestamp_proof(claim_value, payload.bytes()?).await,
+            ("file_hash", 1) => self.generate_hash_proof(claim_value, payload).await,
+            ("identity_attribute", 1) => self.generate_identity_attribute_proof(claim_value, payload.bytes()?, deterministic, progress).await,
+            ("solvency", 1) => self.generate_solvency_proof(claim_value, payload.bytes()?, deterministic, progress).await,
+            ("dkim_email", 1) => self.generate_dkim_email_proof(claim_value, payload.bytes()?, deterministic, progress).await,
+            ("predicate", 1) => self.generate_predicate_proof(claim_value, payload.bytes()?, deterministic, progress).await,
+            (claim_type, version) => Err(ZKProofError::NoProver {
+                claim_type: claim_type.to_string(),
+                version,
+            }),
+        }?;
+
+        if result.public_signals.len() != circuit.public_signals {
+            return Err(ZKProofError::SignalMismatch {
+                claim_type: circuit.claim_type,
+                version: circuit.version,
+                produced: result.public_signals.len(),
+                expected: circuit.public_signals,
+            });
+        }
+        // Archived proofs carry the version and proving key they came from
+        if let Some(proof) = result.proof.as_object_mut() {
+            proof.insert("circuit_version".to_string(), circuit.version.into());
+            if let Some(zkey) = &circuit.zkey_sha256 {
+                proof.insert("zkey_sha256".to_string(), zkey.clone().into());
+            }
+            if deterministic {
+                proof.insert("deterministic".to_string(), true.into());
+            }
         }
+        Ok(result)
     }
 
     async fn generate_keyword_proof(
         &self,
         claim_value: &Value,
         _encrypted_data: &[u8],
-    ) -> Result<ZKProofResult, String> {
+    ) -> Result<ZKProofResult, ZKProofError> {
         // Placeholder: Real implementation would:
         // 1. Decrypt encrypted_data in enclave
         // 2. Search for keyword in decrypted content
@@ -62,7 +196,7 @@ impl ZKProofService {
         let keyword = claim_value
             .get("keyword")
             .and_then(|v| v.as_str())
-            .ok_or("Missing keyword in claim_value")?;
+            .ok_or_else(|| invalid_claim("Missing keyword in claim_value"))?;
 
         // Placeholder proof structure
         let proof = serde_json::json!({
@@ -89,7 +223,7 @@ impl ZKProofService {
         &self,
         claim_value: &Value,
         _encrypted_data: &[u8],
-    ) -> Result<ZKProofResult, String> {
+    ) -> Result<ZKProofResult, ZKProofError> {
         // Placeholder: Real implementation would prove timestamp range
         let min = claim_value.get("min").and_then(|v| v.as_u64());
         let max = claim_value.get("max").and_then(|v| v.as_u64());
@@ -114,20 +248,22 @@ impl ZKProofService {
     async fn generate_hash_proof(
         &self,
         claim_value: &Value,
-        encrypted_data: &[u8],
-    ) -> Result<ZKProofResult, String> {
+        payload: &ProofPayload,
+    ) -> Result<ZKProofResult, ZKProofError> {
         // Placeholder: Real implementation would prove file hash matches
         let expected_hash = claim_value
             .get("hash")
             .and_then(|v| v.as_str())
-            .ok_or("Missing hash in claim_value")?;
+            .ok_or_else(|| invalid_claim("Missing hash in claim_value"))?;
+        let algorithm: HashAlgorithm = claim_value
+            .get("algorithm")
+            .map(|v| serde_json::from_value(v.clone()))
+            .transpose()
+            .map_err(|_| invalid_claim("algorithm must be sha256 or blake3"))?
+            .unwrap_or_default();
 
-        // Calculate actual hash of encrypted data
-        let actual_hash = {
-            let mut hasher = Sha256::new();
-            hasher.update(encrypted_data);
-            hex::encode(hasher.finalize())
-        };
+        // Hash the encrypted data a chunk at a time, never decoded whole
+        let actual_hash = hex::encode(payload.hash(algorithm)?);
 
         let proof = serde_json::json!({
             "pi_a": ["0xaaaa", "0xbbbb"],
@@ -145,5 +281,183 @@ impl ZKProofService {
             public_signals,
         })
     }
+
+    async fn generate_identity_attribute_proof(
+        &self,
+        claim_value: &Value,
+        credential: &[u8],
+        deterministic: bool,
+        progress: &(dyn Fn(Stage) + Sync),
+    ) -> Result<ZKProofResult, ZKProofError> {
+        // Only the predicate result and the public inputs (issuer key,
+        // subject, date, predicate) leave the enclave; the issuer signature
+        // is checked by the circuit
+        let predicate: AttributePredicate = serde_json::from_value(claim_value.clone())
+            .map_err(|e| invalid_claim(format!("Invalid identity_attribute claim: {}", e)))?;
+        let credential =
+            IdentityCredential::parse(credential).map_err(ZKProofError::InvalidClaim)?;
+        let witness = credential
+            .witness(&predicate, credentials::today())
+            .map_err(ZKProofError::InvalidClaim)?;
+        info!("identity_attribute proof for credential from {}", credential.issuer.id);
+
+        Ok(ZKProofResult {
+            proof: prove("identity_attribute", &witness.inputs, self.msm, deterministic, progress)
+                .map_err(ZKProofError::Proving)?,
+            public_signals: witness.public_signals,
+        })
+    }
+
+    async fn generate_solvency_proof(
+        &self,
+        claim_value: &Value,
+        statement: &[u8],
+        deterministic: bool,
+        progress: &(dyn Fn(Stage) + Sync),
+    ) -> Result<ZKProofResult, ZKProofError> {
+        // Balances and account identifiers stay in the enclave; the proof
+        // exposes the result, the threshold and the statement digest
+        let claim: SolvencyClaim = serde_json::from_value(claim_value.clone())
+            .map_err(|e| invalid_claim(format!("Invalid solvency claim: {}", e)))?;
+        let witness = claim
+            .witness(statement)
+            .map_err(ZKProofError::InvalidClaim)?;
+
+        Ok(ZKProofResult {
+            proof: prove("solvency", &witness.inputs, self.msm, deterministic, progress)
+                .map_err(ZKProofError::Proving)?,
+            public_signals: witness.public_signals,
+        })
+    }
+
+    async fn generate_dkim_email_proof(
+        &self,
+        claim_value: &Value,
+        eml: &[u8],
+        deterministic: bool,
+        progress: &(dyn Fn(Stage) + Sync),
+    ) -> Result<ZKProofResult, ZKProofError> {
+        // Headers and body stay in the enclave; the proof exposes the DKIM
+        // key, the signing domain and the pattern
+        let claim: DkimEmailClaim = serde_json::from_value(claim_value.clone())
+            .map_err(|e| invalid_claim(format!("Invalid dkim_email claim: {}", e)))?;
+        let witness = claim.witness(eml).map_err(ZKProofError::InvalidClaim)?;
+        info!("dkim_email proof for a message signed by {}", claim.domain);
+
+        Ok(ZKProofResult {
+            proof: prove("dkim_email", &witness.inputs, self.msm, deterministic, progress)
+                .map_err(ZKProofError::Proving)?,
+            public_signals: witness.public_signals,
+        })
+    }
+
+    async fn generate_predicate_proof(
+        &self,
+        claim_value: &Value,
+        data: &[u8],
+        deterministic: bool,
+        progress: &(dyn Fn(Stage) + Sync),
+    ) -> Result<ZKProofResult, ZKProofError> {
+        // Field values stay in the enclave; the proof exposes the result,
+        // the compiled conditions and the definition digest
+        let definition: ClaimDefinition = serde_json::from_value(claim_value.clone())
+            .map_err(|e| invalid_claim(format!("Invalid claim definition: {}", e)))?;
+        let witness = definition
+            .witness(data)
+            .map_err(ZKProofError::InvalidClaim)?;
+        info!("predicate proof for claim definition {}", definition.name);
+
+        Ok(ZKProofResult {
+            proof: prove("predicate", &witness.inputs, self.msm, deterministic, progress)
+                .map_err(ZKProofError::Proving)?,
+            public_signals: witness.public_signals,
+        })
+    }
+}
+
+fn invalid_claim(reason: impl Into<String>) -> ZKProofError {
+    ZKProofError::InvalidClaim(reason.into())
 }
 
+/// Groth16 proof of `inputs` for `circuit`, its MSMs on `msm`. Placeholder
+/// until the prover (fullProve over the circuit's .wasm and .zkey) runs in
+/// the enclave; it draws the blinding factors r and s a real prover would
+/// and outputs rG1, sG2 and rsG1.
+///
+/// A deterministic proof seeds r and s from the circuit and its inputs, so
+/// the same request proves to the same bytes; for snapshot tests only, as
+/// the blinding is what keeps a proof from revealing its witness.
+fn prove(
+    circuit: &str,
+    inputs: &Value,
+    msm: Msm,
+    deterministic: bool,
+    progress: &(dyn Fn(Stage) + Sync),
+) -> Result<Value, String> {
+    if !inputs.is_object() {
+        return Err(format!("Inputs for {} must be named signals", circuit));
+    }
+    progress(Stage::Msm);
+    debug!("Proving {} with the {} MSM backend", circuit, msm);
+
+    let mut rng: Box<dyn RngCore> = if deterministic {
+        let mut seed = Sha256::new();
+        seed.update(DETERMINISTIC_SEED_DOMAIN);
+        seed.update(circuit.as_bytes());
+        seed.update([0]);
+        seed.update(serde_json::to_vec(inputs).map_err(|e| e.to_string())?);
+        Box::new(ChaCha20Rng::from_seed(seed.finalize().into()))
+    } else {
+        Box::new(OsRng)
+    };
+    let (r, s) = (Fr::rand(&mut rng), Fr::rand(&mut rng));
+
+    let mut proof = proof_encoding::snarkjs(
+        (G1Affine::generator() * r).into_affine(),
+        (G2Affine::generator() * s).into_affine(),
+        (G1Affine::generator() * (r * s)).into_affine(),
+    );
+    proof["circuit"] = circuit.into();
+    Ok(proof)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proof(inputs: Value, deterministic: bool) -> Value {
+        prove("solvency", &inputs, Msm::Serial, deterministic, &|_| {}).unwrap()
+    }
+
+    #[test]
+    fn deterministic_proofs_repeat() {
+        let inputs = serde_json::json!({ "balances": ["1", "2"], "threshold": "3" });
+        assert_eq!(proof(inputs.clone(), true), proof(inputs.clone(), true));
+        assert_ne!(
+            proof(inputs, true),
+            proof(serde_json::json!({ "balances": ["1", "2"], "threshold": "4" }), true)
+        );
+    }
+
+    #[test]
+    fn deterministic_proofs_match_snapshot() {
+        let proof = proof(serde_json::json!({ "threshold": "25000" }), true);
+        let compact = proof_encoding::compact(&proof, &[]).unwrap();
+        // A, B and C; changes here break every stored snapshot
+        assert_eq!(
+            compact.proof_points,
+            concat!(
+                "ee8680fae0748bd211342fb581a60c5eb67c95ff8c447ee23be30215e7803708",
+                "6b49f6e8a0680c871366eff208f2af3cebe2de9949f087ae1bd4c279adacce1b",
+                "f27731177a7fa801c7a0742ab176a122c1541ca4af1b9f87a5c95283c5e10c14",
+                "6eac1054b13a0d2935c75d403f6a6c09623f43ae4765cc0a7d508c02c7832809",
+            )
+        );
+    }
+
+    #[test]
+    fn proofs_are_blinded_by_default() {
+        let inputs = serde_json::json!({ "threshold": "25000" });
+        assert_ne!(proof(inputs.clone(), false), proof(inputs, false));
+    }
+}
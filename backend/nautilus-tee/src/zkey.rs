@@ -0,0 +1,152 @@
+/**
+ * Groth16 Proving Keys
+ * Reads the ceremony record snarkjs keeps in a .zkey
+ *
+ * A .zkey is a sequence of typed sections. The Groth16 header (section 2)
+ * holds the key's delta; the MPC section (10) lists every phase 2
+ * contribution with the delta it produced. The key is only the output of
+ * its ceremony when the last contribution's delta is the header's delta,
+ * so a key whose delta was changed after the ceremony, or that skipped
+ * contributions, is caught here.
+ *
+ * This doesn't redo the pairing checks of `snarkjs zkey verify`; those run
+ * against the circuit and the powers of tau in the release pipeline that
+ * signs the artifact manifest.
+ */
+
+const MAGIC: &[u8; 4] = b"zkey";
+const GROTH16: u32 = 1;
+const HEADER_SECTION: u32 = 1;
+const GROTH16_HEADER_SECTION: u32 = 2;
+const MPC_SECTION: u32 = 10;
+const TRANSCRIPT_BYTES: usize = 64;
+
+/// The last phase 2 contribution to a key
+pub struct Contribution {
+    /// Contributions in the ceremony, this one included
+    pub count: u32,
+    /// Hex challenge hash the contribution answered
+    pub transcript: String,
+    pub name: Option<String>,
+}
+
+/// Check the key is the output of its ceremony's final contribution
+pub fn final_contribution(zkey: &[u8]) -> Result<Contribution, String> {
+    let sections = sections(zkey)?;
+    let section = |kind: u32| {
+        sections
+            .iter()
+            .find(|(k, _)| *k == kind)
+            .map(|(_, body)| *body)
+            .ok_or_else(|| format!("zkey has no section {}", kind))
+    };
+
+    let mut header = Reader(section(HEADER_SECTION)?);
+    if header.u32()? != GROTH16 {
+        return Err("zkey is not a Groth16 key".to_string());
+    }
+
+    // n8q, q, n8r, r, nVars, nPublic, domainSize, alpha1, beta1, beta2,
+    // gamma2, delta1, delta2
+    let mut groth16 = Reader(section(GROTH16_HEADER_SECTION)?);
+    let n8q = groth16.u32()? as usize;
+    groth16.bytes(n8q)?;
+    let n8r = groth16.u32()? as usize;
+    groth16.bytes(n8r)?;
+    groth16.bytes(3 * 4)?;
+    let (g1, g2) = (2 * n8q, 4 * n8q);
+    groth16.bytes(g1 + g1 + g2 + g2)?;
+    let delta = groth16.bytes(g1)?;
+
+    // csHash, then each contribution: deltaAfter, the proof of knowledge
+    // (g1_s, g1_sx, g2_spx), transcript, type and its parameters
+    let mut mpc = Reader(section(MPC_SECTION)?);
+    mpc.bytes(64)?;
+    let count = mpc.u32()?;
+    if count == 0 {
+        return Err("zkey has no phase 2 contributions".to_string());
+    }
+    let mut last = None;
+    for _ in 0..count {
+        let delta_after = mpc.bytes(g1)?;
+        mpc.bytes(g1 + g1 + g2)?;
+        let transcript = mpc.bytes(TRANSCRIPT_BYTES)?;
+        mpc.u32()?;
+        let params_len = mpc.u32()? as usize;
+        let params = Reader(mpc.bytes(params_len)?);
+        last = Some((delta_after, transcript, params));
+    }
+    let (delta_after, transcript, params) = last.unwrap();
+    if delta_after != delta {
+        return Err("zkey delta doesn't match its final contribution".to_string());
+    }
+
+    Ok(Contribution {
+        count,
+        transcript: hex::encode(transcript),
+        name: contribution_name(params),
+    })
+}
+
+/// (section type, body) pairs
+fn sections(zkey: &[u8]) -> Result<Vec<(u32, &[u8])>, String> {
+    let mut file = Reader(zkey);
+    if file.bytes(4)? != MAGIC {
+        return Err("Not a zkey file".to_string());
+    }
+    file.u32()?; // Format version
+    let count = file.u32()?;
+    let mut sections = Vec::new();
+    for _ in 0..count {
+        let kind = file.u32()?;
+        let size = usize::try_from(file.u64()?).map_err(|_| "zkey section too large")?;
+        sections.push((kind, file.bytes(size)?));
+    }
+    Ok(sections)
+}
+
+/// Parameters are (key, value) pairs; key 1 is the contributor's name
+fn contribution_name(mut params: Reader) -> Option<String> {
+    while let Ok(key) = params.u8() {
+        match key {
+            1 | 3 => {
+                let len = params.u8().ok()?;
+                let value = params.bytes(usize::from(len)).ok()?;
+                if key == 1 {
+                    return Some(String::from_utf8_lossy(value).into_owned());
+                }
+            }
+            2 => {
+                params.u8().ok()?;
+            }
+            _ => return None,
+        }
+    }
+    None
+}
+
+/// Little-endian cursor over a byte slice
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.0.len() < len {
+            return Err("Truncated zkey".to_string());
+        }
+        let (head, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+}
@@ -0,0 +1,198 @@
+/**
+ * LUMINA DKIM Email Circuit
+ * Proves an email carries a valid DKIM signature and its body contains a
+ * pattern, without revealing the email
+ *
+ * Built on zk-email's EmailVerifier: the RSA-SHA256 signature is checked
+ * over the canonicalized signed headers, and the body hash in the
+ * DKIM-Signature header over the canonicalized body. The body's SHA-256 is
+ * precomputed up to a 64-byte block before the pattern, so only the tail
+ * of the body needs to fit the circuit.
+ *
+ * The DKIM key is public. Verifiers must check it is the key the domain
+ * publishes (selector._domainkey.domain); the circuit ties the domain in
+ * by matching it against the d= tag of the DKIM-Signature header.
+ *
+ * Byte strings are public as 31-byte little-endian chunks, zero-padded.
+ *
+ * Public inputs:
+ * - pubkey[17] (RSA modulus, 121-bit limbs, least significant first)
+ * - domain[3] (signing domain, up to 64 bytes)
+ * - pattern[3] (pattern in the body, up to 64 bytes)
+ *
+ * Private inputs:
+ * - email_header, email_header_length (SHA-padded signed headers)
+ * - signature[17]
+ * - email_body, email_body_length, body_hash_index, precomputed_sha
+ * - dkim_header_index, domain_index, domain_bytes, domain_length
+ * - pattern_index, pattern_bytes, pattern_length
+ */
+
+pragma circom 2.1.5;
+
+include "../node_modules/circomlib/circuits/bitify.circom";
+include "../node_modules/circomlib/circuits/comparators.circom";
+include "../node_modules/@zk-email/circuits/email-verifier.circom";
+include "../node_modules/@zk-email/circuits/utils/array.circom";
+
+// Bytes (zero after `length`, non-zero before it) packed into 31-byte
+// little-endian chunks
+template PackedBytes(MAX_BYTES) {
+    var CHUNKS = (MAX_BYTES + 30) \ 31;
+
+    signal input bytes[MAX_BYTES];
+    signal input length;
+    signal output out[CHUNKS];
+
+    component in_range[MAX_BYTES];
+    component range[MAX_BYTES];
+    component zero[MAX_BYTES];
+    for (var i = 0; i < MAX_BYTES; i++) {
+        range[i] = Num2Bits(8);
+        range[i].in <== bytes[i];
+
+        in_range[i] = LessThan(8);
+        in_range[i].in[0] <== i;
+        in_range[i].in[1] <== length;
+
+        // Inside the string a byte is non-zero, outside it is zero
+        zero[i] = IsZero();
+        zero[i].in <== bytes[i];
+        zero[i].out === 1 - in_range[i].out;
+    }
+
+    var chunk[CHUNKS];
+    for (var c = 0; c < CHUNKS; c++) {
+        chunk[c] = 0;
+    }
+    for (var i = 0; i < MAX_BYTES; i++) {
+        chunk[i \ 31] += bytes[i] * (1 << (8 * (i % 31)));
+    }
+    for (var c = 0; c < CHUNKS; c++) {
+        out[c] <== chunk[c];
+    }
+}
+
+// haystack[index..index + MAX_NEEDLE] equals the zero-padded needle, and
+// the needle ends within the first `limit` bytes
+template SubstringAt(MAX_HAYSTACK, MAX_NEEDLE) {
+    signal input haystack[MAX_HAYSTACK];
+    signal input index;
+    signal input needle[MAX_NEEDLE];
+    signal input length;
+    signal input limit;
+
+    component within = LessEqThan(16);
+    within.in[0] <== index + length;
+    within.in[1] <== limit;
+    within.out === 1;
+
+    component window = SelectSubArray(MAX_HAYSTACK, MAX_NEEDLE);
+    window.in <== haystack;
+    window.startIndex <== index;
+    window.length <== length;
+    for (var i = 0; i < MAX_NEEDLE; i++) {
+        window.out[i] === needle[i];
+    }
+}
+
+template DkimEmail(MAX_HEADER, MAX_BODY, N, K, MAX_DOMAIN, MAX_PATTERN) {
+    var DOMAIN_CHUNKS = (MAX_DOMAIN + 30) \ 31;
+    var PATTERN_CHUNKS = (MAX_PATTERN + 30) \ 31;
+
+    // Public inputs
+    signal input pubkey[K];
+    signal input domain[DOMAIN_CHUNKS];
+    signal input pattern[PATTERN_CHUNKS];
+
+    // Private inputs
+    signal input email_header[MAX_HEADER];
+    signal input email_header_length;
+    signal input signature[K];
+    signal input email_body[MAX_BODY];
+    signal input email_body_length;
+    signal input body_hash_index;
+    signal input precomputed_sha[32];
+    signal input dkim_header_index;
+    signal input domain_index;
+    signal input domain_bytes[MAX_DOMAIN];
+    signal input domain_length;
+    signal input pattern_index;
+    signal input pattern_bytes[MAX_PATTERN];
+    signal input pattern_length;
+
+    // DKIM signature over the headers and body hash over the body
+    component email = EmailVerifier(MAX_HEADER, MAX_BODY, N, K, 0, 0, 0, 0);
+    email.emailHeader <== email_header;
+    email.emailHeaderLength <== email_header_length;
+    email.pubkey <== pubkey;
+    email.signature <== signature;
+    email.emailBody <== email_body;
+    email.emailBodyLength <== email_body_length;
+    email.bodyHashIndex <== body_hash_index;
+    email.precomputedSHA <== precomputed_sha;
+
+    // d=<domain> inside the DKIM-Signature header, which relaxed
+    // canonicalization lowercases and the signed headers end with
+    var DKIM_HEADER[15] = [100, 107, 105, 109, 45, 115, 105, 103, 110, 97, 116, 117, 114, 101, 58];
+    component dkim_header = SubstringAt(MAX_HEADER, 15);
+    dkim_header.haystack <== email_header;
+    dkim_header.index <== dkim_header_index;
+    for (var i = 0; i < 15; i++) {
+        dkim_header.needle[i] <== DKIM_HEADER[i];
+    }
+    dkim_header.length <== 15;
+    dkim_header.limit <== email_header_length;
+
+    component in_dkim_header = LessEqThan(16);
+    in_dkim_header.in[0] <== dkim_header_index + 15;
+    in_dkim_header.in[1] <== domain_index - 2;
+    in_dkim_header.out === 1;
+
+    component domain_tag = SubstringAt(MAX_HEADER, 2);
+    domain_tag.haystack <== email_header;
+    domain_tag.index <== domain_index - 2;
+    domain_tag.needle[0] <== 100;
+    domain_tag.needle[1] <== 61;
+    domain_tag.length <== 2;
+    domain_tag.limit <== email_header_length;
+
+    component domain_value = SubstringAt(MAX_HEADER, MAX_DOMAIN);
+    domain_value.haystack <== email_header;
+    domain_value.index <== domain_index;
+    domain_value.needle <== domain_bytes;
+    domain_value.length <== domain_length;
+    domain_value.limit <== email_header_length;
+
+    // The tag value ends at ';' or whitespace, so a prefix can't match
+    component terminator = ItemAtIndex(MAX_HEADER);
+    terminator.in <== email_header;
+    terminator.index <== domain_index + domain_length;
+    signal not_separator;
+    not_separator <== (terminator.out - 59) * (terminator.out - 32);
+    not_separator * (terminator.out - 9) === 0;
+
+    component packed_domain = PackedBytes(MAX_DOMAIN);
+    packed_domain.bytes <== domain_bytes;
+    packed_domain.length <== domain_length;
+    for (var c = 0; c < DOMAIN_CHUNKS; c++) {
+        packed_domain.out[c] === domain[c];
+    }
+
+    // Pattern in the signed part of the body
+    component found = SubstringAt(MAX_BODY, MAX_PATTERN);
+    found.haystack <== email_body;
+    found.index <== pattern_index;
+    found.needle <== pattern_bytes;
+    found.length <== pattern_length;
+    found.limit <== email_body_length;
+
+    component packed_pattern = PackedBytes(MAX_PATTERN);
+    packed_pattern.bytes <== pattern_bytes;
+    packed_pattern.length <== pattern_length;
+    for (var c = 0; c < PATTERN_CHUNKS; c++) {
+        packed_pattern.out[c] === pattern[c];
+    }
+}
+
+component main {public [pubkey, domain, pattern]} = DkimEmail(1024, 2048, 121, 17, 64, 64);
@@ -0,0 +1,116 @@
+/**
+ * LUMINA Identity Attribute Circuit
+ * Proves a predicate over an issuer-signed identity credential
+ *
+ * The issuer signs Poseidon(subject, birth_date, nationality, expires) with
+ * EdDSA over Baby Jubjub. The circuit checks that signature and the
+ * credential's expiry, then evaluates the requested predicates. Only the
+ * predicate result leaves the circuit; the birth date and nationality stay
+ * private.
+ *
+ * Dates are YYYYMMDD integers, so someone is at least N years old exactly
+ * when birth_date + N * 10000 <= today. Nationalities are ISO 3166-1
+ * alpha-2 codes packed as (first letter << 8) | second letter.
+ *
+ * Public inputs:
+ * - issuer_ax, issuer_ay (issuer's Baby Jubjub public key)
+ * - subject (vault owner's address, truncated to 248 bits)
+ * - today (YYYYMMDD)
+ * - check_age, min_age (age >= min_age, when check_age = 1)
+ * - check_nationality, allowed_nationalities[16] (0 = unused slot)
+ *
+ * Private inputs:
+ * - birth_date, nationality, expires (the credential's attributes)
+ * - sig_r8x, sig_r8y, sig_s (the issuer's signature)
+ *
+ * Output:
+ * - result (1 when every enabled predicate holds)
+ */
+
+pragma circom 2.0.0;
+
+include "../node_modules/circomlib/circuits/comparators.circom";
+include "../node_modules/circomlib/circuits/eddsaposeidon.circom";
+include "../node_modules/circomlib/circuits/poseidon.circom";
+
+template IdentityAttribute(MAX_NATIONALITIES) {
+    // Public inputs
+    signal input issuer_ax;
+    signal input issuer_ay;
+    signal input subject;
+    signal input today;
+    signal input check_age;
+    signal input min_age;
+    signal input check_nationality;
+    signal input allowed_nationalities[MAX_NATIONALITIES];
+
+    // Private inputs
+    signal input birth_date;
+    signal input nationality;
+    signal input expires;
+    signal input sig_r8x;
+    signal input sig_r8y;
+    signal input sig_s;
+
+    // Output
+    signal output result;
+
+    // Issuer signature over the credential's attributes
+    component message = Poseidon(4);
+    message.inputs[0] <== subject;
+    message.inputs[1] <== birth_date;
+    message.inputs[2] <== nationality;
+    message.inputs[3] <== expires;
+
+    component signature = EdDSAPoseidonVerifier();
+    signature.enabled <== 1;
+    signature.Ax <== issuer_ax;
+    signature.Ay <== issuer_ay;
+    signature.R8x <== sig_r8x;
+    signature.R8y <== sig_r8y;
+    signature.S <== sig_s;
+    signature.M <== message.out;
+
+    // An expired credential can't be proven at all
+    component unexpired = GreaterEqThan(32);
+    unexpired.in[0] <== expires;
+    unexpired.in[1] <== today;
+    unexpired.out === 1;
+
+    check_age * (check_age - 1) === 0;
+    check_nationality * (check_nationality - 1) === 0;
+
+    // Age predicate
+    component adult = LessEqThan(32);
+    adult.in[0] <== birth_date + min_age * 10000;
+    adult.in[1] <== today;
+
+    // Nationality predicate: the product of differences is zero exactly
+    // when the nationality is one of the allowed codes
+    signal remaining[MAX_NATIONALITIES + 1];
+    remaining[0] <== 1;
+    for (var i = 0; i < MAX_NATIONALITIES; i++) {
+        remaining[i + 1] <== remaining[i] * (nationality - allowed_nationalities[i]);
+    }
+    component listed = IsZero();
+    listed.in <== remaining[MAX_NATIONALITIES];
+
+    // Disabled predicates pass
+    signal age_ok;
+    signal nationality_ok;
+    age_ok <== 1 - check_age + check_age * adult.out;
+    nationality_ok <== 1 - check_nationality + check_nationality * listed.out;
+
+    result <== age_ok * nationality_ok;
+}
+
+component main {public [
+    issuer_ax,
+    issuer_ay,
+    subject,
+    today,
+    check_age,
+    min_age,
+    check_nationality,
+    allowed_nationalities
+]} = IdentityAttribute(16);
@@ -0,0 +1,124 @@
+/**
+ * LUMINA Predicate Circuit
+ * Proves declarative claim definitions over vault data without a circuit
+ * per claim
+ *
+ * A definition compiles into MAX_CONDITIONS slots, each an op applied to a
+ * private value and a public constant. Ops:
+ *   0 unused (holds)   1 eq    2 neq    3 lt    4 lte    5 gt    6 gte
+ *   7 commit (holds; the constant is the enclave's commitment to the value)
+ *
+ * Values and constants are field elements: integers offset by 2^63 so
+ * signed order survives, strings as their truncated SHA-256, booleans as
+ * 0 or 1. Ordered ops take both sides as 64-bit values.
+ *
+ * Hashing happens in the enclave, as with the other circuits' digests: the
+ * attestation covers the commitments and definition_digest, the SHA-256
+ * of the canonical definition (truncated to 248 bits), so a proof can't
+ * be passed off for a different claim.
+ *
+ * Public inputs:
+ * - ops[8]
+ * - constants[8]
+ * - definition_digest
+ *
+ * Private inputs:
+ * - values[8]
+ *
+ * Output:
+ * - holds (1 when every slot's condition holds)
+ */
+
+pragma circom 2.0.0;
+
+include "../node_modules/circomlib/circuits/bitify.circom";
+include "../node_modules/circomlib/circuits/comparators.circom";
+
+template Condition() {
+    signal input op;
+    signal input value;
+    signal input constant;
+    signal output holds;
+
+    // One indicator per op; exactly one is set
+    component is_op[8];
+    var selected = 0;
+    for (var j = 0; j < 8; j++) {
+        is_op[j] = IsEqual();
+        is_op[j].in[0] <== op;
+        is_op[j].in[1] <== j;
+        selected += is_op[j].out;
+    }
+    selected === 1;
+
+    component equal = IsEqual();
+    equal.in[0] <== value;
+    equal.in[1] <== constant;
+
+    // Ordered ops compare 64-bit values; other ops compare zeros, so
+    // strings' 248-bit elements never reach the range checks
+    signal ordered;
+    ordered <== is_op[3].out + is_op[4].out + is_op[5].out + is_op[6].out;
+    signal ordered_value;
+    ordered_value <== ordered * value;
+    signal ordered_constant;
+    ordered_constant <== ordered * constant;
+    component value_range = Num2Bits(64);
+    value_range.in <== ordered_value;
+    component constant_range = Num2Bits(64);
+    constant_range.in <== ordered_constant;
+
+    component less = LessThan(65);
+    less.in[0] <== ordered_value;
+    less.in[1] <== ordered_constant;
+    component greater = LessThan(65);
+    greater.in[0] <== ordered_constant;
+    greater.in[1] <== ordered_value;
+
+    signal outcome[8];
+    outcome[0] <== is_op[0].out;
+    outcome[1] <== is_op[1].out * equal.out;
+    outcome[2] <== is_op[2].out * (1 - equal.out);
+    outcome[3] <== is_op[3].out * less.out;
+    outcome[4] <== is_op[4].out * (1 - greater.out);
+    outcome[5] <== is_op[5].out * greater.out;
+    outcome[6] <== is_op[6].out * (1 - less.out);
+    outcome[7] <== is_op[7].out;
+
+    var total = 0;
+    for (var j = 0; j < 8; j++) {
+        total += outcome[j];
+    }
+    holds <== total;
+}
+
+template Predicate(MAX_CONDITIONS) {
+    // Public inputs
+    signal input ops[MAX_CONDITIONS];
+    signal input constants[MAX_CONDITIONS];
+    signal input definition_digest;
+
+    // Private inputs
+    signal input values[MAX_CONDITIONS];
+
+    // Output
+    signal output holds;
+
+    component conditions[MAX_CONDITIONS];
+    signal all[MAX_CONDITIONS + 1];
+    all[0] <== 1;
+    for (var i = 0; i < MAX_CONDITIONS; i++) {
+        conditions[i] = Condition();
+        conditions[i].op <== ops[i];
+        conditions[i].value <== values[i];
+        conditions[i].constant <== constants[i];
+        all[i + 1] <== all[i] * conditions[i].holds;
+    }
+    holds <== all[MAX_CONDITIONS];
+
+    // Ties the digest into the constraint system
+    signal digest_square;
+    digest_square <== definition_digest * definition_digest;
+}
+
+component main {public [ops, constants, definition_digest]} = Predicate(8);
@@ -1,7 +1,7 @@
 #!/bin/bash
 
 # Compile ZK circuits for LUMINA
-# This script compiles tax_proof, kyc_proof, and origin_proof circuits
+# This script compiles tax_proof, kyc_proof, origin_proof, identity_attribute, solvency, dkim_email and predicate circuits
 
 set -e
 
@@ -19,5 +19,21 @@ circom kyc_proof.circom --r1cs --wasm --sym
 echo "Compiling origin_proof.circom..."
 circom origin_proof.circom --r1cs --wasm --sym
 
+# Compile identity attribute circuit
+echo "Compiling identity_attribute.circom..."
+circom identity_attribute.circom --r1cs --wasm --sym -l node_modules
+
+# Compile solvency circuit
+echo "Compiling solvency.circom..."
+circom solvency.circom --r1cs --wasm --sym -l node_modules
+
+# Compile DKIM email circuit (needs the 2^22 powers of tau for setup)
+echo "Compiling dkim_email.circom..."
+circom dkim_email.circom --r1cs --wasm --sym -l node_modules
+
+# Compile generic predicate circuit (claim definitions)
+echo "Compiling predicate.circom..."
+circom predicate.circom --r1cs --wasm --sym -l node_modules
+
 echo "All circuits compiled successfully!"
 
@@ -0,0 +1,37 @@
+#!/bin/bash
+
+# Write and sign the artifact manifest the enclave pins proving keys to
+# Usage: scripts/manifest.sh <ed25519-key.pem>
+# Expects final keys as <circuit>_0001.zkey next to their verification keys
+
+set -e
+
+KEY=${1:?usage: scripts/manifest.sh <ed25519-key.pem>}
+
+# claim_type version artifact
+CIRCUITS="identity_attribute 1 identity_attribute
+solvency 1 solvency
+dkim_email 1 dkim_email
+predicate 1 predicate"
+
+sha() {
+  sha256sum "$1" | cut -d' ' -f1
+}
+
+echo "Writing manifest.json..."
+{
+  echo '{"circuits": ['
+  sep=""
+  while read -r claim_type version artifact; do
+    zkey="${artifact}_0001.zkey"
+    printf '%s  {"claim_type": "%s", "version": %s, "zkey": "%s", "zkey_sha256": "%s", "verification_key_sha256": "%s"}' \
+      "$sep" "$claim_type" "$version" "$zkey" "$(sha "$zkey")" "$(sha "${artifact}_verification_key.json")"
+    sep=$',\n'
+  done <<< "$CIRCUITS"
+  printf '\n]}\n'
+} > manifest.json
+
+echo "Signing manifest.json..."
+openssl pkeyutl -sign -rawin -inkey "$KEY" -in manifest.json | xxd -p -c 64 > manifest.json.sig
+
+echo "Manifest written; set TEE_CIRCUIT_MANIFEST_KEY to the key's public half"
@@ -0,0 +1,70 @@
+/**
+ * LUMINA Solvency Circuit
+ * Proves a statement's net balance meets a public threshold
+ *
+ * Balances are in minor units (cents). Positive account balances are
+ * assets and negative ones (overdrafts, card balances) are liabilities;
+ * both arrive as non-negative amounts, zero-padded to MAX_ACCOUNTS. The
+ * amounts and the number of accounts stay private.
+ *
+ * statement_digest identifies the statement the enclave parsed (its
+ * SHA-256 as submitted, truncated to 248 bits); the enclave's attestation
+ * covers it, so a proof can't be passed off for a different statement.
+ *
+ * Public inputs:
+ * - threshold (minor units)
+ * - statement_digest
+ *
+ * Private inputs:
+ * - assets[32], liabilities[32] (minor units, each < 2^64)
+ *
+ * Output:
+ * - solvent (1 when sum(assets) - sum(liabilities) >= threshold)
+ */
+
+pragma circom 2.0.0;
+
+include "../node_modules/circomlib/circuits/bitify.circom";
+include "../node_modules/circomlib/circuits/comparators.circom";
+
+template Solvency(MAX_ACCOUNTS) {
+    // Public inputs
+    signal input threshold;
+    signal input statement_digest;
+
+    // Private inputs
+    signal input assets[MAX_ACCOUNTS];
+    signal input liabilities[MAX_ACCOUNTS];
+
+    // Output
+    signal output solvent;
+
+    // Every amount is a 64-bit value, so the sums can't wrap the field
+    component asset_range[MAX_ACCOUNTS];
+    component liability_range[MAX_ACCOUNTS];
+    var total_assets = 0;
+    var total_liabilities = 0;
+    for (var i = 0; i < MAX_ACCOUNTS; i++) {
+        asset_range[i] = Num2Bits(64);
+        asset_range[i].in <== assets[i];
+        liability_range[i] = Num2Bits(64);
+        liability_range[i].in <== liabilities[i];
+        total_assets += assets[i];
+        total_liabilities += liabilities[i];
+    }
+
+    component threshold_range = Num2Bits(64);
+    threshold_range.in <== threshold;
+
+    // assets - liabilities >= threshold, rearranged to stay non-negative
+    component covered = GreaterEqThan(72);
+    covered.in[0] <== total_assets;
+    covered.in[1] <== total_liabilities + threshold;
+    solvent <== covered.out;
+
+    // Ties the digest into the constraint system
+    signal digest_square;
+    digest_square <== statement_digest * statement_digest;
+}
+
+component main {public [threshold, statement_digest]} = Solvency(32);